@@ -0,0 +1,315 @@
+# English strings of the editor and the pause menu, the fallback of every other language
+language-name = English
+
+## Pause menu
+menu-title = Paused
+menu-resume = Resume
+menu-graphics = Graphics
+menu-vsync = Vsync
+menu-low-latency = Low latency
+menu-quality = Quality
+menu-quality-custom = Custom
+menu-quality-low = Low
+menu-quality-medium = Medium
+menu-quality-high = High
+menu-quality-ultra = Ultra
+menu-detect-quality = Detect quality
+menu-audio = Audio
+menu-volume = Volume
+menu-mouse = Mouse
+menu-sensitivity = Sensitivity
+menu-invert-y = Invert Y
+menu-controls = Controls
+menu-press-key = Press a key...
+menu-reset-controls = Reset controls
+menu-language = Language
+menu-accessibility = Accessibility
+menu-debug-palette = Debug colors
+menu-palette-standard = Standard
+menu-palette-colorblind = Colorblind safe
+menu-palette-highcontrast = High contrast
+menu-save = Save
+menu-quit = Quit
+control-forward = Forward
+control-back = Back
+control-left = Left
+control-right = Right
+control-up = Up
+control-down = Down
+
+## Editor
+editor-window = Editor window
+editor-minimap = Minimap
+editor-camera-preview = Camera preview
+editor-project = Project
+editor-view = View
+editor-projection = Projection
+editor-cameras = Cameras
+editor-follow-camera = Follow camera
+editor-lights = Lights
+editor-render-settings = Render settings
+editor-occupancy = Occupancy
+editor-environment = Environment
+editor-volumes = Post processing volumes
+editor-weather = Weather
+editor-terrain = Terrain
+editor-world-streaming = World streaming
+editor-levels-of-detail = Levels of detail
+editor-small-object-culling = Small object culling
+editor-frame-graph = Frame graph
+editor-zones = Zones
+editor-agents = Agents
+editor-navmesh = Navmesh
+editor-projectiles = Projectiles
+editor-ragdolls = Ragdolls
+editor-spring-bones = Spring bones
+editor-materials = Materials
+editor-sprite-sheets = Sprite sheets
+editor-instances = Instances
+editor-blockout = Blockout
+editor-vertex-paint = Vertex paint
+editor-scene = Scene
+editor-scene-diff = Scene diff and merge
+editor-sequencer = Sequencer
+editor-autosave = Autosave
+editor-lightmap = Lightmap
+editor-measure = Measure
+editor-mouse = Mouse
+editor-shortcuts = Shortcuts
+editor-profiler = Profiler
+editor-stats = Stats
+editor-state-changes = State changes
+editor-frame-pacing = Frame pacing
+editor-capture = Capture
+editor-debug-palette = Debug colors
+editor-theme = Theme
+
+## Editor widgets
+editor-open = Open
+editor-save = Save
+editor-load = Load
+editor-clear = Clear
+editor-remove = Remove
+editor-add = Add
+editor-push = Push
+editor-spawn = Spawn
+editor-stop = Stop
+editor-copy = Copy
+editor-reset = Reset
+editor-enabled = Enabled
+editor-paused = Paused
+editor-preview = Preview
+editor-none = None
+editor-model = Model
+editor-intensity = Intensity
+editor-gravity = Gravity
+editor-damping = Damping
+editor-material-id = Material ID
+editor-model-id = Model ID
+editor-mesh-id = Mesh ID
+editor-index-none = { $name }: none
+editor-project-open = Open: { $root }
+editor-resident-textures = Resident textures: { $resident } / { $budget } MB
+editor-occupancy-atlas = Texture atlas, brighter textures are at higher resolutions
+editor-occupancy-repacking = Repacking...
+editor-occupancy-repack = Repack
+editor-occupancy-repacked = Repacked { $count } times
+editor-occupancy-columns = Instance slots per mesh column
+editor-occupancy-allocated = Allocated: { $percent }% of { $width }x{ $height }
+editor-occupancy-texture = Texture { $id }, level { $level }, { $width }x{ $height }
+editor-occupancy-slots = { $values } instances in { $slots } slots
+editor-occupancy-column = Column { $index }: { $values } / { $slots }
+editor-weather-transition-speed = Transition speed
+editor-weather-wetting-speed = Wetting speed
+editor-weather-drying-speed = Drying speed
+editor-weather-current = Current: { $kind } { $intensity }, wetness { $wetness }
+editor-weather-play-demo = Play demo script
+editor-weather-scripted = Scripted
+editor-world-cell-size = Cell size
+editor-world-load-radius = Load radius
+editor-world-unload-margin = Unload margin
+editor-world-name = World { $name }, named after the scene
+editor-world-bake = Bake world
+editor-world-stream = Stream world
+editor-lod-fade-frames = Fade frames
+editor-lod-hysteresis = Hysteresis
+editor-lod-instances = Instances: { $count }, fading: { $fading }
+editor-lod-group = Group { $index }
+editor-lod-add-group = Add group
+editor-culling-thresholds = Thresholds are in the render settings
+editor-frame-graph-after = { $pass } after { $after }
+editor-zones-portal-culling = Portal culling
+editor-zones-max-depth = Max portal depth
+editor-zones-camera = Camera zone: { $zone }
+editor-zones-camera-outside = Camera zone: outside
+editor-zones-portal = Portal { $index }: { $a } <-> { $b }
+editor-agents-count = Agents: { $count }
+editor-agents-interpolate = Interpolate
+editor-agents-max-speed = Max speed
+editor-agents-max-force = Max force
+editor-agents-avoid-radius = Avoid radius
+editor-agents-slowing-radius = Slowing radius
+editor-agents-health-bar-height = Health bar height
+editor-agents-spawn = Spawn 100
+editor-navmesh-extent = Extent
+editor-navmesh-cell-size = Cell size
+editor-navmesh-max-slope = Max slope
+editor-navmesh-max-step = Max step
+editor-navmesh-show = Show walkable cells
+editor-navmesh-cells = Walkable cells: { $count }
+editor-navmesh-none = Not built, the agents walk straight
+editor-navmesh-build = Build around the camera
+editor-navmesh-building = Building...
+editor-projectiles-help = Left click fires, right click shoots a ray
+editor-projectiles-counts = Projectiles: { $projectiles }, impacts: { $impacts }, colliders: { $colliders }
+editor-projectiles-speed = Speed
+editor-projectiles-damage = Damage
+editor-projectiles-shake = Shake per hit
+editor-projectiles-flash = Flash
+editor-ragdolls-friction = Friction
+editor-ragdolls-iterations = Iterations
+editor-ragdolls-max-bend = Max bend
+editor-ragdolls-blend-speed = Blend speed
+editor-ragdolls-foot-ik = Foot IK
+editor-ragdolls-puppet = Puppet { $index }: { $weight }
+editor-ragdolls-knock-down = Knock down
+editor-ragdolls-get-up = Get up
+editor-ragdolls-reach = Reach
+editor-ragdolls-release = Release
+editor-ragdolls-events = Events this frame: { $count }
+editor-springs-stiffness = Stiffness
+editor-springs-bone = Bone
+editor-sprites-none = No texture is imported as a sprite sheet
+editor-sprites-animation = Texture { $texture }: frame { $frame } / { $frames } at { $fps } fps, { $materials } materials
+editor-instances-fragmentation = Unused instance slots: { $unused }%, rebalanced { $rebalances } times
+editor-instances-meshlets = Meshlets ({ $count })
+editor-instances-drop = Drop on terrain
+editor-pacing-render-thread = Render thread
+editor-pacing-max-latency = Max frame latency
+editor-pacing-frames-in-flight = Frames in flight
+editor-pacing-waited = Waited on the gpu: { $ms } ms
+editor-pacing-retired = Retired resources: { $count }
+editor-state-changes-count = Count
+editor-state-changes-total = Total
+editor-capture-fps = Fps
+editor-capture-downscale = Downscale
+editor-capture-max-seconds = Max seconds
+editor-capture-record = Record
+editor-capture-progress = { $seconds } s, { $frames } frames, { $dropped } dropped
+editor-capture-encoding = Encoding { $count } recordings...
+editor-capture-folder = Saved to the { $folder } folder
+editor-translation = Translation
+editor-rotation = Rotation
+editor-scale = Scale
+editor-animation-id = Animation id
+editor-params-id = Params id
+editor-zone-id = Zone id
+editor-tint = Tint
+editor-cull-layer = Layer { $layer } pixels
+editor-light-type = Light type
+editor-color = Color
+editor-position = Position
+editor-direction = Direction
+editor-light-cut-off = Cut off
+editor-recover-scene = Recover scene
+editor-recover-scene-info = The editor did not exit cleanly, scene { $name } was autosaved with { $count } instances
+editor-recover-restore = Restore
+editor-recover-discard = Discard
+editor-autosave-interval = Interval (s)
+editor-autosave-slot = Slot: { $path }
+editor-autosave-now = Autosave now
+editor-cameras-show-frusta = Show frusta
+editor-cameras-preview = Preview selected
+editor-cameras-frustum-depth = Frustum depth
+editor-cameras-add = Add from view
+editor-cameras-look-through = Look through
+editor-cameras-fov = Fov deg
+editor-cameras-move-to-view = Move to view
+editor-csg-add-step = Add step
+editor-csg-segments = Segments
+editor-csg-export = Export
+editor-saved-to = Saved to { $path }
+editor-save-failed = Failed to save: { $error }
+editor-gizmo-help = Click an axis to snap the view, drag to orbit
+editor-gizmo-ortho = Orthographic axis views
+editor-light-lens-flare = Lens flare
+editor-light-cast-shadows = Cast shadows
+editor-light-lighting = Lighting
+editor-light-index = Index
+editor-apply = Apply
+editor-light-counts = Lights: { $count } / { $max }, waiting: { $waiting }
+editor-light-shadowed = Shadowed: { $lights }
+editor-light-temperature = Temperature
+editor-lightmap-baking = Baking...
+editor-lightmap-bake = Bake
+editor-lightmap-help = Only meshes whose material has the `lightmap` statement are baked
+editor-measure-help = Pause and click two points in the scene
+editor-measure-distance = Distance: { $distance }
+editor-measure-missed = Nothing under the cursor
+editor-profiler-memory = Memory
+editor-profiler-uncompressed = { $kib } KiB uncompressed
+editor-profiler-jobs = Jobs
+editor-profiler-no-frames = No frames recorded
+editor-profiler-frame = Frame { $index }: { $ms } ms
+editor-profiler-queued = Queued: { $queued }, completions waiting: { $completions }
+editor-profiler-running = { $name } ({ $priority }) for { $ms } ms
+editor-profiler-waited = waited { $ms } ms
+editor-profiler-panicked = panicked
+editor-profiler-ran = ran { $ms } ms
+editor-scene-statics = Static: { $count }
+editor-scene-make-static = Make static
+editor-scene-make-dynamic = Make dynamic
+editor-scene-seed = Seed
+editor-scene-restart-streams = Restart streams
+editor-name = Name
+editor-scene-id = Id { $id }
+editor-scene-instance = { $name }: model { $model } mesh { $mesh } at ({ $position })
+editor-diff-other = Other
+editor-diff-base = Base
+editor-diff = Diff
+editor-diff-no-changes = No changes
+editor-diff-load-failed = Failed to load scene { $name }: { $error }
+editor-diff-merge = Merge
+editor-diff-merge-help = Saves the edited scene with the edits of other since base
+editor-diff-merge-load-failed = Failed to load scene: { $error }
+editor-diff-save-failed = Failed to save scene { $name }: { $error }
+editor-diff-merged = Merged without conflicts
+editor-diff-conflict = Conflict on { $entity }, kept the edited one
+editor-sequencer-keys = { $camera } camera keys, { $lights } light keys, { $duration }s
+editor-sequencer-play = Play
+editor-sequencer-loop = Loop
+editor-sequencer-key-camera = Key camera
+editor-sequencer-key-light = Key light
+editor-sequencer-light = Light
+editor-sequencer-camera = Camera
+editor-sequencer-fov = fov { $fov }
+editor-sequencer-light-key = Light { $light }
+editor-settings-preset = Preset
+editor-settings-default = Default
+editor-settings-retro = Retro
+editor-lut = Color grading lut: { $name }
+editor-shortcuts-conflict = { $action } and { $other } are both bound to { $shortcut }
+editor-shortcuts-reserved = { $action } uses { $shortcut }, also a camera or mode key
+editor-volume = Volume { $index }, weight { $weight }
+editor-volumes-add = Add at view
+editor-terrain-no-tool = No tool
+editor-terrain-sculpt = Sculpt
+editor-paint = Paint
+editor-terrain-layer = Layer { $index }
+editor-terrain-albedo = Albedo
+editor-terrain-normal = Normal
+editor-terrain-texture = { $map }: { $texture }
+editor-terrain-assign = Assign
+editor-radius = Radius
+editor-strength = Strength
+editor-hardness = Hardness
+editor-loaded = Loaded { $path }
+editor-load-failed = Failed to load: { $error }
+editor-theme-dark = Dark
+editor-theme-light = Light
+editor-theme-high-contrast = High contrast
+editor-theme-ui-scale = UI scale
+editor-theme-reload = Reload
+editor-undo = Undo ({ $count })
+editor-redo = Redo ({ $count })
+editor-profiler-size = { $kib } KiB
@@ -0,0 +1,315 @@
+# Chaînes françaises de l'éditeur et du menu de pause
+language-name = Français
+
+## Pause menu
+menu-title = Pause
+menu-resume = Reprendre
+menu-graphics = Graphismes
+menu-vsync = Synchronisation verticale
+menu-low-latency = Faible latence
+menu-quality = Qualité
+menu-quality-custom = Personnalisée
+menu-quality-low = Basse
+menu-quality-medium = Moyenne
+menu-quality-high = Haute
+menu-quality-ultra = Ultra
+menu-detect-quality = Détecter la qualité
+menu-audio = Audio
+menu-volume = Volume
+menu-mouse = Souris
+menu-sensitivity = Sensibilité
+menu-invert-y = Inverser l'axe Y
+menu-controls = Commandes
+menu-press-key = Appuyez sur une touche...
+menu-reset-controls = Réinitialiser les commandes
+menu-language = Langue
+menu-accessibility = Accessibilité
+menu-debug-palette = Couleurs de débogage
+menu-palette-standard = Standard
+menu-palette-colorblind = Adaptées au daltonisme
+menu-palette-highcontrast = Contraste élevé
+menu-save = Enregistrer
+menu-quit = Quitter
+control-forward = Avancer
+control-back = Reculer
+control-left = Gauche
+control-right = Droite
+control-up = Monter
+control-down = Descendre
+
+## Editor
+editor-window = Éditeur
+editor-minimap = Minicarte
+editor-camera-preview = Aperçu de la caméra
+editor-project = Projet
+editor-view = Vue
+editor-projection = Projection
+editor-cameras = Caméras
+editor-follow-camera = Caméra de suivi
+editor-lights = Lumières
+editor-render-settings = Paramètres de rendu
+editor-occupancy = Occupation
+editor-environment = Environnement
+editor-volumes = Volumes de post-traitement
+editor-weather = Météo
+editor-terrain = Terrain
+editor-world-streaming = Streaming du monde
+editor-levels-of-detail = Niveaux de détail
+editor-small-object-culling = Élimination des petits objets
+editor-frame-graph = Graphe de l'image
+editor-zones = Zones
+editor-agents = Agents
+editor-navmesh = Navmesh
+editor-projectiles = Projectiles
+editor-ragdolls = Ragdolls
+editor-spring-bones = Os à ressort
+editor-materials = Matériaux
+editor-sprite-sheets = Planches de sprites
+editor-instances = Instances
+editor-blockout = Maquette
+editor-vertex-paint = Peinture de sommets
+editor-scene = Scène
+editor-scene-diff = Comparaison et fusion de scènes
+editor-sequencer = Séquenceur
+editor-autosave = Sauvegarde automatique
+editor-lightmap = Lightmap
+editor-measure = Mesure
+editor-mouse = Souris
+editor-shortcuts = Raccourcis
+editor-profiler = Profileur
+editor-stats = Statistiques
+editor-state-changes = Changements d'état
+editor-frame-pacing = Cadencement des images
+editor-capture = Capture vidéo
+editor-debug-palette = Couleurs de débogage
+editor-theme = Thème
+
+## Editor widgets
+editor-open = Ouvrir
+editor-save = Enregistrer
+editor-load = Charger
+editor-clear = Vider
+editor-remove = Retirer
+editor-add = Ajouter
+editor-push = Ajouter
+editor-spawn = Faire apparaître
+editor-stop = Arrêter
+editor-copy = Copier
+editor-reset = Réinitialiser
+editor-enabled = Activé
+editor-paused = En pause
+editor-preview = Aperçu
+editor-none = Aucun
+editor-model = Modèle
+editor-intensity = Intensité
+editor-gravity = Gravité
+editor-damping = Amortissement
+editor-material-id = ID du matériau
+editor-model-id = ID du modèle
+editor-mesh-id = ID du maillage
+editor-index-none = { $name } : aucun
+editor-project-open = Ouvert : { $root }
+editor-resident-textures = Textures résidentes : { $resident } / { $budget } Mo
+editor-occupancy-atlas = Atlas de textures, les textures plus claires sont en plus haute résolution
+editor-occupancy-repacking = Réorganisation...
+editor-occupancy-repack = Réorganiser
+editor-occupancy-repacked = Réorganisé { $count } fois
+editor-occupancy-columns = Emplacements d'instances par colonne de maillage
+editor-occupancy-allocated = Alloué : { $percent } % de { $width }x{ $height }
+editor-occupancy-texture = Texture { $id }, niveau { $level }, { $width }x{ $height }
+editor-occupancy-slots = { $values } instances dans { $slots } emplacements
+editor-occupancy-column = Colonne { $index } : { $values } / { $slots }
+editor-weather-transition-speed = Vitesse de transition
+editor-weather-wetting-speed = Vitesse d'humidification
+editor-weather-drying-speed = Vitesse de séchage
+editor-weather-current = Actuelle : { $kind } { $intensity }, humidité { $wetness }
+editor-weather-play-demo = Jouer le script de démonstration
+editor-weather-scripted = Scriptée
+editor-world-cell-size = Taille des cellules
+editor-world-load-radius = Rayon de chargement
+editor-world-unload-margin = Marge de déchargement
+editor-world-name = Monde { $name }, nommé d'après la scène
+editor-world-bake = Précalculer le monde
+editor-world-stream = Streamer le monde
+editor-lod-fade-frames = Images de fondu
+editor-lod-hysteresis = Hystérésis
+editor-lod-instances = Instances : { $count }, en fondu : { $fading }
+editor-lod-group = Groupe { $index }
+editor-lod-add-group = Ajouter un groupe
+editor-culling-thresholds = Les seuils sont dans les réglages du rendu
+editor-frame-graph-after = { $pass } après { $after }
+editor-zones-portal-culling = Élimination par portails
+editor-zones-max-depth = Profondeur de portails max
+editor-zones-camera = Zone de la caméra : { $zone }
+editor-zones-camera-outside = Zone de la caméra : dehors
+editor-zones-portal = Portail { $index } : { $a } <-> { $b }
+editor-agents-count = Agents : { $count }
+editor-agents-interpolate = Interpoler
+editor-agents-max-speed = Vitesse max
+editor-agents-max-force = Force max
+editor-agents-avoid-radius = Rayon d'évitement
+editor-agents-slowing-radius = Rayon de ralentissement
+editor-agents-health-bar-height = Hauteur de la barre de vie
+editor-agents-spawn = En faire apparaître 100
+editor-navmesh-extent = Étendue
+editor-navmesh-cell-size = Taille des cellules
+editor-navmesh-max-slope = Pente max
+editor-navmesh-max-step = Marche max
+editor-navmesh-show = Afficher les cellules praticables
+editor-navmesh-cells = Cellules praticables : { $count }
+editor-navmesh-none = Non construit, les agents vont tout droit
+editor-navmesh-build = Construire autour de la caméra
+editor-navmesh-building = Construction...
+editor-projectiles-help = Le clic gauche tire, le clic droit lance un rayon
+editor-projectiles-counts = Projectiles : { $projectiles }, impacts : { $impacts }, collisionneurs : { $colliders }
+editor-projectiles-speed = Vitesse
+editor-projectiles-damage = Dégâts
+editor-projectiles-shake = Secousse par impact
+editor-projectiles-flash = Flash
+editor-ragdolls-friction = Friction
+editor-ragdolls-iterations = Itérations
+editor-ragdolls-max-bend = Flexion max
+editor-ragdolls-blend-speed = Vitesse de mélange
+editor-ragdolls-foot-ik = IK des pieds
+editor-ragdolls-puppet = Pantin { $index } : { $weight }
+editor-ragdolls-knock-down = Renverser
+editor-ragdolls-get-up = Relever
+editor-ragdolls-reach = Atteindre
+editor-ragdolls-release = Relâcher
+editor-ragdolls-events = Événements de cette image : { $count }
+editor-springs-stiffness = Raideur
+editor-springs-bone = Os
+editor-sprites-none = Aucune texture n'est importée comme planche de sprites
+editor-sprites-animation = Texture { $texture } : image { $frame } / { $frames } à { $fps } ips, { $materials } matériaux
+editor-instances-fragmentation = Emplacements d'instances inutilisés : { $unused } %, rééquilibré { $rebalances } fois
+editor-instances-meshlets = Meshlets ({ $count })
+editor-instances-drop = Poser sur le terrain
+editor-pacing-render-thread = Thread de rendu
+editor-pacing-max-latency = Latence d'images max
+editor-pacing-frames-in-flight = Images en vol
+editor-pacing-waited = Attente du gpu : { $ms } ms
+editor-pacing-retired = Ressources retirées : { $count }
+editor-state-changes-count = Compter
+editor-state-changes-total = Total
+editor-capture-fps = Ips
+editor-capture-downscale = Réduction
+editor-capture-max-seconds = Secondes max
+editor-capture-record = Enregistrer
+editor-capture-progress = { $seconds } s, { $frames } images, { $dropped } perdues
+editor-capture-encoding = Encodage de { $count } enregistrements...
+editor-capture-folder = Enregistré dans le dossier { $folder }
+editor-translation = Translation
+editor-rotation = Rotation
+editor-scale = Échelle
+editor-animation-id = ID de l'animation
+editor-params-id = ID des paramètres
+editor-zone-id = ID de la zone
+editor-tint = Teinte
+editor-cull-layer = Pixels de la couche { $layer }
+editor-light-type = Type de lumière
+editor-color = Couleur
+editor-position = Position
+editor-direction = Direction
+editor-light-cut-off = Angle de coupure
+editor-recover-scene = Récupérer la scène
+editor-recover-scene-info = L'éditeur ne s'est pas fermé correctement, la scène { $name } a été sauvegardée automatiquement avec { $count } instances
+editor-recover-restore = Restaurer
+editor-recover-discard = Abandonner
+editor-autosave-interval = Intervalle (s)
+editor-autosave-slot = Emplacement : { $path }
+editor-autosave-now = Sauvegarder maintenant
+editor-cameras-show-frusta = Afficher les frustums
+editor-cameras-preview = Aperçu de la sélection
+editor-cameras-frustum-depth = Profondeur du frustum
+editor-cameras-add = Ajouter depuis la vue
+editor-cameras-look-through = Regarder à travers
+editor-cameras-fov = Champ de vision (deg)
+editor-cameras-move-to-view = Déplacer sur la vue
+editor-csg-add-step = Ajouter une étape
+editor-csg-segments = Segments
+editor-csg-export = Exporter
+editor-saved-to = Enregistré dans { $path }
+editor-save-failed = Échec de l'enregistrement : { $error }
+editor-gizmo-help = Cliquer sur un axe aligne la vue, glisser tourne autour
+editor-gizmo-ortho = Vues d'axe orthographiques
+editor-light-lens-flare = Reflet de lentille
+editor-light-cast-shadows = Projette des ombres
+editor-light-lighting = Éclairage
+editor-light-index = Index
+editor-apply = Appliquer
+editor-light-counts = Lumières : { $count } / { $max }, en attente : { $waiting }
+editor-light-shadowed = Avec ombres : { $lights }
+editor-light-temperature = Température
+editor-lightmap-baking = Précalcul...
+editor-lightmap-bake = Précalculer
+editor-lightmap-help = Seuls les maillages dont le matériau a l'instruction `lightmap` sont précalculés
+editor-measure-help = Mettre en pause et cliquer deux points de la scène
+editor-measure-distance = Distance : { $distance }
+editor-measure-missed = Rien sous le curseur
+editor-profiler-memory = Mémoire
+editor-profiler-uncompressed = { $kib } Kio non compressés
+editor-profiler-jobs = Tâches
+editor-profiler-no-frames = Aucune image enregistrée
+editor-profiler-frame = Image { $index } : { $ms } ms
+editor-profiler-queued = En file : { $queued }, fins en attente : { $completions }
+editor-profiler-running = { $name } ({ $priority }) depuis { $ms } ms
+editor-profiler-waited = attendu { $ms } ms
+editor-profiler-panicked = a paniqué
+editor-profiler-ran = exécuté { $ms } ms
+editor-scene-statics = Statiques : { $count }
+editor-scene-make-static = Rendre statique
+editor-scene-make-dynamic = Rendre dynamique
+editor-scene-seed = Graine
+editor-scene-restart-streams = Relancer les flux
+editor-name = Nom
+editor-scene-id = Id { $id }
+editor-scene-instance = { $name } : modèle { $model } maillage { $mesh } à ({ $position })
+editor-diff-other = Autre
+editor-diff-base = Base
+editor-diff = Comparer
+editor-diff-no-changes = Aucun changement
+editor-diff-load-failed = Échec du chargement de la scène { $name } : { $error }
+editor-diff-merge = Fusionner
+editor-diff-merge-help = Enregistre la scène éditée avec les modifications de l'autre depuis la base
+editor-diff-merge-load-failed = Échec du chargement de la scène : { $error }
+editor-diff-save-failed = Échec de l'enregistrement de la scène { $name } : { $error }
+editor-diff-merged = Fusionné sans conflit
+editor-diff-conflict = Conflit sur { $entity }, la version éditée est gardée
+editor-sequencer-keys = { $camera } clés de caméra, { $lights } clés de lumière, { $duration } s
+editor-sequencer-play = Lire
+editor-sequencer-loop = Boucler
+editor-sequencer-key-camera = Clé de caméra
+editor-sequencer-key-light = Clé de lumière
+editor-sequencer-light = Lumière
+editor-sequencer-camera = Caméra
+editor-sequencer-fov = champ { $fov }
+editor-sequencer-light-key = Lumière { $light }
+editor-settings-preset = Préréglage
+editor-settings-default = Par défaut
+editor-settings-retro = Rétro
+editor-lut = Lut d'étalonnage : { $name }
+editor-shortcuts-conflict = { $action } et { $other } sont tous deux liés à { $shortcut }
+editor-shortcuts-reserved = { $action } utilise { $shortcut }, aussi une touche de caméra ou de mode
+editor-volume = Volume { $index }, poids { $weight }
+editor-volumes-add = Ajouter à la vue
+editor-terrain-no-tool = Aucun outil
+editor-terrain-sculpt = Sculpter
+editor-paint = Peindre
+editor-terrain-layer = Couche { $index }
+editor-terrain-albedo = Albédo
+editor-terrain-normal = Normale
+editor-terrain-texture = { $map } : { $texture }
+editor-terrain-assign = Assigner
+editor-radius = Rayon
+editor-strength = Force
+editor-hardness = Dureté
+editor-loaded = Chargé { $path }
+editor-load-failed = Échec du chargement : { $error }
+editor-theme-dark = Sombre
+editor-theme-light = Clair
+editor-theme-high-contrast = Contraste élevé
+editor-theme-ui-scale = Échelle de l'interface
+editor-theme-reload = Recharger
+editor-undo = Annuler ({ $count })
+editor-redo = Rétablir ({ $count })
+editor-profiler-size = { $kib } Kio
@@ -0,0 +1,109 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use nalgebra::{Point3, Vector3};
+
+use crate::{
+    game::Mode,
+    graphics::{camera::Camera, ctx::FramePacing, settings::QualityPreset},
+};
+
+use super::{Engine, DISPLAY_SECTION};
+
+/// Seconds rendered before the frame times count, the shaders, the streaming and the caches
+/// settle in the meantime
+const WARMUP: f32 = 1.0;
+/// Seconds of measured frames
+const DURATION: f32 = 4.0;
+/// Highest mean frame time measured at [`QualityPreset::High`] picking each preset, in
+/// milliseconds. Slower machines get [`QualityPreset::Low`]
+const THRESHOLDS: [(f32, QualityPreset); 3] = [
+    (6.0, QualityPreset::Ultra),
+    (12.0, QualityPreset::High),
+    (20.0, QualityPreset::Medium),
+];
+/// Center of the stress test grid of the entities renderer the camera circles around
+const ORBIT_CENTER: Point3<f32> = Point3::new(0.0, 0.0, -125.0);
+const ORBIT_RADIUS: f32 = 100.0;
+const ORBIT_HEIGHT: f32 = 20.0;
+
+/// Flies the camera around the standard scene for a few seconds and picks the quality preset
+/// from the frame times, started by `quality = Auto` in the `[display]` section or from the
+/// pause menu
+///
+/// Frames are measured at [`QualityPreset::High`] without vsync and in low latency, so they
+/// wait for the gpu and their time is the cost of rendering them
+pub struct Benchmark {
+    elapsed: f32,
+    /// In milliseconds, after the warmup
+    frame_times: Vec<f32>,
+    /// Restored once done
+    camera: Camera,
+    mode: Mode,
+    pacing: FramePacing,
+}
+
+impl Benchmark {
+    pub fn start(engine: &mut Engine) -> Self {
+        let benchmark = Self {
+            elapsed: 0.0,
+            frame_times: Vec::new(),
+            camera: engine.game_state.camera,
+            mode: engine.game_state.mode,
+            pacing: engine.pacing(),
+        };
+        QualityPreset::High.apply(&mut engine.renderer.lock().unwrap().settings);
+        engine.request_pacing(FramePacing {
+            vsync: false,
+            low_latency: true,
+            ..benchmark.pacing
+        });
+        engine.game_state.mode = Mode::Game;
+        benchmark
+    }
+
+    /// Called every frame after the camera controls, returns the preset once done
+    pub fn update(&mut self, engine: &mut Engine, dt: Duration) -> Option<QualityPreset> {
+        self.elapsed += dt.as_secs_f32();
+        if self.elapsed > WARMUP {
+            self.frame_times.push(dt.as_secs_f32() * 1000.0);
+        }
+
+        let angle = self.elapsed / (WARMUP + DURATION) * TAU;
+        let camera = &mut engine.game_state.camera;
+        camera.eye = ORBIT_CENTER
+            + Vector3::new(angle.sin(), 0.0, angle.cos()) * ORBIT_RADIUS
+            + Vector3::y() * ORBIT_HEIGHT;
+        camera.look_at(&ORBIT_CENTER);
+
+        if self.elapsed < WARMUP + DURATION || self.frame_times.is_empty() {
+            return None;
+        }
+        let mean = self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32;
+        Some(pick_preset(mean))
+    }
+
+    /// Restores the camera, the mode and the pacing, then applies and saves the preset
+    pub fn finish(self, engine: &mut Engine, preset: QualityPreset) {
+        engine.game_state.camera = self.camera;
+        engine.game_state.mode = self.mode;
+        engine.request_pacing(self.pacing);
+        preset.apply(&mut engine.renderer.lock().unwrap().settings);
+        engine
+            .config
+            .set(DISPLAY_SECTION, "quality", preset.label());
+        engine.config.save();
+        println!(
+            "Benchmark picked the {} quality preset over {} frames",
+            preset.label(),
+            self.frame_times.len()
+        );
+    }
+}
+
+/// From the mean frame time at [`QualityPreset::High`], in milliseconds
+pub fn pick_preset(mean_frame_ms: f32) -> QualityPreset {
+    THRESHOLDS
+        .into_iter()
+        .find(|(max_ms, _)| mean_frame_ms <= *max_ms)
+        .map_or(QualityPreset::Low, |(_, preset)| preset)
+}
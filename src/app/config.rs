@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use crate::constants::CONFIG_PATH;
+
+/// Ini like `key = value` entries grouped in `[section]`s, saved to [`CONFIG_PATH`]
+///
+/// Each system reads and writes its own section, unknown sections are kept as is
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// Missing or invalid files give an empty config
+    pub fn load() -> Self {
+        let src = match std::fs::read_to_string(CONFIG_PATH) {
+            Ok(src) => src,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("Failed to read {CONFIG_PATH}: {e}");
+                return Self::default();
+            }
+        };
+        Self::parse(&src).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {CONFIG_PATH}: {e}");
+            Self::default()
+        })
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = None;
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim().to_string());
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key = value: {line}"))?;
+            let section = section
+                .clone()
+                .ok_or_else(|| format!("Entry outside of any section: {line}"))?;
+            config
+                .sections
+                .entry(section)
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = std::fs::write(CONFIG_PATH, self.to_string()) {
+            eprintln!("Failed to write {CONFIG_PATH}: {e}");
+        }
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .get(section)
+            .and_then(|entries| entries.get(key))
+            .map(String::as_str)
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: impl ToString) {
+        self.sections
+            .entry(section.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (section, entries)) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{section}]")?;
+            for (key, value) in entries {
+                writeln!(f, "{key} = {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
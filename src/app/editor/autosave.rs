@@ -0,0 +1,234 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    time::{Duration, Instant},
+};
+
+use egui::Slider;
+
+use crate::{
+    app::config::Config,
+    graphics::entities::{lightmap::Lightmap, model::ModelsBuffer},
+    locale::{tr, tr_args},
+    random::Random,
+    scene::Scene,
+};
+
+use super::scene::SceneEditor;
+
+/// Autosaves and the session files, relative to the working directory
+pub const AUTOSAVE_PATH: &str = "autosave";
+const CONFIG_SECTION: &str = "autosave";
+/// Holds the name of the edited scene while the editor runs, left behind by a crash
+const LOCK_FILE: &str = "session.lock";
+const CRASH_LOG: &str = "crash.log";
+
+/// Set by the panic hook, the lock file is kept when the editor unwinds
+static CRASHED: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+
+/// Scene autosaved by a session that did not exit cleanly
+struct Recovery {
+    name: String,
+    scene: Scene,
+    /// Panic message, `None` when the process was killed
+    crash: Option<String>,
+}
+
+/// Saves the edited scene to its autosave slot at an interval, and offers to restore it at the
+/// next start when the editor did not exit cleanly
+///
+/// Only the scene file is autosaved, the lightmap is the last one saved with the scene
+pub struct Autosave {
+    pub enabled: bool,
+    pub interval_secs: f32,
+    last_save: Instant,
+    recovery: Option<Recovery>,
+}
+
+impl Autosave {
+    /// Looks for the autosave of a crashed session then starts this one
+    pub fn from_config(config: &Config) -> Self {
+        let mut autosave = Self {
+            enabled: true,
+            interval_secs: 60.0,
+            last_save: Instant::now(),
+            recovery: None,
+        };
+        if let Some(enabled) = config
+            .get(CONFIG_SECTION, "enabled")
+            .and_then(|v| v.parse().ok())
+        {
+            autosave.enabled = enabled;
+        }
+        if let Some(interval) = config
+            .get(CONFIG_SECTION, "interval_secs")
+            .and_then(|v| v.parse::<f32>().ok())
+        {
+            autosave.interval_secs = interval.max(5.0);
+        }
+
+        autosave.recovery = find_recovery();
+        let folder = PathBuf::from(AUTOSAVE_PATH);
+        let _ = std::fs::remove_file(folder.join(CRASH_LOG));
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                CRASHED.store(true, Ordering::Relaxed);
+                let _ = std::fs::write(
+                    PathBuf::from(AUTOSAVE_PATH).join(CRASH_LOG),
+                    info.to_string(),
+                );
+                previous(info);
+            }));
+        });
+        autosave
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        config.set(CONFIG_SECTION, "enabled", self.enabled);
+        config.set(CONFIG_SECTION, "interval_secs", self.interval_secs);
+    }
+
+    pub fn slot_path(name: &str) -> PathBuf {
+        PathBuf::from(AUTOSAVE_PATH)
+            .join(name)
+            .with_extension("scene")
+    }
+
+    /// Autosaves once the interval elapsed, not while a crashed session waits for recovery as
+    /// it would overwrite its slot
+    pub fn update(&mut self, scene_editor: &SceneEditor, seed: u32) {
+        if !self.enabled
+            || self.recovery.is_some()
+            || self.last_save.elapsed() < Duration::from_secs_f32(self.interval_secs)
+        {
+            return;
+        }
+        self.last_save = Instant::now();
+        if let Err(e) = save(scene_editor, seed) {
+            eprintln!("Failed to autosave scene {}: {e}", scene_editor.name);
+        }
+    }
+
+    /// Window offering the autosave of a crashed session, until it is restored or discarded
+    pub fn recovery_ui(
+        &mut self,
+        gui_ctx: &egui::Context,
+        scene_editor: &mut SceneEditor,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+    ) {
+        let Some(recovery) = &self.recovery else {
+            return;
+        };
+        let mut choice = None;
+        egui::Window::new(tr("editor-recover-scene"))
+            .id(egui::Id::new("Recover scene"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(gui_ctx, |ui| {
+                ui.label(tr_args(
+                    "editor-recover-scene-info",
+                    &[
+                        ("name", &recovery.name),
+                        ("count", &recovery.scene.instances.len()),
+                    ],
+                ));
+                if let Some(crash) = &recovery.crash {
+                    ui.monospace(crash);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr("editor-recover-restore")).clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button(tr("editor-recover-discard")).clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+        let Some(restore) = choice else {
+            return;
+        };
+        let recovery = self.recovery.take().unwrap();
+        if restore {
+            scene_editor.name = recovery.name;
+            scene_editor.replace(recovery.scene, models, lightmap, random);
+        }
+        self.last_save = Instant::now();
+    }
+
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        scene_editor: &SceneEditor,
+        config: &mut Config,
+        seed: u32,
+    ) {
+        ui.checkbox(&mut self.enabled, tr("editor-enabled"));
+        ui.add(
+            Slider::new(&mut self.interval_secs, 5.0..=600.0).text(tr("editor-autosave-interval")),
+        );
+        ui.label(tr_args(
+            "editor-autosave-slot",
+            &[("path", &Self::slot_path(&scene_editor.name).display())],
+        ));
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-autosave-now")).clicked() {
+                self.last_save = Instant::now();
+                if let Err(e) = save(scene_editor, seed) {
+                    eprintln!("Failed to autosave scene {}: {e}", scene_editor.name);
+                }
+            }
+            if ui.button(tr("editor-save")).clicked() {
+                self.write_config(config);
+                config.save();
+            }
+        });
+    }
+}
+
+impl Drop for Autosave {
+    /// A clean exit removes the lock, the autosave is not offered at the next start unless this
+    /// session left it pending
+    fn drop(&mut self) {
+        if !CRASHED.load(Ordering::Relaxed) && self.recovery.is_none() {
+            let _ = std::fs::remove_file(PathBuf::from(AUTOSAVE_PATH).join(LOCK_FILE));
+        }
+    }
+}
+
+/// Writes the slot then the lock naming it
+fn save(scene_editor: &SceneEditor, seed: u32) -> Result<(), String> {
+    let folder = PathBuf::from(AUTOSAVE_PATH);
+    std::fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+    let scene = scene_editor.to_scene(seed);
+    std::fs::write(Autosave::slot_path(&scene_editor.name), scene.to_string())
+        .map_err(|e| e.to_string())?;
+    std::fs::write(folder.join(LOCK_FILE), &scene_editor.name).map_err(|e| e.to_string())
+}
+
+/// Autosave named by the lock a previous session left behind
+fn find_recovery() -> Option<Recovery> {
+    let folder = PathBuf::from(AUTOSAVE_PATH);
+    let name = std::fs::read_to_string(folder.join(LOCK_FILE)).ok()?;
+    let name = name.trim().to_string();
+    let path = Autosave::slot_path(&name);
+    let scene = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|src| Scene::parse(&src))
+    {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("Failed to read autosave {}: {e}", path.display());
+            return None;
+        }
+    };
+    let crash = std::fs::read_to_string(folder.join(CRASH_LOG)).ok();
+    Some(Recovery { name, scene, crash })
+}
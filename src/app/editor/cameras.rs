@@ -0,0 +1,148 @@
+use egui::Slider;
+use nalgebra::{Point3, Vector3};
+
+use crate::{
+    game::cameras::GameCamera,
+    graphics::{
+        camera::{Camera, Projection},
+        color::Color3,
+        debug_lines::DebugLines,
+        GlobalRenderer,
+    },
+    locale::tr,
+};
+
+use super::inspect::Inspect;
+
+/// Game cameras seen from the editor camera, their frusta are drawn and the selected one can be
+/// previewed picture in picture
+pub struct CameraEditor {
+    selected: Option<usize>,
+    pub show_frusta: bool,
+    pub preview: bool,
+    /// Depth the frusta are drawn to, they would cover the whole scene otherwise
+    pub frustum_depth: f32,
+}
+
+impl Default for CameraEditor {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            show_frusta: true,
+            preview: true,
+            frustum_depth: 3.0,
+        }
+    }
+}
+
+impl CameraEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        cameras: &mut Vec<GameCamera>,
+        view: &mut Camera,
+        proj: &mut Projection,
+    ) {
+        ui.checkbox(&mut self.show_frusta, tr("editor-cameras-show-frusta"));
+        ui.checkbox(&mut self.preview, tr("editor-cameras-preview"));
+        ui.add(
+            Slider::new(&mut self.frustum_depth, 0.5..=50.0)
+                .text(tr("editor-cameras-frustum-depth")),
+        );
+        ui.separator();
+
+        for (i, camera) in cameras.iter().enumerate() {
+            if ui
+                .selectable_label(self.selected == Some(i), &camera.name)
+                .clicked()
+            {
+                self.selected = (self.selected != Some(i)).then_some(i);
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-cameras-add")).clicked() {
+                let name = format!("Camera {}", cameras.len());
+                cameras.push(GameCamera::new(name, *view, proj.fov_deg));
+                self.selected = Some(cameras.len() - 1);
+            }
+            let Some(index) = self.selected.filter(|i| *i < cameras.len()) else {
+                return;
+            };
+            if ui.button(tr("editor-cameras-look-through")).clicked() {
+                *view = cameras[index].camera;
+                proj.fov_deg = cameras[index].fov_deg;
+                proj.ortho = false;
+            }
+            if ui.button(tr("editor-remove")).clicked() {
+                cameras.remove(index);
+                self.selected = None;
+            }
+        });
+
+        let Some(camera) = self.selected.and_then(|i| cameras.get_mut(i)) else {
+            return;
+        };
+        ui.separator();
+        ui.text_edit_singleline(&mut camera.name);
+        camera.camera.inspect(ui);
+        ui.add(Slider::new(&mut camera.fov_deg, 1.0..=179.0).text(tr("editor-cameras-fov")));
+        if ui.button(tr("editor-cameras-move-to-view")).clicked() {
+            camera.camera = *view;
+        }
+    }
+
+    /// Queues the frusta and the preview of the selected camera, every frame
+    pub fn draw(&self, renderer: &mut GlobalRenderer, cameras: &[GameCamera], proj: &Projection) {
+        let aspect = proj.size.x.max(1) as f32 / proj.size.y.max(1) as f32;
+        if self.show_frusta {
+            let palette = renderer.debug_lines.palette;
+            for (i, camera) in cameras.iter().enumerate() {
+                let color = match self.selected == Some(i) {
+                    true => palette.highlight(),
+                    false => palette.neutral(),
+                };
+                self.frustum(&mut renderer.debug_lines, camera, aspect, color);
+            }
+        }
+        if let Some(camera) = self.preview_camera(cameras) {
+            renderer.set_preview(camera.camera, camera.projection(proj.size));
+        }
+    }
+
+    /// Selected camera while its preview is shown
+    pub fn preview_camera<'a>(&self, cameras: &'a [GameCamera]) -> Option<&'a GameCamera> {
+        self.selected
+            .filter(|_| self.preview)
+            .and_then(|i| cameras.get(i))
+    }
+
+    pub fn frustum(&self, lines: &mut DebugLines, camera: &GameCamera, aspect: f32, color: Color3) {
+        let eye = camera.camera.eye;
+        // Rows of the view rotation are the camera axes in world space
+        let axes = camera.camera.compute_rot_matrix().transpose();
+        let right = axes.transform_vector(&Vector3::x());
+        let up = axes.transform_vector(&Vector3::y());
+        let forward = axes.transform_vector(&-Vector3::z());
+
+        let half_height = self.frustum_depth * (camera.fov_deg.to_radians() * 0.5).tan();
+        let half_width = half_height * aspect;
+        let center = eye + forward * self.frustum_depth;
+        let corner = |x: f32, y: f32| -> Point3<f32> {
+            center + right * (x * half_width) + up * (y * half_height)
+        };
+        let corners = [
+            corner(-1.0, -1.0),
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+        ];
+        for (i, a) in corners.iter().enumerate() {
+            lines.line(eye, *a, color);
+            lines.line(*a, corners[(i + 1) % 4], color);
+        }
+        // Marks the top of the view
+        let top = center + up * (half_height * 1.4);
+        lines.line(corners[2], top, color);
+        lines.line(corners[3], top, color);
+    }
+}
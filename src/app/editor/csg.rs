@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+
+use egui::{Color32, DragValue, Slider};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::{
+    graphics::{
+        debug_lines::DebugLines,
+        entities::csg::{mesh_to_obj, Csg},
+    },
+    locale::{tr, tr_args},
+    ASSETS,
+};
+
+use super::history::{EditHistory, PendingEdit, Snapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgShape {
+    Cuboid,
+    Sphere,
+    Cylinder,
+}
+
+impl CsgShape {
+    pub const ALL: [Self; 3] = [Self::Cuboid, Self::Sphere, Self::Cylinder];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Subtract,
+    Intersect,
+}
+
+impl CsgOp {
+    pub const ALL: [Self; 3] = [Self::Union, Self::Subtract, Self::Intersect];
+}
+
+/// Unit shape scaled then moved, combined with the result of the steps before it
+#[derive(Debug, Clone, Copy)]
+pub struct CsgStep {
+    pub op: CsgOp,
+    pub shape: CsgShape,
+    pub position: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl CsgStep {
+    fn solid(&self, segments: u32) -> Csg {
+        let transform = Matrix4::new_translation(&Vector3::from(self.position))
+            * Matrix4::new_nonuniform_scaling(&Vector3::from(self.scale));
+        let origin = Point3::origin();
+        match self.shape {
+            CsgShape::Cuboid => Csg::cuboid(origin, Vector3::repeat(0.5)),
+            CsgShape::Sphere => Csg::sphere(origin, 0.5, segments, segments / 2),
+            CsgShape::Cylinder => Csg::cylinder(origin, 0.5, 1.0, segments),
+        }
+        .transformed(&transform)
+    }
+}
+
+/// Blocks out level geometry from boolean operations on primitives, previewed as lines and
+/// exported as an obj to the models folder
+pub struct CsgEditor {
+    pub steps: Vec<CsgStep>,
+    pub segments: u32,
+    pub preview: bool,
+    name: String,
+    /// Rebuilt when the steps change
+    result: Option<Csg>,
+    /// Outcome of the last export, shown under its button
+    exported: Option<Result<PathBuf, String>>,
+    /// Steps from before the edit in progress
+    pending: PendingEdit,
+}
+
+impl Default for CsgEditor {
+    fn default() -> Self {
+        Self {
+            steps: vec![CsgStep {
+                op: CsgOp::Union,
+                shape: CsgShape::Cuboid,
+                position: [0.0; 3],
+                scale: [1.0; 3],
+            }],
+            segments: 16,
+            preview: false,
+            name: String::from("Blockout"),
+            result: None,
+            exported: None,
+            pending: PendingEdit::default(),
+        }
+    }
+}
+
+impl CsgEditor {
+    /// Steps put back by an undo, the result is built again
+    pub fn set_steps(&mut self, steps: Vec<CsgStep>, segments: u32) {
+        self.steps = steps;
+        self.segments = segments;
+        self.result = None;
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, history: &mut EditHistory) {
+        let before = Snapshot::csg(self);
+        let mut changed = false;
+        let mut removed = None;
+        for (i, step) in self.steps.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                // The first step is the starting solid
+                if i > 0 {
+                    egui::ComboBox::from_id_salt(("csg op", i))
+                        .selected_text(format!("{:?}", step.op))
+                        .show_ui(ui, |ui| {
+                            for op in CsgOp::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut step.op, op, format!("{op:?}"))
+                                    .changed();
+                            }
+                        });
+                }
+                egui::ComboBox::from_id_salt(("csg shape", i))
+                    .selected_text(format!("{:?}", step.shape))
+                    .show_ui(ui, |ui| {
+                        for shape in CsgShape::ALL {
+                            changed |= ui
+                                .selectable_value(&mut step.shape, shape, format!("{shape:?}"))
+                                .changed();
+                        }
+                    });
+                if ui.button(tr("editor-remove")).clicked() {
+                    removed = Some(i);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("editor-position"));
+                for c in &mut step.position {
+                    changed |= ui.add(DragValue::new(c).speed(0.05)).changed();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("editor-scale"));
+                for c in &mut step.scale {
+                    changed |= ui
+                        .add(DragValue::new(c).speed(0.05).range(0.01..=1000.0))
+                        .changed();
+                }
+            });
+            ui.separator();
+        }
+        if let Some(i) = removed {
+            self.steps.remove(i);
+            changed = true;
+        }
+        if ui.button(tr("editor-csg-add-step")).clicked() {
+            let last = self.steps.last().copied();
+            self.steps.push(CsgStep {
+                op: CsgOp::Subtract,
+                ..last.unwrap_or(Self::default().steps[0])
+            });
+            changed = true;
+        }
+        changed |= ui
+            .add(Slider::new(&mut self.segments, 4..=64).text(tr("editor-csg-segments")))
+            .changed();
+        if changed {
+            self.pending.changed(|| before);
+            self.result = None;
+        }
+        self.pending.commit(ui.ctx(), history);
+
+        ui.checkbox(&mut self.preview, tr("editor-preview"));
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.name);
+            if ui.button(tr("editor-csg-export")).clicked() {
+                let path = ASSETS
+                    .root()
+                    .join("models")
+                    .join(&self.name)
+                    .with_extension("obj");
+                let obj = mesh_to_obj(&self.result().to_mesh());
+                self.exported = Some(match std::fs::write(&path, obj) {
+                    Ok(()) => Ok(path),
+                    Err(e) => {
+                        eprintln!("Failed to save model {}: {e}", path.display());
+                        Err(e.to_string())
+                    }
+                });
+            }
+        });
+        match &self.exported {
+            Some(Ok(path)) => {
+                ui.label(tr_args("editor-saved-to", &[("path", &path.display())]));
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::RED, tr_args("editor-save-failed", &[("error", e)]));
+            }
+            None => {}
+        }
+    }
+
+    /// Outlines of the polygons of the result
+    pub fn draw(&mut self, lines: &mut DebugLines) {
+        if !self.preview {
+            return;
+        }
+        let color = lines.palette.preview();
+        for (a, b) in self.result().edges() {
+            lines.line(a, b, color);
+        }
+    }
+
+    fn result(&mut self) -> &Csg {
+        let segments = self.segments;
+        self.result.get_or_insert_with(|| {
+            let mut steps = self.steps.iter();
+            let Some(first) = steps.next() else {
+                return Csg::default();
+            };
+            steps.fold(first.solid(segments), |result, step| {
+                let solid = step.solid(segments);
+                match step.op {
+                    CsgOp::Union => result.union(&solid),
+                    CsgOp::Subtract => result.subtract(&solid),
+                    CsgOp::Intersect => result.intersect(&solid),
+                }
+            })
+        })
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::graphics::{bounds::Aabb, camera::Camera};
+
+/// Moves the camera to frame bounds, easing in and out
+pub struct CameraFocus {
+    pub duration: Duration,
+    /// Extra space around the framed bounds, 1 fits the bounding sphere exactly
+    pub margin: f32,
+    transition: Option<Transition>,
+}
+
+struct Transition {
+    from: View,
+    to: View,
+    elapsed: Duration,
+}
+
+#[derive(Clone, Copy)]
+struct View {
+    eye: Point3<f32>,
+    yaw_deg: f32,
+    pitch_deg: f32,
+}
+
+impl Default for CameraFocus {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(400),
+            margin: 1.2,
+            transition: None,
+        }
+    }
+}
+
+impl CameraFocus {
+    /// Keeps the side the bounds are looked at from and backs off until they fit in the fov
+    pub fn start(&mut self, camera: &Camera, bounds: Aabb, fov_deg: f32) {
+        let center = bounds.center();
+        let direction = (center - camera.eye)
+            .try_normalize(1e-6)
+            .unwrap_or_else(|| camera.forward());
+        let half_fov = (fov_deg.to_radians() * 0.5).clamp(0.05, 1.5);
+        let distance = bounds.radius().max(0.1) * self.margin / half_fov.sin();
+
+        let mut target = Camera {
+            eye: center - direction * distance,
+            ..*camera
+        };
+        target.look_at(&center);
+
+        self.transition_to(camera, &target);
+    }
+
+    /// Follows the world origin, mid transition
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        if let Some(transition) = &mut self.transition {
+            transition.from.eye -= shift;
+            transition.to.eye -= shift;
+        }
+    }
+
+    /// Swings the camera around the pivot to look at it from the side, keeping its distance
+    pub fn orbit(&mut self, camera: &Camera, pivot: Point3<f32>, side: Vector3<f32>) {
+        let distance = (camera.eye - pivot).norm().max(0.1);
+        let mut target = Camera {
+            eye: pivot + side.normalize() * distance,
+            ..*camera
+        };
+        target.look_at(&pivot);
+        // Looking straight up or down, the yaw is snapped instead of being undefined
+        if side.xz().norm() < 1e-3 {
+            target.yaw_deg = (camera.yaw_deg / 90.0).round() * 90.0;
+        }
+        self.transition_to(camera, &target);
+    }
+
+    /// Turns the camera around the pivot right away, cancelling any transition
+    pub fn orbit_by(
+        &mut self,
+        camera: &mut Camera,
+        pivot: Point3<f32>,
+        yaw_deg: f32,
+        pitch_deg: f32,
+    ) {
+        self.cancel();
+        let distance = (camera.eye - pivot).norm().max(0.1);
+        camera.yaw_deg += yaw_deg;
+        camera.pitch_deg = (camera.pitch_deg + pitch_deg).clamp(-89.0, 89.0);
+        camera.eye = pivot - camera.forward() * distance;
+    }
+
+    /// Moves the camera towards the pivot, dividing its distance by `zoom`
+    pub fn dolly(&mut self, camera: &mut Camera, pivot: Point3<f32>, zoom: f32) {
+        self.cancel();
+        let distance = (camera.eye - pivot).norm();
+        let target = (distance / zoom.max(1e-3)).max(0.1);
+        camera.eye = pivot - camera.forward() * target;
+    }
+
+    fn transition_to(&mut self, camera: &Camera, target: &Camera) {
+        let from = View::of(camera);
+        let mut to = View::of(target);
+        // Turns the short way around
+        to.yaw_deg = from.yaw_deg + (to.yaw_deg - from.yaw_deg + 180.0).rem_euclid(360.0) - 180.0;
+
+        self.transition = Some(Transition {
+            from,
+            to,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    pub fn cancel(&mut self) {
+        self.transition = None;
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, dt: Duration) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        transition.elapsed += dt;
+        let t = (transition.elapsed.as_secs_f32() / self.duration.as_secs_f32().max(1e-3)).min(1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+
+        let (from, to) = (transition.from, transition.to);
+        camera.eye = from.eye + (to.eye - from.eye) * t;
+        camera.yaw_deg = from.yaw_deg + (to.yaw_deg - from.yaw_deg) * t;
+        camera.pitch_deg = from.pitch_deg + (to.pitch_deg - from.pitch_deg) * t;
+
+        if t >= 1.0 {
+            self.transition = None;
+        }
+    }
+}
+
+impl View {
+    fn of(camera: &Camera) -> Self {
+        Self {
+            eye: camera.eye,
+            yaw_deg: camera.yaw_deg,
+            pitch_deg: camera.pitch_deg,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use egui::{Color32, Pos2, Stroke};
+use nalgebra::Vector3;
+
+use crate::{graphics::camera::Camera, locale::tr};
+
+const SIZE: f32 = 96.0;
+const HANDLE_RADIUS: f32 = 9.0;
+/// Degrees turned per dragged point
+const DRAG_SPEED: f32 = 0.5;
+
+/// Axis widget in the top right corner of the viewport, following the camera orientation
+///
+/// Clicking an axis handle snaps the view to look at the scene from that side,
+/// dragging it orbits the camera
+pub struct OrientationGizmo {
+    /// Axis views are drawn with an orthographic projection
+    pub ortho_on_snap: bool,
+    /// Pointer over the gizmo or dragging it, as of the last time it was shown
+    pub(super) hovered: bool,
+}
+
+impl Default for OrientationGizmo {
+    fn default() -> Self {
+        Self {
+            ortho_on_snap: true,
+            hovered: false,
+        }
+    }
+}
+
+pub enum GizmoAction {
+    /// Side to look from
+    Snap(Vector3<f32>),
+    /// Yaw and pitch to turn around the pivot, in degrees
+    Orbit(f32, f32),
+}
+
+struct Handle {
+    /// Side of the scene the view is snapped to
+    side: Vector3<f32>,
+    label: &'static str,
+    color: Color32,
+    positive: bool,
+}
+
+impl OrientationGizmo {
+    pub fn show(&mut self, ctx: &egui::Context, camera: &Camera) -> Option<GizmoAction> {
+        let mut action = None;
+        egui::Area::new(egui::Id::new("Orientation gizmo"))
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+            .show(ctx, |ui| {
+                let (response, painter) =
+                    ui.allocate_painter(egui::Vec2::splat(SIZE), egui::Sense::click_and_drag());
+                let center = response.rect.center();
+                let reach = SIZE * 0.5 - HANDLE_RADIUS;
+                painter.circle_filled(center, SIZE * 0.5, Color32::from_black_alpha(64));
+
+                let rot = camera.compute_rot_matrix();
+                let mut handles: Vec<(f32, Pos2, Handle)> = handles()
+                    .into_iter()
+                    .map(|handle| {
+                        let view = rot.transform_vector(&handle.side);
+                        let pos = center + egui::vec2(view.x, -view.y) * reach;
+                        (view.z, pos, handle)
+                    })
+                    .collect();
+                // Handles pointing at the camera are drawn last, on top
+                handles.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let hovered = response.hover_pos().and_then(|cursor| {
+                    handles
+                        .iter()
+                        .rev()
+                        .position(|(_, pos, _)| pos.distance(cursor) <= HANDLE_RADIUS)
+                        .map(|i| handles.len() - 1 - i)
+                });
+
+                for (i, (_, pos, handle)) in handles.iter().enumerate() {
+                    let color = if hovered == Some(i) {
+                        Color32::WHITE
+                    } else {
+                        handle.color
+                    };
+                    if handle.positive {
+                        painter.line_segment([center, *pos], Stroke::new(2.0, color));
+                        painter.circle_filled(*pos, HANDLE_RADIUS, color);
+                        painter.text(
+                            *pos,
+                            egui::Align2::CENTER_CENTER,
+                            handle.label,
+                            egui::FontId::proportional(11.0),
+                            Color32::BLACK,
+                        );
+                    } else {
+                        painter.circle_stroke(*pos, HANDLE_RADIUS * 0.7, Stroke::new(2.0, color));
+                    }
+                }
+
+                if response.clicked() {
+                    action = hovered.map(|i| GizmoAction::Snap(handles[i].2.side));
+                } else if response.dragged() {
+                    let delta = response.drag_delta() * DRAG_SPEED;
+                    action = Some(GizmoAction::Orbit(-delta.x, -delta.y));
+                }
+                self.hovered = response.hovered() || response.dragged();
+                response.on_hover_text(tr("editor-gizmo-help"));
+            });
+        action
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.ortho_on_snap, tr("editor-gizmo-ortho"));
+    }
+}
+
+fn handles() -> [Handle; 6] {
+    let axes = [
+        (Vector3::x(), "X", Color32::from_rgb(230, 70, 70)),
+        (Vector3::y(), "Y", Color32::from_rgb(100, 200, 80)),
+        (Vector3::z(), "Z", Color32::from_rgb(70, 130, 230)),
+    ];
+    let handle = |i: usize, positive: bool| {
+        let (axis, label, color) = axes[i];
+        Handle {
+            side: if positive { axis } else { -axis },
+            label,
+            color,
+            positive,
+        }
+    };
+    [
+        handle(0, true),
+        handle(1, true),
+        handle(2, true),
+        handle(0, false),
+        handle(1, false),
+        handle(2, false),
+    ]
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "terrain")]
+use crate::graphics::terrain::heightmap::Heightmap;
+use crate::{
+    entity::Uuid,
+    graphics::{
+        light::{LightsUniform, RawLight},
+        GlobalRenderer,
+    },
+    locale::tr_args,
+    scene::SceneInstance,
+};
+
+use super::{
+    csg::{CsgEditor, CsgStep},
+    scene::SceneEditor,
+};
+
+/// Edits kept for undo, the oldest ones are dropped
+const MAX_EDITS: usize = 32;
+
+/// State of an edited resource from before an edit
+pub enum Snapshot {
+    #[cfg(feature = "terrain")]
+    Heightmap(Heightmap),
+    #[cfg(feature = "terrain")]
+    SplatMap(Vec<[u8; 4]>),
+    /// One color per vertex of the mesh
+    VertexColors {
+        model_id: u16,
+        mesh_id: u16,
+        colors: Vec<[u8; 4]>,
+    },
+    /// Placed instance, `None` when it was not placed yet
+    Instance {
+        uuid: Uuid,
+        placed: Option<SceneInstance>,
+    },
+    /// Every light slot, with whether it is enabled
+    Lights(Vec<(RawLight, bool)>),
+    Csg {
+        steps: Vec<CsgStep>,
+        segments: u32,
+    },
+}
+
+/// What the edits are undone on
+pub struct EditTarget<'a> {
+    pub renderer: &'a mut GlobalRenderer,
+    pub scene_editor: &'a mut SceneEditor,
+    pub csg_editor: &'a mut CsgEditor,
+}
+
+impl Snapshot {
+    pub fn lights(lights: &LightsUniform) -> Self {
+        let slots = lights.lights().iter().enumerate();
+        Snapshot::Lights(
+            slots
+                .map(|(i, light)| (*light, lights.is_enabled(i as u32)))
+                .collect(),
+        )
+    }
+
+    pub fn csg(csg_editor: &CsgEditor) -> Self {
+        Snapshot::Csg {
+            steps: csg_editor.steps.clone(),
+            segments: csg_editor.segments,
+        }
+    }
+
+    /// Puts the state back, returning the one it replaced
+    fn restore(self, target: &mut EditTarget) -> Snapshot {
+        let renderer = &mut *target.renderer;
+        match self {
+            #[cfg(feature = "terrain")]
+            Snapshot::Heightmap(map) => {
+                let height = &mut renderer.terrain.height;
+                let previous = std::mem::take(&mut height.map);
+                height.set(map);
+                Snapshot::Heightmap(previous)
+            }
+            #[cfg(feature = "terrain")]
+            Snapshot::SplatMap(weights) => {
+                let splat_map = &mut renderer.terrain.material.splat_map;
+                let previous = std::mem::take(&mut splat_map.weights);
+                splat_map.set(weights);
+                Snapshot::SplatMap(previous)
+            }
+            Snapshot::VertexColors {
+                model_id,
+                mesh_id,
+                colors,
+            } => {
+                let models = &mut renderer.entities.models;
+                let previous = models.vertex_colors(model_id, mesh_id);
+                models.set_vertex_colors(model_id, mesh_id, &colors);
+                Snapshot::VertexColors {
+                    model_id,
+                    mesh_id,
+                    colors: previous,
+                }
+            }
+            Snapshot::Instance { uuid, placed } => {
+                let models = &mut renderer.entities.models;
+                let previous = target.scene_editor.restore(models, uuid, placed);
+                Snapshot::Instance {
+                    uuid,
+                    placed: previous,
+                }
+            }
+            Snapshot::Lights(slots) => {
+                let lights = &mut renderer.lights;
+                let previous = Snapshot::lights(lights);
+                // Lights pushed since then stay in their slot, disabled
+                for i in 0..lights.lights().len() as u32 {
+                    let (light, enabled) = slots.get(i as usize).copied().unwrap_or_default();
+                    lights.set(i, light);
+                    lights.set_enabled(i, enabled);
+                }
+                previous
+            }
+            Snapshot::Csg { steps, segments } => {
+                let previous = Snapshot::csg(target.csg_editor);
+                target.csg_editor.set_steps(steps, segments);
+                previous
+            }
+        }
+    }
+}
+
+/// Changes made over several frames by one widget, recorded as a single edit once the pointer
+/// lets it go
+#[derive(Default)]
+pub struct PendingEdit(Option<Snapshot>);
+
+impl PendingEdit {
+    /// Keeps the state from before the first change
+    pub fn changed(&mut self, before: impl FnOnce() -> Snapshot) {
+        if self.0.is_none() {
+            self.0 = Some(before());
+        }
+    }
+
+    /// Records the edit unless a widget is still dragged or typed in
+    pub fn commit(&mut self, ctx: &egui::Context, history: &mut EditHistory) {
+        if ctx.dragged_id().is_some() || ctx.memory(|memory| memory.focused().is_some()) {
+            return;
+        }
+        if let Some(before) = self.0.take() {
+            history.push(before);
+        }
+    }
+}
+
+/// Undo and redo stacks shared by the editor tools
+///
+/// Tools push the state from before each edit, undoing swaps it with the current one
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl EditHistory {
+    /// Records a new edit, the undone ones can't be redone anymore
+    pub fn push(&mut self, before: Snapshot) {
+        if self.undo.len() == MAX_EDITS {
+            self.undo.remove(0);
+        }
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, mut target: EditTarget) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(snapshot.restore(&mut target));
+        }
+    }
+
+    pub fn redo(&mut self, mut target: EditTarget) {
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(snapshot.restore(&mut target));
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, target: EditTarget) {
+        let (undo, redo) = ui
+            .horizontal(|ui| {
+                let undo = ui.add_enabled(
+                    !self.undo.is_empty(),
+                    egui::Button::new(tr_args("editor-undo", &[("count", &self.undo.len())])),
+                );
+                let redo = ui.add_enabled(
+                    !self.redo.is_empty(),
+                    egui::Button::new(tr_args("editor-redo", &[("count", &self.redo.len())])),
+                );
+                (undo.clicked(), redo.clicked())
+            })
+            .inner;
+        if undo {
+            self.undo(target);
+        } else if redo {
+            self.redo(target);
+        }
+    }
+}
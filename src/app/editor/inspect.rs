@@ -0,0 +1,551 @@
+use egui::{Color32, DragValue, Slider};
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+use crate::{
+    game::{follow::FollowCamera, mouse::MouseSettings},
+    graphics::{
+        camera::{Camera, Projection},
+        color::Color3,
+        entities::{
+            lightmap::bake::BakeSettings,
+            model::{Material, ModelInstance},
+        },
+        light::Light,
+        settings::{
+            AutoExposureSettings, BlobShadowSettings, CloudSettings, EnvironmentSettings,
+            FogSettings, LightOverflow, LightSettings, MinimapSettings, MirrorSettings,
+            OriginSettings, OutlineSettings, PostSettings, PrecipitationSettings, RenderSettings,
+            ResolutionSettings, RetroSettings, ShadowFilter, ShadowSettings, SkySettings,
+            SmallObjectCull, TerrainLayer, TerrainSettings, TextureQuality,
+            TextureStreamingSettings, Tonemapper, WindSettings,
+        },
+        volumes::PostVolume,
+    },
+    locale::{tr, tr_args},
+};
+
+/// Editor ui generated from the fields of a value
+pub trait Inspect {
+    /// Returns true when the value was edited
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool;
+
+    /// Structs are collapsed under their label, plain values are drawn next to it
+    fn inspect_labeled(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+        ui.collapsing(label, |ui| self.inspect(ui))
+            .body_returned
+            .unwrap_or(false)
+    }
+}
+
+/// Slider over the indices of a collection, only a label when it is empty. The index is clamped
+/// into the collection, returns true when it was edited
+pub fn index_slider(ui: &mut egui::Ui, index: &mut u32, len: u32, text: &str) -> bool {
+    let Some(last) = len.checked_sub(1) else {
+        ui.label(tr_args("editor-index-none", &[("name", &text)]));
+        return false;
+    };
+    *index = (*index).min(last);
+    ui.add(Slider::new(index, 0..=last).text(text)).changed()
+}
+
+/// Implements [`Inspect`] by listing the fields, ranged fields get a slider
+///
+/// Fields left out are not editable
+macro_rules! impl_inspect {
+    (@field $ui:ident, $value:expr, $label:expr) => {
+        $value.inspect_labeled($ui, $label)
+    };
+    (@field $ui:ident, $value:expr, $label:expr, $range:expr) => {
+        $ui.add(Slider::new(&mut $value, $range).text($label)).changed()
+    };
+    ($ty:ty { $($field:ident $(: $range:expr)?),* $(,)? }) => {
+        impl Inspect for $ty {
+            fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+                let mut changed = false;
+                $(changed |= impl_inspect!(@field ui, self.$field, &field_label(stringify!($field)) $(, $range)?);)*
+                changed
+            }
+        }
+    };
+}
+
+impl_inspect!(Camera {
+    eye,
+    up,
+    yaw_deg: -180.0..=180.0,
+    pitch_deg: -90.0..=90.0,
+    roll_deg: -90.0..=90.0,
+});
+
+impl_inspect!(FollowCamera {
+    enabled,
+    target,
+    distance: 0.5..=50.0,
+    radius: 0.0..=2.0,
+    return_rate: 0.1..=20.0,
+});
+
+impl_inspect!(PostVolume {
+    center,
+    half_extents,
+    blend_distance: 0.0..=20.0,
+    exposure: 0.0..=8.0,
+    fog_density: 0.0..=0.5,
+    lut_strength: 0.0..=1.0,
+});
+
+impl_inspect!(Projection {
+    fov_deg: 1.0..=179.0,
+    ortho,
+    ortho_height: 0.1..=500.0,
+});
+
+impl_inspect!(Material {
+    diffuse_color,
+    diffuse_texture_id,
+    sampler_id,
+    shader_id,
+    wind: 0.0..=2.0,
+    depth_fade: 0.0..=10.0,
+});
+
+// The rotation is edited as euler angles, the packed quaternion has no direct ui
+impl Inspect for ModelInstance {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui
+            .horizontal(|ui| {
+                ui.label(tr("editor-translation"));
+                axes(ui, &mut self.translation)
+            })
+            .inner;
+
+        let (roll, pitch, yaw) = self.rotation().euler_angles();
+        let mut angles = [roll, pitch, yaw].map(f32::to_degrees);
+        if ui
+            .horizontal(|ui| {
+                ui.label(tr("editor-rotation"));
+                axes(ui, &mut angles)
+            })
+            .inner
+        {
+            let [roll, pitch, yaw] = angles.map(f32::to_radians);
+            self.set_rotation(UnitQuaternion::from_euler_angles(roll, pitch, yaw));
+            changed = true;
+        }
+
+        changed |= ui
+            .horizontal(|ui| {
+                ui.label(tr("editor-scale"));
+                axes(ui, &mut self.scale)
+            })
+            .inner;
+        changed |= self
+            .material_id
+            .inspect_labeled(ui, &tr("editor-material-id"));
+        changed |= self
+            .animation_id
+            .inspect_labeled(ui, &tr("editor-animation-id"));
+        changed |= self.params_id.inspect_labeled(ui, &tr("editor-params-id"));
+        changed |= self.zone_id.inspect_labeled(ui, &tr("editor-zone-id"));
+        changed |= self.tint.inspect_labeled(ui, &tr("editor-tint"));
+        changed
+    }
+}
+
+// The environment has its own editor section
+impl_inspect!(RenderSettings {
+    texture_quality,
+    streaming,
+    post,
+    shadows,
+    lights,
+    resolution,
+    wireframe,
+    depth_sort,
+    small_object_cull,
+    show_motion_vectors,
+    minimap,
+    mirror,
+    origin,
+});
+
+impl Inspect for SmallObjectCull {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        for (layer, min_pixels) in self.min_pixels.iter_mut().enumerate() {
+            changed |= ui
+                .add(
+                    Slider::new(min_pixels, 0.0..=64.0)
+                        .text(tr_args("editor-cull-layer", &[("layer", &layer)])),
+                )
+                .changed();
+        }
+        changed
+    }
+}
+
+impl_inspect!(OriginSettings {
+    enabled,
+    threshold: 128.0..=65536.0,
+    chunk_size: 64.0..=16384.0,
+    camera_relative,
+});
+
+impl_inspect!(MinimapSettings {
+    enabled,
+    resolution: 64..=1024,
+    extent: 5.0..=500.0,
+    interval: 1..=30,
+});
+
+impl_inspect!(MirrorSettings {
+    enabled,
+    center,
+    normal,
+    width: 0.1..=50.0,
+    height: 0.1..=50.0,
+});
+
+// The layers are listed by the terrain editor next to their texture names
+impl_inspect!(TerrainSettings {
+    extent: 1.0..=1000.0,
+    triplanar_start: 0.0..=1.0,
+    triplanar_end: 0.0..=1.0,
+});
+
+impl_inspect!(TerrainLayer {
+    tint,
+    tiling: 0.01..=10.0,
+});
+
+impl_inspect!(ShadowSettings {
+    enabled,
+    cascades: 1..=4,
+    max_distance: 10.0..=1000.0,
+    split_lambda: 0.0..=1.0,
+    blend: 0.0..=0.5,
+    normal_bias: 0.0..=0.5,
+    filter,
+    pcss_softness: 0.0..=0.1,
+    local_lights: 0..=12,
+    blobs,
+});
+
+impl_inspect!(BlobShadowSettings {
+    enabled,
+    strength: 0.0..=1.0,
+    reach: 0.0..=4.0,
+    softness: 0.0..=1.0,
+});
+
+impl_inspect!(LightSettings {
+    enabled,
+    max_lights: 1..=1024,
+    overflow,
+});
+
+impl_inspect!(TextureQuality {
+    anisotropy: 1..=16,
+    mag_filter,
+    min_filter,
+    mip_bias: -4.0..=4.0,
+});
+
+impl_inspect!(TextureStreamingSettings {
+    enabled,
+    budget_mb: 1.0..=16.0,
+    full_res_distance: 1.0..=100.0,
+    uploads_per_frame: 1..=16,
+    repack,
+});
+
+// The lut is loaded from a name typed in the settings editor, not on every keystroke
+impl_inspect!(PostSettings {
+    exposure: 0.0..=8.0,
+    tonemapper,
+    lut_strength: 0.0..=1.0,
+    lens_flare: 0.0..=4.0,
+    paper_white: 80.0..=500.0,
+    peak_brightness: 200.0..=4000.0,
+    outline,
+    retro,
+});
+
+impl_inspect!(ResolutionSettings {
+    scale: 0.25..=1.0,
+    filter,
+});
+
+impl_inspect!(RetroSettings {
+    enabled,
+    affine_textures,
+    color_levels: 2..=256,
+});
+
+impl_inspect!(OutlineSettings {
+    enabled,
+    color,
+    width: 1.0..=8.0,
+    depth_threshold: 0.01..=1.0,
+    normal_threshold: 0.01..=1.0,
+});
+
+impl_inspect!(MouseSettings {
+    sensitivity: 0.1..=10.0,
+    invert_y,
+    smoothing_samples: 1..=16,
+    acceleration,
+});
+
+impl_inspect!(EnvironmentSettings {
+    fog,
+    exposure,
+    wind,
+    sky,
+    precipitation,
+});
+
+impl_inspect!(SkySettings {
+    enabled,
+    turbidity: 1.7..=10.0,
+    intensity: 0.0..=0.5,
+    ambient: 0.0..=2.0,
+    night_color,
+    ground_color,
+    sun_size: 0.1..=5.0,
+    sun_intensity: 0.0..=100.0,
+    clouds,
+});
+
+impl_inspect!(CloudSettings {
+    enabled,
+    coverage: 0.0..=1.0,
+    softness: 0.01..=1.0,
+    size: 10.0..=2000.0,
+    altitude: 50.0..=2000.0,
+    speed: 0.0..=100.0,
+    shadow: 0.0..=1.0,
+});
+
+impl_inspect!(PrecipitationSettings {
+    max_particles: 0..=100000,
+    extent: 5.0..=200.0,
+    rain_speed: 0.0..=50.0,
+    snow_speed: 0.0..=10.0,
+    rain_drift: 0.0..=50.0,
+    snow_drift: 0.0..=50.0,
+    rain_color,
+    snow_color,
+    streak_width: 0.001..=0.1,
+    streak_length: 0.05..=3.0,
+    flake_size: 0.005..=0.5,
+});
+
+impl_inspect!(WindSettings {
+    direction_deg: 0.0..=360.0,
+    strength: 0.0..=2.0,
+    gust_frequency: 0.0..=5.0,
+});
+
+impl_inspect!(FogSettings {
+    enabled,
+    density: 0.0..=0.5,
+    anisotropy: -0.9..=0.9,
+    albedo,
+    max_distance: 10.0..=500.0,
+});
+
+impl_inspect!(BakeSettings {
+    size: 64..=4096,
+    texels_per_unit: 0.5..=64.0,
+    samples: 1..=256,
+    direct,
+    sky,
+    sky_intensity: 0.0..=4.0,
+});
+
+impl_inspect!(AutoExposureSettings {
+    enabled,
+    min_ev: -16.0..=0.0,
+    max_ev: 0.0..=16.0,
+    speed: 0.1..=10.0,
+});
+
+impl Inspect for Light {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::ComboBox::from_label(tr("editor-light-type"))
+            .selected_text(self.label())
+            .show_ui(ui, |ui| {
+                for light in [
+                    Light::None,
+                    Light::default_point(),
+                    Light::default_directional(),
+                    Light::default_spotlight(),
+                ] {
+                    let selected = self.label() == light.label();
+                    if ui.selectable_label(selected, light.label()).clicked() && !selected {
+                        *self = light;
+                        changed = true;
+                    }
+                }
+            });
+
+        match self {
+            Light::None => {}
+            Light::Point {
+                color,
+                intensity,
+                position,
+            } => {
+                changed |= color.inspect_labeled(ui, &tr("editor-color"));
+                changed |= ui
+                    .add(Slider::new(intensity, 0.0..=10.0).text(tr("editor-intensity")))
+                    .changed();
+                changed |= position.inspect_labeled(ui, &tr("editor-position"));
+            }
+            Light::Directional {
+                color,
+                intensity,
+                direction,
+            } => {
+                changed |= color.inspect_labeled(ui, &tr("editor-color"));
+                changed |= ui
+                    .add(Slider::new(intensity, 0.0..=10.0).text(tr("editor-intensity")))
+                    .changed();
+                changed |= direction.inspect_labeled(ui, &tr("editor-direction"));
+            }
+            Light::Spotlight {
+                color,
+                intensity,
+                position,
+                direction,
+                cut_off,
+            } => {
+                changed |= color.inspect_labeled(ui, &tr("editor-color"));
+                changed |= ui
+                    .add(Slider::new(intensity, 0.0..=10.0).text(tr("editor-intensity")))
+                    .changed();
+                changed |= position.inspect_labeled(ui, &tr("editor-position"));
+                changed |= direction.inspect_labeled(ui, &tr("editor-direction"));
+                changed |= ui
+                    .add(Slider::new(cut_off, 0.0..=180.0).text(tr("editor-light-cut-off")))
+                    .changed();
+            }
+        }
+        changed
+    }
+}
+
+/// Plain values, drawn on one line with their label
+macro_rules! impl_inspect_inline {
+    ($ty:ty, |$value:ident, $ui:ident| $body:expr) => {
+        impl Inspect for $ty {
+            fn inspect(&mut self, $ui: &mut egui::Ui) -> bool {
+                let $value = self;
+                $body
+            }
+
+            fn inspect_labeled(&mut self, ui: &mut egui::Ui, label: &str) -> bool {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    self.inspect(ui)
+                })
+                .inner
+            }
+        }
+    };
+}
+
+impl_inspect_inline!(f32, |value, ui| ui
+    .add(DragValue::new(value).speed(0.01))
+    .changed());
+impl_inspect_inline!(u16, |value, ui| ui.add(DragValue::new(value)).changed());
+impl_inspect_inline!(u32, |value, ui| ui.add(DragValue::new(value)).changed());
+impl_inspect_inline!(bool, |value, ui| ui.checkbox(value, "").changed());
+impl_inspect_inline!(Point3<f32>, |value, ui| axes(
+    ui,
+    value.coords.as_mut_slice()
+));
+impl_inspect_inline!(Vector3<f32>, |value, ui| axes(ui, value.as_mut_slice()));
+impl_inspect_inline!(Color3, |value, ui| ui
+    .color_edit_button_rgb(value.array_mut())
+    .changed());
+impl_inspect_inline!([f32; 3], |value, ui| ui
+    .color_edit_button_rgb(value)
+    .changed());
+impl_inspect_inline!([u8; 4], |value, ui| ui
+    .color_edit_button_srgba_unmultiplied(value)
+    .changed());
+impl_inspect_inline!(wgpu::FilterMode, |value, ui| combo(
+    ui,
+    value,
+    &[
+        (wgpu::FilterMode::Nearest, "Nearest"),
+        (wgpu::FilterMode::Linear, "Linear"),
+    ],
+));
+impl_inspect_inline!(Tonemapper, |value, ui| combo(
+    ui,
+    value,
+    &[
+        (Tonemapper::None, "None"),
+        (Tonemapper::Reinhard, "Reinhard"),
+        (Tonemapper::Aces, "Aces"),
+    ],
+));
+impl_inspect_inline!(LightOverflow, |value, ui| combo(
+    ui,
+    value,
+    &[
+        (LightOverflow::Reject, "Reject"),
+        (LightOverflow::ReplaceDimmest, "Replace dimmest"),
+        (LightOverflow::NearestToCamera, "Nearest to camera"),
+    ],
+));
+impl_inspect_inline!(ShadowFilter, |value, ui| combo(
+    ui,
+    value,
+    &[
+        (ShadowFilter::Hard, "Hard"),
+        (ShadowFilter::Pcf3x3, "PCF 3x3"),
+        (ShadowFilter::Pcf5x5, "PCF 5x5"),
+        (ShadowFilter::Pcss, "PCSS"),
+    ],
+));
+
+fn axes(ui: &mut egui::Ui, values: &mut [f32]) -> bool {
+    let colors = [Color32::RED, Color32::GREEN, Color32::CYAN];
+    let mut changed = false;
+    for ((value, color), axis) in values.iter_mut().zip(colors).zip(["X", "Y", "Z"]) {
+        ui.colored_label(color, axis);
+        changed |= ui.add(DragValue::new(value).speed(0.01)).changed();
+    }
+    changed
+}
+
+fn combo<T: PartialEq + Copy + std::fmt::Debug>(
+    ui: &mut egui::Ui,
+    value: &mut T,
+    options: &[(T, &str)],
+) -> bool {
+    let before = *value;
+    egui::ComboBox::from_id_salt(ui.next_auto_id())
+        .selected_text(format!("{value:?}"))
+        .show_ui(ui, |ui| {
+            for (option, label) in options {
+                ui.selectable_value(value, *option, *label);
+            }
+        });
+    *value != before
+}
+
+/// `snake_case` field name to a sentence case label
+fn field_label(field: &str) -> String {
+    let label = field.trim_end_matches("_deg").replace('_', " ");
+    let mut chars = label.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
@@ -1,103 +1,120 @@
 use egui::Slider;
 
-use crate::graphics::{light::Light, GlobalRenderer};
+use crate::{
+    graphics::{
+        color::{Color3, KELVIN_PRESETS},
+        light::{light_flags, Light, RawLight},
+        GlobalRenderer,
+    },
+    locale::{tr, tr_args},
+};
 
-use super::{point_slider, vec3_slider};
+use super::{
+    history::{EditHistory, Snapshot},
+    inspect::{index_slider, Inspect},
+};
 
-#[derive(Default)]
 pub struct LightEditor {
     current: Light,
-    selection_id: usize,
+    lens_flare: bool,
+    casts_shadows: bool,
+    selection_id: u32,
+    /// Last temperature given to the color, the color picker may have changed it since
+    kelvin: f32,
 }
 
-impl LightEditor {
-    pub fn ui(&mut self, ui: &mut egui::Ui, renderer: &mut GlobalRenderer) {
-        let a = Light::None;
-        let b = Light::default_point();
-        let c = Light::default_directional();
-        let d = Light::default_spotlight();
-
-        egui::ComboBox::from_label("")
-            .selected_text(format!("Light type: {}", self.current.label()))
-            .show_ui(ui, |ui| {
-                ui.selectable_value(&mut self.current, a, a.label());
-                ui.selectable_value(&mut self.current, b, b.label());
-                ui.selectable_value(&mut self.current, c, c.label());
-                ui.selectable_value(&mut self.current, d, d.label());
-            });
-
-        ui.separator();
+impl Default for LightEditor {
+    fn default() -> Self {
+        Self {
+            current: Light::default(),
+            lens_flare: false,
+            casts_shadows: false,
+            selection_id: 0,
+            kelvin: 6500.0,
+        }
+    }
+}
 
-        match &mut self.current {
-            Light::None => {
-                ui.label("None");
-            }
-            Light::Point {
-                color,
-                intensity,
-                position,
-            } => {
-                ui.heading("Pointlight");
-                ui.label("Color: ");
-                ui.color_edit_button_rgb(color.array_mut());
-                ui.label("Intensity: ");
-                ui.add(Slider::new(intensity, 0.0..=10.0));
-                ui.label("Position: ");
-                point_slider(ui, position, -10.0..=10.0);
-            }
-            Light::Directional {
-                color,
-                intensity,
-                direction,
-            } => {
-                ui.heading("Directional Light");
-                ui.label("Color: ");
-                ui.color_edit_button_rgb(color.array_mut());
-                ui.label("Intensity: ");
-                ui.add(Slider::new(intensity, 0.0..=10.0));
-                vec3_slider(ui, direction);
-            }
-            Light::Spotlight {
-                color,
-                intensity,
-                position,
-                direction,
-                cut_off,
-            } => {
-                ui.heading("Spotlight");
-                ui.label("Color: ");
-                ui.color_edit_button_rgb(color.array_mut());
-                ui.label("Intensity: ");
-                ui.add(Slider::new(intensity, 0.0..=10.0));
-                ui.label("Position: ");
-                point_slider(ui, position, -10.0..=10.0);
-                ui.label("Direction: ");
-                vec3_slider(ui, direction);
-                ui.add(Slider::new(cut_off, 0.0..=180.0).text("Cut off"));
-            }
+impl LightEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        renderer: &mut GlobalRenderer,
+        history: &mut EditHistory,
+    ) {
+        self.current.inspect(ui);
+        if let Some(color) = self.current.color_mut() {
+            temperature_ui(ui, &mut self.kelvin, color);
         }
 
+        ui.checkbox(&mut self.lens_flare, tr("editor-light-lens-flare"));
+        ui.checkbox(&mut self.casts_shadows, tr("editor-light-cast-shadows"));
+
         ui.separator();
-        ui.label("Index: ");
-        ui.add(Slider::new(
+        ui.checkbox(
+            &mut renderer.settings.lights.enabled,
+            tr("editor-light-lighting"),
+        );
+        index_slider(
+            ui,
             &mut self.selection_id,
-            0..=renderer.lights.storage_buffer.len() as usize - 1,
-        ));
+            renderer.lights.len(),
+            &tr("editor-light-index"),
+        );
+        let raw = RawLight::from(self.current)
+            .with_flag(light_flags::LENS_FLARE, self.lens_flare)
+            .with_flag(light_flags::CAST_SHADOWS, self.casts_shadows);
+        let index = self.selection_id;
         ui.horizontal(|ui| {
-            if self.selection_id <= renderer.lights.storage_buffer.len() as usize {
-                if ui.button("Apply").clicked() {
-                    renderer
-                        .lights
-                        .storage_buffer
-                        .set(self.selection_id as u32, self.current.clone().into());
+            if index < renderer.lights.len() {
+                // Unchanged lights are not uploaded again
+                if ui.button(tr("editor-apply")).clicked()
+                    && renderer.lights.get(index) != Some(&raw)
+                {
+                    history.push(Snapshot::lights(&renderer.lights));
+                    renderer.lights.set(index, raw);
+                }
+                let mut enabled = renderer.lights.is_enabled(index);
+                if ui.checkbox(&mut enabled, tr("editor-enabled")).changed() {
+                    history.push(Snapshot::lights(&renderer.lights));
+                    renderer.lights.set_enabled(index, enabled);
                 }
             }
-            if ui.button("Push").clicked() {
-                renderer
-                    .lights
-                    .storage_buffer
-                    .push(self.current.clone().into());
+            if ui.button(tr("editor-push")).clicked() {
+                history.push(Snapshot::lights(&renderer.lights));
+                renderer.lights.push(raw);
             }
         });
+        let lights = &renderer.lights;
+        ui.label(tr_args(
+            "editor-light-counts",
+            &[
+                ("count", &lights.len()),
+                ("max", &lights.max_lights()),
+                ("waiting", &lights.overflow_len()),
+            ],
+        ));
+        let shadowed = format!("{:?}", renderer.entities.shadows.local.shadowed());
+        ui.label(tr_args("editor-light-shadowed", &[("lights", &shadowed)]));
+    }
+}
+
+/// Sets the color from a temperature, the color picker stays free to tint it afterwards
+fn temperature_ui(ui: &mut egui::Ui, kelvin: &mut f32, color: &mut Color3) {
+    let slider = Slider::new(&mut *kelvin, 1000.0..=12000.0)
+        .step_by(50.0)
+        .suffix("K")
+        .text(tr("editor-light-temperature"));
+    let mut changed = ui.add(slider).changed();
+    ui.horizontal(|ui| {
+        for (name, preset) in KELVIN_PRESETS {
+            if ui.button(name).clicked() {
+                *kelvin = preset;
+                changed = true;
+            }
+        }
+    });
+    if changed {
+        *color = Color3::from_kelvin(*kelvin);
     }
 }
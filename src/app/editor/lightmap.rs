@@ -0,0 +1,101 @@
+use crate::{
+    graphics::{
+        color::Color3,
+        entities::{
+            lightmap::bake::{bake_lightmap, BakeInstance, BakeSettings, BakedLightmap},
+            renderer::EntitiesRenderer,
+        },
+        light::LightsUniform,
+    },
+    jobs::{self, Job, Priority},
+    locale::tr,
+};
+
+use super::{inspect::Inspect, scene::SceneEditor};
+
+/// Bakes the light of the placed static instances as a background job
+#[derive(Default)]
+pub struct LightmapEditor {
+    pub settings: BakeSettings,
+    /// Index of the placed instance of every baked instance, with the bake in progress
+    pending: Option<(Vec<usize>, Job<BakedLightmap>)>,
+}
+
+impl LightmapEditor {
+    pub fn baking(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Applies the finished bake to the scene, every frame
+    pub fn poll(&mut self, scene_editor: &mut SceneEditor, entities: &mut EntitiesRenderer) {
+        let Some((placed, job)) = &mut self.pending else {
+            return;
+        };
+        if job.lost() {
+            self.pending = None;
+            return;
+        }
+        let Some(baked) = job.poll() else {
+            return;
+        };
+        scene_editor.apply_lightmap(entities, placed, baked);
+        self.pending = None;
+    }
+
+    /// Instances whose animation move them are left out of the bake
+    pub fn bake(
+        &mut self,
+        scene_editor: &SceneEditor,
+        entities: &EntitiesRenderer,
+        lights: &LightsUniform,
+    ) {
+        let (placed, instances): (Vec<_>, Vec<_>) = scene_editor
+            .placed()
+            .enumerate()
+            .filter(|(_, placed)| placed.instance.animation_id == 0)
+            .map(|(i, placed)| {
+                let [r, g, b] = entities
+                    .materials
+                    .get(placed.instance.material_id)
+                    .map_or([1.0; 3], |material| material.diffuse_color);
+                let instance = BakeInstance {
+                    triangles: entities
+                        .models
+                        .mesh_triangles(placed.model_id, placed.mesh_id),
+                    transform: placed.instance.transform(),
+                    albedo: Color3::new(r, g, b),
+                    lightmapped: entities
+                        .models
+                        .is_lightmapped(placed.model_id, placed.mesh_id),
+                };
+                (i, instance)
+            })
+            .unzip();
+
+        let settings = self.settings;
+        let lights: Vec<_> = lights.enabled_lights().map(|(_, light)| *light).collect();
+        let job = jobs::spawn("Lightmap bake", Priority::Low, move || {
+            bake_lightmap(&settings, &instances, &lights)
+        });
+        self.pending = Some((placed, job));
+    }
+
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        scene_editor: &SceneEditor,
+        entities: &EntitiesRenderer,
+        lights: &LightsUniform,
+    ) {
+        self.settings.inspect(ui);
+        if self.baking() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr("editor-lightmap-baking"));
+            });
+        } else if ui.button(tr("editor-lightmap-bake")).clicked() {
+            self.bake(scene_editor, entities, lights);
+        }
+        ui.label(tr("editor-lightmap-help"));
+    }
+}
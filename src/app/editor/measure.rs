@@ -0,0 +1,107 @@
+use nalgebra::{Point3, Vector3};
+use winit::event::MouseButton;
+
+use crate::{
+    app::inputs::Inputs,
+    graphics::{debug_lines::DebugLines, picking::DepthPicker},
+    locale::{tr, tr_args},
+};
+
+/// Distance between two picked world points
+///
+/// Points are picked with a left click on the scene in editor mode
+#[derive(Default)]
+pub struct MeasureTool {
+    pub active: bool,
+    points: [Option<Point3<f32>>; 2],
+    missed: bool,
+}
+
+impl MeasureTool {
+    /// Requests a pick on click, `owns_pointer` is true while the scene gets the mouse
+    pub fn update(&mut self, inputs: &Inputs, picker: &mut DepthPicker, owns_pointer: bool) {
+        for pick in picker.take_events() {
+            self.missed = pick.position.is_none();
+            if let Some(position) = pick.position {
+                match self.points {
+                    [Some(_), None] => self.points[1] = Some(position),
+                    _ => self.points = [Some(position), None],
+                }
+            }
+        }
+
+        if !self.active || !owns_pointer || !inputs.mouse_pressed(MouseButton::Left) {
+            return;
+        }
+        if let Some((x, y)) = inputs.cursor() {
+            picker.request((x as u32, y as u32));
+        }
+    }
+
+    /// Follows the world origin
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for point in self.points.iter_mut().flatten() {
+            *point -= shift;
+        }
+    }
+
+    pub fn draw(&self, lines: &mut DebugLines) {
+        if !self.active {
+            return;
+        }
+        let highlight = lines.palette.highlight();
+        let [x, y, z] = lines.palette.axes();
+        for point in self.points.iter().flatten() {
+            lines.cross(*point, 0.25, highlight);
+        }
+        if let [Some(a), Some(b)] = self.points {
+            lines.overlay_line(a, b, highlight);
+            lines.overlay_line(a, Point3::new(b.x, a.y, a.z), x);
+            lines.overlay_line(Point3::new(b.x, a.y, a.z), Point3::new(b.x, b.y, a.z), y);
+            lines.overlay_line(Point3::new(b.x, b.y, a.z), b, z);
+        }
+    }
+
+    pub fn delta(&self) -> Option<Vector3<f32>> {
+        match self.points {
+            [Some(a), Some(b)] => Some(b - a),
+            _ => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points = [None, None];
+        self.missed = false;
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.active, tr("editor-measure"));
+        if !self.active {
+            return;
+        }
+        ui.label(tr("editor-measure-help"));
+        for (name, point) in ["A", "B"].iter().zip(self.points) {
+            match point {
+                Some(p) => ui.label(format!("{name}: {:.3}, {:.3}, {:.3}", p.x, p.y, p.z)),
+                None => ui.label(format!("{name}: -")),
+            };
+        }
+        if let Some(delta) = self.delta() {
+            let distance = format!("{:.3}", delta.norm());
+            ui.label(tr_args(
+                "editor-measure-distance",
+                &[("distance", &distance)],
+            ));
+            ui.label(format!(
+                "dx: {:.3}, dy: {:.3}, dz: {:.3}",
+                delta.x, delta.y, delta.z
+            ));
+        }
+        if self.missed {
+            ui.colored_label(egui::Color32::YELLOW, tr("editor-measure-missed"));
+        }
+        if ui.button(tr("editor-clear")).clicked() {
+            self.clear();
+        }
+    }
+}
@@ -1,32 +1,144 @@
-use std::ops::RangeInclusive;
+use std::{path::PathBuf, time::Duration};
 
+use autosave::Autosave;
+use cameras::CameraEditor;
+use csg::CsgEditor;
 use egui::{Color32, Slider};
 pub use egui_winit::State as EguiWinitState;
+use focus::CameraFocus;
+use gizmo::{GizmoAction, OrientationGizmo};
+use history::{EditHistory, EditTarget, Snapshot};
+use inspect::{index_slider, Inspect};
 use light::LightEditor;
+use lightmap::LightmapEditor;
+use measure::MeasureTool;
 use nalgebra::{Matrix4, Point3, Vector3};
+use navmesh::NavMeshEditor;
+use profiler::ProfilerEditor;
+#[cfg(feature = "remote-editor")]
+use remote::RemoteEditor;
+use scene::SceneEditor;
+use scene_diff::SceneDiffEditor;
+use sequencer::Sequencer;
+use settings::SettingsEditor;
+use shortcuts::{Action, Shortcuts};
+#[cfg(feature = "terrain")]
+use terrain::TerrainEditor;
+use theme::ThemeEditor;
+use vertex_paint::VertexPainter;
+use volumes::VolumeEditor;
 use winit::window::Window;
 
+#[cfg(not(feature = "terrain"))]
+use crate::game::ground::FlatGround;
 use crate::{
-    game::GameState,
-    graphics::{camera::Projection, entities::model::ModelInstance, GlobalRenderer},
+    app::{
+        config::Config,
+        inputs::{Inputs, TouchGesture},
+        menu::palette_name,
+        pointer::{PointerClaims, PointerFocus, PointerOwner},
+        DISPLAY_SECTION,
+    },
+    constants,
+    entity::EntityMeta,
+    game::{agents::Agents, weather::WeatherStep, Body, GameState, Mode},
+    graphics::{
+        camera::{Camera, Projection},
+        capture::{CaptureFormat, FrameCapture, CAPTURE_PATH},
+        churn::{self, StateChanges},
+        ctx::{FramePacing, GraphicsCtx},
+        debug_lines::DebugPalette,
+        entities::{
+            draws::CULL_LAYERS,
+            lod::{LodGroup, LodLevel},
+            model::ModelInstance,
+            renderer::{EntitiesRenderer, ASSET_MODELS},
+            streaming::{CellState, WorldStreamer},
+        },
+        stats::RenderStats,
+        streaming::TextureStreamer,
+        weather::WeatherKind,
+        GlobalRenderer,
+    },
+    locale::{tr, tr_args},
+    scene::{Scene, SceneInstance},
+    world::WorldPartition,
+    ASSETS,
 };
 
+pub mod autosave;
+pub mod cameras;
+pub mod csg;
+pub mod focus;
+pub mod gizmo;
+pub mod history;
+pub mod inspect;
 pub mod light;
+pub mod lightmap;
+pub mod measure;
+pub mod navmesh;
+pub mod profiler;
+#[cfg(feature = "remote-editor")]
+pub mod remote;
+pub mod scene;
+pub mod scene_diff;
+pub mod sequencer;
+pub mod settings;
+pub mod shortcuts;
+#[cfg(feature = "terrain")]
+pub mod terrain;
+pub mod theme;
+pub mod vertex_paint;
+pub mod volumes;
+
+/// Distance in front of the camera the orientation gizmo turns around
+const ORBIT_DISTANCE: f32 = 10.0;
+/// Degrees orbited per pixel a finger moved
+const TOUCH_ORBIT_SPEED: f32 = 0.3;
 
 pub struct Editor {
     pub gui_state: EguiWinitState,
     pub gui_ctx: egui::Context,
 
     pub light_editor: LightEditor,
+    pub settings_editor: SettingsEditor,
+    pub profiler_editor: ProfilerEditor,
+    pub theme_editor: ThemeEditor,
+    pub scene_editor: SceneEditor,
+    pub sequencer: Sequencer,
+    pub scene_diff_editor: SceneDiffEditor,
+    pub autosave: Autosave,
+    pub lightmap_editor: LightmapEditor,
+    pub camera_editor: CameraEditor,
+    pub csg_editor: CsgEditor,
+    pub vertex_painter: VertexPainter,
+    pub volume_editor: VolumeEditor,
+    #[cfg(feature = "terrain")]
+    pub terrain_editor: TerrainEditor,
+    pub shortcuts: Shortcuts,
+    pub history: EditHistory,
+    pub focus: CameraFocus,
+    pub measure: MeasureTool,
+    pub navmesh_editor: NavMeshEditor,
+    pub gizmo: OrientationGizmo,
+    /// Shows the gui in the `editor-remote` viewer while one is connected
+    #[cfg(feature = "remote-editor")]
+    pub remote: Option<RemoteEditor>,
+    /// Root typed in the project section
+    pub project_root: String,
+    /// Root to open once the frame is done, see [`Self::take_project_request`]
+    project_request: Option<PathBuf>,
+    /// Edited in the frame pacing section, see [`Self::take_pacing_request`]
+    pacing_request: Option<FramePacing>,
 
-    pub new_inst_pos: Point3<f32>,
+    pub new_instance: ModelInstance,
     pub mat_id: u32,
     pub model_id: u32,
     pub mesh_id: u32,
 }
 
 impl Editor {
-    pub fn new(window: &Window) -> Self {
+    pub fn new(window: &Window, config: &Config) -> Self {
         let gui_ctx = egui::Context::default();
         let gui_state = EguiWinitState::new(
             gui_ctx.clone(),
@@ -37,143 +149,1359 @@ impl Editor {
             None,
         );
         let light_editor = LightEditor::default();
+        let settings_editor = SettingsEditor::default();
 
         Self {
             gui_state,
             gui_ctx,
             light_editor,
-            new_inst_pos: Default::default(),
+            settings_editor,
+            profiler_editor: ProfilerEditor::default(),
+            theme_editor: ThemeEditor::from_config(config),
+            scene_editor: SceneEditor::default(),
+            sequencer: Sequencer::default(),
+            scene_diff_editor: SceneDiffEditor::default(),
+            autosave: Autosave::from_config(config),
+            lightmap_editor: LightmapEditor::default(),
+            camera_editor: CameraEditor::default(),
+            csg_editor: CsgEditor::default(),
+            vertex_painter: VertexPainter::default(),
+            volume_editor: VolumeEditor::default(),
+            #[cfg(feature = "terrain")]
+            terrain_editor: TerrainEditor::default(),
+            shortcuts: Shortcuts::from_config(config),
+            history: EditHistory::default(),
+            focus: CameraFocus::default(),
+            measure: MeasureTool::default(),
+            navmesh_editor: NavMeshEditor::default(),
+            gizmo: OrientationGizmo::default(),
+            #[cfg(feature = "remote-editor")]
+            remote: RemoteEditor::from_config(config),
+            project_root: ASSETS.root().display().to_string(),
+            project_request: None,
+            pacing_request: None,
+            new_instance: ModelInstance::new(Matrix4::identity(), 0),
             mat_id: 0,
             model_id: 0,
             mesh_id: 0,
         }
     }
 
+    /// `overlay` draws the guis shown above the editor in the same pass, like the pause menu
     pub fn run(
         &mut self,
+        graphics: &GraphicsCtx,
         renderer: &mut GlobalRenderer,
         egui_input: egui::RawInput,
         game_state: &mut GameState,
         proj: &mut Projection,
+        config: &mut Config,
+        mut overlay: impl FnMut(&egui::Context),
     ) -> (egui::FullOutput, egui::Context) {
+        self.theme_editor.apply(&self.gui_ctx);
+        self.measure.draw(&mut renderer.debug_lines);
+        self.navmesh_editor
+            .draw(&game_state.agents, &mut renderer.debug_lines);
+        self.camera_editor.draw(renderer, &game_state.cameras, proj);
+        self.csg_editor.draw(&mut renderer.debug_lines);
+        self.vertex_painter.draw(&mut renderer.debug_lines);
+        #[cfg(feature = "remote-editor")]
+        let egui_input = match &mut self.remote {
+            Some(remote) => remote.input(egui_input),
+            None => egui_input,
+        };
         let output = self.gui_ctx.run(egui_input, |gui_ctx| {
-            egui::Window::new("Editor window").show(gui_ctx, |ui| {
-                ui.collapsing("View", |ui| {
-                    ui.label("Eye: ");
-                    point_slider(ui, &mut game_state.camera.eye, -10.0..=10.0);
-                    ui.label("Up: ");
-                    vec3_slider(ui, &mut game_state.camera.up);
-                    ui.label("Angle: ");
-                    angle_slider(
+            overlay(gui_ctx);
+            let entities = &mut renderer.entities;
+            self.autosave.recovery_ui(
+                gui_ctx,
+                &mut self.scene_editor,
+                &mut entities.models,
+                &mut entities.lightmap,
+                &mut game_state.random,
+            );
+            // Tools still run outside of editor mode, only their windows are hidden
+            if game_state.mode != Mode::Editor {
+                self.gizmo.hovered = false;
+                return;
+            }
+            if let Some(action) = self.gizmo.show(gui_ctx, &game_state.camera) {
+                let camera = &mut game_state.camera;
+                let pivot = camera.eye + camera.forward() * ORBIT_DISTANCE;
+                match action {
+                    GizmoAction::Snap(side) => {
+                        self.focus.orbit(camera, pivot, side);
+                        proj.ortho = self.gizmo.ortho_on_snap;
+                    }
+                    GizmoAction::Orbit(yaw_deg, pitch_deg) => {
+                        self.focus.orbit_by(camera, pivot, yaw_deg, pitch_deg);
+                        proj.ortho = false;
+                    }
+                }
+            }
+            if renderer.settings.minimap.enabled {
+                egui::Window::new(tr("editor-minimap"))
+                    .id(egui::Id::new("Minimap"))
+                    .resizable(false)
+                    .show(gui_ctx, |ui| {
+                        let size = renderer.settings.minimap.resolution as f32
+                            / gui_ctx.pixels_per_point();
+                        let image = ui.image((renderer.minimap_texture(), egui::Vec2::splat(size)));
+                        // The view is centered on the camera
+                        ui.painter()
+                            .circle_filled(image.rect.center(), 3.0, Color32::RED);
+                    });
+            }
+            if let Some(camera) = self.camera_editor.preview_camera(&game_state.cameras) {
+                egui::Window::new(tr("editor-camera-preview"))
+                    .id(egui::Id::new("Camera preview"))
+                    .resizable(false)
+                    .show(gui_ctx, |ui| {
+                        ui.label(&camera.name);
+                        let (width, height) = renderer.preview.size();
+                        let size =
+                            egui::vec2(width as f32, height as f32) / gui_ctx.pixels_per_point();
+                        ui.image((renderer.preview_texture(), size));
+                    });
+            }
+            egui::Window::new(tr("editor-window"))
+                .id(egui::Id::new("Editor window"))
+                .show(gui_ctx, |ui| {
+                    self.history.ui(
                         ui,
-                        (
-                            &mut game_state.camera.yaw_deg,
-                            &mut game_state.camera.pitch_deg,
-                            &mut game_state.camera.roll_deg,
-                        ),
+                        EditTarget {
+                            renderer,
+                            scene_editor: &mut self.scene_editor,
+                            csg_editor: &mut self.csg_editor,
+                        },
                     );
-                });
 
-                ui.collapsing("Projection", |ui| {
-                    ui.label("Fov Y: ");
-                    ui.add(Slider::new(&mut proj.fov_deg, 0.0..=180.0));
-                });
+                    ui.collapsing(tr("editor-project"), |ui| {
+                        ui.label(tr_args(
+                            "editor-project-open",
+                            &[("root", &ASSETS.root().display())],
+                        ));
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.project_root);
+                            if ui.button(tr("editor-open")).clicked() {
+                                self.project_request = Some(PathBuf::from(&self.project_root));
+                            }
+                        });
+                    });
 
-                ui.collapsing("Lights", |ui| self.light_editor.ui(ui, renderer));
+                    ui.collapsing(tr("editor-view"), |ui| game_state.camera.inspect(ui));
 
-                ui.collapsing("Instances", |ui| {
-                    point_slider(ui, &mut self.new_inst_pos, -10.0..=10.);
-                    ui.add(
-                        Slider::new(
-                            &mut self.mat_id,
-                            0..=renderer.entities.materials.len() as u32 - 1,
+                    ui.collapsing(tr("editor-projection"), |ui| {
+                        proj.inspect(ui);
+                        self.gizmo.ui(ui);
+                    });
+
+                    ui.collapsing(tr("editor-cameras"), |ui| {
+                        self.camera_editor.ui(
+                            ui,
+                            &mut game_state.cameras,
+                            &mut game_state.camera,
+                            proj,
+                        );
+                        game_state
+                            .follow
+                            .inspect_labeled(ui, &tr("editor-follow-camera"));
+                    });
+
+                    ui.collapsing(tr("editor-lights"), |ui| {
+                        self.light_editor.ui(ui, renderer, &mut self.history)
+                    });
+
+                    ui.collapsing(tr("editor-render-settings"), |ui| {
+                        self.settings_editor.ui(ui, &mut renderer.settings);
+                        let textures = &renderer.entities.textures;
+                        let resident = textures.resident_bytes() as f32 / (1024.0 * 1024.0);
+                        let budget = textures.budget_bytes() as f32 / (1024.0 * 1024.0);
+                        ui.label(tr_args(
+                            "editor-resident-textures",
+                            &[
+                                ("resident", &format!("{resident:.2}")),
+                                ("budget", &format!("{budget:.2}")),
+                            ],
+                        ));
+                    });
+
+                    ui.collapsing(tr("editor-occupancy"), |ui| {
+                        let textures = &mut renderer.entities.textures;
+                        ui.label(tr("editor-occupancy-atlas"));
+                        atlas_map(ui, textures);
+                        ui.horizontal(|ui| {
+                            if textures.repacking() {
+                                ui.spinner();
+                                ui.label(tr("editor-occupancy-repacking"));
+                            } else if ui.button(tr("editor-occupancy-repack")).clicked() {
+                                textures.repack();
+                            }
+                            ui.label(tr_args(
+                                "editor-occupancy-repacked",
+                                &[("count", &textures.repack_count())],
+                            ));
+                        });
+                        ui.label(tr("editor-occupancy-columns"));
+                        column_bars(ui, &renderer.entities.models.instance_occupancy());
+                    });
+
+                    ui.collapsing(tr("editor-environment"), |ui| {
+                        renderer.settings.environment.inspect(ui)
+                    });
+
+                    ui.collapsing(tr("editor-volumes"), |ui| {
+                        self.volume_editor.ui(
+                            ui,
+                            &mut self.scene_editor.volumes,
+                            renderer,
+                            game_state.camera.eye,
                         )
-                        .text("Material ID"),
-                    );
-                    ui.add(
-                        Slider::new(
+                    });
+
+                    ui.collapsing(tr("editor-weather"), |ui| {
+                        let weather = &mut game_state.weather;
+                        let (mut kind, mut intensity) = weather.target();
+                        let mut changed = false;
+                        ui.horizontal(|ui| {
+                            for option in WeatherKind::ALL {
+                                changed |= ui
+                                    .selectable_value(&mut kind, option, format!("{option:?}"))
+                                    .changed();
+                            }
+                        });
+                        changed |= ui
+                            .add(
+                                Slider::new(&mut intensity, 0.0..=1.0).text(tr("editor-intensity")),
+                            )
+                            .changed();
+                        if changed {
+                            weather.set(kind, intensity);
+                        }
+                        ui.add(
+                            Slider::new(&mut weather.transition_speed, 0.01..=2.0)
+                                .text(tr("editor-weather-transition-speed")),
+                        );
+                        ui.add(
+                            Slider::new(&mut weather.wetting_speed, 0.0..=1.0)
+                                .text(tr("editor-weather-wetting-speed")),
+                        );
+                        ui.add(
+                            Slider::new(&mut weather.drying_speed, 0.0..=1.0)
+                                .text(tr("editor-weather-drying-speed")),
+                        );
+                        let state = weather.state;
+                        ui.label(tr_args(
+                            "editor-weather-current",
+                            &[
+                                ("kind", &format!("{:?}", state.kind)),
+                                ("intensity", &format!("{:.2}", state.intensity)),
+                                ("wetness", &format!("{:.2}", state.wetness)),
+                            ],
+                        ));
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("editor-weather-play-demo")).clicked() {
+                                weather.play(demo_weather_script(), true);
+                            }
+                            if weather.is_scripted() {
+                                ui.label(tr("editor-weather-scripted"));
+                            }
+                        });
+                    });
+
+                    #[cfg(feature = "terrain")]
+                    ui.collapsing(tr("editor-terrain"), |ui| {
+                        self.terrain_editor.ui(ui, renderer)
+                    });
+
+                    ui.collapsing(tr("editor-world-streaming"), |ui| {
+                        let entities = &mut renderer.entities;
+                        let world = &mut entities.world;
+                        ui.add(
+                            Slider::new(&mut world.cell_size, 10.0..=200.0)
+                                .text(tr("editor-world-cell-size")),
+                        );
+                        ui.add(
+                            Slider::new(&mut world.load_radius, 0..=8)
+                                .text(tr("editor-world-load-radius")),
+                        );
+                        ui.add(
+                            Slider::new(&mut world.unload_margin, 0..=4)
+                                .text(tr("editor-world-unload-margin")),
+                        );
+                        let name = &self.scene_editor.name;
+                        ui.label(tr_args("editor-world-name", &[("name", name)]));
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("editor-world-bake")).clicked() {
+                                let scene = self.scene_editor.to_scene(game_state.random.seed());
+                                match WorldPartition::from_scene(&scene, world.cell_size).save(name)
+                                {
+                                    Ok(()) => println!(
+                                        "Saved world {}",
+                                        WorldPartition::path(name).display()
+                                    ),
+                                    Err(e) => eprintln!("Failed to bake world {name}: {e}"),
+                                }
+                            }
+                            // The placed instances are left as they are, clearing the scene
+                            // avoids drawing them twice
+                            if ui.button(tr("editor-world-stream")).clicked() {
+                                match WorldPartition::loader(name) {
+                                    Ok((index, loader)) => {
+                                        world.cell_size = index.cell_size;
+                                        if let Some(seed) = index.seed {
+                                            game_state.random.reseed(seed);
+                                        }
+                                        world.set_loader(&mut entities.models, Some(loader));
+                                    }
+                                    Err(e) => eprintln!("Failed to stream world {name}: {e}"),
+                                }
+                            }
+                            if ui.button(tr("editor-stop")).clicked() {
+                                world.set_loader(&mut entities.models, None);
+                            }
+                        });
+                        streaming_rings(ui, world);
+                    });
+
+                    ui.collapsing(tr("editor-levels-of-detail"), |ui| {
+                        let lods = &mut renderer.entities.lods;
+                        ui.add(
+                            Slider::new(&mut lods.fade_frames, 1..=60)
+                                .text(tr("editor-lod-fade-frames")),
+                        );
+                        ui.add(
+                            Slider::new(&mut lods.hysteresis, 0.0..=10.0)
+                                .text(tr("editor-lod-hysteresis")),
+                        );
+                        ui.label(tr_args(
+                            "editor-lod-instances",
+                            &[("count", &lods.len()), ("fading", &lods.fading())],
+                        ));
+                        for (i, group) in lods.groups.iter_mut().enumerate() {
+                            ui.label(tr_args("editor-lod-group", &[("index", &i)]));
+                            let last = group.levels.len().saturating_sub(1);
+                            for level in &mut group.levels[..last] {
+                                let name = ASSET_MODELS.get(level.model_id as usize);
+                                ui.add(Slider::new(&mut level.distance, 1.0..=500.0).text(
+                                    name.map_or_else(
+                                        || tr("editor-model"),
+                                        |name| name.to_string(),
+                                    ),
+                                ));
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            // Every asset model as a level, nearest first
+                            if ui.button(tr("editor-lod-add-group")).clicked() {
+                                let levels = (0..ASSET_MODELS.len())
+                                    .map(|i| LodLevel {
+                                        model_id: i as u16,
+                                        distance: 20.0 * (i + 1) as f32,
+                                    })
+                                    .collect();
+                                lods.add_group(LodGroup { levels });
+                            }
+                            if let Some(group) = lods.groups.len().checked_sub(1) {
+                                if ui.button(tr("editor-spawn")).clicked() {
+                                    let camera = &game_state.camera;
+                                    let position = camera.eye + camera.forward() * 5.0;
+                                    let transform = Matrix4::new_translation(&position.coords);
+                                    let instance = ModelInstance::new(transform, self.mat_id);
+                                    lods.add(&mut renderer.entities.models, group, instance);
+                                }
+                            }
+                            if ui.button(tr("editor-clear")).clicked() {
+                                lods.clear(&mut renderer.entities.models);
+                            }
+                        });
+                    });
+
+                    ui.collapsing(tr("editor-small-object-culling"), |ui| {
+                        ui.label(tr("editor-culling-thresholds"));
+                        let entities = &mut renderer.entities;
+                        for model_id in 0..entities.models.model_count() as u16 {
+                            if entities.models.mesh_count_of(model_id) == 0 {
+                                continue;
+                            }
+                            let name = ASSET_MODELS.get(model_id as usize).copied();
+                            let mut layer =
+                                entities.draws.cull_layer(&entities.models, model_id, 0);
+                            let slider = Slider::new(&mut layer, 0..=CULL_LAYERS as u32 - 1)
+                                .text(name.map_or_else(|| tr("editor-model"), str::to_string));
+                            if ui.add(slider).changed() {
+                                // Every mesh of the model shares the layer
+                                for mesh_id in 0..entities.models.mesh_count_of(model_id) as u16 {
+                                    entities.draws.set_cull_layer(
+                                        &entities.models,
+                                        model_id,
+                                        mesh_id,
+                                        layer,
+                                    );
+                                }
+                            }
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-frame-graph"), |ui| {
+                        let graph = renderer.frame_graph();
+                        for error in graph.validate() {
+                            ui.colored_label(ui.visuals().error_fg_color, error);
+                        }
+                        for (i, pass) in graph.passes.iter().enumerate() {
+                            let after = graph.dependencies(i);
+                            match after.is_empty() {
+                                true => ui.label(pass.name),
+                                false => ui.label(tr_args(
+                                    "editor-frame-graph-after",
+                                    &[("pass", &pass.name), ("after", &after.join(", "))],
+                                )),
+                            };
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-zones"), |ui| {
+                        let zones = &mut renderer.entities.zones;
+                        ui.checkbox(&mut zones.enabled, tr("editor-zones-portal-culling"));
+                        ui.add(
+                            Slider::new(&mut zones.max_depth, 0..=16)
+                                .text(tr("editor-zones-max-depth")),
+                        );
+                        match zones.camera_zone() {
+                            Some(zone) => {
+                                ui.label(tr_args("editor-zones-camera", &[("zone", &zone)]))
+                            }
+                            None => ui.label(tr("editor-zones-camera-outside")),
+                        };
+                        for (i, portal) in zones.portals.iter_mut().enumerate() {
+                            let [a, b] = portal.zones;
+                            ui.checkbox(
+                                &mut portal.open,
+                                tr_args(
+                                    "editor-zones-portal",
+                                    &[("index", &i), ("a", &a), ("b", &b)],
+                                ),
+                            );
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-agents"), |ui| {
+                        let agents = &mut game_state.agents;
+                        ui.label(tr_args(
+                            "editor-agents-count",
+                            &[("count", &agents.agents.len())],
+                        ));
+                        ui.checkbox(&mut agents.paused, tr("editor-paused"));
+                        ui.checkbox(&mut agents.interpolate, tr("editor-agents-interpolate"));
+                        ui.add(
+                            Slider::new(&mut agents.max_speed, 0.1..=20.0)
+                                .text(tr("editor-agents-max-speed")),
+                        );
+                        ui.add(
+                            Slider::new(&mut agents.max_force, 0.1..=50.0)
+                                .text(tr("editor-agents-max-force")),
+                        );
+                        ui.add(
+                            Slider::new(&mut agents.avoid_radius, 0.1..=10.0)
+                                .text(tr("editor-agents-avoid-radius")),
+                        );
+                        ui.add(
+                            Slider::new(&mut agents.slowing_radius, 0.1..=10.0)
+                                .text(tr("editor-agents-slowing-radius")),
+                        );
+                        ui.add(
+                            Slider::new(&mut agents.health_bar_height, 0.0..=5.0)
+                                .text(tr("editor-agents-health-bar-height")),
+                        );
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("editor-agents-spawn")).clicked() {
+                                spawn_test_agents(agents, &mut renderer.entities, 100);
+                            }
+                            if ui.button(tr("editor-clear")).clicked() {
+                                agents.clear(&mut renderer.entities);
+                            }
+                        });
+                        ui.collapsing(tr("editor-navmesh"), |ui| {
+                            #[cfg(feature = "terrain")]
+                            let ground = &renderer.terrain.height;
+                            #[cfg(not(feature = "terrain"))]
+                            let ground = &FlatGround;
+                            let center = game_state.camera.eye;
+                            self.navmesh_editor.ui(ui, agents, ground, center);
+                        });
+                    });
+
+                    #[cfg(feature = "physics")]
+                    ui.collapsing(tr("editor-projectiles"), |ui| {
+                        let projectiles = &mut game_state.projectiles;
+                        ui.label(tr("editor-projectiles-help"));
+                        ui.label(tr_args(
+                            "editor-projectiles-counts",
+                            &[
+                                ("projectiles", &projectiles.projectiles.len()),
+                                ("impacts", &projectiles.impacts.len()),
+                                ("colliders", &game_state.colliders.len()),
+                            ],
+                        ));
+                        ui.add(
+                            Slider::new(&mut projectiles.speed, 1.0..=100.0)
+                                .text(tr("editor-projectiles-speed")),
+                        );
+                        ui.add(
+                            Slider::new(&mut projectiles.gravity, 0.0..=20.0)
+                                .text(tr("editor-gravity")),
+                        );
+                        if ui.button(tr("editor-clear")).clicked() {
+                            projectiles.clear(&mut renderer.entities.models);
+                        }
+
+                        let feedback = &mut game_state.feedback;
+                        ui.add(
+                            Slider::new(&mut feedback.damage, 0.0..=100.0)
+                                .text(tr("editor-projectiles-damage")),
+                        );
+                        ui.add(
+                            Slider::new(&mut feedback.trauma, 0.0..=1.0)
+                                .text(tr("editor-projectiles-shake")),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label(tr("editor-projectiles-flash"));
+                            ui.color_edit_button_rgb(feedback.flash_color.array_mut());
+                        });
+                    });
+
+                    #[cfg(feature = "physics")]
+                    ui.collapsing(tr("editor-ragdolls"), |ui| {
+                        let puppets = &mut game_state.puppets;
+                        let settings = &mut puppets.settings;
+                        ui.add(
+                            Slider::new(&mut settings.gravity, 0.0..=20.0)
+                                .text(tr("editor-gravity")),
+                        );
+                        ui.add(
+                            Slider::new(&mut settings.damping, 0.0..=1.0)
+                                .text(tr("editor-damping")),
+                        );
+                        ui.add(
+                            Slider::new(&mut settings.friction, 0.0..=1.0)
+                                .text(tr("editor-ragdolls-friction")),
+                        );
+                        ui.add(
+                            Slider::new(&mut settings.iterations, 1..=32)
+                                .text(tr("editor-ragdolls-iterations")),
+                        );
+                        ui.add(
+                            Slider::new(&mut settings.max_bend, 0.0..=std::f32::consts::PI)
+                                .text(tr("editor-ragdolls-max-bend")),
+                        );
+                        ui.add(
+                            Slider::new(&mut puppets.blend_speed, 0.1..=20.0)
+                                .text(tr("editor-ragdolls-blend-speed")),
+                        );
+                        ui.checkbox(&mut puppets.foot_ik, tr("editor-ragdolls-foot-ik"));
+
+                        ui.collapsing(tr("editor-spring-bones"), |ui| {
+                            let bones = &puppets.skeleton.bones;
+                            let mut removed = None;
+                            for (i, spring) in puppets.springs.iter_mut().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.label(&bones[spring.bone].name);
+                                    if ui.button(tr("editor-remove")).clicked() {
+                                        removed = Some(i);
+                                    }
+                                });
+                                ui.add(
+                                    Slider::new(&mut spring.stiffness, 1.0..=500.0)
+                                        .text(tr("editor-springs-stiffness")),
+                                );
+                                ui.add(
+                                    Slider::new(&mut spring.damping, 0.0..=50.0)
+                                        .text(tr("editor-damping")),
+                                );
+                                ui.add(
+                                    Slider::new(&mut spring.gravity, 0.0..=20.0)
+                                        .text(tr("editor-gravity")),
+                                );
+                            }
+                            if let Some(i) = removed {
+                                puppets.springs.remove(i);
+                            }
+                            egui::ComboBox::from_label(tr("editor-add"))
+                                .selected_text(tr("editor-springs-bone"))
+                                .show_ui(ui, |ui| {
+                                    for (i, bone) in bones.iter().enumerate() {
+                                        let used = puppets.springs.iter().any(|s| s.bone == i);
+                                        if !used && ui.selectable_label(false, &bone.name).clicked()
+                                        {
+                                            puppets
+                                                .springs
+                                                .push(crate::game::springs::SpringBone::new(i));
+                                        }
+                                    }
+                                });
+                        });
+
+                        let camera = &game_state.camera;
+                        let push = camera.forward() * 5.0;
+                        for i in 0..puppets.puppets.len() {
+                            ui.horizontal(|ui| {
+                                let weight = format!("{:.2}", puppets.puppets[i].weight);
+                                ui.label(tr_args(
+                                    "editor-ragdolls-puppet",
+                                    &[("index", &i), ("weight", &weight)],
+                                ));
+                                if ui.button(tr("editor-ragdolls-knock-down")).clicked() {
+                                    puppets.knock_down(i, push);
+                                }
+                                if ui.button(tr("editor-ragdolls-get-up")).clicked() {
+                                    puppets.get_up(i);
+                                }
+                                let reach = &mut puppets.puppets[i].reach;
+                                if ui.button(tr("editor-ragdolls-reach")).clicked() {
+                                    *reach = Some(camera.eye + camera.forward());
+                                }
+                                if reach.is_some()
+                                    && ui.button(tr("editor-ragdolls-release")).clicked()
+                                {
+                                    *reach = None;
+                                }
+                                let player = &mut puppets.puppets[i].player;
+                                let name = puppets.clips.get(player.clip).map(|clip| &clip.name);
+                                egui::ComboBox::from_id_salt(("puppet clip", i))
+                                    .selected_text(
+                                        name.cloned().unwrap_or_else(|| tr("editor-none")),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        for (clip_id, clip) in puppets.clips.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut player.clip,
+                                                clip_id,
+                                                &clip.name,
+                                            );
+                                        }
+                                    });
+                            });
+                        }
+                        ui.label(tr_args(
+                            "editor-ragdolls-events",
+                            &[("count", &puppets.events.len())],
+                        ));
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("editor-spawn")).clicked() {
+                                puppets.spawn(
+                                    &mut renderer.entities.models,
+                                    camera.eye + camera.forward() * 4.0,
+                                );
+                            }
+                            if ui.button(tr("editor-clear")).clicked() {
+                                puppets.clear(&mut renderer.entities.models);
+                            }
+                        });
+                    });
+
+                    ui.collapsing(tr("editor-materials"), |ui| {
+                        let materials = &mut renderer.entities.materials;
+                        index_slider(
+                            ui,
+                            &mut self.mat_id,
+                            materials.len(),
+                            &tr("editor-material-id"),
+                        );
+                        if let Some(material) = materials.get(self.mat_id) {
+                            let mut material = *material;
+                            if material.inspect(ui) {
+                                materials.set(self.mat_id, material);
+                            }
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-sprite-sheets"), |ui| {
+                        let sprites = &mut renderer.entities.sprites;
+                        ui.checkbox(&mut sprites.paused, tr("editor-paused"));
+                        if sprites.animations.is_empty() {
+                            ui.label(tr("editor-sprites-none"));
+                        }
+                        for animation in &sprites.animations {
+                            ui.label(tr_args(
+                                "editor-sprites-animation",
+                                &[
+                                    ("texture", &animation.texture_id),
+                                    ("frame", &(animation.frame() + 1)),
+                                    ("frames", &animation.slots.len()),
+                                    ("fps", &animation.fps),
+                                    ("materials", &animation.materials.len()),
+                                ],
+                            ));
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-instances"), |ui| {
+                        let (unused, rebalances) =
+                            renderer.entities.models.instance_fragmentation();
+                        ui.label(tr_args(
+                            "editor-instances-fragmentation",
+                            &[
+                                ("unused", &format!("{:.0}", unused * 100.0)),
+                                ("rebalances", &rebalances),
+                            ],
+                        ));
+                        #[cfg(feature = "meshlets")]
+                        if let Some(meshlets) = &mut renderer.entities.meshlets {
+                            let count = meshlets.meshlet_count();
+                            ui.checkbox(
+                                &mut meshlets.enabled,
+                                tr_args("editor-instances-meshlets", &[("count", &count)]),
+                            );
+                        }
+                        self.new_instance.inspect(ui);
+                        let models = &renderer.entities.models;
+                        index_slider(
+                            ui,
                             &mut self.model_id,
-                            0..=renderer.entities.models.model_count() as u32 - 1,
+                            models.model_count(),
+                            &tr("editor-model-id"),
+                        );
+                        let mesh_count = match self.model_id < models.model_count() {
+                            true => models.mesh_count_of(self.model_id as u16),
+                            false => 0,
+                        };
+                        index_slider(ui, &mut self.mesh_id, mesh_count, &tr("editor-mesh-id"));
+                        #[cfg(feature = "terrain")]
+                        if ui.button(tr("editor-instances-drop")).clicked() {
+                            let [x, _, z] = self.new_instance.translation;
+                            self.new_instance.translation[1] =
+                                renderer.terrain.height.height_at(x, z);
+                        }
+                        if ui
+                            .add_enabled(
+                                self.mesh_id < mesh_count,
+                                egui::Button::new(tr("editor-push")),
+                            )
+                            .clicked()
+                        {
+                            let meta = EntityMeta::new();
+                            self.history.push(Snapshot::Instance {
+                                uuid: meta.uuid,
+                                placed: None,
+                            });
+                            self.scene_editor.place(
+                                &mut renderer.entities.models,
+                                SceneInstance {
+                                    model_id: self.model_id as u16,
+                                    mesh_id: self.mesh_id as u16,
+                                    instance: self.new_instance,
+                                    meta,
+                                    is_static: false,
+                                },
+                            );
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-blockout"), |ui| {
+                        self.csg_editor.ui(ui, &mut self.history)
+                    });
+
+                    ui.collapsing(tr("editor-vertex-paint"), |ui| {
+                        self.vertex_painter.ui(ui, renderer)
+                    });
+
+                    ui.collapsing(tr("editor-scene"), |ui| {
+                        let entities = &mut renderer.entities;
+                        self.scene_editor.ui(
+                            ui,
+                            &mut entities.models,
+                            &mut entities.lightmap,
+                            &mut game_state.random,
+                            &mut self.history,
                         )
-                        .text("Model ID"),
-                    );
-                    ui.add(
-                        Slider::new(
-                            &mut self.mesh_id,
-                            0..=renderer.entities.models.mesh_count_of(self.model_id as u16) as u32
-                                - 1,
+                    });
+
+                    ui.collapsing(tr("editor-sequencer"), |ui| {
+                        self.sequencer.ui(
+                            ui,
+                            &mut self.scene_editor.sequence,
+                            game_state,
+                            renderer,
+                            proj,
                         )
-                        .text("Mesh ID"),
-                    );
-                    if ui.button("Push").clicked() {
-                        renderer.entities.models.add_instance(
-                            self.model_id as u16,
-                            self.mesh_id as u16,
-                            ModelInstance {
-                                transform: Matrix4::new_translation(&self.new_inst_pos.coords)
-                                    .into(),
-                                material_id: self.mat_id,
-                            },
-                        );
-                    }
-                })
-            });
+                    });
+
+                    ui.collapsing(tr("editor-autosave"), |ui| {
+                        self.autosave
+                            .ui(ui, &self.scene_editor, config, game_state.random.seed())
+                    });
+
+                    ui.collapsing(tr("editor-scene-diff"), |ui| {
+                        let entities = &mut renderer.entities;
+                        self.scene_diff_editor.ui(
+                            ui,
+                            &mut self.scene_editor,
+                            &mut entities.models,
+                            &mut entities.lightmap,
+                            &mut game_state.random,
+                        )
+                    });
+
+                    ui.collapsing(tr("editor-lightmap"), |ui| {
+                        self.lightmap_editor.ui(
+                            ui,
+                            &self.scene_editor,
+                            &renderer.entities,
+                            &renderer.lights,
+                        )
+                    });
+
+                    ui.collapsing(tr("editor-measure"), |ui| self.measure.ui(ui));
+
+                    ui.collapsing(tr("editor-mouse"), |ui| {
+                        game_state.mouse.inspect(ui);
+                        if ui.button(tr("editor-save")).clicked() {
+                            game_state.mouse.write_config(config);
+                            config.save();
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-shortcuts"), |ui| self.shortcuts.ui(ui, config));
+
+                    ui.collapsing(tr("editor-profiler"), |ui| self.profiler_editor.ui(ui));
+
+                    ui.collapsing(tr("editor-stats"), |ui| {
+                        let stats = RenderStats::of(renderer).to_string();
+                        ui.label(stats.trim_end());
+                        if ui.button(tr("editor-copy")).clicked() {
+                            ui.ctx().copy_text(stats);
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-state-changes"), state_changes_ui);
+
+                    ui.collapsing(tr("editor-frame-pacing"), |ui| {
+                        let mut pacing = graphics.pacing();
+                        ui.checkbox(&mut pacing.vsync, tr("menu-vsync"));
+                        ui.checkbox(&mut pacing.low_latency, tr("menu-low-latency"));
+                        ui.add_enabled_ui(!pacing.low_latency, |ui| {
+                            ui.checkbox(
+                                &mut pacing.render_thread,
+                                tr("editor-pacing-render-thread"),
+                            );
+                            ui.add(
+                                Slider::new(&mut pacing.max_frame_latency, 1..=3)
+                                    .text(tr("editor-pacing-max-latency")),
+                            );
+                            ui.add(
+                                Slider::new(&mut pacing.frames_in_flight, 1..=3)
+                                    .text(tr("editor-pacing-frames-in-flight")),
+                            );
+                        });
+                        if pacing != graphics.pacing() {
+                            self.pacing_request = Some(pacing);
+                        }
+                        let waited = graphics.last_pacing_wait().as_secs_f64() * 1000.0;
+                        ui.label(tr_args(
+                            "editor-pacing-waited",
+                            &[("ms", &format!("{waited:.2}"))],
+                        ));
+                        ui.label(tr_args(
+                            "editor-pacing-retired",
+                            &[("count", &graphics.retired_len())],
+                        ));
+                    });
+
+                    ui.collapsing(tr("editor-capture"), |ui| {
+                        capture_ui(ui, &mut renderer.capture)
+                    });
+
+                    ui.collapsing(tr("editor-debug-palette"), |ui| {
+                        let palette = &mut renderer.debug_lines.palette;
+                        ui.horizontal_wrapped(|ui| {
+                            for choice in DebugPalette::ALL {
+                                ui.selectable_value(palette, choice, palette_name(choice));
+                            }
+                        });
+                        if ui.button(tr("editor-save")).clicked() {
+                            config.set(DISPLAY_SECTION, "debug_palette", palette.label());
+                            config.save();
+                        }
+                    });
+
+                    ui.collapsing(tr("editor-theme"), |ui| self.theme_editor.ui(ui, config));
+                });
         });
+        #[cfg(feature = "remote-editor")]
+        let output = match &mut self.remote {
+            Some(remote) => remote.send(output, &self.gui_ctx),
+            None => output,
+        };
 
         (output, self.gui_ctx.clone())
     }
+
+    /// Adds the gui and the gizmo to the claims, the cursor only reaches the gizmo in editor
+    /// mode and the gui outside of game mode, for the pause menu
+    pub fn claim_pointer(&self, claims: &mut PointerClaims, mode: Mode) {
+        claims.gizmo = mode == Mode::Editor && self.gizmo.hovered;
+        claims.ui = mode != Mode::Game
+            && (self.gui_ctx.wants_pointer_input() || self.gui_ctx.is_pointer_over_area());
+        // The gui is on another screen
+        #[cfg(feature = "remote-editor")]
+        if self.remote.as_ref().is_some_and(RemoteEditor::connected) {
+            claims.ui = false;
+        }
+    }
+
+    /// Editor driven camera moves and tools, run after the camera controls
+    pub fn update(
+        &mut self,
+        inputs: &Inputs,
+        pointer: &PointerFocus,
+        renderer: &mut GlobalRenderer,
+        game_state: &mut GameState,
+        proj: &Projection,
+        dt: Duration,
+    ) {
+        self.focus.update(&mut game_state.camera, dt);
+        self.lightmap_editor
+            .poll(&mut self.scene_editor, &mut renderer.entities);
+        self.navmesh_editor.poll(&mut game_state.agents);
+        self.autosave
+            .update(&self.scene_editor, game_state.random.seed());
+        self.volume_editor
+            .sync(&self.scene_editor.volumes, renderer);
+        self.scene_editor.update_statics(&mut renderer.entities);
+        self.scene_editor.update_peaks(renderer);
+        if pointer.is(PointerOwner::Scene) {
+            if let Some(gesture) = inputs.touch_gesture() {
+                self.touch_navigation(gesture, &mut game_state.camera);
+            }
+        }
+        self.measure.update(
+            inputs,
+            &mut renderer.picker,
+            pointer.is(PointerOwner::Scene),
+        );
+        #[cfg(feature = "terrain")]
+        self.terrain_editor.update(
+            inputs,
+            renderer,
+            &mut self.history,
+            proj,
+            pointer.is(PointerOwner::Scene),
+            dt.as_secs_f32(),
+        );
+        self.vertex_painter.update(
+            inputs,
+            &self.scene_editor,
+            renderer,
+            &mut self.history,
+            proj,
+            pointer.is(PointerOwner::Scene),
+            dt.as_secs_f32(),
+        );
+    }
+
+    /// One finger orbits around the pivot, two fingers zoom by pinching and turn around it
+    fn touch_navigation(&mut self, gesture: TouchGesture, camera: &mut Camera) {
+        let pivot = camera.eye + camera.forward() * ORBIT_DISTANCE;
+        if gesture.fingers == 1 {
+            let (dx, dy) = gesture.pan;
+            self.focus.orbit_by(
+                camera,
+                pivot,
+                -dx * TOUCH_ORBIT_SPEED,
+                -dy * TOUCH_ORBIT_SPEED,
+            );
+            return;
+        }
+        self.focus
+            .orbit_by(camera, pivot, gesture.rotation.to_degrees(), 0.0);
+        self.focus.dolly(camera, pivot, gesture.pinch);
+    }
+
+    /// Pacing picked from the frame pacing section, the surface cannot be configured while the
+    /// graphics context is shared with the render thread
+    pub fn take_pacing_request(&mut self) -> Option<FramePacing> {
+        self.pacing_request.take()
+    }
+
+    /// Assets root picked from the project section, the renderer cannot be rebuilt while the
+    /// gui runs
+    pub fn take_project_request(&mut self) -> Option<PathBuf> {
+        self.project_request.take()
+    }
+
+    /// Drops the state of the tools bound to the previous project and loads the default scene
+    /// of the new one, once the renderer is rebuilt from its assets
+    pub fn open_project(
+        &mut self,
+        config: &Config,
+        renderer: &mut GlobalRenderer,
+        game_state: &mut GameState,
+    ) {
+        self.theme_editor = ThemeEditor::from_config(config);
+        self.scene_editor = SceneEditor::default();
+        self.sequencer = Sequencer::default();
+        self.scene_diff_editor = SceneDiffEditor::default();
+        self.lightmap_editor = LightmapEditor::default();
+        self.camera_editor = CameraEditor::default();
+        self.csg_editor = CsgEditor::default();
+        self.vertex_painter = VertexPainter::default();
+        self.volume_editor = VolumeEditor::default();
+        #[cfg(feature = "terrain")]
+        {
+            self.terrain_editor = TerrainEditor::default();
+        }
+        self.history = EditHistory::default();
+        self.focus = CameraFocus::default();
+        self.measure = MeasureTool::default();
+        self.navmesh_editor = NavMeshEditor::default();
+        game_state.agents.navmesh = None;
+        self.new_instance = ModelInstance::new(Matrix4::identity(), 0);
+        self.mat_id = 0;
+        self.model_id = 0;
+        self.mesh_id = 0;
+        self.project_root = ASSETS.root().display().to_string();
+
+        // A project without a default scene starts empty
+        if let Ok(scene) = Scene::from_assets(constants::DEFAULT_SCENE) {
+            self.scene_editor.name = constants::DEFAULT_SCENE.to_string();
+            let entities = &mut renderer.entities;
+            self.scene_editor.replace(
+                scene,
+                &mut entities.models,
+                &mut entities.lightmap,
+                &mut game_state.random,
+            );
+        }
+    }
+
+    /// Moves the positions held by the tools back by `shift` along with the world origin
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        self.scene_editor.rebase(shift);
+        self.measure.rebase(shift);
+        self.focus.rebase(shift);
+    }
+
+    /// Runs the actions whose shortcut was pressed in editor mode, unless a text field has the
+    /// focus
+    pub fn handle_shortcuts(
+        &mut self,
+        inputs: &Inputs,
+        renderer: &mut GlobalRenderer,
+        game_state: &mut GameState,
+        proj: &Projection,
+    ) {
+        let actions = self.shortcuts.triggered(inputs);
+        if inputs.text_input() || game_state.mode != Mode::Editor {
+            return;
+        }
+        for action in actions {
+            self.run_action(action, renderer, game_state, proj);
+        }
+    }
+
+    pub fn run_action(
+        &mut self,
+        action: Action,
+        renderer: &mut GlobalRenderer,
+        game_state: &mut GameState,
+        proj: &Projection,
+    ) {
+        match action {
+            Action::SaveScene => self.scene_editor.save(game_state.random.seed()),
+            Action::Duplicate => {
+                let models = &mut renderer.entities.models;
+                if let Some(uuid) = self.scene_editor.duplicate_selection(models) {
+                    self.history.push(Snapshot::Instance { uuid, placed: None });
+                }
+            }
+            Action::FocusSelection => {
+                if let Some(bounds) = self
+                    .scene_editor
+                    .selection_bounds(&renderer.entities.models)
+                {
+                    self.focus.start(&game_state.camera, bounds, proj.fov_deg);
+                }
+            }
+            Action::ToggleWireframe => renderer.settings.wireframe = !renderer.settings.wireframe,
+            Action::Undo => self.history.undo(EditTarget {
+                renderer,
+                scene_editor: &mut self.scene_editor,
+                csg_editor: &mut self.csg_editor,
+            }),
+            Action::Redo => self.history.redo(EditTarget {
+                renderer,
+                scene_editor: &mut self.scene_editor,
+                csg_editor: &mut self.csg_editor,
+            }),
+            Action::ToggleCapture => renderer.capture.toggle(),
+        }
+    }
 }
 
-fn point_slider(ui: &mut egui::Ui, value: &mut Point3<f32>, range: RangeInclusive<f32>) {
-    ui.add(
-        Slider::new(&mut value.coords[0], range.clone())
-            .text("X")
-            .text_color(Color32::RED),
-    );
-    ui.add(
-        Slider::new(&mut value.coords[1], range.clone())
-            .text("Y")
-            .text_color(Color32::GREEN),
-    );
-    ui.add(
-        Slider::new(&mut value.coords[2], range)
-            .text("Z")
-            .text_color(Color32::CYAN),
-    );
+/// Switches recorded by each instrumented system in the last frame, with their total
+fn state_changes_ui(ui: &mut egui::Ui) {
+    let mut enabled = churn::is_enabled();
+    if ui
+        .checkbox(&mut enabled, tr("editor-state-changes-count"))
+        .changed()
+    {
+        churn::set_enabled(enabled);
+    }
+    if !enabled {
+        return;
+    }
+
+    let counts = churn::last_frame();
+    let mut total = StateChanges::default();
+    for changes in counts.values() {
+        total += *changes;
+    }
+    let total_name = tr("editor-state-changes-total");
+    let total_name = total_name.as_str();
+    egui::Grid::new("State changes")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            for (name, _) in total.fields() {
+                ui.label(name);
+            }
+            ui.end_row();
+            for (system, changes) in counts.iter().chain([(&total_name, &total)]) {
+                ui.label(*system);
+                for (_, count) in changes.fields() {
+                    ui.label(count.to_string());
+                }
+                ui.end_row();
+            }
+        });
 }
 
-fn vec3_slider(ui: &mut egui::Ui, value: &mut Vector3<f32>) {
-    ui.add(
-        Slider::new(&mut value.data.0[0][0], -1.0..=1.0)
-            .text("X")
-            .text_color(Color32::RED),
-    );
-    ui.add(
-        Slider::new(&mut value.data.0[0][1], -1.0..=1.0)
-            .text("Y")
-            .text_color(Color32::GREEN),
-    );
-    ui.add(
-        Slider::new(&mut value.data.0[0][2], -1.0..=1.0)
-            .text("Z")
-            .text_color(Color32::CYAN),
-    );
+fn capture_ui(ui: &mut egui::Ui, capture: &mut FrameCapture) {
+    let recording = capture.is_recording();
+    ui.add_enabled_ui(!recording, |ui| {
+        let settings = &mut capture.settings;
+        ui.horizontal(|ui| {
+            for format in CaptureFormat::ALL {
+                ui.selectable_value(&mut settings.format, format, format.label());
+            }
+        });
+        ui.add(Slider::new(&mut settings.fps, 5..=60).text(tr("editor-capture-fps")));
+        ui.add(Slider::new(&mut settings.downscale, 1..=4).text(tr("editor-capture-downscale")));
+        ui.add(
+            Slider::new(&mut settings.max_seconds, 1.0..=300.0)
+                .text(tr("editor-capture-max-seconds")),
+        );
+    });
+    let label = match recording {
+        true => tr("editor-stop"),
+        false => tr("editor-capture-record"),
+    };
+    if ui.button(label).clicked() {
+        capture.toggle();
+    }
+    if let Some((frames, dropped, elapsed)) = capture.progress() {
+        let seconds = format!("{:.1}", elapsed.as_secs_f32());
+        ui.label(tr_args(
+            "editor-capture-progress",
+            &[
+                ("seconds", &seconds),
+                ("frames", &frames),
+                ("dropped", &dropped),
+            ],
+        ));
+    }
+    if capture.finishing() > 0 {
+        ui.label(tr_args(
+            "editor-capture-encoding",
+            &[("count", &capture.finishing())],
+        ));
+    }
+    if let Some(error) = capture.last_error() {
+        ui.colored_label(Color32::RED, error);
+    }
+    ui.label(tr_args(
+        "editor-capture-folder",
+        &[("folder", &CAPTURE_PATH)],
+    ));
 }
 
-fn angle_slider(ui: &mut egui::Ui, (yaw, pitch, roll): (&mut f32, &mut f32, &mut f32)) {
-    ui.add(
-        Slider::new(yaw, -90.0..=90.0)
-            .text("Yaw")
-            .text_color(Color32::RED),
-    );
-    ui.add(
-        Slider::new(pitch, -90.0..=90.0)
-            .text("Pitch")
-            .text_color(Color32::GREEN),
+/// Agents on a ring, each heading through the center to the opposite side. They take a detour
+/// without navmesh, the ones it does not connect to the opposite side are left out
+fn spawn_test_agents(agents: &mut Agents, entities: &mut EntitiesRenderer, count: usize) {
+    let body = Body::new(0, 0);
+    let offset = agents.agents.len();
+    for i in 0..count {
+        let angle = (offset + i) as f32 * 2.399963; // Golden angle
+        let radius = 10.0 + (i % 7) as f32 * 2.0;
+        let start = Point3::new(angle.cos() * radius, 0.0, angle.sin() * radius);
+        let end = Point3::new(-start.x, 0.0, -start.z);
+        let path = match agents.navmesh {
+            Some(_) => agents.path(start, end),
+            None => Some(vec![Point3::new(start.z * 0.25, 0.0, -start.x * 0.25), end]),
+        };
+        if let Some(path) = path {
+            agents.spawn(entities, body, start, path);
+        }
+    }
+}
+
+/// Top down view of the cells around the camera, colored by their loading state
+fn streaming_rings(ui: &mut egui::Ui, world: &WorldStreamer) {
+    let extent = (world.load_radius + world.unload_margin) as i32 + 1;
+    let cells = (extent * 2 + 1) as f32;
+    let (response, painter) = ui.allocate_painter(egui::Vec2::splat(160.0), egui::Sense::hover());
+    let rect = response.rect;
+    let cell_size = rect.width() / cells;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+
+    let center = world.center();
+    for (coord, state) in world.cells() {
+        let (x, z) = (coord.0 - center.0 + extent, coord.1 - center.1 + extent);
+        if x < 0 || z < 0 || x as f32 >= cells || z as f32 >= cells {
+            continue;
+        }
+        let min = rect.min + egui::vec2(x as f32, z as f32) * cell_size;
+        let color = match state {
+            CellState::Loading => Color32::YELLOW,
+            CellState::Loaded => Color32::DARK_GREEN,
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_size(min, egui::Vec2::splat(cell_size)).shrink(1.0),
+            0.0,
+            color,
+        );
+    }
+
+    let load_ring = egui::Rect::from_center_size(
+        rect.center(),
+        egui::Vec2::splat(cell_size * (world.load_radius * 2 + 1) as f32),
     );
-    ui.add(
-        Slider::new(roll, -90.0..=90.0)
-            .text("Roll")
-            .text_color(Color32::CYAN),
+    painter.rect_stroke(
+        load_ring,
+        0.0,
+        egui::Stroke::new(1.0, Color32::WHITE),
+        egui::StrokeKind::Outside,
     );
+    painter.circle_filled(rect.center(), 2.0, Color32::RED);
+}
+
+/// Rectangles allocated in the texture atlas, the free space stays dark
+fn atlas_map(ui: &mut egui::Ui, textures: &TextureStreamer) {
+    let (width, height) = textures.atlas_size();
+    let side = 200.0;
+    let size = egui::vec2(side, side * height as f32 / width.max(1) as f32);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    let scale = egui::vec2(rect.width() / width as f32, rect.height() / height as f32);
+    painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+
+    let mut used = 0;
+    let mut hovered = None;
+    for (id, level, [x0, y0, x1, y1]) in textures.allocations() {
+        used += (x1 - x0) as u64 * (y1 - y0) as u64;
+        let min = rect.min + egui::vec2(x0 as f32, y0 as f32) * scale;
+        let max = rect.min + egui::vec2(x1 as f32, y1 as f32) * scale;
+        let texture_rect = egui::Rect::from_min_max(min, max);
+        let brightness = 255 / (level + 1).min(4) as u8;
+        painter.rect_filled(
+            texture_rect,
+            0.0,
+            Color32::from_rgb(0, brightness, brightness / 2),
+        );
+        painter.rect_stroke(
+            texture_rect,
+            0.0,
+            egui::Stroke::new(1.0, Color32::BLACK),
+            egui::StrokeKind::Inside,
+        );
+        if response
+            .hover_pos()
+            .is_some_and(|pos| texture_rect.contains(pos))
+        {
+            hovered = Some((id, level, x1 - x0, y1 - y0));
+        }
+    }
+    let total = width as u64 * height as u64;
+    let allocated = used as f32 / total.max(1) as f32 * 100.0;
+    ui.label(tr_args(
+        "editor-occupancy-allocated",
+        &[
+            ("percent", &format!("{allocated:.0}")),
+            ("width", &width),
+            ("height", &height),
+        ],
+    ));
+    if let Some((id, level, width, height)) = hovered {
+        response.on_hover_text(tr_args(
+            "editor-occupancy-texture",
+            &[
+                ("id", &id),
+                ("level", &level),
+                ("width", &width),
+                ("height", &height),
+            ],
+        ));
+    }
+}
+
+/// One bar per column, filled by its values over its slots
+fn column_bars(ui: &mut egui::Ui, columns: &[(usize, usize)]) {
+    let largest = columns
+        .iter()
+        .map(|(_, slots)| *slots)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let (response, painter) = ui.allocate_painter(egui::vec2(200.0, 80.0), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+    let bar_width = rect.width() / columns.len().max(1) as f32;
+
+    let mut hovered = None;
+    for (i, (values, slots)) in columns.iter().enumerate() {
+        let x = rect.min.x + i as f32 * bar_width;
+        let height = |count: usize| rect.height() * count as f32 / largest as f32;
+        let bar = |count| {
+            egui::Rect::from_min_max(
+                egui::pos2(x, rect.max.y - height(count)),
+                egui::pos2(x + bar_width, rect.max.y),
+            )
+            .shrink2(egui::vec2(bar_width.min(2.0) * 0.25, 0.0))
+        };
+        painter.rect_filled(bar(*slots), 0.0, Color32::from_gray(70));
+        painter.rect_filled(bar(*values), 0.0, Color32::DARK_GREEN);
+        let column = egui::Rect::from_x_y_ranges(x..=x + bar_width, rect.y_range());
+        if response.hover_pos().is_some_and(|pos| column.contains(pos)) {
+            hovered = Some((i, values, slots));
+        }
+    }
+    let (values, slots) = columns
+        .iter()
+        .fold((0, 0), |(values, slots), (v, s)| (values + v, slots + s));
+    ui.label(tr_args(
+        "editor-occupancy-slots",
+        &[("values", &values), ("slots", &slots)],
+    ));
+    if let Some((i, values, slots)) = hovered {
+        response.on_hover_text(tr_args(
+            "editor-occupancy-column",
+            &[("index", &i), ("values", values), ("slots", slots)],
+        ));
+    }
+}
+
+/// Rain building up and clearing, then a snowfall
+fn demo_weather_script() -> Vec<WeatherStep> {
+    let step = |kind, intensity, seconds| WeatherStep {
+        kind,
+        intensity,
+        duration: Duration::from_secs(seconds),
+    };
+    vec![
+        step(WeatherKind::Rain, 0.3, 10),
+        step(WeatherKind::Rain, 1.0, 20),
+        step(WeatherKind::Clear, 0.0, 15),
+        step(WeatherKind::Snow, 0.8, 25),
+        step(WeatherKind::Clear, 0.0, 15),
+    ]
 }
@@ -0,0 +1,100 @@
+use nalgebra::Point3;
+
+use crate::{
+    game::{
+        agents::Agents,
+        ground::Ground,
+        navmesh::{GroundSamples, NavMesh, NavMeshSettings},
+    },
+    graphics::debug_lines::DebugLines,
+    jobs::{self, Job, Priority},
+    locale::{tr, tr_args},
+};
+
+/// Builds the navmesh of the agents around the camera as a background job
+#[derive(Default)]
+pub struct NavMeshEditor {
+    pub settings: NavMeshSettings,
+    /// Crosses on the walkable cells
+    pub show: bool,
+    pending: Option<Job<NavMesh>>,
+}
+
+impl NavMeshEditor {
+    /// Hands the finished build to the agents, every frame
+    pub fn poll(&mut self, agents: &mut Agents) {
+        let Some(job) = &mut self.pending else {
+            return;
+        };
+        if job.lost() {
+            self.pending = None;
+            return;
+        }
+        if let Some(navmesh) = job.poll() {
+            agents.navmesh = Some(navmesh);
+            self.pending = None;
+        }
+    }
+
+    pub fn draw(&self, agents: &Agents, lines: &mut DebugLines) {
+        if let Some(navmesh) = agents.navmesh.as_ref().filter(|_| self.show) {
+            let color = lines.palette.neutral();
+            navmesh.draw(lines, color);
+        }
+    }
+
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        agents: &mut Agents,
+        ground: &dyn Ground,
+        center: Point3<f32>,
+    ) {
+        ui.add(
+            egui::Slider::new(&mut self.settings.extent, 5.0..=200.0)
+                .text(tr("editor-navmesh-extent")),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.settings.cell_size, 0.25..=4.0)
+                .text(tr("editor-navmesh-cell-size")),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.settings.max_slope_deg, 0.0..=89.0)
+                .text(tr("editor-navmesh-max-slope")),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.settings.max_step, 0.0..=2.0)
+                .text(tr("editor-navmesh-max-step")),
+        );
+        ui.checkbox(&mut self.show, tr("editor-navmesh-show"));
+        match &agents.navmesh {
+            Some(navmesh) => ui.label(tr_args(
+                "editor-navmesh-cells",
+                &[("count", &navmesh.walkable_count())],
+            )),
+            None => ui.label(tr("editor-navmesh-none")),
+        };
+        ui.horizontal(|ui| {
+            let building = self.pending.is_some();
+            let label = match building {
+                true => tr("editor-navmesh-building"),
+                false => tr("editor-navmesh-build"),
+            };
+            if ui
+                .add_enabled(!building, egui::Button::new(label))
+                .clicked()
+            {
+                // Sampled here as the ground is borrowed, the cells are classified on a worker
+                let samples = GroundSamples::new(ground, center, &self.settings);
+                let settings = self.settings.clone();
+                self.pending = Some(jobs::spawn("Navmesh build", Priority::Normal, move || {
+                    NavMesh::build(samples, &settings)
+                }));
+            }
+            if ui.button(tr("editor-clear")).clicked() {
+                agents.navmesh = None;
+                self.pending = None;
+            }
+        });
+    }
+}
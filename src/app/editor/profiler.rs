@@ -0,0 +1,295 @@
+use std::{collections::VecDeque, time::Duration};
+
+use egui::{Color32, Rect, Sense, Stroke, Vec2};
+
+use crate::{
+    jobs::{self, Priority},
+    locale::{tr, tr_args},
+    profiler::{self, FrameRecord, ScopeRecord},
+};
+
+/// Frame times of the last frames and the scopes of the selected one
+#[derive(Default)]
+pub struct ProfilerEditor {
+    enabled: bool,
+    paused: bool,
+    /// Frames kept while paused, the live history keeps moving
+    frozen: VecDeque<FrameRecord>,
+    /// Index in the displayed frames, the last one when unset
+    selected: Option<usize>,
+}
+
+impl ProfilerEditor {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .checkbox(&mut self.enabled, tr("editor-enabled"))
+                .changed()
+            {
+                profiler::set_enabled(self.enabled);
+            }
+            if ui.checkbox(&mut self.paused, tr("editor-paused")).changed() {
+                self.frozen = match self.paused {
+                    true => profiler::with_history(VecDeque::clone),
+                    false => VecDeque::new(),
+                };
+                self.selected = None;
+            }
+            if ui.button(tr("editor-clear")).clicked() {
+                profiler::clear();
+                self.frozen.clear();
+                self.selected = None;
+            }
+        });
+
+        profiler::with_memory(|memory| {
+            if memory.is_empty() {
+                return;
+            }
+            ui.collapsing(tr("editor-profiler-memory"), |ui| {
+                egui::Grid::new("Profiler memory")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, record) in memory {
+                            ui.label(*name);
+                            let kib = record.bytes as f32 / 1024.0;
+                            ui.label(tr_args(
+                                "editor-profiler-size",
+                                &[("kib", &format!("{kib:.1}"))],
+                            ));
+                            if record.uncompressed != record.bytes {
+                                let kib = record.uncompressed as f32 / 1024.0;
+                                ui.label(tr_args(
+                                    "editor-profiler-uncompressed",
+                                    &[("kib", &format!("{kib:.1}"))],
+                                ));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+        ui.collapsing(tr("editor-profiler-jobs"), jobs_ui);
+
+        if self.paused {
+            let frames = std::mem::take(&mut self.frozen);
+            self.frames_ui(ui, &frames);
+            self.frozen = frames;
+        } else {
+            profiler::with_history(|frames| self.frames_ui(ui, frames));
+        }
+    }
+
+    fn frames_ui(&mut self, ui: &mut egui::Ui, frames: &VecDeque<FrameRecord>) {
+        let Some(last) = frames.len().checked_sub(1) else {
+            ui.label(tr("editor-profiler-no-frames"));
+            return;
+        };
+
+        if let Some(selected) = frame_times(ui, frames, self.selected) {
+            self.selected = Some(selected);
+            self.paused = true;
+            self.frozen = frames.clone();
+        }
+
+        let index = self.selected.unwrap_or(last).min(last);
+        let frame = &frames[index];
+        let duration = format!("{:.2}", ms(frame.duration));
+        ui.label(tr_args(
+            "editor-profiler-frame",
+            &[("index", &index), ("ms", &duration)],
+        ));
+
+        timeline(ui, frame);
+
+        let mut scopes = frame.scopes.iter().collect::<Vec<_>>();
+        scopes.sort_by_key(|scope| (thread_order(frame, scope), scope.start));
+        egui::Grid::new("Profiler scopes")
+            .striped(true)
+            .show(ui, |ui| {
+                for scope in scopes {
+                    ui.label(format!(
+                        "{}{}",
+                        "  ".repeat(scope.depth as usize),
+                        scope.name
+                    ));
+                    ui.label(format!("{:.3} ms", ms(scope.duration)));
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+/// Queues and workers of the job system, with the last finished jobs
+fn jobs_ui(ui: &mut egui::Ui) {
+    let stats = jobs::stats();
+    let queued = Priority::ALL
+        .iter()
+        .zip(stats.queued)
+        .map(|(priority, count)| format!("{count} {}", priority.label().to_lowercase()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    ui.label(tr_args(
+        "editor-profiler-queued",
+        &[("queued", &queued), ("completions", &stats.completions)],
+    ));
+
+    for job in &stats.running {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(tr_args(
+                "editor-profiler-running",
+                &[
+                    ("name", &job.name),
+                    ("priority", &job.priority.label()),
+                    ("ms", &format!("{:.1}", ms(job.started.elapsed()))),
+                ],
+            ));
+        });
+    }
+
+    egui::Grid::new("Profiler jobs")
+        .striped(true)
+        .show(ui, |ui| {
+            for job in stats.history.iter().rev() {
+                ui.label(job.name);
+                ui.label(job.priority.label());
+                let waited = format!("{:.2}", ms(job.waited));
+                ui.label(tr_args("editor-profiler-waited", &[("ms", &waited)]));
+                match job.panicked {
+                    true => ui.colored_label(
+                        Color32::from_rgb(220, 80, 60),
+                        tr("editor-profiler-panicked"),
+                    ),
+                    false => {
+                        let ran = format!("{:.2}", ms(job.duration));
+                        ui.label(tr_args("editor-profiler-ran", &[("ms", &ran)]))
+                    }
+                };
+                ui.end_row();
+            }
+        });
+}
+
+/// Bar per frame, returns the clicked frame
+fn frame_times(
+    ui: &mut egui::Ui,
+    frames: &VecDeque<FrameRecord>,
+    selected: Option<usize>,
+) -> Option<usize> {
+    // Bars scale to 33ms, slower frames are clipped
+    let scale = 1.0 / 33.3;
+    let size = Vec2::new(ui.available_width(), 60.0);
+    let (response, painter) = ui.allocate_painter(size, Sense::click());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+    let width = rect.width() / profiler::HISTORY_LEN as f32;
+    let target = rect.bottom() - rect.height() * 16.7 * scale;
+    painter.hline(rect.x_range(), target, Stroke::new(1.0, Color32::DARK_GRAY));
+
+    let selected = selected.unwrap_or(frames.len() - 1);
+    for (i, frame) in frames.iter().enumerate() {
+        let time = ms(frame.duration);
+        let height = (time * scale).min(1.0) * rect.height();
+        let x = rect.left() + i as f32 * width;
+        let color = match (i == selected, time > 16.7) {
+            (true, _) => Color32::WHITE,
+            (false, true) => Color32::from_rgb(220, 80, 60),
+            (false, false) => Color32::from_rgb(80, 180, 90),
+        };
+        painter.rect_filled(
+            Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - height),
+                egui::pos2(x + (width - 1.0).max(1.0), rect.bottom()),
+            ),
+            0.0,
+            color,
+        );
+    }
+
+    let position = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())?;
+    let i = ((position.x - rect.left()) / width) as usize;
+    (i < frames.len()).then_some(i)
+}
+
+/// Scopes of the frame laid out over time, one row per nesting level and thread
+fn timeline(ui: &mut egui::Ui, frame: &FrameRecord) {
+    let row_height = 14.0;
+    let rows = frame
+        .scopes
+        .iter()
+        .map(|scope| row_of(frame, scope))
+        .max()
+        .map_or(1, |row| row + 1);
+    let size = Vec2::new(ui.available_width(), rows as f32 * row_height);
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+    let duration = frame.duration.max(Duration::from_micros(1)).as_secs_f32();
+    let mut hovered = None;
+    for scope in &frame.scopes {
+        let left = rect.left() + scope.start.as_secs_f32() / duration * rect.width();
+        let right = left + (scope.duration.as_secs_f32() / duration * rect.width()).max(1.0);
+        let top = rect.top() + row_of(frame, scope) as f32 * row_height;
+        let bar = Rect::from_min_max(
+            egui::pos2(left, top),
+            egui::pos2(right.min(rect.right()), top + row_height - 1.0),
+        );
+        painter.rect_filled(bar, 2.0, scope_color(scope.name));
+        if bar.width() > 40.0 {
+            painter.text(
+                bar.left_center() + Vec2::new(2.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                scope.name,
+                egui::FontId::proportional(10.0),
+                Color32::BLACK,
+            );
+        }
+        if response.hover_pos().is_some_and(|pos| bar.contains(pos)) {
+            hovered = Some(scope);
+        }
+    }
+
+    if let Some(scope) = hovered {
+        response.on_hover_text(format!("{}: {:.3} ms", scope.name, ms(scope.duration)));
+    }
+}
+
+fn row_of(frame: &FrameRecord, scope: &ScopeRecord) -> usize {
+    let depths = frame
+        .scopes
+        .iter()
+        .map(|scope| scope.depth)
+        .max()
+        .unwrap_or(0) as usize
+        + 1;
+    thread_order(frame, scope) * depths + scope.depth as usize
+}
+
+/// 0 for the thread of the first scope of the frame, the main thread, 1 for the others
+fn thread_order(frame: &FrameRecord, scope: &ScopeRecord) -> usize {
+    frame
+        .scopes
+        .iter()
+        .filter(|scope| scope.depth == 0)
+        .min_by_key(|scope| scope.start)
+        .map_or(0, |first| (first.thread != scope.thread) as usize)
+}
+
+/// Stable color per scope name
+fn scope_color(name: &str) -> Color32 {
+    let hash = name.bytes().fold(2166136261u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(16777619)
+    });
+    let [r, g, b, _] = hash.to_le_bytes();
+    Color32::from_rgb(r / 2 + 110, g / 2 + 110, b / 2 + 110)
+}
+
+fn ms(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
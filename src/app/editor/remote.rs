@@ -0,0 +1,729 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use egui::{
+    epaint::{ClippedPrimitive, ImageDelta, Mesh, Primitive, Vertex},
+    Color32, ColorImage, Event, ImageData, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2,
+    Rect, TextureFilter, TextureId, TextureOptions, TexturesDelta, Vec2, ViewportId,
+};
+use tungstenite::{Message, WebSocket};
+
+use crate::app::config::Config;
+
+const CONFIG_SECTION: &str = "remote_editor";
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9300";
+/// Slept by the connection thread when nothing was exchanged
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// Editor gui shown by the `editor-remote` viewer instead of over the game view, enabled by
+/// `address` in the `[remote_editor]` config section
+///
+/// The viewer sends its window inputs, the editor runs with them on the game side and sends back
+/// the tessellated meshes with the texture changes. The gui is drawn locally again while no
+/// viewer is connected. Textures registered by the renderer, like the minimap, are not sent
+pub struct RemoteEditor {
+    connection: RemoteConnection,
+    /// Last one connected to, a new viewer gets every texture again
+    session: u64,
+    /// Viewport of the viewer, size in points and pixels per point
+    viewport: Option<(Vec2, f32)>,
+    /// Copies of the egui textures, the viewer applies the deltas to its own
+    textures: HashMap<u64, (ColorImage, TextureOptions)>,
+}
+
+impl RemoteEditor {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let address = config.get(CONFIG_SECTION, "address")?;
+        match RemoteConnection::listen(address) {
+            Ok(connection) => {
+                println!("Remote editor listening on {address}");
+                Some(Self {
+                    connection,
+                    session: 0,
+                    viewport: None,
+                    textures: HashMap::new(),
+                })
+            }
+            Err(e) => {
+                eprintln!("Failed to start the remote editor on {address}: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connection.session() != 0
+    }
+
+    /// Replaces the local window inputs by the ones of the viewer while one is connected
+    pub fn input(&mut self, mut local: egui::RawInput) -> egui::RawInput {
+        if !self.connected() {
+            return local;
+        }
+        local.events.clear();
+        while let Ok(input) = self.connection.inputs.try_recv() {
+            self.viewport = Some((input.screen_size, input.pixels_per_point));
+            local.modifiers = input.modifiers;
+            local.events.extend(input.events);
+        }
+        if let Some((size, pixels_per_point)) = self.viewport {
+            local.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, size));
+            if let Some(viewport) = local.viewports.get_mut(&ViewportId::ROOT) {
+                viewport.native_pixels_per_point = Some(pixels_per_point);
+            }
+        }
+        local.focused = true;
+        local
+    }
+
+    /// Sends the gui to the viewer, returns what is left to draw locally
+    pub fn send(&mut self, output: egui::FullOutput, ctx: &egui::Context) -> egui::FullOutput {
+        let session = self.connection.session();
+        if session == 0 {
+            return output;
+        }
+        self.apply_textures(&output.textures_delta);
+        // The local renderer stays in sync for when the viewer leaves
+        let local_textures = output.textures_delta.clone();
+        let textures = match session != self.session {
+            true => {
+                self.session = session;
+                self.full_textures()
+            }
+            false => managed_textures(output.textures_delta),
+        };
+        let frame = RemoteFrame {
+            pixels_per_point: output.pixels_per_point,
+            textures,
+            primitives: ctx.tessellate(output.shapes, output.pixels_per_point),
+        };
+        let _ = self.connection.frames.send(frame.encode());
+        egui::FullOutput {
+            textures_delta: local_textures,
+            pixels_per_point: output.pixels_per_point,
+            ..Default::default()
+        }
+    }
+
+    fn apply_textures(&mut self, delta: &TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let TextureId::Managed(id) = id else {
+                continue;
+            };
+            let image = color_image(&image_delta.image);
+            match image_delta.pos {
+                None => {
+                    self.textures.insert(*id, (image, image_delta.options));
+                }
+                Some([x, y]) => {
+                    let Some((texture, _)) = self.textures.get_mut(id) else {
+                        continue;
+                    };
+                    if image.size[0] == 0 {
+                        continue;
+                    }
+                    let width = texture.size[0];
+                    for (row, pixels) in image.pixels.chunks(image.size[0]).enumerate() {
+                        let start = (y + row) * width + x;
+                        texture.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+                    }
+                }
+            }
+        }
+        for id in &delta.free {
+            if let TextureId::Managed(id) = id {
+                self.textures.remove(id);
+            }
+        }
+    }
+
+    fn full_textures(&self) -> TexturesDelta {
+        TexturesDelta {
+            set: self
+                .textures
+                .iter()
+                .map(|(id, (image, options))| {
+                    let delta = ImageDelta::full(image.clone(), *options);
+                    (TextureId::Managed(*id), delta)
+                })
+                .collect(),
+            free: Vec::new(),
+        }
+    }
+}
+
+/// Only the textures egui manages exist on the viewer
+fn managed_textures(delta: TexturesDelta) -> TexturesDelta {
+    let managed = |id: &TextureId| matches!(id, TextureId::Managed(_));
+    TexturesDelta {
+        set: delta
+            .set
+            .into_iter()
+            .filter(|(id, _)| managed(id))
+            .collect(),
+        free: delta.free.into_iter().filter(managed).collect(),
+    }
+}
+
+fn color_image(image: &ImageData) -> ColorImage {
+    match image {
+        ImageData::Color(image) => (**image).clone(),
+        ImageData::Font(font) => {
+            let mut image = ColorImage::new(font.size, Color32::TRANSPARENT);
+            image.pixels = font.srgba_pixels(None).collect();
+            image
+        }
+    }
+}
+
+/// Websocket of the viewer, served by a thread of its own
+struct RemoteConnection {
+    inputs: Receiver<RemoteInput>,
+    frames: Sender<Vec<u8>>,
+    /// Incremented for each viewer, 0 while none is connected
+    session: Arc<AtomicU64>,
+}
+
+impl RemoteConnection {
+    fn listen(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| e.to_string())?;
+        let (input_sender, inputs) = channel();
+        let (frames, frame_receiver) = channel();
+        let session = Arc::new(AtomicU64::new(0));
+        let thread_session = session.clone();
+        std::thread::Builder::new()
+            .name("Remote editor".to_string())
+            .spawn(move || serve(listener, input_sender, frame_receiver, thread_session))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            inputs,
+            frames,
+            session,
+        })
+    }
+
+    fn session(&self) -> u64 {
+        self.session.load(Ordering::Acquire)
+    }
+}
+
+/// One viewer at a time, the next one is accepted once it disconnects
+fn serve(
+    listener: TcpListener,
+    inputs: Sender<RemoteInput>,
+    frames: Receiver<Vec<u8>>,
+    session: Arc<AtomicU64>,
+) {
+    let mut sessions = 0;
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let mut socket = match tungstenite::accept(stream) {
+            Ok(socket) => socket,
+            Err(e) => {
+                eprintln!("Remote editor handshake failed: {e}");
+                continue;
+            }
+        };
+        if let Err(e) = socket.get_ref().set_nonblocking(true) {
+            eprintln!("Remote editor connection failed: {e}");
+            continue;
+        }
+        // Left by the previous viewer
+        while frames.try_recv().is_ok() {}
+        sessions += 1;
+        session.store(sessions, Ordering::Release);
+        println!("Remote editor connected");
+        let result = exchange(&mut socket, &inputs, &frames);
+        session.store(0, Ordering::Release);
+        match result {
+            Ok(true) => println!("Remote editor disconnected"),
+            Ok(false) => return,
+            Err(e) => eprintln!("Remote editor disconnected: {e}"),
+        }
+    }
+}
+
+/// Returns false once the editor is gone
+fn exchange(
+    socket: &mut WebSocket<TcpStream>,
+    inputs: &Sender<RemoteInput>,
+    frames: &Receiver<Vec<u8>>,
+) -> Result<bool, String> {
+    let would_block = |e: &tungstenite::Error| matches!(e, tungstenite::Error::Io(e) if e.kind() == ErrorKind::WouldBlock);
+    loop {
+        let mut idle = true;
+        match socket.read() {
+            Ok(Message::Binary(data)) => {
+                idle = false;
+                match RemoteInput::decode(&data) {
+                    Ok(input) => {
+                        if inputs.send(input).is_err() {
+                            return Ok(false);
+                        }
+                    }
+                    Err(e) => eprintln!("Invalid remote editor input: {e}"),
+                }
+            }
+            Ok(Message::Close(_)) => return Ok(true),
+            Ok(_) => {}
+            Err(e) if would_block(&e) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        match frames.try_recv() {
+            Ok(frame) => {
+                idle = false;
+                match socket.send(Message::binary(frame)) {
+                    Err(e) if !would_block(&e) => return Err(e.to_string()),
+                    _ => {}
+                }
+            }
+            Err(TryRecvError::Empty) => match socket.flush() {
+                Err(e) if !would_block(&e) => return Err(e.to_string()),
+                _ => {}
+            },
+            Err(TryRecvError::Disconnected) => return Ok(false),
+        }
+        if idle {
+            std::thread::sleep(IDLE_WAIT);
+        }
+    }
+}
+
+/// Window inputs of the viewer for one of its frames
+pub struct RemoteInput {
+    /// In points
+    pub screen_size: Vec2,
+    pub pixels_per_point: f32,
+    pub modifiers: Modifiers,
+    /// Pointer, keyboard, text and clipboard events, the others are not sent
+    pub events: Vec<Event>,
+}
+
+impl RemoteInput {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.vec2(self.screen_size);
+        w.f32(self.pixels_per_point);
+        w.modifiers(self.modifiers);
+        let events: Vec<_> = self
+            .events
+            .iter()
+            .filter(|e| event_tag(e).is_some())
+            .collect();
+        w.u32(events.len() as u32);
+        for event in events {
+            w.event(event);
+        }
+        w.0
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut r = Reader(data);
+        let screen_size = r.vec2()?;
+        let pixels_per_point = r.f32()?;
+        let modifiers = r.modifiers()?;
+        let events = (0..r.u32()?).map(|_| r.event()).collect::<Result<_, _>>()?;
+        Ok(Self {
+            screen_size,
+            pixels_per_point,
+            modifiers,
+            events,
+        })
+    }
+}
+
+/// Gui of one editor frame, drawn as is by the viewer
+pub struct RemoteFrame {
+    pub pixels_per_point: f32,
+    pub textures: TexturesDelta,
+    /// Meshes of the egui textures, the paint callbacks are not sent
+    pub primitives: Vec<ClippedPrimitive>,
+}
+
+impl RemoteFrame {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.f32(self.pixels_per_point);
+        w.u32(self.textures.set.len() as u32);
+        for (id, delta) in &self.textures.set {
+            w.texture_id(*id);
+            w.image_delta(delta);
+        }
+        w.u32(self.textures.free.len() as u32);
+        for id in &self.textures.free {
+            w.texture_id(*id);
+        }
+        let meshes: Vec<_> = self
+            .primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) if matches!(mesh.texture_id, TextureId::Managed(_)) => {
+                    Some((primitive.clip_rect, mesh))
+                }
+                _ => None,
+            })
+            .collect();
+        w.u32(meshes.len() as u32);
+        for (clip_rect, mesh) in meshes {
+            w.rect(clip_rect);
+            w.mesh(mesh);
+        }
+        w.0
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut r = Reader(data);
+        let pixels_per_point = r.f32()?;
+        let set = (0..r.u32()?)
+            .map(|_| Ok((r.texture_id()?, r.image_delta()?)))
+            .collect::<Result<_, String>>()?;
+        let free = (0..r.u32()?)
+            .map(|_| r.texture_id())
+            .collect::<Result<_, _>>()?;
+        let primitives = (0..r.u32()?)
+            .map(|_| {
+                Ok(ClippedPrimitive {
+                    clip_rect: r.rect()?,
+                    primitive: Primitive::Mesh(r.mesh()?),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            pixels_per_point,
+            textures: TexturesDelta { set, free },
+            primitives,
+        })
+    }
+}
+
+fn event_tag(event: &Event) -> Option<u8> {
+    Some(match event {
+        Event::PointerMoved(_) => 0,
+        Event::PointerButton { .. } => 1,
+        Event::PointerGone => 2,
+        Event::MouseWheel { .. } => 3,
+        Event::Zoom(_) => 4,
+        Event::Text(_) => 5,
+        Event::Key { .. } => 6,
+        Event::Copy => 7,
+        Event::Cut => 8,
+        Event::Paste(_) => 9,
+        _ => return None,
+    })
+}
+
+const POINTER_BUTTONS: [PointerButton; 5] = [
+    PointerButton::Primary,
+    PointerButton::Secondary,
+    PointerButton::Middle,
+    PointerButton::Extra1,
+    PointerButton::Extra2,
+];
+const WHEEL_UNITS: [MouseWheelUnit; 3] = [
+    MouseWheelUnit::Point,
+    MouseWheelUnit::Line,
+    MouseWheelUnit::Page,
+];
+const TEXTURE_FILTERS: [TextureFilter; 2] = [TextureFilter::Nearest, TextureFilter::Linear];
+
+/// Little endian
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    fn str(&mut self, v: &str) {
+        self.u32(v.len() as u32);
+        self.0.extend(v.as_bytes());
+    }
+
+    fn vec2(&mut self, v: Vec2) {
+        self.f32(v.x);
+        self.f32(v.y);
+    }
+
+    fn pos2(&mut self, v: Pos2) {
+        self.vec2(v.to_vec2());
+    }
+
+    fn rect(&mut self, v: Rect) {
+        self.pos2(v.min);
+        self.pos2(v.max);
+    }
+
+    fn modifiers(&mut self, v: Modifiers) {
+        let bits = [v.alt, v.ctrl, v.shift, v.mac_cmd, v.command];
+        self.u8(bits.iter().rev().fold(0, |acc, bit| acc << 1 | *bit as u8));
+    }
+
+    fn event(&mut self, event: &Event) {
+        let Some(tag) = event_tag(event) else {
+            return;
+        };
+        self.u8(tag);
+        match event {
+            Event::PointerMoved(pos) => self.pos2(*pos),
+            Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                self.pos2(*pos);
+                self.u8(POINTER_BUTTONS.iter().position(|b| b == button).unwrap() as u8);
+                self.bool(*pressed);
+                self.modifiers(*modifiers);
+            }
+            Event::MouseWheel {
+                unit,
+                delta,
+                modifiers,
+            } => {
+                self.u8(WHEEL_UNITS.iter().position(|u| u == unit).unwrap() as u8);
+                self.vec2(*delta);
+                self.modifiers(*modifiers);
+            }
+            Event::Zoom(factor) => self.f32(*factor),
+            Event::Text(text) | Event::Paste(text) => self.str(text),
+            Event::Key {
+                key,
+                pressed,
+                repeat,
+                modifiers,
+                ..
+            } => {
+                self.str(key.name());
+                self.bool(*pressed);
+                self.bool(*repeat);
+                self.modifiers(*modifiers);
+            }
+            _ => {}
+        }
+    }
+
+    fn texture_id(&mut self, id: TextureId) {
+        match id {
+            TextureId::Managed(id) => self.u64(id),
+            // Never sent, see `managed_textures`
+            TextureId::User(_) => self.u64(u64::MAX),
+        }
+    }
+
+    fn image_delta(&mut self, delta: &ImageDelta) {
+        match delta.pos {
+            Some([x, y]) => {
+                self.bool(true);
+                self.u32(x as u32);
+                self.u32(y as u32);
+            }
+            None => self.bool(false),
+        }
+        let filter = |filter| TEXTURE_FILTERS.iter().position(|f| *f == filter).unwrap() as u8;
+        self.u8(filter(delta.options.magnification));
+        self.u8(filter(delta.options.minification));
+        let image = color_image(&delta.image);
+        self.u32(image.size[0] as u32);
+        self.u32(image.size[1] as u32);
+        for pixel in &image.pixels {
+            self.0.extend(pixel.to_array());
+        }
+    }
+
+    fn mesh(&mut self, mesh: &Mesh) {
+        self.texture_id(mesh.texture_id);
+        self.u32(mesh.vertices.len() as u32);
+        for vertex in &mesh.vertices {
+            self.pos2(vertex.pos);
+            self.pos2(vertex.uv);
+            self.0.extend(vertex.color.to_array());
+        }
+        self.u32(mesh.indices.len() as u32);
+        for index in &mesh.indices {
+            self.u32(*index);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let Some((bytes, rest)) = self.0.split_first_chunk::<N>() else {
+            return Err("Message ended early".to_string());
+        };
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn index<T: Copy>(&mut self, values: &[T]) -> Result<T, String> {
+        let index = self.u8()?;
+        values
+            .get(index as usize)
+            .copied()
+            .ok_or(format!("Unknown value {index}"))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        if len > self.0.len() {
+            return Err("Message ended early".to_string());
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn vec2(&mut self) -> Result<Vec2, String> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn pos2(&mut self) -> Result<Pos2, String> {
+        Ok(self.vec2()?.to_pos2())
+    }
+
+    fn rect(&mut self) -> Result<Rect, String> {
+        Ok(Rect::from_min_max(self.pos2()?, self.pos2()?))
+    }
+
+    fn modifiers(&mut self) -> Result<Modifiers, String> {
+        let bits = self.u8()?;
+        let bit = |i: u8| bits >> i & 1 != 0;
+        Ok(Modifiers {
+            alt: bit(0),
+            ctrl: bit(1),
+            shift: bit(2),
+            mac_cmd: bit(3),
+            command: bit(4),
+        })
+    }
+
+    fn event(&mut self) -> Result<Event, String> {
+        Ok(match self.u8()? {
+            0 => Event::PointerMoved(self.pos2()?),
+            1 => Event::PointerButton {
+                pos: self.pos2()?,
+                button: self.index(&POINTER_BUTTONS)?,
+                pressed: self.bool()?,
+                modifiers: self.modifiers()?,
+            },
+            2 => Event::PointerGone,
+            3 => Event::MouseWheel {
+                unit: self.index(&WHEEL_UNITS)?,
+                delta: self.vec2()?,
+                modifiers: self.modifiers()?,
+            },
+            4 => Event::Zoom(self.f32()?),
+            5 => Event::Text(self.str()?),
+            6 => {
+                let name = self.str()?;
+                Event::Key {
+                    key: Key::from_name(&name).ok_or(format!("Unknown key {name:?}"))?,
+                    physical_key: None,
+                    pressed: self.bool()?,
+                    repeat: self.bool()?,
+                    modifiers: self.modifiers()?,
+                }
+            }
+            7 => Event::Copy,
+            8 => Event::Cut,
+            9 => Event::Paste(self.str()?),
+            tag => return Err(format!("Unknown event {tag}")),
+        })
+    }
+
+    fn texture_id(&mut self) -> Result<TextureId, String> {
+        Ok(TextureId::Managed(self.u64()?))
+    }
+
+    fn image_delta(&mut self) -> Result<ImageDelta, String> {
+        let pos = match self.bool()? {
+            true => Some([self.u32()? as usize, self.u32()? as usize]),
+            false => None,
+        };
+        let options = TextureOptions {
+            magnification: self.index(&TEXTURE_FILTERS)?,
+            minification: self.index(&TEXTURE_FILTERS)?,
+            ..TextureOptions::LINEAR
+        };
+        let size = [self.u32()? as usize, self.u32()? as usize];
+        let mut image = ColorImage::new(size, Color32::TRANSPARENT);
+        for pixel in &mut image.pixels {
+            let [r, g, b, a] = self.bytes()?;
+            *pixel = Color32::from_rgba_premultiplied(r, g, b, a);
+        }
+        Ok(match pos {
+            Some(pos) => ImageDelta::partial(pos, image, options),
+            None => ImageDelta::full(image, options),
+        })
+    }
+
+    fn mesh(&mut self) -> Result<Mesh, String> {
+        let texture_id = self.texture_id()?;
+        let vertices = (0..self.u32()?)
+            .map(|_| {
+                let (pos, uv) = (self.pos2()?, self.pos2()?);
+                let [r, g, b, a] = self.bytes()?;
+                let color = Color32::from_rgba_premultiplied(r, g, b, a);
+                Ok(Vertex { pos, uv, color })
+            })
+            .collect::<Result<_, String>>()?;
+        let indices = (0..self.u32()?)
+            .map(|_| self.u32())
+            .collect::<Result<_, _>>()?;
+        Ok(Mesh {
+            indices,
+            vertices,
+            texture_id,
+        })
+    }
+}
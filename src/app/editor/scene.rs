@@ -0,0 +1,467 @@
+use nalgebra::Vector3;
+
+use crate::{
+    entity::{EntityMeta, Uuid},
+    graphics::{
+        bounds::Aabb,
+        entities::{
+            lightmap::{bake::BakedLightmap, Lightmap, LightmapImage},
+            model::{ModelInstanceId, ModelsBuffer},
+            renderer::EntitiesRenderer,
+        },
+        stats::BufferPeaks,
+        volumes::PostVolume,
+        GlobalRenderer,
+    },
+    locale::{tr, tr_args},
+    random::{Random, DEFAULT_SEED},
+    scene::{Scene, SceneInstance, SceneLight},
+    sequence::Sequence,
+};
+
+use super::{
+    history::{EditHistory, PendingEdit, Snapshot},
+    inspect::Inspect,
+};
+
+/// Instances placed from the editor, saved and loaded as a [`Scene`]
+pub struct SceneEditor {
+    pub name: String,
+    pub selected: Option<usize>,
+    placed: Vec<(SceneInstance, ModelInstanceId)>,
+    /// Made static, merged into [`EntitiesRenderer::statics`] instead of placed
+    statics: Vec<SceneInstance>,
+    /// The static batches must be built again
+    statics_changed: bool,
+    /// Lights of the loaded scene, written back on save
+    lights: Vec<SceneLight>,
+    /// Post processing volumes, relative to the origin like the placed instances
+    pub volumes: Vec<PostVolume>,
+    /// Cutscene of the loaded scene, edited in the sequencer
+    pub sequence: Sequence,
+    /// Baked or loaded with the scene, saved next to it
+    lightmap: Option<LightmapImage>,
+    /// Recorded by the renderer while the scene is edited, saved next to it
+    peaks: BufferPeaks,
+    /// Loaded with the scene, the renderer is presized with them once
+    loaded_peaks: Option<BufferPeaks>,
+    /// Moves of the world origin since startup, placed instances are relative to it but saved
+    /// at their absolute position
+    origin: Vector3<f32>,
+    /// Selected instance from before the edit in progress
+    pending: PendingEdit,
+}
+
+impl Default for SceneEditor {
+    fn default() -> Self {
+        Self {
+            name: String::from("Editor"),
+            selected: None,
+            placed: Vec::new(),
+            statics: Vec::new(),
+            statics_changed: false,
+            lights: Vec::new(),
+            volumes: Vec::new(),
+            sequence: Sequence::default(),
+            lightmap: None,
+            peaks: BufferPeaks::default(),
+            loaded_peaks: None,
+            origin: Vector3::zeros(),
+            pending: PendingEdit::default(),
+        }
+    }
+}
+
+impl SceneEditor {
+    /// Adds the instance and selects it
+    pub fn place(&mut self, models: &mut ModelsBuffer, placed: SceneInstance) {
+        let id = models.add_instance(placed.model_id, placed.mesh_id, placed.instance);
+        self.placed.push((placed, id));
+        self.selected = Some(self.placed.len() - 1);
+    }
+
+    pub fn placed(&self) -> impl Iterator<Item = &SceneInstance> {
+        self.placed.iter().map(|(placed, _)| placed)
+    }
+
+    /// Gives the baked rects to the placed instances at `placed`, the others lose theirs
+    pub fn apply_lightmap(
+        &mut self,
+        entities: &mut EntitiesRenderer,
+        placed: &[usize],
+        baked: BakedLightmap,
+    ) {
+        for (scene_instance, id) in &mut self.placed {
+            scene_instance.instance.lightmap = [0.0; 4];
+            entities.models.set_instance(id, scene_instance.instance);
+        }
+        for (i, rect) in placed.iter().zip(baked.rects) {
+            let (scene_instance, id) = &mut self.placed[*i];
+            scene_instance.instance.lightmap = rect;
+            entities.models.set_instance(id, scene_instance.instance);
+        }
+        entities.lightmap.set(&baked.image);
+        self.lightmap = Some(baked.image);
+    }
+
+    pub fn selection(&self) -> Option<&SceneInstance> {
+        self.selected
+            .and_then(|i| self.placed.get(i))
+            .map(|(placed, _)| placed)
+    }
+
+    /// World space bounds of the selected instance
+    pub fn selection_bounds(&self, models: &ModelsBuffer) -> Option<Aabb> {
+        self.selection().map(|placed| {
+            models
+                .mesh_bounds(placed.model_id, placed.mesh_id)
+                .transformed(&placed.instance.transform())
+        })
+    }
+
+    /// Index of the placed instance, for the references kept across reorders and reloads
+    pub fn find(&self, uuid: Uuid) -> Option<usize> {
+        self.placed
+            .iter()
+            .position(|(placed, _)| placed.meta.uuid == uuid)
+    }
+
+    /// Gpu id of the placed instance
+    pub fn instance_id(&self, uuid: Uuid) -> Option<&ModelInstanceId> {
+        self.find(uuid).map(|i| &self.placed[i].1)
+    }
+
+    /// Places a copy of the selection next to it, with a new id and without a name. Returns the
+    /// id of the copy
+    pub fn duplicate_selection(&mut self, models: &mut ModelsBuffer) -> Option<Uuid> {
+        let mut copy = self.selection().cloned()?;
+        copy.meta = EntityMeta::new();
+        copy.instance.translation[0] += 1.0;
+        let uuid = copy.meta.uuid;
+        self.place(models, copy);
+        Some(uuid)
+    }
+
+    /// Puts the placed instance back as it was, `None` removes it. Returns the state it replaced
+    pub fn restore(
+        &mut self,
+        models: &mut ModelsBuffer,
+        uuid: Uuid,
+        placed: Option<SceneInstance>,
+    ) -> Option<SceneInstance> {
+        match (self.find(uuid), placed) {
+            (Some(i), Some(placed)) => {
+                let (current, id) = &mut self.placed[i];
+                models.set_instance(id, placed.instance);
+                Some(std::mem::replace(current, placed))
+            }
+            (Some(i), None) => {
+                let (current, id) = self.placed.remove(i);
+                models.remove_instance(id);
+                self.selected = match self.selected {
+                    Some(selected) if selected == i => None,
+                    Some(selected) if selected > i => Some(selected - 1),
+                    selected => selected,
+                };
+                Some(current)
+            }
+            (None, Some(placed)) => {
+                self.place(models, placed);
+                None
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Moves the selection into the static instances, it can no longer be selected
+    pub fn make_selection_static(&mut self, models: &mut ModelsBuffer) {
+        let Some(index) = self.selected.filter(|i| *i < self.placed.len()) else {
+            return;
+        };
+        let (mut placed, id) = self.placed.remove(index);
+        models.remove_instance(id);
+        placed.is_static = true;
+        self.statics.push(placed);
+        self.statics_changed = true;
+        self.selected = None;
+    }
+
+    /// Places the static instances back as dynamic ones
+    pub fn make_statics_dynamic(&mut self, models: &mut ModelsBuffer) {
+        for mut placed in std::mem::take(&mut self.statics) {
+            placed.is_static = false;
+            self.place(models, placed);
+        }
+        self.statics_changed = true;
+        self.selected = None;
+    }
+
+    /// Builds the static batches again once the static instances changed
+    pub fn update_statics(&mut self, entities: &mut EntitiesRenderer) {
+        if !std::mem::take(&mut self.statics_changed) {
+            return;
+        }
+        let instances = self
+            .statics
+            .iter()
+            .map(|placed| (placed.model_id, placed.mesh_id, placed.instance));
+        entities.statics.build(&entities.models, instances);
+    }
+
+    /// Presizes the renderer for a loaded scene and keeps its peaks for the next save
+    pub fn update_peaks(&mut self, renderer: &mut GlobalRenderer) {
+        if let Some(peaks) = self.loaded_peaks.take() {
+            renderer.presize(peaks);
+        }
+        self.peaks.clone_from(&renderer.peaks);
+    }
+
+    /// Follows the world origin, the instances and batches were already moved on the gpu
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        self.origin += shift;
+        for (placed, _) in &mut self.placed {
+            translate(&mut placed.instance.translation, -shift);
+        }
+        for placed in &mut self.statics {
+            translate(&mut placed.instance.translation, -shift);
+        }
+        for volume in &mut self.volumes {
+            volume.center -= shift;
+        }
+    }
+
+    pub fn clear(&mut self, models: &mut ModelsBuffer) {
+        for (_, id) in self.placed.drain(..) {
+            models.remove_instance(id);
+        }
+        if !self.statics.is_empty() {
+            self.statics.clear();
+            self.statics_changed = true;
+        }
+        self.selected = None;
+    }
+
+    /// Placed and static instances at their absolute position, with the lights of the loaded
+    /// scene
+    pub fn to_scene(&self, seed: u32) -> Scene {
+        Scene {
+            instances: self
+                .placed
+                .iter()
+                .map(|(placed, _)| placed)
+                .chain(&self.statics)
+                .map(|placed| {
+                    let mut placed = placed.clone();
+                    translate(&mut placed.instance.translation, self.origin);
+                    placed
+                })
+                .collect(),
+            lights: self.lights.clone(),
+            volumes: self
+                .volumes
+                .iter()
+                .map(|volume| PostVolume {
+                    center: volume.center + self.origin,
+                    ..volume.clone()
+                })
+                .collect(),
+            seed: Some(seed),
+            sequence: self.sequence.clone(),
+        }
+    }
+
+    /// The seed of the random service is saved with the instances
+    pub fn save(&self, seed: u32) {
+        let scene = self.to_scene(seed);
+        match scene.save(&self.name) {
+            Ok(()) => println!("Saved scene {}", Scene::path(&self.name).display()),
+            Err(e) => eprintln!("Failed to save scene {}: {e}", self.name),
+        }
+        if let Some(lightmap) = &self.lightmap {
+            if let Err(e) = lightmap.save(&Scene::lightmap_path(&self.name)) {
+                eprintln!("Failed to save lightmap of scene {}: {e}", self.name);
+            }
+        }
+        if let Err(e) = self.peaks.save(&Scene::stats_path(&self.name)) {
+            eprintln!("Failed to save buffer peaks of scene {}: {e}", self.name);
+        }
+    }
+
+    /// Replaces the placed instances by the ones of the scene file, and the lightmap by its own.
+    /// The random service restarts from the seed of the scene
+    pub fn load(
+        &mut self,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+    ) {
+        match Scene::load(&self.name) {
+            Ok(scene) => self.replace(scene, models, lightmap, random),
+            Err(e) => eprintln!("Failed to load scene {}: {e}", self.name),
+        }
+    }
+
+    /// Replaces the placed instances by the ones of `scene`, the lightmap saved under the name
+    /// of the edited scene is loaded with them
+    pub fn replace(
+        &mut self,
+        scene: Scene,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+    ) {
+        self.clear(models);
+        random.reseed(scene.seed.unwrap_or(DEFAULT_SEED));
+        self.lights = scene.lights;
+        self.volumes = scene.volumes;
+        for volume in &mut self.volumes {
+            volume.center -= self.origin;
+        }
+        self.sequence = scene.sequence;
+        self.lightmap = None;
+        let path = Scene::lightmap_path(&self.name);
+        if path.exists() {
+            match LightmapImage::load(&path) {
+                Ok(image) => self.lightmap = Some(image),
+                Err(e) => eprintln!("Failed to load lightmap of scene {}: {e}", self.name),
+            }
+        }
+        match &self.lightmap {
+            Some(image) => lightmap.set(image),
+            None => lightmap.clear(),
+        }
+        // Peaks of the previous scene would keep its buffers, a scene never played has none
+        let path = Scene::stats_path(&self.name);
+        self.loaded_peaks = Some(match path.exists() {
+            true => BufferPeaks::load(&path).unwrap_or_else(|e| {
+                eprintln!("Failed to load buffer peaks of scene {}: {e}", self.name);
+                BufferPeaks::default()
+            }),
+            false => BufferPeaks::default(),
+        });
+        for mut placed in scene.instances {
+            translate(&mut placed.instance.translation, -self.origin);
+            if placed.model_id as u32 >= models.model_count()
+                || placed.mesh_id as u32 >= models.mesh_count_of(placed.model_id)
+            {
+                eprintln!("Skipped scene instance of unknown mesh {placed:?}");
+                continue;
+            }
+            match placed.is_static {
+                true => {
+                    self.statics.push(placed);
+                    self.statics_changed = true;
+                }
+                false => self.place(models, placed),
+            }
+        }
+        self.selected = None;
+    }
+
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+        history: &mut EditHistory,
+    ) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.name);
+            if ui.button(tr("editor-save")).clicked() {
+                self.save(random.seed());
+            }
+            if ui.button(tr("editor-load")).clicked() {
+                self.load(models, lightmap, random);
+            }
+            if ui.button(tr("editor-clear")).clicked() {
+                self.clear(models);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr_args(
+                "editor-scene-statics",
+                &[("count", &self.statics.len())],
+            ));
+            if ui
+                .add_enabled(
+                    self.selected.is_some(),
+                    egui::Button::new(tr("editor-scene-make-static")),
+                )
+                .clicked()
+            {
+                self.make_selection_static(models);
+            }
+            if ui
+                .add_enabled(
+                    !self.statics.is_empty(),
+                    egui::Button::new(tr("editor-scene-make-dynamic")),
+                )
+                .clicked()
+            {
+                self.make_statics_dynamic(models);
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut seed = random.seed();
+            ui.label(tr("editor-scene-seed"));
+            if ui.add(egui::DragValue::new(&mut seed)).changed() {
+                random.reseed(seed);
+            }
+            if ui.button(tr("editor-scene-restart-streams")).clicked() {
+                random.reseed(seed);
+            }
+        });
+
+        if let Some((placed, id)) = self.selected.and_then(|i| self.placed.get_mut(i)) {
+            let before = placed.clone();
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                let mut name = placed.meta.name.clone().unwrap_or_default();
+                ui.label(tr("editor-name"));
+                if ui.text_edit_singleline(&mut name).changed() {
+                    let name = name.trim();
+                    placed.meta.name = (!name.is_empty()).then(|| name.to_string());
+                    changed = true;
+                }
+            });
+            ui.label(tr_args("editor-scene-id", &[("id", &placed.meta.uuid)]));
+            if placed.instance.inspect(ui) {
+                models.set_instance(id, placed.instance);
+                changed = true;
+            }
+            if changed {
+                self.pending.changed(|| Snapshot::Instance {
+                    uuid: before.meta.uuid,
+                    placed: Some(before),
+                });
+            }
+        }
+        self.pending.commit(ui.ctx(), history);
+
+        for (i, (placed, _)) in self.placed.iter().enumerate() {
+            let [x, y, z] = placed.instance.translation;
+            let label = tr_args(
+                "editor-scene-instance",
+                &[
+                    ("name", &placed.meta.label()),
+                    ("model", &placed.model_id),
+                    ("mesh", &placed.mesh_id),
+                    ("position", &format!("{x:.1}, {y:.1}, {z:.1}")),
+                ],
+            );
+            if ui
+                .selectable_label(self.selected == Some(i), label)
+                .clicked()
+            {
+                self.selected = Some(i);
+            }
+        }
+    }
+}
+
+fn translate(translation: &mut [f32; 3], by: Vector3<f32>) {
+    for i in 0..3 {
+        translation[i] += by[i];
+    }
+}
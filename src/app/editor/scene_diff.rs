@@ -0,0 +1,105 @@
+use crate::{
+    graphics::entities::{lightmap::Lightmap, model::ModelsBuffer},
+    locale::{tr, tr_args},
+    random::Random,
+    scene::Scene,
+    scene_diff,
+};
+
+use super::scene::SceneEditor;
+
+/// Compares the edited scene with a saved one, and merges the edits saved in another scene
+/// since a common base into it
+#[derive(Default)]
+pub struct SceneDiffEditor {
+    /// Scene compared with, and the one merged in
+    other: String,
+    base: String,
+    /// Lines of the last diff or merge
+    report: Vec<String>,
+}
+
+impl SceneDiffEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        scene_editor: &mut SceneEditor,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label(tr("editor-diff-other"));
+            ui.text_edit_singleline(&mut self.other);
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("editor-diff-base"));
+            ui.text_edit_singleline(&mut self.base);
+        });
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-diff")).clicked() {
+                self.report = match Scene::load(&self.other) {
+                    Ok(other) => {
+                        let changes =
+                            scene_diff::diff(&other, &scene_editor.to_scene(random.seed()));
+                        match changes.is_empty() {
+                            true => vec![tr("editor-diff-no-changes")],
+                            false => changes.iter().map(ToString::to_string).collect(),
+                        }
+                    }
+                    Err(e) => vec![tr_args(
+                        "editor-diff-load-failed",
+                        &[("name", &self.other), ("error", &e)],
+                    )],
+                };
+            }
+            if ui
+                .add_enabled(
+                    !self.base.is_empty(),
+                    egui::Button::new(tr("editor-diff-merge")),
+                )
+                .on_hover_text(tr("editor-diff-merge-help"))
+                .clicked()
+            {
+                self.merge(scene_editor, models, lightmap, random);
+            }
+        });
+        for line in &self.report {
+            ui.monospace(line);
+        }
+    }
+
+    /// Conflicting entities keep their edited version
+    fn merge(
+        &mut self,
+        scene_editor: &mut SceneEditor,
+        models: &mut ModelsBuffer,
+        lightmap: &mut Lightmap,
+        random: &mut Random,
+    ) {
+        let (base, theirs) = match (Scene::load(&self.base), Scene::load(&self.other)) {
+            (Ok(base), Ok(theirs)) => (base, theirs),
+            (Err(e), _) | (_, Err(e)) => {
+                self.report = vec![tr_args("editor-diff-merge-load-failed", &[("error", &e)])];
+                return;
+            }
+        };
+        let merge = scene_diff::merge(&base, &scene_editor.to_scene(random.seed()), &theirs);
+        if let Err(e) = merge.scene.save(&scene_editor.name) {
+            self.report = vec![tr_args(
+                "editor-diff-save-failed",
+                &[("name", &scene_editor.name), ("error", &e)],
+            )];
+            return;
+        }
+        scene_editor.load(models, lightmap, random);
+        self.report = match merge.conflicts.is_empty() {
+            true => vec![tr("editor-diff-merged")],
+            false => merge
+                .conflicts
+                .iter()
+                .map(|conflict| tr_args("editor-diff-conflict", &[("entity", conflict)]))
+                .collect(),
+        };
+    }
+}
@@ -0,0 +1,159 @@
+use egui::{DragValue, Slider};
+
+use crate::{
+    game::GameState,
+    graphics::{camera::Projection, color::Color3, GlobalRenderer},
+    locale::{tr, tr_args},
+    sequence::{CameraKey, Easing, LightKey, Sequence},
+};
+
+use super::inspect::index_slider;
+
+/// Timeline of the cutscene of the edited scene, keys are taken from the view and the lights
+/// and saved with the scene
+#[derive(Default)]
+pub struct Sequencer {
+    /// Where the next keys are placed, in seconds
+    pub time: f32,
+    /// Light keyed by "Key light"
+    light: u32,
+}
+
+impl Sequencer {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        sequence: &mut Sequence,
+        game_state: &mut GameState,
+        renderer: &GlobalRenderer,
+        proj: &Projection,
+    ) {
+        let cutscene = &mut game_state.cutscene;
+        let duration = sequence.duration();
+        ui.label(tr_args(
+            "editor-sequencer-keys",
+            &[
+                ("camera", &sequence.camera.len()),
+                ("lights", &sequence.lights.len()),
+                ("duration", &format!("{duration:.2}")),
+            ],
+        ));
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(&mut self.time)
+                    .speed(0.05)
+                    .range(0.0..=f32::MAX)
+                    .suffix("s"),
+            );
+            match cutscene.playing {
+                true => {
+                    ui.label(format!("{:.2}s", cutscene.time));
+                    if ui.button(tr("editor-stop")).clicked() {
+                        cutscene.stop();
+                    }
+                }
+                false => {
+                    if ui.button(tr("editor-sequencer-play")).clicked() {
+                        cutscene.play(sequence.clone());
+                    }
+                }
+            }
+            ui.checkbox(&mut cutscene.looping, tr("editor-sequencer-loop"));
+        });
+        if !cutscene.playing
+            && duration > 0.0
+            && ui
+                .add(Slider::new(&mut self.time, 0.0..=duration).text(tr("editor-preview")))
+                .changed()
+        {
+            cutscene.sequence = sequence.clone();
+            cutscene.seek(self.time);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-sequencer-key-camera")).clicked() {
+                let mut camera = game_state.camera;
+                camera.eye = renderer.origin.to_absolute(&camera.eye).cast();
+                sequence.set_camera_key(CameraKey::new(self.time, &camera, proj.fov_deg));
+            }
+            if ui.button(tr("editor-sequencer-key-light")).clicked() {
+                if let Some(light) = renderer.lights.get(self.light) {
+                    let [r, g, b] = light.color;
+                    sequence.set_light_key(LightKey {
+                        light: self.light,
+                        time: self.time,
+                        easing: Easing::default(),
+                        color: Color3::new(r, g, b),
+                        intensity: light.intensity(),
+                    });
+                }
+            }
+        });
+        index_slider(
+            ui,
+            &mut self.light,
+            renderer.lights.len(),
+            &tr("editor-sequencer-light"),
+        );
+
+        ui.separator();
+        let mut retimed = false;
+        let mut removed = None;
+        for (i, key) in sequence.camera.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(tr("editor-sequencer-camera"));
+                retimed |= key_ui(ui, ("camera key", i), &mut key.time, &mut key.easing);
+                let fov = format!("{:.0}", key.fov_deg);
+                ui.label(tr_args("editor-sequencer-fov", &[("fov", &fov)]));
+                if ui.button(tr("editor-remove")).clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed.take() {
+            sequence.camera.remove(i);
+        }
+        for (i, key) in sequence.lights.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(tr_args(
+                    "editor-sequencer-light-key",
+                    &[("light", &key.light)],
+                ));
+                retimed |= key_ui(ui, ("light key", i), &mut key.time, &mut key.easing);
+                ui.add(
+                    DragValue::new(&mut key.intensity)
+                        .speed(0.05)
+                        .range(0.0..=f32::MAX),
+                );
+                if ui.button(tr("editor-remove")).clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            sequence.lights.remove(i);
+        }
+        // Once the drag is over, the keys would swap places under the cursor otherwise
+        if retimed {
+            sequence.sort();
+        }
+    }
+}
+
+/// Time and easing of a key, returns true once a new time was set
+fn key_ui(ui: &mut egui::Ui, id: (&str, usize), time: &mut f32, easing: &mut Easing) -> bool {
+    let response = ui.add(
+        DragValue::new(time)
+            .speed(0.05)
+            .range(0.0..=f32::MAX)
+            .suffix("s"),
+    );
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(easing.label())
+        .show_ui(ui, |ui| {
+            for option in Easing::ALL {
+                ui.selectable_value(easing, option, option.label());
+            }
+        });
+    response.drag_stopped() || response.lost_focus()
+}
@@ -0,0 +1,53 @@
+use crate::{
+    app::menu::preset_name,
+    graphics::settings::{QualityPreset, RenderSettings},
+    locale::{tr, tr_args},
+};
+
+use super::inspect::Inspect;
+
+#[derive(Default)]
+pub struct SettingsEditor {
+    lut_name: String,
+}
+
+impl SettingsEditor {
+    pub fn ui(&mut self, ui: &mut egui::Ui, settings: &mut RenderSettings) {
+        ui.horizontal(|ui| {
+            ui.label(tr("editor-settings-preset"));
+            if ui.button(tr("editor-settings-default")).clicked() {
+                *settings = RenderSettings::default();
+            }
+            if ui.button(tr("editor-settings-retro")).clicked() {
+                *settings = RenderSettings::retro();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("menu-quality"));
+            let current = QualityPreset::of(settings);
+            for preset in QualityPreset::ALL {
+                if ui
+                    .selectable_label(current == Some(preset), preset_name(preset))
+                    .clicked()
+                {
+                    preset.apply(settings);
+                }
+            }
+            ui.selectable_label(current.is_none(), tr("menu-quality-custom"));
+        });
+        settings.inspect(ui);
+
+        let post = &mut settings.post;
+        let lut = post.lut.clone().unwrap_or_else(|| tr("editor-none"));
+        ui.label(tr_args("editor-lut", &[("name", &lut)]));
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.lut_name);
+            if ui.button(tr("editor-load")).clicked() {
+                post.lut = Some(self.lut_name.clone());
+            }
+            if ui.button(tr("editor-clear")).clicked() {
+                post.lut = None;
+            }
+        });
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+
+use egui::Color32;
+use winit::keyboard::KeyCode;
+
+use crate::{
+    app::{config::Config, inputs::Inputs, keys},
+    locale::{tr, tr_args},
+};
+
+/// Config section holding one `action = shortcut` entry per action
+const CONFIG_SECTION: &str = "shortcuts";
+
+/// Keys used by the camera controls and the mode switch, shortcuts without modifiers can't use
+/// them
+const RESERVED: [KeyCode; 9] = [
+    KeyCode::KeyW,
+    KeyCode::KeyA,
+    KeyCode::KeyS,
+    KeyCode::KeyD,
+    KeyCode::Space,
+    KeyCode::ShiftLeft,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::F1,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    SaveScene,
+    Duplicate,
+    FocusSelection,
+    ToggleWireframe,
+    Undo,
+    Redo,
+    ToggleCapture,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::SaveScene,
+        Action::Duplicate,
+        Action::FocusSelection,
+        Action::ToggleWireframe,
+        Action::Undo,
+        Action::Redo,
+        Action::ToggleCapture,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            Action::SaveScene => "Save scene",
+            Action::Duplicate => "Duplicate",
+            Action::FocusSelection => "Focus selection",
+            Action::ToggleWireframe => "Toggle wireframe",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::ToggleCapture => "Start or stop capture",
+        }
+    }
+
+    fn config_key(&self) -> &str {
+        match self {
+            Action::SaveScene => "save_scene",
+            Action::Duplicate => "duplicate",
+            Action::FocusSelection => "focus_selection",
+            Action::ToggleWireframe => "toggle_wireframe",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::ToggleCapture => "toggle_capture",
+        }
+    }
+
+    fn default_shortcut(&self) -> Shortcut {
+        match self {
+            Action::SaveScene => Shortcut::new(KeyCode::KeyS).with_ctrl(),
+            Action::Duplicate => Shortcut::new(KeyCode::KeyD).with_ctrl(),
+            Action::FocusSelection => Shortcut::new(KeyCode::KeyF),
+            Action::ToggleWireframe => Shortcut::new(KeyCode::F2),
+            Action::Undo => Shortcut::new(KeyCode::KeyZ).with_ctrl(),
+            Action::Redo => Shortcut::new(KeyCode::KeyY).with_ctrl(),
+            Action::ToggleCapture => Shortcut::new(KeyCode::F9),
+        }
+    }
+}
+
+/// Key pressed while exactly these modifiers are held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shortcut {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Shortcut {
+    pub const fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub const fn with_ctrl(self) -> Self {
+        Self { ctrl: true, ..self }
+    }
+
+    /// Parses the format written by `Display`, like `Ctrl+Shift+S`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut parts = src.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+        let key = keys::parse_key(key)
+            .filter(|key| !keys::MODIFIERS.contains(key))
+            .ok_or_else(|| format!("Unknown key: {key}"))?;
+
+        let mut shortcut = Self::new(key);
+        for modifier in parts {
+            match modifier {
+                "Ctrl" => shortcut.ctrl = true,
+                "Shift" => shortcut.shift = true,
+                "Alt" => shortcut.alt = true,
+                _ => return Err(format!("Unknown modifier: {modifier}")),
+            }
+        }
+        Ok(shortcut)
+    }
+
+    pub fn is_triggered(&self, inputs: &Inputs) -> bool {
+        inputs.key_pressed(self.key)
+            && inputs.held_control() == self.ctrl
+            && inputs.held_shift() == self.shift
+            && inputs.held_alt() == self.alt
+    }
+
+    fn has_modifiers(&self) -> bool {
+        self.ctrl || self.shift || self.alt
+    }
+}
+
+impl std::fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.shift, "Shift"),
+            (self.alt, "Alt"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        f.write_str(&keys::key_name(self.key))
+    }
+}
+
+/// Editor actions with their key bindings, persisted in the config file
+pub struct Shortcuts {
+    bindings: BTreeMap<Action, Shortcut>,
+    /// Action waiting for its new key, captured on the next key press
+    rebinding: Option<Action>,
+}
+
+impl Shortcuts {
+    pub fn from_config(config: &Config) -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let shortcut = config
+                    .get(CONFIG_SECTION, action.config_key())
+                    .and_then(|src| {
+                        Shortcut::parse(src)
+                            .inspect_err(|e| eprintln!("Invalid {} shortcut: {e}", action.label()))
+                            .ok()
+                    })
+                    .unwrap_or_else(|| action.default_shortcut());
+                (action, shortcut)
+            })
+            .collect();
+        Self {
+            bindings,
+            rebinding: None,
+        }
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        for (action, shortcut) in &self.bindings {
+            config.set(CONFIG_SECTION, action.config_key(), shortcut);
+        }
+    }
+
+    pub fn get(&self, action: Action) -> Shortcut {
+        self.bindings[&action]
+    }
+
+    /// Actions sharing a shortcut, or bound to a camera key without modifiers
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, (action, shortcut)) in self.bindings.iter().enumerate() {
+            for (other, other_shortcut) in self.bindings.iter().skip(i + 1) {
+                if shortcut == other_shortcut {
+                    conflicts.push(tr_args(
+                        "editor-shortcuts-conflict",
+                        &[
+                            ("action", &action.label()),
+                            ("other", &other.label()),
+                            ("shortcut", shortcut),
+                        ],
+                    ));
+                }
+            }
+            if !shortcut.has_modifiers() && RESERVED.contains(&shortcut.key) {
+                conflicts.push(tr_args(
+                    "editor-shortcuts-reserved",
+                    &[("action", &action.label()), ("shortcut", shortcut)],
+                ));
+            }
+        }
+        conflicts
+    }
+
+    /// Actions whose shortcut was pressed this step, nothing fires while rebinding
+    pub fn triggered(&mut self, inputs: &Inputs) -> Vec<Action> {
+        if let Some(action) = self.rebinding {
+            if inputs.key_pressed(KeyCode::Escape) {
+                self.rebinding = None;
+            } else if let Some(key) = keys::BINDABLE
+                .into_iter()
+                .find(|key| inputs.key_pressed(*key))
+            {
+                self.bindings.insert(
+                    action,
+                    Shortcut {
+                        key,
+                        ctrl: inputs.held_control(),
+                        shift: inputs.held_shift(),
+                        alt: inputs.held_alt(),
+                    },
+                );
+                self.rebinding = None;
+            }
+            return Vec::new();
+        }
+
+        self.bindings
+            .iter()
+            .filter(|(_, shortcut)| shortcut.is_triggered(inputs))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut Config) {
+        egui::Grid::new("Shortcuts").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.label());
+                let text = match self.rebinding {
+                    Some(rebinding) if rebinding == action => tr("menu-press-key"),
+                    _ => self.get(action).to_string(),
+                };
+                if ui.button(text).clicked() {
+                    self.rebinding = Some(action);
+                }
+                if ui.small_button(tr("editor-reset")).clicked() {
+                    self.bindings.insert(action, action.default_shortcut());
+                }
+                ui.end_row();
+            }
+        });
+
+        for conflict in self.conflicts() {
+            ui.colored_label(Color32::RED, conflict);
+        }
+
+        if ui.button(tr("editor-save")).clicked() {
+            self.write_config(config);
+            config.save();
+        }
+    }
+}
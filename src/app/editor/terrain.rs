@@ -0,0 +1,287 @@
+use std::path::PathBuf;
+
+use egui::{Color32, Slider};
+use nalgebra::{Point3, Vector4};
+use winit::event::MouseButton;
+
+use crate::{
+    app::inputs::Inputs,
+    graphics::{
+        camera::Projection,
+        settings::{TerrainSettings, TERRAIN_LAYER_COUNT},
+        terrain::{
+            heightmap::{Brush, BrushKind, Heightmap, DEFAULT_HEIGHTMAP},
+            splat::PaintBrush,
+        },
+        GlobalRenderer,
+    },
+    jobs::{self, Job, Priority},
+    locale::{tr, tr_args},
+};
+
+use super::{
+    history::{EditHistory, Snapshot},
+    inspect::Inspect,
+};
+
+/// Farthest the brush reaches from the camera
+const BRUSH_RANGE: f32 = 500.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerrainTool {
+    None,
+    Sculpt,
+    Paint,
+}
+
+/// Edit in progress while the button is held
+struct Stroke {
+    /// Pushed to the history once the button is released
+    before: Snapshot,
+    /// Height under the brush when the stroke started, for flattening
+    flatten_target: f32,
+}
+
+/// Assigns the textures of the terrain layers by name, sculpts the heightmap and paints the
+/// splat map
+///
+/// Brushes are applied while the left button is held on the scene in editor mode, each
+/// stroke is one undo step
+pub struct TerrainEditor {
+    albedo_names: [String; TERRAIN_LAYER_COUNT],
+    normal_names: [String; TERRAIN_LAYER_COUNT],
+    pub tool: TerrainTool,
+    pub brush: Brush,
+    pub paint: PaintBrush,
+    heightmap_name: String,
+    /// Heightmap read in the background, set on the terrain once done
+    loading: Option<(PathBuf, Job<Result<Heightmap, String>>)>,
+    /// Outcome of the last save or load of the heightmap, shown under its buttons
+    status: Option<Result<String, String>>,
+    stroke: Option<Stroke>,
+}
+
+impl Default for TerrainEditor {
+    fn default() -> Self {
+        Self {
+            albedo_names: Default::default(),
+            normal_names: Default::default(),
+            tool: TerrainTool::None,
+            brush: Brush {
+                kind: BrushKind::Raise,
+                radius: 1.0,
+                strength: 1.0,
+            },
+            paint: PaintBrush {
+                layer: 1,
+                radius: 1.0,
+                hardness: 0.5,
+                strength: 4.0,
+            },
+            heightmap_name: DEFAULT_HEIGHTMAP.to_string(),
+            loading: None,
+            status: None,
+            stroke: None,
+        }
+    }
+}
+
+impl TerrainEditor {
+    pub fn ui(&mut self, ui: &mut egui::Ui, renderer: &mut GlobalRenderer) {
+        let settings = &mut renderer.settings.terrain;
+        settings.inspect(ui);
+
+        ui.horizontal(|ui| {
+            for (tool, label) in [
+                (TerrainTool::None, tr("editor-terrain-no-tool")),
+                (TerrainTool::Sculpt, tr("editor-terrain-sculpt")),
+                (TerrainTool::Paint, tr("editor-paint")),
+            ] {
+                ui.selectable_value(&mut self.tool, tool, label);
+            }
+        });
+
+        ui.collapsing(tr("editor-terrain-sculpt"), |ui| {
+            ui.horizontal(|ui| {
+                for kind in BrushKind::ALL {
+                    ui.selectable_value(&mut self.brush.kind, kind, format!("{kind:?}"));
+                }
+            });
+            ui.add(Slider::new(&mut self.brush.radius, 0.1..=20.0).text(tr("editor-radius")));
+            ui.add(Slider::new(&mut self.brush.strength, 0.1..=10.0).text(tr("editor-strength")));
+
+            let height = &mut renderer.terrain.height;
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.heightmap_name);
+                let path = Heightmap::path(&self.heightmap_name);
+                if ui.button(tr("editor-save")).clicked() {
+                    self.status = Some(match height.map.save(&path) {
+                        Ok(()) => Ok(tr_args("editor-saved-to", &[("path", &path.display())])),
+                        Err(e) => {
+                            eprintln!("Failed to save heightmap {}: {e}", path.display());
+                            Err(tr_args("editor-save-failed", &[("error", &e)]))
+                        }
+                    });
+                }
+                if self.loading.is_some() {
+                    ui.spinner();
+                } else if ui.button(tr("editor-load")).clicked() {
+                    let job = jobs::spawn("Heightmap load", Priority::Normal, {
+                        let path = path.clone();
+                        move || Heightmap::load(&path)
+                    });
+                    self.loading = Some((path, job));
+                }
+            });
+            match &self.status {
+                Some(Ok(status)) => {
+                    ui.label(status);
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(Color32::RED, e);
+                }
+                None => {}
+            }
+            if ui.button(tr("editor-reset")).clicked() {
+                height.set(Heightmap::default());
+            }
+        });
+
+        ui.collapsing(tr("editor-paint"), |ui| {
+            ui.horizontal(|ui| {
+                for layer in 0..TERRAIN_LAYER_COUNT {
+                    ui.selectable_value(
+                        &mut self.paint.layer,
+                        layer,
+                        tr_args("editor-terrain-layer", &[("index", &layer)]),
+                    );
+                }
+            });
+            ui.add(Slider::new(&mut self.paint.radius, 0.1..=20.0).text(tr("editor-radius")));
+            ui.add(Slider::new(&mut self.paint.hardness, 0.0..=1.0).text(tr("editor-hardness")));
+            ui.add(Slider::new(&mut self.paint.strength, 0.1..=20.0).text(tr("editor-strength")));
+        });
+
+        self.layers_ui(ui, settings);
+    }
+
+    fn layers_ui(&mut self, ui: &mut egui::Ui, settings: &mut TerrainSettings) {
+        for (i, layer) in settings.layers.iter_mut().enumerate() {
+            ui.collapsing(tr_args("editor-terrain-layer", &[("index", &i)]), |ui| {
+                for (label, texture, name) in [
+                    (
+                        tr("editor-terrain-albedo"),
+                        &mut layer.albedo,
+                        &mut self.albedo_names[i],
+                    ),
+                    (
+                        tr("editor-terrain-normal"),
+                        &mut layer.normal,
+                        &mut self.normal_names[i],
+                    ),
+                ] {
+                    let assigned = texture.clone().unwrap_or_else(|| tr("editor-none"));
+                    ui.label(tr_args(
+                        "editor-terrain-texture",
+                        &[("map", &label), ("texture", &assigned)],
+                    ));
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(name);
+                        if ui.button(tr("editor-terrain-assign")).clicked() {
+                            *texture = Some(name.clone());
+                        }
+                        if ui.button(tr("editor-clear")).clicked() {
+                            *texture = None;
+                        }
+                    });
+                }
+                layer.inspect(ui);
+            });
+        }
+    }
+
+    /// Applies the brush of the tool under the cursor, `owns_pointer` is true while the scene gets
+    /// the mouse
+    pub fn update(
+        &mut self,
+        inputs: &Inputs,
+        renderer: &mut GlobalRenderer,
+        history: &mut EditHistory,
+        proj: &Projection,
+        owns_pointer: bool,
+        dt: f32,
+    ) {
+        if let Some((path, job)) = &mut self.loading {
+            if job.lost() {
+                self.loading = None;
+            } else if let Some(loaded) = job.poll() {
+                self.status = Some(match loaded {
+                    Ok(map) => {
+                        renderer.terrain.height.set(map);
+                        Ok(tr_args("editor-loaded", &[("path", &path.display())]))
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to load heightmap {}: {e}", path.display());
+                        Err(tr_args("editor-load-failed", &[("error", &e)]))
+                    }
+                });
+                self.loading = None;
+            }
+        }
+
+        let held = owns_pointer && inputs.mouse_held(MouseButton::Left);
+        if self.tool == TerrainTool::None || !held {
+            if let Some(stroke) = self.stroke.take() {
+                history.push(stroke.before);
+            }
+            return;
+        }
+        let Some((x, y)) = inputs.cursor() else {
+            return;
+        };
+
+        // Unprojected through the near and far planes, the depth range is 0 to 1
+        let inv_view_proj = renderer.camera.inv_view_proj();
+        let ndc_x = x / proj.size.x as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / proj.size.y as f32 * 2.0;
+        let unproject = |z: f32| {
+            let p = inv_view_proj * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            Point3::from(p.xyz() / p.w)
+        };
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        let Some(direction) = (far - near).try_normalize(1e-6) else {
+            return;
+        };
+
+        let terrain = &mut renderer.terrain;
+        let extent = terrain.height.extent;
+        let Some(center) = terrain.height.raycast(near, direction, BRUSH_RANGE) else {
+            return;
+        };
+        let stroke = self.stroke.get_or_insert_with(|| Stroke {
+            before: match self.tool {
+                TerrainTool::Paint => {
+                    Snapshot::SplatMap(terrain.material.splat_map.weights.clone())
+                }
+                _ => Snapshot::Heightmap(terrain.height.map.clone()),
+            },
+            flatten_target: terrain.height.height_at(center.x, center.z),
+        });
+        // The maps are laid out around the terrain, which moves with the world origin
+        let local = center - terrain.height.center;
+        match self.tool {
+            TerrainTool::Sculpt => terrain.height.map.apply_brush(
+                &self.brush,
+                local,
+                stroke.flatten_target,
+                dt,
+                extent,
+            ),
+            TerrainTool::Paint => terrain
+                .material
+                .splat_map
+                .paint(&self.paint, local, dt, extent),
+            TerrainTool::None => {}
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use egui::{Color32, CornerRadius, FontData, FontDefinitions, FontFamily, Slider, TextStyle};
+
+use crate::{app::config::Config, locale::tr, ASSETS};
+
+/// Interval between two checks of the theme and font files
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+/// Config section holding the chosen theme and the ui scale
+const CONFIG_SECTION: &str = "theme";
+
+/// Custom editor look, parsed from `key = value` lines of a .theme file
+///
+/// Every key is optional, missing colors keep the ones of the base theme
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub base: egui::Theme,
+    pub panel: Option<Color32>,
+    pub window: Option<Color32>,
+    pub background: Option<Color32>,
+    pub accent: Option<Color32>,
+    pub text: Option<Color32>,
+    pub corner_radius: Option<u8>,
+    /// Name of a font of the fonts folder, used for every text style
+    pub font: Option<String>,
+    pub font_size: Option<f32>,
+    /// Width of the widget outlines, in points
+    pub stroke_width: Option<f32>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            base: egui::Theme::Dark,
+            panel: None,
+            window: None,
+            background: None,
+            accent: None,
+            text: None,
+            corner_radius: None,
+            font: None,
+            font_size: None,
+            stroke_width: None,
+        }
+    }
+}
+
+impl Theme {
+    /// White on black with thick yellow outlines, built in so it is there without any file
+    pub fn high_contrast() -> Self {
+        Self {
+            base: egui::Theme::Dark,
+            panel: Some(Color32::BLACK),
+            window: Some(Color32::BLACK),
+            background: Some(Color32::BLACK),
+            accent: Some(Color32::from_rgb(255, 220, 0)),
+            text: Some(Color32::WHITE),
+            corner_radius: Some(0),
+            font: None,
+            font_size: Some(16.0),
+            stroke_width: Some(2.0),
+        }
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut theme = Self::default();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("Expected key = value: {line}"))?;
+            match key {
+                "base" => {
+                    theme.base = match value {
+                        "dark" => egui::Theme::Dark,
+                        "light" => egui::Theme::Light,
+                        _ => return Err(format!("Unknown base theme: {value}")),
+                    }
+                }
+                "panel" => theme.panel = Some(parse_color(value)?),
+                "window" => theme.window = Some(parse_color(value)?),
+                "background" => theme.background = Some(parse_color(value)?),
+                "accent" => theme.accent = Some(parse_color(value)?),
+                "text" => theme.text = Some(parse_color(value)?),
+                "corner_radius" => {
+                    theme.corner_radius =
+                        Some(value.parse().map_err(|e| format!("Invalid {key}: {e}"))?)
+                }
+                "font" => theme.font = Some(value.to_string()),
+                "font_size" => {
+                    theme.font_size =
+                        Some(value.parse().map_err(|e| format!("Invalid {key}: {e}"))?)
+                }
+                "stroke_width" => {
+                    theme.stroke_width =
+                        Some(value.parse().map_err(|e| format!("Invalid {key}: {e}"))?)
+                }
+                _ => return Err(format!("Unknown theme key: {key}")),
+            }
+        }
+        Ok(theme)
+    }
+
+    fn style(&self) -> egui::Style {
+        let mut style = egui::Style::default();
+        let visuals = &mut style.visuals;
+        *visuals = match self.base {
+            egui::Theme::Dark => egui::Visuals::dark(),
+            egui::Theme::Light => egui::Visuals::light(),
+        };
+
+        if let Some(panel) = self.panel {
+            visuals.panel_fill = panel;
+            visuals.widgets.noninteractive.bg_fill = panel;
+        }
+        if let Some(window) = self.window {
+            visuals.window_fill = window;
+            visuals.faint_bg_color = window;
+        }
+        if let Some(background) = self.background {
+            visuals.extreme_bg_color = background;
+        }
+        if let Some(accent) = self.accent {
+            visuals.selection.bg_fill = accent;
+            visuals.hyperlink_color = accent;
+            visuals.widgets.active.bg_fill = accent;
+        }
+        visuals.override_text_color = self.text;
+        if let Some(radius) = self.corner_radius {
+            let radius = CornerRadius::same(radius);
+            visuals.window_corner_radius = radius;
+            visuals.menu_corner_radius = radius;
+            for widget in [
+                &mut visuals.widgets.noninteractive,
+                &mut visuals.widgets.inactive,
+                &mut visuals.widgets.hovered,
+                &mut visuals.widgets.active,
+                &mut visuals.widgets.open,
+            ] {
+                widget.corner_radius = radius;
+            }
+        }
+
+        if let Some(width) = self.stroke_width {
+            let color = self
+                .accent
+                .unwrap_or(visuals.widgets.inactive.fg_stroke.color);
+            visuals.window_stroke.width = width;
+            visuals.selection.stroke.width = width;
+            for widget in [
+                &mut visuals.widgets.inactive,
+                &mut visuals.widgets.hovered,
+                &mut visuals.widgets.active,
+                &mut visuals.widgets.open,
+            ] {
+                widget.bg_stroke.width = width;
+                widget.bg_stroke.color = color;
+                widget.fg_stroke.width = width;
+            }
+        }
+
+        if let Some(size) = self.font_size {
+            for (text_style, font) in style.text_styles.iter_mut() {
+                font.size = match text_style {
+                    TextStyle::Small => size * 0.75,
+                    TextStyle::Heading => size * 1.4,
+                    _ => size,
+                };
+            }
+        }
+        style
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color32, String> {
+    let channels = value
+        .split_whitespace()
+        .map(str::parse::<u8>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid color {value}: {e}"))?;
+    match channels[..] {
+        [r, g, b] => Ok(Color32::from_rgb(r, g, b)),
+        _ => Err(format!("Expected 3 channels: {value}")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThemeChoice {
+    Dark,
+    Light,
+    HighContrast,
+    Custom(String),
+}
+
+impl ThemeChoice {
+    fn name(&self) -> &str {
+        match self {
+            ThemeChoice::Dark => "Dark",
+            ThemeChoice::Light => "Light",
+            ThemeChoice::HighContrast => "HighContrast",
+            ThemeChoice::Custom(name) => name,
+        }
+    }
+
+    fn parse(name: &str) -> Self {
+        match name {
+            "Dark" => ThemeChoice::Dark,
+            "Light" => ThemeChoice::Light,
+            "HighContrast" => ThemeChoice::HighContrast,
+            _ => ThemeChoice::Custom(name.to_string()),
+        }
+    }
+}
+
+/// Applies the selected theme, reloading the themes and fonts when their files change
+pub struct ThemeEditor {
+    pub choice: ThemeChoice,
+    /// Zoom of the whole gui on top of the scale factor of the display
+    pub ui_scale: f32,
+    themes: BTreeMap<String, Theme>,
+    fonts: BTreeMap<String, Arc<FontData>>,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_scan: Option<Instant>,
+    /// Rereads every file from disk on the next scan
+    reload: bool,
+    errors: BTreeMap<String, String>,
+    dirty: bool,
+}
+
+impl Default for ThemeEditor {
+    fn default() -> Self {
+        Self {
+            choice: ThemeChoice::Dark,
+            ui_scale: 1.0,
+            themes: BTreeMap::new(),
+            fonts: BTreeMap::new(),
+            modified: HashMap::new(),
+            last_scan: None,
+            reload: false,
+            errors: BTreeMap::new(),
+            dirty: true,
+        }
+    }
+}
+
+impl ThemeEditor {
+    /// Missing or invalid entries keep their default, an unknown custom theme falls back to the
+    /// dark one once the themes are scanned
+    pub fn from_config(config: &Config) -> Self {
+        let mut editor = Self::default();
+        if let Some(name) = config.get(CONFIG_SECTION, "theme") {
+            editor.choice = ThemeChoice::parse(name);
+        }
+        if let Some(scale) = config
+            .get(CONFIG_SECTION, "ui_scale")
+            .and_then(|v| v.parse::<f32>().ok())
+        {
+            editor.ui_scale = scale.clamp(0.5, 3.0);
+        }
+        editor
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        config.set(CONFIG_SECTION, "theme", self.choice.name());
+        config.set(CONFIG_SECTION, "ui_scale", self.ui_scale);
+    }
+
+    /// Called before every editor frame, styles only change when needed
+    pub fn apply(&mut self, ctx: &egui::Context) {
+        if self
+            .last_scan
+            .map_or(true, |last| last.elapsed() >= SCAN_INTERVAL)
+        {
+            self.last_scan = Some(Instant::now());
+            self.dirty |= self.scan();
+            self.reload = false;
+        }
+        if !std::mem::take(&mut self.dirty) {
+            return;
+        }
+
+        let theme = match &self.choice {
+            ThemeChoice::Dark => Theme::default(),
+            ThemeChoice::Light => Theme {
+                base: egui::Theme::Light,
+                ..Default::default()
+            },
+            ThemeChoice::HighContrast => Theme::high_contrast(),
+            ThemeChoice::Custom(name) => self.themes.get(name).cloned().unwrap_or_default(),
+        };
+
+        let mut fonts = FontDefinitions::default();
+        if let Some(name) = &theme.font {
+            match self.fonts.get(name) {
+                Some(font) => {
+                    fonts.font_data.insert(name.clone(), font.clone());
+                    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                        fonts
+                            .families
+                            .entry(family)
+                            .or_default()
+                            .insert(0, name.clone());
+                    }
+                }
+                None => eprintln!("Theme font {name} not found"),
+            }
+        }
+
+        ctx.set_theme(theme.base);
+        ctx.set_style(theme.style());
+        ctx.set_fonts(fonts);
+        ctx.set_zoom_factor(self.ui_scale);
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut Config) {
+        let before = self.choice.clone();
+        ui.horizontal_wrapped(|ui| {
+            ui.selectable_value(&mut self.choice, ThemeChoice::Dark, tr("editor-theme-dark"));
+            ui.selectable_value(
+                &mut self.choice,
+                ThemeChoice::Light,
+                tr("editor-theme-light"),
+            );
+            ui.selectable_value(
+                &mut self.choice,
+                ThemeChoice::HighContrast,
+                tr("editor-theme-high-contrast"),
+            );
+            for name in self.themes.keys() {
+                ui.selectable_value(&mut self.choice, ThemeChoice::Custom(name.clone()), name);
+            }
+        });
+        self.dirty |= self.choice != before;
+
+        // Applied once released, the slider would move under the cursor while zooming
+        let scale =
+            ui.add(Slider::new(&mut self.ui_scale, 0.5..=3.0).text(tr("editor-theme-ui-scale")));
+        self.dirty |= scale.drag_stopped() || (scale.changed() && !scale.dragged());
+
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-theme-reload")).clicked() {
+                self.reload = true;
+                self.last_scan = None;
+            }
+            if ui.button(tr("editor-save")).clicked() {
+                self.write_config(config);
+                config.save();
+            }
+        });
+        for (name, error) in &self.errors {
+            ui.colored_label(Color32::RED, format!("{name}: {error}"));
+        }
+    }
+
+    /// Reads the files added or modified since the last scan, returns true if any was
+    fn scan(&mut self) -> bool {
+        let mut changed = false;
+        for (folder, extension) in [("themes", "theme"), ("fonts", "ttf")] {
+            let Ok(entries) = std::fs::read_dir(ASSETS.root().join(folder)) else {
+                continue;
+            };
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if path.extension().map_or(true, |ext| ext != extension) {
+                    continue;
+                }
+                let Ok(modified) = path.metadata().and_then(|meta| meta.modified()) else {
+                    continue;
+                };
+                let first_load = match self.modified.insert(path.clone(), modified) {
+                    Some(previous) if previous == modified && !self.reload => continue,
+                    previous => previous.is_none(),
+                };
+                let Some(name) = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                else {
+                    continue;
+                };
+
+                changed = true;
+                match extension {
+                    "theme" => self.load_theme(&path, name, first_load),
+                    _ => self.load_font(&path, name, first_load),
+                }
+            }
+        }
+        changed
+    }
+
+    /// Files are taken from the asset tree at first, from disk once they changed
+    fn load_theme(&mut self, path: &Path, name: String, first_load: bool) {
+        let src = match ASSETS.tree().themes.get(&name).filter(|_| first_load) {
+            Some(file) => Ok(file.0.clone()),
+            None => std::fs::read_to_string(path).map_err(|e| e.to_string()),
+        };
+        match src.and_then(|src| Theme::parse(&src)) {
+            Ok(theme) => {
+                self.errors.remove(&name);
+                self.themes.insert(name, theme);
+            }
+            Err(e) => {
+                eprintln!("Failed to load theme {name}: {e}");
+                self.errors.insert(name, e);
+            }
+        }
+    }
+
+    fn load_font(&mut self, path: &Path, name: String, first_load: bool) {
+        let bytes = match ASSETS.tree().fonts.get(&name).filter(|_| first_load) {
+            Some(file) => Ok(file.0.clone()),
+            None => std::fs::read(path).map_err(|e| e.to_string()),
+        };
+        match bytes {
+            Ok(bytes) => {
+                self.errors.remove(&name);
+                self.fonts
+                    .insert(name, Arc::new(FontData::from_owned(bytes)));
+            }
+            Err(e) => {
+                eprintln!("Failed to load font {name}: {e}");
+                self.errors.insert(name, e);
+            }
+        }
+    }
+}
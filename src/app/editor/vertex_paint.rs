@@ -0,0 +1,285 @@
+use egui::Slider;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+use winit::event::MouseButton;
+
+use crate::{
+    app::inputs::Inputs,
+    graphics::{
+        camera::Projection,
+        color::Color3,
+        debug_lines::DebugLines,
+        entities::{import::save_vertex_colors, renderer::ASSET_MODELS},
+        GlobalRenderer,
+    },
+    locale::tr,
+};
+
+use super::{
+    history::{EditHistory, Snapshot},
+    scene::SceneEditor,
+};
+
+/// Edit in progress while the button is held, on a single mesh
+struct Stroke {
+    model_id: u16,
+    mesh_id: u16,
+    /// Pushed to the history once the button is released
+    before: Vec<[u8; 4]>,
+    /// Blended in floats, small steps would round away in the vertex colors
+    colors: Vec<[f32; 3]>,
+}
+
+/// Nearest triangle under the cursor
+struct Hit {
+    model_id: u16,
+    mesh_id: u16,
+    transform: Matrix4<f32>,
+    position: Point3<f32>,
+}
+
+/// Paints the vertex colors of the instances placed in the scene editor
+///
+/// The brush is applied while the left button is held on the scene in editor mode, each
+/// stroke is one undo step. Colors are shared by every instance of the mesh and saved to the
+/// sidecar of the model, animated instances are painted at their rest transform
+pub struct VertexPainter {
+    pub active: bool,
+    /// Linear
+    pub color: [f32; 3],
+    pub radius: f32,
+    pub hardness: f32,
+    pub strength: f32,
+    stroke: Option<Stroke>,
+    /// Under the cursor, for the preview
+    hovered: Option<Point3<f32>>,
+    /// Models with colors changed since the last save
+    painted: Vec<u16>,
+}
+
+impl Default for VertexPainter {
+    fn default() -> Self {
+        Self {
+            active: false,
+            color: [1.0, 0.0, 0.0],
+            radius: 0.5,
+            hardness: 0.5,
+            strength: 4.0,
+            stroke: None,
+            hovered: None,
+            painted: Vec::new(),
+        }
+    }
+}
+
+impl VertexPainter {
+    pub fn ui(&mut self, ui: &mut egui::Ui, renderer: &GlobalRenderer) {
+        ui.checkbox(&mut self.active, tr("editor-paint"));
+        ui.horizontal(|ui| {
+            ui.label(tr("editor-color"));
+            ui.color_edit_button_rgb(&mut self.color);
+        });
+        ui.add(Slider::new(&mut self.radius, 0.01..=10.0).text(tr("editor-radius")));
+        ui.add(Slider::new(&mut self.hardness, 0.0..=1.0).text(tr("editor-hardness")));
+        ui.add(Slider::new(&mut self.strength, 0.1..=20.0).text(tr("editor-strength")));
+
+        let save = ui.add_enabled(
+            !self.painted.is_empty(),
+            egui::Button::new(tr("editor-save")),
+        );
+        if save.clicked() {
+            let models = &renderer.entities.models;
+            for model_id in self.painted.drain(..) {
+                let Some(name) = ASSET_MODELS.get(model_id as usize) else {
+                    eprintln!(
+                        "Model {model_id} is not loaded from the assets, its colors are lost"
+                    );
+                    continue;
+                };
+                let colors: Vec<_> = (0..models.mesh_count_of(model_id) as u16)
+                    .map(|mesh_id| models.vertex_colors(model_id, mesh_id))
+                    .collect();
+                match save_vertex_colors(name, &colors) {
+                    Ok(()) => println!("Saved vertex colors of {name}"),
+                    Err(e) => eprintln!("Failed to save vertex colors of {name}: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Applies the brush under the cursor, `owns_pointer` is true while the scene gets the mouse
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        inputs: &Inputs,
+        scene: &SceneEditor,
+        renderer: &mut GlobalRenderer,
+        history: &mut EditHistory,
+        proj: &Projection,
+        owns_pointer: bool,
+        dt: f32,
+    ) {
+        let hit = self
+            .active
+            .then(|| self.raycast(inputs, scene, renderer, proj))
+            .flatten();
+        self.hovered = hit.as_ref().map(|hit| hit.position);
+
+        let held = owns_pointer && inputs.mouse_held(MouseButton::Left);
+        let Some(hit) = hit.filter(|_| held) else {
+            self.finish_stroke(history);
+            return;
+        };
+        if self
+            .stroke
+            .as_ref()
+            .is_some_and(|stroke| (stroke.model_id, stroke.mesh_id) != (hit.model_id, hit.mesh_id))
+        {
+            self.finish_stroke(history);
+        }
+
+        let models = &mut renderer.entities.models;
+        let stroke = self.stroke.get_or_insert_with(|| {
+            let before = models.vertex_colors(hit.model_id, hit.mesh_id);
+            Stroke {
+                model_id: hit.model_id,
+                mesh_id: hit.mesh_id,
+                colors: before
+                    .iter()
+                    .map(|[r, g, b, _]| [r, g, b].map(|c| *c as f32 / 255.0))
+                    .collect(),
+                before,
+            }
+        });
+
+        // Same falloff as the splat map brush, in world space
+        let soft = (1.0 - self.hardness).max(1e-3);
+        let (vertices, _) = models.mesh_geometry(hit.model_id, hit.mesh_id);
+        for (vertex, color) in vertices.iter().zip(&mut stroke.colors) {
+            let position = hit
+                .transform
+                .transform_point(&Point3::from(vertex.position));
+            let distance = (position - hit.position).norm() / self.radius;
+            if distance >= 1.0 {
+                continue;
+            }
+            let falloff = ((1.0 - distance) / soft).clamp(0.0, 1.0);
+            let blend = (self.strength * falloff * dt).min(1.0);
+            for (c, target) in color.iter_mut().zip(self.color) {
+                *c += (target - *c) * blend;
+            }
+        }
+        let colors: Vec<_> = stroke
+            .colors
+            .iter()
+            .map(|&[r, g, b]| [r, g, b, 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect();
+        models.set_vertex_colors(hit.model_id, hit.mesh_id, &colors);
+        if !self.painted.contains(&hit.model_id) {
+            self.painted.push(hit.model_id);
+        }
+    }
+
+    fn finish_stroke(&mut self, history: &mut EditHistory) {
+        if let Some(stroke) = self.stroke.take() {
+            history.push(Snapshot::VertexColors {
+                model_id: stroke.model_id,
+                mesh_id: stroke.mesh_id,
+                colors: stroke.before,
+            });
+        }
+    }
+
+    /// Nearest triangle of the placed instances along the cursor ray
+    fn raycast(
+        &self,
+        inputs: &Inputs,
+        scene: &SceneEditor,
+        renderer: &GlobalRenderer,
+        proj: &Projection,
+    ) -> Option<Hit> {
+        let (x, y) = inputs.cursor()?;
+
+        // Unprojected through the near and far planes, the depth range is 0 to 1
+        let inv_view_proj = renderer.camera.inv_view_proj();
+        let ndc_x = x / proj.size.x as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - y / proj.size.y as f32 * 2.0;
+        let unproject = |z: f32| {
+            let p = inv_view_proj * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            Point3::from(p.xyz() / p.w)
+        };
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        let direction = far - near;
+
+        let models = &renderer.entities.models;
+        let mut nearest: Option<(f32, Hit)> = None;
+        for placed in scene.placed() {
+            let transform = placed.instance.transform();
+            let Some(inverse) = transform.try_inverse() else {
+                continue;
+            };
+            // The distance along the ray is the same in model space without normalizing
+            let origin = inverse.transform_point(&near);
+            let local_direction = inverse.transform_vector(&direction);
+            let (vertices, indices) = models.mesh_geometry(placed.model_id, placed.mesh_id);
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| Point3::from(vertices[triangle[i] as usize].position));
+                let Some(t) = ray_triangle(origin, local_direction, a, b, c) else {
+                    continue;
+                };
+                if nearest.as_ref().is_some_and(|(nearest, _)| *nearest <= t) {
+                    continue;
+                }
+                nearest = Some((
+                    t,
+                    Hit {
+                        model_id: placed.model_id,
+                        mesh_id: placed.mesh_id,
+                        transform,
+                        position: near + direction * t,
+                    },
+                ));
+            }
+        }
+        nearest.map(|(_, hit)| hit)
+    }
+
+    pub fn draw(&self, lines: &mut DebugLines) {
+        if let Some(position) = self.hovered {
+            lines.cross(
+                position,
+                self.radius,
+                Color3::new(self.color[0], self.color[1], self.color[2]),
+            );
+        }
+    }
+}
+
+/// Distance along the ray in units of `direction`, both faces are hit
+fn ray_triangle(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    a: Point3<f32>,
+    b: Point3<f32>,
+    c: Point3<f32>,
+) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(&ac);
+    let det = ab.dot(&p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let ao = origin - a;
+    let u = ao.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = ao.cross(&ab);
+    let v = direction.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(&q) * inv_det;
+    (0.0..=1.0).contains(&t).then_some(t)
+}
@@ -0,0 +1,77 @@
+use nalgebra::Point3;
+
+use crate::{
+    graphics::{volumes::PostVolume, GlobalRenderer},
+    locale::{tr, tr_args},
+};
+
+use super::inspect::Inspect;
+
+/// Post processing volumes of the edited scene, given to the renderer whenever they change
+#[derive(Default)]
+pub struct VolumeEditor {
+    selected: Option<usize>,
+    lut_name: String,
+    /// Last volumes given to the renderer, the ones set by the game are left alone until the
+    /// scene has its own
+    synced: Vec<PostVolume>,
+}
+
+impl VolumeEditor {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        volumes: &mut Vec<PostVolume>,
+        renderer: &mut GlobalRenderer,
+        camera: Point3<f32>,
+    ) {
+        ui.checkbox(&mut renderer.volumes.enabled, tr("editor-enabled"));
+        for (i, volume) in volumes.iter().enumerate() {
+            let weight = format!("{:.2}", volume.weight(&camera));
+            let label = tr_args("editor-volume", &[("index", &i), ("weight", &weight)]);
+            if ui
+                .selectable_label(self.selected == Some(i), label)
+                .clicked()
+            {
+                self.selected = (self.selected != Some(i)).then_some(i);
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.button(tr("editor-volumes-add")).clicked() {
+                volumes.push(PostVolume::new(camera, &renderer.settings));
+                self.selected = Some(volumes.len() - 1);
+            }
+            let Some(index) = self.selected.filter(|i| *i < volumes.len()) else {
+                return;
+            };
+            if ui.button(tr("editor-remove")).clicked() {
+                volumes.remove(index);
+                self.selected = None;
+            }
+        });
+
+        let Some(volume) = self.selected.and_then(|i| volumes.get_mut(i)) else {
+            return;
+        };
+        volume.inspect(ui);
+        let lut = volume.lut.clone().unwrap_or_else(|| tr("editor-none"));
+        ui.label(tr_args("editor-lut", &[("name", &lut)]));
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.lut_name);
+            if ui.button(tr("editor-load")).clicked() {
+                volume.lut = Some(self.lut_name.clone());
+            }
+            if ui.button(tr("editor-clear")).clicked() {
+                volume.lut = None;
+            }
+        });
+    }
+
+    /// Gives the volumes to the renderer once they changed, by an edit or a scene load
+    pub fn sync(&mut self, volumes: &[PostVolume], renderer: &mut GlobalRenderer) {
+        if self.synced != volumes {
+            self.synced = volumes.to_vec();
+            renderer.volumes.volumes = self.synced.clone();
+        }
+    }
+}
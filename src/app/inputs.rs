@@ -1,4 +1,5 @@
 use std::{
+    f32::consts::{PI, TAU},
     path::PathBuf,
     time::{Duration, Instant},
 };
@@ -10,6 +11,18 @@ use winit::{
     keyboard::{Key, KeyCode, PhysicalKey},
 };
 
+/// Motion of the touches held during a step
+#[derive(Debug, Clone, Copy)]
+pub struct TouchGesture {
+    pub fingers: usize,
+    /// Mean motion of the touches, in pixels
+    pub pan: (f32, f32),
+    /// Ratio of the distance between the first two touches to its previous value
+    pub pinch: f32,
+    /// Turn of the first two touches around each other, in radians
+    pub rotation: f32,
+}
+
 /// From `winit_input_helper` updated to 3.0
 ///
 /// Create with `WinitInputHelper::new`.
@@ -35,6 +48,8 @@ pub struct Inputs {
     close_requested: bool,
     step_start: Option<Instant>,
     step_duration: Option<Duration>,
+    text_input: bool,
+    gui_text_input: bool,
 }
 
 impl Default for Inputs {
@@ -56,6 +71,8 @@ impl Inputs {
             close_requested: false,
             step_start: None,
             step_duration: None,
+            text_input: false,
+            gui_text_input: false,
         }
     }
 
@@ -347,6 +364,81 @@ impl Inputs {
         (0.0, 0.0)
     }
 
+    /// Returns the fingers and pens touching the window, by touch id, in pixels
+    ///
+    /// Pens are reported by winit as touches on the platforms supporting them
+    pub fn touches(&self) -> &[(u64, (f32, f32))] {
+        match &self.current {
+            Some(current) => &current.touches,
+            None => &[],
+        }
+    }
+
+    /// Returns the motion of the touches held during the whole last step.
+    /// Otherwise returns `None`, when nothing touched the window in both steps.
+    pub fn touch_gesture(&self) -> Option<TouchGesture> {
+        let current = self.current.as_ref()?;
+        let held: Vec<((f32, f32), (f32, f32))> = current
+            .touches
+            .iter()
+            .filter_map(|(id, cur)| {
+                let (_, prev) = current
+                    .touches_prev
+                    .iter()
+                    .find(|(prev_id, _)| prev_id == id)?;
+                Some((*prev, *cur))
+            })
+            .collect();
+        if held.is_empty() {
+            return None;
+        }
+
+        let count = held.len() as f32;
+        let (pan_x, pan_y) = held.iter().fold((0.0, 0.0), |(x, y), (prev, cur)| {
+            (x + cur.0 - prev.0, y + cur.1 - prev.1)
+        });
+        let mut gesture = TouchGesture {
+            fingers: held.len(),
+            pan: (pan_x / count, pan_y / count),
+            pinch: 1.0,
+            rotation: 0.0,
+        };
+        if let [(prev_a, cur_a), (prev_b, cur_b), ..] = held[..] {
+            let span = |a: (f32, f32), b: (f32, f32)| (b.0 - a.0, b.1 - a.1);
+            let (prev, cur) = (span(prev_a, prev_b), span(cur_a, cur_b));
+            let prev_len = prev.0.hypot(prev.1);
+            if prev_len > 1.0 {
+                gesture.pinch = cur.0.hypot(cur.1) / prev_len;
+            }
+            let turn = cur.1.atan2(cur.0) - prev.1.atan2(prev.0);
+            gesture.rotation = (turn + PI).rem_euclid(TAU) - PI;
+        }
+        Some(gesture)
+    }
+
+    /// Returns the text committed by the input method during the last step.
+    pub fn ime_commit(&self) -> &str {
+        match &self.current {
+            Some(current) => &current.ime_commit,
+            None => "",
+        }
+    }
+
+    /// Set while the game's own text field, like a console, has the focus
+    pub fn set_text_input(&mut self, active: bool) {
+        self.text_input = active;
+    }
+
+    /// Set by the engine while an editor text field has the focus
+    pub(crate) fn set_gui_text_input(&mut self, active: bool) {
+        self.gui_text_input = active;
+    }
+
+    /// Returns true while typing goes to a text field, game controls should ignore the keyboard.
+    pub fn text_input(&self) -> bool {
+        self.text_input || self.gui_text_input
+    }
+
     /// Returns the characters pressed during the last step.
     /// The characters are in the order they were pressed.
     pub fn text(&self) -> &[Key] {
@@ -406,7 +498,10 @@ impl Inputs {
 }
 
 pub mod current {
-    use winit::event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+    use winit::event::{
+        DeviceEvent, ElementState, Ime, MouseButton, MouseScrollDelta, Touch, TouchPhase,
+        WindowEvent,
+    };
     use winit::keyboard::{Key, PhysicalKey};
 
     #[derive(Clone)]
@@ -423,6 +518,9 @@ pub mod current {
         pub y_scroll_diff: f32,
         pub x_scroll_diff: f32,
         pub text: Vec<Key>,
+        pub ime_commit: String,
+        pub touches: Vec<(u64, (f32, f32))>,
+        pub touches_prev: Vec<(u64, (f32, f32))>,
     }
 
     impl CurrentInput {
@@ -440,6 +538,9 @@ pub mod current {
                 y_scroll_diff: 0.0,
                 x_scroll_diff: 0.0,
                 text: vec![],
+                ime_commit: String::new(),
+                touches: vec![],
+                touches_prev: vec![],
             }
         }
 
@@ -452,6 +553,8 @@ pub mod current {
             self.y_scroll_diff = 0.0;
             self.x_scroll_diff = 0.0;
             self.text.clear();
+            self.ime_commit.clear();
+            self.touches_prev.clone_from(&self.touches);
         }
 
         pub fn handle_event(&mut self, event: &WindowEvent) {
@@ -512,6 +615,25 @@ pub mod current {
                     self.mouse_held[button_usize] = false;
                     self.mouse_actions.push(MouseAction::Released(*button));
                 }
+                WindowEvent::Ime(Ime::Commit(text)) => self.ime_commit.push_str(text),
+                WindowEvent::Touch(Touch {
+                    phase,
+                    location,
+                    id,
+                    ..
+                }) => {
+                    let point = (location.x as f32, location.y as f32);
+                    let touch = self.touches.iter_mut().find(|(touch_id, _)| touch_id == id);
+                    match (phase, touch) {
+                        (TouchPhase::Started | TouchPhase::Moved, Some(touch)) => touch.1 = point,
+                        (TouchPhase::Started | TouchPhase::Moved, None) => {
+                            self.touches.push((*id, point))
+                        }
+                        (TouchPhase::Ended | TouchPhase::Cancelled, _) => {
+                            self.touches.retain(|(touch_id, _)| touch_id != id)
+                        }
+                    }
+                }
                 WindowEvent::MouseWheel { delta, .. } => {
                     // I just took this from three-rs, no idea why this magic number was chosen ¯\_(ツ)_/¯
                     const PIXELS_PER_LINE: f64 = 38.0;
@@ -0,0 +1,90 @@
+use winit::keyboard::KeyCode;
+
+/// Keys the shortcuts and the controls can be bound to
+pub const BINDABLE: [KeyCode; 62] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Tab,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+];
+
+/// Only the controls can be bound to them, the shortcuts hold them alongside their key
+pub const MODIFIERS: [KeyCode; 3] = [KeyCode::ShiftLeft, KeyCode::ControlLeft, KeyCode::AltLeft];
+
+/// `KeyS` and `Digit1` are shown as `S` and `1`
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    match name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+    {
+        Some(short) => short.to_string(),
+        None => name,
+    }
+}
+
+/// Inverse of [`key_name`], for the keys of [`BINDABLE`] and [`MODIFIERS`]
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE
+        .into_iter()
+        .chain(MODIFIERS)
+        .find(|key| key_name(*key) == name)
+}
@@ -0,0 +1,244 @@
+use egui::{Align2, Slider};
+
+use crate::{
+    game::{
+        controls::{Control, Controls},
+        mouse::MouseSettings,
+        GameState, Mode,
+    },
+    graphics::{ctx::FramePacing, debug_lines::DebugPalette, settings::QualityPreset},
+    locale::{self, tr},
+};
+
+use super::{benchmark::Benchmark, inputs::Inputs, keys, Engine, DISPLAY_SECTION};
+
+/// Asked for by the buttons of the menu, handled once the gui ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuAction {
+    Resume,
+    /// Runs the benchmark picking the quality preset
+    DetectQuality,
+    Save,
+    Quit,
+}
+
+/// Settings shown over the game in [`Mode::Paused`], in its own foreground window above the
+/// editor ones
+///
+/// The gui edits a copy of the settings taken from the engine with [`Self::pull`] and written
+/// back by [`Self::push`], the engine stays free while the gui runs
+#[derive(Default)]
+pub struct PauseMenu {
+    pacing: FramePacing,
+    /// `None` when the render settings were edited by hand
+    quality: Option<QualityPreset>,
+    palette: DebugPalette,
+    #[cfg(feature = "audio")]
+    volume: f32,
+    mouse: MouseSettings,
+    controls: Controls,
+    /// Control waiting for its new key, captured on the next key press
+    rebinding: Option<Control>,
+    /// Code and name of the languages, listed when the menu is first shown
+    languages: Vec<(String, String)>,
+    action: Option<MenuAction>,
+}
+
+impl PauseMenu {
+    /// Binds the key pressed while a control waits for one, Escape resumes the game instead
+    pub fn update(&mut self, inputs: &Inputs, game_state: &mut GameState) {
+        if game_state.mode != Mode::Paused {
+            self.rebinding = None;
+        }
+        let Some(control) = self.rebinding else {
+            return;
+        };
+        let pressed = keys::BINDABLE
+            .into_iter()
+            .chain(keys::MODIFIERS)
+            .find(|key| inputs.key_pressed(*key));
+        if let Some(key) = pressed {
+            game_state.controls.set(control, key);
+            self.rebinding = None;
+        }
+    }
+
+    pub fn pull(&mut self, engine: &Engine) {
+        self.pacing = engine.pacing();
+        let renderer = engine.renderer.lock().unwrap();
+        self.quality = QualityPreset::of(&renderer.settings);
+        self.palette = renderer.debug_lines.palette;
+        #[cfg(feature = "audio")]
+        {
+            self.volume = engine.audio.volume;
+        }
+        self.mouse = engine.game_state.mouse;
+        self.controls = engine.game_state.controls.clone();
+    }
+
+    /// The languages of the new project are listed on the next show
+    pub fn project_opened(&mut self) {
+        self.languages.clear();
+    }
+
+    pub fn ui(&mut self, gui_ctx: &egui::Context) {
+        egui::Window::new(tr("menu-title"))
+            .id(egui::Id::new("Pause menu"))
+            .order(egui::Order::Foreground)
+            .anchor(Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(gui_ctx, |ui| {
+                if ui.button(tr("menu-resume")).clicked() {
+                    self.action = Some(MenuAction::Resume);
+                }
+
+                ui.heading(tr("menu-graphics"));
+                ui.checkbox(&mut self.pacing.vsync, tr("menu-vsync"));
+                ui.checkbox(&mut self.pacing.low_latency, tr("menu-low-latency"));
+                egui::ComboBox::from_label(tr("menu-quality"))
+                    .selected_text(self.quality.map_or(tr("menu-quality-custom"), preset_name))
+                    .show_ui(ui, |ui| {
+                        for preset in QualityPreset::ALL {
+                            ui.selectable_value(
+                                &mut self.quality,
+                                Some(preset),
+                                preset_name(preset),
+                            );
+                        }
+                    });
+                if ui.button(tr("menu-detect-quality")).clicked() {
+                    self.action = Some(MenuAction::DetectQuality);
+                }
+
+                #[cfg(feature = "audio")]
+                {
+                    ui.heading(tr("menu-audio"));
+                    ui.add(Slider::new(&mut self.volume, 0.0..=1.0).text(tr("menu-volume")));
+                }
+
+                ui.heading(tr("menu-mouse"));
+                ui.add(
+                    Slider::new(&mut self.mouse.sensitivity, 0.1..=10.0)
+                        .text(tr("menu-sensitivity")),
+                );
+                ui.checkbox(&mut self.mouse.invert_y, tr("menu-invert-y"));
+
+                ui.heading(tr("menu-controls"));
+                egui::Grid::new("Controls").striped(true).show(ui, |ui| {
+                    for control in Control::ALL {
+                        ui.label(tr(&control.locale_key()));
+                        let text = match self.rebinding {
+                            Some(rebinding) if rebinding == control => tr("menu-press-key"),
+                            _ => keys::key_name(self.controls.key(control)),
+                        };
+                        if ui.button(text).clicked() {
+                            self.rebinding = Some(control);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if ui.button(tr("menu-reset-controls")).clicked() {
+                    self.controls = Controls::default();
+                }
+
+                ui.heading(tr("menu-accessibility"));
+                egui::ComboBox::from_label(tr("menu-debug-palette"))
+                    .selected_text(palette_name(self.palette))
+                    .show_ui(ui, |ui| {
+                        for palette in DebugPalette::ALL {
+                            ui.selectable_value(&mut self.palette, palette, palette_name(palette));
+                        }
+                    });
+
+                if self.languages.is_empty() {
+                    self.languages = locale::languages();
+                }
+                let current = locale::language();
+                egui::ComboBox::from_label(tr("menu-language"))
+                    .selected_text(self.language_name(&current))
+                    .show_ui(ui, |ui| {
+                        for (language, name) in &self.languages {
+                            if ui.selectable_label(*language == current, name).clicked() {
+                                if let Err(e) = locale::set_language(language) {
+                                    eprintln!("Failed to switch the language: {e}");
+                                }
+                            }
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr("menu-save")).clicked() {
+                        self.action = Some(MenuAction::Save);
+                    }
+                    if ui.button(tr("menu-quit")).clicked() {
+                        self.action = Some(MenuAction::Quit);
+                    }
+                });
+            });
+    }
+
+    /// Name of the language from the listed ones, its code when it is not listed
+    fn language_name<'a>(&'a self, language: &'a str) -> &'a str {
+        self.languages
+            .iter()
+            .find(|(code, _)| code == language)
+            .map_or(language, |(_, name)| name)
+    }
+
+    /// Applies the edits and the button pressed, saving writes every setting of the menu to the
+    /// config file
+    pub fn push(&mut self, engine: &mut Engine) {
+        if self.pacing != engine.pacing() {
+            engine.request_pacing(self.pacing);
+        }
+        let mut renderer = engine.renderer.lock().unwrap();
+        let settings = &mut renderer.settings;
+        if let Some(preset) = self
+            .quality
+            .filter(|_| self.quality != QualityPreset::of(settings))
+        {
+            preset.apply(settings);
+        }
+        renderer.debug_lines.palette = self.palette;
+        // Starting a benchmark locks it again
+        drop(renderer);
+        #[cfg(feature = "audio")]
+        {
+            engine.audio.volume = self.volume;
+        }
+        engine.game_state.mouse = self.mouse;
+        engine.game_state.controls = self.controls.clone();
+
+        match self.action.take() {
+            Some(MenuAction::Resume) => engine.game_state.mode = Mode::Game,
+            Some(MenuAction::DetectQuality) => engine.benchmark = Some(Benchmark::start(engine)),
+            Some(MenuAction::Save) => {
+                let config = &mut engine.config;
+                config.set(DISPLAY_SECTION, "vsync", self.pacing.vsync);
+                config.set(DISPLAY_SECTION, "low_latency", self.pacing.low_latency);
+                if let Some(preset) = self.quality {
+                    config.set(DISPLAY_SECTION, "quality", preset.label());
+                }
+                config.set(DISPLAY_SECTION, "debug_palette", self.palette.label());
+                #[cfg(feature = "audio")]
+                engine.audio.write_config(config);
+                self.mouse.write_config(config);
+                self.controls.write_config(config);
+                locale::write_config(config);
+                config.save();
+            }
+            Some(MenuAction::Quit) => engine.exit_requested = true,
+            None => {}
+        }
+    }
+}
+
+pub(crate) fn preset_name(preset: QualityPreset) -> String {
+    tr(&format!("menu-quality-{}", preset.label().to_lowercase()))
+}
+
+pub(crate) fn palette_name(palette: DebugPalette) -> String {
+    tr(&format!("menu-palette-{}", palette.label().to_lowercase()))
+}
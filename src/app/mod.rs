@@ -1,7 +1,18 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
 
+use benchmark::Benchmark;
+use config::Config;
+#[cfg(feature = "editor")]
 use editor::Editor;
 use inputs::Inputs;
+#[cfg(feature = "editor")]
+use menu::PauseMenu;
+use plugin::Plugin;
+use pointer::{PointerClaims, PointerFocus};
 use winit::{
     application::ApplicationHandler,
     event::WindowEvent,
@@ -9,131 +20,570 @@ use winit::{
     window::{CursorGrabMode, Window, WindowAttributes},
 };
 
+#[cfg(feature = "audio")]
+use crate::audio::Audio;
+#[cfg(not(feature = "terrain"))]
+use crate::game::ground::FlatGround;
 use crate::{
     constants,
-    game::GameState,
-    graphics::{camera::Projection, ctx::GraphicsCtx, GlobalRenderer, RenderData},
+    game::{GameState, Mode},
+    graphics::{
+        camera::Projection,
+        ctx::{DisplayOutput, FramePacing, GraphicsCtx},
+        debug_lines::DebugPalette,
+        render_thread::RenderThread,
+        settings::QualityPreset,
+        GlobalRenderer, RenderData,
+    },
+    jobs::{self, Priority},
+    locale, profile_scope, profiler,
+    sequence::SequenceSample,
+    ASSETS,
 };
 
+pub mod benchmark;
+pub mod config;
+#[cfg(feature = "editor")]
 pub mod editor;
 pub mod inputs;
+pub mod keys;
+#[cfg(feature = "editor")]
+pub mod menu;
+pub mod plugin;
+pub mod pointer;
 
-pub struct App {
-    window: Arc<Window>,
-    inputs: Inputs,
-
-    graphics: GraphicsCtx,
-    proj: Projection,
-    renderer: GlobalRenderer,
+/// Config section of the surface settings
+const DISPLAY_SECTION: &str = "display";
 
-    editor: Editor,
-    game_state: GameState,
+/// Config section of the project opened at startup, `root` is its assets folder
+const PROJECT_SECTION: &str = "project";
 
-    last_update: Instant,
+/// `output = hdr` in the `[display]` section requests an hdr surface, read once at startup
+fn display_output(config: &Config) -> DisplayOutput {
+    let Some(name) = config.get(DISPLAY_SECTION, "output") else {
+        return DisplayOutput::default();
+    };
+    DisplayOutput::parse(name).unwrap_or_else(|| {
+        eprintln!("Unknown display output {name:?}, expected sdr or hdr");
+        DisplayOutput::default()
+    })
 }
 
-impl App {
-    pub fn run() {
-        let event_loop = event_loop::EventLoop::new().expect("Failed to create event loop");
-        event_loop.set_control_flow(event_loop::ControlFlow::Poll);
-        event_loop
-            .run_app(&mut AppRunner::default())
-            .unwrap_or_else(|e| panic!("Failed to run app: {e}"));
+/// `vsync` and `low_latency` in the `[display]` section, saved from the pause menu with the
+/// `quality` preset, or `Auto` to run a [`Benchmark`] picking it, and the `debug_palette`.
+/// `render_thread = false` submits and presents the frames on the update thread
+fn frame_pacing(config: &Config) -> FramePacing {
+    let mut pacing = FramePacing::default();
+    let get = |key: &str| {
+        config
+            .get(DISPLAY_SECTION, key)
+            .and_then(|v| v.parse().ok())
+    };
+    if let Some(vsync) = get("vsync") {
+        pacing.vsync = vsync;
+    }
+    if let Some(low_latency) = get("low_latency") {
+        pacing.low_latency = low_latency;
     }
+    if let Some(render_thread) = get("render_thread") {
+        pacing.render_thread = render_thread;
+    }
+    pacing
+}
+
+/// Engine owned state, handed to the [`GameApp`] hooks
+pub struct Engine {
+    pub window: Arc<Window>,
+    pub inputs: Inputs,
+    pub pointer: PointerFocus,
+
+    /// Shared with the [`RenderThread`], write locked to configure the surface
+    pub graphics: Arc<RwLock<GraphicsCtx>>,
+    pub proj: Projection,
+    pub renderer: Arc<Mutex<GlobalRenderer>>,
+
+    /// Camera and built-in gameplay systems
+    pub game_state: GameState,
+    #[cfg(feature = "audio")]
+    pub audio: Audio,
+    pub config: Config,
+    /// Closes the app at the end of the frame
+    pub exit_requested: bool,
+    /// Picking the quality preset, the camera is driven by it until done
+    pub benchmark: Option<Benchmark>,
+    /// Given to [`Self::request_pacing`], applied before the next update
+    pacing_request: Option<FramePacing>,
 
-    fn init(event_loop: &ActiveEventLoop) -> Self {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+/// Game specific code run by the engine loop, every hook defaults to doing nothing
+pub trait GameApp {
+    /// Called once, after the window, the renderer and the assets are ready
+    fn init(&mut self, _engine: &mut Engine) {}
+
+    /// Called every frame after the built-in systems, before the camera is uploaded
+    fn update(&mut self, _engine: &mut Engine, _dt: Duration) {}
+
+    /// Called before the editor and the submission of the frame
+    fn render(&mut self, _engine: &mut Engine) {}
+
+    /// Called once another project is opened, the renderer and the game state were rebuilt from
+    /// its assets
+    fn project_opened(&mut self, _engine: &mut Engine) {}
+}
+
+impl Engine {
+    fn new(event_loop: &ActiveEventLoop) -> Self {
         let window: Arc<_> = event_loop
             .create_window(WindowAttributes::default().with_title(constants::WINDOW_TITLE))
             .expect("Failed to create window")
             .into();
 
+        let config = Config::load();
+        // Files are read while the graphics device is being acquired
+        let project = config.get(PROJECT_SECTION, "root").map(PathBuf::from);
+        let assets = jobs::spawn("Assets", Priority::High, move || {
+            if let Some(root) = project {
+                if let Err(e) = ASSETS.open(&root) {
+                    eprintln!("Failed to open project {}: {e}", root.display());
+                }
+            }
+            ASSETS.force();
+        });
+
         let inputs = Inputs::default();
-        let graphics = GraphicsCtx::new(window.clone());
+        let mut graphics = GraphicsCtx::new(window.clone(), display_output(&config));
+        graphics.set_pacing(frame_pacing(&config));
+        assets.wait().expect("Failed to load game assets");
+        locale::load_config(&config);
         let (w, h) = window.inner_size().into();
-        let proj = Projection {
-            size: [w, h].into(),
-            fov_deg: 90.0,
-        };
-        let renderer = GlobalRenderer::new(&graphics);
-        let editor_state = Editor::new(&window);
-        let game_state = GameState::new();
-        let last_update = Instant::now();
+        let proj = Projection::new([w, h].into(), 90.0);
+        let mut renderer = GlobalRenderer::new(&graphics);
+        let mut auto_quality = false;
+        match config.get(DISPLAY_SECTION, "quality") {
+            Some("Auto") => auto_quality = true,
+            Some(name) => match QualityPreset::parse(name) {
+                Some(preset) => preset.apply(&mut renderer.settings),
+                None => eprintln!("Unknown quality preset {name:?}"),
+            },
+            None => {}
+        }
+        if let Some(name) = config.get(DISPLAY_SECTION, "debug_palette") {
+            match DebugPalette::parse(name) {
+                Some(palette) => renderer.debug_lines.palette = palette,
+                None => eprintln!("Unknown debug palette {name:?}"),
+            }
+        }
 
-        App {
+        let mut engine = Self {
             window,
             inputs,
-            graphics,
+            pointer: PointerFocus::default(),
+            graphics: Arc::new(RwLock::new(graphics)),
             proj,
-            renderer,
-            editor: editor_state,
-            game_state,
-            last_update,
+            renderer: Arc::new(Mutex::new(renderer)),
+            game_state: GameState::new(&config),
+            #[cfg(feature = "audio")]
+            audio: Audio::new(&config),
+            config,
+            exit_requested: false,
+            benchmark: None,
+            pacing_request: None,
+            plugins: Vec::new(),
+        };
+        if auto_quality {
+            engine.benchmark = Some(Benchmark::start(&mut engine));
+        }
+        engine
+    }
+
+    /// Pacing of the next frames, the requested one until it is applied
+    pub fn pacing(&self) -> FramePacing {
+        self.pacing_request
+            .unwrap_or_else(|| self.graphics.read().unwrap().pacing())
+    }
+
+    /// Changes the pacing before the next update, the surface is configured by write locking the
+    /// graphics context which waits for the frame the render thread may still be presenting
+    pub fn request_pacing(&mut self, pacing: FramePacing) {
+        self.pacing_request = Some(pacing);
+    }
+
+    pub fn add_plugin(&mut self, mut plugin: impl Plugin + 'static) {
+        plugin.setup(self);
+        self.plugins.push(Box::new(plugin));
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.plugins.iter().map(|plugin| plugin.as_ref())
+    }
+
+    fn update_plugins(&mut self, dt: Duration) {
+        // Taken out while they run, they get the whole engine and may add plugins themselves
+        let mut plugins = std::mem::take(&mut self.plugins);
+        for plugin in &mut plugins {
+            plugin.update(self, dt);
+        }
+        plugins.append(&mut self.plugins);
+        self.plugins = plugins;
+    }
+
+    /// Switches to the assets at `root` without restarting: the renderer and the game state are
+    /// rebuilt from them, the mode is kept. The current project stays open if they fail to load
+    pub fn open_project(&mut self, root: &Path) -> Result<(), String> {
+        ASSETS.open(root)?;
+        locale::load_config(&self.config);
+        {
+            let graphics = self.graphics.read().unwrap();
+            let mut renderer = self.renderer.lock().unwrap();
+            renderer.reload_assets(&graphics);
+            renderer.update_proj(&graphics, &self.proj);
+        }
+        let mode = self.game_state.mode;
+        self.game_state = GameState::new(&self.config);
+        self.game_state.mode = mode;
+        self.config.set(PROJECT_SECTION, "root", root.display());
+        self.config.save();
+        println!("Opened project {}", root.display());
+        Ok(())
+    }
+
+    /// Camera, fov and lights of a playing cutscene, the keys are at absolute positions
+    fn apply_sequence(&mut self, sample: SequenceSample) {
+        let graphics = self.graphics.read().unwrap();
+        let mut renderer = self.renderer.lock().unwrap();
+        if let Some((mut camera, fov_deg)) = sample.camera {
+            camera.eye = renderer.origin.to_relative(&camera.eye.cast::<f64>());
+            self.game_state.camera = camera;
+            self.proj.fov_deg = fov_deg;
+            renderer.update_proj(&graphics, &self.proj);
+        }
+        // Keys of lights the scene no longer has are skipped
+        let lights = &mut renderer.lights;
+        for (index, color, intensity) in sample.lights {
+            if let Some(light) = lights.get(index).copied() {
+                let mut light = light.with_intensity(intensity);
+                light.color = color.into();
+                lights.set(index, light);
+            }
+        }
+    }
+
+    fn resize_viewport(&mut self) {
+        let (w, h): (u32, u32) = self.window.inner_size().into();
+        self.proj.size = [w, h].into();
+        // Waits for the frame the render thread may still be presenting
+        let mut graphics = self.graphics.write().unwrap();
+        graphics.resize((w, h));
+        let mut renderer = self.renderer.lock().unwrap();
+        renderer.update_proj(&graphics, &self.proj);
+        renderer.update_viewport_size(&graphics);
+    }
+}
+
+pub struct App<G: GameApp> {
+    engine: Engine,
+    #[cfg(feature = "editor")]
+    editor: Editor,
+    #[cfg(feature = "editor")]
+    menu: PauseMenu,
+    game: G,
+    /// Running while [`FramePacing::uses_render_thread`], frames are submitted and presented by
+    /// the update thread otherwise
+    render_thread: Option<RenderThread>,
+
+    last_update: Instant,
+}
+
+impl<G: GameApp> App<G> {
+    pub fn run(game: G) {
+        if let Some(code) = crate::validate::run_from_args() {
+            std::process::exit(code);
+        }
+        let event_loop = event_loop::EventLoop::new().expect("Failed to create event loop");
+        event_loop.set_control_flow(event_loop::ControlFlow::Poll);
+        event_loop
+            .run_app(&mut AppRunner {
+                game: Some(game),
+                app: None,
+            })
+            .unwrap_or_else(|e| panic!("Failed to run app: {e}"));
+    }
+
+    fn init(event_loop: &ActiveEventLoop, mut game: G) -> Self {
+        let mut engine = Engine::new(event_loop);
+        #[cfg(feature = "editor")]
+        let editor = Editor::new(&engine.window, &engine.config);
+        game.init(&mut engine);
+
+        App {
+            engine,
+            #[cfg(feature = "editor")]
+            editor,
+            #[cfg(feature = "editor")]
+            menu: PauseMenu::default(),
+            game,
+            render_thread: None,
+            last_update: Instant::now(),
         }
     }
 
     fn render(&mut self) {
-        let window_size: (u32, u32) = self.window.inner_size().into();
+        let engine = &mut self.engine;
+        let window_size: (u32, u32) = engine.window.inner_size().into();
         if window_size.0 < 1 || window_size.1 < 1 {
             return;
         }
 
-        let egui_input = self.editor.gui_state.take_egui_input(&self.window);
-        let (egui_output, egui_ctx) = self.editor.run(
-            &mut self.renderer,
-            egui_input,
-            &mut self.game_state,
-            &mut self.proj,
-        );
+        profile_scope!("Render");
+        self.game.render(engine);
+        #[cfg(feature = "editor")]
+        let (egui_output, egui_ctx) = {
+            let egui_input = self.editor.gui_state.take_egui_input(&engine.window);
+            let paused = engine.game_state.mode == Mode::Paused;
+            if paused {
+                self.menu.pull(engine);
+            }
+            let menu = &mut self.menu;
+            let (mut output, ctx) = self.editor.run(
+                &engine.graphics.read().unwrap(),
+                &mut engine.renderer.lock().unwrap(),
+                egui_input,
+                &mut engine.game_state,
+                &mut engine.proj,
+                &mut engine.config,
+                |gui_ctx| {
+                    if paused {
+                        menu.ui(gui_ctx);
+                    }
+                },
+            );
+            if let Some(pacing) = self.editor.take_pacing_request() {
+                engine.request_pacing(pacing);
+            }
+            if paused {
+                self.menu.push(engine);
+            }
+            // Ime state, clipboard and cursor icon, the cursor stays hidden in game mode
+            let platform_output = std::mem::take(&mut output.platform_output);
+            if !engine.game_state.playing() {
+                self.editor
+                    .gui_state
+                    .handle_platform_output(&engine.window, platform_output);
+            }
+            // The projection can be edited from the editor
+            let graphics = engine.graphics.read().unwrap();
+            engine
+                .renderer
+                .lock()
+                .unwrap()
+                .update_proj(&graphics, &engine.proj);
+            (output, ctx)
+        };
 
         let render_data = RenderData {
             window_size,
-            aspect_ratio: self.window.scale_factor() as f32,
+            aspect_ratio: engine.window.scale_factor() as f32,
+            camera_position: engine.game_state.camera.eye,
 
+            #[cfg(feature = "editor")]
             egui_ctx,
+            #[cfg(feature = "editor")]
             egui_output,
         };
 
-        self.renderer.submit(&self.graphics, render_data);
-        self.window.request_redraw();
+        let threaded = engine
+            .graphics
+            .read()
+            .unwrap()
+            .pacing()
+            .uses_render_thread();
+        if threaded != self.render_thread.is_some() {
+            // Dropping it waits for the frame it was handed
+            self.render_thread = threaded.then(|| RenderThread::new(engine.graphics.clone()));
+        }
+        match &self.render_thread {
+            Some(render_thread) => {
+                let frame = {
+                    let graphics = engine.graphics.read().unwrap();
+                    let mut renderer = engine.renderer.lock().unwrap();
+                    let snapshot = renderer.prepare(&graphics, render_data);
+                    renderer.record_offscreen(&graphics, snapshot)
+                };
+                render_thread.submit(frame);
+            }
+            None => {
+                let graphics = engine.graphics.read().unwrap();
+                engine
+                    .renderer
+                    .lock()
+                    .unwrap()
+                    .submit(&graphics, render_data);
+            }
+        }
+        engine.window.request_redraw();
     }
 
     fn update(&mut self) {
+        profiler::new_frame();
+        if let Some(pacing) = self.engine.pacing_request.take() {
+            self.engine.graphics.write().unwrap().set_pacing(pacing);
+        }
+        // Before the inputs are read, they are as recent as possible once the frame is shown
+        self.engine.graphics.read().unwrap().pace();
+        profile_scope!("Update");
+        let engine = &mut self.engine;
         let dt = self.last_update.elapsed();
         self.last_update = Instant::now();
-        if self.game_state.paused {
-            self.window.set_cursor_grab(CursorGrabMode::None).unwrap();
-            self.window.set_cursor_visible(true);
-        } else {
-            self.window
-                .set_cursor_grab(CursorGrabMode::Confined)
-                .or_else(|_e| self.window.set_cursor_grab(CursorGrabMode::Locked))
-                .unwrap();
-            self.window.set_cursor_visible(false);
-        }
-        self.game_state.update(&self.inputs, dt);
-
-        self.renderer
-            .camera
-            .update_view(&self.graphics, &self.game_state.camera);
-        self.inputs.step();
-    }
+        {
+            profile_scope!("Job completions");
+            jobs::run_completions(jobs::COMPLETION_BUDGET);
+        }
+        // Before anything reads the renderer or the game state of the previous project
+        #[cfg(feature = "editor")]
+        if let Some(root) = self.editor.take_project_request() {
+            match engine.open_project(&root) {
+                Ok(()) => {
+                    self.editor.open_project(
+                        &engine.config,
+                        &mut engine.renderer.lock().unwrap(),
+                        &mut engine.game_state,
+                    );
+                    self.menu.project_opened();
+                    self.game.project_opened(engine);
+                }
+                Err(e) => eprintln!("Failed to open project {}: {e}", root.display()),
+            }
+        }
+        match engine.game_state.mode {
+            Mode::Editor | Mode::Paused => {
+                engine.window.set_cursor_grab(CursorGrabMode::None).unwrap();
+                engine.window.set_cursor_visible(true);
+            }
+            Mode::Game => {
+                engine
+                    .window
+                    .set_cursor_grab(CursorGrabMode::Confined)
+                    .or_else(|_e| engine.window.set_cursor_grab(CursorGrabMode::Locked))
+                    .unwrap();
+                engine.window.set_cursor_visible(false);
+            }
+        }
 
-    fn resize_viewport(&mut self) {
-        let (w, h): (u32, u32) = self.window.inner_size().into();
-        self.proj.size = [w, h].into();
-        self.renderer.camera.update_proj(&self.graphics, &self.proj);
+        // Before anything writes instances, they are already relative to the new origin
+        let graphics = engine.graphics.read().unwrap();
+        let mut renderer_guard = engine.renderer.lock().unwrap();
+        let renderer = &mut *renderer_guard;
+        if let Some(shift) = renderer
+            .origin
+            .rebase_shift(&renderer.settings.origin, &engine.game_state.camera.eye)
+        {
+            renderer.rebase(&graphics, shift);
+            engine.game_state.rebase(shift);
+            #[cfg(feature = "editor")]
+            self.editor.rebase(shift);
+        }
 
-        self.graphics.resize((w, h));
-        self.renderer.update_viewport_size(&self.graphics);
+        #[cfg(feature = "editor")]
+        engine
+            .inputs
+            .set_gui_text_input(self.editor.gui_ctx.wants_keyboard_input());
+
+        let mut claims = PointerClaims::default();
+        engine.game_state.claim_pointer(&engine.inputs, &mut claims);
+        #[cfg(feature = "editor")]
+        self.editor
+            .claim_pointer(&mut claims, engine.game_state.mode);
+        engine.pointer.route(&engine.inputs, claims);
+
+        engine
+            .game_state
+            .update(&engine.inputs, &engine.pointer, dt);
+        #[cfg(feature = "editor")]
+        {
+            self.menu.update(&engine.inputs, &mut engine.game_state);
+            self.editor.handle_shortcuts(
+                &engine.inputs,
+                renderer,
+                &mut engine.game_state,
+                &engine.proj,
+            );
+            self.editor.update(
+                &engine.inputs,
+                &engine.pointer,
+                renderer,
+                &mut engine.game_state,
+                &engine.proj,
+                dt,
+            );
+        }
+        profile_scope!("Gameplay");
+        #[cfg(feature = "terrain")]
+        let ground = &renderer.terrain.height;
+        #[cfg(not(feature = "terrain"))]
+        let ground = &FlatGround;
+        engine
+            .game_state
+            .update_world(&mut renderer.entities, ground, &engine.inputs, dt);
+        engine
+            .game_state
+            .queue_widgets(&mut renderer.world_bars, renderer.debug_lines.palette);
+
+        // Drained even without audio, they would pile up otherwise
+        let mut sounds = engine.game_state.feedback.take_sounds();
+        sounds.extend(engine.game_state.weather.take_sounds());
+        #[cfg(feature = "physics")]
+        sounds.extend(engine.game_state.puppets.take_sounds());
+        #[cfg(feature = "audio")]
+        for sound in sounds {
+            engine.audio.play(sound);
+        }
+        #[cfg(not(feature = "audio"))]
+        drop(sounds);
+        renderer.weather.state = engine.game_state.weather.state;
+        renderer.weather.seed = engine.game_state.random.stream_seed("precipitation");
+        // The plugins and the game lock them again
+        drop(renderer_guard);
+        drop(graphics);
+
+        engine.update_plugins(dt);
+        self.game.update(engine, dt);
+
+        // Over the camera controls and the editor
+        if let Some(sample) = engine.game_state.cutscene.update(dt) {
+            engine.apply_sequence(sample);
+        }
+
+        if let Some(mut benchmark) = engine.benchmark.take() {
+            match benchmark.update(engine, dt) {
+                Some(preset) => benchmark.finish(engine, preset),
+                None => engine.benchmark = Some(benchmark),
+            }
+        }
+
+        let graphics = engine.graphics.read().unwrap();
+        engine
+            .renderer
+            .lock()
+            .unwrap()
+            .camera
+            .update_view(&graphics, &engine.game_state.view_camera());
+        engine.inputs.step();
     }
 }
 
-#[derive(Default)]
-struct AppRunner(Option<App>);
+struct AppRunner<G: GameApp> {
+    /// Moved into the app once the event loop is running
+    game: Option<G>,
+    app: Option<App<G>>,
+}
 
-impl ApplicationHandler for AppRunner {
+impl<G: GameApp> ApplicationHandler for AppRunner<G> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.0 = Some(App::init(event_loop));
+        if let Some(game) = self.game.take() {
+            self.app = Some(App::init(event_loop, game));
+        }
     }
 
     fn window_event(
@@ -142,19 +592,28 @@ impl ApplicationHandler for AppRunner {
         _: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        if let Some(app) = &mut self.0 {
-            app.inputs.process_window_event(&event);
-            let _ = app.editor.gui_state.on_window_event(&app.window, &event);
+        if let Some(app) = &mut self.app {
+            app.engine.inputs.process_window_event(&event);
+            #[cfg(feature = "editor")]
+            if app.engine.pointer.forwards_to_gui(&event) {
+                let _ = app
+                    .editor
+                    .gui_state
+                    .on_window_event(&app.engine.window, &event);
+            }
 
             match event {
                 WindowEvent::CloseRequested | WindowEvent::Destroyed => {
                     event_loop.exit();
                 }
                 WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
-                    app.resize_viewport();
+                    app.engine.resize_viewport();
                 }
                 WindowEvent::RedrawRequested => {
                     app.render();
+                    if app.engine.exit_requested {
+                        event_loop.exit();
+                    }
                 }
                 _ => {}
             }
@@ -167,8 +626,9 @@ impl ApplicationHandler for AppRunner {
         _: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        if let Some(app) = &mut self.0 {
-            app.inputs.process_device_event(&event);
+        if let Some(app) = &mut self.app {
+            app.engine.inputs.process_device_event(&event);
+            #[cfg(feature = "editor")]
             if let winit::event::DeviceEvent::MouseMotion { delta } = event {
                 app.editor.gui_state.on_mouse_motion(delta);
             }
@@ -176,7 +636,7 @@ impl ApplicationHandler for AppRunner {
     }
 
     fn about_to_wait(&mut self, _: &event_loop::ActiveEventLoop) {
-        if let Some(app) = &mut self.0 {
+        if let Some(app) = &mut self.app {
             app.update();
         }
     }
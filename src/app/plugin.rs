@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+use super::Engine;
+
+/// System added to the engine loop by a downstream crate
+///
+/// Passes are added separately, see [`crate::graphics::plugin::RenderPlugin`]. A plugin can
+/// register its own from `setup` through `engine.renderer.lock().unwrap().add_plugin`
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Called once when the plugin is added
+    fn setup(&mut self, _engine: &mut Engine) {}
+
+    /// Called every frame after the built-in systems, before the game update
+    fn update(&mut self, _engine: &mut Engine, _dt: Duration) {}
+}
@@ -0,0 +1,83 @@
+use winit::event::{MouseButton, WindowEvent};
+
+use super::inputs::Inputs;
+
+const BUTTONS: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+/// Single consumer of the mouse for a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointerOwner {
+    #[default]
+    None,
+    /// Editor windows and widgets
+    Ui,
+    /// Viewport orientation gizmo
+    Gizmo,
+    /// Mouse look, and the gameplay clicks in game mode
+    Camera,
+    /// Clicks on the scene in editor mode, picking tools
+    Scene,
+}
+
+/// Consumers asking for the mouse this frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointerClaims {
+    pub ui: bool,
+    pub gizmo: bool,
+    pub camera: bool,
+    pub scene: bool,
+}
+
+/// Routes the mouse to exactly one consumer per frame
+///
+/// The owner is the first claim of gizmo, ui, camera and scene. A consumer owning the mouse
+/// when a button is pressed keeps it until every button is released, so drags started on a
+/// window never turn the camera and the other way around
+#[derive(Debug, Default)]
+pub struct PointerFocus {
+    owner: PointerOwner,
+    captured: bool,
+}
+
+impl PointerFocus {
+    /// Called once per frame, before any consumer reads the mouse
+    pub fn route(&mut self, inputs: &Inputs, claims: PointerClaims) {
+        let held = BUTTONS.iter().any(|button| inputs.mouse_held(*button));
+        if self.captured && held {
+            return;
+        }
+
+        self.owner = if claims.gizmo {
+            PointerOwner::Gizmo
+        } else if claims.ui {
+            PointerOwner::Ui
+        } else if claims.camera {
+            PointerOwner::Camera
+        } else if claims.scene {
+            PointerOwner::Scene
+        } else {
+            PointerOwner::None
+        };
+        self.captured = held && self.owner != PointerOwner::None;
+    }
+
+    pub fn owner(&self) -> PointerOwner {
+        self.owner
+    }
+
+    pub fn is(&self, owner: PointerOwner) -> bool {
+        self.owner == owner
+    }
+
+    /// Buttons and wheel are only forwarded to the gui while nothing else owns the mouse,
+    /// the cursor position always is so hovering keeps working
+    pub fn forwards_to_gui(&self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. } => matches!(
+                self.owner,
+                PointerOwner::None | PointerOwner::Ui | PointerOwner::Gizmo
+            ),
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use asset_tree::{
+    loader::{AssetLoader, StdOsLoader},
+    Asset,
+};
+
+use crate::assets::AssetsFolder;
+
+/// Asset tree of the open project, loaded on first use from the default root until
+/// [`Self::open`] switches to another one
+///
+/// Readers hold the tree through [`Self::tree`], a replaced tree is dropped once the last of
+/// them lets it go
+pub struct AssetServer {
+    default_root: &'static str,
+    root: RwLock<Option<PathBuf>>,
+    tree: RwLock<Option<Arc<AssetsFolder>>>,
+}
+
+impl AssetServer {
+    pub const fn new(default_root: &'static str) -> Self {
+        Self {
+            default_root,
+            root: RwLock::new(None),
+            tree: RwLock::new(None),
+        }
+    }
+
+    /// Folder the assets are read from, the files written by the editor go there too
+    pub fn root(&self) -> PathBuf {
+        self.root
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(self.default_root))
+    }
+
+    /// Loads the tree now if it is not yet
+    pub fn force(&self) {
+        self.tree();
+    }
+
+    /// Loads the assets of the project at `root` and makes it the open one, the current project
+    /// is kept if they fail to load
+    pub fn open(&self, root: &Path) -> Result<(), String> {
+        let tree = load_tree(root)?;
+        *self.tree.write().unwrap() = Some(Arc::new(tree));
+        *self.root.write().unwrap() = Some(root.to_path_buf());
+        Ok(())
+    }
+
+    /// Assets of the open project, loaded now if they are not yet. The handle keeps them alive
+    /// when another project is opened meanwhile
+    pub fn tree(&self) -> Arc<AssetsFolder> {
+        if let Some(tree) = &*self.tree.read().unwrap() {
+            return tree.clone();
+        }
+        let mut tree = self.tree.write().unwrap();
+        // Another thread may have loaded it while the lock was released
+        tree.get_or_insert_with(|| {
+            Arc::new(load_tree(&self.root()).expect("Failed to load game assets"))
+        })
+        .clone()
+    }
+}
+
+fn load_tree(root: &Path) -> Result<AssetsFolder, String> {
+    let loader = <StdOsLoader as AssetLoader>::new(root.to_string_lossy().into_owned())
+        .map_err(|e| format!("Assets platform is not supported: {e:?}"))?;
+    <AssetsFolder as Asset>::load(&loader)
+        .map_err(|e| format!("Failed to load the assets of {}: {e:?}", root.display()))
+}
@@ -0,0 +1,51 @@
+use rodio::{source::SineWave, OutputStream, OutputStreamHandle, Source};
+
+use crate::{app::config::Config, game::feedback::SoundCue};
+
+/// Config section holding the audio settings
+const CONFIG_SECTION: &str = "audio";
+
+/// Default output device, stays silent when none could be opened
+pub struct Audio {
+    _stream: Option<OutputStream>,
+    handle: Option<OutputStreamHandle>,
+    /// Master volume, scales every sound, persisted in the config file
+    pub volume: f32,
+}
+
+impl Audio {
+    pub fn new(config: &Config) -> Self {
+        let volume = config
+            .get(CONFIG_SECTION, "volume")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1.0);
+        let (stream, handle) = match OutputStream::try_default() {
+            Ok((stream, handle)) => (Some(stream), Some(handle)),
+            Err(e) => {
+                eprintln!("Audio is disabled, could not open the output device: {e}");
+                (None, None)
+            }
+        };
+        Self {
+            _stream: stream,
+            handle,
+            volume,
+        }
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        config.set(CONFIG_SECTION, "volume", self.volume);
+    }
+
+    pub fn play(&self, cue: SoundCue) {
+        let Some(handle) = &self.handle else {
+            return;
+        };
+        let source = SineWave::new(cue.frequency)
+            .take_duration(cue.duration)
+            .amplify(cue.volume * self.volume);
+        if let Err(e) = handle.play_raw(source) {
+            eprintln!("Failed to play sound: {e}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Viewer of the editor gui of a game built with the `remote-editor` feature, connected to the
+//! `address` of its `[remote_editor]` config section
+//!
+//! ```text
+//! editor-remote [address]
+//! ```
+//!
+//! The game view stays clean while the editor is connected, the gui is drawn locally again once
+//! the viewer is closed
+
+use std::{io::ErrorKind, net::TcpStream, sync::Arc};
+
+use egui_wgpu::ScreenDescriptor;
+use foreigntech2::{
+    app::editor::{
+        remote::{RemoteFrame, RemoteInput, DEFAULT_ADDRESS},
+        EguiWinitState,
+    },
+    graphics::{
+        ctx::{DisplayOutput, GraphicsCtx},
+        EguiRenderer,
+    },
+};
+use tungstenite::{Message, WebSocket};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowAttributes, WindowId},
+};
+
+struct Viewer {
+    window: Arc<Window>,
+    graphics: GraphicsCtx,
+    gui_state: EguiWinitState,
+    renderer: EguiRenderer,
+    socket: WebSocket<TcpStream>,
+    /// Last frame received, drawn until the next one
+    frame: Option<RemoteFrame>,
+}
+
+impl Viewer {
+    fn connect(event_loop: &ActiveEventLoop, address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| e.to_string())?;
+        let (socket, _) =
+            tungstenite::client(format!("ws://{address}/"), stream).map_err(|e| e.to_string())?;
+        socket
+            .get_ref()
+            .set_nonblocking(true)
+            .map_err(|e| e.to_string())?;
+
+        let window: Arc<_> = event_loop
+            .create_window(WindowAttributes::default().with_title(format!("Editor - {address}")))
+            .map_err(|e| e.to_string())?
+            .into();
+        let graphics = GraphicsCtx::new(window.clone(), DisplayOutput::Sdr);
+        // Only gathers the inputs, the gui runs in the game
+        let gui_ctx = egui::Context::default();
+        let gui_state = EguiWinitState::new(
+            gui_ctx.clone(),
+            gui_ctx.viewport_id(),
+            &window,
+            Some(window.scale_factor() as f32),
+            None,
+            None,
+        );
+        let renderer = EguiRenderer::new(&graphics.device, graphics.surface_format, None, 1, false);
+        Ok(Self {
+            window,
+            graphics,
+            gui_state,
+            renderer,
+            socket,
+            frame: None,
+        })
+    }
+
+    /// Sends the inputs, applies the frames received since the last call and draws the last one
+    fn redraw(&mut self) -> Result<(), String> {
+        self.graphics.pace();
+        let raw = self.gui_state.take_egui_input(&self.window);
+        let input = RemoteInput {
+            screen_size: raw.screen_rect.map_or(egui::Vec2::ZERO, |rect| rect.size()),
+            pixels_per_point: self.window.scale_factor() as f32,
+            modifiers: raw.modifiers,
+            events: raw.events,
+        };
+        match self.socket.send(Message::binary(input.encode())) {
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+            result => result.map_err(|e| e.to_string())?,
+        }
+
+        loop {
+            match self.socket.read() {
+                Ok(Message::Binary(data)) => {
+                    let frame = RemoteFrame::decode(&data)?;
+                    for (id, delta) in &frame.textures.set {
+                        self.renderer.update_texture(
+                            &self.graphics.device,
+                            &self.graphics.queue,
+                            *id,
+                            delta,
+                        );
+                    }
+                    for id in &frame.textures.free {
+                        self.renderer.free_texture(id);
+                    }
+                    self.frame = Some(frame);
+                }
+                Ok(Message::Close(_)) => return Err("Closed by the game".to_string()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let Some(mut target) = self.graphics.next_frame() else {
+            return Ok(());
+        };
+        let (width, height) = self.window.inner_size().into();
+        let (primitives, pixels_per_point) = match &self.frame {
+            Some(frame) => (&frame.primitives[..], frame.pixels_per_point),
+            None => (&[][..], self.window.scale_factor() as f32),
+        };
+        let screen = ScreenDescriptor {
+            size_in_pixels: [width, height],
+            pixels_per_point,
+        };
+        let commands = self.renderer.update_buffers(
+            &self.graphics.device,
+            &self.graphics.queue,
+            &mut target.encoder,
+            primitives,
+            &screen,
+        );
+        let mut pass = target
+            .encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Remote editor"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            })
+            .forget_lifetime();
+        self.renderer.render(&mut pass, primitives, &screen);
+        drop(pass);
+        target.present(&self.graphics, commands);
+        Ok(())
+    }
+}
+
+struct ViewerApp {
+    address: String,
+    viewer: Option<Viewer>,
+}
+
+impl ApplicationHandler for ViewerApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.viewer.is_some() {
+            return;
+        }
+        match Viewer::connect(event_loop, &self.address) {
+            Ok(viewer) => self.viewer = Some(viewer),
+            Err(e) => {
+                eprintln!("Failed to connect to the editor at {}: {e}", self.address);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(viewer) = &mut self.viewer else {
+            return;
+        };
+        let _ = viewer.gui_state.on_window_event(&viewer.window, &event);
+        match event {
+            WindowEvent::CloseRequested | WindowEvent::Destroyed => event_loop.exit(),
+            WindowEvent::Resized(size) => viewer.graphics.resize(size.into()),
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = viewer.redraw() {
+                    eprintln!("Disconnected from the editor: {e}");
+                    event_loop.exit();
+                }
+                viewer.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or(DEFAULT_ADDRESS.to_string());
+    let event_loop = EventLoop::new().expect("Failed to create event loop");
+    event_loop
+        .run_app(&mut ViewerApp {
+            address,
+            viewer: None,
+        })
+        .unwrap_or_else(|e| panic!("Failed to run the viewer: {e}"));
+}
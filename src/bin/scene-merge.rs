@@ -0,0 +1,50 @@
+//! Three-way merge of scene files, as a git merge driver
+//!
+//! ```text
+//! # .gitattributes
+//! *.scene merge=scene
+//! # .git/config
+//! [merge "scene"]
+//!     driver = scene-merge %O %A %B
+//! ```
+//!
+//! The merged scene replaces `ours`, exits with 1 when some entities conflict so git reports
+//! the file as conflicted. Conflicting entities keep our version
+
+use std::process::ExitCode;
+
+use foreigntech2::{scene::Scene, scene_diff};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [base, ours, theirs] = &args[..] else {
+        eprintln!("Usage: scene-merge <base> <ours> <theirs>");
+        return ExitCode::from(2);
+    };
+    let read = |path: &String| {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| Scene::parse(&src))
+            .map_err(|e| format!("{path}: {e}"))
+    };
+    let scenes = [base, ours, theirs].map(read);
+    for e in scenes.iter().filter_map(|scene| scene.as_ref().err()) {
+        eprintln!("Failed to read scene: {e}");
+    }
+    let [Ok(base_scene), Ok(our_scene), Ok(their_scene)] = scenes else {
+        return ExitCode::from(2);
+    };
+
+    let merge = scene_diff::merge(&base_scene, &our_scene, &their_scene);
+    if let Err(e) = std::fs::write(ours, merge.scene.to_string()) {
+        eprintln!("Failed to write merged scene {ours}: {e}");
+        return ExitCode::from(2);
+    }
+    for conflict in &merge.conflicts {
+        eprintln!("Conflict on {conflict}, kept ours");
+    }
+    match merge.conflicts.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
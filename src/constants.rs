@@ -4,3 +4,17 @@ pub const FEATURES: wgpu::Features = wgpu::Features::INDIRECT_FIRST_INSTANCE;
 
 pub const MODEL_ZNEAR: f32 = 0.1;
 pub const MODE_ZFAR: f32 = 1000.0;
+
+/// Used when the adapter supports them, see `GraphicsCtx::has_feature`
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT)
+    .union(wgpu::Features::FLOAT32_FILTERABLE);
+
+/// User preferences, relative to the working directory
+pub const CONFIG_PATH: &str = "foreigntech.cfg";
+
+/// Scene asset the light rig is loaded from at startup
+pub const DEFAULT_SCENE: &str = "Default";
+
+/// Processed models keyed by the hash of their sources, see `graphics::entities::cache`
+pub const MODEL_CACHE_PATH: &str = "cache/models";
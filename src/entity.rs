@@ -0,0 +1,99 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Stable identifier of an entity, kept when the instances are reordered, saved and loaded
+///
+/// Random version 4 layout, written in the usual hyphenated hex form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(pub u128);
+
+impl Uuid {
+    pub fn new() -> Self {
+        // Unlike the scene random streams, ids must differ between runs with the same seed
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut halves = [0u64; 2];
+        for (i, half) in halves.iter_mut().enumerate() {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(nanos);
+            hasher.write_u64(count);
+            hasher.write_usize(i);
+            *half = hasher.finish();
+        }
+        let bits = ((halves[0] as u128) << 64) | halves[1] as u128;
+        // Version 4 and RFC 4122 variant
+        let bits = (bits & !(0xF << 76)) | (0x4 << 76);
+        Self((bits & !(0x3 << 62)) | (0x2 << 62))
+    }
+
+    /// First group of the hex form, enough to tell entities apart in the editor
+    pub fn short(&self) -> String {
+        format!("{:08x}", (self.0 >> 96) as u32)
+    }
+}
+
+impl Default for Uuid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = String;
+
+    /// Hyphens are optional
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: String = s.chars().filter(|c| *c != '-').collect();
+        if hex.len() != 32 {
+            return Err(format!("Invalid uuid {s}: expected 32 hex digits"));
+        }
+        u128::from_str_radix(&hex, 16)
+            .map(Self)
+            .map_err(|e| format!("Invalid uuid {s}: {e}"))
+    }
+}
+
+/// Identity of a placed instance, next to its gpu id in the scene editor and saved with it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EntityMeta {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+}
+
+impl EntityMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name, or the short uuid of the unnamed entities
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.uuid.short(),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, time::Duration};
+
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+use nd_iter::iter_3d;
+
+use crate::graphics::entities::{
+    model::{ModelInstanceId, ModelsBuffer},
+    renderer::EntitiesRenderer,
+};
+
+use crate::graphics::{color::Color3, debug_lines::DebugPalette, world_bars::WorldBars};
+
+use super::{
+    ground::Ground,
+    health::Health,
+    interpolation::{FixedStep, Interpolated, Pose},
+    navmesh::NavMesh,
+    Body,
+};
+#[cfg(feature = "physics")]
+use super::{projectiles::ColliderOwner, spatial::SpatialGrid};
+
+/// Steering runs at a fixed rate, independently of the frame rate
+pub const TICK: Duration = Duration::from_millis(1000 / 30);
+
+pub struct Agent {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Followed in order, the agent arrives at the last point and stops
+    pub path: Vec<Point3<f32>>,
+    pub next_waypoint: usize,
+    pub health: Health,
+    /// Poses of the last ticks, the instances are drawn in between
+    pose: Interpolated,
+    body: Body,
+    instances: Vec<ModelInstanceId>,
+    /// Block the agent dissolved in with, see [`crate::graphics::entities::dissolve::Dissolves`]
+    params_id: u32,
+    flash: Flash,
+}
+
+/// Tint fading out after a hit
+#[derive(Default)]
+struct Flash {
+    color: Color3,
+    /// From 1 right after the hit down to 0
+    strength: f32,
+    /// Strength lost per second
+    fade: f32,
+}
+
+impl Flash {
+    fn tint(&self) -> [u8; 4] {
+        let [r, g, b]: [f32; 3] = self.color.into();
+        [r, g, b, self.strength].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+    }
+}
+
+/// Seek, arrive and avoid steering of instances along their paths
+pub struct Agents {
+    pub agents: Vec<Agent>,
+    pub max_speed: f32,
+    pub max_force: f32,
+    /// Distance to the last waypoint where agents start braking
+    pub slowing_radius: f32,
+    /// Waypoints closer than this are considered reached
+    pub waypoint_radius: f32,
+    /// Agents closer than this push each other away
+    pub avoid_radius: f32,
+    /// Collider radius
+    pub radius: f32,
+    pub max_health: f32,
+    /// Height of the health bars above the agent positions
+    pub health_bar_height: f32,
+    pub paused: bool,
+    /// Draws the agents between their last two ticks instead of at the last one, one tick behind
+    pub interpolate: bool,
+    /// Paths of the spawned agents go around the unwalkable ground once built
+    pub navmesh: Option<NavMesh>,
+
+    step: FixedStep,
+}
+
+impl Default for Agents {
+    fn default() -> Self {
+        Self {
+            agents: Vec::new(),
+            max_speed: 4.0,
+            max_force: 8.0,
+            slowing_radius: 3.0,
+            waypoint_radius: 0.5,
+            avoid_radius: 1.5,
+            radius: 0.5,
+            max_health: 100.0,
+            health_bar_height: 1.5,
+            paused: false,
+            interpolate: true,
+            navmesh: None,
+            step: FixedStep::new(TICK),
+        }
+    }
+}
+
+impl Agents {
+    /// The agent dissolves in
+    pub fn spawn(
+        &mut self,
+        entities: &mut EntitiesRenderer,
+        body: Body,
+        position: Point3<f32>,
+        path: Vec<Point3<f32>>,
+    ) {
+        let params_id = entities
+            .dissolves
+            .dissolve_in(&mut entities.instance_params);
+        let instance = body
+            .instance(Matrix4::new_translation(&position.coords))
+            .with_params(params_id);
+        let instances = body.spawn_instance(&mut entities.models, instance);
+        self.agents.push(Agent {
+            position,
+            velocity: Vector3::zeros(),
+            path,
+            next_waypoint: 0,
+            health: Health::new(self.max_health),
+            pose: Interpolated::new(Pose::new(position, UnitQuaternion::identity())),
+            body,
+            instances,
+            params_id,
+            flash: Flash::default(),
+        });
+    }
+
+    pub fn clear(&mut self, entities: &mut EntitiesRenderer) {
+        for agent in self.agents.drain(..) {
+            Body::despawn(&mut entities.models, agent.instances);
+            entities
+                .dissolves
+                .release(&mut entities.instance_params, agent.params_id);
+        }
+    }
+
+    /// Runs the pending ticks and writes the moved agents to their instances, every frame when
+    /// interpolating
+    ///
+    /// Agents and their waypoints stick to the ground
+    pub fn update(&mut self, models: &mut ModelsBuffer, ground: &dyn Ground, dt: Duration) {
+        if self.paused || self.agents.is_empty() {
+            return;
+        }
+
+        let ticks = self.step.advance(dt);
+        for _ in 0..ticks {
+            self.tick(TICK.as_secs_f32(), ground);
+            for agent in &mut self.agents {
+                let pose = agent_pose(agent);
+                agent.pose.push(pose);
+            }
+        }
+
+        if ticks > 0 || self.interpolate {
+            for agent in &self.agents {
+                let transform = self.rendered_pose(agent).to_homogeneous();
+                let instance = agent
+                    .body
+                    .instance(transform)
+                    .with_tint(agent.flash.tint())
+                    .with_params(agent.params_id);
+                agent.body.set(models, &agent.instances, instance);
+            }
+        }
+    }
+
+    /// Pose the agent is drawn at this frame
+    fn rendered_pose(&self, agent: &Agent) -> Pose {
+        match self.interpolate {
+            true => agent.pose.at(self.step.alpha()),
+            false => *agent.pose.current(),
+        }
+    }
+
+    fn tick(&mut self, dt: f32, ground: &dyn Ground) {
+        // The terrain can be edited while the agents walk
+        for agent in &mut self.agents {
+            for waypoint in &mut agent.path {
+                waypoint.y = ground.height_at(waypoint.x, waypoint.z);
+            }
+        }
+
+        let neighbours = self.neighbour_grid();
+        let forces: Vec<_> = (0..self.agents.len())
+            .map(|i| self.steering(i, &neighbours).cap_magnitude(self.max_force))
+            .collect();
+
+        for (agent, force) in self.agents.iter_mut().zip(forces) {
+            agent.flash.strength = (agent.flash.strength - agent.flash.fade * dt).max(0.0);
+
+            agent.velocity = (agent.velocity + force * dt).cap_magnitude(self.max_speed);
+            agent.position += agent.velocity * dt;
+            agent.position.y = ground.height_at(agent.position.x, agent.position.z);
+            agent.velocity.y = 0.0;
+
+            if let Some(waypoint) = agent.path.get(agent.next_waypoint) {
+                let is_last = agent.next_waypoint + 1 == agent.path.len();
+                if !is_last && nalgebra::distance(waypoint, &agent.position) < self.waypoint_radius
+                {
+                    agent.next_waypoint += 1;
+                }
+            }
+        }
+    }
+
+    /// Follows the world origin, the instances were already moved on the gpu
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for agent in &mut self.agents {
+            agent.position -= shift;
+            agent.pose.shift(shift);
+            for waypoint in &mut agent.path {
+                *waypoint -= shift;
+            }
+        }
+        if let Some(navmesh) = &mut self.navmesh {
+            navmesh.rebase(shift);
+        }
+    }
+
+    /// Waypoints from `start` to `end`, around the unwalkable ground when the navmesh is built.
+    /// `None` when the navmesh does not connect them
+    pub fn path(&self, start: Point3<f32>, end: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        match &self.navmesh {
+            Some(navmesh) => navmesh.find_path(start, end),
+            None => Some(vec![end]),
+        }
+    }
+
+    /// Flashes the agent, returns true when the damage killed it
+    pub fn damage(&mut self, i: usize, amount: f32, flash_color: Color3, flash: Duration) -> bool {
+        let Some(agent) = self.agents.get_mut(i) else {
+            return false;
+        };
+        agent.flash = Flash {
+            color: flash_color,
+            strength: 1.0,
+            fade: 1.0 / flash.as_secs_f32().max(1e-3),
+        };
+        agent.health.damage(amount)
+    }
+
+    /// Dissolves out the agents without health left, indices of the following agents shift
+    pub fn remove_dead(&mut self, entities: &mut EntitiesRenderer) {
+        let (dead, alive) = std::mem::take(&mut self.agents)
+            .into_iter()
+            .partition::<Vec<_>, _>(|agent| !agent.health.is_alive());
+        self.agents = alive;
+        for agent in dead {
+            let transform = self.rendered_pose(&agent).to_homogeneous();
+            let instance = agent.body.instance(transform).with_params(agent.params_id);
+            entities.dissolves.dissolve_out(
+                &mut entities.models,
+                &mut entities.instance_params,
+                agent.instances,
+                instance,
+            );
+        }
+    }
+
+    /// Health bars of the damaged agents, along the gradient of the palette from full health to
+    /// none
+    pub fn queue_health_bars(&self, bars: &mut WorldBars, palette: DebugPalette) {
+        for agent in &self.agents {
+            let fraction = agent.health.fraction();
+            if fraction < 1.0 {
+                let color = palette.gradient(fraction);
+                let position =
+                    self.rendered_pose(agent).position + Vector3::y() * self.health_bar_height;
+                bars.bar(position, fraction, color);
+            }
+        }
+    }
+
+    #[cfg(feature = "physics")]
+    pub fn register_colliders(&self, colliders: &mut SpatialGrid<ColliderOwner>) {
+        for (i, agent) in self.agents.iter().enumerate() {
+            colliders.insert(agent.position, self.radius, ColliderOwner::Agent(i));
+        }
+    }
+
+    /// Agents bucketed by cells of `avoid_radius`, neighbours are searched in the adjacent cells
+    fn neighbour_grid(&self) -> HashMap<(i32, i32, i32), Vec<usize>> {
+        let mut grid: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, agent) in self.agents.iter().enumerate() {
+            grid.entry(self.grid_cell(&agent.position))
+                .or_default()
+                .push(i);
+        }
+        grid
+    }
+
+    fn grid_cell(&self, position: &Point3<f32>) -> (i32, i32, i32) {
+        let cell = position / self.avoid_radius.max(0.01);
+        (
+            cell.x.floor() as i32,
+            cell.y.floor() as i32,
+            cell.z.floor() as i32,
+        )
+    }
+
+    fn steering(
+        &self,
+        i: usize,
+        neighbours: &HashMap<(i32, i32, i32), Vec<usize>>,
+    ) -> Vector3<f32> {
+        let agent = &self.agents[i];
+        let goal = match agent.path.get(agent.next_waypoint) {
+            Some(waypoint) if agent.next_waypoint + 1 == agent.path.len() => {
+                self.arrive(agent, waypoint)
+            }
+            Some(waypoint) => self.seek(agent, waypoint),
+            None => -agent.velocity,
+        };
+        goal + self.avoid(i, neighbours)
+    }
+
+    fn seek(&self, agent: &Agent, target: &Point3<f32>) -> Vector3<f32> {
+        let desired = (target - agent.position)
+            .try_normalize(1e-6)
+            .unwrap_or_default()
+            * self.max_speed;
+        desired - agent.velocity
+    }
+
+    fn arrive(&self, agent: &Agent, target: &Point3<f32>) -> Vector3<f32> {
+        let offset = target - agent.position;
+        let distance = offset.norm();
+        if distance < 1e-3 {
+            return -agent.velocity;
+        }
+        let speed = self.max_speed * (distance / self.slowing_radius).min(1.0);
+        offset / distance * speed - agent.velocity
+    }
+
+    /// Separation from the neighbours, stronger the closer they are
+    fn avoid(&self, i: usize, neighbours: &HashMap<(i32, i32, i32), Vec<usize>>) -> Vector3<f32> {
+        let agent = &self.agents[i];
+        let (x, y, z) = self.grid_cell(&agent.position);
+        let mut force = Vector3::zeros();
+        for (dx, dy, dz) in iter_3d(-1..2, -1..2, -1..2) {
+            let Some(cell) = neighbours.get(&(x + dx, y + dy, z + dz)) else {
+                continue;
+            };
+            for j in cell.iter().copied().filter(|j| *j != i) {
+                let away = agent.position - self.agents[j].position;
+                let distance = away.norm();
+                if distance > 1e-3 && distance < self.avoid_radius {
+                    force += away / distance * (self.avoid_radius - distance) / self.avoid_radius;
+                }
+            }
+        }
+        force * self.max_force
+    }
+}
+
+/// Faces the direction of travel
+fn agent_pose(agent: &Agent) -> Pose {
+    let heading = Vector3::new(agent.velocity.x, 0.0, agent.velocity.z);
+    if heading.norm_squared() < 1e-6 {
+        return Pose::new(agent.position, UnitQuaternion::identity());
+    }
+    let yaw = heading.x.atan2(heading.z);
+    Pose::new(
+        agent.position,
+        UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw),
+    )
+}
@@ -0,0 +1,28 @@
+use nalgebra::Vector2;
+
+use crate::graphics::camera::{Camera, Projection};
+
+/// Camera placed in the world for gameplay or cutscenes, the player camera is
+/// [`super::GameState::camera`]
+#[derive(Debug, Clone)]
+pub struct GameCamera {
+    pub name: String,
+    pub camera: Camera,
+    /// Vertical field of view
+    pub fov_deg: f32,
+}
+
+impl GameCamera {
+    pub fn new(name: impl Into<String>, camera: Camera, fov_deg: f32) -> Self {
+        Self {
+            name: name.into(),
+            camera,
+            fov_deg,
+        }
+    }
+
+    /// Perspective of the camera rendered at `size`
+    pub fn projection(&self, size: Vector2<u32>) -> Projection {
+        Projection::new(size, self.fov_deg)
+    }
+}
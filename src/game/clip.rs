@@ -0,0 +1,382 @@
+use nalgebra::{Point3, Quaternion, Unit, UnitQuaternion, Vector3, Vector4};
+
+use super::{interpolation::Pose, skeleton::Skeleton};
+
+/// Largest error in meters and radians of the keys dropped from a track, their neighbours
+/// interpolate them closer than that
+pub const KEY_TOLERANCE: f32 = 1e-3;
+
+/// Local pose of a bone at a time of the clip, in seconds
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub pose: Pose,
+}
+
+/// Keyframe quantized to 16 bits per component, relative to the ranges of its track
+///
+/// The rotation keeps its three smallest components, the largest one is recomputed from them
+#[derive(Debug, Clone, Copy)]
+struct PackedKey {
+    time: u16,
+    position: [u16; 3],
+    rotation: [i16; 3],
+    largest: u8,
+}
+
+/// Keyframes of one bone, sorted by time and stored compressed
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub bone: usize,
+    keys: Vec<PackedKey>,
+    /// Time of the last key
+    span: f32,
+    origin: Point3<f32>,
+    extent: Vector3<f32>,
+    /// Before compression, for the memory stats
+    source_keys: usize,
+}
+
+impl Track {
+    /// Drops the keys interpolated by their neighbours and quantizes the others
+    pub fn new(bone: usize, keys: &[Keyframe]) -> Self {
+        let source_keys = keys.len();
+        let keys = reduce(keys);
+        let span = keys.last().map_or(0.0, |key| key.time);
+        let (mut min, mut max) = (Point3::from([f32::MAX; 3]), Point3::from([f32::MIN; 3]));
+        for key in &keys {
+            min = min.inf(&key.pose.position);
+            max = max.sup(&key.pose.position);
+        }
+        let origin = if keys.is_empty() {
+            Point3::origin()
+        } else {
+            min
+        };
+        let extent = (max - origin).map(|extent| extent.max(0.0));
+        Self {
+            bone,
+            keys: keys
+                .iter()
+                .map(|key| {
+                    let (rotation, largest) = pack_rotation(&key.pose.rotation);
+                    let position = key.pose.position - origin;
+                    PackedKey {
+                        time: quantize(key.time, span),
+                        position: [0, 1, 2].map(|i| quantize(position[i], extent[i])),
+                        rotation,
+                        largest,
+                    }
+                })
+                .collect(),
+            span,
+            origin,
+            extent,
+            source_keys,
+        }
+    }
+
+    /// Rest pose of the bone turned around `axis` by the angles, at their times in seconds
+    pub fn swing(
+        skeleton: &Skeleton,
+        bone: usize,
+        axis: Vector3<f32>,
+        angles: &[(f32, f32)],
+    ) -> Self {
+        let rest = skeleton.bones[bone].rest;
+        let axis = Unit::new_normalize(axis);
+        let keys: Vec<_> = angles
+            .iter()
+            .map(|(time, angle)| Keyframe {
+                time: *time,
+                pose: Pose::new(
+                    rest.position,
+                    rest.rotation * UnitQuaternion::from_axis_angle(&axis, *angle),
+                ),
+            })
+            .collect();
+        Self::new(bone, &keys)
+    }
+
+    /// Decompressed keys
+    pub fn keys(&self) -> Vec<Keyframe> {
+        self.keys.iter().map(|key| self.unpack(key)).collect()
+    }
+
+    fn unpack(&self, key: &PackedKey) -> Keyframe {
+        let offset = Vector3::from([0, 1, 2].map(|i| dequantize(key.position[i], self.extent[i])));
+        Keyframe {
+            time: dequantize(key.time, self.span),
+            pose: Pose::new(
+                self.origin + offset,
+                unpack_rotation(key.rotation, key.largest),
+            ),
+        }
+    }
+
+    fn sample(&self, time: f32) -> Option<Pose> {
+        let next = self
+            .keys
+            .partition_point(|key| dequantize(key.time, self.span) <= time);
+        match next {
+            0 => self.keys.first().map(|key| self.unpack(key).pose),
+            next if next == self.keys.len() => self.keys.last().map(|key| self.unpack(key).pose),
+            next => {
+                let (from, to) = (
+                    self.unpack(&self.keys[next - 1]),
+                    self.unpack(&self.keys[next]),
+                );
+                let t = (time - from.time) / (to.time - from.time);
+                Some(from.pose.lerp(&to.pose, t))
+            }
+        }
+    }
+
+    pub fn memory(&self) -> ClipMemory {
+        ClipMemory {
+            keys: self.keys.len(),
+            bytes: size_of::<Self>() + self.keys.len() * size_of::<PackedKey>(),
+            uncompressed: size_of::<Self>() + self.source_keys * size_of::<Keyframe>(),
+        }
+    }
+}
+
+/// Size of clips in memory, see [`Clip::memory`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClipMemory {
+    /// Kept after the reduction
+    pub keys: usize,
+    pub bytes: usize,
+    /// With every key as authored
+    pub uncompressed: usize,
+}
+
+impl std::ops::Add for ClipMemory {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            keys: self.keys + other.keys,
+            bytes: self.bytes + other.bytes,
+            uncompressed: self.uncompressed + other.uncompressed,
+        }
+    }
+}
+
+/// Keeps the keys that the linear interpolation of the kept ones around them can't rebuild
+fn reduce(keys: &[Keyframe]) -> Vec<Keyframe> {
+    let Some((first, last)) = keys.first().zip(keys.last()) else {
+        return Vec::new();
+    };
+    let mut kept = vec![*first];
+    let mut from = 0;
+    for to in 2..keys.len() {
+        let (a, b) = (&keys[from], &keys[to]);
+        let fits = keys[from + 1..to].iter().all(|key| {
+            let t = (key.time - a.time) / (b.time - a.time).max(1e-6);
+            let pose = a.pose.lerp(&b.pose, t);
+            (pose.position - key.pose.position).norm() <= KEY_TOLERANCE
+                && pose.rotation.angle_to(&key.pose.rotation) <= KEY_TOLERANCE
+        });
+        if !fits {
+            from = to - 1;
+            kept.push(keys[from]);
+        }
+    }
+    if keys.len() > 1 {
+        kept.push(*last);
+    }
+    kept
+}
+
+fn quantize(value: f32, range: f32) -> u16 {
+    match range > 0.0 {
+        true => ((value / range).clamp(0.0, 1.0) * u16::MAX as f32).round() as u16,
+        false => 0,
+    }
+}
+
+fn dequantize(value: u16, range: f32) -> f32 {
+    value as f32 / u16::MAX as f32 * range
+}
+
+/// The smallest components of a unit quaternion are within ±1/√2, scaled to the full range
+fn pack_rotation(rotation: &UnitQuaternion<f32>) -> ([i16; 3], u8) {
+    let coords = rotation.coords;
+    let largest = (0..4)
+        .max_by(|a, b| coords[*a].abs().total_cmp(&coords[*b].abs()))
+        .unwrap_or(3);
+    // q and -q are the same rotation, the largest component is kept positive
+    let sign = coords[largest].signum();
+    let mut packed = [0; 3];
+    for (slot, i) in (0..4).filter(|i| *i != largest).enumerate() {
+        let scaled = coords[i] * sign * std::f32::consts::SQRT_2;
+        packed[slot] = (scaled.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+    }
+    (packed, largest as u8)
+}
+
+fn unpack_rotation(packed: [i16; 3], largest: u8) -> UnitQuaternion<f32> {
+    let largest = largest as usize;
+    let mut coords = Vector4::zeros();
+    for (slot, i) in (0..4).filter(|i| *i != largest).enumerate() {
+        coords[i] = packed[slot] as f32 / i16::MAX as f32 / std::f32::consts::SQRT_2;
+    }
+    coords[largest] = (1.0 - coords.norm_squared()).max(0.0).sqrt();
+    UnitQuaternion::new_normalize(Quaternion::from(coords))
+}
+
+/// Named moment of a clip, dispatched when the playback goes past it
+#[derive(Debug, Clone)]
+pub struct ClipEvent {
+    pub name: String,
+    /// From 0 at the start of the clip to 1 at its end
+    pub time: f32,
+}
+
+/// Keyframed animation of some bones of a skeleton, the others keep their pose
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub name: String,
+    /// In seconds
+    pub duration: f32,
+    pub tracks: Vec<Track>,
+    pub events: Vec<ClipEvent>,
+}
+
+impl Clip {
+    /// Writes the pose of the animated bones at `time` in seconds
+    pub fn sample(&self, time: f32, locals: &mut [Pose]) {
+        for track in &self.tracks {
+            if let Some(pose) = track.sample(time) {
+                locals[track.bone] = pose;
+            }
+        }
+    }
+
+    pub fn memory(&self) -> ClipMemory {
+        self.tracks
+            .iter()
+            .fold(ClipMemory::default(), |memory, track| {
+                memory + track.memory()
+            })
+    }
+
+    /// Events after `from` and up to `to`, normalized times without wrapping
+    pub fn events_between(&self, from: f32, to: f32) -> impl Iterator<Item = &ClipEvent> {
+        self.events
+            .iter()
+            .filter(move |event| event.time > from && event.time <= to)
+    }
+
+    /// Arms swinging slowly at rest
+    pub fn idle(skeleton: &Skeleton) -> Self {
+        let duration = std::f32::consts::PI;
+        let tracks = [("l", 1.0), ("r", -1.0)]
+            .into_iter()
+            .filter_map(|(side, sign)| {
+                let arm = skeleton.find(&format!("upper_arm.{side}"))?;
+                let angles = [0.0, 0.4, 0.0, -0.4, 0.0].map(|angle| angle * sign);
+                let keys: Vec<_> = angles
+                    .iter()
+                    .enumerate()
+                    .map(|(i, angle)| (i as f32 * duration / 4.0, *angle))
+                    .collect();
+                Some(Track::swing(skeleton, arm, Vector3::x(), &keys))
+            })
+            .collect();
+        Self {
+            name: String::from("Idle"),
+            duration,
+            tracks,
+            events: Vec::new(),
+        }
+    }
+
+    /// Knees raised in turn, with a footstep each time a foot lands
+    pub fn march(skeleton: &Skeleton) -> Self {
+        let duration = 1.0;
+        let mut tracks = Vec::new();
+        for (side, start) in [("l", 0.0), ("r", 0.5)] {
+            // Raised during the first half of its cycle, down during the second
+            let keys = |peak: f32| {
+                let mut keys = [(0.0, 0.0), (0.25, peak), (0.5, 0.0)]
+                    .map(|(time, angle)| ((time + start) % duration, angle))
+                    .to_vec();
+                keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+                // Closes the loop
+                keys.push((duration, keys[0].1));
+                keys
+            };
+            if let Some(leg) = skeleton.find(&format!("upper_leg.{side}")) {
+                tracks.push(Track::swing(skeleton, leg, Vector3::x(), &keys(1.0)));
+            }
+            if let Some(shin) = skeleton.find(&format!("lower_leg.{side}")) {
+                tracks.push(Track::swing(skeleton, shin, Vector3::x(), &keys(-1.6)));
+            }
+            // The opposite arm swings forward
+            let other = if side == "l" { "r" } else { "l" };
+            if let Some(arm) = skeleton.find(&format!("upper_arm.{other}")) {
+                tracks.push(Track::swing(skeleton, arm, Vector3::x(), &keys(0.5)));
+            }
+        }
+        Self {
+            name: String::from("March"),
+            duration,
+            tracks,
+            events: [0.5, 1.0]
+                .map(|time| ClipEvent {
+                    name: String::from("footstep"),
+                    time,
+                })
+                .to_vec(),
+        }
+    }
+}
+
+/// Playback of a clip
+#[derive(Debug, Clone, Copy)]
+pub struct ClipPlayer {
+    pub clip: usize,
+    /// In seconds
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl ClipPlayer {
+    pub fn new(clip: usize) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    /// Moves the playback forward, `on_event` gets the events passed on the way in order
+    pub fn advance(&mut self, clip: &Clip, dt: f32, mut on_event: impl FnMut(&ClipEvent)) {
+        let duration = clip.duration.max(1e-6);
+        let from = self.time / duration;
+        self.time += dt * self.speed.max(0.0);
+        if self.time < duration {
+            clip.events_between(from, self.time / duration)
+                .for_each(&mut on_event);
+            return;
+        }
+        if !self.looping {
+            self.time = duration;
+            clip.events_between(from, 1.0).for_each(&mut on_event);
+            return;
+        }
+        // Loops skipped by a long frame don't repeat their events
+        clip.events_between(from, 1.0).for_each(&mut on_event);
+        self.time %= duration;
+        clip.events_between(-1.0, self.time / duration)
+            .for_each(&mut on_event);
+    }
+
+    pub fn sample(&self, clip: &Clip, locals: &mut [Pose]) {
+        clip.sample(self.time, locals);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use winit::keyboard::KeyCode;
+
+use crate::app::{config::Config, inputs::Inputs, keys};
+
+/// Config section holding one `control = key` entry per control
+const CONFIG_SECTION: &str = "controls";
+
+/// Camera movement keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Control {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Control {
+    pub const ALL: [Control; 6] = [
+        Control::Forward,
+        Control::Back,
+        Control::Left,
+        Control::Right,
+        Control::Up,
+        Control::Down,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            Control::Forward => "Forward",
+            Control::Back => "Back",
+            Control::Left => "Left",
+            Control::Right => "Right",
+            Control::Up => "Up",
+            Control::Down => "Down",
+        }
+    }
+
+    /// Key of the translated label, see [`crate::locale::tr`]
+    pub fn locale_key(&self) -> String {
+        format!("control-{}", self.config_key())
+    }
+
+    fn config_key(&self) -> &str {
+        match self {
+            Control::Forward => "forward",
+            Control::Back => "back",
+            Control::Left => "left",
+            Control::Right => "right",
+            Control::Up => "up",
+            Control::Down => "down",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Control::Forward => KeyCode::KeyW,
+            Control::Back => KeyCode::KeyS,
+            Control::Left => KeyCode::KeyA,
+            Control::Right => KeyCode::KeyD,
+            Control::Up => KeyCode::Space,
+            Control::Down => KeyCode::ShiftLeft,
+        }
+    }
+}
+
+/// Key bound to each control, persisted in the config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Controls {
+    bindings: BTreeMap<Control, KeyCode>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            bindings: Control::ALL
+                .into_iter()
+                .map(|control| (control, control.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl Controls {
+    /// Missing or invalid entries keep their default
+    pub fn from_config(config: &Config) -> Self {
+        let mut controls = Self::default();
+        for control in Control::ALL {
+            let Some(name) = config.get(CONFIG_SECTION, control.config_key()) else {
+                continue;
+            };
+            match keys::parse_key(name) {
+                Some(key) => controls.set(control, key),
+                None => eprintln!("Invalid {} key: {name}", control.label()),
+            }
+        }
+        controls
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        for (control, key) in &self.bindings {
+            config.set(CONFIG_SECTION, control.config_key(), keys::key_name(*key));
+        }
+    }
+
+    pub fn key(&self, control: Control) -> KeyCode {
+        self.bindings[&control]
+    }
+
+    pub fn set(&mut self, control: Control, key: KeyCode) {
+        self.bindings.insert(control, key);
+    }
+
+    pub fn held(&self, inputs: &Inputs, control: Control) -> bool {
+        inputs.key_held(self.key(control))
+    }
+
+    /// -1, 0 or 1 along the axis going from `negative` to `positive`
+    pub fn axis(&self, inputs: &Inputs, negative: Control, positive: Control) -> f32 {
+        self.held(inputs, positive) as i32 as f32 - self.held(inputs, negative) as i32 as f32
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use nalgebra::Vector3;
+
+use crate::graphics::{camera::Camera, color::Color3};
+
+#[cfg(feature = "physics")]
+use super::{agents::Agents, projectiles::ColliderOwner, spatial::RayHit};
+
+/// Short synthesized tone, played by [`crate::audio::Audio`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundCue {
+    pub frequency: f32,
+    pub duration: Duration,
+    pub volume: f32,
+}
+
+/// Trauma based shake, the offset grows with the square of the trauma left
+pub struct CameraShake {
+    /// From 0 to 1
+    pub trauma: f32,
+    /// Trauma lost per second
+    pub decay: f32,
+    /// Angle offset at full trauma, in degrees
+    pub max_angle: f32,
+    time: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 1.5,
+            max_angle: 3.0,
+            time: 0.0,
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dts = dt.as_secs_f32();
+        self.time += dts;
+        self.trauma = (self.trauma - self.decay * dts).max(0.0);
+    }
+
+    /// Copy of the camera with the shake applied to its angles
+    pub fn apply(&self, camera: &Camera) -> Camera {
+        let shake = self.trauma * self.trauma * self.max_angle;
+        // Incommensurate frequencies per axis, the motion never visibly loops
+        let offset = Vector3::new(
+            (self.time * 37.0).sin(),
+            (self.time * 41.0 + 1.3).sin(),
+            (self.time * 29.0 + 2.7).sin(),
+        ) * shake;
+
+        Camera {
+            yaw_deg: camera.yaw_deg + offset.x,
+            pitch_deg: camera.pitch_deg + offset.y,
+            roll_deg: camera.roll_deg + offset.z,
+            ..*camera
+        }
+    }
+}
+
+/// Turns projectile hits into damage, instance flashes, camera shake and sounds
+pub struct HitFeedback {
+    pub damage: f32,
+    pub flash_color: Color3,
+    pub flash_duration: Duration,
+    /// Trauma added per hit
+    pub trauma: f32,
+    pub hit_sound: SoundCue,
+    pub kill_sound: SoundCue,
+
+    pub shake: CameraShake,
+    sounds: Vec<SoundCue>,
+}
+
+impl Default for HitFeedback {
+    fn default() -> Self {
+        Self {
+            damage: 25.0,
+            flash_color: Color3::RED,
+            flash_duration: Duration::from_millis(150),
+            trauma: 0.3,
+            hit_sound: SoundCue {
+                frequency: 660.0,
+                duration: Duration::from_millis(60),
+                volume: 0.2,
+            },
+            kill_sound: SoundCue {
+                frequency: 220.0,
+                duration: Duration::from_millis(200),
+                volume: 0.3,
+            },
+            shake: CameraShake::default(),
+            sounds: Vec::new(),
+        }
+    }
+}
+
+impl HitFeedback {
+    #[cfg(feature = "physics")]
+    pub fn apply(&mut self, hits: Vec<RayHit<ColliderOwner>>, agents: &mut Agents) {
+        for hit in hits {
+            match hit.data {
+                ColliderOwner::Agent(i) => {
+                    let killed =
+                        agents.damage(i, self.damage, self.flash_color, self.flash_duration);
+                    self.shake.add_trauma(self.trauma);
+                    self.sounds.push(match killed {
+                        true => self.kill_sound,
+                        false => self.hit_sound,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.shake.update(dt);
+    }
+
+    /// Sounds queued since the last call
+    pub fn take_sounds(&mut self) -> Vec<SoundCue> {
+        std::mem::take(&mut self.sounds)
+    }
+}
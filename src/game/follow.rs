@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::graphics::camera::Camera;
+
+use super::{ground::Ground, spatial::SpatialGrid};
+
+/// Halvings of the step the ground contact is refined with
+const GROUND_REFINE_STEPS: u32 = 6;
+
+/// Third person camera turning around a target with the mouse look, the movement keys move the
+/// target instead of the camera
+///
+/// A sphere is cast from the target back to the wanted camera position, against the colliders and
+/// the ground. On a hit the camera is pulled in right away so it never goes through, and eases back
+/// out once the way is clear
+#[derive(Debug, Clone, Copy)]
+pub struct FollowCamera {
+    pub enabled: bool,
+    /// Point looked at, kept above the ground
+    pub target: Point3<f32>,
+    /// Wanted distance from the target
+    pub distance: f32,
+    /// The camera keeps this far from the surfaces
+    pub radius: f32,
+    /// Per second, how fast the camera moves back out to `distance`
+    pub return_rate: f32,
+    /// Distance of the last frame, pulled in by the hits
+    current: f32,
+}
+
+impl Default for FollowCamera {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: Point3::new(0.0, 1.5, 0.0),
+            distance: 5.0,
+            radius: 0.3,
+            return_rate: 4.0,
+            current: 5.0,
+        }
+    }
+}
+
+impl FollowCamera {
+    /// Places the camera behind the target along its view direction, after the colliders are
+    /// registered for the frame
+    pub fn update<T: Copy>(
+        &mut self,
+        camera: &mut Camera,
+        colliders: Option<&SpatialGrid<T>>,
+        ground: &dyn Ground,
+        dt: Duration,
+    ) {
+        let back = -camera.forward();
+        let distance = self.distance.max(0.0);
+        let mut allowed = distance;
+        if let Some(hit) = colliders
+            .and_then(|colliders| colliders.sphere_cast(self.target, back, self.radius, distance))
+        {
+            allowed = allowed.min(hit.distance);
+        }
+        if let Some(contact) = ground_cast(ground, self.target, back, self.radius, distance) {
+            allowed = allowed.min(contact);
+        }
+
+        // Pulled in at once so the camera is never inside, eased back out
+        self.current = match allowed < self.current {
+            true => allowed,
+            false => {
+                let t = 1.0 - (-self.return_rate.max(0.0) * dt.as_secs_f32()).exp();
+                self.current + (allowed - self.current) * t
+            }
+        };
+        camera.eye = self.target + back * self.current;
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        self.target -= shift;
+    }
+}
+
+/// Distance the sphere travels before touching the ground, none when it starts below it
+fn ground_cast(
+    ground: &dyn Ground,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    radius: f32,
+    max_distance: f32,
+) -> Option<f32> {
+    let below = |distance: f32| {
+        let center = origin + direction * distance;
+        center.y - radius < ground.height_at(center.x, center.z)
+    };
+    if below(0.0) {
+        return None;
+    }
+    let step = radius.max(0.25);
+    let mut clear = 0.0;
+    while clear < max_distance {
+        let next = (clear + step).min(max_distance);
+        if below(next) {
+            let mut blocked = next;
+            for _ in 0..GROUND_REFINE_STEPS {
+                let middle = (clear + blocked) * 0.5;
+                match below(middle) {
+                    true => blocked = middle,
+                    false => clear = middle,
+                }
+            }
+            return Some(clear);
+        }
+        clear = next;
+    }
+    None
+}
@@ -0,0 +1,35 @@
+use nalgebra::Vector3;
+
+#[cfg(feature = "terrain")]
+use crate::graphics::terrain::heightmap::TerrainHeight;
+
+/// Surface the gameplay objects stand on
+pub trait Ground {
+    fn height_at(&self, x: f32, z: f32) -> f32;
+    /// Upward unit normal
+    fn normal_at(&self, x: f32, z: f32) -> Vector3<f32>;
+}
+
+/// Ground at `y = 0`, used without terrain
+pub struct FlatGround;
+
+impl Ground for FlatGround {
+    fn height_at(&self, _x: f32, _z: f32) -> f32 {
+        0.0
+    }
+
+    fn normal_at(&self, _x: f32, _z: f32) -> Vector3<f32> {
+        Vector3::y()
+    }
+}
+
+#[cfg(feature = "terrain")]
+impl Ground for TerrainHeight {
+    fn height_at(&self, x: f32, z: f32) -> f32 {
+        TerrainHeight::height_at(self, x, z)
+    }
+
+    fn normal_at(&self, x: f32, z: f32) -> Vector3<f32> {
+        TerrainHeight::normal_at(self, x, z)
+    }
+}
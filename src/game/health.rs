@@ -0,0 +1,31 @@
+/// Hit points of a gameplay object, it dies when they reach zero
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Returns true when this damage killed it
+    pub fn damage(&mut self, amount: f32) -> bool {
+        let was_alive = self.is_alive();
+        self.current = (self.current - amount).max(0.0);
+        was_alive && !self.is_alive()
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.current > 0.0
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+}
@@ -0,0 +1,78 @@
+use nalgebra::{Point3, Unit, UnitQuaternion, Vector3};
+
+use super::{interpolation::Pose, skeleton::Skeleton};
+
+/// Bones bent by [`solve_two_bone`], the lower one is a child of the upper one
+#[derive(Debug, Clone, Copy)]
+pub struct IkChain {
+    pub upper: usize,
+    pub lower: usize,
+}
+
+impl IkChain {
+    pub fn find(skeleton: &Skeleton, upper: &str, lower: &str) -> Option<Self> {
+        let chain = Self {
+            upper: skeleton.find(upper)?,
+            lower: skeleton.find(lower)?,
+        };
+        (skeleton.bones[chain.lower].parent == Some(chain.upper)).then_some(chain)
+    }
+}
+
+/// Bends the chain so that the tail of its lower bone reaches `target`, the children of the
+/// chain follow it
+///
+/// `world` are the poses after the clip sampling, the middle joint bends towards `pole`. Out of
+/// reach targets are pointed at with the chain straight, `weight` blends from the pose given
+pub fn solve_two_bone(
+    skeleton: &Skeleton,
+    world: &mut [Pose],
+    chain: IkChain,
+    target: Point3<f32>,
+    pole: Vector3<f32>,
+    weight: f32,
+) {
+    let (upper, lower) = (world[chain.upper], world[chain.lower]);
+    let start = upper.position;
+    let upper_length = (lower.position - start).norm();
+    let lower_length = skeleton.bones[chain.lower].length;
+    let Some(to_target) = (target - start).try_normalize(1e-6) else {
+        return;
+    };
+    let distance = (target - start).norm().clamp(
+        (upper_length - lower_length).abs() + 1e-4,
+        upper_length + lower_length - 1e-4,
+    );
+
+    // Law of cosines for the angle between the upper bone and the target direction
+    let cos = (upper_length * upper_length + distance * distance - lower_length * lower_length)
+        / (2.0 * upper_length * distance);
+    let angle = cos.clamp(-1.0, 1.0).acos();
+    let Some(normal) = to_target.cross(&pole).try_normalize(1e-6) else {
+        return;
+    };
+    let upper_direction =
+        UnitQuaternion::from_axis_angle(&Unit::new_unchecked(normal), angle) * to_target;
+    let end = start + to_target * distance;
+
+    let locals = skeleton.local_poses(&Pose::identity(), world);
+    let swing = |from: Vector3<f32>, to: Vector3<f32>| {
+        UnitQuaternion::rotation_between(&from, &to).unwrap_or(UnitQuaternion::identity())
+    };
+    let solved = Pose::new(
+        start,
+        swing(lower.position - start, upper_direction) * upper.rotation,
+    );
+    world[chain.upper] = upper.lerp(&solved, weight);
+
+    // Follows the upper bone, then turns towards the target
+    let moved = world[chain.upper].compose(&locals[chain.lower]);
+    if let Some(to_end) = (end - moved.position).try_normalize(1e-6) {
+        let solved = Pose::new(
+            moved.position,
+            swing(moved.rotation * Vector3::y(), to_end) * moved.rotation,
+        );
+        world[chain.lower] = moved.lerp(&solved, weight);
+    }
+    skeleton.update_descendants(world, &locals, chain.lower);
+}
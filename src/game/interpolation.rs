@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+
+/// Accumulates the frame times into fixed ticks, the remainder gives the interpolation alpha
+#[derive(Debug, Clone, Copy)]
+pub struct FixedStep {
+    pub tick: Duration,
+    accumulator: Duration,
+}
+
+impl FixedStep {
+    pub fn new(tick: Duration) -> Self {
+        Self {
+            tick,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Number of ticks to run for this frame
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// How far the frame is between the last tick and the next one, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f32() / self.tick.as_secs_f32().max(1e-6)).clamp(0.0, 1.0)
+    }
+}
+
+/// Rigid transform without scale, of the fixed updates and of the bones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub position: Point3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+}
+
+impl Pose {
+    pub fn new(position: Point3<f32>, rotation: UnitQuaternion<f32>) -> Self {
+        Self { position, rotation }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Point3::origin(), UnitQuaternion::identity())
+    }
+
+    pub fn to_homogeneous(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.position.coords) * self.rotation.to_homogeneous()
+    }
+
+    /// `local` is relative to this pose, the result is in the space of this pose
+    pub fn compose(&self, local: &Pose) -> Pose {
+        Pose {
+            position: self.position + self.rotation * local.position.coords,
+            rotation: self.rotation * local.rotation,
+        }
+    }
+
+    pub fn inverse(&self) -> Pose {
+        let rotation = self.rotation.inverse();
+        Pose {
+            position: Point3::from(rotation * -self.position.coords),
+            rotation,
+        }
+    }
+
+    /// Linear between the positions and spherical between the rotations
+    pub fn lerp(&self, to: &Pose, t: f32) -> Pose {
+        Pose {
+            position: self.position.coords.lerp(&to.position.coords, t).into(),
+            rotation: self
+                .rotation
+                .try_slerp(&to.rotation, t, 1e-6)
+                .unwrap_or(to.rotation),
+        }
+    }
+}
+
+/// Poses of the last two fixed updates, rendered in between so that the movement stays smooth
+/// when the frame rate is above the tick rate
+#[derive(Debug, Clone, Copy)]
+pub struct Interpolated {
+    previous: Pose,
+    current: Pose,
+}
+
+impl Interpolated {
+    pub fn new(pose: Pose) -> Self {
+        Self {
+            previous: pose,
+            current: pose,
+        }
+    }
+
+    /// Called once per tick with the new pose
+    pub fn push(&mut self, pose: Pose) {
+        self.previous = self.current;
+        self.current = pose;
+    }
+
+    /// Moves without interpolating, for spawns and teleports
+    pub fn snap(&mut self, pose: Pose) {
+        *self = Self::new(pose);
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn shift(&mut self, shift: Vector3<f32>) {
+        self.previous.position -= shift;
+        self.current.position -= shift;
+    }
+
+    pub fn current(&self) -> &Pose {
+        &self.current
+    }
+
+    pub fn at(&self, alpha: f32) -> Pose {
+        self.previous.lerp(&self.current, alpha)
+    }
+}
@@ -1,50 +1,341 @@
 use std::time::Duration;
 
-use nalgebra::{Rotation3, Vector3, Vector4};
-use winit::keyboard::KeyCode;
+use agents::Agents;
+use cameras::GameCamera;
+use controls::{Control, Controls};
+use feedback::HitFeedback;
+use follow::FollowCamera;
+use ground::Ground;
+use mouse::{MouseLook, MouseSettings};
+use nalgebra::{Matrix4, Rotation3, Vector3, Vector4};
+#[cfg(feature = "physics")]
+use projectiles::{ColliderOwner, Projectiles};
+#[cfg(feature = "physics")]
+use ragdoll::Puppets;
+#[cfg(feature = "physics")]
+use spatial::SpatialGrid;
+use weather::Weather;
+use winit::{event::MouseButton, keyboard::KeyCode};
 
-use crate::{app::inputs::Inputs, graphics::camera::Camera};
+use crate::{
+    app::{
+        config::Config,
+        inputs::Inputs,
+        pointer::{PointerClaims, PointerFocus, PointerOwner},
+    },
+    graphics::{
+        camera::Camera,
+        debug_lines::DebugPalette,
+        entities::{
+            model::{ModelInstance, ModelInstanceId, ModelsBuffer},
+            renderer::EntitiesRenderer,
+        },
+        world_bars::WorldBars,
+    },
+    random::Random,
+    sequence::Cutscene,
+};
+
+pub mod agents;
+pub mod cameras;
+pub mod clip;
+pub mod controls;
+pub mod feedback;
+pub mod follow;
+pub mod ground;
+pub mod health;
+pub mod ik;
+pub mod interpolation;
+pub mod mouse;
+pub mod navmesh;
+#[cfg(feature = "physics")]
+pub mod projectiles;
+#[cfg(feature = "physics")]
+pub mod ragdoll;
+pub mod skeleton;
+pub mod spatial;
+pub mod springs;
+pub mod weather;
+
+/// Model drawn for a gameplay object, every mesh of the model gets an instance
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub model_id: u16,
+    pub material_id: u32,
+    pub scale: f32,
+}
+
+impl Body {
+    pub fn new(model_id: u16, material_id: u32) -> Self {
+        Self {
+            model_id,
+            material_id,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_scale(self, scale: f32) -> Self {
+        Self { scale, ..self }
+    }
+
+    pub fn instance(&self, transform: Matrix4<f32>) -> ModelInstance {
+        ModelInstance::new(transform.prepend_scaling(self.scale), self.material_id)
+    }
+
+    pub fn spawn(
+        &self,
+        models: &mut ModelsBuffer,
+        transform: Matrix4<f32>,
+    ) -> Vec<ModelInstanceId> {
+        self.spawn_instance(models, self.instance(transform))
+    }
+
+    /// Spawns the meshes as `instance`, made by [`Self::instance`]
+    pub fn spawn_instance(
+        &self,
+        models: &mut ModelsBuffer,
+        instance: ModelInstance,
+    ) -> Vec<ModelInstanceId> {
+        (0..models.mesh_count_of(self.model_id) as u16)
+            .map(|mesh_id| models.add_instance(self.model_id, mesh_id, instance))
+            .collect()
+    }
+
+    pub fn set_transform(
+        &self,
+        models: &mut ModelsBuffer,
+        instances: &[ModelInstanceId],
+        transform: Matrix4<f32>,
+    ) {
+        self.set(models, instances, self.instance(transform));
+    }
+
+    pub fn set(
+        &self,
+        models: &mut ModelsBuffer,
+        instances: &[ModelInstanceId],
+        instance: ModelInstance,
+    ) {
+        for id in instances {
+            models.set_instance(id, instance);
+        }
+    }
+
+    pub fn despawn(models: &mut ModelsBuffer, instances: Vec<ModelInstanceId>) {
+        for id in instances {
+            models.remove_instance(id);
+        }
+    }
+}
+
+/// Keys switching between [`Mode::Game`] and [`Mode::Editor`]
+pub const MODE_KEYS: [KeyCode; 2] = [KeyCode::Tab, KeyCode::F1];
+
+/// Who the mouse and the keyboard are for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Cursor locked for mouse look, gameplay input enabled and the editor hidden
+    #[default]
+    Game,
+    /// Cursor free for the editor, the camera turns with the right button held
+    Editor,
+    /// Entered from game mode with Escape, the gameplay stops under the pause menu
+    Paused,
+}
 
 pub struct GameState {
     pub camera: Camera,
-    pub paused: bool,
+    /// Cameras other than the player one, previewed and tuned from the editor
+    pub cameras: Vec<GameCamera>,
+    /// Third person mode of [`Self::camera`], in game mode only
+    pub follow: FollowCamera,
+    /// Drives the camera and the lights while playing, see [`crate::sequence::Sequence`]
+    pub cutscene: Cutscene,
+    pub mouse: MouseSettings,
+    pub controls: Controls,
+    look: MouseLook,
+    pub agents: Agents,
+    #[cfg(feature = "physics")]
+    pub projectiles: Projectiles,
+    #[cfg(feature = "physics")]
+    pub puppets: Puppets,
+    pub feedback: HitFeedback,
+    #[cfg(feature = "physics")]
+    pub colliders: SpatialGrid<ColliderOwner>,
+    pub weather: Weather,
+    /// Seeded from the scene, replays of the same inputs draw the same numbers
+    pub random: Random,
+    pub mode: Mode,
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
             camera: Camera::default(),
-            paused: false,
+            cameras: Vec::new(),
+            follow: FollowCamera::default(),
+            cutscene: Cutscene::default(),
+            mouse: MouseSettings::from_config(config),
+            controls: Controls::from_config(config),
+            look: MouseLook::default(),
+            agents: Agents::default(),
+            #[cfg(feature = "physics")]
+            projectiles: Projectiles::default(),
+            #[cfg(feature = "physics")]
+            puppets: Puppets::default(),
+            feedback: HitFeedback::default(),
+            #[cfg(feature = "physics")]
+            colliders: SpatialGrid::new(2.0),
+            weather: Weather::default(),
+            random: Random::default(),
+            mode: Mode::default(),
         }
     }
 
-    pub fn update(&mut self, inputs: &Inputs, dt: Duration) -> () {
-        let (dx, dy) = inputs.mouse_diff();
-
-        let sensitivity = 2.;
+    /// Mouse look only runs while the camera owns the pointer, the keys are ignored while typing
+    pub fn update(&mut self, inputs: &Inputs, pointer: &PointerFocus, dt: Duration) -> () {
         let speed = 3.;
 
         let dts = dt.as_secs_f32();
-        if !self.paused {
-            self.camera.yaw_deg -= dx * sensitivity * dts;
-            self.camera.pitch_deg =
-                (self.camera.pitch_deg - dy * sensitivity * dts).clamp(-90., 90.);
+        if pointer.is(PointerOwner::Camera) {
+            let (dx, dy) = self.look.filter(&self.mouse, inputs.mouse_diff());
+            self.camera.yaw_deg -= dx * dts;
+            self.camera.pitch_deg = (self.camera.pitch_deg - dy * dts).clamp(-90., 90.);
+        } else {
+            self.look.reset();
+        }
+        if inputs.text_input() {
+            return;
         }
 
-        #[rustfmt::skip]
-        let (forward, right, up) =(
-            if inputs.key_held(KeyCode::KeyW) { 1. } else { 0. } + if inputs.key_held(KeyCode::KeyS) { -1. } else { 0. },
-            if inputs.key_held(KeyCode::KeyD) { 1. } else { 0. } + if inputs.key_held(KeyCode::KeyA) { -1. } else { 0. },
-            if inputs.key_held(KeyCode::Space) { 1. } else { 0. } + if inputs.key_held(KeyCode::ShiftLeft) { -1. } else { 0. },
+        if inputs.key_pressed(KeyCode::Escape) {
+            self.mode = match self.mode {
+                Mode::Game => Mode::Paused,
+                Mode::Paused => Mode::Game,
+                Mode::Editor => Mode::Editor,
+            };
+        } else if MODE_KEYS.iter().any(|key| inputs.key_pressed(*key)) {
+            self.mode = match self.mode {
+                Mode::Game => Mode::Editor,
+                Mode::Editor => Mode::Game,
+                Mode::Paused => Mode::Paused,
+            };
+        }
+        if self.mode == Mode::Paused {
+            return;
+        }
+
+        let controls = &self.controls;
+        let (forward, right, up) = (
+            controls.axis(inputs, Control::Back, Control::Forward),
+            controls.axis(inputs, Control::Left, Control::Right),
+            controls.axis(inputs, Control::Down, Control::Up),
         );
 
         let transl = Vector4::new(right, up, -forward, 0.);
         let rot = Rotation3::from_axis_angle(&Vector3::y_axis(), self.camera.yaw_deg.to_radians())
             .to_homogeneous();
-        self.camera.eye += (rot * transl).xyz() * speed * dts;
+        let movement = (rot * transl).xyz() * speed * dts;
+        match self.playing() && self.follow.enabled {
+            true => self.follow.target += movement,
+            false => self.camera.eye += movement,
+        }
+    }
 
-        if inputs.key_pressed(KeyCode::Escape) {
-            self.paused = !self.paused;
+    pub fn playing(&self) -> bool {
+        self.mode == Mode::Game
+    }
+
+    /// Moves everything back by `shift` along with the world origin, see
+    /// [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        self.camera.eye -= shift;
+        self.follow.rebase(shift);
+        for camera in &mut self.cameras {
+            camera.camera.eye -= shift;
+        }
+        self.agents.rebase(shift);
+        #[cfg(feature = "physics")]
+        self.projectiles.rebase(shift);
+        #[cfg(feature = "physics")]
+        self.puppets.rebase(shift);
+    }
+
+    /// The camera claims the mouse in game mode, and for looking around with the right button
+    /// held in editor mode
+    pub fn claim_pointer(&self, inputs: &Inputs, claims: &mut PointerClaims) {
+        let editing = self.mode == Mode::Editor;
+        claims.camera = self.playing() || (editing && inputs.mouse_held(MouseButton::Right));
+        claims.scene = editing;
+    }
+
+    /// Gameplay systems moving instances, run after `update`, stopped while paused
+    pub fn update_world(
+        &mut self,
+        entities: &mut EntitiesRenderer,
+        ground: &dyn Ground,
+        inputs: &Inputs,
+        dt: Duration,
+    ) {
+        if self.mode == Mode::Paused {
+            return;
+        }
+        self.agents.remove_dead(entities);
+        let models = &mut entities.models;
+        self.agents.update(models, ground, dt);
+
+        #[cfg(feature = "physics")]
+        self.update_physics(models, ground, inputs, dt);
+        #[cfg(not(feature = "physics"))]
+        let _ = inputs;
+
+        if self.playing() && self.follow.enabled {
+            #[cfg(feature = "physics")]
+            let colliders = Some(&self.colliders);
+            #[cfg(not(feature = "physics"))]
+            let colliders: Option<&spatial::SpatialGrid<()>> = None;
+            self.follow.update(&mut self.camera, colliders, ground, dt);
+        }
+
+        self.feedback.update(dt);
+        self.weather.update(dt, &mut self.random);
+    }
+
+    #[cfg(feature = "physics")]
+    fn update_physics(
+        &mut self,
+        models: &mut ModelsBuffer,
+        ground: &dyn Ground,
+        inputs: &Inputs,
+        dt: Duration,
+    ) {
+        self.colliders.clear();
+        self.agents.register_colliders(&mut self.colliders);
+
+        if self.playing() {
+            let (origin, direction) = (self.camera.eye, self.camera.forward());
+            if inputs.mouse_pressed(MouseButton::Left) {
+                self.projectiles.fire(models, origin, direction);
+            }
+            if inputs.mouse_pressed(MouseButton::Right) {
+                self.projectiles
+                    .hitscan(models, &self.colliders, origin, direction);
+            }
         }
+        self.projectiles.update(models, &self.colliders, dt);
+        self.puppets.update(models, ground, dt);
+
+        self.feedback
+            .apply(self.projectiles.take_hits(), &mut self.agents);
+    }
+
+    /// Widgets drawn above the gameplay objects this frame
+    pub fn queue_widgets(&self, bars: &mut WorldBars, palette: DebugPalette) {
+        self.agents.queue_health_bars(bars, palette);
+    }
+
+    /// Camera used for rendering, shaken by the hit feedback
+    pub fn view_camera(&self) -> Camera {
+        self.feedback.shake.apply(&self.camera)
     }
 }
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+
+use crate::app::config::Config;
+
+/// Config section holding the mouse settings
+const CONFIG_SECTION: &str = "mouse";
+
+/// Extra sensitivity per point moved in a step, when the acceleration is on
+const ACCELERATION: f32 = 0.02;
+
+/// Camera look settings, persisted in the config file
+#[derive(Debug, Clone, Copy)]
+pub struct MouseSettings {
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    /// Steps the mouse motion is averaged over, 1 disables the smoothing
+    pub smoothing_samples: u32,
+    /// Faster motions turn further than their distance
+    pub acceleration: bool,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 2.0,
+            invert_y: false,
+            smoothing_samples: 1,
+            acceleration: false,
+        }
+    }
+}
+
+impl MouseSettings {
+    /// Missing or invalid entries keep their default
+    pub fn from_config(config: &Config) -> Self {
+        let mut settings = Self::default();
+        let get = |key: &str| config.get(CONFIG_SECTION, key);
+        if let Some(sensitivity) = get("sensitivity").and_then(|v| v.parse().ok()) {
+            settings.sensitivity = sensitivity;
+        }
+        if let Some(invert_y) = get("invert_y").and_then(|v| v.parse().ok()) {
+            settings.invert_y = invert_y;
+        }
+        if let Some(samples) = get("smoothing_samples").and_then(|v| v.parse().ok()) {
+            settings.smoothing_samples = samples;
+        }
+        if let Some(acceleration) = get("acceleration").and_then(|v| v.parse().ok()) {
+            settings.acceleration = acceleration;
+        }
+        settings
+    }
+
+    pub fn write_config(&self, config: &mut Config) {
+        config.set(CONFIG_SECTION, "sensitivity", self.sensitivity);
+        config.set(CONFIG_SECTION, "invert_y", self.invert_y);
+        config.set(CONFIG_SECTION, "smoothing_samples", self.smoothing_samples);
+        config.set(CONFIG_SECTION, "acceleration", self.acceleration);
+    }
+}
+
+/// Raw mouse motion filtered by the [`MouseSettings`]
+#[derive(Debug, Default)]
+pub struct MouseLook {
+    samples: VecDeque<(f32, f32)>,
+}
+
+impl MouseLook {
+    /// Motion of this step, turned into the scaled and smoothed look motion
+    pub fn filter(&mut self, settings: &MouseSettings, (dx, dy): (f32, f32)) -> (f32, f32) {
+        self.samples.push_back((dx, dy));
+        while self.samples.len() > settings.smoothing_samples.max(1) as usize {
+            self.samples.pop_front();
+        }
+        let count = self.samples.len() as f32;
+        let (sum_x, sum_y) = self
+            .samples
+            .iter()
+            .fold((0.0, 0.0), |(x, y), (dx, dy)| (x + dx, y + dy));
+        let (dx, dy) = (sum_x / count, sum_y / count);
+
+        let mut sensitivity = settings.sensitivity;
+        if settings.acceleration {
+            sensitivity *= 1.0 + dx.hypot(dy) * ACCELERATION;
+        }
+        let dy = if settings.invert_y { -dy } else { dy };
+        (dx * sensitivity, dy * sensitivity)
+    }
+
+    /// Drops the motion of the previous steps, so looking doesn't drift after a pause
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use nalgebra::{Point3, Vector2, Vector3};
+
+use crate::graphics::{color::Color3, debug_lines::DebugLines};
+
+use super::ground::Ground;
+
+/// Neighbours of a cell, the diagonals come last
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(Clone)]
+pub struct NavMeshSettings {
+    /// Half the width of the square covered around the center
+    pub extent: f32,
+    pub cell_size: f32,
+    /// Steepest walkable ground, in degrees
+    pub max_slope_deg: f32,
+    /// Highest height difference crossed between two cells
+    pub max_step: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            extent: 50.0,
+            cell_size: 1.0,
+            max_slope_deg: 35.0,
+            max_step: 0.5,
+        }
+    }
+}
+
+/// Ground under the cell centers, sampled on the main thread as the ground is borrowed from the
+/// renderer, the navmesh is built from them on a worker
+pub struct GroundSamples {
+    /// Corner of the first cell on the xz plane
+    origin: Vector2<f32>,
+    cell_size: f32,
+    size: (usize, usize),
+    heights: Vec<f32>,
+    normals: Vec<Vector3<f32>>,
+}
+
+impl GroundSamples {
+    pub fn new(ground: &dyn Ground, center: Point3<f32>, settings: &NavMeshSettings) -> Self {
+        let cell_size = settings.cell_size.max(0.1);
+        let cells = (settings.extent * 2.0 / cell_size).ceil().max(1.0) as usize;
+        let origin = Vector2::new(center.x, center.z).add_scalar(-settings.extent);
+        let (mut heights, mut normals) = (Vec::new(), Vec::new());
+        for z in 0..cells {
+            for x in 0..cells {
+                let (x, z) = cell_center(origin, cell_size, x, z);
+                heights.push(ground.height_at(x, z));
+                normals.push(ground.normal_at(x, z));
+            }
+        }
+        Self {
+            origin,
+            cell_size,
+            size: (cells, cells),
+            heights,
+            normals,
+        }
+    }
+}
+
+/// Walkable cells of a grid laid over the ground, agents are pathed between their centers
+///
+/// A cell is walkable when its ground is not too steep, two neighbours are connected when the
+/// step between them is low enough. Diagonals are only crossed when both sides are walkable so
+/// the paths never cut corners
+pub struct NavMesh {
+    origin: Vector2<f32>,
+    cell_size: f32,
+    size: (usize, usize),
+    heights: Vec<f32>,
+    walkable: Vec<bool>,
+    max_step: f32,
+}
+
+impl NavMesh {
+    pub fn build(samples: GroundSamples, settings: &NavMeshSettings) -> Self {
+        let min_normal_y = settings.max_slope_deg.to_radians().cos();
+        let walkable = samples
+            .normals
+            .iter()
+            .map(|normal| normal.y >= min_normal_y)
+            .collect();
+        Self {
+            origin: samples.origin,
+            cell_size: samples.cell_size,
+            size: samples.size,
+            heights: samples.heights,
+            walkable,
+            max_step: settings.max_step,
+        }
+    }
+
+    pub fn walkable_count(&self) -> usize {
+        self.walkable.iter().filter(|walkable| **walkable).count()
+    }
+
+    /// Waypoints from `start` to `end`, `start` excluded. `None` when either is off the navmesh
+    /// or nothing connects them
+    ///
+    /// A* over the cells, the straight stretches are then merged as long as the cells under them
+    /// are walkable
+    pub fn find_path(&self, start: Point3<f32>, end: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        let from = self.walkable_cell_at(start)?;
+        let to = self.walkable_cell_at(end)?;
+
+        let mut open = BinaryHeap::from([Open {
+            cell: from,
+            cost: self.heuristic(from, to),
+        }]);
+        let mut came_from = HashMap::new();
+        let mut costs = HashMap::from([(from, 0.0)]);
+        while let Some(Open { cell, .. }) = open.pop() {
+            if cell == to {
+                break;
+            }
+            let cost = costs[&cell];
+            for (next, step) in self.neighbours(cell) {
+                let next_cost = cost + step;
+                if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                    continue;
+                }
+                costs.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Open {
+                    cell: next,
+                    cost: next_cost + self.heuristic(next, to),
+                });
+            }
+        }
+        if from != to && !came_from.contains_key(&to) {
+            return None;
+        }
+
+        let mut cells = vec![to];
+        while let Some(previous) = came_from.get(cells.last()?) {
+            cells.push(*previous);
+        }
+        cells.reverse();
+
+        let mut path = Vec::new();
+        let mut anchor = start;
+        let points = cells
+            .iter()
+            .skip(1)
+            .map(|cell| self.cell_point(*cell))
+            .chain([end])
+            .collect::<Vec<_>>();
+        for (i, point) in points.iter().enumerate() {
+            let Some(next) = points.get(i + 1) else {
+                path.push(*point);
+                break;
+            };
+            if !self.straight(anchor, *next) {
+                path.push(*point);
+                anchor = *point;
+            }
+        }
+        Some(path)
+    }
+
+    /// Follows the world origin
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        self.origin -= shift.xz();
+        for height in &mut self.heights {
+            *height -= shift.y;
+        }
+    }
+
+    /// A cross on every walkable cell
+    pub fn draw(&self, lines: &mut DebugLines, color: Color3) {
+        let size = self.cell_size * 0.25;
+        for cell in (0..self.walkable.len()).filter(|cell| self.walkable[*cell]) {
+            lines.cross(self.cell_point(cell), size, color);
+        }
+    }
+
+    fn walkable_cell_at(&self, point: Point3<f32>) -> Option<usize> {
+        let cell = (Vector2::new(point.x, point.z) - self.origin) / self.cell_size;
+        let (x, z) = (cell.x.floor() as i32, cell.y.floor() as i32);
+        self.cell(x, z).filter(|cell| self.walkable[*cell])
+    }
+
+    fn cell(&self, x: i32, z: i32) -> Option<usize> {
+        let (width, depth) = self.size;
+        let inside = (0..width as i32).contains(&x) && (0..depth as i32).contains(&z);
+        inside.then(|| z as usize * width + x as usize)
+    }
+
+    fn coords(&self, cell: usize) -> (i32, i32) {
+        ((cell % self.size.0) as i32, (cell / self.size.0) as i32)
+    }
+
+    fn cell_point(&self, cell: usize) -> Point3<f32> {
+        let (x, z) = self.coords(cell);
+        let (x, z) = cell_center(self.origin, self.cell_size, x as usize, z as usize);
+        Point3::new(x, self.heights[cell], z)
+    }
+
+    /// Connected neighbours with the distance to them
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (x, z) = self.coords(cell);
+        let connected = move |dx: i32, dz: i32| {
+            self.cell(x + dx, z + dz).filter(|next| {
+                self.walkable[*next]
+                    && (self.heights[*next] - self.heights[cell]).abs() <= self.max_step
+            })
+        };
+        NEIGHBOURS.into_iter().filter_map(move |(dx, dz)| {
+            let next = connected(dx, dz)?;
+            let diagonal = dx != 0 && dz != 0;
+            if diagonal && (connected(dx, 0).is_none() || connected(0, dz).is_none()) {
+                return None;
+            }
+            let step = match diagonal {
+                true => std::f32::consts::SQRT_2,
+                false => 1.0,
+            };
+            Some((next, step * self.cell_size))
+        })
+    }
+
+    /// Octile distance, never above the cost of the path
+    fn heuristic(&self, from: usize, to: usize) -> f32 {
+        let ((x0, z0), (x1, z1)) = (self.coords(from), self.coords(to));
+        let (dx, dz) = ((x1 - x0).abs() as f32, (z1 - z0).abs() as f32);
+        let diagonal = dx.min(dz);
+        (dx.max(dz) - diagonal + diagonal * std::f32::consts::SQRT_2) * self.cell_size
+    }
+
+    /// Whether every cell crossed from `a` to `b` is connected to the previous one, sampled every
+    /// quarter of a cell
+    fn straight(&self, a: Point3<f32>, b: Point3<f32>) -> bool {
+        let offset = Vector2::new(b.x - a.x, b.z - a.z);
+        let samples = (offset.norm() / self.cell_size * 4.0).ceil() as usize;
+        let mut previous = self.walkable_cell_at(a);
+        for i in 1..=samples {
+            let point = a + Vector3::new(offset.x, 0.0, offset.y) * (i as f32 / samples as f32);
+            let cell = self.walkable_cell_at(point);
+            let (Some(from), Some(to)) = (previous, cell) else {
+                return false;
+            };
+            if from != to && !self.neighbours(from).any(|(next, _)| next == to) {
+                return false;
+            }
+            previous = cell;
+        }
+        true
+    }
+}
+
+fn cell_center(origin: Vector2<f32>, cell_size: f32, x: usize, z: usize) -> (f32, f32) {
+    (
+        origin.x + (x as f32 + 0.5) * cell_size,
+        origin.y + (z as f32 + 0.5) * cell_size,
+    )
+}
+
+/// Cell to expand, the cheapest first
+struct Open {
+    cell: usize,
+    /// Cost from the start plus the heuristic
+    cost: f32,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flat ground, too steep to walk where `blocked` says so
+    struct Walls(fn(f32, f32) -> bool);
+
+    impl Ground for Walls {
+        fn height_at(&self, _x: f32, _z: f32) -> f32 {
+            0.0
+        }
+
+        fn normal_at(&self, x: f32, z: f32) -> Vector3<f32> {
+            match (self.0)(x, z) {
+                true => Vector3::x(),
+                false => Vector3::y(),
+            }
+        }
+    }
+
+    /// 20 by 20 cells of 1 around the origin
+    fn navmesh(blocked: fn(f32, f32) -> bool) -> NavMesh {
+        let settings = NavMeshSettings {
+            extent: 10.0,
+            ..Default::default()
+        };
+        let samples = GroundSamples::new(&Walls(blocked), Point3::origin(), &settings);
+        NavMesh::build(samples, &settings)
+    }
+
+    #[test]
+    fn open_ground_is_crossed_straight() {
+        let navmesh = navmesh(|_, _| false);
+        let end = Point3::new(7.0, 0.0, 3.0);
+        let path = navmesh.find_path(Point3::new(-6.0, 0.0, -8.0), end);
+        assert_eq!(path, Some(vec![end]));
+    }
+
+    #[test]
+    fn walls_are_walked_around() {
+        // Open past z = 5
+        let navmesh = navmesh(|x, z| x.abs() < 1.0 && z < 5.0);
+        let (start, end) = (Point3::new(-5.0, 0.0, 0.0), Point3::new(5.0, 0.0, 0.0));
+        let path = navmesh.find_path(start, end).unwrap();
+        assert!(path.len() > 1);
+        assert_eq!(path.last(), Some(&end));
+        for waypoint in &path {
+            assert!(navmesh.walkable_cell_at(*waypoint).is_some(), "{waypoint}");
+        }
+        for (a, b) in [start].iter().chain(&path).zip(&path) {
+            assert!(navmesh.straight(*a, *b), "{a} to {b}");
+        }
+    }
+
+    #[test]
+    fn unreachable_goal_has_no_path() {
+        let navmesh = navmesh(|x, _| x.abs() < 1.0);
+        let path = navmesh.find_path(Point3::new(-5.0, 0.0, 0.0), Point3::new(5.0, 0.0, 0.0));
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn goal_in_the_start_cell_is_reached_directly() {
+        let navmesh = navmesh(|_, _| false);
+        let end = Point3::new(2.8, 0.0, 2.9);
+        let path = navmesh.find_path(Point3::new(2.2, 0.0, 2.1), end);
+        assert_eq!(path, Some(vec![end]));
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::graphics::entities::model::{ModelInstanceId, ModelsBuffer};
+
+use super::{
+    spatial::{RayHit, SpatialGrid},
+    Body,
+};
+
+/// What a collider of the gameplay spatial grid belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColliderOwner {
+    Agent(usize),
+}
+
+pub struct Projectile {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub lifetime: Duration,
+    instances: Vec<ModelInstanceId>,
+}
+
+/// Marker left where a shot landed, despawned once its lifetime ran out
+pub struct Impact {
+    pub position: Point3<f32>,
+    pub owner: ColliderOwner,
+    pub lifetime: Duration,
+    instances: Vec<ModelInstanceId>,
+}
+
+/// Ballistic projectiles and hitscan shots colliding with the spatial grid
+pub struct Projectiles {
+    pub projectiles: Vec<Projectile>,
+    pub impacts: Vec<Impact>,
+    hits: Vec<RayHit<ColliderOwner>>,
+
+    pub speed: f32,
+    pub gravity: f32,
+    pub lifetime: Duration,
+    pub impact_lifetime: Duration,
+    pub hitscan_range: f32,
+    pub projectile_body: Body,
+    pub impact_body: Body,
+}
+
+impl Default for Projectiles {
+    fn default() -> Self {
+        Self {
+            projectiles: Vec::new(),
+            impacts: Vec::new(),
+            hits: Vec::new(),
+            speed: 30.0,
+            gravity: 9.81,
+            lifetime: Duration::from_secs(3),
+            impact_lifetime: Duration::from_millis(500),
+            hitscan_range: 200.0,
+            projectile_body: Body::new(1, 1).with_scale(0.05),
+            impact_body: Body::new(1, 2).with_scale(0.15),
+        }
+    }
+}
+
+impl Projectiles {
+    pub fn fire(
+        &mut self,
+        models: &mut ModelsBuffer,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+    ) {
+        let instances = self
+            .projectile_body
+            .spawn(models, Matrix4::new_translation(&origin.coords));
+        self.projectiles.push(Projectile {
+            position: origin,
+            velocity: direction.normalize() * self.speed,
+            lifetime: self.lifetime,
+            instances,
+        });
+    }
+
+    /// Instant shot, the impact is spawned on the closest collider along the ray
+    pub fn hitscan(
+        &mut self,
+        models: &mut ModelsBuffer,
+        colliders: &SpatialGrid<ColliderOwner>,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+    ) {
+        if let Some(hit) = colliders.raycast(origin, direction.normalize(), self.hitscan_range) {
+            self.impact(models, hit);
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        models: &mut ModelsBuffer,
+        colliders: &SpatialGrid<ColliderOwner>,
+        dt: Duration,
+    ) {
+        let dts = dt.as_secs_f32();
+
+        let mut i = 0;
+        while i < self.projectiles.len() {
+            let projectile = &mut self.projectiles[i];
+            projectile.velocity -= Vector3::y() * self.gravity * dts;
+            let travel = projectile.velocity * dts;
+
+            // The segment covered this frame is tested, fast projectiles can't tunnel through
+            let hit = travel.try_normalize(1e-6).and_then(|direction| {
+                colliders.raycast(projectile.position, direction, travel.norm())
+            });
+            projectile.position += travel;
+            projectile.lifetime = projectile.lifetime.saturating_sub(dt);
+
+            if hit.is_some() || projectile.lifetime.is_zero() {
+                let projectile = self.projectiles.swap_remove(i);
+                Body::despawn(models, projectile.instances);
+                if let Some(hit) = hit {
+                    self.impact(models, hit);
+                }
+                continue;
+            }
+
+            self.projectile_body.set_transform(
+                models,
+                &projectile.instances,
+                Matrix4::new_translation(&projectile.position.coords),
+            );
+            i += 1;
+        }
+
+        for impact in &mut self.impacts {
+            impact.lifetime = impact.lifetime.saturating_sub(dt);
+        }
+        let (expired, alive) = std::mem::take(&mut self.impacts)
+            .into_iter()
+            .partition::<Vec<_>, _>(|impact| impact.lifetime.is_zero());
+        self.impacts = alive;
+        for impact in expired {
+            Body::despawn(models, impact.instances);
+        }
+    }
+
+    /// Hits since the last call, for the systems reacting to them
+    /// Follows the world origin, the instances were already moved on the gpu
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for projectile in &mut self.projectiles {
+            projectile.position -= shift;
+        }
+        for impact in &mut self.impacts {
+            impact.position -= shift;
+        }
+    }
+
+    pub fn take_hits(&mut self) -> Vec<RayHit<ColliderOwner>> {
+        std::mem::take(&mut self.hits)
+    }
+
+    fn impact(&mut self, models: &mut ModelsBuffer, hit: RayHit<ColliderOwner>) {
+        let instances = self
+            .impact_body
+            .spawn(models, Matrix4::new_translation(&hit.position.coords));
+        self.impacts.push(Impact {
+            position: hit.position,
+            owner: hit.data,
+            lifetime: self.impact_lifetime,
+            instances,
+        });
+        self.hits.push(hit);
+    }
+
+    pub fn clear(&mut self, models: &mut ModelsBuffer) {
+        for projectile in self.projectiles.drain(..) {
+            Body::despawn(models, projectile.instances);
+        }
+        for impact in self.impacts.drain(..) {
+            Body::despawn(models, impact.instances);
+        }
+        self.hits.clear();
+    }
+}
@@ -0,0 +1,518 @@
+use std::{collections::HashMap, time::Duration};
+
+use nalgebra::{Matrix4, Point3, Unit, UnitQuaternion, Vector3};
+
+use crate::{
+    graphics::entities::model::{ModelInstanceId, ModelsBuffer},
+    profiler,
+};
+
+use super::{
+    clip::{Clip, ClipMemory, ClipPlayer},
+    feedback::SoundCue,
+    ground::Ground,
+    ik::{solve_two_bone, IkChain},
+    interpolation::{FixedStep, Pose},
+    skeleton::Skeleton,
+    springs::{SpringBone, SpringState},
+    Body,
+};
+
+/// The simulation runs at a fixed rate, independently of the frame rate
+pub const TICK: Duration = Duration::from_millis(1000 / 60);
+/// Bodies this close above the ground are touching it
+const CONTACT_DISTANCE: f32 = 1e-3;
+
+/// Sphere simulated with verlet integration, one at each end of every bone
+#[derive(Debug, Clone, Copy)]
+pub struct RagdollBody {
+    pub position: Point3<f32>,
+    previous: Point3<f32>,
+    pub radius: f32,
+}
+
+/// Keeps two bodies at their rest distance
+#[derive(Debug, Clone, Copy)]
+struct Joint {
+    a: usize,
+    b: usize,
+    length: f32,
+}
+
+/// Tunables shared by the ragdolls
+#[derive(Debug, Clone, Copy)]
+pub struct RagdollSettings {
+    pub gravity: f32,
+    /// Velocity kept per second
+    pub damping: f32,
+    /// Of the joints, more is stiffer
+    pub iterations: u32,
+    /// Velocity along the ground kept per second of contact
+    pub friction: f32,
+    /// Largest angle in radians between a bone and its parent
+    pub max_bend: f32,
+}
+
+impl Default for RagdollSettings {
+    fn default() -> Self {
+        Self {
+            gravity: 9.81,
+            damping: 0.8,
+            iterations: 8,
+            friction: 0.05,
+            max_bend: 2.0,
+        }
+    }
+}
+
+/// Physics bodies and joints generated from a [`Skeleton`]
+///
+/// Body `2 * i` is the origin of bone `i` and body `2 * i + 1` its tail. Children are held at
+/// their rest distance from both ends of their parent
+#[derive(Debug, Clone)]
+pub struct Ragdoll {
+    pub bodies: Vec<RagdollBody>,
+    joints: Vec<Joint>,
+    /// World poses the bodies were last matched to, the bones keep their twist from them
+    reference: Vec<Pose>,
+}
+
+impl Ragdoll {
+    pub fn new(skeleton: &Skeleton, world: &[Pose]) -> Self {
+        let mut bodies = Vec::with_capacity(skeleton.bones.len() * 2);
+        let mut joints = Vec::new();
+        for (i, (bone, pose)) in skeleton.bones.iter().zip(world).enumerate() {
+            for position in [pose.position, skeleton.tail(i, pose)] {
+                bodies.push(RagdollBody {
+                    position,
+                    previous: position,
+                    radius: bone.radius,
+                });
+            }
+            joints.push(Joint {
+                a: 2 * i,
+                b: 2 * i + 1,
+                length: bone.length,
+            });
+            if let Some(parent) = bone.parent {
+                for end in [2 * parent, 2 * parent + 1] {
+                    joints.push(Joint {
+                        a: end,
+                        b: 2 * i,
+                        length: (bodies[end].position - pose.position).norm(),
+                    });
+                }
+            }
+        }
+        Self {
+            bodies,
+            joints,
+            reference: world.to_vec(),
+        }
+    }
+
+    /// Moves the bodies onto the animated pose, their velocity follows the animation
+    pub fn match_pose(&mut self, skeleton: &Skeleton, world: &[Pose]) {
+        for (i, pose) in world.iter().enumerate() {
+            for (body, position) in [pose.position, skeleton.tail(i, pose)]
+                .into_iter()
+                .enumerate()
+            {
+                let body = &mut self.bodies[2 * i + body];
+                body.previous = body.position;
+                body.position = position;
+            }
+        }
+        self.reference.copy_from_slice(world);
+    }
+
+    /// Adds `velocity` to every body, `dt` is the one of the steps
+    pub fn kick(&mut self, velocity: Vector3<f32>, dt: f32) {
+        for body in &mut self.bodies {
+            body.previous -= velocity * dt;
+        }
+    }
+
+    pub fn step(
+        &mut self,
+        skeleton: &Skeleton,
+        settings: &RagdollSettings,
+        ground: &dyn Ground,
+        dt: f32,
+    ) {
+        let damping = settings.damping.powf(dt);
+        let gravity = Vector3::new(0.0, -settings.gravity * dt * dt, 0.0);
+        for body in &mut self.bodies {
+            let velocity = (body.position - body.previous) * damping;
+            body.previous = body.position;
+            body.position += velocity + gravity;
+        }
+
+        for _ in 0..settings.iterations {
+            for joint in &self.joints {
+                let (a, b) = (self.bodies[joint.a].position, self.bodies[joint.b].position);
+                let delta = b - a;
+                let distance = delta.norm();
+                if distance < 1e-6 {
+                    continue;
+                }
+                let correction = delta * (0.5 * (distance - joint.length) / distance);
+                self.bodies[joint.a].position += correction;
+                self.bodies[joint.b].position -= correction;
+            }
+            self.limit_bends(skeleton, settings.max_bend);
+            self.collide(ground, 1.0);
+        }
+        self.collide(ground, settings.friction.powf(dt));
+    }
+
+    /// Swings the tails of the bones bent too far from their parent back into the cone
+    fn limit_bends(&mut self, skeleton: &Skeleton, max_bend: f32) {
+        for (i, bone) in skeleton.bones.iter().enumerate() {
+            let Some(parent) = bone.parent else {
+                continue;
+            };
+            let direction = |bone: usize| {
+                (self.bodies[2 * bone + 1].position - self.bodies[2 * bone].position)
+                    .try_normalize(1e-6)
+            };
+            let (Some(parent_direction), Some(bone_direction)) = (direction(parent), direction(i))
+            else {
+                continue;
+            };
+            if parent_direction.angle(&bone_direction) <= max_bend {
+                continue;
+            }
+            let Some(axis) = parent_direction.cross(&bone_direction).try_normalize(1e-6) else {
+                continue;
+            };
+            let limited = UnitQuaternion::from_axis_angle(&Unit::new_unchecked(axis), max_bend)
+                * parent_direction;
+            self.bodies[2 * i + 1].position = self.bodies[2 * i].position + limited * bone.length;
+        }
+    }
+
+    /// Pushes the bodies out of the ground, `friction` is the sliding velocity kept by the ones
+    /// touching it
+    fn collide(&mut self, ground: &dyn Ground, friction: f32) {
+        for body in &mut self.bodies {
+            let floor = ground.height_at(body.position.x, body.position.z) + body.radius;
+            if body.position.y > floor + CONTACT_DISTANCE {
+                continue;
+            }
+            body.position.y = body.position.y.max(floor);
+            let velocity = body.position - body.previous;
+            body.previous.x = body.position.x - velocity.x * friction;
+            body.previous.z = body.position.z - velocity.z * friction;
+            body.previous.y = body.previous.y.max(body.position.y);
+        }
+    }
+
+    /// World poses of the bones from the bodies
+    pub fn poses(&self) -> Vec<Pose> {
+        self.reference
+            .iter()
+            .enumerate()
+            .map(|(i, reference)| {
+                let (origin, tail) = (self.bodies[2 * i].position, self.bodies[2 * i + 1].position);
+                let from = reference.rotation * Vector3::y();
+                let swing = (tail - origin)
+                    .try_normalize(1e-6)
+                    .and_then(|to| UnitQuaternion::rotation_between(&from, &to))
+                    .unwrap_or(UnitQuaternion::identity());
+                Pose::new(origin, swing * reference.rotation)
+            })
+            .collect()
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for body in &mut self.bodies {
+            body.position -= shift;
+            body.previous -= shift;
+        }
+        for pose in &mut self.reference {
+            pose.position -= shift;
+        }
+    }
+}
+
+/// Animated skeleton drawn with one instance per bone, blended into its ragdoll on demand
+pub struct Puppet {
+    pub root: Pose,
+    pub ragdoll: Ragdoll,
+    /// From 0 fully animated to 1 fully simulated
+    pub weight: f32,
+    /// Approached by `weight` at [`Puppets::blend_speed`]
+    pub target_weight: f32,
+    /// World poses of the bones of the last update
+    pub pose: Vec<Pose>,
+    pub player: ClipPlayer,
+    /// Reached for with the left hand while standing
+    pub reach: Option<Point3<f32>>,
+    springs: SpringState,
+    /// Per bone
+    instances: Vec<Vec<ModelInstanceId>>,
+}
+
+/// Event of a clip reached by a standing puppet
+#[derive(Debug, Clone)]
+pub struct AnimationEvent {
+    pub puppet: usize,
+    pub name: String,
+    pub position: Point3<f32>,
+}
+
+/// Puppets sharing a skeleton, animated while standing and knocked down into ragdolls
+pub struct Puppets {
+    pub skeleton: Skeleton,
+    pub puppets: Vec<Puppet>,
+    /// Played by the puppets, see [`Puppet::player`]
+    pub clips: Vec<Clip>,
+    /// Events of the last update, for the gameplay code running after it
+    pub events: Vec<AnimationEvent>,
+    /// Played on the events of the same name
+    pub event_sounds: HashMap<String, SoundCue>,
+    sounds: Vec<SoundCue>,
+    /// Layered over the animation of every puppet, edited live
+    pub springs: Vec<SpringBone>,
+    /// Keeps the feet on uneven ground instead of the height of the root
+    pub foot_ik: bool,
+    legs: Vec<IkChain>,
+    arm: Option<IkChain>,
+    pub settings: RagdollSettings,
+    /// Weight change per second
+    pub blend_speed: f32,
+    /// Stretched along each bone to its length and radius
+    pub bone_body: Body,
+
+    step: FixedStep,
+}
+
+impl Default for Puppets {
+    fn default() -> Self {
+        let skeleton = Skeleton::humanoid(1.8);
+        let springs = ["antenna.0", "antenna.1"]
+            .into_iter()
+            .filter_map(|name| skeleton.find(name))
+            .map(SpringBone::new)
+            .collect();
+        let clips = vec![Clip::idle(&skeleton), Clip::march(&skeleton)];
+        let legs = ["l", "r"]
+            .into_iter()
+            .filter_map(|side| {
+                IkChain::find(
+                    &skeleton,
+                    &format!("upper_leg.{side}"),
+                    &format!("lower_leg.{side}"),
+                )
+            })
+            .collect();
+        let arm = IkChain::find(&skeleton, "upper_arm.l", "lower_arm.l");
+        let footstep = SoundCue {
+            frequency: 90.0,
+            duration: Duration::from_millis(50),
+            volume: 0.3,
+        };
+        Self {
+            skeleton,
+            puppets: Vec::new(),
+            clips,
+            events: Vec::new(),
+            event_sounds: HashMap::from([(String::from("footstep"), footstep)]),
+            sounds: Vec::new(),
+            springs,
+            foot_ik: true,
+            legs,
+            arm,
+            settings: RagdollSettings::default(),
+            blend_speed: 4.0,
+            bone_body: Body::new(1, 1),
+            step: FixedStep::new(TICK),
+        }
+    }
+}
+
+impl Puppets {
+    /// Standing at `position`, snapped to the ground on the next update
+    pub fn spawn(&mut self, models: &mut ModelsBuffer, position: Point3<f32>) {
+        let root = Pose::new(position, UnitQuaternion::identity());
+        let pose = self.skeleton.world_poses(&root, &self.skeleton.rest_pose());
+        let instances = self
+            .skeleton
+            .bones
+            .iter()
+            .zip(&pose)
+            .map(|(bone, pose)| {
+                self.bone_body
+                    .spawn(models, bone_transform(bone.length, bone.radius, pose))
+            })
+            .collect();
+        self.puppets.push(Puppet {
+            root,
+            ragdoll: Ragdoll::new(&self.skeleton, &pose),
+            weight: 0.0,
+            target_weight: 0.0,
+            pose,
+            player: ClipPlayer::new(0),
+            reach: None,
+            springs: SpringState::default(),
+            instances,
+        });
+    }
+
+    /// Hands the puppet over to its ragdoll, starting at `velocity`
+    pub fn knock_down(&mut self, puppet: usize, velocity: Vector3<f32>) {
+        let puppet = &mut self.puppets[puppet];
+        puppet.target_weight = 1.0;
+        puppet.ragdoll.kick(velocity, TICK.as_secs_f32());
+    }
+
+    /// Blends the puppet back into its animation where it stood
+    pub fn get_up(&mut self, puppet: usize) {
+        self.puppets[puppet].target_weight = 0.0;
+    }
+
+    pub fn clear(&mut self, models: &mut ModelsBuffer) {
+        for puppet in self.puppets.drain(..) {
+            for instances in puppet.instances {
+                Body::despawn(models, instances);
+            }
+        }
+    }
+
+    pub fn update(&mut self, models: &mut ModelsBuffer, ground: &dyn Ground, dt: Duration) {
+        let ticks = self.step.advance(dt);
+        let tick = self.step.tick.as_secs_f32();
+        let dt = dt.as_secs_f32();
+        self.events.clear();
+        let memory = self
+            .clips
+            .iter()
+            .fold(ClipMemory::default(), |memory, clip| memory + clip.memory());
+        profiler::record_memory("Animation clips", memory.bytes, memory.uncompressed);
+        for (i, puppet) in self.puppets.iter_mut().enumerate() {
+            puppet.root.position.y =
+                ground.height_at(puppet.root.position.x, puppet.root.position.z);
+            let mut locals = self.skeleton.rest_pose();
+            if let Some(clip) = self.clips.get(puppet.player.clip) {
+                // Fallen puppets keep playing, without stepping or firing
+                let standing = puppet.target_weight == 0.0;
+                puppet.player.advance(clip, dt, |event| {
+                    if !standing {
+                        return;
+                    }
+                    if let Some(sound) = self.event_sounds.get(&event.name) {
+                        self.sounds.push(*sound);
+                    }
+                    self.events.push(AnimationEvent {
+                        puppet: i,
+                        name: event.name.clone(),
+                        position: puppet.root.position,
+                    });
+                });
+                puppet.player.sample(clip, &mut locals);
+            }
+            let mut animated = puppet.springs.world_poses(
+                &self.skeleton,
+                &self.springs,
+                &puppet.root,
+                &locals,
+                dt,
+            );
+
+            // Fades out with the animation, the ragdoll does not know about the targets
+            let ik_weight = 1.0 - puppet.weight;
+            if self.foot_ik && ik_weight > 0.0 {
+                let forward = puppet.root.rotation * Vector3::z();
+                for leg in &self.legs {
+                    let foot = self.skeleton.tail(leg.lower, &animated[leg.lower]);
+                    // The clip places the feet relative to the flat ground under the root
+                    let offset = ground.height_at(foot.x, foot.z) - puppet.root.position.y;
+                    let target = foot + Vector3::y() * offset;
+                    solve_two_bone(
+                        &self.skeleton,
+                        &mut animated,
+                        *leg,
+                        target,
+                        forward,
+                        ik_weight,
+                    );
+                }
+            }
+            if let (Some(arm), Some(target)) = (self.arm, puppet.reach) {
+                let backward = puppet.root.rotation * -Vector3::z();
+                solve_two_bone(
+                    &self.skeleton,
+                    &mut animated,
+                    arm,
+                    target,
+                    backward,
+                    ik_weight,
+                );
+            }
+
+            // Fully animated puppets drag their ragdoll along, it starts from their motion
+            if puppet.weight == 0.0 && puppet.target_weight == 0.0 {
+                puppet.ragdoll.match_pose(&self.skeleton, &animated);
+            } else {
+                for _ in 0..ticks {
+                    puppet
+                        .ragdoll
+                        .step(&self.skeleton, &self.settings, ground, tick);
+                }
+            }
+            let change = self.blend_speed * dt;
+            puppet.weight += (puppet.target_weight - puppet.weight).clamp(-change, change);
+
+            let simulated = puppet.ragdoll.poses();
+            let t = puppet.weight * puppet.weight * (3.0 - 2.0 * puppet.weight);
+            puppet.pose = animated
+                .iter()
+                .zip(&simulated)
+                .map(|(animated, simulated)| animated.lerp(simulated, t))
+                .collect();
+
+            for ((bone, pose), instances) in self
+                .skeleton
+                .bones
+                .iter()
+                .zip(&puppet.pose)
+                .zip(&puppet.instances)
+            {
+                self.bone_body.set_transform(
+                    models,
+                    instances,
+                    bone_transform(bone.length, bone.radius, pose),
+                );
+            }
+        }
+    }
+
+    /// Sounds queued since the last call
+    pub fn take_sounds(&mut self) -> Vec<SoundCue> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for puppet in &mut self.puppets {
+            puppet.root.position -= shift;
+            puppet.ragdoll.rebase(shift);
+            puppet.springs.rebase(shift);
+            if let Some(reach) = &mut puppet.reach {
+                *reach -= shift;
+            }
+            for pose in &mut puppet.pose {
+                pose.position -= shift;
+            }
+        }
+    }
+}
+
+/// Unit body centered on the bone and stretched over it
+fn bone_transform(length: f32, radius: f32, pose: &Pose) -> Matrix4<f32> {
+    pose.to_homogeneous()
+        * Matrix4::new_translation(&Vector3::new(0.0, length * 0.5, 0.0))
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(radius, length * 0.5, radius))
+}
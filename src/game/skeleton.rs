@@ -0,0 +1,204 @@
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+use super::interpolation::Pose;
+
+/// Joint of a [`Skeleton`], the bone goes from its origin along its local +y
+#[derive(Debug, Clone)]
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Relative to the parent, or to the root of the skeleton
+    pub rest: Pose,
+    pub length: f32,
+    /// Thickness, for the bodies of the ragdoll
+    pub radius: f32,
+}
+
+/// Bone hierarchy, the parents come before their children
+#[derive(Debug, Clone, Default)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    pub fn new(bones: Vec<Bone>) -> Result<Self, String> {
+        for (i, bone) in bones.iter().enumerate() {
+            if bone.parent.is_some_and(|parent| parent >= i) {
+                return Err(format!("Bone {} comes before its parent", bone.name));
+            }
+        }
+        Ok(Self { bones })
+    }
+
+    /// Biped standing on the origin and facing +z, `height` in meters
+    pub fn humanoid(height: f32) -> Self {
+        let h = height;
+        let (up, down) = (Vector3::y(), -Vector3::y());
+        let mut builder = SkeletonBuilder::default();
+        let hips = builder.bone("hips", None, [0.0, 0.53 * h, 0.0], up, 0.12 * h, 0.09 * h);
+        let spine = builder.bone(
+            "spine",
+            Some(hips),
+            [0.0, 0.65 * h, 0.0],
+            up,
+            0.17 * h,
+            0.1 * h,
+        );
+        let head = builder.bone(
+            "head",
+            Some(spine),
+            [0.0, 0.84 * h, 0.0],
+            up,
+            0.14 * h,
+            0.07 * h,
+        );
+        // Chain for the secondary motion
+        let tilted = Vector3::new(0.3, 1.0, 0.0).normalize();
+        let base = Point3::new(0.03 * h, 0.97 * h, 0.0);
+        let antenna = builder.bone(
+            "antenna.0",
+            Some(head),
+            base.into(),
+            tilted,
+            0.05 * h,
+            0.008 * h,
+        );
+        builder.bone(
+            "antenna.1",
+            Some(antenna),
+            (base + tilted * 0.05 * h).into(),
+            tilted,
+            0.05 * h,
+            0.006 * h,
+        );
+        for (side, x) in [("l", 1.0), ("r", -1.0)] {
+            let arm = [0.12 * h * x, 0.8 * h, 0.0];
+            let upper = builder.bone(
+                &format!("upper_arm.{side}"),
+                Some(spine),
+                arm,
+                down,
+                0.17 * h,
+                0.035 * h,
+            );
+            let elbow = [arm[0], arm[1] - 0.17 * h, 0.0];
+            builder.bone(
+                &format!("lower_arm.{side}"),
+                Some(upper),
+                elbow,
+                down,
+                0.17 * h,
+                0.03 * h,
+            );
+
+            let leg = [0.06 * h * x, 0.52 * h, 0.0];
+            let upper = builder.bone(
+                &format!("upper_leg.{side}"),
+                Some(hips),
+                leg,
+                down,
+                0.25 * h,
+                0.055 * h,
+            );
+            let knee = [leg[0], leg[1] - 0.25 * h, 0.0];
+            builder.bone(
+                &format!("lower_leg.{side}"),
+                Some(upper),
+                knee,
+                down,
+                0.27 * h,
+                0.045 * h,
+            );
+        }
+        builder.skeleton
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    pub fn children(&self, bone: usize) -> impl Iterator<Item = usize> + '_ {
+        (bone + 1..self.bones.len()).filter(move |i| self.bones[*i].parent == Some(bone))
+    }
+
+    /// Local poses of the bones at rest
+    pub fn rest_pose(&self) -> Vec<Pose> {
+        self.bones.iter().map(|bone| bone.rest).collect()
+    }
+
+    /// World poses of the bones from their local ones
+    pub fn world_poses(&self, root: &Pose, locals: &[Pose]) -> Vec<Pose> {
+        let mut world: Vec<Pose> = Vec::with_capacity(self.bones.len());
+        for (bone, local) in self.bones.iter().zip(locals) {
+            let parent = bone.parent.map_or(root, |parent| &world[parent]);
+            world.push(parent.compose(local));
+        }
+        world
+    }
+
+    /// Local poses of the bones from their world ones
+    pub fn local_poses(&self, root: &Pose, world: &[Pose]) -> Vec<Pose> {
+        self.bones
+            .iter()
+            .zip(world)
+            .map(|(bone, pose)| {
+                let parent = bone.parent.map_or(root, |parent| &world[parent]);
+                parent.inverse().compose(pose)
+            })
+            .collect()
+    }
+
+    /// Moves the descendants of `bone` along with it, from their local poses
+    pub fn update_descendants(&self, world: &mut [Pose], locals: &[Pose], bone: usize) {
+        let mut moved = vec![false; self.bones.len()];
+        moved[bone] = true;
+        for i in bone + 1..self.bones.len() {
+            if let Some(parent) = self.bones[i].parent.filter(|parent| moved[*parent]) {
+                world[i] = world[parent].compose(&locals[i]);
+                moved[i] = true;
+            }
+        }
+    }
+
+    /// End of the bone, away from its origin
+    pub fn tail(&self, bone: usize, world: &Pose) -> Point3<f32> {
+        world.position + world.rotation * Vector3::y() * self.bones[bone].length
+    }
+}
+
+/// Bones given in the space of the skeleton, converted to be relative to their parent
+#[derive(Default)]
+struct SkeletonBuilder {
+    skeleton: Skeleton,
+    world: Vec<Pose>,
+}
+
+impl SkeletonBuilder {
+    fn bone(
+        &mut self,
+        name: &str,
+        parent: Option<usize>,
+        origin: [f32; 3],
+        direction: Vector3<f32>,
+        length: f32,
+        radius: f32,
+    ) -> usize {
+        let rotation = UnitQuaternion::rotation_between(&Vector3::y(), &direction).unwrap_or(
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::PI),
+        );
+        let world = Pose::new(Point3::from(origin), rotation);
+        let rest = match parent {
+            Some(parent) => self.world[parent].inverse().compose(&world),
+            None => world,
+        };
+        self.world.push(world);
+        self.skeleton.bones.push(Bone {
+            name: name.to_string(),
+            parent,
+            rest,
+            length,
+            radius,
+        });
+        self.skeleton.bones.len() - 1
+    }
+}
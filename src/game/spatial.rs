@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+use nd_iter::iter_3d;
+
+type Cell = (i32, i32, i32);
+
+struct Collider<T> {
+    center: Point3<f32>,
+    radius: f32,
+    data: T,
+}
+
+pub struct RayHit<T> {
+    pub data: T,
+    pub distance: f32,
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+/// Sphere colliders bucketed in a uniform grid, rebuilt by the systems owning them
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    colliders: Vec<Collider<T>>,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    /// `cell_size` should be close to the diameter of the common colliders
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            colliders: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.colliders.clear();
+        self.cells.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.colliders.len()
+    }
+
+    pub fn insert(&mut self, center: Point3<f32>, radius: f32, data: T) {
+        let id = self.colliders.len();
+        self.colliders.push(Collider {
+            center,
+            radius,
+            data,
+        });
+
+        let (min, max) = (
+            self.cell_of(&(center - Vector3::repeat(radius))),
+            self.cell_of(&(center + Vector3::repeat(radius))),
+        );
+        for cell in iter_3d(min.0..max.0 + 1, min.1..max.1 + 1, min.2..max.2 + 1) {
+            self.cells.entry(cell).or_default().push(id);
+        }
+    }
+
+    /// Every collider intersecting the sphere, a collider may only be reported once
+    pub fn overlap_sphere(&self, center: Point3<f32>, radius: f32) -> Vec<T> {
+        let (min, max) = (
+            self.cell_of(&(center - Vector3::repeat(radius))),
+            self.cell_of(&(center + Vector3::repeat(radius))),
+        );
+        let mut found: Vec<usize> = iter_3d(min.0..max.0 + 1, min.1..max.1 + 1, min.2..max.2 + 1)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|id| {
+                let collider = &self.colliders[*id];
+                nalgebra::distance(&collider.center, &center) <= collider.radius + radius
+            })
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+            .into_iter()
+            .map(|id| self.colliders[id].data)
+            .collect()
+    }
+
+    /// Closest collider along the ray, `direction` must be normalized
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<RayHit<T>> {
+        self.cast(origin, direction, 0.0, max_distance, true)
+    }
+
+    /// Closest collider touched by a sphere of `radius` swept along the ray, `direction` must be
+    /// normalized. The position of the hit is the center of the sphere at contact
+    ///
+    /// Colliders the sphere starts in are ignored, like the body a camera follows
+    pub fn sphere_cast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        radius: f32,
+        max_distance: f32,
+    ) -> Option<RayHit<T>> {
+        self.cast(origin, direction, radius.max(0.0), max_distance, false)
+    }
+
+    /// Rays starting inside a collider hit it at their origin when `from_inside` is set
+    fn cast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        radius: f32,
+        max_distance: f32,
+        from_inside: bool,
+    ) -> Option<RayHit<T>> {
+        let mut closest: Option<(usize, f32)> = None;
+        let mut visited = Vec::new();
+
+        // Cells are sampled every half cell, corners barely clipped by the ray may be missed
+        let step = self.cell_size * 0.5;
+        let mut travelled = 0.0;
+        while travelled <= max_distance + step {
+            if closest.is_some_and(|(_, distance)| distance < travelled - self.cell_size - radius) {
+                break;
+            }
+
+            let sample = origin + direction * travelled;
+            travelled += step;
+            let (min, max) = (
+                self.cell_of(&(sample - Vector3::repeat(radius))),
+                self.cell_of(&(sample + Vector3::repeat(radius))),
+            );
+            for cell in iter_3d(min.0..max.0 + 1, min.1..max.1 + 1, min.2..max.2 + 1) {
+                if visited.contains(&cell) {
+                    continue;
+                }
+                visited.push(cell);
+
+                for id in self.cells.get(&cell).into_iter().flatten() {
+                    let collider = &self.colliders[*id];
+                    let Some((near, far)) =
+                        ray_sphere(origin, direction, collider.center, collider.radius + radius)
+                    else {
+                        continue;
+                    };
+                    let distance = match (near >= 0.0, far >= 0.0) {
+                        (true, _) => near,
+                        (false, true) if from_inside => 0.0,
+                        _ => continue,
+                    };
+                    if distance <= max_distance && closest.map_or(true, |(_, d)| distance < d) {
+                        closest = Some((*id, distance));
+                    }
+                }
+            }
+        }
+
+        closest.map(|(id, distance)| {
+            let collider = &self.colliders[id];
+            let position = origin + direction * distance;
+            RayHit {
+                data: collider.data,
+                distance,
+                position,
+                // Rays starting inside a collider hit it at their origin
+                normal: (position - collider.center)
+                    .try_normalize(1e-6)
+                    .unwrap_or(-direction),
+            }
+        })
+    }
+
+    fn cell_of(&self, position: &Point3<f32>) -> Cell {
+        let cell = position / self.cell_size;
+        (
+            cell.x.floor() as i32,
+            cell.y.floor() as i32,
+            cell.z.floor() as i32,
+        )
+    }
+}
+
+/// Distances to where the ray enters and leaves the sphere, negative behind the origin
+fn ray_sphere(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    center: Point3<f32>,
+    radius: f32,
+) -> Option<(f32, f32)> {
+    let to_center = center - origin;
+    let projected = to_center.dot(&direction);
+    let squared = to_center.norm_squared() - projected * projected;
+    let radius_squared = radius * radius;
+    if squared > radius_squared {
+        return None;
+    }
+    let half_chord = (radius_squared - squared).sqrt();
+    Some((projected - half_chord, projected + half_chord))
+}
@@ -0,0 +1,105 @@
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+
+use super::{interpolation::Pose, skeleton::Skeleton};
+
+/// Longest step of the springs, stiff ones would explode at low frame rates otherwise
+const MAX_SUBSTEP: f32 = 1.0 / 120.0;
+
+/// Secondary motion of a bone, its tail lags behind the animation and springs back to it
+#[derive(Debug, Clone, Copy)]
+pub struct SpringBone {
+    pub bone: usize,
+    /// Pull towards the animated tail
+    pub stiffness: f32,
+    /// Velocity lost per second
+    pub damping: f32,
+    /// Downward acceleration of the tail
+    pub gravity: f32,
+}
+
+impl SpringBone {
+    pub fn new(bone: usize) -> Self {
+        Self {
+            bone,
+            stiffness: 80.0,
+            damping: 6.0,
+            gravity: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpringTail {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+}
+
+/// Simulated tails of the spring bones of one animated skeleton
+#[derive(Debug, Clone, Default)]
+pub struct SpringState {
+    /// Per spring bone, placed on the animated tail at the first update
+    tails: Vec<Option<SpringTail>>,
+}
+
+impl SpringState {
+    /// World poses of the bones from their local ones, with the spring bones swung towards their
+    /// simulated tail
+    ///
+    /// The children of a spring bone follow it, chains of spring bones give hair and tails
+    pub fn world_poses(
+        &mut self,
+        skeleton: &Skeleton,
+        springs: &[SpringBone],
+        root: &Pose,
+        locals: &[Pose],
+        dt: f32,
+    ) -> Vec<Pose> {
+        // The springs were edited, they start over
+        if self.tails.len() != springs.len() {
+            self.tails = vec![None; springs.len()];
+        }
+        let substeps = (dt / MAX_SUBSTEP).ceil().max(1.0);
+        let h = dt / substeps;
+
+        let mut world: Vec<Pose> = Vec::with_capacity(skeleton.bones.len());
+        for (i, (bone, local)) in skeleton.bones.iter().zip(locals).enumerate() {
+            let parent = bone.parent.map_or(root, |parent| &world[parent]);
+            let mut pose = parent.compose(local);
+            if let Some((spring, tail)) = springs
+                .iter()
+                .zip(&mut self.tails)
+                .find(|(spring, _)| spring.bone == i)
+            {
+                let target = skeleton.tail(i, &pose);
+                let tail = tail.get_or_insert(SpringTail {
+                    position: target,
+                    velocity: Vector3::zeros(),
+                });
+                for _ in 0..substeps as u32 {
+                    let acceleration = (target - tail.position) * spring.stiffness
+                        - tail.velocity * spring.damping
+                        - Vector3::y() * spring.gravity;
+                    tail.velocity += acceleration * h;
+                    tail.position += tail.velocity * h;
+                }
+                // The bone keeps its length, only its direction follows the tail
+                if let Some(direction) = (tail.position - pose.position).try_normalize(1e-6) {
+                    tail.position = pose.position + direction * bone.length;
+                    let animated = pose.rotation * Vector3::y();
+                    let swing = UnitQuaternion::rotation_between(&animated, &direction)
+                        .unwrap_or(UnitQuaternion::identity());
+                    pose.rotation = swing * pose.rotation;
+                }
+            }
+            world.push(pose);
+        }
+        world
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for tail in self.tails.iter_mut().flatten() {
+            tail.position -= shift;
+        }
+    }
+}
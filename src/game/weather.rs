@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use crate::{
+    graphics::weather::{WeatherKind, WeatherState},
+    random::{Random, Rng},
+};
+
+use super::feedback::SoundCue;
+
+/// Weather changed to once the previous step ran for its duration, see [`Weather::play`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherStep {
+    pub kind: WeatherKind,
+    /// From 0 to 1
+    pub intensity: f32,
+    /// Time spent in this step, the transition included
+    pub duration: Duration,
+}
+
+/// Rain and snow falling at runtime, set directly or played from a script of steps
+///
+/// Changing the kind fades the current precipitation out before the new one fades in. Rain
+/// soaks the surfaces over time, they dry once it stops
+pub struct Weather {
+    pub state: WeatherState,
+    target: (WeatherKind, f32),
+    /// Intensity gained or lost per second
+    pub transition_speed: f32,
+    /// Wetness gained per second under full rain
+    pub wetting_speed: f32,
+    /// Wetness lost per second without rain
+    pub drying_speed: f32,
+    /// Raindrop sounds per second under full rain
+    pub drops_per_second: f32,
+
+    script: Vec<WeatherStep>,
+    step: usize,
+    step_elapsed: Duration,
+    pub looping: bool,
+
+    sounds: Vec<SoundCue>,
+    drop_timer: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            state: WeatherState::default(),
+            target: (WeatherKind::Clear, 0.0),
+            transition_speed: 0.25,
+            wetting_speed: 0.05,
+            drying_speed: 0.02,
+            drops_per_second: 30.0,
+            script: Vec::new(),
+            step: 0,
+            step_elapsed: Duration::ZERO,
+            looping: false,
+            sounds: Vec::new(),
+            drop_timer: 0.0,
+        }
+    }
+}
+
+impl Weather {
+    /// Transitions to the weather, stops the script
+    pub fn set(&mut self, kind: WeatherKind, intensity: f32) {
+        self.script.clear();
+        self.target = (kind, intensity.clamp(0.0, 1.0));
+    }
+
+    /// Goes through the steps in order, from the first one, and starts over when `looping`
+    pub fn play(&mut self, script: Vec<WeatherStep>, looping: bool) {
+        self.script = script;
+        self.looping = looping;
+        self.step = 0;
+        self.step_elapsed = Duration::ZERO;
+        if let Some(step) = self.script.first() {
+            self.target = (step.kind, step.intensity.clamp(0.0, 1.0));
+        }
+    }
+
+    pub fn is_scripted(&self) -> bool {
+        !self.script.is_empty()
+    }
+
+    pub fn target(&self) -> (WeatherKind, f32) {
+        self.target
+    }
+
+    /// Raindrop pitches are drawn from the `weather` stream
+    pub fn update(&mut self, dt: Duration, random: &mut Random) {
+        self.advance_script(dt);
+
+        let dts = dt.as_secs_f32();
+        let (kind, intensity) = self.target;
+        let state = &mut self.state;
+        let step = self.transition_speed * dts;
+        // The previous precipitation fades out first
+        let goal = if kind == state.kind { intensity } else { 0.0 };
+        state.intensity += (goal - state.intensity).clamp(-step, step);
+        if kind != state.kind && state.intensity <= 0.0 {
+            state.kind = kind;
+        }
+
+        state.wetness = match state.kind {
+            WeatherKind::Rain => state.wetness + state.intensity * self.wetting_speed * dts,
+            _ => state.wetness - self.drying_speed * dts,
+        }
+        .clamp(0.0, 1.0);
+
+        self.queue_drops(dts, random.stream("weather"));
+    }
+
+    fn advance_script(&mut self, dt: Duration) {
+        let Some(step) = self.script.get(self.step) else {
+            return;
+        };
+        self.step_elapsed += dt;
+        if self.step_elapsed < step.duration {
+            return;
+        }
+        self.step_elapsed = Duration::ZERO;
+        self.step += 1;
+        if self.step == self.script.len() {
+            if !self.looping {
+                self.script.clear();
+                return;
+            }
+            self.step = 0;
+        }
+        let step = self.script[self.step];
+        self.target = (step.kind, step.intensity.clamp(0.0, 1.0));
+    }
+
+    /// Short high pitched ticks, denser as the rain gets heavier
+    fn queue_drops(&mut self, dts: f32, rng: &mut Rng) {
+        if self.state.kind != WeatherKind::Rain || self.state.intensity <= 0.0 {
+            self.drop_timer = 0.0;
+            return;
+        }
+        self.drop_timer += dts * self.drops_per_second * self.state.intensity;
+        while self.drop_timer >= 1.0 {
+            self.drop_timer -= 1.0;
+            let pitch = rng.next_f32();
+            self.sounds.push(SoundCue {
+                frequency: 2000.0 + pitch * 3000.0,
+                duration: Duration::from_millis(12),
+                volume: 0.03 * self.state.intensity,
+            });
+        }
+    }
+
+    /// Raindrops queued since the last call
+    pub fn take_sounds(&mut self) -> Vec<SoundCue> {
+        std::mem::take(&mut self.sounds)
+    }
+}
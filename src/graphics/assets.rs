@@ -1,13 +1,47 @@
-use std::string::FromUtf8Error;
+use std::{convert::Infallible, string::FromUtf8Error, sync::OnceLock};
 
 use asset_tree::asset_files;
 use image::ImageError;
+use rayon::prelude::*;
+
+use super::utils::HdrPrecision;
 
 pub struct ModelFile(pub String);
 pub struct MaterialFile(pub String);
-pub struct TextureFile(pub image::DynamicImage);
+/// Adobe .cube color lookup table
+pub struct LutFile(pub String);
+/// Only the format is checked on load, pixels are decoded on first access
+pub struct TextureFile {
+    bytes: Vec<u8>,
+    image: OnceLock<image::DynamicImage>,
+}
+/// Radiance .hdr or OpenEXR image, told apart by their content and decoded to linear rgba floats
+pub struct HdrTextureFile {
+    pub image: image::Rgba32FImage,
+    /// Full for the exr sources, authored with more range and precision than half floats keep
+    pub precision: HdrPrecision,
+}
+/// Editor theme, see [`crate::app::editor::theme::Theme::parse`]
+pub struct ThemeFile(pub String);
+/// Text scene, see [`crate::scene::Scene::parse`]
+pub struct SceneFile(pub String);
+/// TrueType font, registered in egui under the file name
+pub struct FontFile(pub Vec<u8>);
+/// Strings of a language, see [`crate::locale::StringTable::parse`]
+pub struct LocaleFile(pub String);
 
-asset_files!(ModelFile: "obj", MaterialFile: "mtl", TextureFile: "png",);
+asset_files!(
+    ModelFile: "obj",
+    MaterialFile: "mtl",
+    TextureFile: "png",
+    HdrTextureFile: "hdr",
+    HdrTextureFile: "exr",
+    LutFile: "cube",
+    ThemeFile: "theme",
+    FontFile: "ttf",
+    SceneFile: "scene",
+    LocaleFile: "ftl",
+);
 
 impl TryFrom<Vec<u8>> for ModelFile {
     type Error = FromUtf8Error;
@@ -25,10 +59,115 @@ impl TryFrom<Vec<u8>> for MaterialFile {
     }
 }
 
+impl TryFrom<Vec<u8>> for LutFile {
+    type Error = FromUtf8Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self(String::from_utf8(value)?))
+    }
+}
+
+impl TryFrom<Vec<u8>> for ThemeFile {
+    type Error = FromUtf8Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self(String::from_utf8(value)?))
+    }
+}
+
+impl TryFrom<Vec<u8>> for SceneFile {
+    type Error = FromUtf8Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self(String::from_utf8(value)?))
+    }
+}
+
+impl TryFrom<Vec<u8>> for LocaleFile {
+    type Error = FromUtf8Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self(String::from_utf8(value)?))
+    }
+}
+
+impl TryFrom<Vec<u8>> for FontFile {
+    type Error = Infallible;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Ok(Self(value))
+    }
+}
+
 impl TryFrom<Vec<u8>> for TextureFile {
     type Error = ImageError;
 
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
-        Ok(Self(image::load_from_memory(&value)?))
+        image::guess_format(&value)?;
+        Ok(Self {
+            bytes: value,
+            image: OnceLock::new(),
+        })
+    }
+}
+
+impl TextureFile {
+    pub fn image(&self) -> &image::DynamicImage {
+        self.image.get_or_init(|| {
+            image::load_from_memory(&self.bytes)
+                .unwrap_or_else(|e| panic!("Failed to decode texture: {e}"))
+        })
+    }
+}
+
+/// Decodes the textures on the rayon thread pool, already decoded ones are skipped
+pub fn decode_textures(files: &[&TextureFile]) {
+    files.par_iter().for_each(|file| {
+        file.image();
+    });
+}
+
+impl TryFrom<Vec<u8>> for HdrTextureFile {
+    type Error = ImageError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let (format, precision) = match image::guess_format(&value)? {
+            image::ImageFormat::OpenExr => (image::ImageFormat::OpenExr, HdrPrecision::Full),
+            _ => (image::ImageFormat::Hdr, HdrPrecision::Half),
+        };
+        let image = image::load_from_memory_with_format(&value, format)?;
+        Ok(Self {
+            image: image.into_rgba32f(),
+            precision,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn exr_loads_at_full_precision() {
+        let pixels = image::Rgba32FImage::from_fn(2, 2, |x, y| {
+            image::Rgba([x as f32 * 4.0, y as f32 * 0.5, 1e-3, 1.0])
+        });
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba32F(pixels.clone())
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::OpenExr)
+            .unwrap();
+
+        let file = HdrTextureFile::try_from(bytes).unwrap();
+        assert_eq!(file.precision, HdrPrecision::Full);
+        assert_eq!(file.image, pixels);
+    }
+
+    #[test]
+    fn hdr_loads_at_half_precision() {
+        let bytes = include_bytes!("../../assets/environments/Sky.hdr").to_vec();
+        let file = HdrTextureFile::try_from(bytes).unwrap();
+        assert_eq!(file.precision, HdrPrecision::Half);
     }
 }
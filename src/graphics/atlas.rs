@@ -1,39 +1,96 @@
 use std::collections::HashMap;
 
 use guillotiere::{size2, AllocId, AtlasAllocator};
-use image::{imageops::overlay, EncodableLayout, RgbaImage};
+use image::{
+    imageops::{overlay, resize, FilterType},
+    EncodableLayout, RgbaImage,
+};
 
-use crate::graphics::{ctx::GraphicsCtx, utils::TextureWrapper};
+use crate::graphics::{
+    ctx::GraphicsCtx,
+    utils::{ColorSpace, TextureWrapper},
+};
 
-use super::buffer::{CommonBuffer, StorageBuffer};
+use super::{
+    buffer::{CommonBuffer, MappedSparse, StorageBuffer, UniformBuffer, WriteBuffer},
+    sampler::{material_samplers, SamplerDesc},
+    settings::TextureQuality,
+};
+
+/// Mip levels of the atlas, the smaller ones would blend the neighbouring textures together
+const ATLAS_MIPS: u32 = 5;
 
 pub struct AtlasPacker {
     atlas: AtlasAllocator,
     images: HashMap<AllocId, RgbaImage>,
     dims: (u32, u32),
+    /// Every texture of a packed atlas shares the same color space
+    color_space: ColorSpace,
+}
+
+/// Uv rect of a texture slot and the color space its pixels are decoded from
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AtlasSlot {
+    /// Min then max
+    rect: [f32; 4],
+    /// 0 for sRGB, 1 for linear
+    color_space: u32,
+    _padding: [u32; 3],
+}
+
+crate::wgsl_layout!(AtlasSlot as "TextureAtlasUV" {
+    rect,
+    color_space,
+});
+
+impl AtlasSlot {
+    fn new(min: [f32; 2], max: [f32; 2], color_space: ColorSpace) -> Self {
+        Self {
+            rect: [min[0], min[1], max[0], max[1]],
+            color_space: match color_space {
+                ColorSpace::Srgb => 0,
+                ColorSpace::Linear => 1,
+            },
+            _padding: [0; 3],
+        }
+    }
 }
 
+/// Texture of the atlas with the uv rect of each texture slot
+///
+/// The uvs are an indirection table updated in place, a texture added at runtime takes a slot
+/// with [`Self::add_texture`] and the bind group is only recreated when the table grows
+///
+/// Color and data textures share the atlas, it is bound through an sRGB view and a linear one
+/// and each slot says which to sample
 pub struct AtlasUniform {
     /*
-    packer: AtlasAllocator,
+    packer: AtlasAllocator, */
     texture: TextureWrapper,
-    uvs_buffer: StorageBuffer<[[f32; 2]; 2]>, */
+    uvs_buffer: MappedSparse<StorageBuffer<AtlasSlot>>,
+    mip_bias: UniformBuffer<f32>,
+    quality: TextureQuality,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl AtlasPacker {
-    pub fn new() -> Self {
+    pub fn new(color_space: ColorSpace) -> Self {
         let dims = (2048, 2048);
         Self {
             //TODO: add auto growing of atlas
             atlas: AtlasAllocator::new(dims.into()),
             images: HashMap::new(),
             dims: (dims.0 as u32, dims.1 as u32),
+            color_space,
         }
     }
 
-    pub fn from_textures<T: Into<RgbaImage>>(images: impl IntoIterator<Item = T>) -> Self {
-        let mut packer = Self::new();
+    pub fn from_textures<T: Into<RgbaImage>>(
+        color_space: ColorSpace,
+        images: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let mut packer = Self::new(color_space);
         packer.add_images(images);
         packer
     }
@@ -54,19 +111,19 @@ impl AtlasPacker {
         }
     }
 
-    pub fn build_atlas(&mut self, ctx: &GraphicsCtx) -> AtlasUniform {
+    pub fn build_atlas(&mut self, ctx: &GraphicsCtx, quality: &TextureQuality) -> AtlasUniform {
         let (width, height) = self.dims;
-        let mut texture = RgbaImage::new(width, height);
+        let mut pixels = RgbaImage::new(width, height);
         let mut uvs = Vec::with_capacity(self.images.len());
         self.atlas.for_each_allocated_rectangle(|id, rectangle| {
             let image = self.images.get(&id).unwrap();
             overlay(
-                &mut texture,
+                &mut pixels,
                 image,
                 rectangle.min.x as i64,
                 rectangle.min.y as i64,
             );
-            uvs.push([
+            uvs.push(AtlasSlot::new(
                 [
                     rectangle.min.x as f32 / width as f32,
                     rectangle.min.y as f32 / height as f32,
@@ -75,43 +132,237 @@ impl AtlasPacker {
                     rectangle.max.x as f32 / width as f32,
                     rectangle.max.y as f32 / height as f32,
                 ],
-            ]);
+                self.color_space,
+            ));
         });
 
-        let texture =
-            TextureWrapper::new_rgba_2d("Models Atlas", ctx, self.dims, texture.as_bytes());
-
-        let uvs_buffer = StorageBuffer::new_const_array("Atlas uvs", ctx, uvs);
+        let texture = TextureWrapper::new_rgba_2d_mixed(
+            "Models Atlas",
+            ctx,
+            self.dims,
+            pixels.as_bytes(),
+            SamplerDesc::NEAREST,
+            mip_level_count(self.dims),
+        );
+        write_mips(ctx, &texture.texture, (0, 0), &pixels, 1);
 
-        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &atlas_uniform_bind_group_layout(ctx),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: uvs_buffer.binding(),
-                },
-            ],
-            label: Some("Atlas Bind Group"),
-        });
+        let uvs_buffer = MappedSparse::new("Atlas uvs", ctx, uvs);
+        let mip_bias = UniformBuffer::new("Atlas mip bias", ctx, &quality.mip_bias);
+        let bind_group = atlas_bind_group(ctx, &texture, &uvs_buffer, &mip_bias, quality);
 
         AtlasUniform {
             /*
-            packer: self.atlas.clone(),
+            packer: self.atlas.clone(), */
             texture,
-            uvs_buffer, */
+            uvs_buffer,
+            mip_bias,
+            quality: *quality,
             bind_group,
         }
     }
 }
 
+impl AtlasUniform {
+    /// Blank atlas meant to be filled region by region, see [`super::streaming::TextureStreamer`]
+    pub fn new_empty(
+        ctx: &GraphicsCtx,
+        dims: (u32, u32),
+        texture_count: usize,
+        quality: &TextureQuality,
+    ) -> Self {
+        let blank = vec![0u8; (dims.0 * dims.1 * 4) as usize];
+        let texture = TextureWrapper::new_rgba_2d_mixed(
+            "Streamed Atlas",
+            ctx,
+            dims,
+            &blank,
+            SamplerDesc::NEAREST,
+            mip_level_count(dims),
+        );
+
+        let uvs_buffer = MappedSparse::new(
+            "Atlas uvs",
+            ctx,
+            vec![AtlasSlot::default(); texture_count.max(1)],
+        );
+        let mip_bias = UniformBuffer::new("Atlas mip bias", ctx, &quality.mip_bias);
+        let bind_group = atlas_bind_group(ctx, &texture, &uvs_buffer, &mip_bias, quality);
+
+        Self {
+            texture,
+            uvs_buffer,
+            mip_bias,
+            quality: *quality,
+            bind_group,
+        }
+    }
+
+    /// Stable slot of a new texture, empty until [`Self::write_region`] fills it
+    pub fn add_texture(&mut self) -> u32 {
+        self.uvs_buffer.push(Default::default())
+    }
+
+    /// The slot is reused by the next added texture
+    pub fn remove_texture(&mut self, texture_id: u32) {
+        self.uvs_buffer.remove(texture_id);
+    }
+
+    pub fn texture_count(&self) -> u32 {
+        self.uvs_buffer.len()
+    }
+
+    /// Uploads the uv changes, the bind group is recreated only if the table had to grow
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
+        if self.uvs_buffer.apply_changes(ctx) {
+            let bind_group = atlas_bind_group(
+                ctx,
+                &self.texture,
+                &self.uvs_buffer,
+                &self.mip_bias,
+                &self.quality,
+            );
+            ctx.retire(std::mem::replace(&mut self.bind_group, bind_group));
+        }
+    }
+
+    /// Writes the pixels at `origin` of every mip level and points the uvs of `texture_id` to them
+    /// on the next [`Self::apply_changes`], they are decoded as `color_space` when sampled
+    pub fn write_region(
+        &mut self,
+        ctx: &GraphicsCtx,
+        texture_id: u32,
+        origin: (u32, u32),
+        image: &RgbaImage,
+        color_space: ColorSpace,
+    ) {
+        let size = self.texture.texture.size();
+        write_mips(ctx, &self.texture.texture, origin, image, 0);
+
+        let uv = |x: u32, y: u32| [x as f32 / size.width as f32, y as f32 / size.height as f32];
+        self.uvs_buffer.set(
+            texture_id,
+            AtlasSlot::new(
+                uv(origin.0, origin.1),
+                uv(origin.0 + image.width(), origin.1 + image.height()),
+                color_space,
+            ),
+        );
+    }
+
+    /// Recreates the samplers and the bind group using the new texture quality
+    pub fn apply_quality(&mut self, ctx: &GraphicsCtx, quality: &TextureQuality) {
+        self.quality = *quality;
+        self.mip_bias.write(ctx, &quality.mip_bias);
+        self.bind_group = atlas_bind_group(
+            ctx,
+            &self.texture,
+            &self.uvs_buffer,
+            &self.mip_bias,
+            quality,
+        );
+    }
+}
+
+fn mip_level_count((width, height): (u32, u32)) -> u32 {
+    ATLAS_MIPS.min(32 - width.max(height).max(1).leading_zeros())
+}
+
+/// Writes `image` at `origin` from `first_level` on, downsampled on the cpu. The texels of the
+/// smaller levels are snapped to their grid
+fn write_mips(
+    ctx: &GraphicsCtx,
+    texture: &wgpu::Texture,
+    origin: (u32, u32),
+    image: &RgbaImage,
+    first_level: u32,
+) {
+    for level in first_level..texture.mip_level_count() {
+        let level_size = (
+            (texture.width() >> level).max(1),
+            (texture.height() >> level).max(1),
+        );
+        let (x, y) = (origin.0 >> level, origin.1 >> level);
+        if x >= level_size.0 || y >= level_size.1 {
+            break;
+        }
+        let width = ((origin.0 + image.width()) >> level)
+            .saturating_sub(x)
+            .clamp(1, level_size.0 - x);
+        let height = ((origin.1 + image.height()) >> level)
+            .saturating_sub(y)
+            .clamp(1, level_size.1 - y);
+        let resized;
+        let texels = match (width, height) == image.dimensions() {
+            true => image,
+            false => {
+                resized = resize(image, width, height, FilterType::Triangle);
+                &resized
+            }
+        };
+        ctx.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: level,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            texels.as_bytes(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+fn atlas_bind_group(
+    ctx: &GraphicsCtx,
+    texture: &TextureWrapper,
+    uvs_buffer: &StorageBuffer<AtlasSlot>,
+    mip_bias: &UniformBuffer<f32>,
+    quality: &TextureQuality,
+) -> wgpu::BindGroup {
+    let [nearest, filtered] = material_samplers(quality).map(|desc| ctx.sampler(desc));
+    let srgb_view = texture.view_as(ColorSpace::Srgb);
+
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &atlas_uniform_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&srgb_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&nearest),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uvs_buffer.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&filtered),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: mip_bias.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+        ],
+        label: Some("Atlas Bind Group"),
+    })
+}
+
 pub fn atlas_uniform_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
     ctx.device
         .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -142,6 +393,32 @@ pub fn atlas_uniform_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayo
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
             ],
             label: Some("Atlas Bind Group Layout"),
         })
@@ -0,0 +1,71 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// `None` without points
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::point(first), |aabb, point| aabb.with_point(point)))
+    }
+
+    pub fn point(point: Point3<f32>) -> Self {
+        Self {
+            min: point,
+            max: point,
+        }
+    }
+
+    pub fn with_point(self, point: Point3<f32>) -> Self {
+        Self {
+            min: self.min.inf(&point),
+            max: self.max.sup(&point),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Radius of the bounding sphere around the center
+    pub fn radius(&self) -> f32 {
+        self.size().norm() * 0.5
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        })
+    }
+
+    /// Bounds of the transformed box, larger than the transformed content when rotated
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+        Self::from_points(
+            self.corners()
+                .map(|corner| transform.transform_point(&corner)),
+        )
+        .unwrap_or(*self)
+    }
+}
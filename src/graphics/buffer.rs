@@ -1,6 +1,6 @@
 use std::{
     borrow::Borrow,
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Range},
 };
 
 use bytemuck::NoUninit;
@@ -104,8 +104,42 @@ pub trait WriteBuffer: CommonBuffer {
     }
 }
 
+/// Raw uniform contents padded to the 16 bytes steps uniform buffers are bound in
+pub fn padded_uniform_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().max(1).next_multiple_of(16), 0);
+    padded
+}
+
+/// Empty contents still get one zeroed item, empty buffers cannot be bound nor sliced. Capacities
+/// only count the items written, such a buffer grows on its first push
+fn create_buffer_init(
+    ctx: &GraphicsCtx,
+    label: &str,
+    contents: &[u8],
+    item_size: u64,
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    if contents.is_empty() {
+        return ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: item_size.max(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage,
+            mapped_at_creation: false,
+        });
+    }
+    wgpu::util::DeviceExt::create_buffer_init(
+        &ctx.device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage,
+        },
+    )
+}
+
 macro_rules! impl_buffer_write {
-    ($($name:ident : $($usage:ident)?),*) => {
+    ($($name:ident : $($usage:ident)|+),*) => {
         $(
           pub struct $name<T> {
               inner: wgpu::Buffer,
@@ -128,13 +162,12 @@ macro_rules! impl_buffer_write {
                 }
 
                fn new_array(label: &str, ctx: &GraphicsCtx, data: impl Borrow<[Self::Item]>) -> Self {
-                  let buffer = wgpu::util::DeviceExt::create_buffer_init(
-                      &ctx.device,
-                      &wgpu::util::BufferInitDescriptor {
-                          label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                          contents: bytemuck::cast_slice(data.borrow()),
-                          usage: $(wgpu::BufferUsages::$usage |)? wgpu::BufferUsages::COPY_DST,
-                      },
+                  let buffer = create_buffer_init(
+                      ctx,
+                      &format!("{} Buffer: {}", stringify!($name), label),
+                      bytemuck::cast_slice(data.borrow()),
+                      Self::ITEM_BYTE_SIZE,
+                      $(wgpu::BufferUsages::$usage |)+ wgpu::BufferUsages::COPY_DST,
                   );
                   Self {
                       inner: buffer,
@@ -142,24 +175,18 @@ macro_rules! impl_buffer_write {
                   }
               }
 
-                #[allow(unreachable_code)]
                fn new_const_array(label: &str, ctx: &GraphicsCtx, data: impl Borrow<[Self::Item]>) -> Self {
-                    $(
-                        let buffer = wgpu::util::DeviceExt::create_buffer_init(
-                            &ctx.device,
-                            &wgpu::util::BufferInitDescriptor {
-                                label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                                contents: bytemuck::cast_slice(data.borrow()),
-                                usage: wgpu::BufferUsages::$usage,
-                            },
-                        );
-                        return Self {
-                            inner: buffer,
-                            _marker: std::marker::PhantomData,
-                        };
-                    )?
-
-                    unimplemented!("Staging buffer cannot be constant");
+                    let buffer = create_buffer_init(
+                        ctx,
+                        &format!("{} Buffer: {}", stringify!($name), label),
+                        bytemuck::cast_slice(data.borrow()),
+                        Self::ITEM_BYTE_SIZE,
+                        $(wgpu::BufferUsages::$usage)|+,
+                    );
+                    Self {
+                        inner: buffer,
+                        _marker: std::marker::PhantomData,
+                    }
               }
 
                fn new_vec_with_capacity(label: &str, ctx: &GraphicsCtx, data: impl Borrow<[Self::Item]>, capacity: usize) -> Growable<Self>  {
@@ -167,13 +194,12 @@ macro_rules! impl_buffer_write {
                     if capacity < slice.len() {
                         panic!("Growable (vec) buffer capacity must be greater than or equal to the length of the provided data slice")
                     }
-                    let buffer = wgpu::util::DeviceExt::create_buffer_init(
-                        &ctx.device,
-                        &wgpu::util::BufferInitDescriptor {
-                            label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                            contents: bytemuck::cast_slice(slice),
-                            usage: $(wgpu::BufferUsages::$usage |)? wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
-                        },
+                    let buffer = create_buffer_init(
+                        ctx,
+                        &format!("{} Buffer: {}", stringify!($name), label),
+                        bytemuck::cast_slice(slice),
+                        Self::ITEM_BYTE_SIZE,
+                        $(wgpu::BufferUsages::$usage |)+ wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
                     );
                     Growable {
                         inner: Self {
@@ -189,8 +215,8 @@ macro_rules! impl_buffer_write {
                  fn new_empty(label: &str, ctx: &GraphicsCtx, capacity: usize) -> Self {
                     let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
                         label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                        size: capacity as u64 * Self::ITEM_BYTE_SIZE,
-                        usage: $(wgpu::BufferUsages::$usage |)? wgpu::BufferUsages::COPY_DST,
+                        size: capacity.max(1) as u64 * Self::ITEM_BYTE_SIZE,
+                        usage: $(wgpu::BufferUsages::$usage |)+ wgpu::BufferUsages::COPY_DST,
                         mapped_at_creation: false,
                     });
                     Self {
@@ -202,8 +228,8 @@ macro_rules! impl_buffer_write {
                  fn new_empty_vec(label: &str, ctx: &GraphicsCtx, capacity: usize) -> Growable<Self> {
                     let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
                         label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                        size: capacity as u64 * Self::ITEM_BYTE_SIZE,
-                        usage: $(wgpu::BufferUsages::$usage |)? wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                        size: capacity.max(1) as u64 * Self::ITEM_BYTE_SIZE,
+                        usage: $(wgpu::BufferUsages::$usage |)+ wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
                         mapped_at_creation: false,
                     });
                     Growable {
@@ -235,43 +261,37 @@ macro_rules! impl_buffer_write {
 impl_buffer_write!(
     VertexBuffer: VERTEX,
     IndexBuffer: INDEX,
-    InstanceBuffer: VERTEX,
+    // Storage allows compute passes to animate instances in place
+    InstanceBuffer: VERTEX | STORAGE,
     UniformBuffer: UNIFORM,
     StorageBuffer: STORAGE,
     StagingBuffer: COPY_SRC
 );
 
+/// Storage allows compute passes to read meshes from it and generate draws into it
 pub struct IndirectBuffer {
     pub inner: wgpu::Buffer,
 }
 
 impl IndirectBuffer {
-    const ARG_INSTANCE_COUNT_BYTE_OFFSET: u64 = 4;
-    const ARG_FIRST_INSTANCE_BYTE_OFFSET: u64 = 16;
+    const ARG_FIRST_INDEX_BYTE_OFFSET: u64 = 8;
 
-    pub fn write_instance_count_at_index(
+    /// Points the draw at other indices and vertices, its instances are kept
+    pub fn write_geometry_at_index(
         &self,
         ctx: &GraphicsCtx,
         index: u32,
-        instance_count: u32,
+        index_count: u32,
+        first_index: u32,
+        base_vertex: i32,
     ) {
+        let offset = index as u64 * Self::ITEM_BYTE_SIZE;
+        ctx.queue
+            .write_buffer(&self.inner, offset, bytemuck::bytes_of(&index_count));
         ctx.queue.write_buffer(
             &self.inner,
-            Self::ARG_INSTANCE_COUNT_BYTE_OFFSET + index as u64 * Self::ITEM_BYTE_SIZE,
-            bytemuck::bytes_of(&instance_count),
-        );
-    }
-
-    pub fn write_first_instance_at_index(
-        &self,
-        ctx: &GraphicsCtx,
-        index: u32,
-        first_instance: u32,
-    ) {
-        ctx.queue.write_buffer(
-            &self.inner,
-            Self::ARG_FIRST_INSTANCE_BYTE_OFFSET + index as u64 * Self::ITEM_BYTE_SIZE,
-            bytemuck::bytes_of(&first_instance),
+            offset + Self::ARG_FIRST_INDEX_BYTE_OFFSET,
+            bytemuck::cast_slice(&[first_index, base_vertex as u32]),
         );
     }
 }
@@ -292,27 +312,29 @@ impl CommonBuffer for IndirectBuffer {
     }
 
     fn new_array(label: &str, ctx: &GraphicsCtx, data: impl Borrow<[Self::Item]>) -> Self {
-        let inner = wgpu::util::DeviceExt::create_buffer_init(
-            &ctx.device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("Indirect Buffer: {}", label)),
-                contents: cast_iia(data.borrow()),
-                usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
-            },
+        let inner = create_buffer_init(
+            ctx,
+            &format!("Indirect Buffer: {}", label),
+            cast_iia(data.borrow()),
+            Self::ITEM_BYTE_SIZE,
+            // Read back by the robustness tests
+            wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
         );
 
         Self { inner }
     }
 
     fn new_const_array(label: &str, ctx: &GraphicsCtx, data: impl Borrow<[Self::Item]>) -> Self {
-        let inner = wgpu::util::DeviceExt::create_buffer_init(
-            &ctx.device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("Indirect Buffer: {}", label)),
-                // SAFETY: `DrawIndexedIndirectArgs` is repr(C) and made to be casted to `[u32; _]`
-                contents: cast_iia(data.borrow()),
-                usage: wgpu::BufferUsages::INDIRECT,
-            },
+        let inner = create_buffer_init(
+            ctx,
+            &format!("Indirect Buffer: {}", label),
+            // SAFETY: `DrawIndexedIndirectArgs` is repr(C) and made to be casted to `[u32; _]`
+            cast_iia(data.borrow()),
+            Self::ITEM_BYTE_SIZE,
+            wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
         );
 
         Self { inner }
@@ -321,8 +343,10 @@ impl CommonBuffer for IndirectBuffer {
     fn new_empty(label: &str, ctx: &GraphicsCtx, capacity: usize) -> Self {
         let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-            size: capacity as u64 * Self::ITEM_BYTE_SIZE,
-            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+            size: capacity.max(1) as u64 * Self::ITEM_BYTE_SIZE,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
@@ -339,15 +363,15 @@ impl CommonBuffer for IndirectBuffer {
         if capacity < slice.len() {
             panic!("Growable (vec) buffer capacity must be greater than or equal to the length of the provided data slice")
         }
-        let buffer = wgpu::util::DeviceExt::create_buffer_init(
-            &ctx.device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-                contents: cast_iia(slice),
-                usage: wgpu::BufferUsages::INDIRECT
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::COPY_SRC,
-            },
+        let buffer = create_buffer_init(
+            ctx,
+            &format!("{} Buffer: {}", stringify!($name), label),
+            cast_iia(slice),
+            Self::ITEM_BYTE_SIZE,
+            wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
         );
         Growable {
             inner: Self { inner: buffer },
@@ -360,8 +384,9 @@ impl CommonBuffer for IndirectBuffer {
     fn new_empty_vec(label: &str, ctx: &GraphicsCtx, capacity: usize) -> Growable<Self> {
         let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some(&format!("{} Buffer: {}", stringify!($name), label)),
-            size: capacity as u64 * Self::ITEM_BYTE_SIZE,
+            size: capacity.max(1) as u64 * Self::ITEM_BYTE_SIZE,
             usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
                 | wgpu::BufferUsages::COPY_DST
                 | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
@@ -394,6 +419,34 @@ impl WriteBuffer for IndirectBuffer {
     }
 }
 
+/// Blocks until the gpu is done with the queued work, the buffer needs `COPY_SRC`. For tests and
+/// debugging, never on the frame path
+pub fn read_buffer(ctx: &GraphicsCtx, buffer: &wgpu::Buffer, bytes: Range<u64>) -> Vec<u8> {
+    let size = bytes.end - bytes.start;
+    if size == 0 {
+        return Vec::new();
+    }
+    let staging = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback"),
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback"),
+        });
+    encoder.copy_buffer_to_buffer(buffer, bytes.start, &staging, 0, size);
+    ctx.queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| ());
+    let _ = ctx.device.poll(wgpu::Maintain::Wait);
+    let data = slice.get_mapped_range().to_vec();
+    data
+}
+
 fn cast_iia(args: &[wgpu::util::DrawIndexedIndirectArgs]) -> &[u8] {
     // SAFETY: `DrawIndexedIndirectArgs` is repr(C) and made to be casted to `[u32; _]`
     unsafe {
@@ -418,43 +471,77 @@ impl<T: CommonBuffer> Growable<T> {
     }
 
     /// Grows the inner buffer to the next power of two that is greater than or equal to `required_size` if needed.
+    ///
+    /// The capacity is capped by [`GraphicsCtx::max_buffer_bytes`], the callers must not write
+    /// past [`Self::capacity`] when `required_size` does not fit, see [`Self::fitting`]
     pub fn maybe_grow(&mut self, ctx: &GraphicsCtx, required_size: usize) -> bool {
-        let grow = required_size > self.capacity;
+        let max_capacity = (ctx.max_buffer_bytes() / T::ITEM_BYTE_SIZE.max(1)) as usize;
+        let grow = required_size > self.capacity && self.capacity < max_capacity;
         if grow {
             // Compute new buffer size (double current size or required size)
             let new_capacity = self.capacity.max(1) * 2;
-            let new_capacity = new_capacity.max(required_size);
-            let new_buffer = T::new_empty_vec(
-                {
-                    #[cfg(debug_assertions)]
-                    let l = self.label.as_str();
-                    #[cfg(not(debug_assertions))]
-                    let l = "";
-                    l
-                },
-                ctx,
-                new_capacity,
+            let new_capacity = new_capacity.max(required_size).min(max_capacity);
+            self.grow_to(ctx, new_capacity);
+        }
+        grow
+    }
+
+    /// Grows the inner buffer to exactly `capacity` if it is smaller, so a known peak is
+    /// allocated once up front instead of doubling during gameplay. Returns true if it grew
+    pub fn reserve(&mut self, ctx: &GraphicsCtx, capacity: usize) -> bool {
+        let max_capacity = (ctx.max_buffer_bytes() / T::ITEM_BYTE_SIZE.max(1)) as usize;
+        let capacity = capacity.min(max_capacity);
+        let grow = capacity > self.capacity;
+        if grow {
+            self.grow_to(ctx, capacity);
+        }
+        grow
+    }
+
+    /// The start of `items` the buffer has room for, a warning is printed when some are dropped
+    pub fn fitting<'a, I>(&self, items: &'a [I]) -> &'a [I] {
+        if items.len() > self.capacity {
+            eprintln!(
+                "Dropped {} items over the capacity of {} allowed by the device",
+                items.len() - self.capacity,
+                self.capacity
             );
+        }
+        &items[..items.len().min(self.capacity)]
+    }
 
-            if self.capacity > 0 {
-                let mut encoder =
-                    ctx.device
-                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                            label: Some("Growable Buffer Copy Encoder"),
-                        });
-                encoder.copy_buffer_to_buffer(
-                    &self.inner(),
-                    0,
-                    &new_buffer.inner(),
-                    0,
-                    self.capacity as u64 * T::ITEM_BYTE_SIZE,
-                );
-                ctx.queue.submit(Some(encoder.finish()));
-            }
+    /// Moves the contents into a new buffer of `new_capacity` items
+    fn grow_to(&mut self, ctx: &GraphicsCtx, new_capacity: usize) {
+        let new_buffer = T::new_empty_vec(
+            {
+                #[cfg(debug_assertions)]
+                let l = self.label.as_str();
+                #[cfg(not(debug_assertions))]
+                let l = "";
+                l
+            },
+            ctx,
+            new_capacity,
+        );
 
-            *self = new_buffer;
+        if self.capacity > 0 {
+            let mut encoder = ctx
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Growable Buffer Copy Encoder"),
+                });
+            encoder.copy_buffer_to_buffer(
+                &self.inner(),
+                0,
+                &new_buffer.inner(),
+                0,
+                self.capacity as u64 * T::ITEM_BYTE_SIZE,
+            );
+            ctx.queue.submit(Some(encoder.finish()));
         }
-        grow
+
+        ctx.retire(self.inner().clone());
+        *self = new_buffer;
     }
 }
 
@@ -512,13 +599,30 @@ impl<I: Default, T: CommonBuffer<Item = I> + WriteBuffer<Item = I>> MappedSparse
         self.ids.len()
     }
 
+    /// Whether the next push reuses a removed slot instead of growing the length
+    pub fn has_free_slot(&self) -> bool {
+        self.ids.has_free()
+    }
+
     //TODO: use staging belt?
     /// Returns true if the buffer was grown
     pub fn apply_changes(&mut self, ctx: &GraphicsCtx) -> bool {
         let grown = self.inner.maybe_grow(ctx, self.ids.len() as usize);
+        let capacity = self.inner.capacity();
+        let mut dropped = 0;
         for (idx, data) in self.changes.drain(..) {
+            // Over the device limit, the slot is never read
+            if idx as usize >= capacity {
+                dropped += 1;
+                continue;
+            }
             self.inner.write_at_index(ctx, &data, idx);
         }
+        if dropped > 0 {
+            eprintln!(
+                "Dropped {dropped} writes over the capacity of {capacity} allowed by the device"
+            );
+        }
         grown
     }
 }
@@ -536,11 +640,25 @@ impl<T: CommonBuffer> DerefMut for MappedSparse<T> {
     }
 }
 
+/// Columns of a growing buffer are repacked once this share of the slots is unused, see
+/// [`DenseMapped2d::rebalance_threshold`]
+pub const DEFAULT_REBALANCE_THRESHOLD: f32 = 0.5;
+/// Grows remembered per column, a column that grew this often gets the most headroom
+const MAX_GROW_HISTORY: usize = 8;
+const OWNER_BYTE_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
 pub struct DenseMapped2d<T: CommonBuffer> {
     inner: Growable<T>,
     columns: Vec<ColumnMeta<T::Item>>,
+    /// Column of every slot plus one, 0 on the free slots. Lets compute passes find the values
+    /// of a column without the cpu, see [`Self::owners`]
+    owners: Growable<StorageBuffer<u32>>,
 
     ttl_capacity: usize,
+    /// Share of unused slots above which a grow repacks every column instead of doubling the one
+    /// that overflowed, the capacities are then reallocated from the grow history of the columns
+    pub rebalance_threshold: f32,
+    rebalance_count: u32,
 
     #[cfg(debug_assertions)]
     label: String,
@@ -551,10 +669,72 @@ struct ColumnMeta<T> {
     index_offset: usize,
     changes: Vec<ColumnOp<T>>,
     ids: DenseIdAllocator,
+    /// Times the column overflowed, halved by every rebalance so old growth fades out
+    grows: usize,
+    /// Capacity kept at least, see [`DenseMapped2d::reserve`]
+    reserved: usize,
 }
 
+impl<T> ColumnMeta<T> {
+    /// Slots possibly read on the gpu once the changes are applied, the pending removals still
+    /// swap from the end of the column
+    fn resident_len(&self) -> usize {
+        let removals = self
+            .changes
+            .iter()
+            .filter(|op| matches!(op, ColumnOp::Remove(_)))
+            .count();
+        self.ids.len() + removals
+    }
+
+    /// Room left after a rebalance, columns that kept growing get the most headroom and quiet ones
+    /// are packed tightly
+    fn rebalanced_capacity(&self) -> usize {
+        let len = self.ids.len();
+        let headroom = len * self.grows.min(MAX_GROW_HISTORY) / 2 + len / 8;
+        (len + headroom).max(self.resident_len()).max(self.reserved)
+    }
+}
+
+/// Capacities of the columns once their pending changes fit, then whether the buffer is repacked
+/// with them instead of growing the overflowing columns in place
+fn planned_capacities<T>(
+    columns: &mut [ColumnMeta<T>],
+    ttl_capacity: usize,
+    rebalance_threshold: f32,
+) -> (Vec<usize>, bool) {
+    let mut capacities = Vec::with_capacity(columns.len());
+    for column in columns.iter_mut() {
+        // Slots past the final length may be written before a removal swaps them back
+        let peak = column.resident_len();
+        if peak > column.capacity {
+            column.grows += 1;
+            capacities.push((column.capacity.max(1) * 2).max(peak).max(column.reserved));
+        } else {
+            capacities.push(column.capacity.max(column.reserved));
+        }
+    }
+    let ttl_new_capacity = capacities.iter().sum::<usize>();
+
+    let used = columns.iter().map(|c| c.ids.len()).sum::<usize>();
+    let unused = 1.0 - used as f32 / ttl_new_capacity.max(1) as f32;
+    if ttl_new_capacity > ttl_capacity && unused > rebalance_threshold {
+        // The grow copies the buffer anyway, each column is moved once to its new place
+        let capacities = columns
+            .iter()
+            .map(ColumnMeta::rebalanced_capacity)
+            .collect();
+        (capacities, true)
+    } else {
+        (capacities, false)
+    }
+}
+
+/// Replayed in order on the gpu, the indices are the ones of the slots when the op was queued
+/// since later removals may swap them
 enum ColumnOp<T> {
-    Insert(T, DenseId),
+    Insert(T, u32),
+    Update(T, u32),
     Remove(DenseArrayOp),
 }
 
@@ -564,12 +744,6 @@ pub struct Slot2dId {
     pub dense: DenseId,
 }
 
-#[derive(Debug)]
-pub enum ColumnChange {
-    Moved { new_offset: usize },
-    Resized { new_size: usize },
-}
-
 impl<T: CommonBuffer + WriteBuffer> DenseMapped2d<T>
 where
     T::Item: NoUninit,
@@ -582,9 +756,21 @@ where
     ) -> Self {
         let data = data.borrow();
         let inner = T::new_vec(label, ctx, data);
+        let columns_size: Vec<u16> = columns_size.into_iter().collect();
+        let owners: Vec<u32> = (1..)
+            .zip(&columns_size)
+            .flat_map(|(owner, size)| std::iter::repeat(owner).take(*size as usize))
+            .collect();
+        let owners = StorageBuffer::new_vec_with_capacity(
+            &format!("{label} owners"),
+            ctx,
+            &owners[..],
+            data.len(),
+        );
         let mut offset_acc = 0;
         Self {
             inner,
+            owners,
             columns: columns_size
                 .into_iter()
                 .map(|c| ColumnMeta {
@@ -596,9 +782,13 @@ where
                     },
                     changes: vec![],
                     ids: DenseIdAllocator::new_packed(c as u32),
+                    grows: 0,
+                    reserved: 0,
                 })
                 .collect(),
             ttl_capacity: data.len(),
+            rebalance_threshold: DEFAULT_REBALANCE_THRESHOLD,
+            rebalance_count: 0,
 
             #[cfg(debug_assertions)]
             label: label.to_string(),
@@ -608,13 +798,22 @@ where
     pub fn push(&mut self, column_id: u16, value: T::Item) -> Slot2dId {
         let column = &mut self.columns[column_id as usize];
         let id = column.ids.allocate();
-        column.changes.push(ColumnOp::Insert(value, id));
+        let index = column.ids.len() as u32 - 1;
+        column.changes.push(ColumnOp::Insert(value, index));
         Slot2dId {
             row_id: column_id,
             dense: id,
         }
     }
 
+    /// Overwrites the value in place, the slot keeps its position
+    pub fn set(&mut self, id: &Slot2dId, value: T::Item) {
+        let column = &mut self.columns[id.row_id as usize];
+        if let Some(index) = column.ids.get_index(id.dense) {
+            column.changes.push(ColumnOp::Update(value, index));
+        }
+    }
+
     pub fn remove(&mut self, id: Slot2dId) {
         let column = &mut self.columns[id.row_id as usize];
         if let Some(array_op) = column.ids.free(id.dense) {
@@ -622,23 +821,129 @@ where
         }
     }
 
-    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) -> (bool, Vec<(u16, ColumnChange)>) {
-        let mut changes = Vec::new();
-        let new_capacities = self
-            .columns
-            .iter()
-            .map(|c| {
-                if c.ids.len() > c.capacity {
-                    (c.capacity.max(1) * 2).max(c.ids.len())
-                } else {
-                    c.capacity
-                }
-            })
-            .collect::<Box<_>>();
-        let ttl_new_capacity = new_capacities.iter().sum::<usize>();
+    /// Keeps at least `capacity` slots for the column from the next [`Self::apply_changes`] on,
+    /// even through a rebalance, so the column does not grow until it passes them
+    pub fn reserve(&mut self, column_id: u16, capacity: usize) {
+        self.columns[column_id as usize].reserved = capacity;
+    }
+
+    /// Share of the slots not holding a value, from 0 to 1
+    pub fn fragmentation(&self) -> f32 {
+        let used = self.columns.iter().map(|c| c.ids.len()).sum::<usize>();
+        match self.ttl_capacity {
+            0 => 0.0,
+            capacity => 1.0 - used.min(capacity) as f32 / capacity as f32,
+        }
+    }
+
+    /// Values then slots of each column, once the changes are applied
+    pub fn column_occupancy(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.columns.iter().map(|c| (c.ids.len(), c.capacity))
+    }
+
+    /// Slots of the values of the column, once the changes are applied
+    pub fn column_range(&self, column_id: u16) -> Range<usize> {
+        let column = &self.columns[column_id as usize];
+        column.index_offset..column.index_offset + column.ids.len()
+    }
+
+    /// Ids of the values of the column, in slot order once the changes are applied
+    pub fn column_ids(&self, column_id: u16) -> impl Iterator<Item = DenseId> + '_ {
+        self.columns[column_id as usize].ids.iter().copied()
+    }
+
+    /// Column plus one of each slot, 0 when free, as of the last [`Self::apply_changes`]. The values
+    /// of a column are packed from its first slot
+    pub fn owners(&self) -> &Growable<StorageBuffer<u32>> {
+        &self.owners
+    }
+
+    /// Values of the column read back from the gpu, in slot order
+    pub fn read_column(&self, ctx: &GraphicsCtx, column_id: u16) -> Vec<T::Item>
+    where
+        T::Item: bytemuck::AnyBitPattern,
+    {
+        let range = self.column_range(column_id);
+        let bytes = read_buffer(
+            ctx,
+            self.inner.inner(),
+            range.start as u64 * T::ITEM_BYTE_SIZE..range.end as u64 * T::ITEM_BYTE_SIZE,
+        );
+        bytemuck::pod_collect_to_vec(&bytes)
+    }
 
-        if ttl_new_capacity > self.ttl_capacity {
+    /// Columns are packed in order within the buffer and hold their values, once the changes are
+    /// applied
+    pub fn check(&self) -> Result<(), String> {
+        let mut offset = 0;
+        for (column_id, column) in self.columns.iter().enumerate() {
+            column
+                .ids
+                .check()
+                .map_err(|e| format!("Column {column_id}: {e}"))?;
+            if !column.changes.is_empty() {
+                return Err(format!("Column {column_id} has changes left"));
+            }
+            if column.index_offset != offset {
+                return Err(format!(
+                    "Column {column_id} is at {}, expected {offset}",
+                    column.index_offset
+                ));
+            }
+            if column.ids.len() > column.capacity {
+                return Err(format!(
+                    "Column {column_id} holds {} values in {} slots",
+                    column.ids.len(),
+                    column.capacity
+                ));
+            }
+            offset += column.capacity;
+        }
+        if offset != self.ttl_capacity || offset > self.inner.capacity() {
+            return Err(format!(
+                "Columns take {offset} slots, {} expected in a buffer of {}",
+                self.ttl_capacity,
+                self.inner.capacity()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Times the columns were repacked, see [`Self::rebalance_threshold`]
+    pub fn rebalance_count(&self) -> u32 {
+        self.rebalance_count
+    }
+
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
+        let (new_capacities, repack) = planned_capacities(
+            &mut self.columns,
+            self.ttl_capacity,
+            self.rebalance_threshold,
+        );
+        let ttl_new_capacity = new_capacities.iter().sum::<usize>();
+        if repack {
+            self.rebalance(ctx, &new_capacities);
+        } else if ttl_new_capacity > self.ttl_capacity {
             let new_buffer = T::new_empty_vec(&self.label, ctx, ttl_new_capacity);
+            let new_owners: Growable<StorageBuffer<u32>> =
+                StorageBuffer::new_empty_vec(&self.label, ctx, ttl_new_capacity);
+            // Moves the slots with their owners
+            let copy = |encoder: &mut wgpu::CommandEncoder, from: usize, to: usize, size: usize| {
+                encoder.copy_buffer_to_buffer(
+                    self.inner.inner(),
+                    from as u64 * T::ITEM_BYTE_SIZE,
+                    new_buffer.inner(),
+                    to as u64 * T::ITEM_BYTE_SIZE,
+                    size as u64 * T::ITEM_BYTE_SIZE,
+                );
+                encoder.copy_buffer_to_buffer(
+                    self.owners.inner(),
+                    from as u64 * OWNER_BYTE_SIZE,
+                    new_owners.inner(),
+                    to as u64 * OWNER_BYTE_SIZE,
+                    size as u64 * OWNER_BYTE_SIZE,
+                );
+            };
             let mut encoder = ctx
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -669,12 +974,11 @@ where
                 old_block_size += old_cap;
                 new_block_size += new_cap;
                 if grow {
-                    encoder.copy_buffer_to_buffer(
-                        self.inner.inner(),
-                        rest.old_offset as u64 * T::ITEM_BYTE_SIZE,
-                        new_buffer.inner(),
-                        rest.new_offset as u64 * T::ITEM_BYTE_SIZE,
-                        old_block_size as u64 * T::ITEM_BYTE_SIZE,
+                    copy(
+                        &mut encoder,
+                        rest.old_offset,
+                        rest.new_offset,
+                        old_block_size,
                     );
                     rest = MoveBlock {
                         old_offset: rest.old_offset + old_block_size,
@@ -689,71 +993,100 @@ where
                 // skip first columns before first update
                 if move_needed {
                     column.index_offset = prev_offset;
-                    changes.push((
-                        column_id as u16,
-                        ColumnChange::Moved {
-                            new_offset: prev_offset,
-                        },
-                    ));
                 } else if grow {
                     move_needed = true;
                 }
                 prev_offset += new_cap;
             }
 
-            encoder.copy_buffer_to_buffer(
-                self.inner.inner(),
-                rest.old_offset as u64 * T::ITEM_BYTE_SIZE,
-                new_buffer.inner(),
-                rest.new_offset as u64 * T::ITEM_BYTE_SIZE,
-                rest.size as u64 * T::ITEM_BYTE_SIZE,
-            );
+            copy(&mut encoder, rest.old_offset, rest.new_offset, rest.size);
 
             ctx.queue.submit(Some(encoder.finish()));
+            ctx.retire(self.inner.inner().clone());
+            ctx.retire(self.owners.inner().clone());
             self.inner = new_buffer;
+            self.owners = new_owners;
         }
 
         for (column_id, column) in self.columns.iter_mut().enumerate() {
-            let mut size_diff = 0;
+            let offset = column.index_offset as u32;
+            let owner = column_id as u32 + 1;
+            // Length on the gpu before the changes, the freed slot is the last one
+            let inserts = column
+                .changes
+                .iter()
+                .filter(|op| matches!(op, ColumnOp::Insert(..)))
+                .count();
+            let mut len = (column.resident_len() - inserts) as u32;
             for op in column.changes.drain(..) {
                 match op {
-                    ColumnOp::Insert(value, id) => {
-                        if let Some(idx) = column.ids.get_index(id) {
-                            size_diff += 1;
-                            self.inner.write_at_index(
-                                ctx,
-                                &value,
-                                column.index_offset as u32 + idx,
-                            );
-                        }
+                    ColumnOp::Insert(value, idx) => {
+                        len += 1;
+                        self.inner.write_at_index(ctx, &value, offset + idx);
+                        self.owners.write_at_index(ctx, &owner, offset + idx);
+                    }
+                    ColumnOp::Update(value, idx) => {
+                        self.inner.write_at_index(ctx, &value, offset + idx);
                     }
                     ColumnOp::Remove(op) => {
-                        size_diff -= 1;
-                        match op {
-                            DenseArrayOp::RemoveLast {} => (),
-                            DenseArrayOp::SwapRemove { index, last } => {
-                                self.inner.swap_at_indices(ctx, index, last);
-                            }
+                        len -= 1;
+                        if let DenseArrayOp::SwapRemove { index, last } = op {
+                            self.inner
+                                .swap_at_indices(ctx, offset + index, offset + last);
                         }
+                        self.owners.write_at_index(ctx, &0, offset + len);
                     }
                 }
-                if size_diff != 0 {
-                    changes.push((
-                        column_id as u16,
-                        ColumnChange::Resized {
-                            new_size: column.ids.len() as usize,
-                        },
-                    ));
-                }
             }
         }
 
         self.ttl_capacity = ttl_new_capacity;
-        for (i, new_cap) in IntoIterator::into_iter(new_capacities).enumerate() {
+        for (i, new_cap) in new_capacities.into_iter().enumerate() {
             self.columns[i].capacity = new_cap;
         }
+    }
+
+    /// Copies the resident slots of every column into a buffer laid out with `capacities`
+    fn rebalance(&mut self, ctx: &GraphicsCtx, capacities: &[usize]) {
+        let new_buffer = T::new_empty_vec(&self.label, ctx, capacities.iter().sum());
+        let new_owners: Growable<StorageBuffer<u32>> =
+            StorageBuffer::new_empty_vec(&self.label, ctx, capacities.iter().sum());
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Mapped2d Rebalance Encoder"),
+            });
+
+        let mut offset = 0;
+        for (column_id, column) in self.columns.iter_mut().enumerate() {
+            let size = column.resident_len().min(column.capacity);
+            if size > 0 {
+                encoder.copy_buffer_to_buffer(
+                    self.inner.inner(),
+                    column.index_offset as u64 * T::ITEM_BYTE_SIZE,
+                    new_buffer.inner(),
+                    offset as u64 * T::ITEM_BYTE_SIZE,
+                    size as u64 * T::ITEM_BYTE_SIZE,
+                );
+                encoder.copy_buffer_to_buffer(
+                    self.owners.inner(),
+                    column.index_offset as u64 * OWNER_BYTE_SIZE,
+                    new_owners.inner(),
+                    offset as u64 * OWNER_BYTE_SIZE,
+                    size as u64 * OWNER_BYTE_SIZE,
+                );
+            }
+            column.index_offset = offset;
+            column.grows /= 2;
+            offset += capacities[column_id];
+        }
 
-        (false, changes)
+        ctx.queue.submit(Some(encoder.finish()));
+        ctx.retire(self.inner.inner().clone());
+        ctx.retire(self.owners.inner().clone());
+        self.inner = new_buffer;
+        self.owners = new_owners;
+        self.rebalance_count += 1;
     }
 }
 
@@ -769,3 +1102,74 @@ impl<T: CommonBuffer> DerefMut for DenseMapped2d<T> {
         &mut self.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(len: u32, capacity: usize) -> ColumnMeta<u32> {
+        ColumnMeta {
+            capacity,
+            index_offset: 0,
+            changes: vec![],
+            ids: DenseIdAllocator::new_packed(len),
+            grows: 0,
+            reserved: 0,
+        }
+    }
+
+    fn push(column: &mut ColumnMeta<u32>) {
+        column.ids.allocate();
+        let index = column.ids.len() as u32 - 1;
+        column.changes.push(ColumnOp::Insert(0, index));
+    }
+
+    #[test]
+    fn pending_removals_stay_resident() {
+        let mut column = column(4, 4);
+        let op = column.ids.free(DenseId::from_raw(1)).unwrap();
+        column.changes.push(ColumnOp::Remove(op));
+        push(&mut column);
+        assert_eq!(column.ids.len(), 4);
+        assert_eq!(column.resident_len(), 5);
+    }
+
+    #[test]
+    fn overflowing_column_doubles() {
+        let mut columns = [column(4, 4), column(2, 4)];
+        push(&mut columns[0]);
+        let (capacities, repack) = planned_capacities(&mut columns, 8, 0.5);
+        assert_eq!(capacities, [8, 4]);
+        assert!(!repack);
+        assert_eq!(columns[0].grows, 1);
+        assert_eq!(columns[1].grows, 0);
+    }
+
+    #[test]
+    fn fitting_changes_keep_the_capacities() {
+        let mut columns = [column(3, 4), column(0, 2)];
+        push(&mut columns[0]);
+        let (capacities, repack) = planned_capacities(&mut columns, 6, 0.5);
+        assert_eq!(capacities, [4, 2]);
+        assert!(!repack);
+    }
+
+    #[test]
+    fn sparse_grow_repacks_by_history() {
+        let mut columns = [column(1, 64), column(4, 4)];
+        push(&mut columns[1]);
+        let (capacities, repack) = planned_capacities(&mut columns, 68, 0.5);
+        assert!(repack);
+        // The quiet column is packed, the one that grew gets half its length of headroom
+        assert_eq!(capacities, [1, 7]);
+    }
+
+    #[test]
+    fn reserve_survives_a_repack() {
+        let mut columns = [column(0, 2)];
+        columns[0].reserved = 10;
+        let (capacities, repack) = planned_capacities(&mut columns, 2, 0.5);
+        assert!(repack);
+        assert_eq!(capacities, [10]);
+    }
+}
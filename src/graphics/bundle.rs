@@ -0,0 +1,92 @@
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use wgpu::RenderBundle;
+
+use super::ctx::GraphicsCtx;
+
+static NEXT_RESOURCE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies one creation of a resource, a recreated resource gets a new id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(u64);
+
+impl ResourceId {
+    fn next() -> Self {
+        Self(NEXT_RESOURCE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Bind group or buffer recorded into bundles, see [`TrackedBundle`]
+pub struct Tracked<T> {
+    value: T,
+    id: ResourceId,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            id: ResourceId::next(),
+        }
+    }
+
+    pub fn id(&self) -> ResourceId {
+        self.id
+    }
+}
+
+impl<T: Send + 'static> Tracked<T> {
+    /// The bundles recorded with the previous value are rerecorded on their next refresh, the
+    /// previous value is retired until the gpu is done with them
+    pub fn replace(&mut self, ctx: &GraphicsCtx, value: T) {
+        ctx.retire(std::mem::replace(&mut self.value, value));
+        self.id = ResourceId::next();
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// Render bundle rerecorded once one of the resources it was recorded with is recreated
+pub struct TrackedBundle {
+    bundle: RenderBundle,
+    dependencies: Vec<ResourceId>,
+}
+
+impl TrackedBundle {
+    pub fn new(dependencies: &[ResourceId], record: impl FnOnce() -> RenderBundle) -> Self {
+        Self {
+            bundle: record(),
+            dependencies: dependencies.to_vec(),
+        }
+    }
+
+    /// Rerecords the bundle when `dependencies` differ from the ones it was recorded with,
+    /// returns true when it did
+    pub fn refresh(
+        &mut self,
+        dependencies: &[ResourceId],
+        record: impl FnOnce() -> RenderBundle,
+    ) -> bool {
+        if self.dependencies == dependencies {
+            return false;
+        }
+        self.bundle = record();
+        self.dependencies = dependencies.to_vec();
+        true
+    }
+}
+
+impl Deref for TrackedBundle {
+    type Target = RenderBundle;
+    fn deref(&self) -> &Self::Target {
+        &self.bundle
+    }
+}
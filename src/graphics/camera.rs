@@ -1,20 +1,53 @@
-use nalgebra::{Matrix4, Perspective3, Point3, Rotation3, Vector2, Vector3, Vector4};
+use nalgebra::{Matrix4, Orthographic3, Perspective3, Point3, Rotation3, Vector2, Vector3};
 
 use crate::constants;
 
 use super::{
     buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
     ctx::GraphicsCtx,
+    frame::{FrameConstants, RawFrameConstants},
 };
 
 #[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0, 
     0.0, 1.0, 0.0, 0.0,
     0.0, 0.0, 0.5, 0.5, 
     0.0, 0.0, 0.0, 1.0,
 );
 
+/// Matches `RelativeView` in the entities shader, bound with the view it is derived from
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RawRelativeView {
+    /// View of the positions taken relative to `eye`, only its rotation is left
+    pub view: Matrix4<f32>,
+    pub eye: [f32; 3],
+    pub _padding: u32,
+}
+
+crate::wgsl_layout!(RawRelativeView as "RelativeView" { view, eye });
+
+impl RawRelativeView {
+    /// Composed in f64, the eye is rounded once to the precision of the instances translations
+    /// and the translation left to the view cancels out instead of carrying the rounding errors
+    pub fn new(view: &Matrix4<f32>) -> Self {
+        let view = view.cast::<f64>();
+        let eye = view
+            .try_inverse()
+            .map(|inverse| inverse.transform_point(&Point3::origin()))
+            .unwrap_or_else(Point3::origin)
+            .map(|c| c as f32);
+        let relative = view * Matrix4::new_translation(&eye.coords.cast::<f64>());
+        Self {
+            view: relative.cast::<f32>(),
+            eye: eye.into(),
+            _padding: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub eye: Point3<f32>,
     pub pitch_deg: f32,
@@ -36,8 +69,27 @@ impl Default for Camera {
 }
 
 impl Camera {
+    /// Composed in f64, the translation is rounded once instead of at every product
     pub fn compute_view_matrix(&self) -> Matrix4<f32> {
-        self.compute_rot_matrix() * Matrix4::new_translation(&-Vector4::from(self.eye).xyz())
+        let rotation = self.compute_rot_matrix().cast::<f64>();
+        let eye = self.eye.coords.cast::<f64>();
+        (rotation * Matrix4::new_translation(&-eye)).cast::<f32>()
+    }
+
+    /// World space direction the camera looks at
+    pub fn forward(&self) -> Vector3<f32> {
+        self.compute_rot_matrix()
+            .transpose()
+            .transform_vector(&-Vector3::z())
+    }
+
+    /// Turns the camera towards the target, the roll is kept
+    pub fn look_at(&mut self, target: &Point3<f32>) {
+        let Some(direction) = (target - self.eye).try_normalize(1e-6) else {
+            return;
+        };
+        self.pitch_deg = direction.y.asin().to_degrees();
+        self.yaw_deg = (-direction.x).atan2(-direction.z).to_degrees();
     }
 
     pub fn compute_rot_matrix(&self) -> Matrix4<f32> {
@@ -48,21 +100,48 @@ impl Camera {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct Projection {
     pub size: Vector2<u32>,
     pub fov_deg: f32,
+    pub ortho: bool,
+    /// World space height of the view when `ortho` is set
+    pub ortho_height: f32,
 }
 
 impl Projection {
+    pub fn new(size: Vector2<u32>, fov_deg: f32) -> Self {
+        Self {
+            size,
+            fov_deg,
+            ortho: false,
+            ortho_height: 20.0,
+        }
+    }
+
     pub fn compute_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX
-            * Perspective3::new(
-                self.size.x as f32 / self.size.y as f32,
+        let aspect = self.size.x as f32 / self.size.y as f32;
+        let matrix = if self.ortho {
+            let (half_w, half_h) = (self.ortho_height * aspect * 0.5, self.ortho_height * 0.5);
+            Orthographic3::new(
+                -half_w,
+                half_w,
+                -half_h,
+                half_h,
+                constants::MODEL_ZNEAR,
+                constants::MODE_ZFAR,
+            )
+            .to_homogeneous()
+        } else {
+            Perspective3::new(
+                aspect,
                 self.fov_deg.to_radians(),
                 constants::MODEL_ZNEAR,
                 constants::MODE_ZFAR,
             )
             .to_homogeneous()
+        };
+        OPENGL_TO_WGPU_MATRIX * matrix
     }
 }
 
@@ -90,6 +169,29 @@ pub fn view_proj_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
                     },
                     count: None,
                 },
+                // Frame constants, also read by the fragment and compute stages
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT
+                        | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("view_proj_bind_group_layout"),
         })
@@ -101,7 +203,7 @@ pub fn inv_view_proj_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayo
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -111,7 +213,7 @@ pub fn inv_view_proj_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayo
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -121,7 +223,19 @@ pub fn inv_view_proj_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayo
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT
+                        | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -134,10 +248,12 @@ pub fn inv_view_proj_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayo
         })
 }
 
-fn view_proj_bindgroup(
+pub fn view_proj_bindgroup(
     ctx: &GraphicsCtx,
     view_buffer: &UniformBuffer<Matrix4<f32>>,
     proj_buffer: &UniformBuffer<Matrix4<f32>>,
+    frame_buffer: &UniformBuffer<RawFrameConstants>,
+    relative_buffer: &UniformBuffer<RawRelativeView>,
 ) -> wgpu::BindGroup {
     ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: &view_proj_bind_group_layout(ctx),
@@ -150,6 +266,14 @@ fn view_proj_bindgroup(
                 binding: 1,
                 resource: proj_buffer.binding(),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: frame_buffer.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: relative_buffer.binding(),
+            },
         ],
         label: Some("view_proj_bindgroup"),
     })
@@ -160,6 +284,7 @@ fn inv_view_proj_bind_group(
     view_buffer: &UniformBuffer<Matrix4<f32>>,
     proj_buffer: &UniformBuffer<Matrix4<f32>>,
     size_buffer: &UniformBuffer<Vector2<u32>>,
+    frame_buffer: &UniformBuffer<RawFrameConstants>,
 ) -> wgpu::BindGroup {
     ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: &inv_view_proj_bind_group_layout(ctx),
@@ -176,28 +301,48 @@ fn inv_view_proj_bind_group(
                 binding: 2,
                 resource: size_buffer.binding(),
             },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: frame_buffer.binding(),
+            },
         ],
         label: Some("view_proj_bindgroup"),
     })
 }
 
 /// Uniforms for view and projection matrices in vertex shader
-/// Inversed and screen size can also be used in fragment shader
+/// Inversed and screen size can also be used in fragment and compute shaders
 pub struct CameraUniform {
     view: UniformBuffer<Matrix4<f32>>,
     proj: UniformBuffer<Matrix4<f32>>,
+    relative: UniformBuffer<RawRelativeView>,
     inv_view: UniformBuffer<Matrix4<f32>>,
     inv_proj: UniformBuffer<Matrix4<f32>>,
     viewport_size: UniformBuffer<Vector2<u32>>,
     pub view_proj_bindgroup: wgpu::BindGroup,
     pub inv_view_proj_bindgroup: wgpu::BindGroup,
+
+    /// Last written matrices, kept for cpu side projections
+    view_matrix: Matrix4<f32>,
+    proj_matrix: Matrix4<f32>,
 }
 
 impl CameraUniform {
-    pub fn new(ctx: &GraphicsCtx) -> Self {
+    pub fn new(ctx: &GraphicsCtx, frame: &FrameConstants) -> Self {
         let view_buffer = UniformBuffer::new("view", ctx, &Matrix4::identity());
         let proj_buffer = UniformBuffer::new("camera", ctx, &Matrix4::identity());
-        let view_proj_bindgroup = view_proj_bindgroup(ctx, &view_buffer, &proj_buffer);
+        let relative_buffer = UniformBuffer::new(
+            "relative_view",
+            ctx,
+            &RawRelativeView::new(&Matrix4::identity()),
+        );
+        let view_proj_bindgroup = view_proj_bindgroup(
+            ctx,
+            &view_buffer,
+            &proj_buffer,
+            &frame.buffer,
+            &relative_buffer,
+        );
 
         let inv_view_buffer = UniformBuffer::new("inv_view", ctx, &Matrix4::identity());
         let inv_proj_buffer = UniformBuffer::new("inv_camera", ctx, &Matrix4::identity());
@@ -207,22 +352,60 @@ impl CameraUniform {
             &inv_view_buffer,
             &inv_proj_buffer,
             &viewport_size_buffer,
+            &frame.buffer,
         );
 
         Self {
             view: view_buffer,
             proj: proj_buffer,
+            relative: relative_buffer,
             inv_view: inv_view_buffer,
             inv_proj: inv_proj_buffer,
             viewport_size: viewport_size_buffer,
             view_proj_bindgroup,
             inv_view_proj_bindgroup,
+            view_matrix: Matrix4::identity(),
+            proj_matrix: Matrix4::identity(),
         }
     }
 
+    /// Matrices of the current frame, for cpu side projections
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.proj_matrix * self.view_matrix
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        self.view_matrix
+    }
+
+    pub fn proj_matrix(&self) -> Matrix4<f32> {
+        self.proj_matrix
+    }
+
+    /// Position of the camera of the current frame
+    pub fn eye(&self) -> Point3<f32> {
+        self.view_matrix
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .transform_point(&Point3::origin())
+    }
+
+    /// Maps normalized device coordinates of the current frame back to world space
+    pub fn inv_view_proj(&self) -> Matrix4<f32> {
+        (self.proj_matrix * self.view_matrix)
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+    }
+
     pub fn update_view(&mut self, ctx: &GraphicsCtx, camera: &Camera) {
-        let view = camera.compute_view_matrix();
+        self.update_view_matrix(ctx, camera.compute_view_matrix());
+    }
+
+    /// For views no [`Camera`] can describe, such as reflections
+    pub fn update_view_matrix(&mut self, ctx: &GraphicsCtx, view: Matrix4<f32>) {
+        self.view_matrix = view;
         self.view.write(ctx, &view);
+        self.relative.write(ctx, &RawRelativeView::new(&view));
         self.inv_view.write(
             ctx,
             &view.try_inverse().expect("View matrix is not invertible"),
@@ -230,8 +413,17 @@ impl CameraUniform {
     }
 
     pub fn update_proj(&mut self, ctx: &GraphicsCtx, proj: &Projection) {
-        let size = proj.size;
-        let proj = proj.compute_matrix();
+        self.update_proj_matrix(ctx, proj.compute_matrix(), proj.size);
+    }
+
+    /// `size` is the viewport the projection is drawn into
+    pub fn update_proj_matrix(
+        &mut self,
+        ctx: &GraphicsCtx,
+        proj: Matrix4<f32>,
+        size: Vector2<u32>,
+    ) {
+        self.proj_matrix = proj;
         self.proj.write(ctx, &proj);
         self.inv_proj.write(
             ctx,
@@ -0,0 +1,554 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::ctx::GraphicsCtx;
+
+/// Folder the recordings are written to, next to the executable's working directory
+pub const CAPTURE_PATH: &str = "captures";
+/// Frames copied out at once, none is copied while every slot waits for the gpu
+const RING_SIZE: usize = 3;
+/// Frames read back but not encoded yet, the next ones are dropped once it is full
+const QUEUE_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureFormat {
+    /// Encoded in the engine, the frames are held in memory until the recording stops since
+    /// their count comes first in the file
+    Apng,
+    /// Through `ffmpeg`, which must be on the path
+    Gif,
+    /// H.264 through `ffmpeg`, which must be on the path
+    #[default]
+    Mp4,
+}
+
+impl CaptureFormat {
+    pub const ALL: [CaptureFormat; 3] =
+        [CaptureFormat::Apng, CaptureFormat::Gif, CaptureFormat::Mp4];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaptureFormat::Apng => "APNG",
+            CaptureFormat::Gif => "GIF",
+            CaptureFormat::Mp4 => "MP4",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CaptureFormat::Apng => "png",
+            CaptureFormat::Gif => "gif",
+            CaptureFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+/// Read when a recording starts, edits apply to the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureSettings {
+    pub format: CaptureFormat,
+    pub fps: u32,
+    /// Frames are shrunk by this factor, averaging the pixels
+    pub downscale: u32,
+    /// Recordings stop by themselves past this many seconds
+    pub max_seconds: f32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            format: CaptureFormat::default(),
+            fps: 30,
+            downscale: 1,
+            max_seconds: 60.0,
+        }
+    }
+}
+
+/// Pixels of a frame without the row padding of the copy, in the order of the surface format
+struct CapturedFrame {
+    pixels: Vec<u8>,
+    /// Since the start of the recording
+    time: Duration,
+}
+
+enum SlotState {
+    Free,
+    /// Copy recorded in the frame, mapped once submitted
+    Copied,
+    Mapping(Arc<AtomicBool>),
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+    time: Duration,
+}
+
+struct Recording {
+    path: PathBuf,
+    size: (u32, u32),
+    row_bytes: u32,
+    settings: CaptureSettings,
+    slots: Vec<Slot>,
+    /// Slots holding frames, oldest first so they are encoded in order
+    pending: VecDeque<usize>,
+    start: Instant,
+    last_frame: Option<Duration>,
+    frames: u32,
+    dropped: u32,
+    sender: SyncSender<CapturedFrame>,
+    encoder: JoinHandle<Result<u32, String>>,
+}
+
+impl Recording {
+    /// Sends the frames whose copy reached the buffers to the encoder thread
+    fn read_ready(&mut self) {
+        while let Some(&i) = self.pending.front() {
+            let slot = &mut self.slots[i];
+            let SlotState::Mapping(mapped) = &slot.state else {
+                break;
+            };
+            if !mapped.load(Ordering::Acquire) {
+                break;
+            }
+            self.pending.pop_front();
+
+            let width_bytes = self.size.0 as usize * 4;
+            let pixels = slot
+                .buffer
+                .slice(..)
+                .get_mapped_range()
+                .chunks(self.row_bytes as usize)
+                .flat_map(|row| &row[..width_bytes])
+                .copied()
+                .collect();
+            slot.buffer.unmap();
+            slot.state = SlotState::Free;
+
+            let frame = CapturedFrame {
+                pixels,
+                time: slot.time,
+            };
+            match self.sender.try_send(frame) {
+                Ok(()) => self.frames += 1,
+                Err(TrySendError::Full(_)) => self.dropped += 1,
+                // The encoder failed, reported once the recording stops
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+    }
+}
+
+/// Records the frames to a video or an animated image while toggled on, for bug reports and
+/// devlogs
+///
+/// Frames are copied into a ring of readback buffers before the gui is drawn over them, read
+/// a frame or two later and encoded on their own thread. Frames arriving while the ring or
+/// the encoder queue are full are dropped and counted
+#[derive(Default)]
+pub struct FrameCapture {
+    pub settings: CaptureSettings,
+    recording: Option<Recording>,
+    /// Encoders of the stopped recordings still writing their file
+    finishing: Vec<(PathBuf, JoinHandle<Result<u32, String>>)>,
+    toggle_requested: bool,
+    last_error: Option<String>,
+}
+
+impl FrameCapture {
+    /// Starts or stops recording on the next frame
+    pub fn toggle(&mut self) {
+        self.toggle_requested = !self.toggle_requested;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Frames sent to the encoder and frames dropped, with the length of the recording
+    pub fn progress(&self) -> Option<(u32, u32, Duration)> {
+        let recording = self.recording.as_ref()?;
+        Some((
+            recording.frames,
+            recording.dropped,
+            recording.start.elapsed(),
+        ))
+    }
+
+    /// Recordings whose file is still being written
+    pub fn finishing(&self) -> usize {
+        self.finishing.len()
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Called at the start of every frame, handles the toggle and reads back the finished
+    /// copies
+    pub fn poll(&mut self, ctx: &GraphicsCtx) {
+        if std::mem::take(&mut self.toggle_requested) {
+            match self.recording {
+                Some(_) => self.stop(),
+                None => {
+                    if let Err(e) = self.start(ctx) {
+                        eprintln!("Failed to start capturing: {e}");
+                        self.last_error = Some(e);
+                    }
+                }
+            }
+        }
+        self.join_finished();
+
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if recording.size != ctx.viewport_size {
+            eprintln!("Capture stopped, the viewport was resized");
+            self.stop();
+            return;
+        }
+        if recording.encoder.is_finished()
+            || recording.start.elapsed().as_secs_f32() > recording.settings.max_seconds
+        {
+            self.stop();
+            return;
+        }
+        ctx.device.poll(wgpu::Maintain::Poll);
+        recording.read_ready();
+    }
+
+    /// Copies the frame `texture` into a free slot when one is due, before the gui is drawn
+    pub fn copy(&mut self, encoder: &mut wgpu::CommandEncoder, texture: Option<&wgpu::Texture>) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        let time = recording.start.elapsed();
+        let interval = Duration::from_secs_f32(1.0 / recording.settings.fps.max(1) as f32);
+        if recording
+            .last_frame
+            .is_some_and(|last| time < last + interval)
+        {
+            return;
+        }
+        recording.last_frame = Some(time);
+
+        let Some(i) = recording
+            .slots
+            .iter()
+            .position(|slot| matches!(slot.state, SlotState::Free))
+        else {
+            recording.dropped += 1;
+            return;
+        };
+        let Some(texture) = texture else {
+            return;
+        };
+        let slot = &mut recording.slots[i];
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &slot.buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(recording.row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        slot.state = SlotState::Copied;
+        slot.time = time;
+        recording.pending.push_back(i);
+    }
+
+    /// Maps the slots copied this frame, once it was submitted
+    pub fn after_submit(&mut self) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        for slot in &mut recording.slots {
+            if !matches!(slot.state, SlotState::Copied) {
+                continue;
+            }
+            let mapped = Arc::new(AtomicBool::new(false));
+            let done = mapped.clone();
+            slot.buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    // Fails when the recording stopped before, the frame is dropped with it
+                    if result.is_ok() {
+                        done.store(true, Ordering::Release);
+                    }
+                });
+            slot.state = SlotState::Mapping(mapped);
+        }
+    }
+
+    fn start(&mut self, ctx: &GraphicsCtx) -> Result<(), String> {
+        if !ctx.can_copy_frames() {
+            return Err("The surface does not allow copying its frames".to_string());
+        }
+        let bgra = match ctx.surface_format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => {
+                return Err(format!(
+                    "Frames in {format:?} can not be captured, use the sdr output"
+                ))
+            }
+        };
+
+        std::fs::create_dir_all(CAPTURE_PATH).map_err(|e| e.to_string())?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let settings = self.settings;
+        let path = Path::new(CAPTURE_PATH)
+            .join(format!("capture-{stamp}.{}", settings.format.extension()));
+
+        let size = ctx.viewport_size;
+        let row_bytes = (size.0 * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let slots = (0..RING_SIZE)
+            .map(|_| Slot {
+                buffer: ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Capture readback"),
+                    size: row_bytes as u64 * size.1 as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Free,
+                time: Duration::ZERO,
+            })
+            .collect();
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let encoder = {
+            let path = path.clone();
+            std::thread::Builder::new()
+                .name("Capture encoder".to_string())
+                .spawn(move || encode(settings, size, bgra, &path, receiver))
+                .map_err(|e| e.to_string())?
+        };
+
+        println!("Capturing to {}", path.display());
+        self.last_error = None;
+        self.recording = Some(Recording {
+            path,
+            size,
+            row_bytes,
+            settings,
+            slots,
+            pending: VecDeque::new(),
+            start: Instant::now(),
+            last_frame: None,
+            frames: 0,
+            dropped: 0,
+            sender,
+            encoder,
+        });
+        Ok(())
+    }
+
+    /// The frames still in the ring are dropped, the encoder finishes the file on its own
+    fn stop(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        if recording.dropped > 0 {
+            eprintln!(
+                "Capture dropped {} frames, lower the fps or raise the downscale",
+                recording.dropped
+            );
+        }
+        self.finishing.push((recording.path, recording.encoder));
+    }
+
+    fn join_finished(&mut self) {
+        let (done, finishing): (Vec<_>, Vec<_>) = std::mem::take(&mut self.finishing)
+            .into_iter()
+            .partition(|(_, encoder)| encoder.is_finished());
+        self.finishing = finishing;
+        for (path, encoder) in done {
+            match encoder.join() {
+                Ok(Ok(frames)) => println!("Saved {frames} frames to {}", path.display()),
+                Ok(Err(e)) => {
+                    eprintln!("Failed to encode {}: {e}", path.display());
+                    self.last_error = Some(e);
+                }
+                Err(_) => eprintln!("The encoder of {} panicked", path.display()),
+            }
+        }
+    }
+}
+
+/// Where the encoder thread writes the frames
+enum Sink {
+    /// Frames with their time, written once all are known
+    Apng(Vec<(Vec<u8>, Duration)>),
+    Ffmpeg {
+        child: Child,
+        stdin: ChildStdin,
+        written: u64,
+    },
+}
+
+/// Runs on the encoder thread until the recording stops, returns the frames written
+fn encode(
+    settings: CaptureSettings,
+    size: (u32, u32),
+    bgra: bool,
+    path: &Path,
+    frames: Receiver<CapturedFrame>,
+) -> Result<u32, String> {
+    let downscale = settings.downscale.max(1);
+    let out_size = ((size.0 / downscale).max(1), (size.1 / downscale).max(1));
+    let fps = settings.fps.max(1);
+    let mut sink = match settings.format {
+        CaptureFormat::Apng => Sink::Apng(Vec::new()),
+        format => spawn_ffmpeg(format, out_size, fps, path)?,
+    };
+
+    let mut count = 0;
+    for frame in frames {
+        let pixels = to_rgba(&frame.pixels, size, bgra, downscale);
+        match &mut sink {
+            Sink::Apng(frames) => frames.push((pixels, frame.time)),
+            Sink::Ffmpeg { stdin, written, .. } => {
+                // The video has a constant rate, frames are repeated over the ones missed
+                let due = (frame.time.as_secs_f64() * fps as f64).round() as u64 + 1;
+                while *written < due {
+                    stdin
+                        .write_all(&pixels)
+                        .map_err(|e| format!("ffmpeg stopped: {e}"))?;
+                    *written += 1;
+                }
+            }
+        }
+        count += 1;
+    }
+
+    match sink {
+        Sink::Apng(frames) => write_apng(path, out_size, fps, &frames)?,
+        Sink::Ffmpeg { child, stdin, .. } => {
+            drop(stdin);
+            let output = child.wait_with_output().map_err(|e| e.to_string())?;
+            if !output.status.success() {
+                return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn spawn_ffmpeg(
+    format: CaptureFormat,
+    (width, height): (u32, u32),
+    fps: u32,
+    path: &Path,
+) -> Result<Sink, String> {
+    let filter = match format {
+        // A palette computed over the whole clip, instead of the fixed web one
+        CaptureFormat::Gif => "split[a][b];[a]palettegen[p];[b][p]paletteuse",
+        // Yuv 4:2:0 needs even sizes
+        _ => "scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p",
+    };
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{width}x{height}"), "-r", &fps.to_string()])
+        .args(["-i", "-", "-vf", filter])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("ffmpeg is needed for {}: {e}", format.label()))?;
+    let stdin = child.stdin.take().ok_or("No ffmpeg stdin")?;
+    Ok(Sink::Ffmpeg {
+        child,
+        stdin,
+        written: 0,
+    })
+}
+
+fn write_apng(
+    path: &Path,
+    (width, height): (u32, u32),
+    fps: u32,
+    frames: &[(Vec<u8>, Duration)],
+) -> Result<(), String> {
+    if frames.is_empty() {
+        return Err("No frame was captured".to_string());
+    }
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(|e| e.to_string())?;
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    let last_delay = Duration::from_secs_f32(1.0 / fps as f32);
+    for (i, (pixels, time)) in frames.iter().enumerate() {
+        let delay = frames
+            .get(i + 1)
+            .map_or(last_delay, |(_, next)| next.saturating_sub(*time));
+        writer
+            .set_frame_delay(delay.as_millis().min(u16::MAX as u128) as u16, 1000)
+            .map_err(|e| e.to_string())?;
+        writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    }
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Rgba pixels of the frame shrunk by `downscale`, averaging each block of pixels
+fn to_rgba(pixels: &[u8], (width, height): (u32, u32), bgra: bool, downscale: u32) -> Vec<u8> {
+    let (red, blue) = if bgra { (2, 0) } else { (0, 2) };
+    let (out_width, out_height) = ((width / downscale).max(1), (height / downscale).max(1));
+    let mut out = Vec::with_capacity(out_width as usize * out_height as usize * 4);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in y * downscale..((y + 1) * downscale).min(height) {
+                for sx in x * downscale..((x + 1) * downscale).min(width) {
+                    let i = (sy * width + sx) as usize * 4;
+                    for (channel, sum) in sum.iter_mut().enumerate() {
+                        *sum += pixels[i + channel] as u32;
+                    }
+                    count += 1;
+                }
+            }
+            let count = count.max(1);
+            out.extend([
+                (sum[red] / count) as u8,
+                (sum[1] / count) as u8,
+                (sum[blue] / count) as u8,
+                // Opaque, the swapchain alpha is not meant to be shown
+                255,
+            ]);
+        }
+    }
+    out
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::BTreeMap,
+    ops::AddAssign,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+/// Counting is free when disabled, only the flag is read
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Counts of the frame being recorded then of the last submitted one, by system
+static COUNTS: LazyLock<Mutex<[BTreeMap<&'static str, StateChanges>; 2]>> =
+    LazyLock::new(Default::default);
+
+/// Render state switches recorded by a system in a frame, a multi draw counts as one draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StateChanges {
+    pub pipelines: u32,
+    pub bind_groups: u32,
+    pub vertex_buffers: u32,
+    pub index_buffers: u32,
+    pub draws: u32,
+}
+
+impl StateChanges {
+    pub fn fields(&self) -> [(&'static str, u32); 5] {
+        [
+            ("Pipelines", self.pipelines),
+            ("Bind groups", self.bind_groups),
+            ("Vertex buffers", self.vertex_buffers),
+            ("Index buffers", self.index_buffers),
+            ("Draws", self.draws),
+        ]
+    }
+}
+
+impl AddAssign for StateChanges {
+    fn add_assign(&mut self, other: Self) {
+        self.pipelines += other.pipelines;
+        self.bind_groups += other.bind_groups;
+        self.vertex_buffers += other.vertex_buffers;
+        self.index_buffers += other.index_buffers;
+        self.draws += other.draws;
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        *COUNTS.lock().unwrap() = Default::default();
+    }
+}
+
+/// Adds to the counts of `system` for the frame being recorded, ignored when disabled
+pub fn record(system: &'static str, changes: StateChanges) {
+    if !is_enabled() {
+        return;
+    }
+    *COUNTS.lock().unwrap()[0].entry(system).or_default() += changes;
+}
+
+/// Closes the frame once submitted
+pub fn end_frame() {
+    if !is_enabled() {
+        return;
+    }
+    let mut counts = COUNTS.lock().unwrap();
+    counts[1] = std::mem::take(&mut counts[0]);
+}
+
+/// Counts of the last submitted frame by system
+pub fn last_frame() -> BTreeMap<&'static str, StateChanges> {
+    COUNTS.lock().unwrap()[1].clone()
+}
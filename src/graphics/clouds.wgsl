@@ -0,0 +1,56 @@
+// Cloud layer, appended to the sky and terrain shaders which declare `frame`, see `RawFrameConstants`
+
+// Cells of the lattice before the noise repeats, matches `CLOUD_NOISE_PERIOD`
+const CLOUD_PERIOD: i32 = 256;
+const CLOUD_OCTAVES: u32 = 5u;
+
+fn cloud_hash(cell: vec2i) -> f32 {
+    let c = vec2u(cell & vec2i(CLOUD_PERIOD - 1));
+    var h = c.x * 374761393u + c.y * 668265263u;
+    h = (h ^ (h >> 13u)) * 1274126177u;
+    return f32(h ^ (h >> 16u)) / 4294967295.0;
+}
+
+fn cloud_noise(p: vec2f) -> f32 {
+    let cell = vec2i(floor(p));
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(cloud_hash(cell), cloud_hash(cell + vec2i(1, 0)), u.x),
+        mix(cloud_hash(cell + vec2i(0, 1)), cloud_hash(cell + vec2i(1, 1)), u.x),
+        u.y,
+    );
+}
+
+// Octaves are not rotated, every one of them repeats with the first
+fn cloud_fbm(p: vec2f) -> f32 {
+    var value = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var i = 0u; i < CLOUD_OCTAVES; i++) {
+        value += cloud_noise(q) * amplitude;
+        q *= 2.0;
+        amplitude *= 0.5;
+    }
+    return value / (1.0 - amplitude * 2.0);
+}
+
+// Opacity of the cloud layer above a point of the xz plane, relative to the world origin
+fn cloud_density(xz: vec2f) -> f32 {
+    if frame.cloud_coverage <= 0.0 {
+        return 0.0;
+    }
+    let p = (xz + frame.cloud_origin - frame.time * frame.cloud_velocity) / frame.cloud_size;
+    let threshold = 1.0 - frame.cloud_coverage;
+    return smoothstep(threshold, threshold + frame.cloud_softness, cloud_fbm(p));
+}
+
+// Share of the sun light reaching `world` through the clouds
+fn cloud_shadow(world: vec3f) -> f32 {
+    let sun = frame.sun_direction;
+    if sun.y <= 0.01 {
+        return 1.0;
+    }
+    let hit = world + sun * max((frame.cloud_altitude - world.y) / sun.y, 0.0);
+    return 1.0 - cloud_density(hit.xz) * frame.cloud_shadow;
+}
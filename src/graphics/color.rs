@@ -50,8 +50,37 @@ impl Color3 {
     pub fn array_mut(&mut self) -> &mut [f32; 3] {
         unsafe { std::mem::transmute(self) }
     }
+
+    /// Color of a black body at `kelvin`, from 1000K to 40000K, with its brightest channel at 1
+    ///
+    /// Fitted curve of Tanner Helland, within a few percent of the black body color
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+        let r = match t <= 66.0 {
+            true => 255.0,
+            false => 329.69873 * (t - 60.0).powf(-0.13320476),
+        };
+        let g = match t <= 66.0 {
+            true => 99.4708 * t.ln() - 161.11957,
+            false => 288.12216 * (t - 60.0).powf(-0.07551485),
+        };
+        let b = match t {
+            t if t >= 66.0 => 255.0,
+            t if t <= 19.0 => 0.0,
+            t => 138.51773 * (t - 10.0).ln() - 305.0448,
+        };
+        let [r, g, b] = [r, g, b].map(|c: f32| c.clamp(0.0, 255.0) / 255.0);
+        Self::new(r, g, b)
+    }
 }
 
+/// Color temperatures of common light sources, in Kelvin
+pub const KELVIN_PRESETS: [(&str, f32); 3] = [
+    ("Candle", 1900.0),
+    ("Tungsten", 3200.0),
+    ("Daylight", 6500.0),
+];
+
 impl std::ops::Mul<f32> for Color3 {
     type Output = Color3;
     fn mul(self, rhs: f32) -> Self::Output {
@@ -1,25 +1,136 @@
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use wgpu::*;
 use winit::window::Window;
 
+use crate::{constants, profile_scope};
+
+use super::sampler::{SamplerCache, SamplerDesc};
+
+/// Material shader params are bound after the five groups of the entities
+const REQUIRED_BIND_GROUPS: u32 = 6;
+
 pub struct GraphicsCtx {
+    pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
-    pub surface: Surface<'static>,
+    /// None when headless, the frames are drawn into a texture read by [`Self::read_frame`]
+    pub surface: Option<Surface<'static>>,
+    headless_target: Option<Texture>,
     pub surface_format: TextureFormat,
+    /// Output actually granted by the surface, see [`DisplayOutput`]
+    pub output: DisplayOutput,
     pub surface_capabilities: SurfaceCapabilities,
     pub viewport_size: (u32, u32),
+    pub samplers: SamplerCache,
+    pacing: FramePacing,
+    in_flight: Mutex<InFlight>,
+}
+
+/// Range of the presented frames, chosen once when the surface is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayOutput {
+    /// 8 bits sRGB
+    #[default]
+    Sdr,
+    /// Linear extended range `Rgba16Float` (scRGB), where 1 is 80 nits and brighter values go
+    /// past the sdr white. HDR10 is not exposed by wgpu, displays receive it through the
+    /// compositor. Falls back to sdr when the surface does not support the format
+    Hdr,
+}
+
+impl DisplayOutput {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sdr" => Some(Self::Sdr),
+            "hdr" => Some(Self::Hdr),
+            _ => None,
+        }
+    }
+}
+
+/// Trades throughput for input latency, see [`GraphicsCtx::set_pacing`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacing {
+    /// Frames the presentation engine may queue, `desired_maximum_frame_latency` of the surface
+    pub max_frame_latency: u32,
+    /// Submitted frames the gpu may still be working on when the cpu starts the next one
+    pub frames_in_flight: u32,
+    /// Waits for the gpu to finish the previous frame before starting the next one, the inputs
+    /// are then read as late as possible. Overrides the two counts with 1
+    pub low_latency: bool,
+    /// Presents on vertical blanks, otherwise with the mailbox or immediate mode when supported
+    pub vsync: bool,
+    /// Submits and presents the frames from a thread of its own, see
+    /// [`super::render_thread::RenderThread`]. Off with the low latency, it lets the cpu start a
+    /// frame before the last one is shown
+    pub render_thread: bool,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            max_frame_latency: 2,
+            frames_in_flight: 2,
+            low_latency: false,
+            vsync: true,
+            render_thread: true,
+        }
+    }
+}
+
+impl FramePacing {
+    fn frame_latency(&self) -> u32 {
+        match self.low_latency {
+            true => 1,
+            false => self.max_frame_latency.max(1),
+        }
+    }
+
+    pub fn uses_render_thread(&self) -> bool {
+        self.render_thread && !self.low_latency
+    }
+
+    fn frames_in_flight(&self) -> usize {
+        match self.low_latency {
+            true => 1,
+            false => self.frames_in_flight.max(1) as usize,
+        }
+    }
+}
+
+#[derive(Default)]
+struct InFlight {
+    /// Oldest first
+    submissions: VecDeque<SubmissionIndex>,
+    /// Frames presented so far
+    submitted: u64,
+    /// Time the last call to [`GraphicsCtx::pace`] blocked for
+    last_wait: Duration,
+    /// Resources passed to [`GraphicsCtx::retire`], with the frame that may still use them
+    retired: VecDeque<(u64, Box<dyn Send>)>,
+}
+
+impl InFlight {
+    /// Frames the gpu is known to be done with, the ones not waited on yet are assumed busy
+    fn completed(&self) -> u64 {
+        self.submitted - self.submissions.len() as u64
+    }
 }
 
 pub struct Frame {
     pub view: TextureView,
     pub encoder: CommandEncoder,
-    pub surface_texture: SurfaceTexture,
+    /// None when headless
+    pub surface_texture: Option<SurfaceTexture>,
 }
 
 impl GraphicsCtx {
-    pub fn new(window: Arc<Window>) -> Self {
+    pub fn new(window: Arc<Window>, output: DisplayOutput) -> Self {
         let window_size = window.inner_size().into();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: Backends::from_env().unwrap_or_default(),
@@ -34,33 +145,43 @@ impl GraphicsCtx {
             force_fallback_adapter: false,
         }))
         .unwrap();
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::INDIRECT_FIRST_INSTANCE
-                    | wgpu::Features::MULTI_DRAW_INDIRECT,
-                required_limits: wgpu::Limits::default(),
-                memory_hints: wgpu::MemoryHints::default(),
-            },
-            None,
-        ))
-        .unwrap_or_else(|e| panic!("Could not acquire graphics device: {e}"));
+        let (device, queue) = request_device(&adapter)
+            .unwrap_or_else(|e| panic!("Could not acquire graphics device: {e}"));
 
         let surface_capabilities = surface.get_capabilities(&adapter);
-        let surface_texture_format = surface_capabilities
+        let sdr_format = surface_capabilities
             .formats
             .iter()
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_capabilities.formats[0]);
+        let hdr_supported = surface_capabilities
+            .formats
+            .contains(&TextureFormat::Rgba16Float);
+        let (surface_texture_format, output) = match output {
+            DisplayOutput::Hdr if hdr_supported => (TextureFormat::Rgba16Float, output),
+            DisplayOutput::Hdr => {
+                eprintln!("The surface does not support hdr output, falling back to sdr");
+                (sdr_format, DisplayOutput::Sdr)
+            }
+            DisplayOutput::Sdr => (sdr_format, output),
+        };
+
+        let samplers = SamplerCache::new(&adapter);
 
         let mut _self = Self {
+            adapter,
             device,
             queue,
-            surface,
+            surface: Some(surface),
+            headless_target: None,
             surface_capabilities,
             surface_format: surface_texture_format,
+            output,
             viewport_size: window_size,
+            samplers,
+            pacing: FramePacing::default(),
+            in_flight: Mutex::default(),
         };
 
         _self.resize(window_size);
@@ -68,9 +189,201 @@ impl GraphicsCtx {
         _self
     }
 
+    /// Without a window, for tests and tools. The frames are drawn into an sRGB texture of `size`
+    pub fn new_headless(size: (u32, u32)) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: Backends::from_env().unwrap_or_default(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .ok_or("No graphics adapter")?;
+        let (device, queue) = request_device(&adapter)?;
+        let samplers = SamplerCache::new(&adapter);
+
+        let mut _self = Self {
+            adapter,
+            device,
+            queue,
+            surface: None,
+            headless_target: None,
+            surface_capabilities: SurfaceCapabilities::default(),
+            surface_format: TextureFormat::Rgba8UnormSrgb,
+            output: DisplayOutput::Sdr,
+            viewport_size: size,
+            samplers,
+            pacing: FramePacing::default(),
+            in_flight: Mutex::default(),
+        };
+
+        _self.resize(size);
+
+        Ok(_self)
+    }
+
+    /// Waits for the last headless frame and copies it out, `None` with a surface
+    pub fn read_frame(&self) -> Option<image::RgbaImage> {
+        let target = self.headless_target.as_ref()?;
+        let (width, height) = (target.width(), target.height());
+        let row_bytes = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: Some("Frame readback"),
+            size: row_bytes as u64 * height as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Frame readback"),
+            });
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            target.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        let _ = self.device.poll(Maintain::Wait);
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks(row_bytes as usize)
+            .flat_map(|row| &row[..width as usize * 4])
+            .copied()
+            .collect();
+        image::RgbaImage::from_raw(width, height, pixels)
+    }
+
+    /// Whether the frames can be copied out, see [`super::capture::FrameCapture`]
+    pub fn can_copy_frames(&self) -> bool {
+        self.surface.is_none()
+            || self
+                .surface_capabilities
+                .usages
+                .contains(TextureUsages::COPY_SRC)
+    }
+
+    /// Whether one of the `constants::OPTIONAL_FEATURES` was enabled
+    pub fn has_feature(&self, feature: Features) -> bool {
+        self.device.features().contains(feature)
+    }
+
+    pub fn sampler(&self, desc: SamplerDesc) -> Arc<Sampler> {
+        self.samplers.get(&self.device, desc)
+    }
+
+    /// Largest side of a 2d texture
+    pub fn max_texture_size(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    /// Largest buffer that can also be bound as a storage buffer
+    pub fn max_buffer_bytes(&self) -> u64 {
+        let limits = self.device.limits();
+        limits
+            .max_buffer_size
+            .min(limits.max_storage_buffer_binding_size as u64)
+    }
+
+    pub fn pacing(&self) -> FramePacing {
+        self.pacing
+    }
+
+    /// Reconfigures the surface when the frame latency or the vsync changes
+    pub fn set_pacing(&mut self, pacing: FramePacing) {
+        let reconfigure = pacing.frame_latency() != self.pacing.frame_latency()
+            || pacing.vsync != self.pacing.vsync;
+        self.pacing = pacing;
+        if reconfigure {
+            self.resize(self.viewport_size);
+        }
+    }
+
+    /// Blocks until the gpu caught up with the frames in flight, called before a frame reads its
+    /// inputs
+    pub fn pace(&self) {
+        profile_scope!("Frame pacing");
+        let start = Instant::now();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // The one being started counts as in flight
+        while in_flight.submissions.len() >= self.pacing.frames_in_flight() {
+            let Some(index) = in_flight.submissions.pop_front() else {
+                break;
+            };
+            let _ = self.device.poll(Maintain::WaitForSubmissionIndex(index));
+        }
+        in_flight.last_wait = start.elapsed();
+
+        let completed = in_flight.completed();
+        while in_flight
+            .retired
+            .front()
+            .is_some_and(|(frame, _)| *frame <= completed)
+        {
+            in_flight.retired.pop_front();
+        }
+    }
+
+    /// Keeps a replaced buffer, bind group or texture alive until the gpu finished the frames
+    /// that may reference it, recorded bundles included. Dropped by [`Self::pace`]
+    pub fn retire(&self, resource: impl Send + 'static) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // The frame being recorded may still use it
+        let frame = in_flight.submitted + 1;
+        in_flight.retired.push_back((frame, Box::new(resource)));
+    }
+
+    /// Resources waiting in [`Self::retire`]
+    pub fn retired_len(&self) -> usize {
+        self.in_flight.lock().unwrap().retired.len()
+    }
+
+    /// Time the cpu last waited on the gpu in [`Self::pace`]
+    pub fn last_pacing_wait(&self) -> Duration {
+        self.in_flight.lock().unwrap().last_wait
+    }
+
+    /// Fifo is always supported, without vsync the first supported of mailbox and immediate
+    fn present_mode(&self) -> wgpu::PresentMode {
+        if self.pacing.vsync {
+            return wgpu::PresentMode::Fifo;
+        }
+        let supported = &self.surface_capabilities.present_modes;
+        [wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+            .into_iter()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
     pub fn next_frame(&self) -> Option<Frame> {
-        let surface_texture = self
-            .surface
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let Some(surface) = &self.surface else {
+            let view = self
+                .headless_target
+                .as_ref()?
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            return Some(Frame {
+                surface_texture: None,
+                encoder,
+                view,
+            });
+        };
+
+        let surface_texture = surface
             .get_current_texture()
             .map_err(|e| match e {
                 wgpu::SurfaceError::OutOfMemory => {
@@ -83,40 +396,159 @@ impl GraphicsCtx {
         let view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        let encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
         Some(Frame {
-            surface_texture,
+            surface_texture: Some(surface_texture),
             encoder,
             view,
         })
     }
 
-    pub(crate) fn resize(&mut self, window_size: (u32, u32)) {
-        if window_size.0 > 0 && window_size.1 > 0 {
-            self.surface.configure(
-                &self.device,
-                &wgpu::SurfaceConfiguration {
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                    format: self.surface_format,
+    pub fn resize(&mut self, window_size: (u32, u32)) {
+        if window_size.0 == 0 || window_size.1 == 0 {
+            return;
+        }
+        self.viewport_size = window_size;
+        let Some(surface) = &self.surface else {
+            self.headless_target = Some(self.device.create_texture(&TextureDescriptor {
+                label: Some("Headless frame"),
+                size: Extent3d {
                     width: window_size.0,
                     height: window_size.1,
-                    present_mode: self.surface_capabilities.present_modes[0],
-                    alpha_mode: self.surface_capabilities.alpha_modes[0],
-                    view_formats: vec![],
-                    desired_maximum_frame_latency: 2,
+                    depth_or_array_layers: 1,
                 },
-            );
-            self.viewport_size = window_size;
-        }
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.surface_format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            }));
+            return;
+        };
+        surface.configure(
+            &self.device,
+            &wgpu::SurfaceConfiguration {
+                // Copied out while capturing, where supported
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | (self.surface_capabilities.usages & TextureUsages::COPY_SRC),
+                format: self.surface_format,
+                width: window_size.0,
+                height: window_size.1,
+                present_mode: self.present_mode(),
+                alpha_mode: self.surface_capabilities.alpha_modes[0],
+                view_formats: vec![],
+                desired_maximum_frame_latency: self.pacing.frame_latency(),
+            },
+        );
+    }
+}
+
+/// Indirect draws are required, the optional features are enabled when supported
+fn request_device(adapter: &Adapter) -> Result<(Device, Queue), String> {
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::INDIRECT_FIRST_INSTANCE
+                | wgpu::Features::MULTI_DRAW_INDIRECT
+                | (adapter.features() & constants::OPTIONAL_FEATURES),
+            required_limits: negotiate_limits(adapter),
+            memory_hints: wgpu::MemoryHints::default(),
+        },
+        None,
+    ))
+    .map_err(|e| e.to_string())
+}
+
+/// Everything the adapter supports, the buffers and textures are sized against it instead of
+/// assuming the defaults. Limits below the wgpu defaults are reported, as on integrated gpus
+/// and in browsers, the renderer scales down to them
+fn negotiate_limits(adapter: &Adapter) -> Limits {
+    let supported = adapter.limits();
+    if supported.max_bind_groups < REQUIRED_BIND_GROUPS {
+        panic!(
+            "The graphics adapter supports {} bind groups, {REQUIRED_BIND_GROUPS} are required",
+            supported.max_bind_groups
+        );
+    }
+
+    let defaults = Limits::default();
+    let below: Vec<_> = [
+        (
+            "max_texture_dimension_2d",
+            supported.max_texture_dimension_2d as u64,
+            defaults.max_texture_dimension_2d as u64,
+        ),
+        (
+            "max_texture_array_layers",
+            supported.max_texture_array_layers as u64,
+            defaults.max_texture_array_layers as u64,
+        ),
+        (
+            "max_buffer_size",
+            supported.max_buffer_size,
+            defaults.max_buffer_size,
+        ),
+        (
+            "max_storage_buffer_binding_size",
+            supported.max_storage_buffer_binding_size as u64,
+            defaults.max_storage_buffer_binding_size as u64,
+        ),
+        (
+            "max_storage_buffers_per_shader_stage",
+            supported.max_storage_buffers_per_shader_stage as u64,
+            defaults.max_storage_buffers_per_shader_stage as u64,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, supported, default)| supported < default)
+    .map(|(name, supported, default)| format!("{name} {supported} < {default}"))
+    .collect();
+    if !below.is_empty() {
+        eprintln!(
+            "The graphics adapter is below the default limits, scaling down: {}",
+            below.join(", ")
+        );
     }
+    supported
 }
 
 impl Frame {
-    pub fn present(self, ctx: &GraphicsCtx) {
-        ctx.queue.submit(std::iter::once(self.encoder.finish()));
-        self.surface_texture.present();
+    /// Texture behind [`Self::view`]
+    pub fn texture<'a>(&'a self, ctx: &'a GraphicsCtx) -> Option<&'a Texture> {
+        match &self.surface_texture {
+            Some(surface_texture) => Some(&surface_texture.texture),
+            None => ctx.headless_target.as_ref(),
+        }
+    }
+
+    /// `scene_commands` are submitted in order, before the frame encoder
+    pub fn present(
+        self,
+        ctx: &GraphicsCtx,
+        scene_commands: impl IntoIterator<Item = CommandBuffer>,
+    ) {
+        if let Some(surface_texture) = self.submit(ctx, scene_commands) {
+            surface_texture.present();
+        }
+    }
+
+    /// Like [`Self::present`], the surface texture is returned to be presented later, once the
+    /// locks held while recording are released
+    pub fn submit(
+        self,
+        ctx: &GraphicsCtx,
+        scene_commands: impl IntoIterator<Item = CommandBuffer>,
+    ) -> Option<SurfaceTexture> {
+        let index = ctx.queue.submit(
+            scene_commands
+                .into_iter()
+                .chain(std::iter::once(self.encoder.finish())),
+        );
+        let mut in_flight = ctx.in_flight.lock().unwrap();
+        in_flight.submissions.push_back(index);
+        in_flight.submitted += 1;
+        drop(in_flight);
+        self.surface_texture
     }
 }
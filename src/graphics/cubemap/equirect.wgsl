@@ -0,0 +1,36 @@
+@group(0) @binding(0)
+var equirect: texture_2d<f32>;
+@group(0) @binding(1)
+var equirect_sampler: sampler;
+@group(0) @binding(2)
+var cubemap: texture_storage_2d_array<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+
+// Face order and orientation follow the +X, -X, +Y, -Y, +Z, -Z cube layer convention
+fn face_direction(face: u32, uv: vec2f) -> vec3f {
+    let p = uv * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3f(1.0, -p.y, -p.x)); }
+        case 1u: { return normalize(vec3f(-1.0, -p.y, p.x)); }
+        case 2u: { return normalize(vec3f(p.x, 1.0, p.y)); }
+        case 3u: { return normalize(vec3f(p.x, -1.0, -p.y)); }
+        case 4u: { return normalize(vec3f(p.x, -p.y, 1.0)); }
+        default: { return normalize(vec3f(-p.x, -p.y, -1.0)); }
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(cubemap);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let uv = (vec2f(id.xy) + 0.5) / vec2f(size);
+    let dir = face_direction(id.z, uv);
+    let equirect_uv = vec2f(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(dir.y) / PI);
+
+    let color = textureSampleLevel(equirect, equirect_sampler, equirect_uv, 0.0);
+    textureStore(cubemap, id.xy, id.z, color);
+}
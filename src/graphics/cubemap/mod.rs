@@ -0,0 +1,153 @@
+use wgpu::include_wgsl;
+
+use crate::ASSETS;
+
+use super::{
+    ctx::GraphicsCtx,
+    sampler::SamplerDesc,
+    utils::{HdrPrecision, TextureWrapper},
+};
+
+/// Loads an equirectangular environment from the asset tree and projects it onto a cubemap
+pub fn load_environment_cubemap(ctx: &GraphicsCtx, name: &str, face_size: u32) -> TextureWrapper {
+    let assets = ASSETS.tree();
+    let file = assets
+        .environments
+        .get(name)
+        .unwrap_or_else(|| panic!("Failed to load environment {name}"));
+    // Sampled with filtering while projected
+    let precision = match file.precision {
+        HdrPrecision::Full if ctx.has_feature(wgpu::Features::FLOAT32_FILTERABLE) => {
+            HdrPrecision::Full
+        }
+        _ => HdrPrecision::Half,
+    };
+
+    let equirect = TextureWrapper::new_hdr_2d(
+        name,
+        ctx,
+        file.image.dimensions(),
+        file.image.as_raw(),
+        precision,
+        SamplerDesc::LINEAR.with_wrap(wgpu::AddressMode::Repeat),
+    );
+
+    equirect_to_cubemap(ctx, &equirect, face_size)
+}
+
+/// Compute pass writing each cube face from an equirectangular float texture, half or full
+/// precision
+pub fn equirect_to_cubemap(
+    ctx: &GraphicsCtx,
+    equirect: &TextureWrapper,
+    face_size: u32,
+) -> TextureWrapper {
+    let cubemap = TextureWrapper::new_cubemap(
+        "Environment",
+        ctx,
+        face_size,
+        HdrPrecision::Half.format(),
+        wgpu::TextureUsages::STORAGE_BINDING,
+    );
+
+    let layout = ctx
+        .device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: HdrPrecision::Half.format(),
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Equirect to Cubemap Bind Group Layout"),
+        });
+
+    let faces_view = cubemap.texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Cubemap faces"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+
+    let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&equirect.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&equirect.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&faces_view),
+            },
+        ],
+        label: Some("Equirect to Cubemap Bind Group"),
+    });
+
+    let pipeline_layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+    let shader = ctx
+        .device
+        .create_shader_module(include_wgsl!("equirect.wgsl"));
+
+    let pipeline = ctx
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Equirect to Cubemap"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Equirect to Cubemap Encoder"),
+        });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Equirect to Cubemap"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        let groups = face_size.div_ceil(8);
+        pass.dispatch_workgroups(groups, groups, 6);
+    }
+    ctx.queue.submit(Some(encoder.finish()));
+
+    cubemap
+}
@@ -0,0 +1,304 @@
+use nalgebra::{Point3, Vector3};
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    bounds::Aabb,
+    buffer::{CommonBuffer, Growable, VertexBuffer, WriteBuffer},
+    camera::{view_proj_bind_group_layout, CameraUniform},
+    color::Color3,
+    ctx::GraphicsCtx,
+    post::HDR_FORMAT,
+    utils::TextureWrapper,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl DebugVertex {
+    fn buffer_desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Colors of the debug drawings, by their meaning rather than their hue so each palette can
+/// pick hues its viewers tell apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugPalette {
+    #[default]
+    Standard,
+    /// Okabe-Ito colors, distinct for deuteranopia, protanopia and tritanopia
+    Colorblind,
+    /// Saturated colors at full brightness, readable over any scene
+    HighContrast,
+}
+
+impl DebugPalette {
+    pub const ALL: [DebugPalette; 3] = [
+        DebugPalette::Standard,
+        DebugPalette::Colorblind,
+        DebugPalette::HighContrast,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DebugPalette::Standard => "Standard",
+            DebugPalette::Colorblind => "Colorblind",
+            DebugPalette::HighContrast => "HighContrast",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|palette| palette.label() == name)
+    }
+
+    /// Colors of the x, y and z axes
+    pub fn axes(&self) -> [Color3; 3] {
+        match self {
+            DebugPalette::Standard => [Color3::RED, Color3::GREEN, Color3::BLUE],
+            DebugPalette::Colorblind => [
+                Color3::new(0.835, 0.369, 0.0),
+                Color3::new(0.0, 0.62, 0.451),
+                Color3::new(0.337, 0.706, 0.914),
+            ],
+            DebugPalette::HighContrast => [
+                Color3::new(1.0, 0.2, 0.2),
+                Color3::new(0.2, 1.0, 0.2),
+                Color3::new(0.3, 0.6, 1.0),
+            ],
+        }
+    }
+
+    /// Selected or measured things
+    pub fn highlight(&self) -> Color3 {
+        match self {
+            DebugPalette::Standard => Color3::YELLOW,
+            DebugPalette::Colorblind => Color3::new(0.941, 0.894, 0.259),
+            DebugPalette::HighContrast => Color3::MAGENTA,
+        }
+    }
+
+    /// Unselected things
+    pub fn neutral(&self) -> Color3 {
+        match self {
+            DebugPalette::HighContrast => Color3::CYAN,
+            _ => Color3::WHITE,
+        }
+    }
+
+    /// Previews of edits not applied yet
+    pub fn preview(&self) -> Color3 {
+        match self {
+            DebugPalette::Standard => Color3::new(1.0, 0.6, 0.1),
+            DebugPalette::Colorblind => Color3::new(0.902, 0.624, 0.0),
+            DebugPalette::HighContrast => Color3::YELLOW,
+        }
+    }
+
+    /// From bad at 0 to good at 1, the colorblind ramp goes through hues and brightness
+    pub fn gradient(&self, fraction: f32) -> Color3 {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let (bad, good) = match self {
+            DebugPalette::Standard => (Color3::RED, Color3::GREEN),
+            DebugPalette::Colorblind => (
+                Color3::new(0.835, 0.369, 0.0),
+                Color3::new(0.337, 0.706, 0.914),
+            ),
+            DebugPalette::HighContrast => (Color3::new(1.0, 0.0, 0.3), Color3::CYAN),
+        };
+        bad * (1.0 - fraction) + good * fraction
+    }
+}
+
+/// Immediate mode lines, queued by any system during the frame and cleared once drawn
+///
+/// Depth tested lines are hidden by the scene, overlay lines are always visible
+pub struct DebugLines {
+    /// Colors the editor tools draw with
+    pub palette: DebugPalette,
+    lines: Vec<DebugVertex>,
+    overlay: Vec<DebugVertex>,
+    buffer: Growable<VertexBuffer<DebugVertex>>,
+    pipeline: wgpu::RenderPipeline,
+    overlay_pipeline: wgpu::RenderPipeline,
+}
+
+impl DebugLines {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&view_proj_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("debug_lines.wgsl"));
+
+        Self {
+            palette: DebugPalette::default(),
+            lines: Vec::new(),
+            overlay: Vec::new(),
+            buffer: VertexBuffer::new_empty_vec("Debug lines", ctx, 1024),
+            pipeline: lines_pipeline(ctx, &pipeline_layout, &shader, true),
+            overlay_pipeline: lines_pipeline(ctx, &pipeline_layout, &shader, false),
+        }
+    }
+
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Color3) {
+        self.lines.extend([vertex(a, color), vertex(b, color)]);
+    }
+
+    pub fn overlay_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Color3) {
+        self.overlay.extend([vertex(a, color), vertex(b, color)]);
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: Color3) {
+        let corners = aabb.corners();
+        // Corners differing by a single axis bit share an edge
+        for (i, j) in (0..8).flat_map(|i| [1, 2, 4].map(|bit| (i, i | bit))) {
+            if i != j {
+                self.line(corners[i], corners[j], color);
+            }
+        }
+    }
+
+    /// Three axis aligned overlay segments crossing at the point
+    pub fn cross(&mut self, center: Point3<f32>, size: f32, color: Color3) {
+        for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+            let offset = axis * size * 0.5;
+            self.overlay_line(center - offset, center + offset, color);
+        }
+    }
+
+    /// Vertices queued for this frame, both kinds of lines
+    pub fn queued_len(&self) -> usize {
+        self.lines.len() + self.overlay.len()
+    }
+
+    /// Room for `vertices` queued in a frame, see [`Growable::reserve`]
+    pub fn reserve(&mut self, ctx: &GraphicsCtx, vertices: usize) {
+        self.buffer.reserve(ctx, vertices);
+    }
+
+    /// Draws the queued lines over the scene color, then clears them
+    pub fn render(
+        &mut self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &CameraUniform,
+        scene_color: &wgpu::TextureView,
+        depth: &TextureWrapper,
+    ) {
+        let (lines, overlay) = (self.lines.len() as u32, self.overlay.len() as u32);
+        if lines + overlay == 0 {
+            return;
+        }
+        let vertices = [
+            std::mem::take(&mut self.lines),
+            std::mem::take(&mut self.overlay),
+        ]
+        .concat();
+        self.buffer.maybe_grow(ctx, vertices.len());
+        // Whole lines only, the overlay is dropped first
+        let fitting = self.buffer.fitting(&vertices);
+        let vertices = &fitting[..fitting.len() & !1];
+        self.buffer.write_array(ctx, &vertices);
+        let lines = lines.min(vertices.len() as u32);
+        let overlay = vertices.len() as u32 - lines;
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug lines"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: scene_color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.as_slice());
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..lines, 0..1);
+        render_pass.set_pipeline(&self.overlay_pipeline);
+        render_pass.draw(lines..lines + overlay, 0..1);
+    }
+}
+
+fn vertex(position: Point3<f32>, color: Color3) -> DebugVertex {
+    DebugVertex {
+        position: position.into(),
+        color: color.into(),
+    }
+}
+
+fn lines_pipeline(
+    ctx: &GraphicsCtx,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    depth_test: bool,
+) -> wgpu::RenderPipeline {
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug lines"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[DebugVertex::buffer_desc()],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: TextureWrapper::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: if depth_test {
+                    wgpu::CompareFunction::LessEqual
+                } else {
+                    wgpu::CompareFunction::Always
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+}
@@ -0,0 +1,65 @@
+use super::{bundle::Tracked, ctx::GraphicsCtx, utils::TextureWrapper};
+
+/// Sampled copy of the scene depth, taken after the opaque entities
+///
+/// The depth texture stays attached to the scene passes, effects reading the depth bind this copy
+/// instead of their own. The terrain is drawn first and sees the depth of the previous frame
+pub struct SceneDepth {
+    pub texture: TextureWrapper,
+    pub bind_group: Tracked<wgpu::BindGroup>,
+}
+
+impl SceneDepth {
+    /// `size` is the render size of the scene
+    pub fn new(ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        let texture = TextureWrapper::new_depth_copy("Scene depth", ctx, size);
+        let bind_group = Tracked::new(scene_depth_bind_group(ctx, &texture));
+        Self {
+            texture,
+            bind_group,
+        }
+    }
+
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, size: (u32, u32)) {
+        self.texture = TextureWrapper::new_depth_copy("Scene depth", ctx, size);
+        self.bind_group
+            .replace(ctx, scene_depth_bind_group(ctx, &self.texture));
+    }
+
+    /// Must be recorded after the passes writing to `depth`
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder, depth: &TextureWrapper) {
+        encoder.copy_texture_to_texture(
+            depth.texture.as_image_copy(),
+            self.texture.texture.as_image_copy(),
+            depth.texture.size(),
+        );
+    }
+}
+
+pub fn scene_depth_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            }],
+            label: Some("Scene Depth Bind Group Layout"),
+        })
+}
+
+fn scene_depth_bind_group(ctx: &GraphicsCtx, texture: &TextureWrapper) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &scene_depth_bind_group_layout(ctx),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        }],
+        label: Some("Scene Depth Bind Group"),
+    })
+}
@@ -0,0 +1,229 @@
+use std::time::Instant;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{
+        CommonBuffer, InstanceBuffer, MappedSparse, StorageBuffer, UniformBuffer, WriteBuffer,
+    },
+    ctx::GraphicsCtx,
+};
+
+use super::model::{decompose, ModelInstance};
+
+/// Waypoints stored inline in every animation
+pub const MAX_PATH_POINTS: usize = 4;
+
+/// Procedural motion evaluated on the gpu, the result is written over the instance transform
+///
+/// `translation = base_translation + path + bob`, `rotation = base_rotation * rotate(axis, t *
+/// speed)` and the scale is the base one. The spin is applied after the scale, unlike a product
+/// of matrices, so the instance stays representable by [`ModelInstance`]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+pub struct InstanceAnimation {
+    pub base_translation: [f32; 3],
+    /// Radians per second
+    pub rotation_speed: f32,
+    /// Unit quaternion, xyz then w
+    pub base_rotation: [f32; 4],
+    pub base_scale: [f32; 3],
+    pub bob_amplitude: f32,
+    pub rotation_axis: [f32; 3],
+    /// Oscillations per second
+    pub bob_frequency: f32,
+    pub bob_axis: [f32; 3],
+    /// Seconds added to the time, to desynchronize copies of the same motion
+    pub phase: f32,
+    /// Path points travelled per second
+    pub path_speed: f32,
+    pub path_len: u32,
+    pub _padding: [u32; 2],
+    pub path: [[f32; 4]; MAX_PATH_POINTS],
+}
+
+impl InstanceAnimation {
+    pub fn new(base: Matrix4<f32>) -> Self {
+        let (translation, rotation, scale) = decompose(&base);
+        let rotation = rotation.coords;
+        Self {
+            base_translation: translation.into(),
+            base_rotation: [rotation.x, rotation.y, rotation.z, rotation.w],
+            base_scale: scale.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn rotating(self, axis: Vector3<f32>, speed: f32) -> Self {
+        Self {
+            rotation_axis: axis.into(),
+            rotation_speed: speed,
+            ..self
+        }
+    }
+
+    pub fn bobbing(self, axis: Vector3<f32>, amplitude: f32, frequency: f32) -> Self {
+        Self {
+            bob_axis: axis.into(),
+            bob_amplitude: amplitude,
+            bob_frequency: frequency,
+            ..self
+        }
+    }
+
+    /// Loops through the points, extra points past [`MAX_PATH_POINTS`] are ignored
+    pub fn along_path(mut self, points: &[Point3<f32>], speed: f32) -> Self {
+        let len = points.len().min(MAX_PATH_POINTS);
+        for (slot, point) in self.path.iter_mut().zip(&points[..len]) {
+            *slot = [point.x, point.y, point.z, 0.0];
+        }
+        self.path_len = len as u32;
+        self.path_speed = speed;
+        self
+    }
+
+    pub fn with_phase(self, phase: f32) -> Self {
+        Self { phase, ..self }
+    }
+}
+
+/// Optional compute pass animating instances in place, it only runs when animations exist
+pub struct InstanceAnimator {
+    pub animations: MappedSparse<StorageBuffer<InstanceAnimation>>,
+    pub paused: bool,
+    time: UniformBuffer<f32>,
+    start: Instant,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl InstanceAnimator {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let animations = MappedSparse::new("Instance animations", ctx, Vec::new());
+        let time = UniformBuffer::new("Animation time", ctx, &0.0);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&animation_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("animation.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Instance animation"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("cs_main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        Self {
+            animations,
+            paused: false,
+            time,
+            start: Instant::now(),
+            pipeline,
+        }
+    }
+
+    /// Returns the id to store in `ModelInstance::animation_id`
+    pub fn add(&mut self, animation: InstanceAnimation) -> u32 {
+        self.animations.push(animation) + 1
+    }
+
+    pub fn set(&mut self, animation_id: u32, animation: InstanceAnimation) {
+        self.animations.set(animation_id - 1, animation);
+    }
+
+    pub fn remove(&mut self, animation_id: u32) {
+        self.animations.remove(animation_id - 1);
+    }
+
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
+        self.animations.apply_changes(ctx);
+    }
+
+    pub fn animate(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &InstanceBuffer<ModelInstance>,
+    ) {
+        if self.paused || self.animations.len() == 0 {
+            return;
+        }
+
+        self.time.write(ctx, &self.start.elapsed().as_secs_f32());
+
+        // The instance buffer is reallocated when growing, binding it every frame keeps up with it
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &animation_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.time.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.animations.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: instances.binding(),
+                },
+            ],
+            label: Some("Instance Animation Bind Group"),
+        });
+
+        let instance_count =
+            instances.inner().size() / InstanceBuffer::<ModelInstance>::ITEM_BYTE_SIZE;
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance animation"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups((instance_count as u32).div_ceil(64), 1, 1);
+    }
+}
+
+fn animation_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+            ],
+            label: Some("Instance Animation Bind Group Layout"),
+        })
+}
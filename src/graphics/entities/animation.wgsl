@@ -0,0 +1,95 @@
+// Matches `ModelInstance`, the vectors are kept as arrays to avoid the vec3 alignment
+struct Instance {
+    translation: array<f32, 3>,
+    material_id: u32,
+    // Snorm quaternion, xy then zw
+    rotation: array<u32, 2>,
+    scale: array<f32, 3>,
+    // Animation id in the low 16 bits, parameter block id in the high ones
+    animation_params: u32,
+    // Zone id in the low 16 bits, snorm level of detail fade in the high ones
+    zone_fade: u32,
+    tint: u32,
+    lightmap: array<f32, 4>,
+}
+
+struct InstanceAnimation {
+    base_translation: vec3f,
+    rotation_speed: f32,
+    base_rotation: vec4f,
+    base_scale: vec3f,
+    bob_amplitude: f32,
+    rotation_axis: vec3f,
+    bob_frequency: f32,
+    bob_axis: vec3f,
+    phase: f32,
+    path_speed: f32,
+    path_len: u32,
+    path: array<vec4f, 4>,
+}
+
+@group(0) @binding(0)
+var<uniform> time: f32;
+@group(0) @binding(1)
+var<storage, read> animations: array<InstanceAnimation>;
+@group(0) @binding(2)
+var<storage, read_write> instances: array<Instance>;
+
+const TAU: f32 = 6.28318530718;
+
+fn axis_angle(axis: vec3f, angle: f32) -> vec4f {
+    return vec4f(normalize(axis) * sin(angle * 0.5), cos(angle * 0.5));
+}
+
+// Hamilton product, `b` is applied first
+fn quat_mul(a: vec4f, b: vec4f) -> vec4f {
+    return vec4f(a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz), a.w * b.w - dot(a.xyz, b.xyz));
+}
+
+// Looping linear interpolation between the path points
+fn path_offset(animation: InstanceAnimation, t: f32) -> vec3f {
+    if animation.path_len == 0u {
+        return vec3f(0.0);
+    }
+    if animation.path_len == 1u {
+        return animation.path[0].xyz;
+    }
+
+    let len = f32(animation.path_len);
+    let progress = t * animation.path_speed;
+    let segment = progress - floor(progress / len) * len;
+    let i = min(u32(segment), animation.path_len - 1u);
+    let next = (i + 1u) % animation.path_len;
+    return mix(animation.path[i].xyz, animation.path[next].xyz, fract(segment));
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3u) {
+    if id.x >= arrayLength(&instances) {
+        return;
+    }
+
+    let animation_id = instances[id.x].animation_params & 0xffffu;
+    if animation_id == 0u || animation_id > arrayLength(&animations) {
+        return;
+    }
+
+    let animation = animations[animation_id - 1u];
+    let t = time + animation.phase;
+
+    let bob = animation.bob_axis * animation.bob_amplitude * sin(t * animation.bob_frequency * TAU);
+    let translation = animation.base_translation + path_offset(animation, t) + bob;
+
+    var rotation = animation.base_rotation;
+    if animation.rotation_speed != 0.0 && any(animation.rotation_axis != vec3f(0.0)) {
+        rotation = quat_mul(rotation, axis_angle(animation.rotation_axis, t * animation.rotation_speed));
+    }
+    rotation = normalize(rotation);
+
+    for (var i = 0u; i < 3u; i++) {
+        instances[id.x].translation[i] = translation[i];
+        instances[id.x].scale[i] = animation.base_scale[i];
+    }
+    instances[id.x].rotation[0] = pack2x16snorm(rotation.xy);
+    instances[id.x].rotation[1] = pack2x16snorm(rotation.zw);
+}
@@ -0,0 +1,326 @@
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, IndexBuffer, IndirectBuffer, UniformBuffer, WriteBuffer},
+    camera::{inv_view_proj_bind_group_layout, view_proj_bind_group_layout, CameraUniform},
+    ctx::GraphicsCtx,
+    depth::{scene_depth_bind_group_layout, SceneDepth},
+    post::HDR_FORMAT,
+    settings::ShadowSettings,
+};
+
+use super::{draws::DrawGenerator, model::ModelsBuffer};
+
+/// Faces of the box of a blob, by the corner bits x, y then z. Seen from the inside, the front
+/// faces are culled
+const BOX_INDICES: [u16; 36] = [
+    0, 2, 1, 1, 2, 3, // -z
+    4, 5, 6, 5, 7, 6, // +z
+    0, 4, 2, 2, 4, 6, // -x
+    1, 3, 5, 3, 7, 5, // +x
+    0, 1, 4, 1, 5, 4, // -y
+    2, 6, 3, 3, 6, 7, // +y
+];
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Matches `BlobParams` in `blobs.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct RawBlobParams {
+    strength: f32,
+    reach: f32,
+    softness: f32,
+    mesh_count: u32,
+}
+
+crate::wgsl_layout!(RawBlobParams as "BlobParams" {
+    strength,
+    reach,
+    softness,
+    mesh_count,
+});
+
+impl RawBlobParams {
+    fn new(settings: &ShadowSettings, mesh_count: u32) -> Self {
+        let blobs = &settings.blobs;
+        Self {
+            strength: blobs.strength.clamp(0.0, 1.0),
+            reach: blobs.reach.max(0.0),
+            softness: blobs.softness.clamp(0.0, 1.0),
+            mesh_count,
+        }
+    }
+}
+
+/// Cheap stand-in for the shadow maps on low end targets, a dark decal straight under every
+/// visible instance
+///
+/// A box around the footprint of the bounds of each instance culled by the [`DrawGenerator`] is
+/// drawn over the scene color. The fragments find the opaque surface behind them in the scene
+/// depth, and darken it by how close it is to the center of the footprint and below the bounds
+pub struct BlobShadows {
+    /// Follows the settings, the blobs are only drawn without the shadow maps
+    pub enabled: bool,
+    params: UniformBuffer<RawBlobParams>,
+    index_buffer: IndexBuffer<u16>,
+    /// One draw of the box per mesh
+    draws: IndirectBuffer,
+    mesh_count: u32,
+    draws_pipeline: wgpu::ComputePipeline,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl BlobShadows {
+    pub fn new(ctx: &GraphicsCtx, settings: &ShadowSettings, models: &ModelsBuffer) -> Self {
+        let mesh_count = models.mesh_count();
+        let params = UniformBuffer::new(
+            "Blob shadows",
+            ctx,
+            &RawBlobParams::new(settings, mesh_count),
+        );
+        let index_buffer = IndexBuffer::new_const_array("Blob box", ctx, &BOX_INDICES[..]);
+        let draws = IndirectBuffer::new_empty("Blob draws", ctx, mesh_count.max(1) as usize);
+
+        let shader = ctx.device.create_shader_module(include_wgsl!("blobs.wgsl"));
+
+        let draws_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&blob_draws_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let draws_pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Blob draws"),
+                layout: Some(&draws_layout),
+                module: &shader,
+                entry_point: Some("cs_draws"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &blobs_bind_group_layout(ctx),
+                    &view_proj_bind_group_layout(ctx),
+                    &inv_view_proj_bind_group_layout(ctx),
+                    &scene_depth_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Blob shadows"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                // The back faces stay in view with the camera inside a box
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Front),
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        // Multiplied with the scene color, overlapping blobs darken further
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::Src,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            enabled: settings.blobs.enabled && !settings.enabled,
+            params,
+            index_buffer,
+            draws,
+            mesh_count,
+            draws_pipeline,
+            pipeline,
+        }
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &ShadowSettings) {
+        self.enabled = settings.blobs.enabled && !settings.enabled;
+        self.params
+            .write(ctx, &RawBlobParams::new(settings, self.mesh_count));
+    }
+
+    /// Darkens `target`, the scene color, must run after the draw generation and the scene depth
+    /// copy
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera: &CameraUniform,
+        scene_depth: &SceneDepth,
+        models: &ModelsBuffer,
+        draws: &DrawGenerator,
+    ) {
+        if !self.enabled || self.mesh_count == 0 {
+            return;
+        }
+
+        // The instance buffers are reallocated when growing, as in the draw generation
+        let draws_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &blob_draws_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: models.indirect_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: draws.visible_counts().binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.draws.binding(),
+                },
+            ],
+            label: Some("Blob Draws Bind Group"),
+        });
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &blobs_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: draws.spheres().binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: draws.instances().binding(),
+                },
+            ],
+            label: Some("Blob Shadows Bind Group"),
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Blob draws"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.draws_pipeline);
+        pass.set_bind_group(0, &draws_bind_group, &[]);
+        pass.dispatch_workgroups(self.mesh_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        drop(pass);
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blob shadows"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.set_bind_group(1, &camera.view_proj_bindgroup, &[]);
+        pass.set_bind_group(2, &camera.inv_view_proj_bindgroup, &[]);
+        pass.set_bind_group(3, &*scene_depth.bind_group, &[]);
+        pass.set_index_buffer(self.index_buffer.as_slice(), wgpu::IndexFormat::Uint16);
+        pass.multi_draw_indexed_indirect(self.draws.inner(), 0, self.mesh_count);
+    }
+}
+
+fn storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn params_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn blobs_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let stages = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(stages),
+                storage_entry(1, wgpu::ShaderStages::VERTEX, true),
+                storage_entry(2, wgpu::ShaderStages::VERTEX, true),
+            ],
+            label: Some("Blob Shadows Bind Group Layout"),
+        })
+}
+
+/// Same group as [`blobs_bind_group_layout`] for the compute entry point, the draws are written
+/// there and cannot be bound in the render pass reading them
+fn blob_draws_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let stage = wgpu::ShaderStages::COMPUTE;
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(stage),
+                storage_entry(3, stage, true),
+                storage_entry(4, stage, true),
+                storage_entry(5, stage, false),
+            ],
+            label: Some("Blob Draws Bind Group Layout"),
+        })
+}
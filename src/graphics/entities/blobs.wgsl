@@ -0,0 +1,182 @@
+// Matches `ModelInstance`, the vectors are kept as arrays to avoid the vec3 alignment
+struct Instance {
+    translation: array<f32, 3>,
+    material_id: u32,
+    // Snorm quaternion, xy then zw
+    rotation: array<u32, 2>,
+    scale: array<f32, 3>,
+    animation_params: u32,
+    zone_fade: u32,
+    tint: u32,
+    lightmap: array<f32, 4>,
+}
+
+// Matches `VisibleInstance`
+struct VisibleInstance {
+    instance: Instance,
+    previous_transform: array<vec4f, 3>,
+}
+
+// Matches `wgpu::util::DrawIndexedIndirectArgs`
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+// Matches `RawBlobParams`
+struct BlobParams {
+    strength: f32,
+    reach: f32,
+    softness: f32,
+    mesh_count: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: BlobParams;
+// Model space bounding sphere of every mesh, center then radius
+@group(0) @binding(1)
+var<storage, read> spheres: array<vec4f>;
+// Written by the draw generation, the visible instances are at the start of the mesh column
+@group(0) @binding(2)
+var<storage, read> visible: array<VisibleInstance>;
+
+// Only bound by the generation of the draws
+@group(0) @binding(3)
+var<storage, read> meshes: array<DrawArgs>;
+@group(0) @binding(4)
+var<storage, read> visible_counts: array<u32>;
+@group(0) @binding(5)
+var<storage, read_write> draws: array<DrawArgs>;
+
+@group(1) @binding(0)
+var<uniform> view: mat4x4f;
+@group(1) @binding(1)
+var<uniform> proj: mat4x4f;
+
+@group(2) @binding(0)
+var<uniform> inv_view: mat4x4f;
+@group(2) @binding(1)
+var<uniform> inv_proj: mat4x4f;
+@group(2) @binding(2)
+var<uniform> viewport_size: vec2<u32>;
+
+// Copy of the depth of this frame, taken after the opaque entities
+@group(3) @binding(0)
+var t_scene_depth: texture_depth_2d;
+
+// Corners of the box of a blob
+const BOX_VERTICES: u32 = 8u;
+// Matches `BOX_INDICES`
+const BOX_INDEX_COUNT: u32 = 36u;
+const WORKGROUP_SIZE: u32 = 64u;
+
+fn quat_to_matrix(q: vec4f) -> mat3x3f {
+    let x2 = q.xyz * 2.0;
+    let xx = q.x * x2.x;
+    let yy = q.y * x2.y;
+    let zz = q.z * x2.z;
+    let xy = q.x * x2.y;
+    let xz = q.x * x2.z;
+    let yz = q.y * x2.z;
+    let wx = q.w * x2.x;
+    let wy = q.w * x2.y;
+    let wz = q.w * x2.z;
+    return mat3x3f(
+        vec3f(1.0 - yy - zz, xy + wz, xz - wy),
+        vec3f(xy - wz, 1.0 - xx - zz, yz + wx),
+        vec3f(xz + wy, yz - wx, 1.0 - xx - yy),
+    );
+}
+
+// World space bounding sphere of the instance
+fn instance_sphere(instance: Instance, sphere: vec4f) -> vec4f {
+    let q = normalize(vec4f(unpack2x16snorm(instance.rotation[0]), unpack2x16snorm(instance.rotation[1])));
+    let rotation = quat_to_matrix(q);
+    let s = instance.scale;
+    let scale = vec3f(s[0], s[1], s[2]);
+    let t = instance.translation;
+    let center = vec3f(t[0], t[1], t[2]) + rotation * (sphere.xyz * scale);
+    let size = abs(scale);
+    return vec4f(center, sphere.w * max(size.x, max(size.y, size.z)));
+}
+
+// One draw of the box per mesh, over its visible instances. The base vertex gives the mesh to the
+// vertex shader
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn cs_draws(@builtin(global_invocation_id) id: vec3u) {
+    let mesh = id.x;
+    if mesh >= params.mesh_count {
+        return;
+    }
+    draws[mesh] = DrawArgs(
+        BOX_INDEX_COUNT,
+        visible_counts[mesh],
+        0u,
+        i32(mesh * BOX_VERTICES),
+        meshes[mesh].first_instance,
+    );
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) @interpolate(flat) sphere: vec4f,
+}
+
+// Box covering the footprint of the bounds, from their center down to the reach of the blob
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let mesh = vertex_index / BOX_VERTICES;
+    let corner = vertex_index % BOX_VERTICES;
+    let sphere = instance_sphere(visible[instance_index].instance, spheres[mesh]);
+    let radius = sphere.w;
+    let unit = vec3f(f32(corner & 1u), f32((corner >> 1u) & 1u), f32(corner >> 2u));
+    let bottom = sphere.y - radius * (1.0 + params.reach);
+    let position = vec3f(
+        sphere.x + radius * (unit.x * 2.0 - 1.0),
+        mix(bottom, sphere.y, unit.y),
+        sphere.z + radius * (unit.z * 2.0 - 1.0),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = proj * view * vec4f(position, 1.0);
+    out.sphere = sphere;
+    return out;
+}
+
+// World space position under a pixel
+fn world_position(pixel: vec2i) -> vec3f {
+    let size = vec2i(viewport_size);
+    let texel = clamp(pixel, vec2i(0), size - 1);
+    let depth = textureLoad(t_scene_depth, texel, 0);
+    let uv = (vec2f(texel) + 0.5) / vec2f(size);
+    let ndc = vec2f(uv.x, 1.0 - uv.y) * 2.0 - 1.0;
+    let position = inv_proj * vec4f(ndc, depth, 1.0);
+    return (inv_view * vec4f(position.xyz / position.w, 1.0)).xyz;
+}
+
+// Multiplied with the scene color, 1 leaves it as is
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let world = world_position(vec2i(in.clip_position.xy));
+    let normal = normalize(cross(dpdy(world), dpdx(world)));
+    let offset = (world - in.sphere.xyz) / in.sphere.w;
+
+    let across = length(offset.xz);
+    let spread = 1.0 - smoothstep(1.0 - params.softness, 1.0, across);
+    // Nothing above the center, so the upper half of the instance is left alone, then fading out
+    // past the bottom of the bounds
+    let below = -offset.y;
+    let drop = saturate((below - 1.0) / max(params.reach, 1e-3));
+    let height = smoothstep(0.0, 0.5, below) * (1.0 - drop);
+    // Walls and the sides of the instance are not darkened
+    let facing = saturate(normal.y);
+
+    let shade = params.strength * spread * height * facing;
+    return vec4f(vec3f(1.0 - shade), 1.0);
+}
@@ -0,0 +1,200 @@
+use std::path::PathBuf;
+
+use tobj::Mesh;
+
+use crate::{constants::MODEL_CACHE_PATH, ASSETS};
+
+use super::{
+    model::{ImportOptions, Material},
+    BakedVertices,
+};
+
+/// Bumped whenever the import processing or the layout below changes, older entries are ignored
+const CACHE_VERSION: u32 = 2;
+const MAGIC: &[u8; 4] = b"FTMC";
+
+/// Model data left by the import processing, before the textures are decoded
+#[derive(Debug, Clone, Default)]
+pub struct ProcessedModel {
+    pub meshes: Vec<Mesh>,
+    /// One per mesh
+    pub baked: Vec<BakedVertices>,
+    pub materials: Vec<Material>,
+    /// Names in the textures folder
+    pub textures: Vec<String>,
+}
+
+/// Hash of the obj, its mtl files and the import options, `None` when the model is missing
+pub fn cache_key(model_name: &str, options: &ImportOptions) -> Option<u64> {
+    let assets = ASSETS.tree();
+    let obj = &assets.models.get(model_name)?.0;
+    let mut hash = Fnv1a::default();
+    hash.write(&CACHE_VERSION.to_le_bytes());
+    hash.write(obj.as_bytes());
+    for library in obj
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("mtllib "))
+    {
+        let name = library.trim().trim_end_matches(".mtl");
+        if let Some(material) = assets.materials.get(name) {
+            hash.write(material.0.as_bytes());
+        }
+    }
+    hash.write(format!("{options:?}").as_bytes());
+    Some(hash.0)
+}
+
+fn cache_path(model_name: &str, key: u64) -> PathBuf {
+    PathBuf::from(MODEL_CACHE_PATH).join(format!("{model_name}-{key:016x}.bin"))
+}
+
+/// `None` on a miss, unreadable entries are reported and imported again
+pub fn read(model_name: &str, key: u64) -> Option<ProcessedModel> {
+    let path = cache_path(model_name, key);
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", path.display());
+            return None;
+        }
+    };
+    decode(&bytes)
+        .map_err(|e| eprintln!("Ignoring the cache of {model_name}: {e}"))
+        .ok()
+}
+
+/// Also removes the older entries of the model
+pub fn write(model_name: &str, key: u64, model: &ProcessedModel) -> Result<(), String> {
+    std::fs::create_dir_all(MODEL_CACHE_PATH).map_err(|e| e.to_string())?;
+    let prefix = format!("{model_name}-");
+    for entry in std::fs::read_dir(MODEL_CACHE_PATH).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let stale = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(&prefix))
+            .is_some_and(|hash| hash.len() == 16);
+        if stale {
+            std::fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+    }
+    std::fs::write(cache_path(model_name, key), encode(model)).map_err(|e| e.to_string())
+}
+
+fn encode(model: &ProcessedModel) -> Vec<u8> {
+    let mut out = Writer(MAGIC.to_vec());
+    out.u32(CACHE_VERSION);
+    out.u32(model.meshes.len() as u32);
+    for (mesh, baked) in model.meshes.iter().zip(&model.baked) {
+        out.slice(&mesh.positions);
+        out.slice(&mesh.vertex_color);
+        out.slice(&mesh.normals);
+        out.slice(&mesh.texcoords);
+        out.slice(&mesh.indices);
+        out.u32(mesh.material_id.map_or(u32::MAX, |id| id as u32));
+        out.slice(&baked.lightmap_uvs);
+        out.slice(&baked.ao);
+        out.slice(&baked.tangents);
+    }
+    out.slice(&model.materials);
+    out.u32(model.textures.len() as u32);
+    for texture in &model.textures {
+        out.slice(texture.as_bytes());
+    }
+    out.0
+}
+
+fn decode(bytes: &[u8]) -> Result<ProcessedModel, String> {
+    let mut src = Reader(bytes);
+    if src.take(MAGIC.len())? != MAGIC || src.u32()? != CACHE_VERSION {
+        return Err(String::from("Not a cache entry of this version"));
+    }
+    let mut model = ProcessedModel::default();
+    for _ in 0..src.u32()? {
+        let mut mesh = Mesh {
+            positions: src.vec()?,
+            vertex_color: src.vec()?,
+            normals: src.vec()?,
+            texcoords: src.vec()?,
+            indices: src.vec()?,
+            ..Default::default()
+        };
+        mesh.material_id = Some(src.u32()?)
+            .filter(|id| *id != u32::MAX)
+            .map(|id| id as usize);
+        model.meshes.push(mesh);
+        model.baked.push(BakedVertices {
+            lightmap_uvs: src.vec()?,
+            ao: src.vec()?,
+            tangents: src.vec()?,
+        });
+    }
+    model.materials = src.vec()?;
+    for _ in 0..src.u32()? {
+        let name = src.vec::<u8>()?;
+        model
+            .textures
+            .push(String::from_utf8(name).map_err(|e| e.to_string())?);
+    }
+    Ok(model)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    /// Length prefixed
+    fn slice<T: bytemuck::Pod>(&mut self, values: &[T]) {
+        self.u32(values.len() as u32);
+        self.0.extend_from_slice(bytemuck::cast_slice(values));
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err(String::from("Truncated cache entry"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Copied out, the bytes are not aligned for `T`
+    fn vec<T: bytemuck::Pod>(&mut self) -> Result<Vec<T>, String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len * std::mem::size_of::<T>())?;
+        Ok(bytes
+            .chunks_exact(std::mem::size_of::<T>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect())
+    }
+}
+
+/// Stable across builds, unlike the std hasher
+#[derive(Debug, Clone, Copy)]
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
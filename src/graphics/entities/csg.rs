@@ -0,0 +1,579 @@
+use std::fmt::Write;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+use tobj::Mesh;
+
+/// Points closer than this to a plane are on it
+const EPSILON: f32 = 1e-5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CsgVertex {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+impl CsgVertex {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            normal: self.normal.lerp(&other.normal, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Plane {
+    normal: Vector3<f32>,
+    w: f32,
+}
+
+impl Plane {
+    fn from_points(a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Option<Self> {
+        let normal = (b - a).cross(&(c - a)).try_normalize(1e-12)?;
+        Some(Self {
+            normal,
+            w: normal.dot(&a.coords),
+        })
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+
+    fn distance(&self, point: &Point3<f32>) -> f32 {
+        self.normal.dot(&point.coords) - self.w
+    }
+}
+
+/// Convex and planar, counter clockwise seen from the outside
+#[derive(Debug, Clone)]
+struct Polygon {
+    vertices: Vec<CsgVertex>,
+    plane: Plane,
+}
+
+impl Polygon {
+    fn new(vertices: Vec<CsgVertex>) -> Option<Self> {
+        let plane = Plane::from_points(
+            vertices[0].position,
+            vertices[1].position,
+            vertices[2].position,
+        )?;
+        Some(Self { vertices, plane })
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        for vertex in &mut self.vertices {
+            vertex.normal = -vertex.normal;
+        }
+        self.plane.flip();
+    }
+}
+
+/// Polygons sorted by [`split_polygon`]
+#[derive(Default)]
+struct Split {
+    coplanar_front: Vec<Polygon>,
+    coplanar_back: Vec<Polygon>,
+    front: Vec<Polygon>,
+    back: Vec<Polygon>,
+}
+
+fn split_polygon(plane: &Plane, polygon: Polygon, split: &mut Split) {
+    const COPLANAR: u8 = 0;
+    const FRONT: u8 = 1;
+    const BACK: u8 = 2;
+    const SPANNING: u8 = 3;
+
+    let sides: Vec<_> = polygon
+        .vertices
+        .iter()
+        .map(|vertex| match plane.distance(&vertex.position) {
+            d if d < -EPSILON => BACK,
+            d if d > EPSILON => FRONT,
+            _ => COPLANAR,
+        })
+        .collect();
+    match sides.iter().fold(COPLANAR, |kind, side| kind | side) {
+        COPLANAR if plane.normal.dot(&polygon.plane.normal) > 0.0 => {
+            split.coplanar_front.push(polygon)
+        }
+        COPLANAR => split.coplanar_back.push(polygon),
+        FRONT => split.front.push(polygon),
+        BACK => split.back.push(polygon),
+        _ => {
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            let count = polygon.vertices.len();
+            for i in 0..count {
+                let (j, a, b) = (
+                    (i + 1) % count,
+                    &polygon.vertices[i],
+                    &polygon.vertices[(i + 1) % count],
+                );
+                let (side_a, side_b) = (sides[i], sides[j]);
+                if side_a != BACK {
+                    front.push(*a);
+                }
+                if side_a != FRONT {
+                    back.push(*a);
+                }
+                if (side_a | side_b) == SPANNING {
+                    let t =
+                        -plane.distance(&a.position) / plane.normal.dot(&(b.position - a.position));
+                    let vertex = a.lerp(b, t);
+                    front.push(vertex);
+                    back.push(vertex);
+                }
+            }
+            if front.len() >= 3 {
+                split.front.extend(Polygon::new(front));
+            }
+            if back.len() >= 3 {
+                split.back.extend(Polygon::new(back));
+            }
+        }
+    }
+}
+
+/// Binary space partition of a solid, the front of each plane is outside
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    polygons: Vec<Polygon>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut node = Self::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Swaps the inside and the outside
+    fn invert(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.flip();
+        }
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        for child in [&mut self.front, &mut self.back].into_iter().flatten() {
+            child.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Parts of the polygons outside of this solid
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let Some(plane) = &self.plane else {
+            return polygons;
+        };
+        let mut split = Split::default();
+        for polygon in polygons {
+            split_polygon(plane, polygon, &mut split);
+        }
+        let mut front = split.front;
+        front.extend(split.coplanar_front);
+        let mut back = split.back;
+        back.extend(split.coplanar_back);
+
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        // Without a back child, the back is inside the solid
+        if let Some(node) = &self.back {
+            front.extend(node.clip_polygons(back));
+        }
+        front
+    }
+
+    /// Removes the parts of the polygons inside `other`
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+        for child in [&mut self.front, &mut self.back].into_iter().flatten() {
+            child.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+        for child in [&self.front, &self.back].into_iter().flatten() {
+            polygons.extend(child.all_polygons());
+        }
+        polygons
+    }
+
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        let Some(first) = polygons.first() else {
+            return;
+        };
+        let plane = *self.plane.get_or_insert(first.plane);
+        let mut split = Split::default();
+        for polygon in polygons {
+            split_polygon(&plane, polygon, &mut split);
+        }
+        self.polygons.extend(split.coplanar_front);
+        self.polygons.extend(split.coplanar_back);
+        for (child, polygons) in [(&mut self.front, split.front), (&mut self.back, split.back)] {
+            if !polygons.is_empty() {
+                child.get_or_insert_with(Default::default).build(polygons);
+            }
+        }
+    }
+}
+
+/// Solid made of polygons, combined with the boolean operations
+///
+/// Port of the classic csg.js, the inputs must be closed meshes
+#[derive(Debug, Clone, Default)]
+pub struct Csg {
+    polygons: Vec<Polygon>,
+}
+
+impl Csg {
+    /// Every triangle of the mesh, the normals are the face ones when the mesh has none
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let vertex = |i: u32| {
+            let i = i as usize;
+            let position = Point3::from_slice(&mesh.positions[i * 3..i * 3 + 3]);
+            let normal = mesh
+                .normals
+                .get(i * 3..i * 3 + 3)
+                .map(Vector3::from_column_slice);
+            (position, normal)
+        };
+        let polygons = mesh
+            .indices
+            .chunks_exact(3)
+            .filter_map(|face| {
+                let vertices = [vertex(face[0]), vertex(face[1]), vertex(face[2])];
+                let plane = Plane::from_points(vertices[0].0, vertices[1].0, vertices[2].0)?;
+                let vertices = vertices
+                    .map(|(position, normal)| CsgVertex {
+                        position,
+                        normal: normal.unwrap_or(plane.normal),
+                    })
+                    .to_vec();
+                Some(Polygon { vertices, plane })
+            })
+            .collect();
+        Self { polygons }
+    }
+
+    /// Axis aligned box of the given half size
+    pub fn cuboid(center: Point3<f32>, half_size: Vector3<f32>) -> Self {
+        // Corners of each face as bits of x, y and z, counter clockwise from the outside
+        let faces = [
+            ([0, 4, 6, 2], -Vector3::x()),
+            ([1, 3, 7, 5], Vector3::x()),
+            ([0, 1, 5, 4], -Vector3::y()),
+            ([2, 6, 7, 3], Vector3::y()),
+            ([0, 2, 3, 1], -Vector3::z()),
+            ([4, 5, 7, 6], Vector3::z()),
+        ];
+        let polygons = faces
+            .into_iter()
+            .filter_map(|(corners, normal)| {
+                let vertices = corners
+                    .map(|corner: u32| {
+                        let sign = |bit| if corner & bit != 0 { 1.0 } else { -1.0 };
+                        let offset = Vector3::new(sign(1), sign(2), sign(4));
+                        CsgVertex {
+                            position: center + offset.component_mul(&half_size),
+                            normal,
+                        }
+                    })
+                    .to_vec();
+                Polygon::new(vertices)
+            })
+            .collect();
+        Self { polygons }
+    }
+
+    /// Made of `slices` around y and `stacks` from pole to pole
+    pub fn sphere(center: Point3<f32>, radius: f32, slices: u32, stacks: u32) -> Self {
+        let (slices, stacks) = (slices.max(3), stacks.max(2));
+        let vertex = |slice: u32, stack: u32| {
+            let theta = slice as f32 / slices as f32 * std::f32::consts::TAU;
+            let phi = stack as f32 / stacks as f32 * std::f32::consts::PI;
+            let normal = Vector3::new(theta.cos() * phi.sin(), phi.cos(), theta.sin() * phi.sin());
+            CsgVertex {
+                position: center + normal * radius,
+                normal,
+            }
+        };
+        let mut polygons = Vec::new();
+        for slice in 0..slices {
+            for stack in 0..stacks {
+                // Triangles at the poles
+                let mut vertices = vec![vertex(slice, stack)];
+                if stack > 0 {
+                    vertices.push(vertex(slice + 1, stack));
+                }
+                if stack < stacks - 1 {
+                    vertices.push(vertex(slice + 1, stack + 1));
+                }
+                vertices.push(vertex(slice, stack + 1));
+                polygons.extend(Polygon::new(vertices));
+            }
+        }
+        Self { polygons }
+    }
+
+    /// Along y, centered on `center`
+    pub fn cylinder(center: Point3<f32>, radius: f32, height: f32, slices: u32) -> Self {
+        let slices = slices.max(3);
+        let half = height * 0.5;
+        let point = |slice: u32, y: f32| {
+            let theta = slice as f32 / slices as f32 * std::f32::consts::TAU;
+            let out = Vector3::new(theta.cos(), 0.0, theta.sin());
+            (center + out * radius + Vector3::y() * y, out)
+        };
+        let cap = |y: f32, normal: Vector3<f32>| CsgVertex {
+            position: center + Vector3::y() * y,
+            normal,
+        };
+        let mut polygons = Vec::new();
+        for slice in 0..slices {
+            let (a_bottom, out_a) = point(slice, -half);
+            let (b_bottom, out_b) = point(slice + 1, -half);
+            let (a_top, b_top) = (point(slice, half).0, point(slice + 1, half).0);
+            let side = |position, normal| CsgVertex { position, normal };
+            polygons.extend(Polygon::new(vec![
+                side(a_bottom, out_a),
+                side(a_top, out_a),
+                side(b_top, out_b),
+                side(b_bottom, out_b),
+            ]));
+            polygons.extend(Polygon::new(vec![
+                cap(half, Vector3::y()),
+                side(b_top, Vector3::y()),
+                side(a_top, Vector3::y()),
+            ]));
+            polygons.extend(Polygon::new(vec![
+                cap(-half, -Vector3::y()),
+                side(a_bottom, -Vector3::y()),
+                side(b_bottom, -Vector3::y()),
+            ]));
+        }
+        Self { polygons }
+    }
+
+    pub fn transformed(mut self, transform: &Matrix4<f32>) -> Self {
+        let linear = transform.fixed_view::<3, 3>(0, 0).into_owned();
+        let normal_matrix = linear.try_inverse().unwrap_or_default().transpose();
+        // Mirroring transforms turn the solid inside out
+        let flip = linear.determinant() < 0.0;
+        self.polygons = self
+            .polygons
+            .into_iter()
+            .filter_map(|polygon| {
+                let vertices = polygon
+                    .vertices
+                    .into_iter()
+                    .map(|vertex| CsgVertex {
+                        position: transform.transform_point(&vertex.position),
+                        normal: (normal_matrix * vertex.normal).normalize(),
+                    })
+                    .collect();
+                let mut polygon = Polygon::new(vertices)?;
+                if flip {
+                    polygon.flip();
+                }
+                Some(polygon)
+            })
+            .collect();
+        self
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    pub fn subtract(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// Outlines of the polygons, for previews
+    pub fn edges(&self) -> impl Iterator<Item = (Point3<f32>, Point3<f32>)> + '_ {
+        self.polygons.iter().flat_map(|polygon| {
+            let vertices = &polygon.vertices;
+            (0..vertices.len()).map(move |i| {
+                let next = (i + 1) % vertices.len();
+                (vertices[i].position, vertices[next].position)
+            })
+        })
+    }
+
+    /// Polygons fanned into triangles, vertices are not shared
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::default();
+        for polygon in &self.polygons {
+            let base = (mesh.positions.len() / 3) as u32;
+            for vertex in &polygon.vertices {
+                mesh.positions.extend(vertex.position.iter());
+                mesh.normals.extend(vertex.normal.iter());
+                // Planar mapping along the dominant axis of the face
+                let n = polygon.plane.normal.abs();
+                let p = vertex.position;
+                mesh.texcoords.extend(match n.imax() {
+                    0 => [p.z, p.y],
+                    1 => [p.x, p.z],
+                    _ => [p.x, p.y],
+                });
+            }
+            for i in 1..polygon.vertices.len() as u32 - 1 {
+                mesh.indices.extend([base, base + i, base + i + 1]);
+            }
+        }
+        mesh
+    }
+}
+
+/// Wavefront obj of the mesh, loadable as a model asset
+pub fn mesh_to_obj(mesh: &Mesh) -> String {
+    let mut obj = String::new();
+    for p in mesh.positions.chunks_exact(3) {
+        let _ = writeln!(obj, "v {} {} {}", p[0], p[1], p[2]);
+    }
+    for n in mesh.normals.chunks_exact(3) {
+        let _ = writeln!(obj, "vn {} {} {}", n[0], n[1], n[2]);
+    }
+    for t in mesh.texcoords.chunks_exact(2) {
+        let _ = writeln!(obj, "vt {} {}", t[0], t[1]);
+    }
+    for face in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [face[0] + 1, face[1] + 1, face[2] + 1];
+        let _ = writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}");
+    }
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signed volume enclosed by the polygons, from the tetrahedra they form with the origin
+    fn volume(csg: &Csg) -> f32 {
+        let mesh = csg.to_mesh();
+        let point = |i: u32| Point3::from_slice(&mesh.positions[i as usize * 3..][..3]);
+        mesh.indices
+            .chunks_exact(3)
+            .map(|face| {
+                let [a, b, c] = [point(face[0]), point(face[1]), point(face[2])];
+                a.coords.dot(&b.coords.cross(&c.coords)) / 6.0
+            })
+            .sum()
+    }
+
+    fn unit_cube(x: f32) -> Csg {
+        Csg::cuboid(Point3::new(x, 0.0, 0.0), Vector3::repeat(0.5))
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-3,
+            "expected {expected}, got {value}"
+        );
+    }
+
+    #[test]
+    fn primitives_are_closed_and_outward() {
+        assert_near(volume(&unit_cube(0.0)), 1.0);
+        let cylinder = Csg::cylinder(Point3::origin(), 0.5, 1.0, 64);
+        // Prism on a regular polygon of 64 sides
+        let base = 0.5 * 64.0 * 0.25 * (std::f32::consts::TAU / 64.0).sin();
+        assert_near(volume(&cylinder), base);
+        let sphere = Csg::sphere(Point3::origin(), 1.0, 64, 32);
+        assert!((volume(&sphere) - 4.0 / 3.0 * std::f32::consts::PI).abs() < 0.05);
+    }
+
+    #[test]
+    fn overlapping_cubes() {
+        let (a, b) = (unit_cube(0.0), unit_cube(0.5));
+        assert_near(volume(&a.union(&b)), 1.5);
+        assert_near(volume(&a.subtract(&b)), 0.5);
+        assert_near(volume(&a.intersect(&b)), 0.5);
+    }
+
+    #[test]
+    fn disjoint_cubes() {
+        let (a, b) = (unit_cube(0.0), unit_cube(2.0));
+        assert_near(volume(&a.union(&b)), 2.0);
+        assert_near(volume(&a.subtract(&b)), 1.0);
+        assert!(a.intersect(&b).is_empty());
+    }
+
+    #[test]
+    fn subtracting_a_container_leaves_nothing() {
+        let inner = unit_cube(0.0);
+        let outer = Csg::cuboid(Point3::origin(), Vector3::repeat(1.0));
+        assert!(inner.subtract(&outer).is_empty());
+        assert_near(volume(&outer.subtract(&inner)), 7.0);
+    }
+
+    #[test]
+    fn mirroring_keeps_the_solid_outward() {
+        let mirrored = unit_cube(0.0).transformed(&Matrix4::new_nonuniform_scaling(&Vector3::new(
+            -1.0, 1.0, 2.0,
+        )));
+        assert_near(volume(&mirrored), 2.0);
+    }
+
+    #[test]
+    fn obj_export_indexes_from_one() {
+        let obj = mesh_to_obj(&unit_cube(0.0).to_mesh());
+        let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+        // Six quads of four vertices, fanned into two triangles each
+        assert_eq!(count("v "), 24);
+        assert_eq!(count("vn "), 24);
+        assert_eq!(count("f "), 12);
+        assert!(obj.contains("f 1/1/1 2/2/2 3/3/3"));
+    }
+}
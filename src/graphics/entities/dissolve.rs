@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    model::{ModelInstance, ModelInstanceId, ModelsBuffer},
+    params::{InstanceParams, ParamBlock},
+};
+
+/// Instances appearing and disappearing burn in and out from a noise, along a glowing edge
+///
+/// The progress is written in [`ParamBlock::dissolve`] of their block every frame. Instances
+/// dissolved in keep their block while they live, it is freed by [`Self::dissolve_out`] or
+/// [`Self::release`]
+pub struct Dissolves {
+    pub duration: Duration,
+    /// Linear, above 1 so the edge blooms
+    pub edge_color: [f32; 3],
+    active: Vec<Dissolve>,
+}
+
+struct Dissolve {
+    params_id: u32,
+    started: Instant,
+    /// Removed once dissolved, empty while dissolving in
+    removed: Vec<ModelInstanceId>,
+}
+
+impl Default for Dissolves {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(600),
+            edge_color: [4.0, 1.6, 0.4],
+            active: Vec::new(),
+        }
+    }
+}
+
+impl Dissolves {
+    /// Block of instances dissolving in, to give to them with [`ModelInstance::with_params`]
+    /// before they are added
+    pub fn dissolve_in(&mut self, params: &mut InstanceParams) -> u32 {
+        let params_id = params.add(ParamBlock {
+            dissolve: self.dissolve(1.0),
+            ..Default::default()
+        });
+        self.active.push(Dissolve {
+            params_id,
+            started: Instant::now(),
+            removed: Vec::new(),
+        });
+        params_id
+    }
+
+    /// Dissolves the instances, all drawn as `instance`, then removes them along with their block.
+    /// Instances without a block get one, the values of an existing one are kept
+    pub fn dissolve_out(
+        &mut self,
+        models: &mut ModelsBuffer,
+        params: &mut InstanceParams,
+        instances: Vec<ModelInstanceId>,
+        instance: ModelInstance,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+        let params_id = match instance.params_id {
+            0 => params.add(ParamBlock::default()),
+            params_id => params_id as u32,
+        };
+        // Taken over from a dissolve in still running
+        self.stop_dissolving_in(params_id);
+        let instance = instance.with_params(params_id);
+        for id in &instances {
+            models.set_instance(id, instance);
+        }
+        self.active.push(Dissolve {
+            params_id,
+            started: Instant::now(),
+            removed: instances,
+        });
+    }
+
+    /// Frees the block of instances dissolved in then removed without dissolving out
+    pub fn release(&mut self, params: &mut InstanceParams, params_id: u32) {
+        if params_id == 0 {
+            return;
+        }
+        self.stop_dissolving_in(params_id);
+        params.remove(params_id);
+    }
+
+    /// Dissolves running, in and out
+    pub fn running(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Called every frame, before the instance and parameter changes are applied
+    pub fn update(&mut self, models: &mut ModelsBuffer, params: &mut InstanceParams) {
+        let duration = self.duration.as_secs_f32().max(1e-3);
+        for dissolve in std::mem::take(&mut self.active) {
+            let t = (dissolve.started.elapsed().as_secs_f32() / duration).min(1.0);
+            let removing = !dissolve.removed.is_empty();
+            let mut block = params.get(dissolve.params_id).copied().unwrap_or_default();
+            block.dissolve = self.dissolve(if removing { t } else { 1.0 - t });
+            params.set(dissolve.params_id, block);
+
+            if t < 1.0 {
+                self.active.push(dissolve);
+            } else if removing {
+                for id in dissolve.removed {
+                    models.remove_instance(id);
+                }
+                params.remove(dissolve.params_id);
+            }
+        }
+    }
+
+    fn stop_dissolving_in(&mut self, params_id: u32) {
+        self.active
+            .retain(|dissolve| dissolve.params_id != params_id || !dissolve.removed.is_empty());
+    }
+
+    /// 0 is fully drawn, 1 fully dissolved
+    fn dissolve(&self, progress: f32) -> [f32; 4] {
+        let [r, g, b] = self.edge_color;
+        [r, g, b, progress]
+    }
+}
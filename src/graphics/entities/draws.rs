@@ -0,0 +1,468 @@
+use nalgebra::Matrix4;
+use wgpu::{include_wgsl, util::DrawIndexedIndirectArgs};
+
+use crate::graphics::{
+    buffer::{
+        CommonBuffer, Growable, IndirectBuffer, InstanceBuffer, StorageBuffer, UniformBuffer,
+        WriteBuffer,
+    },
+    ctx::GraphicsCtx,
+};
+
+use super::model::{ModelInstance, ModelsBuffer, VertexPrecision, INSTANCE_ATTRIBUTES};
+
+/// Meshes are assigned to one of them, see [`DrawGenerator::set_cull_layer`]
+pub const CULL_LAYERS: usize = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct RawCulling {
+    /// Inward facing planes of the clip volume, the fifth is the near one
+    planes: [[f32; 4]; 6],
+    depth_sort: u32,
+    /// Pixels covered by a unit length at a clip w of 1, vertically
+    pixel_scale: f32,
+    _padding: [u32; 2],
+    /// Last row of the view projection, the clip w of a point
+    clip_w: [f32; 4],
+    /// Per cull layer, the smallest projected diameter in pixels drawn
+    min_pixels: [f32; CULL_LAYERS],
+}
+
+crate::wgsl_layout!(RawCulling as "Culling" {
+    planes,
+    depth_sort,
+    pixel_scale,
+    clip_w,
+    min_pixels,
+});
+
+impl RawCulling {
+    /// With the wgpu depth range of 0 to 1
+    fn new(
+        view_proj: &Matrix4<f32>,
+        depth_sort: bool,
+        viewport_height: u32,
+        min_pixels: [f32; CULL_LAYERS],
+    ) -> Self {
+        let row = |i| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.xyz().norm().max(1e-6);
+            (plane / length).into()
+        });
+        Self {
+            planes,
+            depth_sort: depth_sort as u32,
+            // The view is a rigid transform, the norm of the row is the vertical projection scale
+            pixel_scale: y.xyz().norm() * viewport_height as f32 * 0.5,
+            _padding: [0; 2],
+            clip_w: w.into(),
+            min_pixels,
+        }
+    }
+}
+
+/// Instance copied by the draw generation, with its transform of the previous frame
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VisibleInstance {
+    pub instance: ModelInstance,
+    /// Rows of the affine transform, for the motion vectors
+    pub previous_transform: [[f32; 4]; 3],
+}
+
+crate::wgsl_layout!(VisibleInstance as "VisibleInstance" {
+    instance,
+    previous_transform,
+});
+
+/// Row of [`VisibleInstance::previous_transform`], at locations 13 to 15
+const fn previous_row(row: u32) -> wgpu::VertexAttribute {
+    wgpu::VertexAttribute {
+        offset: std::mem::size_of::<ModelInstance>() as u64 + row as u64 * 16,
+        shader_location: 13 + row,
+        format: wgpu::VertexFormat::Float32x4,
+    }
+}
+
+impl VisibleInstance {
+    /// Attributes of [`ModelInstance::buffer_desc`] followed by the previous transform
+    pub fn buffer_desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; INSTANCE_ATTRIBUTES.len() + 3] = {
+            let mut attributes = [previous_row(0); INSTANCE_ATTRIBUTES.len() + 3];
+            let mut i = 0;
+            while i < INSTANCE_ATTRIBUTES.len() {
+                attributes[i] = INSTANCE_ATTRIBUTES[i];
+                i += 1;
+            }
+            attributes[i + 1] = previous_row(1);
+            attributes[i + 2] = previous_row(2);
+            attributes
+        };
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<VisibleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Compute pass generating the draws of the entities pass or of a shadow cascade, every frame
+///
+/// Each mesh of the `ModelsBuffer` indirect args is one workgroup, its instances outside the
+/// camera frustum or in a hidden zone are culled and the rest copied into `visible`. Meshes left
+/// with instances are appended to the region of their vertex precision in `draws`, whose lengths
+/// are written to `draw_count`.
+///
+/// The instances drawn are stored as `previous` at the end of the frame. Their columns move when
+/// the instance buffer grows and instances are swapped when removed, their motion is off for a frame
+pub struct DrawGenerator {
+    /// Copies the visible instances of each mesh front to back, by slices of distance
+    pub depth_sort: bool,
+    /// Per cull layer, instances whose bounding sphere covers fewer pixels across are skipped.
+    /// 0 keeps every size
+    pub min_pixels: [f32; CULL_LAYERS],
+
+    culling: UniformBuffer<RawCulling>,
+    /// Model space bounding sphere of every mesh
+    spheres: StorageBuffer<[f32; 4]>,
+    /// Cull layer of every mesh
+    layers: Vec<u32>,
+    mesh_layers: StorageBuffer<u32>,
+    changed_layers: Vec<u32>,
+    visible: Growable<InstanceBuffer<VisibleInstance>>,
+    previous: Growable<InstanceBuffer<ModelInstance>>,
+    /// Per mesh, the precision of its vertices and the first draw of that precision
+    regions: StorageBuffer<[u32; 2]>,
+    /// Per mesh, the instances kept by the last generation
+    visible_counts: StorageBuffer<u32>,
+    /// The full precision draws come first, then the half ones
+    draws: IndirectBuffer,
+    /// One counter per precision
+    draw_count: wgpu::Buffer,
+    mesh_count: u32,
+    full_mesh_count: u32,
+    /// Whether the draws are limited to `draw_count`, see `constants::OPTIONAL_FEATURES`
+    counted: bool,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl DrawGenerator {
+    pub fn new(ctx: &GraphicsCtx, models: &ModelsBuffer) -> Self {
+        let spheres = (0..models.model_count() as u16)
+            .flat_map(|model_id| {
+                (0..models.mesh_count_of(model_id) as u16).map(move |mesh_id| (model_id, mesh_id))
+            })
+            .map(|(model_id, mesh_id)| {
+                let bounds = models.mesh_bounds(model_id, mesh_id);
+                let center = bounds.center();
+                [center.x, center.y, center.z, bounds.radius()]
+            })
+            .collect::<Vec<_>>();
+        let spheres = StorageBuffer::new_array("Mesh bounding spheres", ctx, &spheres[..]);
+        let culling = UniformBuffer::new(
+            "Draws culling",
+            ctx,
+            &RawCulling::new(&Matrix4::identity(), false, 1, [0.0; CULL_LAYERS]),
+        );
+        let layers = vec![0; models.mesh_count().max(1) as usize];
+        let mesh_layers = StorageBuffer::new_array("Mesh cull layers", ctx, &layers[..]);
+
+        let mesh_count = models.mesh_count();
+        let full_mesh_count = models.mesh_count_with(VertexPrecision::Full);
+        let regions = (0..models.model_count() as u16)
+            .flat_map(|model_id| {
+                (0..models.mesh_count_of(model_id) as u16)
+                    .map(move |mesh_id| models.mesh_precision(model_id, mesh_id))
+            })
+            .map(|precision| match precision {
+                VertexPrecision::Full => [0, 0],
+                VertexPrecision::Half => [1, full_mesh_count],
+            })
+            .collect::<Vec<_>>();
+        let regions = StorageBuffer::new_array("Mesh draw regions", ctx, &regions[..]);
+        let visible_counts = StorageBuffer::new_array(
+            "Mesh visible counts",
+            ctx,
+            &vec![0; mesh_count.max(1) as usize][..],
+        );
+        let visible =
+            InstanceBuffer::new_empty_vec("Visible instances", ctx, instance_capacity(models));
+        let previous =
+            InstanceBuffer::new_empty_vec("Previous instances", ctx, instance_capacity(models));
+        let draws = IndirectBuffer::new_empty("Generated draws", ctx, mesh_count as usize);
+        let draw_count = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Generated draw count"),
+            size: std::mem::size_of::<[u32; 2]>() as u64,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&draws_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx.device.create_shader_module(include_wgsl!("draws.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Draw generation"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("cs_main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        Self {
+            depth_sort: false,
+            min_pixels: [0.0; CULL_LAYERS],
+            culling,
+            spheres,
+            layers,
+            mesh_layers,
+            changed_layers: Vec::new(),
+            visible,
+            previous,
+            regions,
+            visible_counts,
+            draws,
+            draw_count,
+            mesh_count,
+            full_mesh_count,
+            counted: ctx.has_feature(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            pipeline,
+        }
+    }
+
+    /// Keeps the visible and previous instances as large as the instances they are copied from
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx, models: &ModelsBuffer) {
+        self.visible.maybe_grow(ctx, instance_capacity(models));
+        self.previous.maybe_grow(ctx, instance_capacity(models));
+        for mesh in self.changed_layers.drain(..) {
+            self.mesh_layers
+                .write_at_index(ctx, &self.layers[mesh as usize], mesh);
+        }
+    }
+
+    pub fn cull_layer(&self, models: &ModelsBuffer, model_id: u16, mesh_id: u16) -> u32 {
+        self.layers[models.mesh_index(model_id, mesh_id) as usize]
+    }
+
+    /// Which of [`Self::min_pixels`] applies to the instances of the mesh, uploaded by
+    /// `apply_changes`
+    pub fn set_cull_layer(
+        &mut self,
+        models: &ModelsBuffer,
+        model_id: u16,
+        mesh_id: u16,
+        layer: u32,
+    ) {
+        let mesh = models.mesh_index(model_id, mesh_id);
+        self.layers[mesh as usize] = layer.min(CULL_LAYERS as u32 - 1);
+        self.changed_layers.push(mesh);
+    }
+
+    /// Instance buffer the generated draws index into
+    pub fn instances(&self) -> &InstanceBuffer<VisibleInstance> {
+        &self.visible
+    }
+
+    /// Visible instances of each mesh, compacted at the start of its column in `instances`
+    pub fn visible_counts(&self) -> &StorageBuffer<u32> {
+        &self.visible_counts
+    }
+
+    /// Model space bounding sphere of every mesh, center then radius
+    pub fn spheres(&self) -> &StorageBuffer<[f32; 4]> {
+        &self.spheres
+    }
+
+    /// Instances of the previous frame, for the motion vectors
+    pub fn previous(&self) -> &InstanceBuffer<ModelInstance> {
+        &self.previous
+    }
+
+    /// Must be recorded after the pass drawing the entities, before the instances change
+    pub fn store_history(&self, encoder: &mut wgpu::CommandEncoder, models: &ModelsBuffer) {
+        // Both are capped by the device limit, the history stops there too
+        let size = models
+            .instance_buffer
+            .inner()
+            .size()
+            .min(self.previous.inner().size());
+        encoder.copy_buffer_to_buffer(
+            models.instance_buffer.inner(),
+            0,
+            self.previous.inner(),
+            0,
+            size,
+        );
+    }
+
+    pub fn generate(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        models: &ModelsBuffer,
+        zones_visibility: &StorageBuffer<u32>,
+        view_proj: &Matrix4<f32>,
+    ) {
+        let culling = RawCulling::new(
+            view_proj,
+            self.depth_sort,
+            ctx.viewport_size.1,
+            self.min_pixels,
+        );
+        self.culling.write(ctx, &culling);
+        // Unused draws stay zeroed for devices drawing every slot
+        encoder.clear_buffer(self.draws.inner(), 0, None);
+        encoder.clear_buffer(&self.draw_count, 0, None);
+
+        // The instance buffers are reallocated when growing, binding them every frame keeps up
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &draws_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.culling.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: models.indirect_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.spheres.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: zones_visibility.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: models.instance_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.visible.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: self.draws.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: self.draw_count.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: self.previous.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: self.regions.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: self.visible_counts.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: self.mesh_layers.binding(),
+                },
+            ],
+            label: Some("Draw Generation Bind Group"),
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Draw generation"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(self.mesh_count, 1, 1);
+    }
+
+    /// Draws of the meshes of `precision` compacted by `generate`, every slot of the precision is
+    /// drawn without `MULTI_DRAW_INDIRECT_COUNT`
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'static>, precision: VertexPrecision) {
+        let (region, first, count) = match precision {
+            VertexPrecision::Full => (0, 0, self.full_mesh_count),
+            VertexPrecision::Half => (
+                1,
+                self.full_mesh_count,
+                self.mesh_count - self.full_mesh_count,
+            ),
+        };
+        if count == 0 {
+            return;
+        }
+        let offset = first as u64 * std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+        if self.counted {
+            render_pass.multi_draw_indexed_indirect_count(
+                self.draws.inner(),
+                offset,
+                &self.draw_count,
+                region * std::mem::size_of::<u32>() as u64,
+                count,
+            );
+        } else {
+            render_pass.multi_draw_indexed_indirect(self.draws.inner(), offset, count);
+        }
+    }
+}
+
+fn instance_capacity(models: &ModelsBuffer) -> usize {
+    (models.instance_buffer.inner().size() / InstanceBuffer::<ModelInstance>::ITEM_BYTE_SIZE)
+        as usize
+}
+
+fn draws_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, true),
+                storage(5, false),
+                storage(6, false),
+                storage(7, false),
+                storage(8, true),
+                storage(9, true),
+                storage(10, false),
+                storage(11, true),
+            ],
+            label: Some("Draw Generation Bind Group Layout"),
+        })
+}
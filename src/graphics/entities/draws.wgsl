@@ -0,0 +1,234 @@
+// Matches `ModelInstance`, the vectors are kept as arrays to avoid the vec3 alignment
+struct Instance {
+    translation: array<f32, 3>,
+    material_id: u32,
+    // Snorm quaternion, xy then zw
+    rotation: array<u32, 2>,
+    scale: array<f32, 3>,
+    // Animation id in the low 16 bits, parameter block id in the high ones
+    animation_params: u32,
+    // Zone id in the low 16 bits, snorm level of detail fade in the high ones
+    zone_fade: u32,
+    tint: u32,
+    lightmap: array<f32, 4>,
+}
+
+// Matches `VisibleInstance`
+struct VisibleInstance {
+    instance: Instance,
+    // Rows of the affine transform of the previous frame
+    previous_transform: array<vec4f, 3>,
+}
+
+// Matches `wgpu::util::DrawIndexedIndirectArgs`
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+struct Culling {
+    // Inward facing frustum planes, xyz is the normal and w the distance. The fifth is the near one
+    planes: array<vec4f, 6>,
+    depth_sort: u32,
+    // Pixels covered by a unit length at a clip w of 1, vertically
+    pixel_scale: f32,
+    // Last row of the view projection
+    clip_w: vec4f,
+    // Per cull layer, the smallest projected diameter in pixels drawn
+    min_pixels: vec4f,
+}
+
+@group(0) @binding(0)
+var<uniform> culling: Culling;
+@group(0) @binding(1)
+var<storage, read> meshes: array<DrawArgs>;
+// Model space bounding sphere of every mesh, center then radius
+@group(0) @binding(2)
+var<storage, read> spheres: array<vec4f>;
+@group(0) @binding(3)
+var<storage, read> zones_visible: array<u32>;
+@group(0) @binding(4)
+var<storage, read> instances: array<Instance>;
+@group(0) @binding(5)
+var<storage, read_write> visible: array<VisibleInstance>;
+@group(0) @binding(6)
+var<storage, read_write> draws: array<DrawArgs>;
+// One counter per vertex precision
+@group(0) @binding(7)
+var<storage, read_write> draw_count: array<atomic<u32>, 2>;
+// The instances of the previous frame, zeroed before the first one
+@group(0) @binding(8)
+var<storage, read> previous: array<Instance>;
+// Per mesh, the counter of its vertex precision and the first draw of that precision
+@group(0) @binding(9)
+var<storage, read> regions: array<vec2u>;
+// Per mesh, the instances left at the start of its column in `visible`
+@group(0) @binding(10)
+var<storage, read_write> visible_counts: array<u32>;
+// Per mesh, the index of its threshold in `culling.min_pixels`
+@group(0) @binding(11)
+var<storage, read> mesh_layers: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Logarithmic slices of the distance to the camera, the visible instances are counting sorted by slice
+const DEPTH_BINS: u32 = 64u;
+// log2 of the far plane distance is about 10
+const BINS_PER_OCTAVE: f32 = 6.0;
+
+// Zero initialized for every workgroup
+var<workgroup> bins: array<atomic<u32>, DEPTH_BINS>;
+var<workgroup> visible_count: u32;
+
+fn quat_to_matrix(q: vec4f) -> mat3x3f {
+    let x2 = q.xyz * 2.0;
+    let xx = q.x * x2.x;
+    let yy = q.y * x2.y;
+    let zz = q.z * x2.z;
+    let xy = q.x * x2.y;
+    let xz = q.x * x2.z;
+    let yz = q.y * x2.z;
+    let wx = q.w * x2.x;
+    let wy = q.w * x2.y;
+    let wz = q.w * x2.z;
+    return mat3x3f(
+        vec3f(1.0 - yy - zz, xy + wz, xz - wy),
+        vec3f(xy - wz, 1.0 - xx - zz, yz + wx),
+        vec3f(xz + wy, yz - wx, 1.0 - xx - yy),
+    );
+}
+
+fn translation_of(instance: Instance) -> vec3f {
+    let t = instance.translation;
+    return vec3f(t[0], t[1], t[2]);
+}
+
+fn scale_of(instance: Instance) -> vec3f {
+    let s = instance.scale;
+    return vec3f(s[0], s[1], s[2]);
+}
+
+// Rotation then scale, the columns of the model matrix without the translation
+fn basis_of(instance: Instance) -> mat3x3f {
+    let q = normalize(vec4f(unpack2x16snorm(instance.rotation[0]), unpack2x16snorm(instance.rotation[1])));
+    let rotation = quat_to_matrix(q);
+    let scale = scale_of(instance);
+    return mat3x3f(rotation[0] * scale.x, rotation[1] * scale.y, rotation[2] * scale.z);
+}
+
+// World space bounding sphere of the instance
+fn instance_sphere(instance: Instance, sphere: vec4f) -> vec4f {
+    let center = translation_of(instance) + basis_of(instance) * sphere.xyz;
+    let scale = abs(scale_of(instance));
+    return vec4f(center, sphere.w * max(scale.x, max(scale.y, scale.z)));
+}
+
+// Small object culling, clutter is dropped once it covers only a few pixels
+fn is_large_enough(sphere: vec4f, layer: u32) -> bool {
+    let min_pixels = culling.min_pixels[layer];
+    if min_pixels <= 0.0 {
+        return true;
+    }
+    // The camera inside the sphere always draws it
+    let w = dot(culling.clip_w, vec4f(sphere.xyz, 1.0));
+    return w <= sphere.w || 2.0 * sphere.w * culling.pixel_scale / w >= min_pixels;
+}
+
+fn is_visible(instance: Instance, sphere: vec4f, layer: u32) -> bool {
+    if zones_visible[instance.zone_fade & 0xffffu] == 0u || !is_large_enough(sphere, layer) {
+        return false;
+    }
+    for (var i = 0u; i < 6u; i++) {
+        let plane = culling.planes[i];
+        if dot(plane.xyz, sphere.xyz) + plane.w < -sphere.w {
+            return false;
+        }
+    }
+    return true;
+}
+
+fn previous_rows(instance: Instance, index: u32) -> array<vec4f, 3> {
+    var previous_instance = previous[index];
+    // Rotations are unit quaternions, the instance was not drawn yet
+    if previous_instance.rotation[0] == 0u && previous_instance.rotation[1] == 0u {
+        previous_instance = instance;
+    }
+    let b = basis_of(previous_instance);
+    let t = translation_of(previous_instance);
+    return array<vec4f, 3>(
+        vec4f(b[0].x, b[1].x, b[2].x, t.x),
+        vec4f(b[0].y, b[1].y, b[2].y, t.y),
+        vec4f(b[0].z, b[1].z, b[2].z, t.z),
+    );
+}
+
+// Every instance is in the first bin without sorting
+fn depth_bin(sphere: vec4f) -> u32 {
+    if culling.depth_sort == 0u {
+        return 0u;
+    }
+    let near = culling.planes[4];
+    let depth = max(dot(near.xyz, sphere.xyz) + near.w, 0.0);
+    return min(u32(log2(1.0 + depth) * BINS_PER_OCTAVE), DEPTH_BINS - 1u);
+}
+
+// One workgroup per mesh, its visible instances are compacted at the start of its column
+//
+// The first walk counts the instances of each depth bin, the second one writes them at the offset
+// of their bin, nearest first
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn cs_main(
+    @builtin(workgroup_id) workgroup: vec3u,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let mesh = meshes[workgroup.x];
+    let sphere = spheres[workgroup.x];
+    let layer = mesh_layers[workgroup.x];
+
+    for (var i = local; i < mesh.instance_count; i += WORKGROUP_SIZE) {
+        let instance = instances[mesh.first_instance + i];
+        let bounds = instance_sphere(instance, sphere);
+        if is_visible(instance, bounds, layer) {
+            atomicAdd(&bins[depth_bin(bounds)], 1u);
+        }
+    }
+    workgroupBarrier();
+
+    if local == 0u {
+        var offset = 0u;
+        for (var bin = 0u; bin < DEPTH_BINS; bin++) {
+            let size = atomicLoad(&bins[bin]);
+            atomicStore(&bins[bin], offset);
+            offset += size;
+        }
+        visible_count = offset;
+    }
+    workgroupBarrier();
+
+    for (var i = local; i < mesh.instance_count; i += WORKGROUP_SIZE) {
+        let instance = instances[mesh.first_instance + i];
+        let bounds = instance_sphere(instance, sphere);
+        if is_visible(instance, bounds, layer) {
+            let slot = mesh.first_instance + atomicAdd(&bins[depth_bin(bounds)], 1u);
+            visible[slot] = VisibleInstance(instance, previous_rows(instance, mesh.first_instance + i));
+        }
+    }
+
+    let count = workgroupUniformLoad(&visible_count);
+    if local == 0u {
+        visible_counts[workgroup.x] = count;
+    }
+    if local == 0u && count > 0u && mesh.index_count > 0u {
+        let region = regions[workgroup.x];
+        let slot = region.y + atomicAdd(&draw_count[region.x], 1u);
+        draws[slot] = DrawArgs(
+            mesh.index_count,
+            count,
+            mesh.first_index,
+            mesh.base_vertex,
+            mesh.first_instance,
+        );
+    }
+}
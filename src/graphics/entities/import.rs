@@ -0,0 +1,650 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Deserializer};
+use tobj::Mesh;
+
+use crate::ASSETS;
+
+use super::{
+    lightmap::ao::AoBake,
+    model::{ImportOptions, VertexPrecision},
+};
+
+/// Up axis of the authoring tool, the engine is Y up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+/// Length unit of the authoring tool, the engine works in meters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Units {
+    #[default]
+    Meters,
+    Centimeters,
+    Millimeters,
+    Inches,
+    Feet,
+}
+
+impl Units {
+    pub fn meters(self) -> f32 {
+        match self {
+            Self::Meters => 1.0,
+            Self::Centimeters => 0.01,
+            Self::Millimeters => 0.001,
+            Self::Inches => 0.0254,
+            Self::Feet => 0.3048,
+        }
+    }
+}
+
+/// Sidecar of a model, `<model>.import.ron` next to the obj in the models folder
+pub fn sidecar_path(model_name: &str) -> PathBuf {
+    ASSETS
+        .root()
+        .join("models")
+        .join(format!("{model_name}.import.ron"))
+}
+
+/// Defaults of every model of the folder, overridden field by field by their own sidecar
+pub fn folder_sidecar_path() -> PathBuf {
+    ASSETS.root().join("models").join("import.ron")
+}
+
+/// Processing of a texture before it enters the atlas, for legacy assets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureImportOptions {
+    /// Multiplies the color by the alpha, for the textures blended as premultiplied
+    pub premultiply_alpha: bool,
+    /// Pixels of this srgb color become transparent
+    pub color_key: Option<[u8; 3]>,
+    /// Largest difference per channel still matching the color key
+    pub color_key_tolerance: u8,
+    /// For the textures authored with the origin at the bottom
+    pub flip_y: bool,
+    /// Scales each side up to the next power of two
+    pub power_of_two: bool,
+    /// Splits the texture into the frames of an animation, see [`super::sprites`]
+    pub sprite_sheet: Option<SpriteSheet>,
+}
+
+/// Grid of animation frames, read row by row from the top left
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "(u32, u32, f32)")]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    /// Frames per second
+    pub fps: f32,
+}
+
+/// Written as a tuple of the columns, rows and frames per second
+impl TryFrom<(u32, u32, f32)> for SpriteSheet {
+    type Error = String;
+
+    fn try_from((columns, rows, fps): (u32, u32, f32)) -> Result<Self, String> {
+        if columns == 0 || rows == 0 || fps <= 0.0 {
+            return Err(format!(
+                "({columns}, {rows}, {fps}) has no frames or no frame rate"
+            ));
+        }
+        Ok(Self { columns, rows, fps })
+    }
+}
+
+impl SpriteSheet {
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+}
+
+/// Sidecar of a texture, `<texture>.import.ron` in the materials folder next to the mtl files
+/// referencing it, every file of the textures folder must be an image
+pub fn texture_sidecar_path(texture_name: &str) -> PathBuf {
+    ASSETS
+        .root()
+        .join("materials")
+        .join(format!("{texture_name}.import.ron"))
+}
+
+/// Options of the sidecar of the texture, the defaults without one
+///
+/// ```ron
+/// (
+///     premultiply_alpha: true,
+///     color_key: Some((255, 0, 255)),
+///     color_key_tolerance: 8,
+///     flip_y: false,
+///     power_of_two: true,
+///     // Columns, rows and frames per second
+///     sprite_sheet: Some((4, 2, 12)),
+/// )
+/// ```
+pub fn load_texture_sidecar(texture_name: &str) -> Result<TextureImportOptions, String> {
+    let path = texture_sidecar_path(texture_name);
+    let src = match std::fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(TextureImportOptions::default())
+        }
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+    };
+    parse_texture_options(&src).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn parse_texture_options(src: &str) -> Result<TextureImportOptions, String> {
+    ron::from_str(src).map_err(|e| e.to_string())
+}
+
+/// Applies the options in order: color key, premultiplication, flip then resize, so that the
+/// filtering of the resize does not bleed the keyed color
+///
+/// A sprite sheet is split later, when its frames enter the atlas
+pub fn process_texture(image: &DynamicImage, options: &TextureImportOptions) -> DynamicImage {
+    if *options
+        == (TextureImportOptions {
+            sprite_sheet: options.sprite_sheet,
+            ..Default::default()
+        })
+    {
+        return image.clone();
+    }
+    let mut rgba: RgbaImage = image.to_rgba8();
+    if let Some(key) = options.color_key {
+        let tolerance = options.color_key_tolerance;
+        for pixel in rgba.pixels_mut() {
+            let matches = (0..3).all(|c| pixel[c].abs_diff(key[c]) <= tolerance);
+            if matches {
+                pixel.0 = [0; 4];
+            }
+        }
+    }
+    if options.premultiply_alpha {
+        for pixel in rgba.pixels_mut() {
+            let alpha = pixel[3] as u16;
+            for c in 0..3 {
+                pixel[c] = ((pixel[c] as u16 * alpha + 127) / 255) as u8;
+            }
+        }
+    }
+    if options.flip_y {
+        image::imageops::flip_vertical_in_place(&mut rgba);
+    }
+    if options.power_of_two {
+        let (width, height) = rgba.dimensions();
+        let size = (width.next_power_of_two(), height.next_power_of_two());
+        if size != (width, height) {
+            rgba = image::imageops::resize(&rgba, size.0, size.1, FilterType::Triangle);
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Vertex colors painted from the editor, `<model>.colors` next to the obj in the models folder
+pub fn colors_path(model_name: &str) -> PathBuf {
+    ASSETS
+        .root()
+        .join("models")
+        .join(format!("{model_name}.colors"))
+}
+
+/// Replaces the vertex colors of the meshes by the painted ones, if the model has any
+///
+/// Painted over the processed meshes, the colors are dropped when the import options change
+/// the vertex count
+pub fn load_vertex_colors(model_name: &str, meshes: &mut [Mesh]) -> Result<(), String> {
+    let path = colors_path(model_name);
+    let src = match std::fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+    };
+    let lines: Vec<_> = src.lines().collect();
+    if lines.len() != meshes.len() {
+        return Err(format!(
+            "{} meshes painted, the model has {}",
+            lines.len(),
+            meshes.len()
+        ));
+    }
+    for (i, (mesh, line)) in meshes.iter_mut().zip(lines).enumerate() {
+        let colors = line
+            .split_whitespace()
+            .map(|hex| {
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 6)
+                    .ok_or(format!("Invalid color {hex:?} in mesh {i}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.len() != mesh.positions.len() / 3 {
+            return Err(format!(
+                "Vertex count of mesh {i} changed since the painting"
+            ));
+        }
+        mesh.vertex_color = colors
+            .iter()
+            .flat_map(|rgb| [16, 8, 0].map(|shift| ((rgb >> shift) & 0xff) as f32 / 255.0))
+            .collect();
+    }
+    Ok(())
+}
+
+/// One line per mesh of hex colors, one per vertex
+pub fn save_vertex_colors(model_name: &str, meshes: &[Vec<[u8; 4]>]) -> Result<(), String> {
+    let mut src = String::new();
+    for colors in meshes {
+        let line: Vec<_> = colors
+            .iter()
+            .map(|[r, g, b, _]| format!("{r:02x}{g:02x}{b:02x}"))
+            .collect();
+        src.push_str(&line.join(" "));
+        src.push('\n');
+    }
+    let path = colors_path(model_name);
+    std::fs::write(&path, src).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Import options of the sidecar of the model over the folder ones, the defaults without any
+pub fn load_sidecar(model_name: &str) -> Result<ImportOptions, String> {
+    let folder = read_options(&folder_sidecar_path(), ImportOptions::default())?;
+    read_options(&sidecar_path(model_name), folder)
+}
+
+fn read_options(path: &Path, base: ImportOptions) -> Result<ImportOptions, String> {
+    match std::fs::read_to_string(path) {
+        Ok(src) => parse_import_options(&src, base).map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(base),
+        Err(e) => Err(format!("Failed to read {}: {e}", path.display())),
+    }
+}
+
+/// Ron struct of the import options, missing fields keep their value in `base`
+///
+/// ```ron
+/// (
+///     units: Centimeters,
+///     scale: 2.0,
+///     up_axis: Z,
+///     generate_normals: true,
+///     generate_tangents: true,
+///     merge_meshes: true,
+///     dedup_vertices: true,
+///     precision: Half,
+///     ambient_occlusion: Some((samples: 32, distance: 0.5)),
+///     default_material: Some((diffuse: (0.8, 0.8, 0.8))),
+/// )
+/// ```
+pub fn parse_import_options(src: &str, base: ImportOptions) -> Result<ImportOptions, String> {
+    let sidecar: ImportSidecar = ron::from_str(src).map_err(|e| e.to_string())?;
+    if sidecar.scale.is_some_and(|scale| scale <= 0.0) {
+        return Err(String::from(
+            "Invalid scale: must be positive, mirroring flips the faces",
+        ));
+    }
+    Ok(ImportOptions {
+        units: sidecar.units.unwrap_or(base.units),
+        scale: sidecar.scale.unwrap_or(base.scale),
+        up_axis: sidecar.up_axis.unwrap_or(base.up_axis),
+        generate_normals: sidecar.generate_normals.unwrap_or(base.generate_normals),
+        generate_tangents: sidecar.generate_tangents.unwrap_or(base.generate_tangents),
+        merge_meshes: sidecar.merge_meshes.unwrap_or(base.merge_meshes),
+        dedup_vertices: sidecar.dedup_vertices.unwrap_or(base.dedup_vertices),
+        default_material: sidecar
+            .default_material
+            .map_or(base.default_material, |material| {
+                material.map(|material| material.diffuse)
+            }),
+        ambient_occlusion: sidecar.ambient_occlusion.unwrap_or(base.ambient_occlusion),
+        precision: sidecar.precision.unwrap_or(base.precision),
+    })
+}
+
+/// Fields given by a sidecar, `None` for the missing ones
+#[derive(Deserialize)]
+#[serde(rename = "ImportOptions", deny_unknown_fields)]
+struct ImportSidecar {
+    #[serde(default, deserialize_with = "given")]
+    units: Option<Units>,
+    #[serde(default, deserialize_with = "given")]
+    scale: Option<f32>,
+    #[serde(default, deserialize_with = "given")]
+    up_axis: Option<UpAxis>,
+    #[serde(default, deserialize_with = "given")]
+    generate_normals: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    generate_tangents: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    merge_meshes: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    dedup_vertices: Option<bool>,
+    #[serde(default, deserialize_with = "given")]
+    precision: Option<VertexPrecision>,
+    #[serde(default, deserialize_with = "given")]
+    ambient_occlusion: Option<Option<AoBake>>,
+    #[serde(default, deserialize_with = "given")]
+    default_material: Option<Option<DefaultMaterial>>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DefaultMaterial {
+    diffuse: [f32; 3],
+}
+
+impl Default for DefaultMaterial {
+    fn default() -> Self {
+        Self { diffuse: [1.0; 3] }
+    }
+}
+
+/// Tells a field set to `None` apart from a missing one, which is left to `#[serde(default)]`
+fn given<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Scales and rotates the positions and normals into the engine space, the handedness is kept
+pub fn convert_space(meshes: &mut [Mesh], up_axis: UpAxis, scale: f32) {
+    if up_axis == UpAxis::Y && scale == 1.0 {
+        return;
+    }
+    let convert = |v: &mut [f32]| {
+        if up_axis == UpAxis::Z {
+            let [x, y, z] = [v[0], v[1], v[2]];
+            v.copy_from_slice(&[x, z, -y]);
+        }
+    };
+    for mesh in meshes {
+        for position in mesh.positions.chunks_exact_mut(3) {
+            convert(position);
+            position.iter_mut().for_each(|c| *c *= scale);
+        }
+        mesh.normals.chunks_exact_mut(3).for_each(convert);
+    }
+}
+
+/// Smooth normals weighted by the area of the faces, replacing the exported ones
+pub fn generate_normals(mesh: &mut Mesh) {
+    let position = |i: u32| Vector3::from_column_slice(&mesh.positions[i as usize * 3..][..3]);
+    let mut normals = vec![Vector3::zeros(); mesh.positions.len() / 3];
+    for face in mesh.indices.chunks_exact(3) {
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        // Not normalized, the length is twice the area of the face
+        let normal = (b - a).cross(&(c - a));
+        for i in face {
+            normals[*i as usize] += normal;
+        }
+    }
+    mesh.normals = normals
+        .into_iter()
+        .flat_map(|n| <[f32; 3]>::from(n.try_normalize(1e-12).unwrap_or_else(Vector3::y)))
+        .collect();
+}
+
+/// Tangents along the u axis of the texture coordinates, four per vertex: the direction then the
+/// sign of the bitangent. Summed over the faces, then made orthogonal to the normals
+///
+/// Empty when the mesh has no normals or no texture coordinates
+pub fn generate_tangents(mesh: &Mesh) -> Vec<f32> {
+    let vertex_count = mesh.positions.len() / 3;
+    if mesh.normals.len() != vertex_count * 3 || mesh.texcoords.len() != vertex_count * 2 {
+        return Vec::new();
+    }
+    let position = |i: u32| Vector3::from_column_slice(&mesh.positions[i as usize * 3..][..3]);
+    let uv = |i: u32| Vector2::from_column_slice(&mesh.texcoords[i as usize * 2..][..2]);
+    let mut tangents = vec![Vector3::zeros(); vertex_count];
+    let mut bitangents = vec![Vector3::zeros(); vertex_count];
+    for face in mesh.indices.chunks_exact(3) {
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        let (uv_b, uv_c) = (uv(face[1]) - uv(face[0]), uv(face[2]) - uv(face[0]));
+        let det = uv_b.x * uv_c.y - uv_c.x * uv_b.y;
+        // The texture is not mapped on the face
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let (edge_b, edge_c) = (b - a, c - a);
+        let tangent = (edge_b * uv_c.y - edge_c * uv_b.y) / det;
+        let bitangent = (edge_c * uv_b.x - edge_b * uv_c.x) / det;
+        for i in face {
+            tangents[*i as usize] += tangent;
+            bitangents[*i as usize] += bitangent;
+        }
+    }
+    (0..vertex_count)
+        .flat_map(|i| {
+            let normal = Vector3::from_column_slice(&mesh.normals[i * 3..][..3]);
+            let tangent = (tangents[i] - normal * normal.dot(&tangents[i]))
+                .try_normalize(1e-12)
+                .or_else(|| normal.cross(&Vector3::x()).try_normalize(1e-6))
+                .unwrap_or_else(Vector3::z);
+            let sign = match normal.cross(&tangent).dot(&bitangents[i]) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            [tangent.x, tangent.y, tangent.z, sign]
+        })
+        .collect()
+}
+
+/// Concatenates the meshes sharing a material, in the order of their first mesh
+///
+/// The merged meshes stay under the u16 index range, a material with more vertices keeps several
+pub fn merge_meshes(meshes: Vec<Mesh>) -> Vec<Mesh> {
+    let mut merged: Vec<Mesh> = Vec::new();
+    for mesh in meshes {
+        let vertex_count = mesh.positions.len() / 3;
+        let target = merged.iter_mut().rev().find(|merged| {
+            merged.material_id == mesh.material_id
+                && merged.positions.len() / 3 + vertex_count <= u16::MAX as usize + 1
+        });
+        let Some(target) = target else {
+            merged.push(mesh);
+            continue;
+        };
+
+        let base = target.positions.len() / 3;
+        append(&mut target.normals, &mesh.normals, base, vertex_count, 3);
+        append(
+            &mut target.texcoords,
+            &mesh.texcoords,
+            base,
+            vertex_count,
+            2,
+        );
+        target.positions.extend(&mesh.positions);
+        let base = base as u32;
+        target.indices.extend(mesh.indices.iter().map(|i| i + base));
+    }
+    merged
+}
+
+/// Appends the attribute of `count` vertices after `base` ones, zeroed on the side missing it
+fn append(target: &mut Vec<f32>, values: &[f32], base: usize, count: usize, size: usize) {
+    if target.is_empty() && values.is_empty() {
+        return;
+    }
+    target.resize(base * size, 0.0);
+    match values.is_empty() {
+        true => target.resize((base + count) * size, 0.0),
+        false => target.extend(values),
+    }
+}
+
+/// Shares the vertices with the same attributes, compared bit for bit, and drops the unused ones
+pub fn dedup_vertices(mesh: &mut Mesh) {
+    let vertex_count = mesh.positions.len() / 3;
+    let attributes = |i: usize| {
+        let normal = mesh.normals.get(i * 3..i * 3 + 3).unwrap_or(&[]);
+        let texcoord = mesh.texcoords.get(i * 2..i * 2 + 2).unwrap_or(&[]);
+        [&mesh.positions[i * 3..i * 3 + 3], normal, texcoord]
+            .concat()
+            .into_iter()
+            .map(f32::to_bits)
+            .collect::<Vec<_>>()
+    };
+
+    let mut unique = HashMap::new();
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut kept = Vec::new();
+    for index in &mesh.indices {
+        let i = *index as usize;
+        if remap[i] == u32::MAX {
+            remap[i] = *unique.entry(attributes(i)).or_insert_with(|| {
+                kept.push(i);
+                kept.len() as u32 - 1
+            });
+        }
+    }
+    if kept.len() == vertex_count {
+        return;
+    }
+
+    let gather = |values: &[f32], size: usize| match values.is_empty() {
+        true => Vec::new(),
+        false => kept
+            .iter()
+            .flat_map(|i| &values[i * size..i * size + size])
+            .copied()
+            .collect(),
+    };
+    mesh.positions = gather(&mesh.positions, 3);
+    mesh.normals = gather(&mesh.normals, 3);
+    mesh.texcoords = gather(&mesh.texcoords, 2);
+    for index in &mut mesh.indices {
+        *index = remap[*index as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_overrides_the_given_fields() {
+        let folder = parse_import_options(
+            "(units: Centimeters, dedup_vertices: true)",
+            ImportOptions::default(),
+        )
+        .unwrap();
+        let options = parse_import_options(
+            "
+            // Exported Z up
+            ImportOptions(
+                scale: 2,
+                up_axis: Z,
+                generate_tangents: true,
+                precision: Half,
+                ambient_occlusion: Some((samples: 8)),
+                default_material: Some((diffuse: (0.8, 0.8, 0.8))),
+            )
+            ",
+            folder,
+        )
+        .unwrap();
+        assert_eq!(options.units, Units::Centimeters);
+        assert!(options.dedup_vertices);
+        assert_eq!(options.scale, 2.0);
+        assert_eq!(options.up_axis, UpAxis::Z);
+        assert!(options.generate_tangents);
+        assert!(!options.generate_normals);
+        assert_eq!(options.precision, VertexPrecision::Half);
+        assert_eq!(
+            options.ambient_occlusion,
+            Some(AoBake {
+                samples: 8,
+                ..Default::default()
+            })
+        );
+        assert_eq!(options.default_material, Some([0.8; 3]));
+    }
+
+    #[test]
+    fn sidecar_clears_a_folder_option_with_none() {
+        let folder = ImportOptions {
+            ambient_occlusion: Some(AoBake::default()),
+            default_material: Some([0.5; 3]),
+            ..Default::default()
+        };
+        let options = parse_import_options("(ambient_occlusion: None)", folder).unwrap();
+        assert_eq!(options.ambient_occlusion, None);
+        assert_eq!(options.default_material, Some([0.5; 3]));
+    }
+
+    #[test]
+    fn invalid_sidecars_are_rejected() {
+        let parse = |src| parse_import_options(src, ImportOptions::default());
+        assert!(parse("(scale: 0.0)").is_err());
+        assert!(parse("(scale: -1.0)").is_err());
+        assert!(parse("(unknown: true)").is_err());
+        assert!(parse("(units: Furlongs)").is_err());
+        assert!(parse("(ambient_occlusion: Some((radius: 1.0)))").is_err());
+        assert!(parse("(generate_normals: true").is_err());
+    }
+
+    #[test]
+    fn texture_sidecar() {
+        let options = parse_texture_options(
+            r#"(
+                premultiply_alpha: true,
+                color_key: Some((255, 0, 255)),
+                // Columns, rows and frames per second
+                sprite_sheet: Some((4, 2, 12)),
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            TextureImportOptions {
+                premultiply_alpha: true,
+                color_key: Some([255, 0, 255]),
+                sprite_sheet: Some(SpriteSheet {
+                    columns: 4,
+                    rows: 2,
+                    fps: 12.0,
+                }),
+                ..Default::default()
+            }
+        );
+        assert!(parse_texture_options("(color_key: Some((256, 0, 0)))").is_err());
+        assert!(parse_texture_options("(sprite_sheet: Some((0, 2, 12)))").is_err());
+        assert!(parse_texture_options("(sprite_sheet: Some((4, 2, 0)))").is_err());
+    }
+
+    /// Quad facing +z, with the u axis of its texture along `u_axis` on x
+    fn quad(u_axis: f32) -> Mesh {
+        Mesh {
+            positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+            normals: [0.0, 0.0, 1.0].repeat(4),
+            texcoords: vec![0.0, 0.0, u_axis, 0.0, u_axis, 1.0, 0.0, 1.0],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tangents_follow_the_texture() {
+        assert_eq!(
+            generate_tangents(&quad(1.0)),
+            [1.0, 0.0, 0.0, 1.0].repeat(4)
+        );
+        // Mirrored texture, the bitangent flips
+        assert_eq!(
+            generate_tangents(&quad(-1.0)),
+            [-1.0, 0.0, 0.0, -1.0].repeat(4)
+        );
+        let mut untextured = quad(1.0);
+        untextured.texcoords.clear();
+        assert!(generate_tangents(&untextured).is_empty());
+    }
+}
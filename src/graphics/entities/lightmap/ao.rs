@@ -0,0 +1,90 @@
+use nalgebra::{Point3, Vector3};
+use rayon::prelude::*;
+use tobj::Mesh;
+
+use crate::random::Rng;
+
+use super::{
+    bake::cosine_sample,
+    bvh::{Bvh, Triangle},
+};
+
+/// Offset of the rays along the vertex normal, against self intersections
+const RAY_OFFSET: f32 = 1e-3;
+
+/// Hemisphere raycast of every vertex against the model it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AoBake {
+    pub samples: u32,
+    /// Model space distance past which the geometry doesn't occlude
+    pub distance: f32,
+}
+
+impl Default for AoBake {
+    fn default() -> Self {
+        Self {
+            samples: 32,
+            distance: 0.5,
+        }
+    }
+}
+
+/// Ambient light left around each vertex, per mesh then per vertex, in [0, 1]
+///
+/// Every mesh of the model occludes the others. Meshes without normals are left empty
+pub fn bake_vertex_ao(meshes: &[Mesh], bake: &AoBake) -> Vec<Vec<f32>> {
+    let bvh = Bvh::new(
+        meshes
+            .iter()
+            .flat_map(|mesh| {
+                mesh.indices.chunks_exact(3).map(|triangle| {
+                    Triangle::new(triangle.map(|i| position(mesh, i)), Vector3::zeros())
+                })
+            })
+            .collect(),
+    );
+
+    let samples = bake.samples.max(1);
+    let distance = bake.distance.max(1e-6);
+    meshes
+        .iter()
+        .map(|mesh| {
+            if mesh.normals.is_empty() {
+                return Vec::new();
+            }
+            (0..mesh.positions.len() / 3)
+                .into_par_iter()
+                .map(|i| {
+                    let Some(normal) = normal(mesh, i).try_normalize(1e-6) else {
+                        return 1.0;
+                    };
+                    let origin = position(mesh, i as u32) + normal * RAY_OFFSET;
+                    let mut rng = Rng::new(i as u32);
+                    let occlusion: f32 = (0..samples)
+                        .map(|_| {
+                            let direction = cosine_sample(&normal, rng.next_f32(), rng.next_f32());
+                            bvh.intersect(&origin, &direction, distance)
+                                .map_or(0.0, |(_, hit)| 1.0 - hit / distance)
+                        })
+                        .sum();
+                    1.0 - occlusion / samples as f32
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn position(mesh: &Mesh, index: u32) -> Point3<f32> {
+    let i = index as usize * 3;
+    Point3::new(
+        mesh.positions[i],
+        mesh.positions[i + 1],
+        mesh.positions[i + 2],
+    )
+}
+
+fn normal(mesh: &Mesh, index: usize) -> Vector3<f32> {
+    let i = index * 3;
+    Vector3::new(mesh.normals[i], mesh.normals[i + 1], mesh.normals[i + 2])
+}
@@ -0,0 +1,369 @@
+use std::f32::consts::TAU;
+
+use guillotiere::{size2, AtlasAllocator};
+use nalgebra::{Matrix4, Point3, Vector2, Vector3};
+use rayon::prelude::*;
+
+use crate::{
+    graphics::{color::Color3, entities::model::ModelVertex, light::RawLight},
+    random::Rng,
+};
+
+use super::{
+    bvh::{Bvh, Triangle},
+    LightmapImage,
+};
+
+/// Offset of the rays along the surface normal, against self intersections
+const RAY_OFFSET: f32 = 1e-3;
+/// Density halvings tried before leaving the instances that still don't fit out of the atlas
+const PACK_ATTEMPTS: u32 = 8;
+/// Smallest side of the grid cells of [`super::unwrap::unwrap_lightmap_uvs`], in texels
+const MIN_CELL_SIZE: f32 = 2.0;
+/// Border kept around every rect, filled by dilating its texels
+const RECT_PADDING: i32 = 2;
+
+/// Parameters of a lightmap bake
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Width and height of the lightmap atlas
+    pub size: u32,
+    /// Lowered until every instance fits the atlas
+    pub texels_per_unit: f32,
+    /// Hemisphere rays per texel
+    pub samples: u32,
+    /// Bakes the direct light of the scene lights too, they are already shaded in real time
+    /// so by default only their bounce and the sky are
+    pub direct: bool,
+    /// Light of the rays escaping the scene
+    pub sky: Color3,
+    pub sky_intensity: f32,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            size: 1024,
+            texels_per_unit: 8.0,
+            samples: 16,
+            direct: false,
+            sky: Color3::new(0.6, 0.7, 0.9),
+            sky_intensity: 0.3,
+        }
+    }
+}
+
+/// Static instance of a bake
+pub struct BakeInstance {
+    /// Vertices of the mesh, three per triangle
+    pub triangles: Vec<[ModelVertex; 3]>,
+    pub transform: Matrix4<f32>,
+    pub albedo: Color3,
+    /// Instances without lightmap uvs still block and bounce the light of the others
+    pub lightmapped: bool,
+}
+
+pub struct BakedLightmap {
+    pub image: LightmapImage,
+    /// [`crate::graphics::entities::model::ModelInstance::lightmap`] of each instance, zero
+    /// for the ones left out
+    pub rects: Vec<[f32; 4]>,
+}
+
+/// Path traces the light reaching the lightmapped instances, one bounce, on the rayon pool
+///
+/// Blocks for a while, run it off the main thread
+pub fn bake_lightmap(
+    settings: &BakeSettings,
+    instances: &[BakeInstance],
+    lights: &[RawLight],
+) -> BakedLightmap {
+    let size = settings.size.max(1);
+    let bvh = Bvh::new(
+        instances
+            .iter()
+            .flat_map(|instance| {
+                let albedo = Vector3::from(Into::<[f32; 3]>::into(instance.albedo));
+                instance.triangles.iter().map(move |triangle| {
+                    let positions =
+                        triangle.map(|v| instance.transform.transform_point(&v.position.into()));
+                    Triangle::new(positions, albedo)
+                })
+            })
+            .collect(),
+    );
+    let scene = BakeScene {
+        bvh,
+        lights,
+        settings,
+    };
+
+    let rects = pack_rects(settings, instances);
+    let mut texels = vec![Vector3::zeros(); (size * size) as usize];
+    let mut covered = vec![false; texels.len()];
+    let baked: Vec<_> = instances
+        .par_iter()
+        .zip(&rects)
+        .filter_map(|(instance, rect)| Some(bake_instance(&scene, instance, (*rect)?)))
+        .collect();
+    for (index, light) in baked.into_iter().flatten() {
+        texels[index] = light;
+        covered[index] = true;
+    }
+    for _ in 0..RECT_PADDING {
+        dilate(size, &mut texels, &mut covered);
+    }
+
+    BakedLightmap {
+        image: LightmapImage {
+            size,
+            texels: texels.into_iter().map(Into::into).collect(),
+        },
+        rects: rects
+            .into_iter()
+            .map(|rect| {
+                rect.map_or([0.0; 4], |(x, y, side)| {
+                    let [x, y, side] = [x, y, side].map(|v| v as f32 / size as f32);
+                    [x, y, side, side]
+                })
+            })
+            .collect(),
+    }
+}
+
+struct BakeScene<'a> {
+    bvh: Bvh,
+    lights: &'a [RawLight],
+    settings: &'a BakeSettings,
+}
+
+/// Texel origin and side of each lightmapped instance, sized by their world surface
+fn pack_rects(settings: &BakeSettings, instances: &[BakeInstance]) -> Vec<Option<(i32, i32, i32)>> {
+    let size = settings.size.max(1) as i32;
+    let areas: Vec<f32> = instances
+        .iter()
+        .map(|instance| {
+            instance
+                .triangles
+                .iter()
+                .map(|triangle| {
+                    let [a, b, c] =
+                        triangle.map(|v| instance.transform.transform_point(&v.position.into()));
+                    (b - a).cross(&(c - a)).norm() * 0.5
+                })
+                .sum()
+        })
+        .collect();
+
+    let mut density = settings.texels_per_unit;
+    for attempt in 0..PACK_ATTEMPTS {
+        let mut allocator = AtlasAllocator::new(size2(size, size));
+        let mut fits = true;
+        let rects = instances
+            .iter()
+            .zip(&areas)
+            .map(|(instance, area)| {
+                if !instance.lightmapped {
+                    return None;
+                }
+                let cells = (instance.triangles.len().div_ceil(2) as f32).sqrt().ceil();
+                let side = (area.sqrt() * density).max(cells * MIN_CELL_SIZE).ceil() as i32;
+                let side = side.clamp(1, size);
+                let padded = side + RECT_PADDING * 2;
+                let Some(allocation) = allocator.allocate(size2(padded, padded)) else {
+                    fits = false;
+                    return None;
+                };
+                let min = allocation.rectangle.min;
+                Some((min.x + RECT_PADDING, min.y + RECT_PADDING, side))
+            })
+            .collect();
+        if fits || attempt + 1 == PACK_ATTEMPTS {
+            if !fits {
+                eprintln!("Lightmap atlas too small, some instances were left out");
+            }
+            return rects;
+        }
+        density *= 0.5;
+    }
+    unreachable!()
+}
+
+/// Light reaching every texel covered by the triangles of the instance
+fn bake_instance(
+    scene: &BakeScene,
+    instance: &BakeInstance,
+    (x, y, side): (i32, i32, i32),
+) -> Vec<(usize, Vector3<f32>)> {
+    let size = scene.settings.size.max(1) as i32;
+    let mut out = Vec::new();
+    for triangle in &instance.triangles {
+        let uvs = triangle.map(|v| Vector2::from(v.lightmap_uv) * side as f32);
+        let positions = triangle.map(|v| instance.transform.transform_point(&v.position.into()));
+        let normals = triangle.map(|v| instance.transform.transform_vector(&v.normal.into()));
+        let geometric = Triangle::new(positions, Vector3::zeros()).normal;
+
+        let min = uvs.iter().fold(uvs[0], |min, uv| min.inf(uv));
+        let max = uvs.iter().fold(uvs[0], |max, uv| max.sup(uv));
+        for ty in (min.y.floor() as i32).max(0)..(max.y.ceil() as i32).min(side) {
+            for tx in (min.x.floor() as i32).max(0)..(max.x.ceil() as i32).min(side) {
+                let center = Vector2::new(tx as f32 + 0.5, ty as f32 + 0.5);
+                let Some([a, b, c]) = barycentric(&uvs, &center) else {
+                    continue;
+                };
+                let position = Point3::from(
+                    positions[0].coords * a + positions[1].coords * b + positions[2].coords * c,
+                );
+                let normal = (normals[0] * a + normals[1] * b + normals[2] * c)
+                    .try_normalize(1e-6)
+                    .unwrap_or(geometric);
+                let index = ((y + ty) * size + x + tx) as usize;
+                out.push((index, texel_light(scene, position, normal, index as u32)));
+            }
+        }
+    }
+    out
+}
+
+/// Barycentric coordinates of the point, `None` outside of the triangle
+fn barycentric(triangle: &[Vector2<f32>; 3], point: &Vector2<f32>) -> Option<[f32; 3]> {
+    let [a, b, c] = triangle;
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let det = ab.x * ac.y - ab.y * ac.x;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let v = (ap.x * ac.y - ap.y * ac.x) / det;
+    let w = (ab.x * ap.y - ab.y * ap.x) / det;
+    let u = 1.0 - v - w;
+    (u >= 0.0 && v >= 0.0 && w >= 0.0).then_some([u, v, w])
+}
+
+fn texel_light(
+    scene: &BakeScene,
+    position: Point3<f32>,
+    normal: Vector3<f32>,
+    seed: u32,
+) -> Vector3<f32> {
+    let settings = scene.settings;
+    let mut light = Vector3::zeros();
+    if settings.direct {
+        light += direct_light(scene, &position, &normal);
+    }
+
+    let sky = Vector3::from(Into::<[f32; 3]>::into(settings.sky)) * settings.sky_intensity;
+    let origin = position + normal * RAY_OFFSET;
+    let mut rng = Rng::new(seed);
+    let samples = settings.samples.max(1);
+    let mut bounced = Vector3::zeros();
+    for _ in 0..samples {
+        let direction = cosine_sample(&normal, rng.next_f32(), rng.next_f32());
+        bounced += match scene.bvh.intersect(&origin, &direction, f32::INFINITY) {
+            Some((hit, distance)) => {
+                let point = origin + direction * distance;
+                // Light leaving the side of the hit facing the ray
+                let hit_normal = if hit.normal.dot(&direction) > 0.0 {
+                    -hit.normal
+                } else {
+                    hit.normal
+                };
+                hit.albedo
+                    .component_mul(&direct_light(scene, &point, &hit_normal))
+            }
+            None => sky,
+        };
+    }
+    light + bounced / samples as f32
+}
+
+/// Light of the scene lights reaching the surface, with the attenuation of the entities shader
+fn direct_light(scene: &BakeScene, position: &Point3<f32>, normal: &Vector3<f32>) -> Vector3<f32> {
+    let origin = position + normal * RAY_OFFSET;
+    let mut light = Vector3::zeros();
+    for raw in scene.lights {
+        let color = Vector3::from(raw.color) * raw.intensity();
+        let (direction, distance, factor) = match raw.light_type {
+            1 | 3 => {
+                let to_light = Point3::from(raw.position) - position;
+                let distance = to_light.norm();
+                let Some(direction) = to_light.try_normalize(1e-6) else {
+                    continue;
+                };
+                let mut factor = 1.0 / (1.0 + 0.09 * distance + 0.032 * distance * distance);
+                if raw.light_type == 3 {
+                    let spot = direction.dot(&Vector3::from(raw.direction));
+                    if spot <= raw.cut_off {
+                        continue;
+                    }
+                    factor *= smoothstep(raw.cut_off, raw.cut_off + 0.1, spot);
+                }
+                (direction, distance, factor)
+            }
+            2 => {
+                let Some(direction) = (-Vector3::from(raw.direction)).try_normalize(1e-6) else {
+                    continue;
+                };
+                (direction, f32::INFINITY, 1.0)
+            }
+            _ => continue,
+        };
+        let cosine = normal.dot(&direction);
+        if cosine <= 0.0 || scene.bvh.occluded(&origin, &direction, distance) {
+            continue;
+        }
+        light += color * cosine * factor;
+    }
+    light
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Direction around the normal, more likely the closer it is to the normal
+pub(super) fn cosine_sample(normal: &Vector3<f32>, u: f32, v: f32) -> Vector3<f32> {
+    let tangent = if normal.x.abs() > 0.9 {
+        Vector3::y()
+    } else {
+        Vector3::x()
+    };
+    let tangent = normal.cross(&tangent).normalize();
+    let bitangent = normal.cross(&tangent);
+    let radius = u.sqrt();
+    let angle = TAU * v;
+    let (x, y) = (radius * angle.cos(), radius * angle.sin());
+    (tangent * x + bitangent * y + normal * (1.0 - u).max(0.0).sqrt()).normalize()
+}
+
+/// Fills the uncovered texels next to covered ones with the average of their neighbours
+fn dilate(size: u32, texels: &mut [Vector3<f32>], covered: &mut [bool]) {
+    let size = size as i32;
+    let source = texels.to_vec();
+    let was_covered = covered.to_vec();
+    for y in 0..size {
+        for x in 0..size {
+            let index = (y * size + x) as usize;
+            if was_covered[index] {
+                continue;
+            }
+            let mut sum = Vector3::zeros();
+            let mut count = 0;
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= size || ny >= size {
+                    continue;
+                }
+                let neighbour = (ny * size + nx) as usize;
+                if was_covered[neighbour] {
+                    sum += source[neighbour];
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                texels[index] = sum / count as f32;
+                covered[index] = true;
+            }
+        }
+    }
+}
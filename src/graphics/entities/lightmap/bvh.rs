@@ -0,0 +1,186 @@
+use nalgebra::{Point3, Vector3};
+
+use crate::graphics::bounds::Aabb;
+
+/// Triangles per leaf
+const LEAF_SIZE: usize = 4;
+
+/// World space triangle the bake rays can hit
+pub struct Triangle {
+    pub positions: [Point3<f32>; 3],
+    /// Geometric, facing the side of the counter clockwise winding
+    pub normal: Vector3<f32>,
+    pub albedo: Vector3<f32>,
+}
+
+impl Triangle {
+    pub fn new(positions: [Point3<f32>; 3], albedo: Vector3<f32>) -> Self {
+        let [a, b, c] = positions;
+        let normal = (b - a).cross(&(c - a)).try_normalize(1e-12);
+        Self {
+            positions,
+            normal: normal.unwrap_or(Vector3::y()),
+            albedo,
+        }
+    }
+
+    fn centroid(&self) -> Point3<f32> {
+        let [a, b, c] = self.positions;
+        Point3::from((a.coords + b.coords + c.coords) / 3.0)
+    }
+
+    /// Möller–Trumbore, distance along the ray of a hit on either side
+    fn intersect(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<f32> {
+        let [a, b, c] = self.positions;
+        let (ab, ac) = (b - a, c - a);
+        let p = direction.cross(&ac);
+        let det = ab.dot(&p);
+        if det.abs() < 1e-9 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let t = origin - a;
+        let u = t.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t.cross(&ab);
+        let v = direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        Some(ac.dot(&q) * inv_det).filter(|distance| *distance > 0.0)
+    }
+}
+
+struct Node {
+    bounds: Aabb,
+    /// First triangle of a leaf
+    first: u32,
+    /// Triangles of a leaf, 0 for inner nodes whose left child follows them
+    count: u32,
+    /// Right child of inner nodes
+    right: u32,
+}
+
+/// Bounding volume hierarchy over the triangles of a bake, split at the median of the longest axis
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    pub fn new(mut triangles: Vec<Triangle>) -> Self {
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            build(&mut nodes, &mut triangles, 0);
+        }
+        Self { nodes, triangles }
+    }
+
+    /// Closest triangle hit before `max_distance` and its distance
+    pub fn intersect(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<(&Triangle, f32)> {
+        self.traverse(origin, direction, max_distance, false)
+    }
+
+    /// Whether anything is hit before `max_distance`, cheaper than [`Bvh::intersect`]
+    pub fn occluded(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        max_distance: f32,
+    ) -> bool {
+        self.traverse(origin, direction, max_distance, true)
+            .is_some()
+    }
+
+    fn traverse(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        max_distance: f32,
+        any_hit: bool,
+    ) -> Option<(&Triangle, f32)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_direction = direction.map(|d| 1.0 / d);
+        let mut closest: Option<(&Triangle, f32)> = None;
+        let mut stack = vec![0u32];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index as usize];
+            let limit = closest.map_or(max_distance, |(_, distance)| distance);
+            if !hits_bounds(&node.bounds, origin, &inv_direction, limit) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.right);
+                stack.push(index + 1);
+                continue;
+            }
+            let first = node.first as usize;
+            for triangle in &self.triangles[first..first + node.count as usize] {
+                let Some(distance) = triangle.intersect(origin, direction) else {
+                    continue;
+                };
+                if distance < closest.map_or(max_distance, |(_, closest)| closest) {
+                    closest = Some((triangle, distance));
+                    if any_hit {
+                        return closest;
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+fn build(nodes: &mut Vec<Node>, triangles: &mut [Triangle], first: usize) -> u32 {
+    let index = nodes.len();
+    let bounds = Aabb::from_points(triangles.iter().flat_map(|t| t.positions))
+        .expect("Bvh nodes have triangles");
+    nodes.push(Node {
+        bounds,
+        first: first as u32,
+        count: triangles.len() as u32,
+        right: 0,
+    });
+    if triangles.len() <= LEAF_SIZE {
+        return index as u32;
+    }
+
+    let axis = bounds.size().imax();
+    let middle = triangles.len() / 2;
+    triangles.select_nth_unstable_by(middle, |a, b| {
+        a.centroid()[axis].total_cmp(&b.centroid()[axis])
+    });
+    let (left, right) = triangles.split_at_mut(middle);
+    build(nodes, left, first);
+    let right = build(nodes, right, first + middle);
+    nodes[index].count = 0;
+    nodes[index].right = right;
+    index as u32
+}
+
+/// Slab test of the ray against the box, before `max_distance`
+fn hits_bounds(
+    bounds: &Aabb,
+    origin: &Point3<f32>,
+    inv_direction: &Vector3<f32>,
+    max_distance: f32,
+) -> bool {
+    let mut near = 0.0f32;
+    let mut far = max_distance;
+    for axis in 0..3 {
+        let t0 = (bounds.min[axis] - origin[axis]) * inv_direction[axis];
+        let t1 = (bounds.max[axis] - origin[axis]) * inv_direction[axis];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far
+}
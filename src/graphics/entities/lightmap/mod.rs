@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use crate::graphics::{
+    ctx::GraphicsCtx,
+    sampler::SamplerDesc,
+    utils::{HdrPrecision, TextureWrapper},
+};
+
+pub mod ao;
+pub mod bake;
+mod bvh;
+pub mod unwrap;
+
+/// Square atlas of baked light, linear rgb in the units of the shader `Surface::light`
+#[derive(Debug, Clone)]
+pub struct LightmapImage {
+    pub size: u32,
+    /// Row major, `size * size` texels
+    pub texels: Vec<[f32; 3]>,
+}
+
+impl LightmapImage {
+    /// Saved as an exr, keeping the light above 1
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = self.texels.as_flattened().to_vec();
+        image::Rgb32FImage::from_raw(self.size, self.size, data)
+            .ok_or("Lightmap texels don't match its size")?
+            .save(path)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|e| e.to_string())?.into_rgb32f();
+        if image.width() != image.height() {
+            return Err(format!(
+                "Lightmap {} is not square: {}x{}",
+                path.display(),
+                image.width(),
+                image.height()
+            ));
+        }
+        Ok(Self {
+            size: image.width(),
+            texels: image.pixels().map(|pixel| pixel.0).collect(),
+        })
+    }
+}
+
+/// Lightmap sampled by the entities shader, added to the light of instances with a lightmap rect
+///
+/// Bound with the shadows, see [`super::shadows::shadows_bind_group_layout`]
+pub struct Lightmap {
+    pub(super) texture: TextureWrapper,
+    /// Uploaded by `apply_changes`
+    pending: Option<(u32, Vec<f32>)>,
+}
+
+impl Lightmap {
+    /// Black until a baked image is set
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        Self {
+            texture: lightmap_texture(ctx, 1, &[0.0; 4]),
+            pending: None,
+        }
+    }
+
+    pub fn set(&mut self, image: &LightmapImage) {
+        let rgba = image
+            .texels
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 1.0])
+            .collect();
+        self.pending = Some((image.size, rgba));
+    }
+
+    /// Back to black, for scenes without a baked lightmap
+    pub fn clear(&mut self) {
+        self.pending = Some((1, vec![0.0; 4]));
+    }
+
+    /// Returns true if the texture was recreated, the bind group sampling it must be too
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) -> bool {
+        let Some((size, rgba)) = self.pending.take() else {
+            return false;
+        };
+        self.texture = lightmap_texture(ctx, size, &rgba);
+        true
+    }
+}
+
+fn lightmap_texture(ctx: &GraphicsCtx, size: u32, rgba: &[f32]) -> TextureWrapper {
+    TextureWrapper::new_hdr_2d(
+        "Lightmap",
+        ctx,
+        (size, size),
+        rgba,
+        HdrPrecision::Half,
+        SamplerDesc::LINEAR,
+    )
+}
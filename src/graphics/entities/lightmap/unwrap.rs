@@ -0,0 +1,57 @@
+use tobj::Mesh;
+
+/// Fraction of a cell left empty around its triangles, keeps the filtering from bleeding
+const CELL_MARGIN: f32 = 0.1;
+
+/// Gives every triangle of the mesh its own vertices and its own chart of a grid over [0, 1]
+///
+/// Two triangles share a cell, split along its diagonal. Charts don't follow the shape of
+/// their triangle so the texel density varies, but they never overlap. Returns the lightmap
+/// uvs, two per vertex, or `None` when the unshared vertices don't fit the u16 indices
+pub fn unwrap_lightmap_uvs(mesh: &mut Mesh) -> Option<Vec<f32>> {
+    let vertex_count = mesh.indices.len();
+    if vertex_count > u16::MAX as usize + 1 {
+        return None;
+    }
+
+    let unshare = |values: &[f32], components: usize| -> Vec<f32> {
+        if values.is_empty() {
+            return Vec::new();
+        }
+        mesh.indices
+            .iter()
+            .flat_map(|index| {
+                let start = *index as usize * components;
+                values[start..start + components].iter().copied()
+            })
+            .collect()
+    };
+    let positions = unshare(&mesh.positions, 3);
+    let normals = unshare(&mesh.normals, 3);
+    let texcoords = unshare(&mesh.texcoords, 2);
+    let vertex_color = unshare(&mesh.vertex_color, 3);
+    mesh.positions = positions;
+    mesh.normals = normals;
+    mesh.texcoords = texcoords;
+    mesh.vertex_color = vertex_color;
+    mesh.indices = (0..vertex_count as u32).collect();
+
+    let triangles = vertex_count / 3;
+    let cells = (triangles.div_ceil(2) as f32).sqrt().ceil().max(1.0) as usize;
+    let cell_size = 1.0 / cells as f32;
+    let m = CELL_MARGIN;
+    let lower = [(m, m), (1.0 - 2.0 * m, m), (m, 1.0 - 2.0 * m)];
+    let upper = [(1.0 - m, 1.0 - m), (2.0 * m, 1.0 - m), (1.0 - m, 2.0 * m)];
+
+    let mut uvs = Vec::with_capacity(vertex_count * 2);
+    for triangle in 0..triangles {
+        let cell = triangle / 2;
+        let (x, y) = ((cell % cells) as f32, (cell / cells) as f32);
+        let corners = if triangle % 2 == 0 { lower } else { upper };
+        for (u, v) in corners {
+            uvs.push((x + u) * cell_size);
+            uvs.push((y + v) * cell_size);
+        }
+    }
+    Some(uvs)
+}
@@ -0,0 +1,347 @@
+use guillotiere::{size2, AtlasAllocator};
+use nalgebra::{Matrix4, Perspective3, Point3, Vector3};
+
+use crate::graphics::{
+    buffer::{CommonBuffer, StorageBuffer, UniformBuffer, WriteBuffer},
+    camera::{view_proj_bindgroup, RawRelativeView, OPENGL_TO_WGPU_MATRIX},
+    ctx::GraphicsCtx,
+    frame::FrameConstants,
+    light::{light_flags, LightsUniform, RawLight},
+    settings::ShadowSettings,
+};
+
+use super::{
+    draws::DrawGenerator,
+    model::{MaterialsBuffer, ModelsBuffer},
+    shadows::{draw_casters, SHADOW_FORMAT},
+};
+
+/// Shadow maps shared by the shadowed point and spot lights of a frame, a point light takes six
+pub const MAX_SHADOW_VIEWS: usize = 12;
+pub const LOCAL_SHADOW_ATLAS_SIZE: u32 = 2048;
+/// Side of the views of the most important light, halved for each following one
+const LOCAL_SHADOW_MAX_TILE: u32 = 1024;
+const LOCAL_SHADOW_MIN_TILE: u32 = 128;
+/// Casters farther than this from a light do not shadow it, its light is faint there
+const LOCAL_SHADOW_FAR: f32 = 50.0;
+const LOCAL_SHADOW_NEAR: f32 = 0.05;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct RawShadowView {
+    view_proj: Matrix4<f32>,
+    /// Uv rect of the view in the atlas, min then size
+    rect: [f32; 4],
+    light_index: u32,
+    _padding: [u32; 3],
+}
+
+crate::wgsl_layout!(RawShadowView as "ShadowView" {
+    view_proj,
+    rect,
+    light_index,
+});
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct RawLocalShadows {
+    views: [RawShadowView; MAX_SHADOW_VIEWS],
+    count: u32,
+    normal_bias: f32,
+    _padding: [f32; 2],
+}
+
+crate::wgsl_layout!(RawLocalShadows as "LocalShadows" {
+    views,
+    count,
+    normal_bias,
+});
+
+struct ShadowView {
+    view: UniformBuffer<Matrix4<f32>>,
+    proj: UniformBuffer<Matrix4<f32>>,
+    /// Casters are drawn relative to the light, see [`RawRelativeView`]
+    relative: UniformBuffer<RawRelativeView>,
+    view_proj: Matrix4<f32>,
+    bind_group: wgpu::BindGroup,
+    /// Pixel rect of the view in the atlas, x, y, width, height
+    viewport: [u32; 4],
+    /// Casters inside the view, culled on the gpu
+    draws: DrawGenerator,
+}
+
+/// Shadow maps of the point and spot lights flagged with [`light_flags::CAST_SHADOWS`], given
+/// each frame to the most important ones, see [`shadow_budget`]
+///
+/// A spotlight is drawn from one view covering its cone, a point light from the six faces of a
+/// cube. The views share one depth atlas, reallocated every frame: the views of a light are
+/// smaller the less important it is, and a light left without room is not shadowed
+pub struct LocalShadows {
+    views: Vec<ShadowView>,
+    pub(super) raw: UniformBuffer<RawLocalShadows>,
+    count: u32,
+    /// Lights shadowed this frame, the most important first
+    shadowed: Vec<u32>,
+    allocator: AtlasAllocator,
+    pub(super) atlas_view: wgpu::TextureView,
+}
+
+impl LocalShadows {
+    pub fn new(ctx: &GraphicsCtx, frame: &FrameConstants, models: &ModelsBuffer) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Local shadows"),
+            size: wgpu::Extent3d {
+                width: LOCAL_SHADOW_ATLAS_SIZE,
+                height: LOCAL_SHADOW_ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let views = (0..MAX_SHADOW_VIEWS)
+            .map(|_| {
+                let view = UniformBuffer::new("Local shadow view", ctx, &Matrix4::identity());
+                let proj = UniformBuffer::new("Local shadow proj", ctx, &Matrix4::identity());
+                let relative = UniformBuffer::new(
+                    "Local shadow relative view",
+                    ctx,
+                    &RawRelativeView::new(&Matrix4::identity()),
+                );
+                ShadowView {
+                    bind_group: view_proj_bindgroup(ctx, &view, &proj, &frame.buffer, &relative),
+                    view,
+                    proj,
+                    relative,
+                    view_proj: Matrix4::identity(),
+                    viewport: [0; 4],
+                    draws: DrawGenerator::new(ctx, models),
+                }
+            })
+            .collect();
+        let size = LOCAL_SHADOW_ATLAS_SIZE as i32;
+
+        Self {
+            views,
+            raw: UniformBuffer::new("Local shadows", ctx, &bytemuck::Zeroable::zeroed()),
+            count: 0,
+            shadowed: Vec::new(),
+            allocator: AtlasAllocator::new(size2(size, size)),
+            atlas_view: texture.create_view(&Default::default()),
+        }
+    }
+
+    /// Gives the views to the lights picked by [`shadow_budget`] and their rects in the atlas,
+    /// every frame
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &ShadowSettings,
+        lights: &LightsUniform,
+        camera: Point3<f32>,
+    ) {
+        let mut raw: RawLocalShadows = bytemuck::Zeroable::zeroed();
+        raw.normal_bias = settings.normal_bias;
+        self.shadowed.clear();
+        self.allocator.clear();
+        let mut count = 0;
+        let mut tile = LOCAL_SHADOW_MAX_TILE;
+        if settings.enabled {
+            for (light_index, views) in
+                shadow_budget(lights.enabled_lights(), camera, settings.local_lights)
+            {
+                let Some(viewports) = self.allocate(views.len(), &mut tile) else {
+                    continue;
+                };
+                for ((view, proj), viewport) in views.into_iter().zip(viewports) {
+                    let shadow_view = &mut self.views[count];
+                    shadow_view.view.write(ctx, &view);
+                    shadow_view
+                        .relative
+                        .write(ctx, &RawRelativeView::new(&view));
+                    shadow_view.proj.write(ctx, &proj);
+                    shadow_view.view_proj = proj * view;
+                    shadow_view.viewport = viewport;
+                    raw.views[count] = RawShadowView {
+                        view_proj: proj * view,
+                        rect: viewport.map(|v| v as f32 / LOCAL_SHADOW_ATLAS_SIZE as f32),
+                        light_index,
+                        _padding: [0; 3],
+                    };
+                    count += 1;
+                }
+                self.shadowed.push(light_index);
+                tile = (tile / 2).max(LOCAL_SHADOW_MIN_TILE);
+            }
+        }
+        self.count = count as u32;
+        raw.count = self.count;
+        self.raw.write(ctx, &raw);
+    }
+
+    /// Rects of `count` views of `tile` texels, all of them or none, halving `tile` until they fit
+    fn allocate(&mut self, count: usize, tile: &mut u32) -> Option<Vec<[u32; 4]>> {
+        loop {
+            let allocations: Vec<_> = (0..count)
+                .map_while(|_| self.allocator.allocate(size2(*tile as i32, *tile as i32)))
+                .collect();
+            if allocations.len() == count {
+                let viewports = allocations.iter().map(|allocation| {
+                    let min = allocation.rectangle.min;
+                    [min.x as u32, min.y as u32, *tile, *tile]
+                });
+                return Some(viewports.collect());
+            }
+            for allocation in allocations {
+                self.allocator.deallocate(allocation.id);
+            }
+            if *tile <= LOCAL_SHADOW_MIN_TILE {
+                return None;
+            }
+            *tile /= 2;
+        }
+    }
+
+    /// Lights shadowed this frame, the most important first
+    pub fn shadowed(&self) -> &[u32] {
+        &self.shadowed
+    }
+
+    /// Keeps the draws of the views grown with the instances
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx, models: &ModelsBuffer) {
+        for view in &mut self.views {
+            view.draws.apply_changes(ctx, models);
+        }
+    }
+
+    /// Depth of the casters of each view in use into its viewport, one pass over the atlas, see
+    /// [`super::shadows::ShadowCascades::render`]
+    pub(super) fn render(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        models: &ModelsBuffer,
+        materials: &MaterialsBuffer,
+        zones_visible: &StorageBuffer<u32>,
+        pipelines: &[wgpu::RenderPipeline; 2],
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        let views = &self.views[..self.count as usize];
+        for view in views {
+            view.draws
+                .generate(ctx, encoder, models, zones_visible, &view.view_proj);
+        }
+        let mut pass = encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Local shadows"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.atlas_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            })
+            .forget_lifetime();
+        for view in views {
+            let [x, y, width, height] = view.viewport.map(|v| v as f32);
+            pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            draw_casters(
+                &mut pass,
+                &view.bind_group,
+                &view.draws,
+                models,
+                materials,
+                pipelines,
+            );
+        }
+    }
+}
+
+/// Picks the shadowed lights of the frame: point and spot lights flagged with
+/// [`light_flags::CAST_SHADOWS`], the brightest at the camera first, until `max_lights` of them
+/// or the [`MAX_SHADOW_VIEWS`] views are taken. Returns their index and the view and projection
+/// of each of their views
+pub fn shadow_budget<'a>(
+    lights: impl Iterator<Item = (u32, &'a RawLight)>,
+    camera: Point3<f32>,
+    max_lights: u32,
+) -> Vec<(u32, Vec<(Matrix4<f32>, Matrix4<f32>)>)> {
+    let mut candidates: Vec<_> = lights
+        .filter(|(_, light)| {
+            light.flags & light_flags::CAST_SHADOWS != 0 && matches!(light.light_type, 1 | 3)
+        })
+        .map(|(index, light)| {
+            let distance = (Point3::from(light.position) - camera).norm();
+            (
+                index,
+                light,
+                light.intensity() / (1.0 + distance * distance),
+            )
+        })
+        .collect();
+    candidates.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    let mut picked = Vec::new();
+    let mut views = 0;
+    for (index, light, _) in candidates {
+        if picked.len() >= max_lights as usize {
+            break;
+        }
+        let light_views = light_views(light);
+        // A spotlight may still fit after a point light did not
+        if views + light_views.len() > MAX_SHADOW_VIEWS {
+            continue;
+        }
+        views += light_views.len();
+        picked.push((index, light_views));
+    }
+    picked
+}
+
+/// View and projection of each view of a point or spot light
+fn light_views(light: &RawLight) -> Vec<(Matrix4<f32>, Matrix4<f32>)> {
+    let eye = Point3::from(light.position);
+    let look = |direction: Vector3<f32>, fov_deg: f32| {
+        let up = match direction.y.abs() > 0.99 {
+            true => Vector3::z(),
+            false => Vector3::y(),
+        };
+        let view = Matrix4::look_at_rh(&eye, &(eye + direction), &up);
+        let perspective = Perspective3::new(
+            1.0,
+            fov_deg.to_radians(),
+            LOCAL_SHADOW_NEAR,
+            LOCAL_SHADOW_FAR,
+        );
+        (view, OPENGL_TO_WGPU_MATRIX * perspective.to_homogeneous())
+    };
+    match light.light_type {
+        3 => {
+            let direction = Vector3::from(light.direction)
+                .try_normalize(1e-6)
+                .unwrap_or(-Vector3::y());
+            // Cut off is the half angle of the cone
+            vec![look(direction, (light.cut_off * 2.0).clamp(1.0, 170.0))]
+        }
+        _ => [
+            Vector3::x(),
+            -Vector3::x(),
+            Vector3::y(),
+            -Vector3::y(),
+            Vector3::z(),
+            -Vector3::z(),
+        ]
+        .map(|direction| look(direction, 90.0))
+        .to_vec(),
+    }
+}
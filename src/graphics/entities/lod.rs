@@ -0,0 +1,205 @@
+use nalgebra::{Point3, Vector3};
+
+use super::model::{ModelInstance, ModelInstanceId, ModelsBuffer};
+
+/// Model drawn up to a distance from the camera
+#[derive(Debug, Clone, Copy)]
+pub struct LodLevel {
+    pub model_id: u16,
+    /// The next level takes over beyond it, unused on the last level
+    pub distance: f32,
+}
+
+/// Levels of detail of one object, nearest first
+#[derive(Debug, Clone)]
+pub struct LodGroup {
+    pub levels: Vec<LodLevel>,
+}
+
+impl LodGroup {
+    /// The current level is kept until `margin` meters past its range, moving along a boundary
+    /// does not switch back and forth
+    fn level_at(&self, distance: f32, current: usize, margin: f32) -> usize {
+        let last = self.levels.len().saturating_sub(1);
+        let start = current
+            .checked_sub(1)
+            .map_or(0.0, |previous| self.levels[previous].distance);
+        let end = match current < last {
+            true => self.levels[current].distance,
+            false => f32::INFINITY,
+        };
+        if distance >= start - margin && distance <= end + margin {
+            return current;
+        }
+        self.levels
+            .iter()
+            .position(|level| distance <= level.distance)
+            .unwrap_or(last)
+            .min(last)
+    }
+}
+
+struct Drawn {
+    level: usize,
+    ids: Vec<ModelInstanceId>,
+}
+
+struct LodInstance {
+    group: usize,
+    /// Given to every mesh of the drawn levels
+    instance: ModelInstance,
+    current: Drawn,
+    /// Previous level with the progress of the switch, from 0 to 1
+    fading: Option<(Drawn, f32)>,
+}
+
+/// Instances switching between the models of their [`LodGroup`] with the camera distance
+///
+/// A switch draws both levels for [`Self::fade_frames`] frames with complementary screen door
+/// dithering, driven by [`ModelInstance::fade`], instead of popping
+pub struct LodInstances {
+    pub groups: Vec<LodGroup>,
+    /// Frames taken by a switch
+    pub fade_frames: u32,
+    /// Meters past a boundary before switching back
+    pub hysteresis: f32,
+    /// Removed instances leave a hole, their id stays valid for the others
+    instances: Vec<Option<LodInstance>>,
+}
+
+impl Default for LodInstances {
+    fn default() -> Self {
+        Self {
+            groups: Vec::new(),
+            fade_frames: 8,
+            hysteresis: 1.0,
+            instances: Vec::new(),
+        }
+    }
+}
+
+impl LodInstances {
+    pub fn add_group(&mut self, group: LodGroup) -> usize {
+        self.groups.push(group);
+        self.groups.len() - 1
+    }
+
+    /// Drawn with the first level, the right one fades in from the next update
+    pub fn add(
+        &mut self,
+        models: &mut ModelsBuffer,
+        group: usize,
+        instance: ModelInstance,
+    ) -> usize {
+        let ids = match self.groups[group].levels.first() {
+            Some(level) => spawn(models, level.model_id, instance),
+            None => Vec::new(),
+        };
+        self.instances.push(Some(LodInstance {
+            group,
+            instance,
+            current: Drawn { level: 0, ids },
+            fading: None,
+        }));
+        self.instances.len() - 1
+    }
+
+    pub fn remove(&mut self, models: &mut ModelsBuffer, id: usize) {
+        let Some(lod) = self.instances.get_mut(id).and_then(Option::take) else {
+            return;
+        };
+        despawn(models, lod.current);
+        if let Some((fading, _)) = lod.fading {
+            despawn(models, fading);
+        }
+    }
+
+    pub fn clear(&mut self, models: &mut ModelsBuffer) {
+        for id in 0..self.instances.len() {
+            self.remove(models, id);
+        }
+        self.instances.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Instances in the middle of a switch
+    pub fn fading(&self) -> usize {
+        self.instances
+            .iter()
+            .flatten()
+            .filter(|lod| lod.fading.is_some())
+            .count()
+    }
+
+    pub fn update(&mut self, models: &mut ModelsBuffer, camera_position: Point3<f32>) {
+        let step = 1.0 / self.fade_frames.max(1) as f32;
+        for lod in self.instances.iter_mut().flatten() {
+            // A switch runs to its end before the next one starts
+            if let Some((fading, progress)) = lod.fading.take() {
+                let progress = progress + step;
+                if progress >= 1.0 {
+                    despawn(models, fading);
+                    set(models, &lod.current.ids, lod.instance);
+                } else {
+                    set(models, &lod.current.ids, lod.instance.with_fade(progress));
+                    set(models, &fading.ids, lod.instance.with_fade(-progress));
+                    lod.fading = Some((fading, progress));
+                }
+                continue;
+            }
+
+            let group = &self.groups[lod.group];
+            let distance = (Point3::from(lod.instance.translation) - camera_position).norm();
+            let level = group.level_at(distance, lod.current.level, self.hysteresis);
+            if level == lod.current.level {
+                continue;
+            }
+            let ids = spawn(
+                models,
+                group.levels[level].model_id,
+                lod.instance.with_fade(step),
+            );
+            let fading = std::mem::replace(&mut lod.current, Drawn { level, ids });
+            set(models, &fading.ids, lod.instance.with_fade(-step));
+            lod.fading = Some((fading, step));
+        }
+    }
+
+    /// Follows the world origin, the instances themselves are moved on the gpu
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for lod in self.instances.iter_mut().flatten() {
+            for axis in 0..3 {
+                lod.instance.translation[axis] -= shift[axis];
+            }
+        }
+    }
+}
+
+fn spawn(
+    models: &mut ModelsBuffer,
+    model_id: u16,
+    instance: ModelInstance,
+) -> Vec<ModelInstanceId> {
+    (0..models.mesh_count_of(model_id) as u16)
+        .map(|mesh_id| models.add_instance(model_id, mesh_id, instance))
+        .collect()
+}
+
+fn set(models: &mut ModelsBuffer, ids: &[ModelInstanceId], instance: ModelInstance) {
+    for id in ids {
+        models.set_instance(id, instance);
+    }
+}
+
+fn despawn(models: &mut ModelsBuffer, drawn: Drawn) {
+    for id in drawn.ids {
+        models.remove_instance(id);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::graphics::{
+    buffer::{padded_uniform_bytes, CommonBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+};
+
+use super::renderer::{EntitiesPipelines, SHADER_SOURCE};
+
+/// Custom look for the entities, materials opt in through their `shader_id`
+///
+/// The source must define `struct Params` and `fn shade(surface: Surface, params: Params) -> vec4f`,
+/// everything declared by `shader.wgsl` is in scope (`Surface`, `lights`, `t_atlas`...). The
+/// parameter block of the instance is read with `instance_params(surface)`, zeros without one
+pub struct MaterialShaderDesc<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+    /// Initial value of `Params`, laid out as in wgsl
+    pub params: &'a [u8],
+}
+
+pub struct MaterialShader {
+    pub name: String,
+    /// Kept to recreate the pipelines when the built-in variants change
+    pub(super) module: wgpu::ShaderModule,
+    pub(super) pipelines: EntitiesPipelines,
+    pub(super) bind_group: wgpu::BindGroup,
+    params: UniformBuffer<u8>,
+}
+
+impl MaterialShader {
+    /// The module and its pipelines are created by the caller from
+    /// [`MaterialShader::module_source`]
+    pub(super) fn new(
+        ctx: &GraphicsCtx,
+        desc: &MaterialShaderDesc,
+        module: wgpu::ShaderModule,
+        pipelines: EntitiesPipelines,
+    ) -> Self {
+        let params = UniformBuffer::new_array(
+            &format!("Material shader params: {}", desc.name),
+            ctx,
+            padded_uniform_bytes(desc.params),
+        );
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &material_shader_bind_group_layout(ctx),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            }],
+            label: Some("Material shader Bind Group"),
+        });
+
+        Self {
+            name: desc.name.to_string(),
+            module,
+            pipelines,
+            bind_group,
+            params,
+        }
+    }
+
+    /// Must have the size given at creation
+    pub fn write_params(&self, ctx: &GraphicsCtx, params: &[u8]) {
+        self.params.write_array(ctx, &padded_uniform_bytes(params));
+    }
+
+    /// Entities shader with `fs_custom`, its pipelines set `SHADER_ID` to draw only the materials
+    /// of the shader
+    pub(crate) fn module_source(source: &str) -> String {
+        format!(
+            "{SHADER_SOURCE}\n{source}\n
+@group(5) @binding(0)
+var<uniform> params: Params;
+
+@fragment
+fn fs_custom(in: VertexOutput) -> FragmentOutput {{
+    return output_of(in, shade(surface_of(in), params));
+}}
+"
+        )
+    }
+}
+
+pub fn material_shader_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Material shader Bind Group Layout"),
+        })
+}
@@ -0,0 +1,354 @@
+use nalgebra::{Matrix4, Point3};
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{
+        CommonBuffer, IndexBuffer, IndirectBuffer, StorageBuffer, UniformBuffer, WriteBuffer,
+    },
+    ctx::GraphicsCtx,
+};
+
+use super::{
+    draws::DrawGenerator,
+    model::{ModelVertex, ModelsBuffer, VertexPrecision},
+};
+
+/// Limits of each cluster, the triangle count keeps the meshlet indices within 3 * 124
+const MAX_MESHLET_VERTICES: usize = 64;
+const MAX_MESHLET_TRIANGLES: usize = 124;
+/// How much the clusters favour similar normals, for tighter cones
+const CONE_WEIGHT: f32 = 0.5;
+/// Draws generated per vertex precision, the meshlets of the instances over it are not drawn
+pub const MAX_MESHLET_DRAWS: u32 = 1 << 18;
+/// Workgroups along one dimension of a dispatch, the meshlets past it wrap to the next row
+const MAX_DISPATCH_WIDTH: u32 = 65535;
+
+/// Matches `Meshlet` in `meshlets.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawMeshlet {
+    /// Model space bounding sphere, center then radius
+    center: [f32; 3],
+    radius: f32,
+    /// Normal cone of the triangles, a cutoff of 1 is never backfacing
+    cone_axis: [f32; 3],
+    cone_cutoff: f32,
+    /// Into the meshlet indices, relative to the first vertex of the mesh
+    first_index: u32,
+    index_count: u32,
+    /// Column of the mesh, its draw args give the instances and the vertices
+    mesh: u32,
+    /// Draw region of the vertex precision of the mesh
+    region: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawMeshletCulling {
+    /// Inward facing planes of the clip volume, as in [`super::draws`]
+    planes: [[f32; 4]; 6],
+    eye: [f32; 3],
+    max_draws: u32,
+    /// The last row of the dispatch is not full
+    meshlet_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Experimental per cluster culling, for the very high poly scenes
+///
+/// The meshes are split into meshlets at load. After the [`DrawGenerator`] culled the instances,
+/// every meshlet of every visible instance is tested against the frustum and its normal cone, and
+/// the ones left are drawn one by one from their own indices
+pub struct MeshletCulling {
+    /// Draws the meshlets instead of the whole meshes
+    pub enabled: bool,
+
+    culling: UniformBuffer<RawMeshletCulling>,
+    meshlets: StorageBuffer<RawMeshlet>,
+    meshlet_count: u32,
+    pub(super) index_buffer: IndexBuffer<u16>,
+    /// [`MAX_MESHLET_DRAWS`] for the full precision, then as many for the half one
+    draws: IndirectBuffer,
+    /// One counter per precision
+    draw_count: wgpu::Buffer,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl MeshletCulling {
+    /// The draws are limited by a count, `None` without `MULTI_DRAW_INDIRECT_COUNT`
+    pub fn new(ctx: &GraphicsCtx, models: &ModelsBuffer) -> Option<Self> {
+        if !ctx.has_feature(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT) {
+            eprintln!("Meshlets need the multi draw indirect count feature");
+            return None;
+        }
+
+        let mut meshlets = Vec::new();
+        let mut indices = Vec::new();
+        let mut mesh = 0;
+        for model_id in 0..models.model_count() as u16 {
+            for mesh_id in 0..models.mesh_count_of(model_id) as u16 {
+                let (vertices, mesh_indices) = models.mesh_geometry(model_id, mesh_id);
+                let region = models.mesh_precision(model_id, mesh_id) as u32;
+                build_meshlets(
+                    vertices,
+                    mesh_indices,
+                    mesh,
+                    region,
+                    &mut meshlets,
+                    &mut indices,
+                );
+                mesh += 1;
+            }
+        }
+        let meshlet_count = meshlets.len() as u32;
+        // Bindings and slices cannot be empty
+        if meshlets.is_empty() {
+            meshlets.push(bytemuck::Zeroable::zeroed());
+            indices.push(0);
+        }
+
+        let culling = UniformBuffer::new(
+            "Meshlet culling",
+            ctx,
+            &RawMeshletCulling {
+                planes: [[0.0; 4]; 6],
+                eye: [0.0; 3],
+                max_draws: MAX_MESHLET_DRAWS,
+                meshlet_count,
+                _padding: [0; 3],
+            },
+        );
+        let meshlets = StorageBuffer::new_const_array("Meshlets", ctx, &meshlets[..]);
+        let index_buffer = IndexBuffer::new_const_array("Meshlet indices", ctx, &indices[..]);
+        let draws = IndirectBuffer::new_empty(
+            "Meshlet draws",
+            ctx,
+            MAX_MESHLET_DRAWS as usize * VertexPrecision::ALL.len(),
+        );
+        let draw_count = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Meshlet draw count"),
+            size: std::mem::size_of::<[u32; 2]>() as u64,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&meshlets_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("meshlets.wgsl"));
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Meshlet culling"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("cs_main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        Some(Self {
+            enabled: false,
+            culling,
+            meshlets,
+            meshlet_count,
+            index_buffer,
+            draws,
+            draw_count,
+            pipeline,
+        })
+    }
+
+    pub fn meshlet_count(&self) -> u32 {
+        self.meshlet_count
+    }
+
+    /// Must be recorded after the generation of `draws`, with the same camera
+    pub fn generate(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        models: &ModelsBuffer,
+        draws: &DrawGenerator,
+        view_proj: &Matrix4<f32>,
+        eye: Point3<f32>,
+    ) {
+        let row = |i| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.xyz().norm().max(1e-6);
+            (plane / length).into()
+        });
+        self.culling.write(
+            ctx,
+            &RawMeshletCulling {
+                planes,
+                eye: eye.into(),
+                max_draws: MAX_MESHLET_DRAWS,
+                meshlet_count: self.meshlet_count,
+                _padding: [0; 3],
+            },
+        );
+        encoder.clear_buffer(&self.draw_count, 0, None);
+
+        // The instance buffers are reallocated when growing, as in the draw generation
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &meshlets_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.culling.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.meshlets.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: models.indirect_buffer.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: draws.visible_counts().binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: draws.instances().binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.draws.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: self.draw_count.as_entire_binding(),
+                },
+            ],
+            label: Some("Meshlet Culling Bind Group"),
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Meshlet culling"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        let (width, height) = dispatch_size(self.meshlet_count);
+        pass.dispatch_workgroups(width, height, 1);
+    }
+
+    /// Meshlets of the meshes of `precision` left by `generate`, with [`Self::index_buffer`] bound
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'static>, precision: VertexPrecision) {
+        let region = precision as u64;
+        render_pass.multi_draw_indexed_indirect_count(
+            self.draws.inner(),
+            region * MAX_MESHLET_DRAWS as u64 * IndirectBuffer::ITEM_BYTE_SIZE,
+            &self.draw_count,
+            region * std::mem::size_of::<u32>() as u64,
+            MAX_MESHLET_DRAWS,
+        );
+    }
+}
+
+/// Workgroups along x and y covering one per meshlet, within the limit of each dimension
+fn dispatch_size(meshlet_count: u32) -> (u32, u32) {
+    let width = meshlet_count.min(MAX_DISPATCH_WIDTH);
+    match width {
+        0 => (0, 0),
+        width => (width, meshlet_count.div_ceil(width)),
+    }
+}
+
+/// Appends the meshlets of one mesh, their indices stay relative to its first vertex
+fn build_meshlets(
+    vertices: &[ModelVertex],
+    indices: &[u16],
+    mesh: u32,
+    region: u32,
+    meshlets: &mut Vec<RawMeshlet>,
+    meshlet_indices: &mut Vec<u16>,
+) {
+    if indices.is_empty() {
+        return;
+    }
+    let Ok(adapter) = meshopt::VertexDataAdapter::new(
+        bytemuck::cast_slice(vertices),
+        std::mem::size_of::<ModelVertex>(),
+        0,
+    ) else {
+        eprintln!("Failed to read the vertices of mesh {mesh} for its meshlets");
+        return;
+    };
+    let indices = indices.iter().map(|i| *i as u32).collect::<Vec<_>>();
+    let clusters = meshopt::build_meshlets(
+        &indices,
+        &adapter,
+        MAX_MESHLET_VERTICES,
+        MAX_MESHLET_TRIANGLES,
+        CONE_WEIGHT,
+    );
+    for meshlet in clusters.iter() {
+        let bounds = meshopt::compute_meshlet_bounds(meshlet, &adapter);
+        let first_index = meshlet_indices.len() as u32;
+        meshlet_indices.extend(
+            meshlet
+                .triangles
+                .iter()
+                .map(|local| meshlet.vertices[*local as usize] as u16),
+        );
+        meshlets.push(RawMeshlet {
+            center: bounds.center,
+            radius: bounds.radius,
+            cone_axis: bounds.cone_axis,
+            cone_cutoff: bounds.cone_cutoff,
+            first_index,
+            index_count: meshlet.triangles.len() as u32,
+            mesh,
+            region,
+        });
+    }
+}
+
+fn meshlets_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, true),
+                storage(5, false),
+                storage(6, false),
+            ],
+            label: Some("Meshlet Culling Bind Group Layout"),
+        })
+}
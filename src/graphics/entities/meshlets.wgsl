@@ -0,0 +1,149 @@
+// Matches `ModelInstance`, the vectors are kept as arrays to avoid the vec3 alignment
+struct Instance {
+    translation: array<f32, 3>,
+    material_id: u32,
+    // Snorm quaternion, xy then zw
+    rotation: array<u32, 2>,
+    scale: array<f32, 3>,
+    // Animation id in the low 16 bits, parameter block id in the high ones
+    animation_params: u32,
+    // Zone id in the low 16 bits, snorm level of detail fade in the high ones
+    zone_fade: u32,
+    tint: u32,
+    lightmap: array<f32, 4>,
+}
+
+// Matches `VisibleInstance`
+struct VisibleInstance {
+    instance: Instance,
+    previous_transform: array<vec4f, 3>,
+}
+
+// Matches `wgpu::util::DrawIndexedIndirectArgs`
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+// Matches `RawMeshlet`
+struct Meshlet {
+    center: vec3f,
+    radius: f32,
+    cone_axis: vec3f,
+    cone_cutoff: f32,
+    first_index: u32,
+    index_count: u32,
+    mesh: u32,
+    region: u32,
+}
+
+struct Culling {
+    planes: array<vec4f, 6>,
+    eye: vec3f,
+    max_draws: u32,
+    meshlet_count: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> culling: Culling;
+@group(0) @binding(1)
+var<storage, read> meshlets: array<Meshlet>;
+@group(0) @binding(2)
+var<storage, read> meshes: array<DrawArgs>;
+// Written by the draw generation, the visible instances are at the start of the mesh column
+@group(0) @binding(3)
+var<storage, read> visible_counts: array<u32>;
+@group(0) @binding(4)
+var<storage, read> visible: array<VisibleInstance>;
+@group(0) @binding(5)
+var<storage, read_write> draws: array<DrawArgs>;
+// One counter per vertex precision
+@group(0) @binding(6)
+var<storage, read_write> draw_count: array<atomic<u32>, 2>;
+
+const WORKGROUP_SIZE: u32 = 64u;
+
+fn quat_to_matrix(q: vec4f) -> mat3x3f {
+    let x2 = q.xyz * 2.0;
+    let xx = q.x * x2.x;
+    let yy = q.y * x2.y;
+    let zz = q.z * x2.z;
+    let xy = q.x * x2.y;
+    let xz = q.x * x2.z;
+    let yz = q.y * x2.z;
+    let wx = q.w * x2.x;
+    let wy = q.w * x2.y;
+    let wz = q.w * x2.z;
+    return mat3x3f(
+        vec3f(1.0 - yy - zz, xy + wz, xz - wy),
+        vec3f(xy - wz, 1.0 - xx - zz, yz + wx),
+        vec3f(xz + wy, yz - wx, 1.0 - xx - yy),
+    );
+}
+
+fn rotation_of(instance: Instance) -> mat3x3f {
+    let q = normalize(vec4f(unpack2x16snorm(instance.rotation[0]), unpack2x16snorm(instance.rotation[1])));
+    return quat_to_matrix(q);
+}
+
+fn is_visible(instance: Instance, meshlet: Meshlet) -> bool {
+    let t = instance.translation;
+    let s = vec3f(instance.scale[0], instance.scale[1], instance.scale[2]);
+    let rotation = rotation_of(instance);
+    let center = vec3f(t[0], t[1], t[2]) + rotation * (meshlet.center * s);
+    let scale = abs(s);
+    let radius = meshlet.radius * max(scale.x, max(scale.y, scale.z));
+    for (var i = 0u; i < 6u; i++) {
+        let plane = culling.planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return false;
+        }
+    }
+
+    // The cones only hold under uniform scales, mirrored instances flip the facing
+    if s.x <= 0.0 || s.x != s.y || s.y != s.z {
+        return true;
+    }
+    let to_center = center - culling.eye;
+    let axis = rotation * meshlet.cone_axis;
+    return dot(to_center, axis) < meshlet.cone_cutoff * length(to_center) + radius;
+}
+
+// One workgroup per meshlet, tested against each visible instance of its mesh. The workgroups
+// are laid out in rows, a dimension of a dispatch is limited
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn cs_main(
+    @builtin(workgroup_id) workgroup: vec3u,
+    @builtin(num_workgroups) workgroups: vec3u,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let index = workgroup.y * workgroups.x + workgroup.x;
+    if index >= culling.meshlet_count {
+        return;
+    }
+    let meshlet = meshlets[index];
+    let mesh = meshes[meshlet.mesh];
+    let count = visible_counts[meshlet.mesh];
+
+    for (var i = local; i < count; i += WORKGROUP_SIZE) {
+        let slot = mesh.first_instance + i;
+        if !is_visible(visible[slot].instance, meshlet) {
+            continue;
+        }
+        // Counted past the end, the draws are limited to `max_draws`
+        let draw = atomicAdd(&draw_count[meshlet.region], 1u);
+        if draw >= culling.max_draws {
+            continue;
+        }
+        draws[meshlet.region * culling.max_draws + draw] = DrawArgs(
+            meshlet.index_count,
+            1u,
+            meshlet.first_index,
+            mesh.base_vertex,
+            slot,
+        );
+    }
+}
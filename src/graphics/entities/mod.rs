@@ -1,12 +1,58 @@
 use image::DynamicImage;
-use model::Material;
+use import::SpriteSheet;
+use model::{Material, VertexPrecision};
 use tobj::Mesh;
 
+use crate::graphics::utils::ColorSpace;
+
+pub mod animation;
+pub mod blobs;
+pub mod cache;
+pub mod csg;
+pub mod dissolve;
+pub mod draws;
+pub mod import;
+pub mod lightmap;
+pub mod local_shadows;
+pub mod lod;
+pub mod material_shader;
+#[cfg(feature = "meshlets")]
+pub mod meshlets;
 pub mod model;
+pub mod params;
+pub mod ranges;
+pub mod rebase;
 pub mod renderer;
+pub mod shadows;
+pub mod sprites;
+pub mod statics;
+pub mod streaming;
+pub mod text;
+pub mod toon;
+pub mod wind;
+pub mod zones;
+
+/// Vertex data computed at import, each is empty when not baked
+#[derive(Debug, Clone, Default)]
+pub struct BakedVertices {
+    /// Two per vertex, see [`lightmap::unwrap`]
+    pub lightmap_uvs: Vec<f32>,
+    /// One per vertex, see [`lightmap::ao`]
+    pub ao: Vec<f32>,
+    /// Four per vertex, see [`import::generate_tangents`]
+    pub tangents: Vec<f32>,
+}
 
 pub struct EntityModel {
     pub meshes: Vec<Mesh>,
+    /// One per mesh
+    pub baked: Vec<BakedVertices>,
+    /// From [`model::ImportOptions::precision`]
+    pub precision: VertexPrecision,
     pub materials: Vec<Material>,
     pub textures: Vec<DynamicImage>,
+    /// One per texture, from [`import::TextureImportOptions::sprite_sheet`]
+    pub sprite_sheets: Vec<Option<SpriteSheet>>,
+    /// One per texture, guessed from its name by [`ColorSpace::from_texture_name`]
+    pub color_spaces: Vec<ColorSpace>,
 }
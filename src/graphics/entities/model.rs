@@ -1,35 +1,140 @@
 use std::{
+    collections::HashMap,
     io::{BufReader, Cursor},
+    ops::Range,
     sync::atomic::{AtomicU32, Ordering},
     u16,
 };
 
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Point3, Quaternion, UnitQuaternion, Vector3};
 use tobj::Mesh;
 use wgpu::util::DrawIndexedIndirectArgs;
 
 use crate::{
     graphics::{
+        assets::decode_textures,
+        bounds::Aabb,
         buffer::{
-            ColumnChange, CommonBuffer, DenseMapped2d, IndexBuffer, IndirectBuffer, InstanceBuffer,
-            Slot2dId, StorageBuffer, VertexBuffer,
+            read_buffer, CommonBuffer, DenseMapped2d, IndexBuffer, IndirectBuffer, InstanceBuffer,
+            Slot2dId, StorageBuffer, UniformBuffer, VertexBuffer, WriteBuffer,
         },
         color::Color3,
         ctx::GraphicsCtx,
+        utils::{f32_to_f16, ColorSpace},
     },
+    utils::DenseId,
     ASSETS,
 };
 
-use super::EntityModel;
+use super::{
+    cache::{self, ProcessedModel},
+    import::{self, TextureImportOptions, Units, UpAxis},
+    lightmap::{
+        ao::{bake_vertex_ao, AoBake},
+        unwrap::unwrap_lightmap_uvs,
+    },
+    params::ParamBlock,
+    ranges::InstanceRanges,
+    streaming::StreamedGeometry,
+    toon::TOON_SHADER_ID,
+    wind::RawWind,
+    BakedVertices, EntityModel,
+};
 
 pub struct ModelsBuffer {
     pub(super) vertex_buffer: VertexBuffer<ModelVertex>,
+    /// Vertices of the meshes imported with [`VertexPrecision::Half`]
+    pub(super) packed_vertex_buffer: VertexBuffer<PackedVertex>,
     pub(super) index_buffer: IndexBuffer<u16>,
     pub(super) instance_buffer: DenseMapped2d<InstanceBuffer<ModelInstance>>,
+    /// Every instance of each mesh, culled into the entities draws by [`super::draws::DrawGenerator`].
+    /// The instances are found on the gpu by [`Self::record_ranges`]
     pub(super) indirect_buffer: IndirectBuffer,
+    ranges: InstanceRanges,
 
     models_column_id: Vec<u16>,
     instances_count: Vec<Vec<u16>>,
+    /// Cpu side copy of the live instances of each mesh, in [`Self::mesh_index`] order, as last
+    /// written
+    live: Vec<HashMap<DenseId, ModelInstance>>,
+    /// Model space bounds, per model then per mesh. Kept when the geometry is unloaded, the
+    /// same geometry comes back
+    mesh_bounds: Vec<Vec<Aabb>>,
+    /// Cpu side copy of the geometry, per model then per mesh, read by the lightmap baker
+    geometry: Vec<Vec<MeshGeometry>>,
+    /// Per model, whether its geometry is loaded, see [`Self::unload_geometry`]
+    resident: Vec<bool>,
+    /// The buffers are rebuilt from `geometry` on the next [`Self::apply_changes`]
+    geometry_changed: bool,
+    /// Per model then per mesh
+    mesh_ranges: Vec<Vec<MeshRange>>,
+    /// Consecutive meshes sharing a vertex buffer, for the draws indexing the meshes in order
+    precision_runs: Vec<(VertexPrecision, Range<u32>)>,
+    /// Meshes with vertex colors changed since the last [`Self::apply_changes`]
+    recolored: Vec<(u16, u16)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MeshRange {
+    placement: MeshPlacement,
+    /// Unwrapped by [`super::lightmap::unwrap::unwrap_lightmap_uvs`]
+    lightmapped: bool,
+    precision: VertexPrecision,
+}
+
+/// Where a mesh is in the gpu buffers, its base vertex is relative to the buffer of its precision
+#[derive(Debug, Clone, Copy, Default)]
+struct MeshPlacement {
+    first_index: u32,
+    index_count: u32,
+    base_vertex: i32,
+}
+
+impl MeshPlacement {
+    /// Draw args of the mesh with the instances of `args`
+    fn args(&self, args: &DrawIndexedIndirectArgs) -> DrawIndexedIndirectArgs {
+        DrawIndexedIndirectArgs {
+            index_count: self.index_count,
+            first_index: self.first_index,
+            base_vertex: self.base_vertex,
+            ..*args
+        }
+    }
+}
+
+/// Vertices and indices of a mesh, empty while its model is unloaded
+#[derive(Debug, Clone, Default)]
+struct MeshGeometry {
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u16>,
+}
+
+impl MeshGeometry {
+    fn new(mesh: &Mesh, baked: &BakedVertices) -> Self {
+        Self {
+            vertices: model_vertices(mesh, baked).collect(),
+            indices: mesh.indices.iter().map(|i| *i as u16).collect(),
+        }
+    }
+
+    /// Vertices referenced by the indices
+    fn used_vertices(&self) -> impl Iterator<Item = &ModelVertex> {
+        self.indices.iter().map(|i| &self.vertices[*i as usize])
+    }
+
+    /// Bounds of the used vertices, empty meshes are a point at the origin
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(
+            self.used_vertices()
+                .map(|vertex| Point3::from(vertex.position)),
+        )
+        .unwrap_or(Aabb::point(Point3::origin()))
+    }
+
+    fn is_lightmapped(&self) -> bool {
+        self.used_vertices()
+            .any(|vertex| vertex.lightmap_uv != [0.0; 2])
+    }
 }
 
 pub struct ModelInstanceId {
@@ -38,17 +143,64 @@ pub struct ModelInstanceId {
     pub instance_id: Slot2dId,
 }
 
+impl ModelInstanceId {
+    pub fn key(&self) -> InstanceKey {
+        InstanceKey {
+            model_id: self.model_id,
+            mesh_id: self.mesh_id,
+            dense: self.instance_id.dense,
+        }
+    }
+}
+
+/// Copyable name of a live instance, to look it up without owning its [`ModelInstanceId`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceKey {
+    pub model_id: u16,
+    pub mesh_id: u16,
+    pub dense: DenseId,
+}
+
 impl ModelsBuffer {
+    /// `precisions` has one entry per mesh, the vertices of the half ones are packed here
     pub fn from_raw(
         ctx: &GraphicsCtx,
         vertices: &[ModelVertex],
         indices: &[u16],
         instances: &[ModelInstance],
         indirects: &[wgpu::util::DrawIndexedIndirectArgs],
+        precisions: &[VertexPrecision],
         instances_count: Vec<Vec<u16>>,
     ) -> Self {
-        let vertex_buffer = VertexBuffer::new_const_array("Models vertices", ctx, vertices);
-        let index_buffer = IndexBuffer::new_const_array("Models indices", ctx, indices);
+        // Meshes are laid out in order, each one ends where the next starts
+        let mut meshes = indirects.iter().enumerate().map(|(i, indirect)| {
+            let start = indirect.base_vertex as usize;
+            let end = indirects
+                .get(i + 1)
+                .map_or(vertices.len(), |next| next.base_vertex as usize);
+            let first = indirect.first_index as usize;
+            MeshGeometry {
+                vertices: vertices[start..end].to_vec(),
+                indices: indices[first..first + indirect.index_count as usize].to_vec(),
+            }
+        });
+        let geometry: Vec<Vec<_>> = instances_count
+            .iter()
+            .map(|counts| (&mut meshes).take(counts.len()).collect())
+            .collect();
+
+        let (full_vertices, packed_vertices, indices, placements) =
+            pack_geometry(&geometry, precisions);
+        let gpu_indirects: Vec<_> = indirects
+            .iter()
+            .zip(&placements)
+            .map(|(indirect, placement)| placement.args(indirect))
+            .collect();
+        // Writable for the vertex colors painted from the editor
+        let vertex_buffer = VertexBuffer::new_array("Models vertices", ctx, &full_vertices);
+        let packed_vertex_buffer =
+            VertexBuffer::new_array("Models packed vertices", ctx, &packed_vertices);
+        let index_buffer = IndexBuffer::new_const_array("Models indices", ctx, &indices[..]);
         let instance_buffer = DenseMapped2d::new(
             "Models instances",
             ctx,
@@ -56,13 +208,57 @@ impl ModelsBuffer {
             instances_count.iter().flatten().copied(),
         );
         let indirect_buffer =
-            IndirectBuffer::new_array("Models index indirect args", ctx, indirects);
+            IndirectBuffer::new_array("Models index indirect args", ctx, &gpu_indirects);
+        // Packed columns hand out the ids in slot order
+        let mut instances_iter = instances.iter();
+        let live = instances_count
+            .iter()
+            .flatten()
+            .map(|count| {
+                (&mut instances_iter)
+                    .take(*count as usize)
+                    .enumerate()
+                    .map(|(i, instance)| (DenseId::from_raw(i as u32), *instance))
+                    .collect()
+            })
+            .collect();
+
+        let mesh_bounds = geometry
+            .iter()
+            .map(|meshes| meshes.iter().map(MeshGeometry::bounds).collect())
+            .collect();
+        let mut placements = placements.into_iter().zip(precisions);
+        let mesh_ranges = geometry
+            .iter()
+            .map(|meshes| {
+                meshes
+                    .iter()
+                    .zip(&mut placements)
+                    .map(|(mesh, (placement, precision))| MeshRange {
+                        placement,
+                        lightmapped: mesh.is_lightmapped(),
+                        precision: *precision,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut precision_runs: Vec<(VertexPrecision, Range<u32>)> = Vec::new();
+        for (column, precision) in precisions.iter().enumerate() {
+            let column = column as u32;
+            match precision_runs.last_mut() {
+                Some((last, columns)) if last == precision => columns.end = column + 1,
+                _ => precision_runs.push((*precision, column..column + 1)),
+            }
+        }
 
         Self {
             vertex_buffer,
+            packed_vertex_buffer,
             index_buffer,
             instance_buffer,
             indirect_buffer,
+            ranges: InstanceRanges::new(ctx),
             models_column_id: {
                 let mut acc = 0;
                 instances_count
@@ -74,13 +270,30 @@ impl ModelsBuffer {
                     })
                     .collect()
             },
+            resident: vec![true; instances_count.len()],
             instances_count,
+            live,
+            mesh_bounds,
+            geometry,
+            geometry_changed: false,
+            mesh_ranges,
+            precision_runs,
+            recolored: Vec::new(),
         }
     }
 
+    /// Meshes come with their baked vertex data, see [`EntityModel::baked`], and the precision
+    /// their model was imported with
     pub fn new<'a>(
         ctx: &GraphicsCtx,
-        iter: impl IntoIterator<Item = (&'a Vec<Mesh>, Vec<Vec<ModelInstance>>)>,
+        iter: impl IntoIterator<
+            Item = (
+                &'a Vec<Mesh>,
+                &'a Vec<BakedVertices>,
+                VertexPrecision,
+                Vec<Vec<ModelInstance>>,
+            ),
+        >,
     ) -> Self {
         let idx_counter = AtomicU32::new(0);
         let vtx_counter = AtomicU32::new(0);
@@ -88,6 +301,7 @@ impl ModelsBuffer {
 
         struct PerModel<T> {
             meshes: T,
+            precision: VertexPrecision,
         }
 
         struct PerMesh<T> {
@@ -96,43 +310,12 @@ impl ModelsBuffer {
             instances: Vec<ModelInstance>,
         }
 
-        let (vertices, indices, indirect, instances, instances_count) =
-            iter.into_iter()
-                .map(|(meshes, instances)| {
-                    let meshes = meshes.into_iter().zip(instances).map(|(mesh, instances)| {
-                        let vertices = (0..mesh.positions.len() / 3).map(|i| {
-                            if mesh.normals.is_empty() {
-                                ModelVertex {
-                                    position: [
-                                        mesh.positions[i * 3],
-                                        mesh.positions[i * 3 + 1],
-                                        mesh.positions[i * 3 + 2],
-                                    ],
-                                    tex_coords: [
-                                        mesh.texcoords[i * 2],
-                                        1.0 - mesh.texcoords[i * 2 + 1],
-                                    ],
-                                    normal: [0.0, 0.0, 0.0],
-                                }
-                            } else {
-                                ModelVertex {
-                                    position: [
-                                        mesh.positions[i * 3],
-                                        mesh.positions[i * 3 + 1],
-                                        mesh.positions[i * 3 + 2],
-                                    ],
-                                    tex_coords: [
-                                        mesh.texcoords[i * 2],
-                                        1.0 - mesh.texcoords[i * 2 + 1],
-                                    ],
-                                    normal: [
-                                        mesh.normals[i * 3],
-                                        mesh.normals[i * 3 + 1],
-                                        mesh.normals[i * 3 + 2],
-                                    ],
-                                }
-                            }
-                        });
+        let (vertices, indices, indirect, precisions, instances, instances_count) = iter
+            .into_iter()
+            .map(|(meshes, baked, precision, instances)| {
+                let meshes = meshes.into_iter().zip(baked).zip(instances).map(
+                    |((mesh, baked), instances)| {
+                        let vertices = model_vertices(mesh, baked);
 
                         let indices = mesh.indices.iter().map(|i| *i as u16);
 
@@ -153,35 +336,45 @@ impl ModelsBuffer {
                             indirect,
                             instances,
                         }
-                    });
+                    },
+                );
 
-                    PerModel { meshes }
-                })
-                .fold(
-                    Default::default(),
-                    |(
-                        mut vertices,
-                        mut indices,
-                        mut indirect,
-                        mut instances,
-                        mut instances_count,
-                    ): (Vec<_>, Vec<_>, Vec<_>, Vec<_>, Vec<_>),
-                     model| {
-                        let mut instance_count = Vec::with_capacity(model.meshes.len());
-                        for mesh in model.meshes {
-                            let (local_vertices, local_indices) = mesh.geometry;
-                            vertices.extend(local_vertices);
-                            indices.extend(local_indices);
-                            instance_count.push(mesh.instances.len() as u16);
-                            instances.extend(mesh.instances);
-                            indirect.push(mesh.indirect);
-                        }
+                PerModel { meshes, precision }
+            })
+            .fold(
+                Default::default(),
+                |(
+                    mut vertices,
+                    mut indices,
+                    mut indirect,
+                    mut precisions,
+                    mut instances,
+                    mut instances_count,
+                ): (Vec<_>, Vec<_>, Vec<_>, Vec<_>, Vec<_>, Vec<_>),
+                 model| {
+                    let mut instance_count = Vec::with_capacity(model.meshes.len());
+                    for mesh in model.meshes {
+                        let (local_vertices, local_indices) = mesh.geometry;
+                        vertices.extend(local_vertices);
+                        indices.extend(local_indices);
+                        instance_count.push(mesh.instances.len() as u16);
+                        instances.extend(mesh.instances);
+                        indirect.push(mesh.indirect);
+                        precisions.push(model.precision);
+                    }
 
-                        instances_count.push(instance_count);
+                    instances_count.push(instance_count);
 
-                        (vertices, indices, indirect, instances, instances_count)
-                    },
-                );
+                    (
+                        vertices,
+                        indices,
+                        indirect,
+                        precisions,
+                        instances,
+                        instances_count,
+                    )
+                },
+            );
 
         Self::from_raw(
             ctx,
@@ -189,6 +382,7 @@ impl ModelsBuffer {
             &indices,
             &instances,
             &indirect,
+            &precisions,
             instances_count,
         )
     }
@@ -202,6 +396,7 @@ impl ModelsBuffer {
         let column_id = self.models_column_id[model_id as usize] + mesh_id;
         let instance_id = self.instance_buffer.push(column_id, instance);
         self.instances_count[model_id as usize][mesh_id as usize] += 1;
+        self.live[column_id as usize].insert(instance_id.dense, instance);
 
         ModelInstanceId {
             model_id,
@@ -210,15 +405,167 @@ impl ModelsBuffer {
         }
     }
 
+    pub fn set_instance(&mut self, id: &ModelInstanceId, instance: ModelInstance) {
+        self.instance_buffer.set(&id.instance_id, instance);
+        let column_id = self.models_column_id[id.model_id as usize] + id.mesh_id;
+        if let Some(live) = self.live[column_id as usize].get_mut(&id.instance_id.dense) {
+            *live = instance;
+        }
+    }
+
+    /// Last value written to the instance, `None` once removed
+    pub fn instance(&self, key: InstanceKey) -> Option<&ModelInstance> {
+        let column_id = self.models_column_id[key.model_id as usize] + key.mesh_id;
+        self.live[column_id as usize].get(&key.dense)
+    }
+
+    /// Live instances of the mesh in slot order, the order they are drawn in
+    pub fn mesh_instances(
+        &self,
+        model_id: u16,
+        mesh_id: u16,
+    ) -> impl Iterator<Item = (InstanceKey, &ModelInstance)> + '_ {
+        let column_id = self.models_column_id[model_id as usize] + mesh_id;
+        let live = &self.live[column_id as usize];
+        self.instance_buffer
+            .column_ids(column_id)
+            .filter_map(move |dense| {
+                let key = InstanceKey {
+                    model_id,
+                    mesh_id,
+                    dense,
+                };
+                live.get(&dense).map(|instance| (key, instance))
+            })
+    }
+
+    /// Live instances of every mesh, mesh by mesh in [`Self::mesh_index`] order
+    pub fn instances(&self) -> impl Iterator<Item = (InstanceKey, &ModelInstance)> + '_ {
+        self.instances_count
+            .iter()
+            .enumerate()
+            .flat_map(move |(model_id, meshes)| {
+                (0..meshes.len() as u16)
+                    .flat_map(move |mesh_id| self.mesh_instances(model_id as u16, mesh_id))
+            })
+    }
+
     pub fn instance_count(&self) -> u32 {
         self.instances_count[..].iter().flatten().sum::<u16>() as u32
     }
 
+    /// Live instances of every mesh of the model
+    pub fn model_instance_count(&self, model_id: u16) -> u32 {
+        self.instances_count[model_id as usize]
+            .iter()
+            .map(|count| *count as u32)
+            .sum()
+    }
+
     pub fn remove_instance(&mut self, id: ModelInstanceId) {
+        let column_id = self.models_column_id[id.model_id as usize] + id.mesh_id;
+        self.live[column_id as usize].remove(&id.instance_id.dense);
         self.instance_buffer.remove(id.instance_id);
         self.instances_count[id.model_id as usize][id.mesh_id as usize] -= 1;
     }
 
+    /// Model space vertices of the mesh, three per triangle
+    pub fn mesh_triangles(&self, model_id: u16, mesh_id: u16) -> Vec<[ModelVertex; 3]> {
+        let mesh = &self.geometry[model_id as usize][mesh_id as usize];
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]))
+            .collect()
+    }
+
+    /// Vertices of the mesh, and its indices relative to them. Empty while the model is unloaded
+    pub fn mesh_geometry(&self, model_id: u16, mesh_id: u16) -> (&[ModelVertex], &[u16]) {
+        let mesh = &self.geometry[model_id as usize][mesh_id as usize];
+        (&mesh.vertices, &mesh.indices)
+    }
+
+    /// Whether the geometry of the model is loaded, its meshes draw nothing otherwise
+    pub fn is_resident(&self, model_id: u16) -> bool {
+        self.resident[model_id as usize]
+    }
+
+    /// Replaces the geometry of the model, uploaded by the next [`Self::apply_changes`]. The
+    /// meshes must be the ones the model was created with, its bounds and precision are kept
+    pub fn load_geometry(
+        &mut self,
+        model_id: u16,
+        meshes: &[Mesh],
+        baked: &[BakedVertices],
+    ) -> Result<(), String> {
+        let mesh_count = self.mesh_count_of(model_id) as usize;
+        if meshes.len() != mesh_count {
+            return Err(format!(
+                "Model {model_id} has {mesh_count} meshes, its geometry has {}",
+                meshes.len()
+            ));
+        }
+        let no_baked = BakedVertices::default();
+        self.geometry[model_id as usize] = meshes
+            .iter()
+            .enumerate()
+            .map(|(i, mesh)| MeshGeometry::new(mesh, baked.get(i).unwrap_or(&no_baked)))
+            .collect();
+        self.resident[model_id as usize] = true;
+        self.geometry_changed = true;
+        Ok(())
+    }
+
+    /// Frees the geometry of a model without instances, on the cpu now and on the gpu with the
+    /// next [`Self::apply_changes`]
+    pub fn unload_geometry(&mut self, model_id: u16) -> Result<(), String> {
+        let instances = self.model_instance_count(model_id);
+        if instances > 0 {
+            return Err(format!(
+                "Model {model_id} still has {instances} instances, its geometry stays loaded"
+            ));
+        }
+        for mesh in &mut self.geometry[model_id as usize] {
+            *mesh = MeshGeometry::default();
+        }
+        self.resident[model_id as usize] = false;
+        self.geometry_changed = true;
+        Ok(())
+    }
+
+    /// Unloaded models that got instances since, their geometry should be loaded again
+    pub fn missing_geometry(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.model_count() as u16)
+            .filter(|model_id| !self.is_resident(*model_id))
+            .filter(|model_id| self.model_instance_count(*model_id) > 0)
+    }
+
+    /// One per vertex of the mesh, in the order of [`Self::mesh_geometry`]
+    pub fn vertex_colors(&self, model_id: u16, mesh_id: u16) -> Vec<[u8; 4]> {
+        let (vertices, _) = self.mesh_geometry(model_id, mesh_id);
+        vertices.iter().map(|vertex| vertex.color).collect()
+    }
+
+    /// Uploaded by [`Self::apply_changes`], `colors` has one entry per vertex of the mesh
+    pub fn set_vertex_colors(&mut self, model_id: u16, mesh_id: u16, colors: &[[u8; 4]]) {
+        let mesh = &mut self.geometry[model_id as usize][mesh_id as usize];
+        for (vertex, color) in mesh.vertices.iter_mut().zip(colors) {
+            vertex.color = *color;
+        }
+        if !self.recolored.contains(&(model_id, mesh_id)) {
+            self.recolored.push((model_id, mesh_id));
+        }
+    }
+
+    /// Whether the mesh has lightmap uvs, only those instances can be baked
+    pub fn is_lightmapped(&self, model_id: u16, mesh_id: u16) -> bool {
+        self.mesh_ranges[model_id as usize][mesh_id as usize].lightmapped
+    }
+
+    /// Model space bounds of the mesh
+    pub fn mesh_bounds(&self, model_id: u16, mesh_id: u16) -> Aabb {
+        self.mesh_bounds[model_id as usize][mesh_id as usize]
+    }
+
     pub fn model_count(&self) -> u32 {
         self.instances_count.len() as u32
     }
@@ -227,41 +574,250 @@ impl ModelsBuffer {
         self.instances_count.iter().map(|m| m.len()).sum::<usize>() as u32
     }
 
+    /// Vertices of every loaded mesh, both precisions
+    pub fn vertex_count(&self) -> u32 {
+        self.geometry
+            .iter()
+            .flatten()
+            .map(|mesh| mesh.vertices.len() as u32)
+            .sum()
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.geometry
+            .iter()
+            .flatten()
+            .map(|mesh| mesh.indices.len() as u32)
+            .sum()
+    }
+
+    /// Position of the mesh among the meshes of every model, its column in the instances
+    pub fn mesh_index(&self, model_id: u16, mesh_id: u16) -> u32 {
+        (self.models_column_id[model_id as usize] + mesh_id) as u32
+    }
+
     pub fn mesh_count_of(&self, model_id: u16) -> u32 {
         self.instances_count[model_id as usize].len() as u32
     }
 
+    pub fn mesh_precision(&self, model_id: u16, mesh_id: u16) -> VertexPrecision {
+        self.mesh_ranges[model_id as usize][mesh_id as usize].precision
+    }
+
+    /// Meshes stored with the precision, over every model
+    pub fn mesh_count_with(&self, precision: VertexPrecision) -> u32 {
+        self.mesh_ranges
+            .iter()
+            .flatten()
+            .filter(|range| range.precision == precision)
+            .count() as u32
+    }
+
+    /// Ranges of consecutive mesh columns sharing the vertex buffer of their precision
+    pub fn precision_runs(&self) -> &[(VertexPrecision, Range<u32>)] {
+        &self.precision_runs
+    }
+
+    /// Vertex buffer the meshes of the precision are drawn from, at slot 0
+    pub fn vertex_slice(&self, precision: VertexPrecision) -> wgpu::BufferSlice<'_> {
+        match precision {
+            VertexPrecision::Full => self.vertex_buffer.as_slice(),
+            VertexPrecision::Half => self.packed_vertex_buffer.as_slice(),
+        }
+    }
+
+    /// Share of the instance slots left unused by the mesh columns, and the times they were
+    /// repacked to reclaim them
+    pub fn instance_fragmentation(&self) -> (f32, u32) {
+        (
+            self.instance_buffer.fragmentation(),
+            self.instance_buffer.rebalance_count(),
+        )
+    }
+
+    /// Instances then slots of the column of each mesh, in [`Self::mesh_index`] order
+    pub fn instance_occupancy(&self) -> Vec<(usize, usize)> {
+        self.instance_buffer.column_occupancy().collect()
+    }
+
+    /// Slots kept for the instances of each mesh, in [`Self::mesh_index`] order, allocated on
+    /// the next [`Self::apply_changes`]. Meshes past the end of `capacities` keep none
+    pub fn reserve_instances(&mut self, capacities: &[usize]) {
+        for column_id in 0..self.mesh_count() as u16 {
+            let capacity = capacities.get(column_id as usize).copied().unwrap_or(0);
+            self.instance_buffer.reserve(column_id, capacity);
+        }
+    }
+
+    /// Instances of the mesh read back from the gpu, in slot order
+    pub fn read_instances(
+        &self,
+        ctx: &GraphicsCtx,
+        model_id: u16,
+        mesh_id: u16,
+    ) -> Vec<ModelInstance> {
+        let column_id = self.models_column_id[model_id as usize] + mesh_id;
+        self.instance_buffer.read_column(ctx, column_id)
+    }
+
+    /// Points the draw args of every mesh at the instances of its column, must be recorded before
+    /// the args are read in the frame
+    pub fn record_ranges(&self, ctx: &GraphicsCtx, encoder: &mut wgpu::CommandEncoder) {
+        self.ranges.record(
+            ctx,
+            encoder,
+            &self.indirect_buffer,
+            self.mesh_count(),
+            self.instance_buffer.owners(),
+        );
+    }
+
+    /// The instance columns are consistent, the draw args of every mesh on the gpu cover its
+    /// column and the cpu copy of the instances matches it, once the changes are applied
+    pub fn check(&self, ctx: &GraphicsCtx) -> Result<(), String> {
+        self.instance_buffer.check()?;
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Check instance ranges"),
+            });
+        self.record_ranges(ctx, &mut encoder);
+        ctx.queue.submit(Some(encoder.finish()));
+        let args = read_buffer(
+            ctx,
+            self.indirect_buffer.inner(),
+            0..self.mesh_count() as u64 * IndirectBuffer::ITEM_BYTE_SIZE,
+        );
+        let args: Vec<[u32; 5]> = bytemuck::pod_collect_to_vec(&args);
+        let counts = self.instances_count.iter().flatten();
+        for (column_id, (args, count)) in args.iter().zip(counts).enumerate() {
+            let range = self.instance_buffer.column_range(column_id as u16);
+            let [_, instance_count, _, _, first_instance] = *args;
+            // Nothing is drawn from an empty column, its first instance is left out
+            if range.len() != *count as usize
+                || instance_count != *count as u32
+                || (*count > 0 && first_instance != range.start as u32)
+            {
+                return Err(format!(
+                    "Mesh {column_id} draws {instance_count} instances from {first_instance}, its \
+                     column holds {count} at {range:?}"
+                ));
+            }
+            let column_id = column_id as u16;
+            let gpu = self.instance_buffer.read_column(ctx, column_id);
+            let live = &self.live[column_id as usize];
+            let mirrored =
+                self.instance_buffer
+                    .column_ids(column_id)
+                    .zip(&gpu)
+                    .all(|(dense, instance)| {
+                        live.get(&dense).map(bytemuck::bytes_of)
+                            == Some(bytemuck::bytes_of(instance))
+                    });
+            if live.len() != gpu.len() || !mirrored {
+                return Err(format!(
+                    "Mesh {column_id} mirrors {} instances on the cpu, they differ from the {} of \
+                     its column",
+                    live.len(),
+                    gpu.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
     //TODO: Use staging belt please
     pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
-        let (_grown, changes) = self.instance_buffer.apply_changes(ctx);
-
-        for (column_id, change) in changes {
-            match change {
-                ColumnChange::Moved { new_offset } => {
-                    self.indirect_buffer.write_first_instance_at_index(
-                        ctx,
-                        column_id as u32,
-                        new_offset as u32,
-                    );
-                }
-                ColumnChange::Resized { new_size } => {
-                    self.indirect_buffer.write_instance_count_at_index(
-                        ctx,
-                        column_id as u32,
-                        new_size as u32,
-                    );
+        if std::mem::take(&mut self.geometry_changed) {
+            // The painted colors are uploaded with the rest
+            self.recolored.clear();
+            self.upload_geometry(ctx);
+        }
+        for (model_id, mesh_id) in self.recolored.drain(..) {
+            let range = self.mesh_ranges[model_id as usize][mesh_id as usize];
+            let vertices = &self.geometry[model_id as usize][mesh_id as usize].vertices[..];
+            let offset = range.placement.base_vertex as u32;
+            match range.precision {
+                VertexPrecision::Full => self
+                    .vertex_buffer
+                    .write_array_at_index(ctx, &vertices, offset),
+                VertexPrecision::Half => {
+                    let packed: Vec<_> = vertices.iter().map(PackedVertex::from).collect();
+                    self.packed_vertex_buffer
+                        .write_array_at_index(ctx, &packed, offset)
                 }
             }
         }
+
+        self.instance_buffer.apply_changes(ctx);
+    }
+
+    /// Lays the loaded meshes out again in new buffers and points their draw args at them
+    fn upload_geometry(&mut self, ctx: &GraphicsCtx) {
+        let precisions: Vec<_> = self
+            .mesh_ranges
+            .iter()
+            .flatten()
+            .map(|range| range.precision)
+            .collect();
+        let (full_vertices, packed_vertices, indices, placements) =
+            pack_geometry(&self.geometry, &precisions);
+
+        let vertex_buffer = VertexBuffer::new_array("Models vertices", ctx, &full_vertices);
+        let packed_vertex_buffer =
+            VertexBuffer::new_array("Models packed vertices", ctx, &packed_vertices);
+        let index_buffer = IndexBuffer::new_const_array("Models indices", ctx, &indices[..]);
+        ctx.retire(std::mem::replace(&mut self.vertex_buffer, vertex_buffer));
+        ctx.retire(std::mem::replace(
+            &mut self.packed_vertex_buffer,
+            packed_vertex_buffer,
+        ));
+        ctx.retire(std::mem::replace(&mut self.index_buffer, index_buffer));
+
+        let ranges = self
+            .mesh_ranges
+            .iter_mut()
+            .zip(&self.geometry)
+            .flat_map(|(ranges, meshes)| ranges.iter_mut().zip(meshes));
+        for (column_id, ((range, mesh), placement)) in ranges.zip(placements).enumerate() {
+            range.placement = placement;
+            range.lightmapped = mesh.is_lightmapped();
+            self.indirect_buffer.write_geometry_at_index(
+                ctx,
+                column_id as u32,
+                placement.index_count,
+                placement.first_index,
+                placement.base_vertex,
+            );
+        }
     }
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub tex_coords: [f32; 2],
+    /// Position in the lightmap rect of the instance, zero on meshes without lightmap
+    pub lightmap_uv: [f32; 2],
+    /// Ambient light left by the model around the vertex, 1 when not baked
+    pub ao: f32,
+    /// Linear unorm multiplied with the albedo, the alpha is unused
+    pub color: [u8; 4],
+}
+
+impl Default for ModelVertex {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            normal: [0.0; 3],
+            tex_coords: [0.0; 2],
+            lightmap_uv: [0.0; 2],
+            ao: 0.0,
+            color: [u8::MAX; 4],
+        }
+    }
 }
 
 impl ModelVertex {
@@ -285,105 +841,469 @@ impl ModelVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x2,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Unorm8x4,
+                },
             ],
         }
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ModelInstance {
-    pub transform: [[f32; 4]; 4],
-    pub material_id: u32,
+/// Storage of the vertices of a model, chosen at import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+pub enum VertexPrecision {
+    /// [`ModelVertex`], for large meshes and the ones needing exact positions
+    #[default]
+    Full,
+    /// [`PackedVertex`], less vertex fetch bandwidth for dense meshes
+    Half,
 }
 
-impl ModelInstance {
-    pub fn new(transform: Matrix4<f32>, material_id: u32) -> Self {
-        Self {
-            transform: transform.into(),
-            material_id,
+impl VertexPrecision {
+    pub const ALL: [Self; 2] = [Self::Full, Self::Half];
+
+    pub fn buffer_desc(self) -> wgpu::VertexBufferLayout<'static> {
+        match self {
+            Self::Full => ModelVertex::buffer_desc(),
+            Self::Half => PackedVertex::buffer_desc(),
         }
     }
+}
+
+/// [`ModelVertex`] in 32 bytes instead of 48, read by the same shader inputs
+///
+/// Half float positions keep about 3 significant digits, meshes spanning more than a few hundred
+/// units should stay at full precision
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PackedVertex {
+    /// Half floats, w is unused
+    pub position: [u16; 4],
+    /// Half floats, w is unused
+    pub normal: [u16; 4],
+    /// Half floats
+    pub tex_coords: [u16; 2],
+    /// Unorm, the lightmap uvs are within the rect of the instance
+    pub lightmap_uv: [u16; 2],
+    /// Unorm in x
+    pub ao: [u8; 4],
+    pub color: [u8; 4],
+}
 
+impl PackedVertex {
+    /// Shader locations of [`ModelVertex::buffer_desc`], the extra components are ignored
     pub fn buffer_desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<ModelInstance>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Instance,
+            array_stride: std::mem::size_of::<PackedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
                     offset: 0,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x4,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float16x4,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float16x4,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 16,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float16x2,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: wgpu::VertexFormat::Float32x4,
+                    offset: 20,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Unorm16x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: 24,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Unorm8x4,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
-                    shader_location: 7,
-                    format: wgpu::VertexFormat::Uint32,
+                    offset: 28,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Unorm8x4,
                 },
             ],
         }
     }
 }
 
+impl From<&ModelVertex> for PackedVertex {
+    fn from(vertex: &ModelVertex) -> Self {
+        let [x, y, z] = vertex.position;
+        let [nx, ny, nz] = vertex.normal;
+        Self {
+            position: [x, y, z, 1.0].map(f32_to_f16),
+            normal: [nx, ny, nz, 0.0].map(f32_to_f16),
+            tex_coords: vertex.tex_coords.map(f32_to_f16),
+            lightmap_uv: vertex
+                .lightmap_uv
+                .map(|c| (c.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16),
+            ao: [(vertex.ao.clamp(0.0, 1.0) * 255.0).round() as u8, 0, 0, 0],
+            color: vertex.color,
+        }
+    }
+}
+
+/// Quaternion components are stored as snorm, the precision loss is under 1e-4 radians
+const SNORM_MAX: f32 = i16::MAX as f32;
+
+/// Compact affine transform, translation then rotation then scale, rebuilt in the shaders
+///
+/// 64 bytes with the other attributes, instead of 96 with a full matrix. Transforms with a shear
+/// cannot be represented, the nearest rotation is kept
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelInstance {
+    pub translation: [f32; 3],
+    pub material_id: u32,
+    /// Unit quaternion as snorm, xyz then w, see [`Self::rotation`]
+    pub rotation: [i16; 4],
+    /// Along the model axes, the x axis is negated for mirrored transforms
+    pub scale: [f32; 3],
+    /// Id returned by `InstanceAnimator::add`, 0 for static instances
+    pub animation_id: u16,
+    /// Id returned by `InstanceParams::add`, 0 for instances without a parameter block
+    pub params_id: u16,
+    /// Id returned by `ZoneCulling::add_zone`, 0 for instances always drawn
+    pub zone_id: u16,
+    /// Screen door dissolve of a level of detail switch as snorm, see [`Self::with_fade`]
+    pub fade: i16,
+    /// Srgb color blended over the shaded instance, by its linear alpha
+    pub tint: [u8; 4],
+    /// Offset then scale of the baked rect in the lightmap, zero scale without one
+    pub lightmap: [f32; 4],
+}
+
+crate::wgsl_layout!(ModelInstance as "Instance" {
+    translation,
+    material_id,
+    rotation,
+    scale,
+    animation_id: animation_params,
+    zone_id: zone_fade,
+    tint,
+    lightmap,
+});
+
+impl ModelInstance {
+    pub fn new(transform: Matrix4<f32>, material_id: u32) -> Self {
+        let mut instance = Self {
+            translation: [0.0; 3],
+            material_id,
+            rotation: [0; 4],
+            scale: [1.0; 3],
+            animation_id: 0,
+            params_id: 0,
+            zone_id: 0,
+            fade: 0,
+            tint: [0; 4],
+            lightmap: [0.0; 4],
+        };
+        instance.set_transform(&transform);
+        instance
+    }
+
+    pub fn transform(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation.into())
+            * self.rotation().to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale.into())
+    }
+
+    pub fn set_transform(&mut self, transform: &Matrix4<f32>) {
+        let (translation, rotation, scale) = decompose(transform);
+        self.translation = translation.into();
+        self.set_rotation(rotation);
+        self.scale = scale.into();
+    }
+
+    pub fn rotation(&self) -> UnitQuaternion<f32> {
+        let [x, y, z, w] = self.rotation.map(|c| c as f32 / SNORM_MAX);
+        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z))
+    }
+
+    pub fn set_rotation(&mut self, rotation: UnitQuaternion<f32>) {
+        let coords = rotation.coords;
+        self.rotation = [coords.x, coords.y, coords.z, coords.w]
+            .map(|c| (c.clamp(-1.0, 1.0) * SNORM_MAX).round() as i16);
+    }
+
+    /// The transform is overwritten every frame by the animation pass
+    pub fn with_animation(self, animation_id: u32) -> Self {
+        Self {
+            animation_id: animation_id as u16,
+            ..self
+        }
+    }
+
+    /// Read by the dissolve and the custom material shaders, see [`super::params::InstanceParams`]
+    pub fn with_params(self, params_id: u32) -> Self {
+        Self {
+            params_id: params_id as u16,
+            ..self
+        }
+    }
+
+    pub fn in_zone(self, zone_id: u32) -> Self {
+        Self {
+            zone_id: zone_id as u16,
+            ..self
+        }
+    }
+
+    /// Progress of a level of detail switch, from 0 to 1. The new level fades in with a positive
+    /// value and the old one out with its negation, they draw complementary pixels. 0 is no fade
+    pub fn with_fade(self, fade: f32) -> Self {
+        let fade = (fade.clamp(-1.0, 1.0) * SNORM_MAX).round() as i16;
+        Self { fade, ..self }
+    }
+
+    pub fn with_tint(self, tint: [u8; 4]) -> Self {
+        Self { tint, ..self }
+    }
+
+    /// Rect returned by [`super::lightmap::bake::bake_lightmap`]
+    pub fn with_lightmap(self, lightmap: [f32; 4]) -> Self {
+        Self { lightmap, ..self }
+    }
+
+    pub fn buffer_desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ModelInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &INSTANCE_ATTRIBUTES,
+        }
+    }
+}
+
+/// Attributes of [`ModelInstance`], the transform is read from locations 3 to 5. The ids, the
+/// fade and the animation id only read by the compute passes, are read together as two u32
+pub const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 7] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 3,
+        format: wgpu::VertexFormat::Float32x3,
+    },
+    wgpu::VertexAttribute {
+        offset: 12,
+        shader_location: 7,
+        format: wgpu::VertexFormat::Uint32,
+    },
+    wgpu::VertexAttribute {
+        offset: 16,
+        shader_location: 4,
+        format: wgpu::VertexFormat::Snorm16x4,
+    },
+    wgpu::VertexAttribute {
+        offset: 24,
+        shader_location: 5,
+        format: wgpu::VertexFormat::Float32x3,
+    },
+    wgpu::VertexAttribute {
+        offset: 36,
+        shader_location: 8,
+        format: wgpu::VertexFormat::Uint32x2,
+    },
+    wgpu::VertexAttribute {
+        offset: 44,
+        shader_location: 9,
+        format: wgpu::VertexFormat::Unorm8x4,
+    },
+    wgpu::VertexAttribute {
+        offset: 48,
+        shader_location: 11,
+        format: wgpu::VertexFormat::Float32x4,
+    },
+];
+
+/// Translation, rotation and scale of an affine transform
+pub fn decompose(transform: &Matrix4<f32>) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>) {
+    let translation = transform.fixed_view::<3, 1>(0, 3).into_owned();
+    let mut basis = transform.fixed_view::<3, 3>(0, 0).into_owned();
+    let mut scale = Vector3::from_fn(|i, _| basis.column(i).norm());
+    if basis.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    for (i, scale) in scale.iter().enumerate() {
+        if *scale != 0.0 {
+            basis.column_mut(i).unscale_mut(*scale);
+        }
+    }
+    (translation, UnitQuaternion::from_matrix(&basis), scale)
+}
+
 pub struct MaterialsBuffer {
     pub storage_buffer: StorageBuffer<Material>,
     pub bind_group: wgpu::BindGroup,
     pub len: u32,
+
+    materials: Vec<Material>,
+    changed: Vec<u32>,
 }
 
 impl MaterialsBuffer {
-    pub fn new(ctx: &GraphicsCtx, materials: &[Material]) -> Self {
+    /// Zones visibility, the wind and the instance parameter blocks are bound alongside, see
+    /// [`super::zones::ZoneCulling`]
+    pub fn new(
+        ctx: &GraphicsCtx,
+        materials: &[Material],
+        zones_visibility: &StorageBuffer<u32>,
+        wind: &UniformBuffer<RawWind>,
+        instance_params: &StorageBuffer<ParamBlock>,
+    ) -> Self {
         let storage_buffer = StorageBuffer::new_array("Materials", ctx, materials);
-
-        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &materials_buffer_bind_group_layout(ctx),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: storage_buffer.binding(),
-            }],
-            label: Some("Materials Bind Group"),
-        });
+        let bind_group = materials_bind_group(
+            ctx,
+            &storage_buffer,
+            zones_visibility,
+            wind,
+            instance_params,
+        );
 
         Self {
             storage_buffer,
             bind_group,
             len: materials.len() as u32,
+            materials: materials.to_vec(),
+            changed: Vec::new(),
         }
     }
 
+    /// Called when a buffer bound alongside is reallocated
+    pub fn rebind(
+        &mut self,
+        ctx: &GraphicsCtx,
+        zones_visibility: &StorageBuffer<u32>,
+        wind: &UniformBuffer<RawWind>,
+        instance_params: &StorageBuffer<ParamBlock>,
+    ) {
+        self.bind_group = materials_bind_group(
+            ctx,
+            &self.storage_buffer,
+            zones_visibility,
+            wind,
+            instance_params,
+        );
+    }
+
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    pub fn get(&self, material_id: u32) -> Option<&Material> {
+        self.materials.get(material_id as usize)
+    }
+
+    /// Uploaded by `apply_changes`
+    pub fn set(&mut self, material_id: u32, material: Material) {
+        self.materials[material_id as usize] = material;
+        self.changed.push(material_id);
+    }
+
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
+        for material_id in self.changed.drain(..) {
+            self.storage_buffer.write_at_index(
+                ctx,
+                &self.materials[material_id as usize],
+                material_id,
+            );
+        }
+    }
+}
+
+fn materials_bind_group(
+    ctx: &GraphicsCtx,
+    materials: &StorageBuffer<Material>,
+    zones_visibility: &StorageBuffer<u32>,
+    wind: &UniformBuffer<RawWind>,
+    instance_params: &StorageBuffer<ParamBlock>,
+) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &materials_buffer_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: materials.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: zones_visibility.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wind.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: instance_params.binding(),
+            },
+        ],
+        label: Some("Materials Bind Group"),
+    })
 }
 
 pub fn materials_buffer_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
     ctx.device
         .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    // Instances of other shaders are clipped in the vertex stage
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
             label: Some("Materials Bind Group Layout"),
         })
 }
@@ -394,10 +1314,255 @@ pub struct Material {
     pub diffuse_color: [f32; 3],
 
     pub diffuse_texture_id: u32,
+    /// Index into [`crate::graphics::sampler::material_samplers`]
+    pub sampler_id: u32,
+    /// 0 for the built-in shading, see [`super::renderer::EntitiesRenderer::add_material_shader`]
+    pub shader_id: u32,
+    /// How much the vertices sway with the wind, 0 for static surfaces
+    pub wind: f32,
+    /// Distance over which the surface fades out in front of the scene, 0 for hard intersections
+    ///
+    /// Fading materials are drawn after the opaque ones and do not write the depth
+    pub depth_fade: f32,
+}
+
+crate::wgsl_layout!(Material as "Material" {
+    diffuse_color,
+    diffuse_texture_id: diffuse_tex_id,
+    sampler_id,
+    shader_id,
+    wind,
+    depth_fade,
+});
+
+/// Vertices of the obj mesh with its baked data
+fn model_vertices<'a>(
+    mesh: &'a Mesh,
+    baked: &'a BakedVertices,
+) -> impl Iterator<Item = ModelVertex> + 'a {
+    (0..mesh.positions.len() / 3).map(move |i| {
+        let lightmap_uv = if baked.lightmap_uvs.is_empty() {
+            [0.0; 2]
+        } else {
+            [baked.lightmap_uvs[i * 2], baked.lightmap_uvs[i * 2 + 1]]
+        };
+        let ao = baked.ao.get(i).copied().unwrap_or(1.0);
+        let color = match mesh.vertex_color.get(i * 3..i * 3 + 3) {
+            Some(&[r, g, b]) => [r, g, b, 1.0].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8),
+            _ => [u8::MAX; 4],
+        };
+        if mesh.normals.is_empty() {
+            ModelVertex {
+                position: [
+                    mesh.positions[i * 3],
+                    mesh.positions[i * 3 + 1],
+                    mesh.positions[i * 3 + 2],
+                ],
+                tex_coords: [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]],
+                normal: [0.0, 0.0, 0.0],
+                lightmap_uv,
+                ao,
+                color,
+            }
+        } else {
+            ModelVertex {
+                position: [
+                    mesh.positions[i * 3],
+                    mesh.positions[i * 3 + 1],
+                    mesh.positions[i * 3 + 2],
+                ],
+                tex_coords: [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]],
+                normal: [
+                    mesh.normals[i * 3],
+                    mesh.normals[i * 3 + 1],
+                    mesh.normals[i * 3 + 2],
+                ],
+                lightmap_uv,
+                ao,
+                color,
+            }
+        }
+    })
+}
+
+/// Meshes of every model laid out in order, their vertices in the buffer of their precision
+///
+/// The vertex buffers are kept non empty, wgpu has no empty slices
+fn pack_geometry(
+    geometry: &[Vec<MeshGeometry>],
+    precisions: &[VertexPrecision],
+) -> (
+    Vec<ModelVertex>,
+    Vec<PackedVertex>,
+    Vec<u16>,
+    Vec<MeshPlacement>,
+) {
+    let mut full = Vec::new();
+    let mut packed = Vec::new();
+    let mut indices = Vec::new();
+    let mut placements = Vec::with_capacity(precisions.len());
+    for (mesh, precision) in geometry.iter().flatten().zip(precisions) {
+        let base_vertex = match precision {
+            VertexPrecision::Full => {
+                full.extend_from_slice(&mesh.vertices);
+                full.len() - mesh.vertices.len()
+            }
+            VertexPrecision::Half => {
+                packed.extend(mesh.vertices.iter().map(PackedVertex::from));
+                packed.len() - mesh.vertices.len()
+            }
+        };
+        placements.push(MeshPlacement {
+            first_index: indices.len() as u32,
+            index_count: mesh.indices.len() as u32,
+            base_vertex: base_vertex as i32,
+        });
+        indices.extend_from_slice(&mesh.indices);
+    }
+    if full.is_empty() {
+        full.push(ModelVertex::default());
+    }
+    if packed.is_empty() {
+        packed.push(bytemuck::Zeroable::zeroed());
+    }
+    if indices.is_empty() {
+        indices.push(0);
+    }
+    (full, packed, indices, placements)
 }
 
+/// Optional processing of a model at import, read from its sidecar by [`load_model`]
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    /// Unit of the exported positions, converted to meters
+    pub units: Units,
+    /// Applied to the positions on top of the units, after the axis conversion
+    pub scale: f32,
+    pub up_axis: UpAxis,
+    /// Replaces the exported normals by smooth ones
+    pub generate_normals: bool,
+    /// Bakes a tangent per vertex from the texture coordinates, see [`import::generate_tangents`]
+    pub generate_tangents: bool,
+    /// One mesh per material, see [`import::merge_meshes`]
+    pub merge_meshes: bool,
+    /// Shares the identical vertices, see [`import::dedup_vertices`]
+    pub dedup_vertices: bool,
+    /// Diffuse color of the material given to the meshes without one
+    pub default_material: Option<[f32; 3]>,
+    /// Bakes the occlusion of the model by itself into its vertices
+    pub ambient_occlusion: Option<AoBake>,
+    /// Half precision saves bandwidth on dense meshes, see [`PackedVertex`]
+    pub precision: VertexPrecision,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            units: Units::Meters,
+            scale: 1.0,
+            up_axis: UpAxis::Y,
+            generate_normals: false,
+            generate_tangents: false,
+            merge_meshes: false,
+            dedup_vertices: false,
+            default_material: None,
+            ambient_occlusion: None,
+            precision: VertexPrecision::Full,
+        }
+    }
+}
+
+/// With the options of the sidecar of the model, see [`import::load_sidecar`]
 pub fn load_model(model_name: &str) -> EntityModel {
-    let model_file = ASSETS.models.get(model_name).unwrap();
+    let options = import::load_sidecar(model_name).unwrap_or_else(|e| {
+        eprintln!("Ignoring the import options of {model_name}: {e}");
+        ImportOptions::default()
+    });
+    load_model_with(model_name, &options)
+}
+
+/// Meshes of the model without its textures, to load its geometry again once streamed out
+pub fn load_model_geometry(model_name: &str) -> StreamedGeometry {
+    let options = import::load_sidecar(model_name).unwrap_or_else(|e| {
+        eprintln!("Ignoring the import options of {model_name}: {e}");
+        ImportOptions::default()
+    });
+    let model = load_processed_model(model_name, &options);
+    StreamedGeometry {
+        meshes: model.meshes,
+        baked: model.baked,
+    }
+}
+
+/// The processed model with its textures decoded
+pub fn load_model_with(model_name: &str, options: &ImportOptions) -> EntityModel {
+    let model = load_processed_model(model_name, options);
+
+    let assets = ASSETS.tree();
+    let texture_files: Vec<_> = model
+        .textures
+        .iter()
+        .map(|texture| assets.textures.get(texture).unwrap())
+        .collect();
+    decode_textures(&texture_files);
+
+    let (textures, sprite_sheets) = model
+        .textures
+        .iter()
+        .zip(texture_files)
+        .map(|(name, file)| {
+            let options = import::load_texture_sidecar(name).unwrap_or_else(|e| {
+                eprintln!("Ignoring the import options of texture {name}: {e}");
+                TextureImportOptions::default()
+            });
+            (
+                import::process_texture(file.image(), &options),
+                options.sprite_sheet,
+            )
+        })
+        .unzip();
+    let color_spaces = model
+        .textures
+        .iter()
+        .map(|name| ColorSpace::from_texture_name(name))
+        .collect();
+
+    EntityModel {
+        meshes: model.meshes,
+        baked: model.baked,
+        precision: options.precision,
+        textures,
+        sprite_sheets,
+        color_spaces,
+        materials: model.materials,
+    }
+}
+
+/// Reuses the processed data cached by a previous run when the sources and options are the same
+fn load_processed_model(model_name: &str, options: &ImportOptions) -> ProcessedModel {
+    let key = cache::cache_key(model_name, options);
+    let mut model = match key.and_then(|key| cache::read(model_name, key)) {
+        Some(model) => model,
+        None => {
+            let model = import_model(model_name, options);
+            if let Some(key) = key {
+                if let Err(e) = cache::write(model_name, key, &model) {
+                    eprintln!("Failed to cache model {model_name}: {e}");
+                }
+            }
+            model
+        }
+    };
+    if let Err(e) = import::load_vertex_colors(model_name, &mut model.meshes) {
+        eprintln!("Ignoring the painted colors of {model_name}: {e}");
+    }
+    model
+}
+
+/// Parses and processes the model, the slow part cached by [`load_processed_model`]
+fn import_model(model_name: &str, options: &ImportOptions) -> ProcessedModel {
+    let assets = ASSETS.tree();
+    let model_file = assets.models.get(model_name).unwrap();
     let obj_cursor = Cursor::new(model_file.0.clone());
     let mut obj_reader = BufReader::new(obj_cursor);
     let (models, mat_res) = tobj::load_obj_buf(
@@ -413,7 +1578,7 @@ pub fn load_model(model_name: &str) -> EntityModel {
                 .unwrap_or_else(|| panic!("Invalid material name {p:?} in model {model_name}"))
                 .strip_suffix(".mtl")
                 .expect("Invalid material file type {m:?} in model {model_name}. Expected .mtl");
-            let material_file = ASSETS
+            let material_file = assets
                 .materials
                 .get(&material)
                 .unwrap_or_else(|| panic!("Failed to load material {material}"));
@@ -423,31 +1588,115 @@ pub fn load_model(model_name: &str) -> EntityModel {
         },
     )
     .expect("Failed to load model");
-    let materials: Vec<_> = mat_res.expect("Failed to load materials");
+    let mut materials: Vec<_> = mat_res.expect("Failed to load materials");
 
-    EntityModel {
-        meshes: models.into_iter().map(|m| m.mesh).collect(),
-        textures: materials
-            .iter()
-            .filter_map(|m| {
-                let texture_file = m
-                    .diffuse_texture
-                    .as_ref()?;
-                let texture = texture_file
-                    .strip_suffix(".png")
-                    .or(texture_file.strip_prefix(".jpg"))
-                    .expect("Invalid texture file type {m:?} in model {model_name}. Expected .png or .jpg");
-                Some(ASSETS.textures.get(texture).unwrap().0.clone())
+    let textures: Vec<_> = materials
+        .iter()
+        .filter_map(|m| {
+            let texture_file = m.diffuse_texture.as_ref()?;
+            let texture = texture_file
+                .strip_suffix(".png")
+                .or(texture_file.strip_prefix(".jpg"))
+                .expect(
+                    "Invalid texture file type {m:?} in model {model_name}. Expected .png or .jpg",
+                );
+            Some(texture.to_string())
+        })
+        .collect();
+
+    let mut meshes: Vec<_> = models.into_iter().map(|m| m.mesh).collect();
+    if let Some(diffuse) = options.default_material {
+        if meshes.iter().any(|mesh| mesh.material_id.is_none()) {
+            let id = materials.len();
+            materials.push(tobj::Material {
+                name: String::from("default"),
+                diffuse: Some(diffuse),
+                ..Default::default()
+            });
+            for mesh in meshes.iter_mut().filter(|mesh| mesh.material_id.is_none()) {
+                mesh.material_id = Some(id);
+            }
+        }
+    }
+    if options.merge_meshes {
+        meshes = import::merge_meshes(meshes);
+    }
+    if options.dedup_vertices {
+        meshes.iter_mut().for_each(import::dedup_vertices);
+    }
+    let scale = options.units.meters() * options.scale;
+    import::convert_space(&mut meshes, options.up_axis, scale);
+    if options.generate_normals {
+        meshes.iter_mut().for_each(import::generate_normals);
+    }
+    // Non standard `lightmap` mtl statement, the static meshes of the material get baked light
+    let lightmap_uvs: Vec<_> = meshes
+        .iter_mut()
+        .map(|mesh| {
+            let material = mesh.material_id.and_then(|id| materials.get(id));
+            if !material.is_some_and(|m| m.unknown_param.contains_key("lightmap")) {
+                return Vec::new();
+            }
+            unwrap_lightmap_uvs(mesh).unwrap_or_else(|| {
+                eprintln!("Mesh of {model_name} has too many triangles for a lightmap");
+                Vec::new()
             })
-            .collect(),
+        })
+        .collect();
+    // After the unwrap, which unshares the vertices
+    let mut ao = match &options.ambient_occlusion {
+        Some(bake) => bake_vertex_ao(&meshes, bake),
+        None => vec![Vec::new(); meshes.len()],
+    };
+    let baked = lightmap_uvs
+        .into_iter()
+        .zip(&mut ao)
+        .zip(&meshes)
+        .map(|((lightmap_uvs, ao), mesh)| BakedVertices {
+            lightmap_uvs,
+            ao: std::mem::take(ao),
+            tangents: match options.generate_tangents {
+                true => import::generate_tangents(mesh),
+                false => Vec::new(),
+            },
+        })
+        .collect();
+
+    ProcessedModel {
+        meshes,
+        baked,
+        textures,
         materials: materials
             .into_iter()
             .map(|m| Material {
+                // Kd is linear, as exported from the authoring tools
                 diffuse_color: m.diffuse.unwrap_or(Color3::WHITE.into()),
-                diffuse_texture_id: match  m.diffuse_texture {
+                diffuse_texture_id: match m.diffuse_texture {
                     None => u32::MAX,
                     Some(_) => 0,
                 },
+                // Non standard `sampler <nearest|linear>` mtl statement
+                sampler_id: match m.unknown_param.get("sampler").map(|s| s.trim()) {
+                    Some("linear") => 1,
+                    _ => 0,
+                },
+                // Non standard `shading <standard|toon>` mtl statement
+                shader_id: match m.unknown_param.get("shading").map(|s| s.trim()) {
+                    Some("toon") => TOON_SHADER_ID,
+                    _ => 0,
+                },
+                // Non standard `wind <weight>` mtl statement
+                wind: m
+                    .unknown_param
+                    .get("wind")
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(0.0),
+                // Non standard `depth_fade <distance>` mtl statement
+                depth_fade: m
+                    .unknown_param
+                    .get("depth_fade")
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(0.0),
             })
             .collect(),
     }
@@ -0,0 +1,68 @@
+use crate::graphics::{
+    buffer::{MappedSparse, StorageBuffer},
+    ctx::GraphicsCtx,
+};
+
+/// Vectors in a parameter block
+pub const PARAM_BLOCK_LEN: usize = 4;
+
+/// Values of one instance read by the custom material shaders, like a dissolve amount or a
+/// highlight phase, meaning is up to the shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+pub struct ParamBlock {
+    pub values: [[f32; 4]; PARAM_BLOCK_LEN],
+    /// Edge color then progress of the built-in dissolve, see [`super::dissolve::Dissolves`]
+    pub dissolve: [f32; 4],
+}
+
+crate::wgsl_layout!(ParamBlock as "ParamBlock" { values, dissolve });
+
+/// Per instance parameter blocks, referenced by `ModelInstance::params_id`
+///
+/// Kept apart from the instances so only the ones with per object effects pay for them. At most
+/// `u16::MAX` blocks
+pub struct InstanceParams {
+    pub blocks: MappedSparse<StorageBuffer<ParamBlock>>,
+    /// Cpu side blocks, freed ones included
+    values: Vec<ParamBlock>,
+}
+
+impl InstanceParams {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        Self {
+            blocks: MappedSparse::new("Instance params", ctx, Vec::new()),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the id to store in `ModelInstance::params_id`
+    pub fn add(&mut self, block: ParamBlock) -> u32 {
+        let index = self.blocks.push(block);
+        if index as usize >= self.values.len() {
+            self.values
+                .resize(index as usize + 1, ParamBlock::default());
+        }
+        self.values[index as usize] = block;
+        index + 1
+    }
+
+    pub fn get(&self, params_id: u32) -> Option<&ParamBlock> {
+        self.values.get(params_id.checked_sub(1)? as usize)
+    }
+
+    pub fn set(&mut self, params_id: u32, block: ParamBlock) {
+        self.blocks.set(params_id - 1, block);
+        self.values[params_id as usize - 1] = block;
+    }
+
+    pub fn remove(&mut self, params_id: u32) {
+        self.blocks.remove(params_id - 1);
+        self.values[params_id as usize - 1] = ParamBlock::default();
+    }
+
+    /// Returns true when the buffer was reallocated, the materials must be bound again
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) -> bool {
+        self.blocks.apply_changes(ctx)
+    }
+}
@@ -0,0 +1,101 @@
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, Growable, IndirectBuffer, StorageBuffer},
+    ctx::GraphicsCtx,
+};
+
+/// Compute pass writing the instance count and first instance of the draw args of every mesh,
+/// from the column owning each instance slot
+///
+/// The draw generation, the blob shadows and the meshlets read their instances from these args,
+/// it is recorded before them every frame
+pub struct InstanceRanges {
+    clear_pipeline: wgpu::ComputePipeline,
+    count_pipeline: wgpu::ComputePipeline,
+}
+
+impl InstanceRanges {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("ranges.wgsl"));
+        let layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&ranges_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |label, entry_point| {
+            ctx.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                })
+        };
+
+        Self {
+            clear_pipeline: pipeline("Clear instance ranges", "cs_clear"),
+            count_pipeline: pipeline("Count instance ranges", "cs_count"),
+        }
+    }
+
+    pub fn record(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        args: &IndirectBuffer,
+        mesh_count: u32,
+        owners: &Growable<StorageBuffer<u32>>,
+    ) {
+        // The owners are reallocated when the instances grow, binding them every frame keeps up
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &ranges_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: args.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: owners.binding(),
+                },
+            ],
+            label: Some("Instance Ranges Bind Group"),
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance ranges"),
+            timestamp_writes: None,
+        });
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.set_pipeline(&self.clear_pipeline);
+        pass.dispatch_workgroups(mesh_count.div_ceil(64), 1, 1);
+        pass.set_pipeline(&self.count_pipeline);
+        pass.dispatch_workgroups((owners.capacity() as u32).div_ceil(256), 1, 1);
+    }
+}
+
+fn ranges_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[storage(0, false), storage(1, true)],
+            label: Some("Instance Ranges Bind Group Layout"),
+        })
+}
@@ -0,0 +1,33 @@
+// Draw args of every mesh, see `wgpu::util::DrawIndexedIndirectArgs`
+@group(0) @binding(0)
+var<storage, read_write> args: array<atomic<u32>>;
+// Column of every instance slot plus one, 0 on the free ones. Each mesh is a column
+@group(0) @binding(1)
+var<storage, read> owners: array<u32>;
+
+const ARGS_WORDS: u32 = 5u;
+const INSTANCE_COUNT: u32 = 1u;
+const FIRST_INSTANCE: u32 = 4u;
+
+@compute @workgroup_size(64, 1, 1)
+fn cs_clear(@builtin(global_invocation_id) id: vec3u) {
+    let mesh = id.x;
+    if mesh >= arrayLength(&args) / ARGS_WORDS {
+        return;
+    }
+    atomicStore(&args[mesh * ARGS_WORDS + INSTANCE_COUNT], 0u);
+    // Lowered by `cs_count`, an empty mesh draws nothing from it
+    atomicStore(&args[mesh * ARGS_WORDS + FIRST_INSTANCE], 0xffffffffu);
+}
+
+// The instances of a column are packed from its first slot, the lowest one is where they start
+@compute @workgroup_size(256, 1, 1)
+fn cs_count(@builtin(global_invocation_id) id: vec3u) {
+    let slot = id.x;
+    if slot >= arrayLength(&owners) || owners[slot] == 0u {
+        return;
+    }
+    let mesh = owners[slot] - 1u;
+    atomicAdd(&args[mesh * ARGS_WORDS + INSTANCE_COUNT], 1u);
+    atomicMin(&args[mesh * ARGS_WORDS + FIRST_INSTANCE], slot);
+}
@@ -0,0 +1,170 @@
+use nalgebra::Vector3;
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, InstanceBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+};
+
+use super::{animation::InstanceAnimator, model::ModelInstance};
+
+/// Moves the instances already on the gpu when the world origin shifts, see
+/// [`crate::graphics::origin::WorldOrigin`]
+///
+/// Submitted on its own right away, instances written afterwards are already relative to the new
+/// origin
+pub struct InstanceRebase {
+    shift: UniformBuffer<[f32; 4]>,
+    instances_pipeline: wgpu::ComputePipeline,
+    animations_pipeline: wgpu::ComputePipeline,
+}
+
+impl InstanceRebase {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("rebase.wgsl"));
+        let pipeline = |label, entry_point, layouts: &[&wgpu::BindGroupLayout]| {
+            let layout = ctx
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: layouts,
+                    push_constant_ranges: &[],
+                });
+            ctx.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                })
+        };
+        let instances_layout = instances_bind_group_layout(ctx);
+        let animations_layout = animations_bind_group_layout(ctx);
+
+        Self {
+            shift: UniformBuffer::new("Rebase shift", ctx, &[0.0; 4]),
+            instances_pipeline: pipeline(
+                "Rebase instances",
+                "shift_instances",
+                &[&instances_layout],
+            ),
+            animations_pipeline: pipeline(
+                "Rebase animations",
+                "shift_animations",
+                &[&instances_layout, &animations_layout],
+            ),
+        }
+    }
+
+    /// Subtracts `shift` from the translation of every instance buffer and animation
+    pub fn shift(
+        &self,
+        ctx: &GraphicsCtx,
+        shift: Vector3<f32>,
+        instance_buffers: &[&InstanceBuffer<ModelInstance>],
+        animator: &InstanceAnimator,
+    ) {
+        self.shift.write(ctx, &[shift.x, shift.y, shift.z, 0.0]);
+        let mut encoder = ctx
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Rebase"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Rebase"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.instances_pipeline);
+            for instances in instance_buffers {
+                let count =
+                    instances.inner().size() / InstanceBuffer::<ModelInstance>::ITEM_BYTE_SIZE;
+                pass.set_bind_group(0, &self.instances_bind_group(ctx, instances), &[]);
+                pass.dispatch_workgroups((count as u32).div_ceil(64), 1, 1);
+            }
+
+            let animations = animator.animations.len();
+            if let (Some(instances), true) = (instance_buffers.first(), animations > 0) {
+                let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &animations_bind_group_layout(ctx),
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: animator.animations.binding(),
+                    }],
+                    label: Some("Rebase Animations Bind Group"),
+                });
+                pass.set_pipeline(&self.animations_pipeline);
+                pass.set_bind_group(0, &self.instances_bind_group(ctx, instances), &[]);
+                pass.set_bind_group(1, &bind_group, &[]);
+                pass.dispatch_workgroups(animations.div_ceil(64), 1, 1);
+            }
+        }
+        ctx.queue.submit(Some(encoder.finish()));
+    }
+
+    fn instances_bind_group(
+        &self,
+        ctx: &GraphicsCtx,
+        instances: &InstanceBuffer<ModelInstance>,
+    ) -> wgpu::BindGroup {
+        ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &instances_bind_group_layout(ctx),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.shift.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instances.binding(),
+                },
+            ],
+            label: Some("Rebase Instances Bind Group"),
+        })
+    }
+}
+
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn instances_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+            ],
+            label: Some("Rebase Instances Bind Group Layout"),
+        })
+}
+
+fn animations_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[storage_entry(0)],
+            label: Some("Rebase Animations Bind Group Layout"),
+        })
+}
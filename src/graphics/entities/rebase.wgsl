@@ -0,0 +1,64 @@
+// Matches `ModelInstance`, the vectors are kept as arrays to avoid the vec3 alignment
+struct Instance {
+    translation: array<f32, 3>,
+    material_id: u32,
+    // Snorm quaternion, xy then zw
+    rotation: array<u32, 2>,
+    scale: array<f32, 3>,
+    // Animation id in the low 16 bits, parameter block id in the high ones
+    animation_params: u32,
+    // Zone id in the low 16 bits, snorm level of detail fade in the high ones
+    zone_fade: u32,
+    tint: u32,
+    lightmap: array<f32, 4>,
+}
+
+// Matches `InstanceAnimation`
+struct InstanceAnimation {
+    base_translation: vec3f,
+    rotation_speed: f32,
+    base_rotation: vec4f,
+    base_scale: vec3f,
+    bob_amplitude: f32,
+    rotation_axis: vec3f,
+    bob_frequency: f32,
+    bob_axis: vec3f,
+    phase: f32,
+    path_speed: f32,
+    path_len: u32,
+    path: array<vec4f, 4>,
+}
+
+@group(0) @binding(0)
+var<uniform> shift: vec4f;
+@group(0) @binding(1)
+var<storage, read_write> instances: array<Instance>;
+
+@group(1) @binding(0)
+var<storage, read_write> animations: array<InstanceAnimation>;
+
+@compute @workgroup_size(64, 1, 1)
+fn shift_instances(@builtin(global_invocation_id) id: vec3u) {
+    if id.x >= arrayLength(&instances) {
+        return;
+    }
+    for (var i = 0u; i < 3u; i++) {
+        instances[id.x].translation[i] -= shift[i];
+    }
+}
+
+// Paths hold absolute points added to the base, only one of them is moved
+@compute @workgroup_size(64, 1, 1)
+fn shift_animations(@builtin(global_invocation_id) id: vec3u) {
+    if id.x >= arrayLength(&animations) {
+        return;
+    }
+    let len = animations[id.x].path_len;
+    if len == 0u {
+        animations[id.x].base_translation -= shift.xyz;
+        return;
+    }
+    for (var i = 0u; i < len; i++) {
+        animations[id.x].path[i] -= vec4f(shift.xyz, 0.0);
+    }
+}
@@ -1,156 +1,686 @@
-use nalgebra::{Matrix4, Vector3};
+use std::{collections::HashMap, sync::Arc};
+
+use nalgebra::{Matrix4, Point3, Vector3};
 use nd_iter::iter_3d;
-use wgpu::{include_wgsl, DepthStencilState};
-
-use crate::graphics::{
-    atlas::{atlas_uniform_bind_group_layout, AtlasPacker, AtlasUniform},
-    buffer::CommonBuffer,
-    camera::{view_proj_bind_group_layout, CameraUniform},
-    ctx::GraphicsCtx,
-    entities::model::materials_buffer_bind_group_layout,
-    light::{lights_buffer_bind_group_layout, LightsUniform},
-    utils::TextureWrapper,
+use wgpu::DepthStencilState;
+
+use crate::{
+    graphics::{
+        atlas::{atlas_uniform_bind_group_layout, AtlasSlot},
+        buffer::CommonBuffer,
+        camera::{view_proj_bind_group_layout, CameraUniform, RawRelativeView},
+        churn::{self, StateChanges},
+        ctx::GraphicsCtx,
+        depth::SceneDepth,
+        entities::model::materials_buffer_bind_group_layout,
+        frame::{FrameConstants, RawFrameConstants},
+        light::{lights_buffer_bind_group_layout, LightsUniform, RawLight},
+        motion::VELOCITY_FORMAT,
+        post::HDR_FORMAT,
+        reflect::ShaderReflection,
+        settings::{RenderSettings, ShadowFilter},
+        streaming::TextureStreamer,
+        utils::{stencil_state, TextureWrapper},
+    },
+    profile_scope, ASSETS,
 };
 
-use super::model::{load_model, MaterialsBuffer, ModelInstance, ModelVertex, ModelsBuffer};
+use super::{
+    animation::{InstanceAnimation, InstanceAnimator},
+    blobs::{BlobShadows, RawBlobParams},
+    dissolve::Dissolves,
+    draws::{DrawGenerator, RawCulling, VisibleInstance},
+    lightmap::Lightmap,
+    local_shadows::{RawLocalShadows, RawShadowView},
+    lod::LodInstances,
+    material_shader::{material_shader_bind_group_layout, MaterialShader, MaterialShaderDesc},
+    model::{
+        load_model, load_model_geometry, Material, MaterialsBuffer, ModelInstance, ModelsBuffer,
+        VertexPrecision,
+    },
+    params::{InstanceParams, ParamBlock},
+    rebase::InstanceRebase,
+    shadows::{shadows_bind_group_layout, RawCascades, ShadowCascades},
+    sprites::{split_sheets, SpriteAnimator},
+    statics::StaticBatches,
+    streaming::WorldStreamer,
+    text::{text_model, TextMeshOptions},
+    toon::{toon_shader_desc, ToonParams, TOON_SHADER_ID},
+    wind::{RawWind, Wind},
+    zones::ZoneCulling,
+    EntityModel,
+};
+
+#[cfg(feature = "meshlets")]
+use super::meshlets::MeshletCulling;
+
+/// Models loaded from the assets folder, their model id is their index
+pub const ASSET_MODELS: [&str; 2] = ["Astronaut", "Earth"];
+
+/// Placed from the editor, a lit label to check the text meshes. Its model id follows the ones
+/// of [`ASSET_MODELS`]
+pub fn label_model() -> EntityModel {
+    let assets = ASSETS.tree();
+    let font = &assets.fonts.get("DejaVuSansMono").unwrap().0;
+    text_model(font, "Foreigntech", &TextMeshOptions::default())
+        .expect("Failed to build the label model")
+}
 
 pub struct EntitiesRenderer {
     pub models: ModelsBuffer,
     pub materials: MaterialsBuffer,
-    pub atlas: AtlasUniform,
+    pub textures: TextureStreamer,
+    pub sprites: SpriteAnimator,
+    pub animator: InstanceAnimator,
+    /// Read by the dissolve and the custom material shaders, see [`ModelInstance::with_params`]
+    pub instance_params: InstanceParams,
+    pub dissolves: Dissolves,
+    pub world: WorldStreamer,
+    pub lods: LodInstances,
+    pub zones: ZoneCulling,
+    pub wind: Wind,
+    pub shadows: ShadowCascades,
+    /// Drawn instead of [`Self::shadows`] when the shadow maps are disabled
+    pub blobs: BlobShadows,
+    pub draws: DrawGenerator,
+    /// Instances made static, merged out of [`Self::models`]
+    pub statics: StaticBatches,
+    /// Only for the main camera, `None` when the device cannot count the draws
+    #[cfg(feature = "meshlets")]
+    pub meshlets: Option<MeshletCulling>,
+    /// Baked light of the static instances, see [`super::lightmap::bake::bake_lightmap`]
+    pub lightmap: Lightmap,
+    rebase: InstanceRebase,
+
+    /// Draws the entities with the wireframe pipeline when the device supports it
+    pub wireframe: bool,
 
-    pipeline: wgpu::RenderPipeline,
+    /// Every built-in pipeline is created from it, the variants only differ by their constants
+    shader: wgpu::ShaderModule,
+    pipelines: EntitiesPipelines,
+    /// Set as a constant of every entities pipeline
+    shadow_filter: ShadowFilter,
+    /// Indexed by `shader_id - 1`
+    material_shaders: Vec<MaterialShader>,
+}
+
+pub(crate) const SHADER_SOURCE: &str = include_str!("shader.wgsl");
+
+/// The entities are drawn in two passes, the scene depth is copied in between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntitiesPass {
+    /// Materials without depth fade, writing the depth
+    Opaque,
+    /// Materials with a depth fade, read against the depth of the opaque pass
+    Fade,
+    /// Opaque materials drawn only where the stencil equals the reference of the render pass, into
+    /// a [`TextureWrapper::DEPTH_STENCIL_FORMAT`] depth
+    Masked,
+}
+
+/// Pipelines of one entities shader for both passes and each vertex precision
+pub(super) struct EntitiesPipelines {
+    /// Indexed by [`VertexPrecision`]
+    precisions: [PassPipelines; 2],
+}
+
+impl EntitiesPipelines {
+    fn get(
+        &self,
+        pass: EntitiesPass,
+        wireframe: bool,
+        precision: VertexPrecision,
+    ) -> &wgpu::RenderPipeline {
+        self.precisions[precision as usize].get(pass, wireframe)
+    }
+}
+
+/// Pipelines of one vertex layout for every pass, with the wireframe ones when the device
+/// supports it
+struct PassPipelines {
+    opaque: wgpu::RenderPipeline,
+    opaque_wireframe: Option<wgpu::RenderPipeline>,
+    fade: wgpu::RenderPipeline,
+    fade_wireframe: Option<wgpu::RenderPipeline>,
+    masked: wgpu::RenderPipeline,
+}
+
+impl PassPipelines {
+    fn get(&self, pass: EntitiesPass, wireframe: bool) -> &wgpu::RenderPipeline {
+        let (pipeline, wireframe_pipeline) = match pass {
+            EntitiesPass::Opaque => (&self.opaque, self.opaque_wireframe.as_ref()),
+            EntitiesPass::Fade => (&self.fade, self.fade_wireframe.as_ref()),
+            EntitiesPass::Masked => (&self.masked, None),
+        };
+        match wireframe_pipeline {
+            Some(wireframe_pipeline) if wireframe => wireframe_pipeline,
+            _ => pipeline,
+        }
+    }
+}
+
+/// Panics when a struct uploaded to the entities shaders is laid out differently on the cpu, a
+/// mismatch would only show as garbled rendering
+fn check_layouts() {
+    let reflect = |name, source| {
+        ShaderReflection::new(source).unwrap_or_else(|e| panic!("Invalid {name} shader: {e}"))
+    };
+    let shader = reflect("entities", SHADER_SOURCE);
+    let draws = reflect("draws", include_str!("draws.wgsl"));
+    let blobs = reflect("blobs", include_str!("blobs.wgsl"));
+    [
+        shader.check_layout::<RawFrameConstants>(),
+        shader.check_layout::<RawRelativeView>(),
+        shader.check_layout::<Material>(),
+        shader.check_layout::<RawWind>(),
+        shader.check_layout::<RawLight>(),
+        shader.check_layout::<RawCascades>(),
+        shader.check_layout::<RawShadowView>(),
+        shader.check_layout::<RawLocalShadows>(),
+        draws.check_layout::<ModelInstance>(),
+        draws.check_layout::<VisibleInstance>(),
+        draws.check_layout::<RawCulling>(),
+        shader.check_layout::<ParamBlock>(),
+        shader.check_layout::<AtlasSlot>(),
+        blobs.check_layout::<RawBlobParams>(),
+    ]
+    .into_iter()
+    .collect::<Result<(), String>>()
+    .unwrap_or_else(|e| panic!("{e}"));
+}
+
+/// Values of the `override` constants of the entities shader selecting the variant of a pipeline
+fn pipeline_constants(
+    shadow_filter: ShadowFilter,
+    pass: EntitiesPass,
+    shader_id: u32,
+) -> HashMap<String, f64> {
+    let shadow_filter = match shadow_filter {
+        ShadowFilter::Hard => 0,
+        ShadowFilter::Pcf3x3 => 1,
+        ShadowFilter::Pcf5x5 => 2,
+        ShadowFilter::Pcss => 3,
+    };
+    HashMap::from([
+        ("SHADOW_FILTER".to_string(), shadow_filter as f64),
+        (
+            "FADE_PASS".to_string(),
+            (pass == EntitiesPass::Fade) as u32 as f64,
+        ),
+        ("SHADER_ID".to_string(), shader_id as f64),
+    ])
 }
 
 impl EntitiesRenderer {
-    pub fn new(ctx: &GraphicsCtx) -> Self {
-        let pipeline_layout = ctx
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: None,
-                bind_group_layouts: &[
-                    &view_proj_bind_group_layout(ctx),
-                    &materials_buffer_bind_group_layout(ctx),
-                    &atlas_uniform_bind_group_layout(ctx),
-                    &lights_buffer_bind_group_layout(ctx),
-                ],
-                push_constant_ranges: &[],
-            });
+    pub fn new(
+        ctx: &GraphicsCtx,
+        settings: &RenderSettings,
+        frame: &FrameConstants,
+        scene_depth: &SceneDepth,
+    ) -> Self {
+        check_layouts();
+        let shadow_filter = settings.shadows.filter;
+        let shader = builtin_shader(ctx);
+        let pipelines = builtin_pipelines(ctx, &shader, shadow_filter);
+        let lightmap = Lightmap::new(ctx);
 
-        let shader = ctx
-            .device
-            .create_shader_module(include_wgsl!("shader.wgsl"));
+        // Occlusion and vertex precision come from the sidecars of the models
+        let (astronaut, earth) = rayon::join(
+            || load_model(ASSET_MODELS[0]),
+            || load_model(ASSET_MODELS[1]),
+        );
 
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    buffers: &[ModelVertex::buffer_desc(), ModelInstance::buffer_desc()],
-                    compilation_options: Default::default(),
-                },
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Cw,
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    conservative: false,
-                    unclipped_depth: false,
-                },
-                depth_stencil: Some(DepthStencilState {
-                    format: TextureWrapper::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.surface_format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                    compilation_options: Default::default(),
-                }),
-                multiview: None,
-                cache: None,
-            });
+        let label = label_model();
 
-        let astronaut = load_model("Astronaut");
-        let earth = load_model("Earth");
+        let materials = [astronaut.materials, earth.materials, label.materials].concat();
+        let mut textures = [astronaut.textures, earth.textures, label.textures].concat();
+        let sprite_sheets = [
+            astronaut.sprite_sheets,
+            earth.sprite_sheets,
+            label.sprite_sheets,
+        ]
+        .concat();
+        let color_spaces = [
+            astronaut.color_spaces,
+            earth.color_spaces,
+            label.color_spaces,
+        ]
+        .concat();
+        let sheets = split_sheets(&mut textures, &sprite_sheets);
+        let mut animator = InstanceAnimator::new(ctx);
+        let floating = animator.add(
+            InstanceAnimation::new(Matrix4::identity())
+                .rotating(Vector3::y(), 0.5)
+                .bobbing(Vector3::y(), 0.1, 0.25),
+        );
 
-        let materials = [astronaut.materials, earth.materials].concat();
-        let textures = [astronaut.textures, earth.textures].concat();
         let entities = [
-            (&astronaut.meshes, vec![single_instance(0)]),
+            (
+                &astronaut.meshes,
+                &astronaut.baked,
+                astronaut.precision,
+                vec![single_instance(0, floating)],
+            ),
             (
                 &earth.meshes,
+                &earth.baked,
+                earth.precision,
                 vec![stress_test_instances(1), stress_test_instances(2)],
             ),
+            (
+                &label.meshes,
+                &label.baked,
+                label.precision,
+                vec![Vec::new()],
+            ),
         ];
 
+        // Their usage is fed every frame, see [`Self::update_texture_usage`]
+        let mut textures = TextureStreamer::new(
+            ctx,
+            textures.into_iter().zip(color_spaces),
+            &settings.texture_quality,
+            &settings.streaming,
+        );
+
+        let sprites = SpriteAnimator::new(ctx, &mut textures, sheets, &materials);
+
         let models = ModelsBuffer::new(ctx, entities);
-        let materials = MaterialsBuffer::new(ctx, &materials);
-        let atlas = AtlasPacker::from_textures(textures).build_atlas(ctx);
+        let shadows = ShadowCascades::new(ctx, &shader, &lightmap, frame, scene_depth, &models);
+        let blobs = BlobShadows::new(ctx, &settings.shadows, &models);
+        let draws = DrawGenerator::new(ctx, &models);
+        #[cfg(feature = "meshlets")]
+        let meshlets = MeshletCulling::new(ctx, &models);
+        let zones = ZoneCulling::new(ctx);
+        let wind = Wind::new(ctx, &settings.environment.wind);
+        let instance_params = InstanceParams::new(ctx);
+        let materials = MaterialsBuffer::new(
+            ctx,
+            &materials,
+            &zones.visibility,
+            &wind.buffer,
+            &instance_params.blocks,
+        );
 
-        Self {
+        let mut renderer = Self {
             models,
             materials,
-            atlas,
-            pipeline,
+            textures,
+            sprites,
+            animator,
+            instance_params,
+            dissolves: Dissolves::default(),
+            world: WorldStreamer::new(50.0, 2),
+            lods: LodInstances::default(),
+            zones,
+            wind,
+            shadows,
+            blobs,
+            draws,
+            statics: StaticBatches::default(),
+            #[cfg(feature = "meshlets")]
+            meshlets,
+            lightmap,
+            rebase: InstanceRebase::new(ctx),
+            wireframe: false,
+            shader,
+            pipelines,
+            shadow_filter,
+            material_shaders: Vec::new(),
+        };
+        // The label model is generated, it is never streamed out as no cell places it
+        renderer
+            .world
+            .set_geometry_loader(Some(Arc::new(|model_id: u16| {
+                let name = ASSET_MODELS.get(model_id as usize)?;
+                Some(load_model_geometry(name))
+            })));
+        let toon = renderer.add_material_shader(ctx, toon_shader_desc(&ToonParams::default()));
+        debug_assert_eq!(toon, TOON_SHADER_ID);
+        renderer
+    }
+
+    /// Returns the `shader_id` materials should use to be drawn with it
+    pub fn add_material_shader(&mut self, ctx: &GraphicsCtx, desc: MaterialShaderDesc) -> u32 {
+        let shader_id = self.material_shaders.len() as u32 + 1;
+        let module = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(desc.name),
+                source: wgpu::ShaderSource::Wgsl(MaterialShader::module_source(desc.source).into()),
+            });
+        let pipelines = material_shader_pipelines(ctx, &module, shader_id, self.shadow_filter);
+
+        self.material_shaders
+            .push(MaterialShader::new(ctx, &desc, module, pipelines));
+        shader_id
+    }
+
+    /// Recreates the built-in and the custom pipelines with the filter, the shader modules are
+    /// kept
+    pub fn set_shadow_filter(&mut self, ctx: &GraphicsCtx, shadow_filter: ShadowFilter) {
+        if self.shadow_filter == shadow_filter {
+            return;
+        }
+        self.shadow_filter = shadow_filter;
+        self.pipelines = builtin_pipelines(ctx, &self.shader, shadow_filter);
+        for (i, shader) in self.material_shaders.iter_mut().enumerate() {
+            shader.pipelines =
+                material_shader_pipelines(ctx, &shader.module, i as u32 + 1, shadow_filter);
         }
     }
 
+    pub fn material_shader(&self, shader_id: u32) -> Option<&MaterialShader> {
+        self.material_shaders
+            .get(shader_id.checked_sub(1)? as usize)
+    }
+
+    /// Called when the scene depth is recreated with the viewport
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, scene_depth: &SceneDepth) {
+        self.shadows
+            .bind_scene_depth(ctx, &self.lightmap, scene_depth);
+    }
+
+    /// The fade pass must be recorded after the scene depth is copied from the opaque one
     pub fn render(
-        &mut self,
+        &self,
         render_pass: &mut wgpu::RenderPass<'static>,
         camera: &CameraUniform,
         lights: &LightsUniform,
+        pass: EntitiesPass,
     ) {
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
-        render_pass.set_bind_group(1, &self.materials.bind_group, &[]);
-        render_pass.set_bind_group(2, &self.atlas.bind_group, &[]);
-        render_pass.set_bind_group(3, &lights.bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.models.vertex_buffer.as_slice());
-        render_pass.set_vertex_buffer(1, self.models.instance_buffer.as_slice());
+        #[cfg(feature = "meshlets")]
+        if let Some(meshlets) = self.meshlets.as_ref().filter(|meshlets| meshlets.enabled) {
+            self.bind(render_pass, camera, lights, &self.draws);
+            render_pass
+                .set_index_buffer(meshlets.index_buffer.as_slice(), wgpu::IndexFormat::Uint16);
+            churn::record(
+                "Entities",
+                StateChanges {
+                    index_buffers: 1,
+                    ..Default::default()
+                },
+            );
+            self.draw_precisions(render_pass, pass, |render_pass, precision| {
+                meshlets.draw(render_pass, precision)
+            });
+            self.draw_statics(render_pass, pass);
+            return;
+        }
+        self.render_with(render_pass, camera, lights, pass, &self.draws);
+    }
+
+    /// Draws the instances culled into `draws`, for views other than the main camera
+    pub fn render_with(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        camera: &CameraUniform,
+        lights: &LightsUniform,
+        pass: EntitiesPass,
+        draws: &DrawGenerator,
+    ) {
+        self.bind(render_pass, camera, lights, draws);
         render_pass.set_index_buffer(
             self.models.index_buffer.as_slice(),
             wgpu::IndexFormat::Uint16,
         );
-        render_pass.multi_draw_indexed_indirect(
-            &self.models.indirect_buffer.inner(),
-            0,
-            self.models.mesh_count(),
+        churn::record(
+            "Entities",
+            StateChanges {
+                index_buffers: 1,
+                ..Default::default()
+            },
         );
+        self.draw_precisions(render_pass, pass, |render_pass, precision| {
+            draws.draw(render_pass, precision)
+        });
+        self.draw_statics(render_pass, pass);
     }
 
-    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
-        self.models.apply_changes(ctx);
+    fn bind(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        camera: &CameraUniform,
+        lights: &LightsUniform,
+        draws: &DrawGenerator,
+    ) {
+        render_pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
+        render_pass.set_bind_group(1, &self.materials.bind_group, &[]);
+        render_pass.set_bind_group(2, &self.textures.atlas.bind_group, &[]);
+        render_pass.set_bind_group(3, &lights.bind_group, &[]);
+        render_pass.set_bind_group(4, &self.shadows.bind_group, &[]);
+        render_pass.set_vertex_buffer(1, draws.instances().as_slice());
+        churn::record(
+            "Entities",
+            StateChanges {
+                bind_groups: 5,
+                vertex_buffers: 1,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Every pipeline goes through all the meshes of its precision, instances of other shaders
+    /// and of the other pass are clipped
+    fn draw_precisions(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        pass: EntitiesPass,
+        draw: impl Fn(&mut wgpu::RenderPass<'static>, VertexPrecision),
+    ) {
+        for precision in VertexPrecision::ALL {
+            if self.models.mesh_count_with(precision) == 0 {
+                continue;
+            }
+            render_pass.set_vertex_buffer(0, self.models.vertex_slice(precision));
+            render_pass.set_pipeline(self.pipelines.get(pass, self.wireframe, precision));
+            draw(render_pass, precision);
+            for shader in &self.material_shaders {
+                render_pass.set_bind_group(5, &shader.bind_group, &[]);
+                render_pass.set_pipeline(shader.pipelines.get(pass, self.wireframe, precision));
+                draw(render_pass, precision);
+            }
+            let shaders = self.material_shaders.len() as u32;
+            churn::record(
+                "Entities",
+                StateChanges {
+                    pipelines: 1 + shaders,
+                    bind_groups: shaders,
+                    vertex_buffers: 1,
+                    draws: 1 + shaders,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Merged static instances, over the buffers bound for the culled ones. They are not culled
+    fn draw_statics(&self, render_pass: &mut wgpu::RenderPass<'static>, pass: EntitiesPass) {
+        if !self.statics.bind(render_pass) {
+            return;
+        }
+        let precision = VertexPrecision::Full;
+        render_pass.set_pipeline(self.pipelines.get(pass, self.wireframe, precision));
+        self.statics.draw(render_pass);
+        for shader in &self.material_shaders {
+            render_pass.set_bind_group(5, &shader.bind_group, &[]);
+            render_pass.set_pipeline(shader.pipelines.get(pass, self.wireframe, precision));
+            self.statics.draw(render_pass);
+        }
+        let shaders = self.material_shaders.len() as u32;
+        churn::record(
+            "Entities",
+            StateChanges {
+                pipelines: 1 + shaders,
+                bind_groups: shaders,
+                vertex_buffers: 2,
+                index_buffers: 1,
+                draws: (1 + shaders) * self.statics.batch_count(),
+            },
+        );
+    }
+
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx, camera_position: Point3<f32>) {
+        {
+            profile_scope!("World streaming");
+            self.world.update(&mut self.models, camera_position);
+        }
+        {
+            profile_scope!("Levels of detail");
+            self.lods.update(&mut self.models, camera_position);
+        }
+        {
+            profile_scope!("Instance bookkeeping");
+            // The dissolved instances are removed before the changes are applied
+            self.dissolves
+                .update(&mut self.models, &mut self.instance_params);
+            self.models.apply_changes(ctx);
+            self.statics.apply_changes(ctx);
+            self.draws.apply_changes(ctx, &self.models);
+            self.shadows.apply_changes(ctx, &self.models);
+            self.animator.apply_changes(ctx);
+            if self.instance_params.apply_changes(ctx) {
+                self.materials.rebind(
+                    ctx,
+                    &self.zones.visibility,
+                    &self.wind.buffer,
+                    &self.instance_params.blocks,
+                );
+            }
+            self.sprites.update(&mut self.materials);
+            self.materials.apply_changes(ctx);
+            if self.lightmap.apply_changes(ctx) {
+                self.shadows.bind_lightmap(ctx, &self.lightmap);
+            }
+        }
+        {
+            profile_scope!("Zone culling");
+            self.zones.update(ctx, camera_position);
+        }
+        profile_scope!("Texture streaming");
+        self.update_texture_usage();
+        self.textures.update(ctx, camera_position);
+    }
+
+    /// Usage of the textures by the placed instances of the visible zones, the streamed
+    /// resolutions follow them as they move, appear and go away
+    fn update_texture_usage(&mut self) {
+        self.textures.clear_usage();
+        for (_, instance) in self.models.instances() {
+            if !self.zones.is_visible(instance.zone_id as u32) {
+                continue;
+            }
+            let Some(material) = self.materials.get(instance.material_id) else {
+                continue;
+            };
+            let [x, y, z] = instance.translation;
+            self.textures
+                .add_usage(material.diffuse_texture_id, Point3::new(x, y, z));
+        }
+        self.sprites.share_usage(&mut self.textures);
+    }
+
+    /// Moves every instance, animation and zone back by `shift`, see
+    /// [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, ctx: &GraphicsCtx, shift: Vector3<f32>, origin: Vector3<f64>) {
+        self.rebase.shift(
+            ctx,
+            shift,
+            &[&self.models.instance_buffer, self.draws.previous()],
+            &self.animator,
+        );
+        self.world.rebase(origin);
+        self.lods.rebase(shift);
+        self.statics.rebase(shift);
+        for zone in &mut self.zones.zones {
+            zone.min -= shift;
+            zone.max -= shift;
+        }
+    }
+
+    /// Must be recorded before the pass drawing the entities
+    /// Must be recorded before the draws are generated, see [`ModelsBuffer::record_ranges`]
+    pub fn record_ranges(&self, ctx: &GraphicsCtx, encoder: &mut wgpu::CommandEncoder) {
+        self.models.record_ranges(ctx, encoder);
+    }
+
+    pub fn animate(&self, ctx: &GraphicsCtx, encoder: &mut wgpu::CommandEncoder) {
+        self.animator
+            .animate(ctx, encoder, &self.models.instance_buffer);
+    }
+
+    /// Must be recorded after `animate` and before the pass drawing the entities
+    pub fn generate_draws(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &CameraUniform,
+    ) {
+        self.generate_draws_into(ctx, encoder, camera, &self.draws);
+        #[cfg(feature = "meshlets")]
+        if let Some(meshlets) = self.meshlets.as_ref().filter(|meshlets| meshlets.enabled) {
+            meshlets.generate(
+                ctx,
+                encoder,
+                &self.models,
+                &self.draws,
+                &camera.view_proj(),
+                camera.eye(),
+            );
+        }
+    }
+
+    /// Culls the instances against another camera, `draws` must be kept grown with the models
+    pub fn generate_draws_into(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &CameraUniform,
+        draws: &DrawGenerator,
+    ) {
+        draws.generate(
+            ctx,
+            encoder,
+            &self.models,
+            &self.zones.visibility,
+            &camera.view_proj(),
+        );
+    }
+
+    /// Must be recorded after `animate` and before the pass drawing the entities
+    pub fn render_shadows(&self, ctx: &GraphicsCtx, encoder: &mut wgpu::CommandEncoder) {
+        self.shadows
+            .render(ctx, encoder, &self.models, &self.materials);
+    }
+
+    /// Must be recorded after the scene depth is copied from the opaque pass
+    pub fn render_blob_shadows(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera: &CameraUniform,
+        scene_depth: &SceneDepth,
+    ) {
+        self.blobs.render(
+            ctx,
+            encoder,
+            target,
+            camera,
+            scene_depth,
+            &self.models,
+            &self.draws,
+        );
+    }
+
+    /// Must be recorded after the pass drawing the entities
+    pub fn store_history(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.draws.store_history(encoder, &self.models);
     }
 }
 
-fn single_instance(material_id: u32) -> Vec<ModelInstance> {
-    vec![ModelInstance {
-        transform: [
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, 1.0, 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ],
-        material_id: material_id,
-    }]
+fn single_instance(material_id: u32, animation_id: u32) -> Vec<ModelInstance> {
+    vec![ModelInstance::new(Matrix4::identity(), material_id).with_animation(animation_id)]
 }
 
 fn stress_test_instances(material_id: u32) -> Vec<ModelInstance> {
@@ -167,3 +697,181 @@ fn stress_test_instances(material_id: u32) -> Vec<ModelInstance> {
         })
         .collect::<Vec<_>>()
 }
+
+/// Also used for the shadows, drawn with its default constants
+fn builtin_shader(ctx: &GraphicsCtx) -> wgpu::ShaderModule {
+    ctx.device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Entities shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        })
+}
+
+fn builtin_pipelines(
+    ctx: &GraphicsCtx,
+    shader: &wgpu::ShaderModule,
+    shadow_filter: ShadowFilter,
+) -> EntitiesPipelines {
+    let pipeline_layout = entities_pipeline_layout(ctx, None);
+    entities_pipelines(ctx, &pipeline_layout, shader, "fs_main", shadow_filter, 0)
+}
+
+/// `module` is built from [`MaterialShader::module_source`]
+fn material_shader_pipelines(
+    ctx: &GraphicsCtx,
+    module: &wgpu::ShaderModule,
+    shader_id: u32,
+    shadow_filter: ShadowFilter,
+) -> EntitiesPipelines {
+    let params_layout = material_shader_bind_group_layout(ctx);
+    let pipeline_layout = entities_pipeline_layout(ctx, Some(&params_layout));
+    entities_pipelines(
+        ctx,
+        &pipeline_layout,
+        module,
+        "fs_custom",
+        shadow_filter,
+        shader_id,
+    )
+}
+
+/// Custom material shaders bind their parameters after the built-in groups
+fn entities_pipeline_layout(
+    ctx: &GraphicsCtx,
+    params: Option<&wgpu::BindGroupLayout>,
+) -> wgpu::PipelineLayout {
+    let layouts = [
+        view_proj_bind_group_layout(ctx),
+        materials_buffer_bind_group_layout(ctx),
+        atlas_uniform_bind_group_layout(ctx),
+        lights_buffer_bind_group_layout(ctx),
+        shadows_bind_group_layout(ctx),
+    ];
+    let bind_group_layouts = layouts.iter().chain(params).collect::<Vec<_>>();
+    ctx.device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        })
+}
+
+/// Every pass and vertex precision shares `shader`, see [`pipeline_constants`]
+fn entities_pipelines(
+    ctx: &GraphicsCtx,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    fragment_entry: &str,
+    shadow_filter: ShadowFilter,
+    shader_id: u32,
+) -> EntitiesPipelines {
+    let has_wireframe = ctx.has_feature(wgpu::Features::POLYGON_MODE_LINE);
+    EntitiesPipelines {
+        precisions: VertexPrecision::ALL.map(|precision| {
+            let pipeline = |pass, polygon_mode| {
+                entities_pipeline(
+                    ctx,
+                    layout,
+                    shader,
+                    fragment_entry,
+                    &pipeline_constants(shadow_filter, pass, shader_id),
+                    pass,
+                    polygon_mode,
+                    precision,
+                )
+            };
+            PassPipelines {
+                opaque: pipeline(EntitiesPass::Opaque, wgpu::PolygonMode::Fill),
+                opaque_wireframe: has_wireframe
+                    .then(|| pipeline(EntitiesPass::Opaque, wgpu::PolygonMode::Line)),
+                fade: pipeline(EntitiesPass::Fade, wgpu::PolygonMode::Fill),
+                fade_wireframe: has_wireframe
+                    .then(|| pipeline(EntitiesPass::Fade, wgpu::PolygonMode::Line)),
+                masked: pipeline(EntitiesPass::Masked, wgpu::PolygonMode::Fill),
+            }
+        }),
+    }
+}
+
+/// Fading surfaces do not write the depth, nor the velocity of the opaque ones behind them. Masked
+/// ones test the stencil with [`stencil_state`]
+fn entities_pipeline(
+    ctx: &GraphicsCtx,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    fragment_entry: &str,
+    constants: &HashMap<String, f64>,
+    pass: EntitiesPass,
+    polygon_mode: wgpu::PolygonMode,
+    precision: VertexPrecision,
+) -> wgpu::RenderPipeline {
+    let opaque = pass != EntitiesPass::Fade;
+    let (format, stencil) = match pass {
+        EntitiesPass::Masked => (
+            TextureWrapper::DEPTH_STENCIL_FORMAT,
+            stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+        ),
+        _ => (TextureWrapper::DEPTH_FORMAT, wgpu::StencilState::default()),
+    };
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[precision.buffer_desc(), VisibleInstance::buffer_desc()],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                polygon_mode,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format,
+                depth_write_enabled: opaque,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil,
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fragment_entry),
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: VELOCITY_FORMAT,
+                        blend: None,
+                        write_mask: if opaque {
+                            wgpu::ColorWrites::ALL
+                        } else {
+                            wgpu::ColorWrites::empty()
+                        },
+                    }),
+                ],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+            }),
+            multiview: None,
+            cache: None,
+        })
+}
@@ -2,15 +2,33 @@ struct VertexInput {
     @location(0) position: vec3f,
     @location(1) normal: vec3f,
     @location(2) tex_coords: vec2f,
+    @location(10) lightmap_uv: vec2f,
+    @location(12) ao: f32,
+    // Linear, white on models without vertex colors
+    @location(6) color: vec4f,
 };
 
+// Compact transform, see `model_of`
 struct InstanceInput {
-    @location(3) model_matrix_0: vec4f,
-    @location(4) model_matrix_1: vec4f,
-    @location(5) model_matrix_2: vec4f,
-    @location(6) model_matrix_3: vec4f,
+    @location(3) translation: vec3f,
+    // Unit quaternion, xyz then w
+    @location(4) rotation: vec4f,
+    @location(5) scale: vec3f,
 
     @location(7) material_id: u32,
+    // Animation id then parameter block id in the 16 bits halves of x, zone id then snorm level of
+    // detail fade in the ones of y
+    @location(8) ids: vec2u,
+    @location(9) tint: vec4f,
+    // Offset then scale of the rect of the instance in the lightmap
+    @location(11) lightmap: vec4f,
+}
+
+// Rows of the affine transform of the previous frame, only given to the entities pass
+struct PreviousInput {
+    @location(13) row_0: vec4f,
+    @location(14) row_1: vec4f,
+    @location(15) row_2: vec4f,
 }
 
 struct VertexOutput {
@@ -19,6 +37,28 @@ struct VertexOutput {
     @location(1) tex_coords: vec2f,
     @location(2) position: vec3f,
     @location(3) material_id: u32,
+    @location(4) tint: vec4f,
+    @location(5) world_position: vec3f,
+    @location(6) world_normal: vec3f,
+    // Negative without lightmap
+    @location(7) lightmap_uv: vec2f,
+    @location(8) ao: f32,
+    // Both with the current camera, the motion vectors only hold the motion of the instance
+    @location(9) clip_current: vec4f,
+    @location(10) clip_previous: vec4f,
+    // Interpolated in screen space, for the retro mode
+    @location(11) @interpolate(linear) affine_tex_coords: vec2f,
+    @location(12) color: vec4f,
+    // Positive while fading in, negative while fading out, 0 for opaque instances
+    @location(13) @interpolate(flat) fade: f32,
+    // Block of `instance_params` read by the custom material shaders, 0 without one
+    @location(14) @interpolate(flat) params_id: u32,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4f,
+    // In uv per frame, see `VELOCITY_FORMAT`
+    @location(1) velocity: vec2f,
 };
 
 
@@ -27,53 +67,255 @@ var<uniform> view: mat4x4f;
 @group(0) @binding(1)
 var<uniform> proj: mat4x4f;
 
+// Matches `RawFrameConstants`, also readable by custom material shaders
+struct FrameConstants {
+    time: f32,
+    delta_time: f32,
+    frame_index: u32,
+    exposure: f32,
+    screen_size: vec2f,
+    inv_screen_size: vec2f,
+    snap_resolution: vec2f,
+    affine_textures: u32,
+    camera_relative: u32,
+    eye: vec3f,
+    wetness: f32,
+    ambient: vec3f,
+};
+
+@group(0) @binding(2)
+var<uniform> frame: FrameConstants;
+
+// Matches `RawRelativeView`, composed in f64 on the cpu from `view`
+struct RelativeView {
+    // Only the rotation is left, the translation cancels out with `eye`
+    view: mat4x4f,
+    eye: vec3f,
+};
+
+@group(0) @binding(3)
+var<uniform> relative_to_eye: RelativeView;
+
 const INVALID_TEX_ID: u32 = 4294967295;
+const SAMPLER_LINEAR: u32 = 1;
+// Set by the pipelines of custom material shaders
+override SHADER_ID: u32 = 0u;
+// Set by the pipelines of the second pass, drawing the materials with a depth fade
+override FADE_PASS: bool = false;
 
 struct Material {
     diffuse_color: vec3f,
 
     diffuse_tex_id: u32,
+    sampler_id: u32,
+    shader_id: u32,
+    wind: f32,
+    depth_fade: f32,
 }
 
 @group(1) @binding(0)
 var<storage, read> materials: array<Material>;
+@group(1) @binding(1)
+var<storage, read> zones_visible: array<u32>;
+
+struct Wind {
+    direction: vec2f,
+    strength: f32,
+    gust_frequency: f32,
+}
+
+@group(1) @binding(2)
+var<uniform> wind: Wind;
+
+// Matches `ParamBlock`
+struct ParamBlock {
+    values: array<vec4f, 4>,
+    // Edge color then progress of the dissolve, see `Dissolves`
+    dissolve: vec4f,
+}
+
+@group(1) @binding(3)
+var<storage, read> param_blocks: array<ParamBlock>;
+
+// Zeros for the instances without a block
+fn param_block_of(params_id: u32) -> ParamBlock {
+    if params_id == 0u || params_id > arrayLength(&param_blocks) {
+        return ParamBlock();
+    }
+    return param_blocks[params_id - 1u];
+}
+
+// Values of the parameter block of the instance, for the custom material shaders
+fn instance_params(surface: Surface) -> array<vec4f, 4> {
+    return param_block_of(surface.params_id).values;
+}
+
+// World space sway of a vertex `height` units above its model origin, timed by the frame so the
+// shadow views sway with the camera one
+fn wind_offset(world: vec3f, height: f32, weight: f32) -> vec3f {
+    // Gusts travel along the wind direction
+    let phase = dot(world.xz, wind.direction) * 0.5;
+    let gust = sin(frame.time * wind.gust_frequency * 6.2831853 - phase) * 0.5 + 0.5;
+    let flutter = sin(frame.time * 7.0 - phase * 3.0) * 0.15;
+    let sway = wind.strength * weight * max(height, 0.0) * (gust + flutter);
+    return vec3f(wind.direction.x, 0.0, wind.direction.y) * sway;
+}
 
+// sRGB view of the atlas, the data textures are sampled from the linear one
 @group(2) @binding(0)
 var t_atlas: texture_2d<f32>;
+@group(2) @binding(5)
+var t_atlas_data: texture_2d<f32>;
 @group(2) @binding(1)
-var s_atlas: sampler;
+var s_atlas_nearest: sampler;
+@group(2) @binding(3)
+var s_atlas_linear: sampler;
+
+const COLOR_SPACE_LINEAR: u32 = 1u;
 
 struct TextureAtlasUV {
-    min: vec2f,
-    max: vec2f,
+    // Min then max
+    rect: vec4f,
+    color_space: u32,
 }
 
 @group(2) @binding(2)
 var<storage, read> atlas_uvs: array<TextureAtlasUV>;
+@group(2) @binding(4)
+var<uniform> mip_bias: f32;
+
+fn quat_to_matrix(q: vec4f) -> mat3x3f {
+    let x2 = q.xyz * 2.0;
+    let xx = q.x * x2.x;
+    let yy = q.y * x2.y;
+    let zz = q.z * x2.z;
+    let xy = q.x * x2.y;
+    let xz = q.x * x2.z;
+    let yz = q.y * x2.z;
+    let wx = q.w * x2.x;
+    let wy = q.w * x2.y;
+    let wz = q.w * x2.z;
+    return mat3x3f(
+        vec3f(1.0 - yy - zz, xy + wz, xz - wy),
+        vec3f(xy - wz, 1.0 - xx - zz, yz + wx),
+        vec3f(xz + wy, yz - wx, 1.0 - xx - yy),
+    );
+}
+
+// Translation then rotation then scale, the quaternion is renormalized after its quantization
+fn model_of(instance: InstanceInput) -> mat4x4f {
+    let rotation = quat_to_matrix(normalize(instance.rotation));
+    return mat4x4f(
+        vec4f(rotation[0] * instance.scale.x, 0.0),
+        vec4f(rotation[1] * instance.scale.y, 0.0),
+        vec4f(rotation[2] * instance.scale.z, 0.0),
+        vec4f(instance.translation, 1.0),
+    );
+}
+
+// Model matrix with the translation taken relative to `origin`, the subtraction happens before the
+// vertex is transformed so nearby vertices keep their precision
+fn relative_model_of(instance: InstanceInput, origin: vec3f) -> mat4x4f {
+    var model = model_of(instance);
+    model[3] -= vec4f(origin, 0.0);
+    return model;
+}
+
+// Position relative to `origin`
+fn world_position_of(vertex: VertexInput, instance: InstanceInput, origin: vec3f) -> vec4f {
+    let material = materials[instance.material_id];
+    var world = relative_model_of(instance, origin) * vec4f(vertex.position, 1.0);
+    if material.wind > 0.0 {
+        world += vec4f(wind_offset(world.xyz + origin, vertex.position.y, material.wind), 0.0);
+    }
+    return world;
+}
+
+// Origin the entities are transformed relative to, the eye of the bound view when
+// `camera_relative` is set. Shadow views have their own
+fn view_origin() -> vec3f {
+    return select(vec3f(0.0), relative_to_eye.eye, frame.camera_relative != 0u);
+}
+
+// View matrix of positions relative to `view_origin`
+fn relative_view() -> mat4x4f {
+    if frame.camera_relative == 0u {
+        return view;
+    }
+    return relative_to_eye.view;
+}
 
 @vertex
 fn vs_main(
     vertex: VertexInput,
-    instance: InstanceInput
+    instance: InstanceInput,
+    previous: PreviousInput,
 ) -> VertexOutput {
-    let model = mat4x4f(
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
-    );
     let position = vec4f(vertex.position, 1.0);
-    let mvp = proj * view * model;
+    let material = materials[instance.material_id];
+    let origin = view_origin();
+    let relative_view = relative_view();
+    let relative = world_position_of(vertex, instance, origin);
+    let world = relative + vec4f(origin, 0.0);
 
     var out: VertexOutput;
     out.normal = vertex.normal;
     out.tex_coords = vertex.tex_coords;
-    out.clip_position = mvp * position;
+    out.affine_tex_coords = vertex.tex_coords;
+    out.clip_position = proj * relative_view * relative;
+    // The wind offset is the same, it does not move the instance
+    let offset = relative - relative_model_of(instance, origin) * position;
+    let previous_relative = vec4f(
+        dot(previous.row_0, position),
+        dot(previous.row_1, position),
+        dot(previous.row_2, position),
+        1.0,
+    ) - vec4f(origin, 0.0) + offset;
+    out.clip_current = out.clip_position;
+    out.clip_previous = proj * relative_view * previous_relative;
+    if frame.snap_resolution.x > 0.0 && out.clip_position.w > 0.0 {
+        // Snapped to the pixels of the scene targets, the motion vectors ignore the jitter
+        let half = frame.snap_resolution * 0.5;
+        let ndc = round(out.clip_position.xy / out.clip_position.w * half) / half;
+        out.clip_position = vec4f(ndc * out.clip_position.w, out.clip_position.zw);
+    }
+    out.world_position = world.xyz;
+    out.world_normal = normalize((model_of(instance) * vec4f(vertex.normal, 0.0)).xyz);
+    out.ao = vertex.ao;
+    out.color = vertex.color;
+    out.fade = unpack2x16snorm(instance.ids.y).y;
+    out.params_id = instance.ids.x >> 16u;
+    out.lightmap_uv = select(
+        vec2f(-1.0),
+        instance.lightmap.xy + vertex.lightmap_uv * instance.lightmap.zw,
+        instance.lightmap.z > 0.0,
+    );
+    let fading = material.depth_fade > 0.0;
+    if zones_visible[instance.ids.y & 0xffffu] == 0 || material.shader_id != SHADER_ID || fading != FADE_PASS {
+        // Behind the far plane, the whole instance is clipped
+        out.clip_position = vec4f(0.0, 0.0, 2.0, 1.0);
+    }
     out.position = position.xyz;
     out.material_id = instance.material_id;
+    // Picked as srgb bytes, blended in linear light
+    out.tint = vec4f(srgb_decode(instance.tint.rgb), instance.tint.a);
     return out;
 }
 
+fn srgb_decode(c: vec3f) -> vec3f {
+    return select(pow((c + 0.055) / 1.055, vec3f(2.4)), c / 12.92, c <= vec3f(0.04045));
+}
+
+// Shadow casters, `view` and `proj` are those of a shadow cascade, every instance casts
+@vertex
+fn vs_shadow(
+    vertex: VertexInput,
+    instance: InstanceInput
+) -> @builtin(position) vec4f {
+    // Swayed by the wind like in `vs_main`, the casters match their shading
+    return proj * relative_view() * world_position_of(vertex, instance, view_origin());
+}
+
 
 //TODO: impl
 struct Light {
@@ -83,6 +325,7 @@ struct Light {
     cutoff: f32,          // Spotlight cutoff angle (cosine)
     color: vec3f, 
     light_type: u32,      // 0 = Point, 1 = Directional, 2 = Spotlight
+    flags: u32,
 };
 
 @group(3) @binding(0)
@@ -90,39 +333,384 @@ var<storage, read> lights: array<Light>;
 @group(3) @binding(1)
 var<uniform> lights_count: u32;
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+struct ShadowCascades {
+    view_proj: array<mat4x4f, 4>,
+    // Far distance of each cascade along the camera forward
+    splits: vec4f,
+    camera_position: vec3f,
+    light_index: u32,
+    camera_forward: vec3f,
+    count: u32,
+    blend: f32,
+    normal_bias: f32,
+    pcss_softness: f32,
+    // Near to far world distance of each cascade
+    depth_ranges: vec4f,
+    // World width of each cascade
+    extents: vec4f,
+}
+
+// Set by the pipelines for the selected shadow filter: hard, PCF 3x3, PCF 5x5 or PCSS
+override SHADOW_FILTER: u32 = 1u;
+const PCSS_SEARCH_TEXELS: i32 = 6;
+const PCSS_MAX_TEXELS: f32 = 12.0;
+
+@group(4) @binding(0)
+var t_shadow: texture_depth_2d_array;
+@group(4) @binding(1)
+var s_shadow: sampler_comparison;
+@group(4) @binding(2)
+var<uniform> cascades: ShadowCascades;
+@group(4) @binding(3)
+var t_lightmap: texture_2d<f32>;
+@group(4) @binding(4)
+var s_lightmap: sampler;
+// Copy of the depth after the first pass, see `SceneDepth`
+@group(4) @binding(5)
+var t_scene_depth: texture_depth_2d;
+
+// One view of a shadowed point or spot light, a rect of `t_local_shadow`
+struct ShadowView {
+    view_proj: mat4x4f,
+    // Uv min then size in the atlas
+    rect: vec4f,
+    light_index: u32,
+}
+
+struct LocalShadows {
+    views: array<ShadowView, 12>,
+    count: u32,
+    normal_bias: f32,
+}
+
+@group(4) @binding(6)
+var t_local_shadow: texture_depth_2d;
+@group(4) @binding(7)
+var<uniform> local_shadows: LocalShadows;
+
+// Average of the comparisons over a square of `2 * radius + 1` taps, `spacing` texels apart
+fn pcf(cascade: u32, uv: vec2f, depth: f32, radius: i32, spacing: f32) -> f32 {
+    let texel = spacing / vec2f(textureDimensions(t_shadow));
+    var lit = 0.0;
+    for (var x = -radius; x <= radius; x++) {
+        for (var y = -radius; y <= radius; y++) {
+            let offset = vec2f(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, cascade, depth);
+        }
+    }
+    let taps = f32(2 * radius + 1);
+    return lit / (taps * taps);
+}
+
+// Penumbra grows with the distance between the receiver and the average blocker around it
+fn pcss(cascade: u32, uv: vec2f, depth: f32) -> f32 {
+    let size = vec2i(textureDimensions(t_shadow));
+    let center = vec2i(uv * vec2f(size));
+    var blockers = 0.0;
+    var blocker_depth = 0.0;
+    for (var x = -PCSS_SEARCH_TEXELS; x <= PCSS_SEARCH_TEXELS; x += 2) {
+        for (var y = -PCSS_SEARCH_TEXELS; y <= PCSS_SEARCH_TEXELS; y += 2) {
+            let texel = clamp(center + vec2i(x, y), vec2i(0), size - 1);
+            let occluder = textureLoad(t_shadow, texel, cascade, 0);
+            if occluder < depth {
+                blockers += 1.0;
+                blocker_depth += occluder;
+            }
+        }
+    }
+    if blockers == 0.0 {
+        return 1.0;
+    }
+    let gap = (depth - blocker_depth / blockers) * cascades.depth_ranges[cascade];
+    let penumbra = gap * cascades.pcss_softness / cascades.extents[cascade] * f32(size.x);
+    return pcf(cascade, uv, depth, 2, clamp(penumbra / 2.0, 0.5, PCSS_MAX_TEXELS / 2.0));
+}
+
+// Filtered visibility in a cascade, positions outside of it are lit
+fn cascade_visibility(cascade: u32, world: vec3f) -> f32 {
+    let clip = cascades.view_proj[cascade] * vec4f(world, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2f(0.5, -0.5) + 0.5;
+    if any(uv < vec2f(0.0)) || any(uv > vec2f(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+    switch SHADOW_FILTER {
+        case 0u: {
+            return textureSampleCompareLevel(t_shadow, s_shadow, uv, cascade, ndc.z);
+        }
+        case 2u: {
+            return pcf(cascade, uv, ndc.z, 2, 1.0);
+        }
+        case 3u: {
+            return pcss(cascade, uv, ndc.z);
+        }
+        default: {
+            return pcf(cascade, uv, ndc.z, 1, 1.0);
+        }
+    }
+}
+
+// Light of the shadowed directional light reaching the position, the end of each cascade
+// fades into the next one, and the last one into the light
+fn shadow_visibility(world: vec3f, normal: vec3f) -> f32 {
+    let depth = dot(world - cascades.camera_position, cascades.camera_forward);
+    let biased = world + normal * cascades.normal_bias;
+    for (var i = 0u; i < cascades.count; i++) {
+        let far = cascades.splits[i];
+        if depth > far {
+            continue;
+        }
+        let near = select(0.0, cascades.splits[max(i, 1u) - 1u], i > 0u);
+        let fade_start = far - (far - near) * cascades.blend;
+        let visibility = cascade_visibility(i, biased);
+        if depth <= fade_start {
+            return visibility;
+        }
+        var next = 1.0;
+        if i + 1u < cascades.count {
+            next = cascade_visibility(i + 1u, biased);
+        }
+        return mix(visibility, next, (depth - fade_start) / max(far - fade_start, 1e-4));
+    }
+    return 1.0;
+}
+
+// Light of a point or spot light reaching the position, lit when it has no shadow map this frame.
+// The first view of the light containing the position is sampled, the faces of a point light
+// do not overlap
+fn local_shadow_visibility(light: u32, world: vec3f, normal: vec3f) -> f32 {
+    let biased = world + normal * local_shadows.normal_bias;
+    let texel = 1.0 / vec2f(textureDimensions(t_local_shadow));
+    for (var v = 0u; v < local_shadows.count; v++) {
+        let shadow_view = local_shadows.views[v];
+        if shadow_view.light_index != light {
+            continue;
+        }
+        let clip = shadow_view.view_proj * vec4f(biased, 1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = ndc.xy * vec2f(0.5, -0.5) + 0.5;
+        if any(uv < vec2f(0.0)) || any(uv > vec2f(1.0)) || ndc.z > 1.0 {
+            continue;
+        }
+        // The taps stay inside the rect, its neighbours belong to other views
+        let atlas_uv = shadow_view.rect.xy + uv * shadow_view.rect.zw;
+        let rect_min = shadow_view.rect.xy + texel * 0.5;
+        let rect_max = shadow_view.rect.xy + shadow_view.rect.zw - texel * 0.5;
+        var lit = 0.0;
+        for (var x = -1; x <= 1; x++) {
+            for (var y = -1; y <= 1; y++) {
+                let tap = clamp(atlas_uv + vec2f(f32(x), f32(y)) * texel, rect_min, rect_max);
+                lit += textureSampleCompareLevel(t_local_shadow, s_shadow, tap, ndc.z);
+            }
+        }
+        return lit / 9.0;
+    }
+    return 1.0;
+}
+
+/// Built-in shading inputs, also handed to custom material shaders
+struct Surface {
+    position: vec3f,
+    normal: vec3f,
+    world_position: vec3f,
+    world_normal: vec3f,
+    tex_coords: vec2f,
+    tint: vec4f,
+    /// Diffuse texture times the diffuse color
+    albedo: vec4f,
+    /// Ambient and baked light, without the scene lights
+    ambient: vec3f,
+    /// Ambient, diffuse and baked light reaching the surface
+    light: vec3f,
+    /// Sun highlight of the wet surfaces, added over the lit albedo
+    specular: vec3f,
+    /// See `instance_params` of the custom material shaders
+    params_id: u32,
+}
+
+// Diffuse terms are snapped up to `bands` levels, left smooth for 0
+fn quantize(value: f32, bands: f32) -> f32 {
+    return select(value, ceil(value * bands) / bands, bands > 0.0);
+}
+
+// Diffuse light of the scene lights, shadowed by the cascades and the local shadows
+fn direct_light(surface: Surface, bands: f32) -> vec3f {
+    var light = vec3f(0.0);
+    for (var i: u32 = 0; i < lights_count; i = i + 1) {
+        let l = lights[i];
+        var light_dir = normalize(l.position - surface.position);
+        let light_dist = length(l.position.xyz - surface.position.xyz);
+        let attenuation = 1.0 / (1.0 + 0.09 * light_dist + 0.032 * light_dist * light_dist);
+        
+        var local_visibility = 1.0;
+        if l.light_type == 1 || l.light_type == 3 {
+            local_visibility = local_shadow_visibility(i, surface.world_position, surface.world_normal);
+        }
+        if l.light_type == 1 {
+            light += quantize(diffuse(surface.normal, light_dir) * local_visibility, bands) * attenuation * l.intensity * l.color;
+        }else if l.light_type == 2 { 
+            var visibility = 1.0;
+            if i == cascades.light_index {
+                visibility = shadow_visibility(surface.world_position, surface.world_normal);
+            }
+            light += quantize(diffuse(surface.normal, -l.direction) * visibility, bands) * l.intensity * l.color;
+        }else if l.light_type == 3 {
+            let spot_effect = dot(light_dir, l.direction); // Cosine of angle
+
+            if spot_effect > l.cutoff { 
+                let intensity = smoothstep(l.cutoff, l.cutoff + 0.1, spot_effect);
+                light += quantize(diffuse(surface.normal, light_dir) * intensity * local_visibility, bands) * attenuation * l.intensity * l.color;
+            }
+        }
+    }
+    return light;
+}
+
+// Highlight of the light casting the cascades, sharp as water
+fn wet_specular(position: vec3f, normal: vec3f, wet: f32) -> vec3f {
+    if wet <= 0.0 || cascades.light_index >= lights_count {
+        return vec3f(0.0);
+    }
+    let sun = lights[cascades.light_index];
+    let half_dir = normalize(normalize(frame.eye - position) - sun.direction);
+    return pow(max(dot(normal, half_dir), 0.0), 64.0) * wet * sun.intensity * sun.color;
+}
+
+fn surface_of(in: VertexOutput) -> Surface {
     let material = materials[in.material_id];
     let tex_id = material.diffuse_tex_id;
+    let tex_coords = select(in.tex_coords, in.affine_tex_coords, frame.affine_textures != 0u);
     var tex_color = vec4(1.0);
     if tex_id != INVALID_TEX_ID {
         let uvs = atlas_uvs[tex_id];
-        tex_color = textureSample(t_atlas, s_atlas, lerp2(uvs.min, uvs.max, in.tex_coords));
+        let uv = lerp2(uvs.rect.xy, uvs.rect.zw, tex_coords);
+        var nearest: vec4f;
+        var linear: vec4f;
+        if uvs.color_space == COLOR_SPACE_LINEAR {
+            nearest = textureSampleBias(t_atlas_data, s_atlas_nearest, uv, mip_bias);
+            linear = textureSampleBias(t_atlas_data, s_atlas_linear, uv, mip_bias);
+        } else {
+            nearest = textureSampleBias(t_atlas, s_atlas_nearest, uv, mip_bias);
+            linear = textureSampleBias(t_atlas, s_atlas_linear, uv, mip_bias);
+        }
+        tex_color = select(nearest, linear, material.sampler_id == SAMPLER_LINEAR);
     }
 
-    var ambient = vec3f(0.2);
-    for (var i: u32 = 0; i < lights_count; i = i + 1) {
-        let light = lights[i];
-        var light_dir = normalize(light.position - in.position);
-        let light_dist = length(light.position.xyz - in.position.xyz);
-        let attenuation = 1.0 / (1.0 + 0.09 * light_dist + 0.032 * light_dist * light_dist);
-        
-        if light.light_type == 1 {
-            ambient += diffuse(in.normal, light_dir) * attenuation * light.intensity * light.color;
-        }else if light.light_type == 2 { 
-            ambient += diffuse(in.normal, -light.direction) * light.intensity * light.color;
-        }else if light.light_type == 3 {
-            let spot_effect = dot(light_dir, light.direction); // Cosine of angle
-
-            if spot_effect > light.cutoff { 
-                let intensity = smoothstep(light.cutoff, light.cutoff + 0.1, spot_effect);
-                ambient += diffuse(in.normal, light_dir) * intensity * attenuation * light.intensity * light.color;
-            }
+    // Baked occlusion only darkens the sky ambient, lights keep their contact shadows
+    var ambient = frame.ambient * in.ao;
+    if in.lightmap_uv.x >= 0.0 {
+        ambient += textureSampleLevel(t_lightmap, s_lightmap, in.lightmap_uv, 0.0).rgb;
+    }
+
+    var out: Surface;
+    out.position = in.position;
+    out.normal = in.normal;
+    out.world_position = in.world_position;
+    out.world_normal = in.world_normal;
+    out.tex_coords = tex_coords;
+    out.tint = in.tint;
+    out.params_id = in.params_id;
+    // Water gathers on the upward surfaces, darker and glossier
+    let wet = frame.wetness * smoothstep(0.3, 0.9, in.world_normal.y);
+    let albedo = tex_color * vec4(material.diffuse_color * in.color.rgb, 1.);
+    out.albedo = vec4(albedo.rgb * mix(1.0, 0.55, wet), albedo.a);
+    out.ambient = ambient;
+    out.light = ambient + direct_light(out, 0.0);
+    out.specular = wet_specular(in.world_position, in.world_normal, wet);
+    return out;
+}
+
+fn velocity_of(in: VertexOutput) -> vec2f {
+    if in.clip_current.w <= 0.0 || in.clip_previous.w <= 0.0 {
+        return vec2f(0.0);
+    }
+    let ndc = in.clip_current.xy / in.clip_current.w - in.clip_previous.xy / in.clip_previous.w;
+    return ndc * vec2f(0.5, -0.5);
+}
+
+// View space z of a depth buffer value, for perspective and orthographic projections
+fn view_z_of(depth: f32) -> f32 {
+    return (proj[3][2] - depth * proj[3][3]) / (depth * proj[2][3] - proj[2][2]);
+}
+
+// Fades the fragment out as it gets closer to the opaque scene behind it
+fn depth_fade_of(in: VertexOutput) -> f32 {
+    let distance = materials[in.material_id].depth_fade;
+    let scene = textureLoad(t_scene_depth, vec2u(in.clip_position.xy), 0);
+    let gap = view_z_of(in.clip_position.z) - view_z_of(scene);
+    return saturate(gap / distance);
+}
+
+// 4x4 ordered dither of the pixel, from 0 to 1
+// Noise cells per model unit of the dissolve
+const DISSOLVE_NOISE_SCALE: f32 = 4.0;
+// Width of the glowing edge of the dissolve, in noise values
+const DISSOLVE_EDGE: f32 = 0.08;
+
+fn hash3(p: vec3f) -> f32 {
+    var q = fract(p * 0.1031);
+    q += dot(q, q.zyx + 31.32);
+    return fract((q.x + q.y) * q.z);
+}
+
+// Smooth value noise from 0 to 1
+fn value_noise(p: vec3f) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let e = vec2f(1.0, 0.0);
+    let bottom = mix(
+        mix(hash3(i), hash3(i + e.xyy), u.x),
+        mix(hash3(i + e.yxy), hash3(i + e.xxy), u.x),
+        u.y,
+    );
+    let top = mix(
+        mix(hash3(i + e.yyx), hash3(i + e.xyx), u.x),
+        mix(hash3(i + e.yxx), hash3(i + e.xxx), u.x),
+        u.y,
+    );
+    return mix(bottom, top, u.z);
+}
+
+fn bayer(pixel: vec2f) -> f32 {
+    var matrix = array<f32, 16>(0., 8., 2., 10., 12., 4., 14., 6., 3., 11., 1., 9., 15., 7., 13., 5.);
+    let p = vec2u(pixel) % 4u;
+    return (matrix[p.y * 4u + p.x] + 0.5) / 16.0;
+}
+
+fn output_of(in: VertexOutput, color: vec4f) -> FragmentOutput {
+    // Screen door dissolve between two levels of detail, they draw complementary pixels
+    let threshold = bayer(in.clip_position.xy);
+    if (in.fade > 0.0 && threshold >= in.fade) || (in.fade < 0.0 && threshold < -in.fade) {
+        discard;
+    }
+    var out: FragmentOutput;
+    out.color = color;
+    // Spawns and removals burn away from the noise, along a glowing edge
+    let dissolve = param_block_of(in.params_id).dissolve;
+    if dissolve.w > 0.0 {
+        let above = value_noise(in.position * DISSOLVE_NOISE_SCALE) - dissolve.w;
+        if above < 0.0 {
+            discard;
         }
+        let edge = 1.0 - smoothstep(0.0, DISSOLVE_EDGE, above);
+        out.color += vec4(dissolve.rgb * edge, 0.0);
+    }
+    if FADE_PASS {
+        out.color.a *= depth_fade_of(in);
     }
-    
-    let diffuse_color = tex_color * vec4(material.diffuse_color, 1.);
-    return diffuse_color * vec4(ambient, 1.);
+    out.velocity = velocity_of(in);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let surface = surface_of(in);
+    let shaded = surface.albedo * vec4(surface.light, 1.) + vec4(surface.specular, 0.);
+    return output_of(in, vec4(mix(shaded.rgb, surface.tint.rgb, surface.tint.a), shaded.a));
 }
 
 fn diffuse(normal: vec3f, light_dir: vec3f) -> f32 { return max(dot(normal, light_dir), 0.0); }
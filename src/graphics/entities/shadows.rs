@@ -0,0 +1,649 @@
+use nalgebra::{Matrix4, Orthographic3, Point3, Vector3, Vector4};
+
+use crate::{
+    constants,
+    graphics::{
+        buffer::{CommonBuffer, StorageBuffer, UniformBuffer, WriteBuffer},
+        camera::{
+            view_proj_bind_group_layout, view_proj_bindgroup, RawRelativeView,
+            OPENGL_TO_WGPU_MATRIX,
+        },
+        churn::{self, StateChanges},
+        ctx::GraphicsCtx,
+        depth::SceneDepth,
+        frame::FrameConstants,
+        settings::ShadowSettings,
+    },
+};
+
+use super::{
+    draws::{DrawGenerator, VisibleInstance},
+    lightmap::Lightmap,
+    local_shadows::LocalShadows,
+    model::{materials_buffer_bind_group_layout, MaterialsBuffer, ModelsBuffer, VertexPrecision},
+    zones::MAX_ZONES,
+};
+
+pub const MAX_CASCADES: usize = 4;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+pub(super) const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Casters this far behind a cascade towards the light still land in its map
+const CASTER_DISTANCE: f32 = 100.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(super) struct RawCascades {
+    view_proj: [Matrix4<f32>; MAX_CASCADES],
+    /// Far distance of each cascade along the camera forward
+    splits: [f32; MAX_CASCADES],
+    camera_position: [f32; 3],
+    /// Index of the shadowed light, `u32::MAX` when there is none
+    light_index: u32,
+    camera_forward: [f32; 3],
+    count: u32,
+    blend: f32,
+    normal_bias: f32,
+    pcss_softness: f32,
+    _padding: f32,
+    /// World units between the near and far planes of each cascade
+    depth_ranges: [f32; MAX_CASCADES],
+    /// World width covered by each cascade
+    extents: [f32; MAX_CASCADES],
+}
+
+crate::wgsl_layout!(RawCascades as "ShadowCascades" {
+    view_proj,
+    splits,
+    camera_position,
+    light_index,
+    camera_forward,
+    count,
+    blend,
+    normal_bias,
+    pcss_softness,
+    depth_ranges,
+    extents,
+});
+
+struct Cascade {
+    view: UniformBuffer<Matrix4<f32>>,
+    proj: UniformBuffer<Matrix4<f32>>,
+    /// Casters are drawn relative to the eye of the cascade, see [`RawRelativeView`]
+    relative: UniformBuffer<RawRelativeView>,
+    view_proj: Matrix4<f32>,
+    bind_group: wgpu::BindGroup,
+    target: wgpu::TextureView,
+    /// Casters inside the cascade, culled on the gpu
+    draws: DrawGenerator,
+}
+
+/// Shadow maps of the first directional light, split over the camera frustum
+///
+/// Each cascade is a bounding sphere of its slice of the frustum, so its size does not change
+/// as the camera turns, and is moved by whole texels to keep the edges from shimmering
+pub struct ShadowCascades {
+    cascades: Vec<Cascade>,
+    raw: UniformBuffer<RawCascades>,
+    /// Every zone visible, casters of hidden zones still shadow the visible ones
+    zones_visible: StorageBuffer<u32>,
+    count: u32,
+    /// Indexed by [`VertexPrecision`]
+    pipelines: [wgpu::RenderPipeline; 2],
+    array_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    /// Bound with the shadows, recreated with the viewport
+    scene_depth: wgpu::TextureView,
+    /// Shadows of the point and spot lights, drawn with the same casters pipelines
+    pub local: LocalShadows,
+    /// Also binds the lightmap, the scene depth and the local shadows, see
+    /// [`shadows_bind_group_layout`]
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowCascades {
+    /// `shader` is the entities shader, casters are drawn with its `vs_shadow` entry
+    pub fn new(
+        ctx: &GraphicsCtx,
+        shader: &wgpu::ShaderModule,
+        lightmap: &Lightmap,
+        frame: &FrameConstants,
+        scene_depth: &SceneDepth,
+        models: &ModelsBuffer,
+    ) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow cascades"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: MAX_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let cascades = (0..MAX_CASCADES as u32)
+            .map(|layer| {
+                let view = UniformBuffer::new("Shadow cascade view", ctx, &Matrix4::identity());
+                let proj = UniformBuffer::new("Shadow cascade proj", ctx, &Matrix4::identity());
+                let relative = UniformBuffer::new(
+                    "Shadow cascade relative view",
+                    ctx,
+                    &RawRelativeView::new(&Matrix4::identity()),
+                );
+                Cascade {
+                    bind_group: view_proj_bindgroup(ctx, &view, &proj, &frame.buffer, &relative),
+                    view,
+                    proj,
+                    relative,
+                    view_proj: Matrix4::identity(),
+                    draws: DrawGenerator::new(ctx, models),
+                    target: texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("Shadow cascade target"),
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_array_layer: layer,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    }),
+                }
+            })
+            .collect();
+
+        let raw = UniformBuffer::new(
+            "Shadow cascades",
+            ctx,
+            &RawCascades {
+                light_index: u32::MAX,
+                ..bytemuck::Zeroable::zeroed()
+            },
+        );
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let scene_depth = scene_depth.texture.view.clone();
+        let local = LocalShadows::new(ctx, frame, models);
+        let bind_group = shadows_bind_group(
+            ctx,
+            &array_view,
+            &sampler,
+            &raw,
+            lightmap,
+            &scene_depth,
+            &local,
+        );
+
+        Self {
+            cascades,
+            raw,
+            zones_visible: StorageBuffer::new_array(
+                "Shadow zones visibility",
+                ctx,
+                &[1; MAX_ZONES][..],
+            ),
+            count: 0,
+            pipelines: VertexPrecision::ALL
+                .map(|precision| shadow_pipeline(ctx, shader, precision)),
+            array_view,
+            sampler,
+            scene_depth,
+            local,
+            bind_group,
+        }
+    }
+
+    /// Called when the lightmap texture is recreated
+    pub fn bind_lightmap(&mut self, ctx: &GraphicsCtx, lightmap: &Lightmap) {
+        self.bind_group = shadows_bind_group(
+            ctx,
+            &self.array_view,
+            &self.sampler,
+            &self.raw,
+            lightmap,
+            &self.scene_depth,
+            &self.local,
+        );
+    }
+
+    /// Called when the scene depth is recreated with the viewport
+    pub fn bind_scene_depth(
+        &mut self,
+        ctx: &GraphicsCtx,
+        lightmap: &Lightmap,
+        scene_depth: &SceneDepth,
+    ) {
+        self.scene_depth = scene_depth.texture.view.clone();
+        self.bind_lightmap(ctx, lightmap);
+    }
+
+    /// Fits the cascades to the camera frustum, every frame
+    ///
+    /// `light` is the index and the direction of the shadowed directional light
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &ShadowSettings,
+        inv_view_proj: Matrix4<f32>,
+        light: Option<(u32, Vector3<f32>)>,
+    ) {
+        let Some((light_index, direction)) = light
+            .filter(|_| settings.enabled)
+            .and_then(|(index, direction)| Some((index, direction.try_normalize(1e-6)?)))
+        else {
+            self.count = 0;
+            self.raw.write(
+                ctx,
+                &RawCascades {
+                    light_index: u32::MAX,
+                    ..bytemuck::Zeroable::zeroed()
+                },
+            );
+            return;
+        };
+
+        let unproject = |x: f32, y: f32, z: f32| {
+            let point = inv_view_proj * Vector4::new(x, y, z, 1.0);
+            Point3::from(point.xyz() / point.w)
+        };
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let near = corners.map(|(x, y)| unproject(x, y, 0.0));
+        let far = corners.map(|(x, y)| unproject(x, y, 1.0));
+        let near_center = Point3::from(near.iter().map(|p| p.coords).sum::<Vector3<f32>>() / 4.0);
+        let far_center = Point3::from(far.iter().map(|p| p.coords).sum::<Vector3<f32>>() / 4.0);
+        let depth = (far_center - near_center).norm().max(1e-3);
+        let forward = (far_center - near_center) / depth;
+
+        let count = settings.cascades.clamp(1, MAX_CASCADES as u32);
+        let max_distance = settings
+            .max_distance
+            .clamp(constants::MODEL_ZNEAR * 2.0, depth);
+        let splits = split_distances(count, max_distance, settings.split_lambda);
+
+        let mut raw = RawCascades {
+            view_proj: [Matrix4::identity(); MAX_CASCADES],
+            splits: [0.0; MAX_CASCADES],
+            camera_position: near_center.into(),
+            light_index,
+            camera_forward: forward.into(),
+            count,
+            blend: settings.blend.clamp(0.0, 1.0),
+            normal_bias: settings.normal_bias,
+            pcss_softness: settings.pcss_softness,
+            _padding: 0.0,
+            depth_ranges: [0.0; MAX_CASCADES],
+            extents: [0.0; MAX_CASCADES],
+        };
+        let mut start = 0.0;
+        for (i, end) in splits.iter().copied().enumerate() {
+            let slice = [start, end].map(|distance| distance / depth);
+            let points = slice.iter().flat_map(|t| {
+                near.iter()
+                    .zip(&far)
+                    .map(move |(near, far)| near + (far - near) * *t)
+            });
+            let (view, proj, radius) = fit_cascade(points.collect(), direction);
+            self.cascades[i].view.write(ctx, &view);
+            self.cascades[i]
+                .relative
+                .write(ctx, &RawRelativeView::new(&view));
+            self.cascades[i].proj.write(ctx, &proj);
+            self.cascades[i].view_proj = proj * view;
+            raw.view_proj[i] = proj * view;
+            raw.splits[i] = end;
+            raw.depth_ranges[i] = radius * 2.0 + CASTER_DISTANCE;
+            raw.extents[i] = radius * 2.0;
+            start = end;
+        }
+        self.count = count;
+        self.raw.write(ctx, &raw);
+    }
+
+    /// Keeps the draws of the cascades grown with the instances
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx, models: &ModelsBuffer) {
+        for cascade in &mut self.cascades {
+            cascade.draws.apply_changes(ctx, models);
+        }
+        self.local.apply_changes(ctx, models);
+    }
+
+    /// Depth of the instances inside each cascade from the light, then inside each view of the
+    /// local shadows, before the pass sampling the maps. The casters are culled into a compacted
+    /// draw list, see [`DrawGenerator::draw`]
+    pub fn render(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        models: &ModelsBuffer,
+        materials: &MaterialsBuffer,
+    ) {
+        for cascade in &self.cascades[..self.count as usize] {
+            cascade.draws.generate(
+                ctx,
+                encoder,
+                models,
+                &self.zones_visible,
+                &cascade.view_proj,
+            );
+
+            let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow cascade"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &cascade.target,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            draw_casters(
+                &mut pass.forget_lifetime(),
+                &cascade.bind_group,
+                &cascade.draws,
+                models,
+                materials,
+                &self.pipelines,
+            );
+        }
+        self.local.render(
+            ctx,
+            encoder,
+            models,
+            materials,
+            &self.zones_visible,
+            &self.pipelines,
+        );
+    }
+}
+
+/// Culled casters of a shadow view into the depth target of `pass`
+pub(super) fn draw_casters(
+    pass: &mut wgpu::RenderPass<'static>,
+    bind_group: &wgpu::BindGroup,
+    draws: &DrawGenerator,
+    models: &ModelsBuffer,
+    materials: &MaterialsBuffer,
+    pipelines: &[wgpu::RenderPipeline; 2],
+) {
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.set_bind_group(1, &materials.bind_group, &[]);
+    pass.set_vertex_buffer(1, draws.instances().as_slice());
+    pass.set_index_buffer(models.index_buffer.as_slice(), wgpu::IndexFormat::Uint16);
+    churn::record(
+        "Shadows",
+        StateChanges {
+            bind_groups: 2,
+            vertex_buffers: 1,
+            index_buffers: 1,
+            ..Default::default()
+        },
+    );
+    for precision in VertexPrecision::ALL {
+        if models.mesh_count_with(precision) == 0 {
+            continue;
+        }
+        pass.set_pipeline(&pipelines[precision as usize]);
+        pass.set_vertex_buffer(0, models.vertex_slice(precision));
+        draws.draw(pass, precision);
+        churn::record(
+            "Shadows",
+            StateChanges {
+                pipelines: 1,
+                vertex_buffers: 1,
+                draws: 1,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Far distance of each cascade, between even and logarithmic splits by `lambda`
+fn split_distances(count: u32, max_distance: f32, lambda: f32) -> Vec<f32> {
+    let near = constants::MODEL_ZNEAR;
+    (1..=count)
+        .map(|i| {
+            let f = i as f32 / count as f32;
+            let uniform = near + (max_distance - near) * f;
+            let log = near * (max_distance / near).powf(f);
+            uniform + (log - uniform) * lambda.clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// Light view and projection of the sphere around the points, snapped to the map texels,
+/// with the radius of the sphere
+fn fit_cascade(
+    points: Vec<Point3<f32>>,
+    direction: Vector3<f32>,
+) -> (Matrix4<f32>, Matrix4<f32>, f32) {
+    let center =
+        Point3::from(points.iter().map(|p| p.coords).sum::<Vector3<f32>>() / points.len() as f32);
+    let radius = points
+        .iter()
+        .map(|p| (p - center).norm())
+        .fold(0.0, f32::max);
+    // Rounded so the size is the same from one frame to the next
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    let eye = center - direction * (radius + CASTER_DISTANCE);
+    let view = Matrix4::look_at_rh(&eye, &center, &up);
+    let ortho = Orthographic3::new(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        radius * 2.0 + CASTER_DISTANCE,
+    );
+    let mut proj = OPENGL_TO_WGPU_MATRIX * ortho.to_homogeneous();
+
+    let origin = proj * view * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let half_size = SHADOW_MAP_SIZE as f32 * 0.5;
+    let texel = origin.xy() * half_size;
+    let offset = (texel.map(f32::round) - texel) / half_size;
+    proj[(0, 3)] += offset.x;
+    proj[(1, 3)] += offset.y;
+    (view, proj, radius)
+}
+
+fn shadow_pipeline(
+    ctx: &GraphicsCtx,
+    shader: &wgpu::ShaderModule,
+    precision: VertexPrecision,
+) -> wgpu::RenderPipeline {
+    let layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow casters"),
+            bind_group_layouts: &[
+                &view_proj_bind_group_layout(ctx),
+                &materials_buffer_bind_group_layout(ctx),
+            ],
+            push_constant_ranges: &[],
+        });
+    ctx.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow casters"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_shadow"),
+                buffers: &[precision.buffer_desc(), VisibleInstance::buffer_desc()],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: None,
+            multiview: None,
+            cache: None,
+        })
+}
+
+fn shadows_bind_group(
+    ctx: &GraphicsCtx,
+    array_view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    raw: &UniformBuffer<RawCascades>,
+    lightmap: &Lightmap,
+    scene_depth: &wgpu::TextureView,
+    local: &LocalShadows,
+) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &shadows_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(array_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: raw.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&lightmap.texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Sampler(&lightmap.texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(scene_depth),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&local.atlas_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: local.raw.binding(),
+            },
+        ],
+        label: Some("Shadows Bind Group"),
+    })
+}
+
+/// Shadow cascades, the baked lightmap at bindings 3 and 4, the scene depth at 5 and the local
+/// shadows at 6 and 7 so the built-in groups stay within the bind group limit
+pub fn shadows_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Shadows Bind Group Layout"),
+        })
+}
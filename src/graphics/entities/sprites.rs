@@ -0,0 +1,156 @@
+use std::time::Instant;
+
+use image::DynamicImage;
+
+use crate::graphics::{ctx::GraphicsCtx, streaming::TextureStreamer};
+
+use super::{
+    import::SpriteSheet,
+    model::{Material, MaterialsBuffer},
+};
+
+/// Frames of a sprite sheet split by [`split_sheets`], waiting for their atlas slots
+pub struct SheetFrames {
+    texture_id: u32,
+    fps: f32,
+    /// Every frame but the first, which replaced the sheet
+    frames: Vec<DynamicImage>,
+}
+
+/// Replaces each sprite sheet with its first frame, so the texture id of the sheet stays the
+/// slot its materials sample until they are animated
+pub fn split_sheets(
+    textures: &mut [DynamicImage],
+    sheets: &[Option<SpriteSheet>],
+) -> Vec<SheetFrames> {
+    let mut split = Vec::new();
+    for (texture_id, (texture, sheet)) in textures.iter_mut().zip(sheets).enumerate() {
+        let Some(sheet) = sheet else {
+            continue;
+        };
+        let (width, height) = (
+            texture.width() / sheet.columns,
+            texture.height() / sheet.rows,
+        );
+        if width == 0 || height == 0 || sheet.frame_count() < 2 {
+            eprintln!("Texture {texture_id} is too small for its sprite sheet, it stays still");
+            continue;
+        }
+        let mut frames: Vec<_> = (0..sheet.frame_count())
+            .map(|frame| {
+                let (column, row) = (frame % sheet.columns, frame / sheet.columns);
+                texture.crop_imm(column * width, row * height, width, height)
+            })
+            .collect();
+        *texture = frames.remove(0);
+        split.push(SheetFrames {
+            texture_id: texture_id as u32,
+            fps: sheet.fps,
+            frames,
+        });
+    }
+    split
+}
+
+/// A sprite sheet with each frame in its own atlas slot
+pub struct TextureAnimation {
+    /// Id of the sheet, holding its first frame
+    pub texture_id: u32,
+    /// Texture id of each frame, in order
+    pub slots: Vec<u32>,
+    pub fps: f32,
+    /// Materials with the sheet as their diffuse texture
+    pub materials: Vec<u32>,
+    frame: usize,
+}
+
+impl TextureAnimation {
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+}
+
+/// Advances the frames of the sprite sheets by pointing the `diffuse_texture_id` of their
+/// materials at the slot of the current frame, the shader samples it like any texture
+pub struct SpriteAnimator {
+    pub animations: Vec<TextureAnimation>,
+    pub paused: bool,
+    start: Instant,
+}
+
+impl SpriteAnimator {
+    /// Adds the frames to the atlas, they are streamed like the sheet they come from
+    pub fn new(
+        ctx: &GraphicsCtx,
+        textures: &mut TextureStreamer,
+        sheets: Vec<SheetFrames>,
+        materials: &[Material],
+    ) -> Self {
+        let animations = sheets
+            .into_iter()
+            .map(|sheet| {
+                let mut slots = vec![sheet.texture_id];
+                let color_space = textures.color_space(sheet.texture_id);
+                for frame in sheet.frames {
+                    let slot = textures.add_texture(ctx, frame, color_space);
+                    textures.share_usage(sheet.texture_id, slot);
+                    slots.push(slot);
+                }
+                TextureAnimation {
+                    texture_id: sheet.texture_id,
+                    slots,
+                    fps: sheet.fps,
+                    materials: (0..materials.len() as u32)
+                        .filter(|&id| materials[id as usize].diffuse_texture_id == sheet.texture_id)
+                        .collect(),
+                    frame: 0,
+                }
+            })
+            .collect();
+        Self {
+            animations,
+            paused: false,
+            start: Instant::now(),
+        }
+    }
+
+    /// Gives every frame of a sheet the usage of all of them, only the current one is used by
+    /// the instances
+    pub fn share_usage(&self, textures: &mut TextureStreamer) {
+        for animation in &self.animations {
+            for &slot in &animation.slots {
+                textures.merge_usage(slot, animation.texture_id);
+            }
+            for &slot in &animation.slots[1..] {
+                textures.share_usage(animation.texture_id, slot);
+            }
+        }
+    }
+
+    /// Every frame, only the materials of the sheets changing frame are uploaded
+    pub fn update(&mut self, materials: &mut MaterialsBuffer) {
+        if self.paused {
+            return;
+        }
+        let time = self.start.elapsed().as_secs_f32();
+        for animation in &mut self.animations {
+            let frame = (time * animation.fps) as usize % animation.slots.len();
+            if frame == animation.frame {
+                continue;
+            }
+            animation.frame = frame;
+            for &material_id in &animation.materials {
+                let Some(&material) = materials.get(material_id) else {
+                    continue;
+                };
+                materials.set(
+                    material_id,
+                    Material {
+                        diffuse_texture_id: animation.slots[frame],
+                        ..material
+                    },
+                );
+            }
+        }
+    }
+}
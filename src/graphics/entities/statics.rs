@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::graphics::{
+    buffer::{CommonBuffer, IndexBuffer, VertexBuffer},
+    ctx::GraphicsCtx,
+};
+
+use super::{
+    draws::VisibleInstance,
+    model::{ModelInstance, ModelVertex, ModelsBuffer},
+};
+
+/// Instances merged into one draw share these, the rest of the instance is baked into the
+/// vertices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct BatchKey {
+    material_id: u32,
+    tint: [u8; 4],
+    lightmapped: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct StaticBatch {
+    first_index: u32,
+    index_count: u32,
+    base_vertex: i32,
+}
+
+/// Instances that never move, merged per material into pre-transformed geometry drawn without
+/// the culling and the instance buffer of the dynamic instances
+///
+/// Each batch is one instance at the position of the first merged one, its vertices are relative
+/// to it so the world origin only moves the instance. A batch is split once it would pass the
+/// vertices a `u16` index reaches
+#[derive(Default)]
+pub struct StaticBatches {
+    batches: Vec<StaticBatch>,
+    /// One per batch, same index
+    instances: Vec<VisibleInstance>,
+    merged_count: u32,
+    vertex_buffer: Option<VertexBuffer<ModelVertex>>,
+    index_buffer: Option<IndexBuffer<u16>>,
+    instance_buffer: Option<VertexBuffer<VisibleInstance>>,
+    /// Geometry built since the last [`Self::apply_changes`]
+    pending: Option<(Vec<ModelVertex>, Vec<u16>)>,
+    instances_changed: bool,
+}
+
+impl StaticBatches {
+    /// Replaces the batches by the merged `instances`, given by their model and mesh ids and
+    /// uploaded on the next [`Self::apply_changes`]
+    pub fn build(
+        &mut self,
+        models: &ModelsBuffer,
+        instances: impl IntoIterator<Item = (u16, u16, ModelInstance)>,
+    ) {
+        let mut grouped = BTreeMap::<BatchKey, Vec<_>>::new();
+        for (model_id, mesh_id, instance) in instances {
+            let key = BatchKey {
+                material_id: instance.material_id,
+                tint: instance.tint,
+                lightmapped: instance.lightmap[2] > 0.0,
+            };
+            grouped
+                .entry(key)
+                .or_default()
+                .push((model_id, mesh_id, instance));
+        }
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        self.batches.clear();
+        self.instances.clear();
+        self.merged_count = 0;
+        for (key, group) in grouped {
+            let mut batch: Option<(StaticBatch, Point3<f32>)> = None;
+            for (model_id, mesh_id, instance) in group {
+                let (mesh_vertices, mesh_indices) = models.mesh_geometry(model_id, mesh_id);
+                let full = batch.as_ref().is_some_and(|(batch, _)| {
+                    vertices.len() - batch.base_vertex as usize + mesh_vertices.len()
+                        > u16::MAX as usize + 1
+                });
+                if full {
+                    if let Some((batch, origin)) = batch.take() {
+                        self.push_batch(batch, origin, key);
+                    }
+                }
+                let (current, origin) = batch.get_or_insert_with(|| {
+                    let batch = StaticBatch {
+                        first_index: indices.len() as u32,
+                        index_count: 0,
+                        base_vertex: vertices.len() as i32,
+                    };
+                    (batch, Point3::from(instance.translation))
+                });
+
+                let offset = (vertices.len() - current.base_vertex as usize) as u16;
+                indices.extend(mesh_indices.iter().map(|i| i + offset));
+                current.index_count += mesh_indices.len() as u32;
+                vertices.extend(
+                    mesh_vertices
+                        .iter()
+                        .map(|vertex| baked_vertex(vertex, &instance, *origin)),
+                );
+                self.merged_count += 1;
+            }
+            if let Some((batch, origin)) = batch {
+                self.push_batch(batch, origin, key);
+            }
+        }
+        self.pending = Some((vertices, indices));
+        self.instances_changed = true;
+    }
+
+    fn push_batch(&mut self, batch: StaticBatch, origin: Point3<f32>, key: BatchKey) {
+        let lightmap = match key.lightmapped {
+            // The rects were applied to the vertices
+            true => [0.0, 0.0, 1.0, 1.0],
+            false => [0.0; 4],
+        };
+        let instance =
+            ModelInstance::new(Matrix4::new_translation(&origin.coords), key.material_id)
+                .with_tint(key.tint)
+                .with_lightmap(lightmap);
+        self.batches.push(batch);
+        self.instances.push(VisibleInstance {
+            instance,
+            previous_transform: previous_transform(&origin),
+        });
+    }
+
+    pub fn apply_changes(&mut self, ctx: &GraphicsCtx) {
+        if let Some((vertices, indices)) = self.pending.take() {
+            let (vertex_buffer, index_buffer) = match vertices.is_empty() {
+                true => (None, None),
+                false => (
+                    Some(VertexBuffer::new_array(
+                        "Static batches",
+                        ctx,
+                        &vertices[..],
+                    )),
+                    Some(IndexBuffer::new_array("Static batches", ctx, &indices[..])),
+                ),
+            };
+            self.vertex_buffer = vertex_buffer;
+            self.index_buffer = index_buffer;
+        }
+        if std::mem::take(&mut self.instances_changed) {
+            self.instance_buffer = (!self.instances.is_empty()).then(|| {
+                VertexBuffer::new_array("Static batch instances", ctx, &self.instances[..])
+            });
+        }
+    }
+
+    /// Follows the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for visible in &mut self.instances {
+            let translation = Point3::from(visible.instance.translation) - shift;
+            visible.instance.translation = translation.into();
+            visible.previous_transform = previous_transform(&translation);
+        }
+        self.instances_changed = !self.instances.is_empty();
+    }
+
+    pub fn batch_count(&self) -> u32 {
+        self.batches.len() as u32
+    }
+
+    /// Instances merged into the batches
+    pub fn merged_count(&self) -> u32 {
+        self.merged_count
+    }
+
+    /// Binds the merged geometry over the vertex, instance and index buffers of the entities,
+    /// returns false when there is nothing to draw
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass<'static>) -> bool {
+        let (Some(vertices), Some(indices), Some(instances)) = (
+            &self.vertex_buffer,
+            &self.index_buffer,
+            &self.instance_buffer,
+        ) else {
+            return false;
+        };
+        render_pass.set_vertex_buffer(0, vertices.as_slice());
+        render_pass.set_vertex_buffer(1, instances.as_slice());
+        render_pass.set_index_buffer(indices.as_slice(), wgpu::IndexFormat::Uint16);
+        true
+    }
+
+    /// One draw per batch, after [`Self::bind`] with a full precision pipeline
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'static>) {
+        for (i, batch) in self.batches.iter().enumerate() {
+            let indices = batch.first_index..batch.first_index + batch.index_count;
+            let i = i as u32;
+            render_pass.draw_indexed(indices, batch.base_vertex, i..i + 1);
+        }
+    }
+}
+
+/// `vertex` of `instance` in the space of a batch placed at `origin`
+fn baked_vertex(
+    vertex: &ModelVertex,
+    instance: &ModelInstance,
+    origin: Point3<f32>,
+) -> ModelVertex {
+    let rotation = instance.rotation();
+    let scale = Vector3::from(instance.scale);
+    let translation = Point3::from(instance.translation) - origin;
+    let position = rotation * Vector3::from(vertex.position).component_mul(&scale) + translation;
+    // Inverse transpose of the scale, the normals stay perpendicular under a non uniform one
+    let normal = rotation * Vector3::from(vertex.normal).component_div(&scale);
+    let normal = normal.try_normalize(1e-6).unwrap_or(normal);
+    let [x, y, z, w] = instance.lightmap;
+    let lightmap_uv = match z > 0.0 {
+        true => [x + vertex.lightmap_uv[0] * z, y + vertex.lightmap_uv[1] * w],
+        false => vertex.lightmap_uv,
+    };
+    ModelVertex {
+        position: position.into(),
+        normal: normal.into(),
+        lightmap_uv,
+        ..*vertex
+    }
+}
+
+/// Rows of the affine transform of a batch instance, it never moved
+fn previous_transform(origin: &Point3<f32>) -> [[f32; 4]; 3] {
+    [
+        [1.0, 0.0, 0.0, origin.x],
+        [0.0, 1.0, 0.0, origin.y],
+        [0.0, 0.0, 1.0, origin.z],
+    ]
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+use nalgebra::{Point3, Vector3};
+
+use crate::jobs::{self, Priority};
+
+use tobj::Mesh;
+
+use super::{
+    model::{ModelInstance, ModelInstanceId, ModelsBuffer},
+    BakedVertices,
+};
+
+/// Horizontal coordinates of a world cell
+pub type CellCoord = (i32, i32);
+
+pub struct StreamedInstance {
+    pub model_id: u16,
+    pub mesh_id: u16,
+    pub instance: ModelInstance,
+}
+
+/// Produces the instances of a cell at their absolute position, runs as a background job so it
+/// may block on io
+pub type CellLoader = Arc<dyn Fn(CellCoord) -> Vec<StreamedInstance> + Send + Sync>;
+
+/// Meshes of a model, in the order the [`ModelsBuffer`] was created with
+pub struct StreamedGeometry {
+    pub meshes: Vec<Mesh>,
+    pub baked: Vec<BakedVertices>,
+}
+
+/// Produces the geometry of a model, `None` when it can not be loaded again. Runs as a
+/// background job like [`CellLoader`]
+pub type GeometryLoader = Arc<dyn Fn(u16) -> Option<StreamedGeometry> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    Loading,
+    Loaded,
+}
+
+enum Cell {
+    Loading,
+    Loaded(Vec<ModelInstanceId>),
+}
+
+/// Loads the instances of the cells around the camera and unloads the ones left behind
+///
+/// The geometry of a model follows its instances, it is unloaded with the last cell using it
+/// and loaded again when a cell needs it
+pub struct WorldStreamer {
+    pub cell_size: f32,
+    /// Cells within this distance of the camera cell are loaded
+    pub load_radius: u32,
+    /// Extra ring kept loaded, avoids reloading cells when moving along their border
+    pub unload_margin: u32,
+
+    loader: Option<CellLoader>,
+    geometry_loader: Option<GeometryLoader>,
+    /// Models whose geometry is being loaded
+    geometry_requests: HashSet<u16>,
+    geometry_sender: Sender<(u16, StreamedGeometry)>,
+    geometry_receiver: Receiver<(u16, StreamedGeometry)>,
+    /// Absolute position of the world origin, cells stay put when it moves
+    origin: Vector3<f64>,
+    cells: HashMap<CellCoord, Cell>,
+    center: CellCoord,
+    sender: Sender<(CellCoord, Vec<StreamedInstance>)>,
+    receiver: Receiver<(CellCoord, Vec<StreamedInstance>)>,
+}
+
+impl WorldStreamer {
+    pub fn new(cell_size: f32, load_radius: u32) -> Self {
+        let (sender, receiver) = channel();
+        let (geometry_sender, geometry_receiver) = channel();
+        Self {
+            cell_size,
+            load_radius,
+            unload_margin: 1,
+            loader: None,
+            geometry_loader: None,
+            geometry_requests: HashSet::new(),
+            geometry_sender,
+            geometry_receiver,
+            origin: Vector3::zeros(),
+            cells: HashMap::new(),
+            center: (0, 0),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Replaces the loader, cells loaded by the previous one are unloaded
+    pub fn set_loader(&mut self, models: &mut ModelsBuffer, loader: Option<CellLoader>) {
+        for (_, cell) in self.cells.drain() {
+            if let Cell::Loaded(ids) = cell {
+                unload_instances(models, ids, self.geometry_loader.is_some());
+            }
+        }
+        // Results of the previous loader are still in flight, they are dropped on arrival
+        let (sender, receiver) = channel();
+        self.sender = sender;
+        self.receiver = receiver;
+        self.loader = loader;
+    }
+
+    /// Without a geometry loader the geometry of the models stays resident
+    pub fn set_geometry_loader(&mut self, loader: Option<GeometryLoader>) {
+        self.geometry_loader = loader;
+    }
+
+    /// Cell of a position relative to the world origin
+    pub fn cell_of(&self, position: Point3<f32>) -> CellCoord {
+        let cell_size = self.cell_size as f64;
+        (
+            ((position.x as f64 + self.origin.x) / cell_size).floor() as i32,
+            ((position.z as f64 + self.origin.z) / cell_size).floor() as i32,
+        )
+    }
+
+    /// Follows the world origin, instances already loaded are moved by the caller
+    pub fn rebase(&mut self, origin: Vector3<f64>) {
+        self.origin = origin;
+    }
+
+    /// Cell containing the camera at the last update
+    pub fn center(&self) -> CellCoord {
+        self.center
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (CellCoord, CellState)> + '_ {
+        self.cells.iter().map(|(coord, cell)| {
+            let state = match cell {
+                Cell::Loading => CellState::Loading,
+                Cell::Loaded(_) => CellState::Loaded,
+            };
+            (*coord, state)
+        })
+    }
+
+    pub fn update(&mut self, models: &mut ModelsBuffer, camera_position: Point3<f32>) {
+        let Some(loader) = self.loader.clone() else {
+            return;
+        };
+        self.center = self.cell_of(camera_position);
+
+        while let Ok((coord, instances)) = self.receiver.try_recv() {
+            // Cells unloaded while loading are missing from the map
+            let Some(cell) = self.cells.get_mut(&coord) else {
+                continue;
+            };
+            if !matches!(cell, Cell::Loading) {
+                continue;
+            }
+            let ids = instances
+                .into_iter()
+                .map(|mut i| {
+                    let translation = &mut i.instance.translation;
+                    for axis in 0..3 {
+                        translation[axis] = (translation[axis] as f64 - self.origin[axis]) as f32;
+                    }
+                    models.add_instance(i.model_id, i.mesh_id, i.instance)
+                })
+                .collect();
+            *cell = Cell::Loaded(ids);
+        }
+
+        let unload_radius = (self.load_radius + self.unload_margin) as i32;
+        let center = self.center;
+        let far: Vec<_> = self
+            .cells
+            .keys()
+            .filter(|coord| ring_distance(**coord, center) > unload_radius)
+            .copied()
+            .collect();
+        for coord in far {
+            if let Some(Cell::Loaded(ids)) = self.cells.remove(&coord) {
+                unload_instances(models, ids, self.geometry_loader.is_some());
+            }
+        }
+        self.stream_geometry(models);
+
+        let radius = self.load_radius as i32;
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                let coord = (center.0 + x, center.1 + z);
+                if self.cells.contains_key(&coord) {
+                    continue;
+                }
+                self.cells.insert(coord, Cell::Loading);
+
+                let loader = loader.clone();
+                let sender = self.sender.clone();
+                jobs::spawn_then(
+                    "Cell load",
+                    Priority::Normal,
+                    move || loader(coord),
+                    move |instances| {
+                        // The streamer may be gone when the load finishes
+                        let _ = sender.send((coord, instances));
+                    },
+                );
+            }
+        }
+    }
+
+    /// Loads the geometry of the unloaded models that got instances again
+    fn stream_geometry(&mut self, models: &mut ModelsBuffer) {
+        let Some(loader) = self.geometry_loader.clone() else {
+            return;
+        };
+        while let Ok((model_id, geometry)) = self.geometry_receiver.try_recv() {
+            self.geometry_requests.remove(&model_id);
+            // Its instances may be gone again while loading
+            if models.is_resident(model_id) || models.model_instance_count(model_id) == 0 {
+                continue;
+            }
+            if let Err(e) = models.load_geometry(model_id, &geometry.meshes, &geometry.baked) {
+                eprintln!("Failed to stream the geometry of model {model_id}: {e}");
+            }
+        }
+
+        let missing: Vec<_> = models
+            .missing_geometry()
+            .filter(|model_id| !self.geometry_requests.contains(model_id))
+            .collect();
+        for model_id in missing {
+            self.geometry_requests.insert(model_id);
+            let loader = loader.clone();
+            let sender = self.geometry_sender.clone();
+            jobs::spawn_then(
+                "Model geometry load",
+                Priority::Normal,
+                move || loader(model_id),
+                move |geometry| match geometry {
+                    Some(geometry) => {
+                        let _ = sender.send((model_id, geometry));
+                    }
+                    None => eprintln!("No geometry to stream for model {model_id}"),
+                },
+            );
+        }
+    }
+}
+
+/// Removes the instances of a cell, and the geometry of the models left without instances when
+/// it can be loaded again
+fn unload_instances(models: &mut ModelsBuffer, ids: Vec<ModelInstanceId>, unload_geometry: bool) {
+    let mut model_ids = HashSet::new();
+    for id in ids {
+        model_ids.insert(id.model_id);
+        models.remove_instance(id);
+    }
+    if !unload_geometry {
+        return;
+    }
+    for model_id in model_ids {
+        if models.is_resident(model_id) && models.model_instance_count(model_id) == 0 {
+            // Checked above, it has no instance left
+            let _ = models.unload_geometry(model_id);
+        }
+    }
+}
+
+/// Chebyshev distance, cells are loaded in square rings
+fn ring_distance(a: CellCoord, b: CellCoord) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
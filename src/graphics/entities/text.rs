@@ -0,0 +1,288 @@
+use nalgebra::{Point2, Vector2};
+use tobj::Mesh;
+use ttf_parser::{Face, OutlineBuilder};
+
+use crate::graphics::color::Color3;
+
+use super::{model::Material, BakedVertices, EntityModel};
+
+/// Shape of the extruded text, lengths are in meters
+#[derive(Debug, Clone, Copy)]
+pub struct TextMeshOptions {
+    /// Height of the em square
+    pub size: f32,
+    /// Thickness along z, the front faces +z
+    pub depth: f32,
+    /// Line segments per quadratic or cubic curve of the outlines
+    pub curve_segments: u32,
+    pub color: Color3,
+}
+
+impl Default for TextMeshOptions {
+    fn default() -> Self {
+        Self {
+            size: 1.0,
+            depth: 0.1,
+            curve_segments: 6,
+            color: Color3::WHITE,
+        }
+    }
+}
+
+/// Extruded glyphs of `text` as a model of one mesh and one untextured material
+///
+/// The text starts at the origin on the baseline and goes towards +x, lines go down. Glyphs
+/// missing from the font are skipped
+pub fn text_model(
+    font: &[u8],
+    text: &str,
+    options: &TextMeshOptions,
+) -> Result<EntityModel, String> {
+    let face = Face::parse(font, 0).map_err(|e| format!("Invalid font: {e}"))?;
+    let scale = options.size / face.units_per_em() as f32;
+    let line_height = (face.ascender() - face.descender() + face.line_gap()) as f32 * scale;
+
+    let mut mesh = Mesh {
+        material_id: Some(0),
+        ..Default::default()
+    };
+    let mut pen = Vector2::zeros();
+    for c in text.chars() {
+        if c == '\n' {
+            pen = Vector2::new(0.0, pen.y - line_height);
+            continue;
+        }
+        let Some(glyph) = face.glyph_index(c) else {
+            continue;
+        };
+        let mut outline = Outline {
+            contours: Vec::new(),
+            segments: options.curve_segments.max(1),
+            scale,
+            offset: pen,
+        };
+        if face.outline_glyph(glyph, &mut outline).is_some() {
+            extrude_glyph(&mut mesh, outline.contours, options.depth)
+                .map_err(|e| format!("Failed to triangulate {c:?}: {e}"))?;
+        }
+        pen.x += face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+    }
+    if mesh.positions.len() / 3 > u16::MAX as usize + 1 {
+        return Err(String::from(
+            "Too many vertices for one mesh, split the text",
+        ));
+    }
+    // Planar mapping over the em square
+    mesh.texcoords = mesh
+        .positions
+        .chunks_exact(3)
+        .flat_map(|p| [p[0] / options.size, p[1] / options.size])
+        .collect();
+
+    Ok(EntityModel {
+        meshes: vec![mesh],
+        baked: vec![BakedVertices::default()],
+        precision: Default::default(),
+        materials: vec![Material {
+            diffuse_color: options.color.into(),
+            diffuse_texture_id: u32::MAX,
+            sampler_id: 0,
+            shader_id: 0,
+            wind: 0.0,
+            depth_fade: 0.0,
+        }],
+        textures: Vec::new(),
+        sprite_sheets: Vec::new(),
+        color_spaces: Vec::new(),
+    })
+}
+
+/// Flattened contours of a glyph, in meters
+struct Outline {
+    contours: Vec<Vec<Point2<f32>>>,
+    segments: u32,
+    scale: f32,
+    offset: Vector2<f32>,
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> Point2<f32> {
+        Point2::new(x, y) * self.scale + self.offset
+    }
+
+    fn last(&self) -> Point2<f32> {
+        self.contours
+            .last()
+            .and_then(|contour| contour.last())
+            .copied()
+            .unwrap_or(Point2::origin())
+    }
+
+    fn push(&mut self, point: Point2<f32>) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push(point);
+        }
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.contours.push(vec![point]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.push(point);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.last(), self.point(x1, y1), self.point(x, y));
+        for i in 1..=self.segments {
+            let t = i as f32 / self.segments as f32;
+            let u = 1.0 - t;
+            self.push(Point2::from(
+                p0.coords * u * u + p1.coords * 2.0 * u * t + p2.coords * t * t,
+            ));
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (
+            self.last(),
+            self.point(x1, y1),
+            self.point(x2, y2),
+            self.point(x, y),
+        );
+        for i in 1..=self.segments {
+            let t = i as f32 / self.segments as f32;
+            let u = 1.0 - t;
+            self.push(Point2::from(
+                p0.coords * u * u * u
+                    + p1.coords * 3.0 * u * u * t
+                    + p2.coords * 3.0 * u * t * t
+                    + p3.coords * t * t * t,
+            ));
+        }
+    }
+
+    fn close(&mut self) {
+        let Some(contour) = self.contours.last_mut() else {
+            return;
+        };
+        // The closing point repeats the first one
+        if contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+    }
+}
+
+/// Twice the signed area, positive for counter clockwise contours
+fn signed_area(contour: &[Point2<f32>]) -> f32 {
+    (0..contour.len())
+        .map(|i| {
+            let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum()
+}
+
+fn contains(contour: &[Point2<f32>], point: Point2<f32>) -> bool {
+    let mut inside = false;
+    for i in 0..contour.len() {
+        let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Front and back caps plus the flat shaded sides of one glyph
+fn extrude_glyph(
+    mesh: &mut Mesh,
+    contours: Vec<Vec<Point2<f32>>>,
+    depth: f32,
+) -> Result<(), String> {
+    let mut contours: Vec<_> = contours.into_iter().filter(|c| c.len() >= 3).collect();
+    // Holes are inside an odd number of contours, the fonts disagree on the winding
+    let nesting: Vec<_> = contours
+        .iter()
+        .enumerate()
+        .map(|(i, contour)| {
+            contours
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| *j != i && contains(other, contour[0]))
+                .count()
+        })
+        .collect();
+    // Solids counter clockwise and holes clockwise, the sides face away from the solid
+    for (contour, nesting) in contours.iter_mut().zip(&nesting) {
+        if (signed_area(contour) > 0.0) == (nesting % 2 == 1) {
+            contour.reverse();
+        }
+    }
+
+    let (front, back) = (depth * 0.5, -depth * 0.5);
+    for (i, outer) in contours
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| nesting[*i] % 2 == 0)
+    {
+        // Holes directly inside this solid
+        let holes: Vec<_> = contours
+            .iter()
+            .enumerate()
+            .filter(|(j, hole)| nesting[*j] == nesting[i] + 1 && contains(outer, hole[0]))
+            .map(|(_, hole)| hole)
+            .collect();
+
+        let mut points = outer.clone();
+        let mut hole_starts = Vec::new();
+        for hole in &holes {
+            hole_starts.push(points.len());
+            points.extend(hole.iter());
+        }
+        let flat: Vec<f32> = points.iter().flat_map(|p| [p.x, p.y]).collect();
+        let triangles = earcutr::earcut(&flat, &hole_starts, 2).map_err(|e| format!("{e:?}"))?;
+
+        for (z, normal_z) in [(front, 1.0), (back, -1.0)] {
+            let base = (mesh.positions.len() / 3) as u32;
+            for p in &points {
+                mesh.positions.extend([p.x, p.y, z]);
+                mesh.normals.extend([0.0, 0.0, normal_z]);
+            }
+            for triangle in triangles.chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                let ccw = (points[b] - points[a]).perp(&(points[c] - points[a])) > 0.0;
+                // Counter clockwise seen from the side the face is looking at
+                let [b, c] = match ccw == (normal_z > 0.0) {
+                    true => [b, c],
+                    false => [c, b],
+                };
+                mesh.indices
+                    .extend([a, b, c].map(|vertex| base + vertex as u32));
+            }
+        }
+    }
+
+    for contour in &contours {
+        for i in 0..contour.len() {
+            let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+            let Some(normal) = Vector2::new(b.y - a.y, a.x - b.x).try_normalize(1e-9) else {
+                continue;
+            };
+            let base = (mesh.positions.len() / 3) as u32;
+            for (p, z) in [(a, front), (a, back), (b, back), (b, front)] {
+                mesh.positions.extend([p.x, p.y, z]);
+                mesh.normals.extend([normal.x, normal.y, 0.0]);
+            }
+            mesh.indices
+                .extend([0, 1, 2, 0, 2, 3].map(|vertex| base + vertex));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use super::material_shader::MaterialShaderDesc;
+
+/// Registered first by [`super::renderer::EntitiesRenderer::new`], materials opt in with
+/// the `shading toon` mtl statement
+pub const TOON_SHADER_ID: u32 = 1;
+
+/// Matches `Params` in `toon.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ToonParams {
+    /// Levels of the diffuse light, per light
+    pub bands: f32,
+    pub rim_strength: f32,
+    /// Higher values give a thinner rim
+    pub rim_power: f32,
+}
+
+impl Default for ToonParams {
+    fn default() -> Self {
+        Self {
+            bands: 3.0,
+            rim_strength: 0.4,
+            rim_power: 2.0,
+        }
+    }
+}
+
+/// Quantized diffuse bands and a rim light, sharing the lights and the shadows of the built-in
+/// shading
+pub fn toon_shader_desc(params: &ToonParams) -> MaterialShaderDesc {
+    MaterialShaderDesc {
+        name: "Toon",
+        source: include_str!("toon.wgsl"),
+        params: bytemuck::bytes_of(params),
+    }
+}
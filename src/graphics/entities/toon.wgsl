@@ -0,0 +1,26 @@
+// Cel shading of the materials using `TOON_SHADER_ID`, appended to the entities shader
+
+struct Params {
+    // Levels of the diffuse light, per light
+    bands: f32,
+    rim_strength: f32,
+    // Higher values give a thinner rim
+    rim_power: f32,
+}
+
+fn camera_position() -> vec3f {
+    let rotation = mat3x3f(view[0].xyz, view[1].xyz, view[2].xyz);
+    return -(transpose(rotation) * view[3].xyz);
+}
+
+fn shade(surface: Surface, params: Params) -> vec4f {
+    let light = surface.ambient + direct_light(surface, max(params.bands, 1.0));
+
+    // Hard edged rim where the surface turns away from the camera
+    let to_camera = normalize(camera_position() - surface.world_position);
+    let facing = saturate(dot(normalize(surface.world_normal), to_camera));
+    let rim = smoothstep(0.45, 0.55, pow(1.0 - facing, params.rim_power)) * params.rim_strength;
+
+    let shaded = surface.albedo.rgb * (light + rim);
+    return vec4f(mix(shaded, surface.tint.rgb, surface.tint.a), surface.albedo.a);
+}
@@ -0,0 +1,51 @@
+use crate::graphics::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+    settings::WindSettings,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RawWind {
+    /// Normalized, on the xz plane
+    pub direction: [f32; 2],
+    pub strength: f32,
+    pub gust_frequency: f32,
+}
+
+crate::wgsl_layout!(RawWind as "Wind" {
+    direction,
+    strength,
+    gust_frequency,
+});
+
+/// Global wind read by the entities vertex shaders, see `wind_offset` in `shader.wgsl`
+///
+/// Materials sway by their `wind` weight, in the shadow maps too. Foliage, flags and particles
+/// drawn with custom material shaders get the same motion. The gusts are timed by `frame.time`,
+/// every pass sways in step
+pub struct Wind {
+    pub buffer: UniformBuffer<RawWind>,
+}
+
+impl Wind {
+    pub fn new(ctx: &GraphicsCtx, settings: &WindSettings) -> Self {
+        Self {
+            buffer: UniformBuffer::new("Wind", ctx, &raw_wind(settings)),
+        }
+    }
+
+    /// Uploads the settings, every frame
+    pub fn update(&self, ctx: &GraphicsCtx, settings: &WindSettings) {
+        self.buffer.write(ctx, &raw_wind(settings));
+    }
+}
+
+fn raw_wind(settings: &WindSettings) -> RawWind {
+    let angle = settings.direction_deg.to_radians();
+    RawWind {
+        direction: [angle.cos(), angle.sin()],
+        strength: settings.strength,
+        gust_frequency: settings.gust_frequency,
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+
+use nalgebra::Point3;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, StorageBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+};
+
+/// Zone ids are stored in `ModelInstance::zone_id`, 0 is reserved for instances outside any zone
+pub const MAX_ZONES: usize = 256;
+
+/// Room like volume, instances tagged with its id are hidden when it can't be reached
+pub struct Zone {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Zone {
+    fn contains(&self, point: &Point3<f32>) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+
+    fn volume(&self) -> f32 {
+        (self.max - self.min).product()
+    }
+}
+
+/// Opening between two zones, closing it stops visibility from flowing through
+pub struct Portal {
+    pub zones: [u32; 2],
+    pub open: bool,
+}
+
+/// Coarse visibility of whole zones, reached by walking the portals from the camera zone
+///
+/// The result is uploaded as one flag per zone, read by the entities vertex shader.
+/// When the camera is outside every zone all of them are visible
+pub struct ZoneCulling {
+    pub enabled: bool,
+    /// Portals crossed at most starting from the camera zone
+    pub max_depth: u32,
+    pub zones: Vec<Zone>,
+    pub portals: Vec<Portal>,
+
+    visible: Vec<u32>,
+    camera_zone: Option<u32>,
+    pub visibility: StorageBuffer<u32>,
+}
+
+impl ZoneCulling {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let visible = vec![1; MAX_ZONES];
+        let visibility = StorageBuffer::new_array("Zones visibility", ctx, &visible[..]);
+
+        Self {
+            enabled: true,
+            max_depth: 8,
+            zones: Vec::new(),
+            portals: Vec::new(),
+            visible,
+            camera_zone: None,
+            visibility,
+        }
+    }
+
+    /// Returns the id to store in `ModelInstance::zone_id`
+    pub fn add_zone(&mut self, zone: Zone) -> u32 {
+        assert!(self.zones.len() + 1 < MAX_ZONES, "Too many zones");
+        self.zones.push(zone);
+        self.zones.len() as u32
+    }
+
+    pub fn connect(&mut self, a: u32, b: u32) -> usize {
+        self.portals.push(Portal {
+            zones: [a, b],
+            open: true,
+        });
+        self.portals.len() - 1
+    }
+
+    /// Zone the camera is in, the smallest one when they overlap
+    pub fn camera_zone(&self) -> Option<u32> {
+        self.camera_zone
+    }
+
+    pub fn is_visible(&self, zone_id: u32) -> bool {
+        self.visible[zone_id as usize] != 0
+    }
+
+    pub fn update(&mut self, ctx: &GraphicsCtx, camera_position: Point3<f32>) {
+        self.camera_zone = self
+            .zones
+            .iter()
+            .enumerate()
+            .filter(|(_, zone)| zone.contains(&camera_position))
+            .min_by(|(_, a), (_, b)| a.volume().total_cmp(&b.volume()))
+            .map(|(i, _)| i as u32 + 1);
+
+        let mut visible = vec![0; MAX_ZONES];
+        visible[0] = 1;
+        match self.camera_zone.filter(|_| self.enabled) {
+            None => visible.fill(1),
+            Some(start) => {
+                let mut queue = VecDeque::from([(start, 0)]);
+                visible[start as usize] = 1;
+                while let Some((zone, depth)) = queue.pop_front() {
+                    if depth == self.max_depth {
+                        continue;
+                    }
+                    for portal in self.portals.iter().filter(|p| p.open) {
+                        let next = match portal.zones {
+                            [a, b] if a == zone => b,
+                            [a, b] if b == zone => a,
+                            _ => continue,
+                        };
+                        if visible[next as usize] == 0 {
+                            visible[next as usize] = 1;
+                            queue.push_back((next, depth + 1));
+                        }
+                    }
+                }
+            }
+        }
+
+        if visible != self.visible {
+            self.visibility.write_array(ctx, &visible);
+            self.visible = visible;
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::Instant;
+
+use nalgebra::Point3;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+    origin::WorldOrigin,
+    settings::RenderSettings,
+    sky::Sky,
+    weather::WeatherState,
+};
+
+/// Lattice cells of the cloud noise before it repeats, matches `CLOUD_PERIOD` in `clouds.wgsl`
+const CLOUD_NOISE_PERIOD: f64 = 256.0;
+
+/// Matches `FrameConstants` in the scene shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+pub struct RawFrameConstants {
+    /// Seconds since the renderer started
+    pub time: f32,
+    /// Seconds since the previous frame
+    pub delta_time: f32,
+    pub frame_index: u32,
+    /// Manual exposure multiplier, the auto exposure adapts on top of it in the tonemap pass
+    pub exposure: f32,
+    pub screen_size: [f32; 2],
+    pub inv_screen_size: [f32; 2],
+    /// Resolution the entities vertices snap to in the retro mode, the screen size or 0 without
+    /// snapping
+    pub snap_resolution: [f32; 2],
+    /// Non zero when the entities textures are mapped without perspective correction
+    pub affine_textures: u32,
+    /// Non zero when the entities are transformed relative to the eye of their view, see
+    /// [`super::camera::RawRelativeView`]
+    pub camera_relative: u32,
+    /// Camera position relative to the world origin
+    pub eye: [f32; 3],
+    /// From 0 for dry surfaces to 1 for soaked ones, darkens and glosses the upward surfaces
+    pub wetness: f32,
+    /// Light received from the sky, see [`super::sky::Sky::ambient`]
+    pub ambient: [f32; 3],
+    pub _padding2: u32,
+    /// Direction towards the sun
+    pub sun_direction: [f32; 3],
+    /// 0 without clouds, see `cloud_density` in `clouds.wgsl`
+    pub cloud_coverage: f32,
+    /// World units per second the clouds drift by
+    pub cloud_velocity: [f32; 2],
+    /// World origin wrapped to the period of the cloud noise, the clouds stay put when it moves
+    pub cloud_origin: [f32; 2],
+    /// World units covered by a cell of the cloud noise
+    pub cloud_size: f32,
+    pub cloud_altitude: f32,
+    pub cloud_softness: f32,
+    /// Share of the sun light blocked under a dense cloud
+    pub cloud_shadow: f32,
+}
+
+crate::wgsl_layout!(RawFrameConstants as "FrameConstants" {
+    time,
+    delta_time,
+    frame_index,
+    exposure,
+    screen_size,
+    inv_screen_size,
+    snap_resolution,
+    affine_textures,
+    camera_relative,
+    eye,
+    wetness,
+    ambient,
+    sun_direction,
+    cloud_coverage,
+    cloud_velocity,
+    cloud_origin,
+    cloud_size,
+    cloud_altitude,
+    cloud_softness,
+    cloud_shadow,
+});
+
+/// Per frame values bound with the camera to every scene shader, see
+/// [`super::camera::view_proj_bind_group_layout`]
+pub struct FrameConstants {
+    pub buffer: UniformBuffer<RawFrameConstants>,
+    frame_index: u32,
+    start: Instant,
+    last_frame: Instant,
+}
+
+impl FrameConstants {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let now = Instant::now();
+        Self {
+            buffer: UniformBuffer::new("Frame constants", ctx, &RawFrameConstants::default()),
+            frame_index: 0,
+            start: now,
+            last_frame: now,
+        }
+    }
+
+    /// Advances to the next frame, once per submit. `render_size` is the one of the scene targets
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        render_size: (u32, u32),
+        settings: &RenderSettings,
+        eye: Point3<f32>,
+        sky: &Sky,
+        origin: &WorldOrigin,
+        weather: &WeatherState,
+    ) {
+        let now = Instant::now();
+        let (width, height) = render_size;
+        let screen_size = [width.max(1) as f32, height.max(1) as f32];
+        self.frame_index = self.frame_index.wrapping_add(1);
+        let retro = &settings.post.retro;
+        let snap_resolution = if retro.enabled { screen_size } else { [0.0; 2] };
+        let constants = RawFrameConstants {
+            time: (now - self.start).as_secs_f32(),
+            delta_time: (now - self.last_frame).as_secs_f32(),
+            frame_index: self.frame_index,
+            exposure: settings.post.exposure,
+            screen_size,
+            inv_screen_size: screen_size.map(|size| 1.0 / size),
+            snap_resolution,
+            affine_textures: (retro.enabled && retro.affine_textures) as u32,
+            camera_relative: settings.origin.camera_relative as u32,
+            eye: eye.into(),
+            wetness: weather.wetness,
+            ambient: sky.ambient().into(),
+            _padding2: 0,
+            sun_direction: sky.sun().into(),
+            ..cloud_constants(settings, origin)
+        };
+        self.last_frame = now;
+        self.buffer.write(ctx, &constants);
+    }
+}
+
+/// Cloud fields of the constants, the others are left zeroed
+fn cloud_constants(settings: &RenderSettings, origin: &WorldOrigin) -> RawFrameConstants {
+    let sky = &settings.environment.sky;
+    let clouds = &sky.clouds;
+    if !sky.enabled || !clouds.enabled {
+        return RawFrameConstants::default();
+    }
+    let wind = settings.environment.wind.direction_deg.to_radians();
+    let size = clouds.size.max(1.0);
+    let period = CLOUD_NOISE_PERIOD * size as f64;
+    let offset = origin.offset();
+    RawFrameConstants {
+        cloud_coverage: clouds.coverage,
+        cloud_velocity: [wind.cos() * clouds.speed, wind.sin() * clouds.speed],
+        cloud_origin: [
+            offset.x.rem_euclid(period) as f32,
+            offset.z.rem_euclid(period) as f32,
+        ],
+        cloud_size: size,
+        cloud_altitude: clouds.altitude,
+        cloud_softness: clouds.softness.max(1e-3),
+        cloud_shadow: clouds.shadow,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,137 @@
+use wgpu::include_wgsl;
+
+use super::{ctx::GraphicsCtx, sampler::SamplerDesc, utils::TextureWrapper};
+
+/// Viewport sized texture the frames are recorded into when the surface is acquired by another
+/// thread, see [`super::render_thread::RenderThread`]
+///
+/// The recorded frame only references it, it is drawn over the surface by a [`FrameBlit`] once
+/// the surface texture is acquired
+pub struct FrameTarget {
+    pub texture: TextureWrapper,
+    blit: FrameBlit,
+}
+
+/// Draws the [`FrameTarget`] it was made for over the surface, cloned into every frame recorded
+/// into it
+#[derive(Clone)]
+pub struct FrameBlit {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl FrameTarget {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        // Same format as the surface, the passes are recorded as if drawing into it
+        let texture = TextureWrapper::new_render_target(
+            "Frame",
+            ctx,
+            ctx.viewport_size,
+            ctx.surface_format,
+            SamplerDesc::NEAREST,
+            wgpu::TextureUsages::COPY_SRC,
+        );
+        let layout = ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &TextureWrapper::layout_entries(
+                    0,
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::TextureViewDimension::D2,
+                ),
+                label: Some("Frame Blit Bind Group Layout"),
+            });
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &texture.bind_group_entries(0),
+            label: Some("Frame Blit Bind Group"),
+        });
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        // Samples the texture once per pixel, stretched when the viewport was resized since
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("post/upscale.wgsl"));
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Frame blit"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            texture,
+            blit: FrameBlit {
+                pipeline,
+                bind_group,
+            },
+        }
+    }
+
+    /// Whether it must be recreated for the current viewport
+    pub fn outdated(&self, ctx: &GraphicsCtx) -> bool {
+        let texture = &self.texture.texture;
+        (texture.width(), texture.height()) != ctx.viewport_size
+            || texture.format() != ctx.surface_format
+    }
+
+    pub fn blit(&self) -> FrameBlit {
+        self.blit.clone()
+    }
+}
+
+impl FrameBlit {
+    /// Draws the frame over the next surface texture and submits it, the surface texture is left
+    /// to present
+    pub fn submit(&self, ctx: &GraphicsCtx) -> Option<wgpu::SurfaceTexture> {
+        let mut frame = ctx.next_frame()?;
+        self.render(&mut frame.encoder, &frame.view);
+        frame.submit(ctx, std::iter::empty())
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, surface: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Frame blit"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
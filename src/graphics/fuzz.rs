@@ -0,0 +1,216 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::random::Rng;
+
+use super::{
+    color::Color3,
+    ctx::GraphicsCtx,
+    entities::model::{ModelInstance, ModelInstanceId, ModelVertex, ModelsBuffer, VertexPrecision},
+    light::{Light, LightsUniform, RawLight},
+    settings::{LightOverflow, LightSettings},
+};
+
+/// Mesh columns of the fuzzed instance buffer, per model
+const MESHES_PER_MODEL: [u16; 3] = [1, 3, 2];
+
+#[derive(Debug, Clone, Copy)]
+pub struct FuzzOptions {
+    /// Runs with the same seed apply the same operations
+    pub seed: u32,
+    pub steps: u32,
+    /// Operations queued between two applies of the changes, each apply is followed by the checks
+    pub batch: u32,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            steps: 5000,
+            batch: 16,
+        }
+    }
+}
+
+/// Adds, moves and removes instances at random, in batches applied like the editor does between
+/// frames. After each batch the buffer invariants are checked and every mesh column read back is
+/// compared to a cpu side copy of the instances
+///
+/// The error names the seed and the step, the run is reproduced with the same options
+pub fn fuzz_instances(ctx: &GraphicsCtx, options: FuzzOptions) -> Result<(), String> {
+    let mut rng = Rng::new(options.seed);
+    let mut models = fuzz_models(ctx);
+    let mut placed: Vec<(ModelInstanceId, ModelInstance)> = Vec::new();
+    // Tags each instance by its x translation, the columns are compared as sets
+    let mut next_tag = 0.0;
+    let mut new_instance = |rng: &mut Rng| {
+        next_tag += 1.0;
+        let height = rng.below(100) as f32;
+        ModelInstance::new(
+            Matrix4::new_translation(&Vector3::new(next_tag, height, 0.0)),
+            0,
+        )
+    };
+
+    for step in 0..options.steps {
+        let context = |e: String| format!("Seed {}, step {step}: {e}", options.seed);
+        match rng.below(10) {
+            // Removals are a bit rarer, the columns keep growing over the run
+            0..=4 => {
+                let model_id = rng.below(MESHES_PER_MODEL.len()) as u16;
+                let mesh_id = rng.below(MESHES_PER_MODEL[model_id as usize] as usize) as u16;
+                let instance = new_instance(&mut rng);
+                placed.push((models.add_instance(model_id, mesh_id, instance), instance));
+            }
+            5..=8 if !placed.is_empty() => {
+                let (id, _) = placed.swap_remove(rng.below(placed.len()));
+                models.remove_instance(id);
+            }
+            _ if !placed.is_empty() => {
+                let index = rng.below(placed.len());
+                let mut instance = new_instance(&mut rng);
+                // Keeps the tag of the instance it replaces
+                instance.translation[0] = placed[index].1.translation[0];
+                models.set_instance(&placed[index].0, instance);
+                placed[index].1 = instance;
+            }
+            _ => {}
+        }
+
+        if (step + 1) % options.batch.max(1) != 0 && step + 1 != options.steps {
+            continue;
+        }
+        models.apply_changes(ctx);
+        models.check(ctx).map_err(context)?;
+        for (model_id, mesh_count) in MESHES_PER_MODEL.iter().enumerate() {
+            for mesh_id in 0..*mesh_count {
+                let model_id = model_id as u16;
+                let expected = placed
+                    .iter()
+                    .filter(|(id, _)| id.model_id == model_id && id.mesh_id == mesh_id)
+                    .map(|(_, instance)| *instance)
+                    .collect::<Vec<_>>();
+                let read = models.read_instances(ctx, model_id, mesh_id);
+                if !same_instances(read, expected) {
+                    return Err(context(format!(
+                        "The instances of mesh {mesh_id} of model {model_id} differ from the cpu"
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pushes, edits and toggles lights at random, past the cap of the slots with every overflow
+/// policy, checking the uploaded slots against the cpu side lights after each batch
+pub fn fuzz_lights(ctx: &GraphicsCtx, options: FuzzOptions) -> Result<(), String> {
+    let mut rng = Rng::new(options.seed);
+    let mut settings = LightSettings {
+        max_lights: 32,
+        ..Default::default()
+    };
+    let mut lights = LightsUniform::new(ctx, &[], &settings);
+    let random_light = |rng: &mut Rng| {
+        let intensity = rng.below(1000) as f32 / 100.0;
+        let position = Point3::new(rng.below(200) as f32 - 100.0, 2.0, 0.0);
+        RawLight::from(Light::Point {
+            color: Color3::WHITE,
+            intensity,
+            position,
+        })
+    };
+    let overflows = [
+        LightOverflow::Reject,
+        LightOverflow::ReplaceDimmest,
+        LightOverflow::NearestToCamera,
+    ];
+
+    for step in 0..options.steps {
+        let context = |e: String| format!("Seed {}, step {step}: {e}", options.seed);
+        match rng.below(20) {
+            0..=7 => {
+                lights.push(random_light(&mut rng));
+            }
+            8..=12 if !lights.is_empty() => {
+                let index = rng.below(lights.len() as usize) as u32;
+                lights.set(index, random_light(&mut rng));
+            }
+            13..=18 if !lights.is_empty() => {
+                let index = rng.below(lights.len() as usize) as u32;
+                lights.set_enabled(index, !lights.is_enabled(index));
+            }
+            19 => {
+                settings.overflow = overflows[rng.below(overflows.len())];
+                settings.max_lights = 8 + rng.below(64) as u32;
+                lights.apply_settings(ctx, &settings);
+            }
+            _ => {}
+        }
+
+        if (step + 1) % options.batch.max(1) != 0 && step + 1 != options.steps {
+            continue;
+        }
+        let camera = Point3::new(rng.below(200) as f32 - 100.0, 0.0, 0.0);
+        lights.apply_changes(ctx, camera);
+        lights.check(ctx).map_err(context)?;
+    }
+    Ok(())
+}
+
+/// A triangle per mesh, one instance each to start with
+fn fuzz_models(ctx: &GraphicsCtx) -> ModelsBuffer {
+    let mesh_count = MESHES_PER_MODEL.iter().sum::<u16>() as u32;
+    let vertices = (0..mesh_count)
+        .flat_map(|_| {
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|position| ModelVertex {
+                position,
+                ao: 1.0,
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = (0..mesh_count)
+        .flat_map(|_| [0, 1, 2])
+        .collect::<Vec<u16>>();
+    let indirects = (0..mesh_count)
+        .map(|mesh| wgpu::util::DrawIndexedIndirectArgs {
+            index_count: 3,
+            instance_count: 1,
+            first_index: mesh * 3,
+            base_vertex: mesh as i32 * 3,
+            first_instance: mesh,
+        })
+        .collect::<Vec<_>>();
+    let instances = (0..mesh_count)
+        .map(|_| ModelInstance::new(Matrix4::new_translation(&Vector3::new(-1.0, 0.0, 0.0)), 0))
+        .collect::<Vec<_>>();
+    let instances_count = MESHES_PER_MODEL
+        .iter()
+        .map(|meshes| vec![1; *meshes as usize])
+        .collect();
+
+    ModelsBuffer::from_raw(
+        ctx,
+        &vertices,
+        &indices,
+        &instances,
+        &indirects,
+        &vec![VertexPrecision::Full; mesh_count as usize],
+        instances_count,
+    )
+}
+
+/// The starting instances are tagged -1 and never removed, they are left out of the comparison
+fn same_instances(read: Vec<ModelInstance>, expected: Vec<ModelInstance>) -> bool {
+    let sorted = |instances: Vec<ModelInstance>| {
+        let mut instances = instances
+            .into_iter()
+            .filter(|instance| instance.translation[0] >= 0.0)
+            .collect::<Vec<_>>();
+        instances.sort_by(|a, b| a.translation[0].total_cmp(&b.translation[0]));
+        instances
+    };
+    let (read, expected) = (sorted(read), sorted(expected));
+    bytemuck::cast_slice::<_, u8>(&read) == bytemuck::cast_slice::<_, u8>(&expected)
+}
@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+
+use image::RgbaImage;
+
+use super::{
+    camera::{Camera, Projection},
+    ctx::GraphicsCtx,
+    light::RawLight,
+    stats::RenderStats,
+    GlobalRenderer, RenderData,
+};
+use crate::scene::Scene;
+
+/// Recorded images and stats, `<name>.png` and `<name>.stats`
+pub const GOLDEN_PATH: &str = "tests/golden";
+/// Set to record the golden data again instead of comparing against it
+pub const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+/// How far a frame may drift from its golden image, the drivers do not rasterize identically
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageTolerance {
+    /// Largest difference of a channel, out of 255, before a pixel counts as different
+    pub channel: u8,
+    /// Share of the pixels allowed to differ
+    pub pixels: f32,
+}
+
+impl Default for ImageTolerance {
+    fn default() -> Self {
+        Self {
+            channel: 8,
+            pixels: 0.005,
+        }
+    }
+}
+
+pub struct GoldenFrame {
+    pub image: RgbaImage,
+    pub stats: RenderStats,
+}
+
+/// Renders one frame of `scene` into the headless target of `ctx`
+///
+/// The builtin entities are drawn along with the scene, its lights replace the default rig
+pub fn render_scene(
+    ctx: &GraphicsCtx,
+    scene: &Scene,
+    camera: &Camera,
+) -> Result<GoldenFrame, String> {
+    let mut renderer = GlobalRenderer::new(ctx);
+    let models = &mut renderer.entities.models;
+    for placed in &scene.instances {
+        if placed.model_id as u32 >= models.model_count()
+            || placed.mesh_id as u32 >= models.mesh_count_of(placed.model_id)
+        {
+            return Err(format!("Scene instance of unknown mesh {placed:?}"));
+        }
+        models.add_instance(placed.model_id, placed.mesh_id, placed.instance);
+    }
+    let lights = &mut renderer.lights;
+    let rig = lights.len();
+    for (index, light) in scene.lights.iter().enumerate() {
+        let light = RawLight::from(*light);
+        match (index as u32) < rig {
+            true => lights.set(index as u32, light),
+            false => {
+                lights
+                    .push(light)
+                    .ok_or("The scene has more lights than slots")?;
+            }
+        }
+    }
+    for index in scene.lights.len() as u32..rig {
+        lights.set_enabled(index, false);
+    }
+
+    let (width, height) = ctx.viewport_size;
+    renderer.update_proj(ctx, &Projection::new([width, height].into(), 90.0));
+    renderer.camera.update_view(ctx, camera);
+    renderer.submit(
+        ctx,
+        RenderData {
+            window_size: ctx.viewport_size,
+            aspect_ratio: 1.0,
+            camera_position: camera.eye,
+            #[cfg(feature = "editor")]
+            egui_ctx: egui::Context::default(),
+            #[cfg(feature = "editor")]
+            egui_output: Default::default(),
+        },
+    );
+
+    Ok(GoldenFrame {
+        image: ctx.read_frame().ok_or("The context is not headless")?,
+        stats: RenderStats::of(&renderer),
+    })
+}
+
+/// Every pixel past `tolerance.channel` counts, the frame matches while they are few enough
+pub fn compare_images(
+    image: &RgbaImage,
+    golden: &RgbaImage,
+    tolerance: ImageTolerance,
+) -> Result<(), String> {
+    if image.dimensions() != golden.dimensions() {
+        return Err(format!(
+            "Frame is {:?}, golden image is {:?}",
+            image.dimensions(),
+            golden.dimensions()
+        ));
+    }
+    let different = image
+        .pixels()
+        .zip(golden.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0)
+                .any(|(a, b)| a.abs_diff(b) > tolerance.channel)
+        })
+        .count();
+    let share = different as f32 / (image.width() * image.height()).max(1) as f32;
+    match share <= tolerance.pixels {
+        true => Ok(()),
+        false => Err(format!(
+            "{:.2}% of the pixels differ, {:.2}% allowed",
+            share * 100.0,
+            tolerance.pixels * 100.0
+        )),
+    }
+}
+
+/// Compares `frame` to the golden data of `name`, the stats must match exactly
+///
+/// With [`UPDATE_ENV`] set the golden data is written instead. A mismatching frame is saved as
+/// `<name>.actual.png` next to the golden image
+pub fn check(name: &str, frame: &GoldenFrame, tolerance: ImageTolerance) -> Result<(), String> {
+    let image_path = golden_path(name, "png");
+    let stats_path = golden_path(name, "stats");
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        std::fs::create_dir_all(GOLDEN_PATH).map_err(|e| e.to_string())?;
+        frame.image.save(&image_path).map_err(|e| e.to_string())?;
+        return std::fs::write(&stats_path, frame.stats.to_string()).map_err(|e| e.to_string());
+    }
+
+    let missing = |e| format!("No golden data for {name}, record it with {UPDATE_ENV}=1: {e}");
+    let golden_stats = std::fs::read_to_string(&stats_path).map_err(missing)?;
+    let golden_image = image::open(&image_path)
+        .map_err(|e| missing(std::io::Error::other(e)))?
+        .into_rgba8();
+
+    let mut errors = frame.stats.diff(&RenderStats::parse(&golden_stats)?, 0.0);
+    if let Err(e) = compare_images(&frame.image, &golden_image, tolerance) {
+        let actual = golden_path(&format!("{name}.actual"), "png");
+        if let Err(e) = frame.image.save(&actual) {
+            eprintln!("Failed to save the frame of {name}: {e}");
+        }
+        errors.push(e);
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(format!("{name}: {}", errors.join(", "))),
+    }
+}
+
+fn golden_path(name: &str, extension: &str) -> PathBuf {
+    PathBuf::from(GOLDEN_PATH)
+        .join(name)
+        .with_extension(extension)
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashSet, thread::ScopedJoinHandle};
+
+use crate::profile_scope;
+
+use super::ctx::GraphicsCtx;
+
+/// How a pass uses a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Sampled, bound read only or copied from
+    Read,
+    /// Written by a shader or copied into
+    Write,
+    /// Bound as a color or depth attachment
+    Attachment,
+}
+
+/// What a pass records with, handed to it by [`FrameGraph::record`]
+pub struct PassContext<'f> {
+    pub ctx: &'f GraphicsCtx,
+    pub encoder: &'f mut wgpu::CommandEncoder,
+    /// Surface view of the frame
+    pub surface: &'f wgpu::TextureView,
+    /// None when there is nothing to copy the frame from
+    pub surface_texture: Option<&'f wgpu::Texture>,
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&mut PassContext) + 'a>;
+type ParallelRecordFn<'a> = Box<dyn FnOnce(&GraphicsCtx, &mut wgpu::CommandEncoder) + Send + 'a>;
+
+enum Recorder<'a> {
+    /// Into the encoder of the passes around it
+    Serial(RecordFn<'a>),
+    /// Into an encoder of its own, on a thread of its own
+    Parallel(ParallelRecordFn<'a>),
+}
+
+#[derive(Clone, Copy)]
+enum EncoderTarget {
+    Own(&'static str),
+    Frame,
+}
+
+pub struct GraphPass<'a> {
+    pub name: &'static str,
+    pub uses: Vec<(&'static str, Access)>,
+    recorder: Recorder<'a>,
+    /// Encoder opened before the pass is recorded
+    opens: Option<EncoderTarget>,
+}
+
+/// Passes of a frame with the resources they use, declared in recording order and checked before
+/// anything is recorded
+///
+/// Mistakes wgpu would only report mid-frame, like a texture sampled by the pass it is bound as
+/// an attachment of, are caught when the graph is built. The passes are recorded by
+/// [`Self::record`], the commands are submitted in declaration order
+#[derive(Default)]
+pub struct FrameGraph<'a> {
+    pub passes: Vec<GraphPass<'a>>,
+    /// Written outside the graph, by uploads or by the previous frames
+    imported: HashSet<&'static str>,
+    /// Opened by the next pass
+    next_encoder: Option<EncoderTarget>,
+    on_frame_encoder: bool,
+}
+
+impl<'a> FrameGraph<'a> {
+    pub fn import(&mut self, resource: &'static str) -> &mut Self {
+        self.imported.insert(resource);
+        self
+    }
+
+    /// Starts a command buffer, the next passes are recorded into it. A pass recorded outside of
+    /// any opens one named after itself
+    pub fn encoder(&mut self, label: &'static str) -> &mut Self {
+        assert!(
+            !self.on_frame_encoder,
+            "{label} would be submitted before the passes on the frame encoder"
+        );
+        self.next_encoder = Some(EncoderTarget::Own(label));
+        self
+    }
+
+    /// The next passes are recorded into the encoder of the frame, submitted last
+    pub fn frame_encoder(&mut self) -> &mut Self {
+        self.next_encoder = Some(EncoderTarget::Frame);
+        self.on_frame_encoder = true;
+        self
+    }
+
+    pub fn pass(
+        &mut self,
+        name: &'static str,
+        uses: &[(&'static str, Access)],
+        record: impl FnOnce(&mut PassContext) + 'a,
+    ) -> &mut Self {
+        self.push(name, uses, Recorder::Serial(Box::new(record)))
+    }
+
+    /// Recorded on a thread of its own while the passes after it are recorded, into a command
+    /// buffer of its own
+    pub fn parallel_pass(
+        &mut self,
+        name: &'static str,
+        uses: &[(&'static str, Access)],
+        record: impl FnOnce(&GraphicsCtx, &mut wgpu::CommandEncoder) + Send + 'a,
+    ) -> &mut Self {
+        assert!(
+            !self.on_frame_encoder,
+            "{name} would be submitted before the passes on the frame encoder"
+        );
+        self.push(name, uses, Recorder::Parallel(Box::new(record)))
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        uses: &[(&'static str, Access)],
+        recorder: Recorder<'a>,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            name,
+            uses: uses.to_vec(),
+            recorder,
+            opens: self.next_encoder.take(),
+        });
+        self
+    }
+
+    /// Records every pass in order, returns the command buffers to submit before
+    /// `frame_encoder`. The passes draw into `surface`, the surface of the frame or a texture
+    /// copied onto it later
+    pub fn record(
+        self,
+        ctx: &GraphicsCtx,
+        surface: &wgpu::TextureView,
+        surface_texture: Option<&wgpu::Texture>,
+        frame_encoder: &mut wgpu::CommandEncoder,
+    ) -> Vec<wgpu::CommandBuffer> {
+        enum Current {
+            None,
+            Own(wgpu::CommandEncoder),
+            Frame,
+        }
+        enum Recorded<'s> {
+            Thread(ScopedJoinHandle<'s, wgpu::CommandBuffer>),
+            Finished(wgpu::CommandBuffer),
+        }
+        fn finish(current: &mut Current, recorded: &mut Vec<Recorded<'_>>) {
+            if let Current::Own(encoder) = std::mem::replace(current, Current::None) {
+                recorded.push(Recorded::Finished(encoder.finish()));
+            }
+        }
+
+        std::thread::scope(|scope| {
+            let mut recorded = Vec::new();
+            let mut current = Current::None;
+            for pass in self.passes {
+                if let Some(target) = pass.opens {
+                    finish(&mut current, &mut recorded);
+                    current = match target {
+                        EncoderTarget::Own(label) => Current::Own(new_encoder(ctx, label)),
+                        EncoderTarget::Frame => Current::Frame,
+                    };
+                }
+                match pass.recorder {
+                    Recorder::Parallel(record) => {
+                        // Its commands go between the passes before and after it
+                        finish(&mut current, &mut recorded);
+                        let name = pass.name;
+                        recorded.push(Recorded::Thread(scope.spawn(move || {
+                            profile_scope!(name);
+                            let mut encoder = new_encoder(ctx, name);
+                            record(ctx, &mut encoder);
+                            encoder.finish()
+                        })));
+                    }
+                    Recorder::Serial(record) => {
+                        profile_scope!(pass.name);
+                        if let Current::None = current {
+                            current = Current::Own(new_encoder(ctx, pass.name));
+                        }
+                        let encoder = match &mut current {
+                            Current::Own(encoder) => encoder,
+                            Current::Frame => &mut *frame_encoder,
+                            Current::None => unreachable!(),
+                        };
+                        record(&mut PassContext {
+                            ctx,
+                            encoder,
+                            surface,
+                            surface_texture,
+                        });
+                    }
+                }
+            }
+            finish(&mut current, &mut recorded);
+
+            recorded
+                .into_iter()
+                .map(|recorded| match recorded {
+                    Recorded::Thread(thread) => {
+                        thread.join().expect("Pass recording thread panicked")
+                    }
+                    Recorded::Finished(commands) => commands,
+                })
+                .collect()
+        })
+    }
+
+    /// Every problem found, the passes are valid in their order when empty
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut written: HashSet<&str> = self.imported.clone();
+        for pass in &self.passes {
+            let has = |resource: &str, access: Access| pass.uses.contains(&(resource, access));
+            for (resource, access) in &pass.uses {
+                match access {
+                    Access::Read if has(resource, Access::Attachment) => errors.push(format!(
+                        "{} samples {resource} while it is bound as an attachment",
+                        pass.name
+                    )),
+                    Access::Read if has(resource, Access::Write) => errors.push(format!(
+                        "{} reads and writes {resource}, the reads race with the writes",
+                        pass.name
+                    )),
+                    Access::Read if !written.contains(resource) => {
+                        match self.first_writer(resource) {
+                            Some(writer) => errors.push(format!(
+                                "{} reads {resource} before {writer} writes it",
+                                pass.name
+                            )),
+                            None => errors.push(format!(
+                                "{} reads {resource} which nothing writes",
+                                pass.name
+                            )),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            written.extend(
+                pass.uses
+                    .iter()
+                    .filter(|(_, access)| *access != Access::Read)
+                    .map(|(resource, _)| *resource),
+            );
+        }
+        errors
+    }
+
+    /// Passes writing what `pass` reads, the ones it must be recorded after
+    pub fn dependencies(&self, pass: usize) -> Vec<&'static str> {
+        let reads: Vec<_> = self.passes[pass]
+            .uses
+            .iter()
+            .filter(|(_, access)| *access == Access::Read)
+            .map(|(resource, _)| *resource)
+            .collect();
+        self.passes[..pass]
+            .iter()
+            .filter(|earlier| {
+                earlier
+                    .uses
+                    .iter()
+                    .any(|(resource, access)| *access != Access::Read && reads.contains(resource))
+            })
+            .map(|earlier| earlier.name)
+            .collect()
+    }
+
+    fn first_writer(&self, resource: &str) -> Option<&'static str> {
+        self.passes
+            .iter()
+            .find(|pass| {
+                pass.uses
+                    .iter()
+                    .any(|(used, access)| *used == resource && *access != Access::Read)
+            })
+            .map(|pass| pass.name)
+    }
+}
+
+fn new_encoder(ctx: &GraphicsCtx, label: &'static str) -> wgpu::CommandEncoder {
+    ctx.device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) })
+}
@@ -1,20 +1,46 @@
 use nalgebra::{Point3, Vector3};
 
 use super::{
-    buffer::{CommonBuffer, MappedSparse, StorageBuffer, WriteBuffer},
+    buffer::{read_buffer, CommonBuffer, MappedSparse, StorageBuffer, WriteBuffer},
     color::Color3,
+    settings::{LightOverflow, LightSettings},
 };
 
+/// Lights bound to the scene shaders, up to [`LightSettings::max_lights`] so their loops stay
+/// bounded, lights pushed past it are handled by the [`LightOverflow`] policy
+///
+/// Only the slots whose light or enabled flag actually changed are uploaded
 pub struct LightsUniform {
-    pub storage_buffer: MappedSparse<StorageBuffer<RawLight>>,
+    storage_buffer: MappedSparse<StorageBuffer<RawLight>>,
     count_uniform: super::UniformBuffer<u32>,
     pub bind_group: wgpu::BindGroup,
+    /// Cpu side lights, disabled ones included
+    lights: Vec<RawLight>,
+    /// Cpu side switch of each slot, disabled lights are uploaded as empty slots
+    enabled: Vec<bool>,
+    /// Lights waiting for a slot, see [`LightOverflow::NearestToCamera`]
+    overflow: Vec<RawLight>,
+    settings: LightSettings,
+    /// Lights fitting in the largest storage buffer of the device
+    device_max: u32,
+    /// Value of `count_uniform`
+    count: u32,
 }
 
 impl LightsUniform {
-    pub fn new(ctx: &super::GraphicsCtx, lights: &[RawLight]) -> Self {
-        let storage_buffer = MappedSparse::<StorageBuffer<_>>::new("Lights", ctx, lights);
-        let count_uniform = super::UniformBuffer::new("lights_count", ctx, &(lights.len() as u32));
+    pub fn new(ctx: &super::GraphicsCtx, lights: &[RawLight], settings: &LightSettings) -> Self {
+        let device_max =
+            (ctx.max_buffer_bytes() / size_of::<RawLight>() as u64).min(u32::MAX as u64) as u32;
+        let max = settings.max_lights.min(device_max).max(1) as usize;
+        let (lights, overflow) = fit(lights, max, settings.overflow);
+
+        let storage_buffer = MappedSparse::<StorageBuffer<_>>::new("Lights", ctx, &lights[..]);
+        let count = if settings.enabled {
+            lights.len() as u32
+        } else {
+            0
+        };
+        let count_uniform = super::UniformBuffer::new("lights_count", ctx, &count);
 
         let bind_group = lights_buffer_bindgroup(ctx, &(**storage_buffer), &count_uniform);
 
@@ -22,17 +48,304 @@ impl LightsUniform {
             storage_buffer,
             count_uniform,
             bind_group,
+            enabled: vec![true; lights.len()],
+            lights,
+            overflow,
+            settings: *settings,
+            device_max,
+            count,
+        }
+    }
+
+    /// Lowering the cap under the current count rebuilds the buffer, the lights may be reordered
+    pub fn apply_settings(&mut self, ctx: &super::GraphicsCtx, settings: &LightSettings) {
+        if settings.overflow != LightOverflow::NearestToCamera && !self.overflow.is_empty() {
+            eprintln!("Dropping {} lights waiting for a slot", self.overflow.len());
+            self.overflow.clear();
+        }
+        self.settings = *settings;
+        if self.len() > self.max_lights() {
+            let mut lights: Vec<_> = self
+                .lights
+                .iter()
+                .zip(&self.enabled)
+                .filter(|(light, enabled)| **enabled && light.light_type != 0)
+                .map(|(light, _)| *light)
+                .collect();
+            lights.append(&mut self.overflow);
+            *self = Self::new(ctx, &lights, settings);
+        }
+    }
+
+    /// Slots the shaders loop over at most, the setting clamped to the device
+    pub fn max_lights(&self) -> u32 {
+        self.settings.max_lights.min(self.device_max).max(1)
+    }
+
+    /// Slots in use, empty and disabled ones included
+    pub fn len(&self) -> u32 {
+        self.storage_buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Slots the shaders loop over this frame, 0 with the lighting turned off
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Lights not uploaded, waiting for a slot
+    pub fn overflow_len(&self) -> usize {
+        self.overflow.len()
+    }
+
+    pub fn get(&self, index: u32) -> Option<&RawLight> {
+        self.lights.get(index as usize)
+    }
+
+    /// Queues the slot for upload, unless the light did not change
+    pub fn set(&mut self, index: u32, light: RawLight) {
+        let Some(current) = self.lights.get_mut(index as usize) else {
+            panic!("Light index out of bounds");
+        };
+        if *current == light {
+            return;
         }
+        *current = light;
+        self.upload(index);
     }
 
-    /// Returns true if the bindgroup was recreated
-    pub fn apply_changes(&mut self, ctx: &super::GraphicsCtx) {
+    pub fn is_enabled(&self, index: u32) -> bool {
+        self.enabled.get(index as usize).copied().unwrap_or(false)
+    }
+
+    /// Disabled lights keep their data on the cpu and are uploaded as empty slots
+    pub fn set_enabled(&mut self, index: u32, enabled: bool) {
+        let Some(current) = self.enabled.get_mut(index as usize) else {
+            panic!("Light index out of bounds");
+        };
+        if *current == enabled {
+            return;
+        }
+        *current = enabled;
+        self.upload(index);
+    }
+
+    fn upload(&mut self, index: u32) {
+        let light = match self.enabled[index as usize] {
+            true => self.lights[index as usize],
+            false => RawLight::default(),
+        };
+        self.storage_buffer.set(index, light);
+    }
+
+    /// Takes a free slot, enabled
+    fn insert(&mut self, light: RawLight) -> u32 {
+        let index = self.storage_buffer.push(light);
+        let len = self.lights.len().max(index as usize + 1);
+        self.lights.resize(len, RawLight::default());
+        self.enabled.resize(len, true);
+        self.lights[index as usize] = light;
+        self.enabled[index as usize] = true;
+        index
+    }
+
+    /// Index of the light, `None` when it did not fit. A light replacing another one takes its
+    /// index, the other one is dropped or waits for a slot depending on the policy
+    pub fn push(&mut self, light: RawLight) -> Option<u32> {
+        if self.storage_buffer.has_free_slot() || self.len() < self.max_lights() {
+            return Some(self.insert(light));
+        }
+        match self.settings.overflow {
+            LightOverflow::Reject => {
+                eprintln!(
+                    "Light rejected, the cap of {} lights is reached",
+                    self.max_lights()
+                );
+                None
+            }
+            LightOverflow::ReplaceDimmest => {
+                // Directional lights light the whole scene, they are kept
+                let dimmest = self
+                    .lights
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| other.light_type != 2)
+                    .min_by(|(_, a), (_, b)| a.intensity.total_cmp(&b.intensity));
+                match dimmest {
+                    Some((i, dimmest))
+                        if light.light_type == 2 || dimmest.intensity < light.intensity =>
+                    {
+                        self.set(i as u32, light);
+                        self.set_enabled(i as u32, true);
+                        Some(i as u32)
+                    }
+                    _ => None,
+                }
+            }
+            LightOverflow::NearestToCamera => {
+                self.overflow.push(light);
+                None
+            }
+        }
+    }
+
+    /// Every slot on the gpu holds its cpu side light, empty when disabled, once the changes are
+    /// applied
+    pub fn check(&self, ctx: &super::GraphicsCtx) -> Result<(), String> {
+        let len = self.len() as usize;
+        if self.lights.len() != len || self.enabled.len() != len {
+            return Err(format!(
+                "{len} slots for {} lights and {} switches",
+                self.lights.len(),
+                self.enabled.len()
+            ));
+        }
+        let bytes = read_buffer(
+            ctx,
+            self.storage_buffer.inner(),
+            0..len as u64 * StorageBuffer::<RawLight>::ITEM_BYTE_SIZE,
+        );
+        let uploaded: Vec<RawLight> = bytemuck::pod_collect_to_vec(&bytes);
+        for (index, uploaded) in uploaded.iter().enumerate() {
+            let expected = match self.enabled[index] {
+                true => self.lights[index],
+                false => RawLight::default(),
+            };
+            if *uploaded != expected {
+                return Err(format!(
+                    "Light {index} is {uploaded:?} on the gpu, expected {expected:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Uploads the changed lights, swapping the waiting ones in when they are nearer to `camera`
+    pub fn apply_changes(&mut self, ctx: &super::GraphicsCtx, camera: Point3<f32>) {
+        if !self.overflow.is_empty() {
+            self.prioritize(camera);
+        }
         if self.storage_buffer.apply_changes(ctx) {
-            self.bind_group =
-                lights_buffer_bindgroup(ctx, &(**self.storage_buffer), &self.count_uniform)
+            let bind_group =
+                lights_buffer_bindgroup(ctx, &(**self.storage_buffer), &self.count_uniform);
+            ctx.retire(std::mem::replace(&mut self.bind_group, bind_group));
+        }
+        let count = if self.settings.enabled { self.len() } else { 0 };
+        if count != self.count {
+            self.count = count;
+            self.count_uniform.write(ctx, &count);
+        }
+    }
+
+    /// Room for `count` lights up to the cap, so pushing them later does not grow the buffer
+    pub fn reserve(&mut self, ctx: &super::GraphicsCtx, count: u32) {
+        let count = count.min(self.max_lights()) as usize;
+        if self.storage_buffer.reserve(ctx, count) {
+            let bind_group =
+                lights_buffer_bindgroup(ctx, &(**self.storage_buffer), &self.count_uniform);
+            ctx.retire(std::mem::replace(&mut self.bind_group, bind_group));
+        }
+    }
+
+    /// Nearest waiting lights first, each one takes a free slot or the slot of the farthest
+    /// uploaded light farther than itself, which starts waiting instead
+    fn prioritize(&mut self, camera: Point3<f32>) {
+        // Directional lights have no position, they always win a slot
+        let distance = |light: &RawLight| match light.light_type {
+            2 => 0.0,
+            _ => (Point3::from(light.position) - camera).norm_squared(),
+        };
+        let mut waiting = std::mem::take(&mut self.overflow);
+        waiting.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        for light in waiting {
+            if self.storage_buffer.has_free_slot() || self.len() < self.max_lights() {
+                self.insert(light);
+                continue;
+            }
+            let farthest = self
+                .lights
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| other.light_type == 1 || other.light_type == 3)
+                .max_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)));
+            match farthest {
+                Some((i, farthest)) if distance(farthest) > distance(&light) => {
+                    self.overflow.push(*farthest);
+                    self.set(i as u32, light);
+                }
+                _ => self.overflow.push(light),
+            }
+        }
+    }
+
+    /// Moves the point lights and spotlights back by `shift`, see
+    /// [`super::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for i in 0..self.lights.len() {
+            let mut light = self.lights[i];
+            if light.light_type == 1 || light.light_type == 3 {
+                light.position = (Vector3::from(light.position) - shift).into();
+                self.set(i as u32, light);
+            }
+        }
+        for light in &mut self.overflow {
+            light.position = (Vector3::from(light.position) - shift).into();
+        }
+    }
+
+    /// Cpu side lights, disabled ones included, see [`Self::is_enabled`]
+    pub fn lights(&self) -> &[RawLight] {
+        &self.lights
+    }
+
+    /// Lights the shaders see, with their index
+    pub fn enabled_lights(&self) -> impl Iterator<Item = (u32, &RawLight)> {
+        self.lights
+            .iter()
+            .zip(&self.enabled)
+            .enumerate()
+            .filter(|(_, (_, enabled))| **enabled)
+            .map(|(i, (light, _))| (i as u32, light))
+    }
+
+    /// Index and direction of the first enabled directional light, the one casting shadows
+    pub fn shadow_caster(&self) -> Option<(u32, Vector3<f32>)> {
+        self.enabled_lights()
+            .find(|(_, light)| light.light_type == 2)
+            .map(|(i, light)| (i, Vector3::from(light.direction)))
+    }
+}
+
+/// Splits the lights into the uploaded ones and the waiting ones
+fn fit(lights: &[RawLight], max: usize, overflow: LightOverflow) -> (Vec<RawLight>, Vec<RawLight>) {
+    if lights.len() <= max {
+        return (lights.to_vec(), Vec::new());
+    }
+    let mut lights: Vec<_> = lights
+        .iter()
+        .copied()
+        .filter(|light| light.light_type != 0)
+        .collect();
+    if overflow == LightOverflow::ReplaceDimmest {
+        // Stable, the directional lights first then the brightest
+        lights.sort_by(|a, b| {
+            (b.light_type == 2)
+                .cmp(&(a.light_type == 2))
+                .then(b.intensity.total_cmp(&a.intensity))
+        });
+    }
+    let rest = lights.split_off(lights.len().min(max));
+    match overflow {
+        LightOverflow::NearestToCamera => (lights, rest),
+        _ => {
+            if !rest.is_empty() {
+                eprintln!("Dropping {} lights past the cap of {max}", rest.len());
+            }
+            (lights, Vec::new())
         }
-        self.count_uniform
-            .write(ctx, &(self.storage_buffer.len() as u32));
     }
 }
 
@@ -42,7 +355,9 @@ pub fn lights_buffer_bind_group_layout(ctx: &super::GraphicsCtx) -> wgpu::BindGr
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT
+                        | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Storage { read_only: true },
                         has_dynamic_offset: false,
@@ -52,7 +367,9 @@ pub fn lights_buffer_bind_group_layout(ctx: &super::GraphicsCtx) -> wgpu::BindGr
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::VERTEX
+                        | wgpu::ShaderStages::FRAGMENT
+                        | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -87,7 +404,7 @@ fn lights_buffer_bindgroup(
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default, PartialEq)]
 pub struct RawLight {
     pub position: [f32; 3],
     intensity: f32,
@@ -95,6 +412,48 @@ pub struct RawLight {
     pub cut_off: f32,
     pub color: [f32; 3],
     pub light_type: u32, // 0 = None, 1 = Point, 2 = Directional, 3 = Spotlight
+    /// Bitset of [`light_flags`]
+    pub flags: u32,
+    _padding: [u32; 3],
+}
+
+crate::wgsl_layout!(RawLight as "Light" {
+    position,
+    intensity,
+    direction,
+    cut_off: cutoff,
+    color,
+    light_type,
+    flags,
+});
+
+/// Per light feature toggles stored in `RawLight::flags`
+pub mod light_flags {
+    /// Draws a screen space lens flare when the light is visible
+    pub const LENS_FLARE: u32 = 1 << 0;
+    /// Point and spot lights opting into a shadow map, see
+    /// [`crate::graphics::entities::local_shadows::shadow_budget`]
+    pub const CAST_SHADOWS: u32 = 1 << 1;
+}
+
+impl RawLight {
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_flag(mut self, flag: u32, enabled: bool) -> Self {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+        self
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
@@ -121,9 +480,9 @@ pub enum Light {
     },
 }
 
-impl Into<RawLight> for Light {
-    fn into(self) -> RawLight {
-        match self {
+impl From<Light> for RawLight {
+    fn from(light: Light) -> Self {
+        match light {
             Light::None => RawLight::default(),
             Light::Point {
                 position,
@@ -160,6 +519,7 @@ impl Into<RawLight> for Light {
                 color: color.into(),
                 cut_off,
                 light_type: 3,
+                ..Default::default()
             },
         }
     }
@@ -192,6 +552,15 @@ impl Light {
         }
     }
 
+    pub fn color_mut(&mut self) -> Option<&mut Color3> {
+        match self {
+            Light::None => None,
+            Light::Point { color, .. }
+            | Light::Directional { color, .. }
+            | Light::Spotlight { color, .. } => Some(color),
+        }
+    }
+
     pub fn label(&self) -> &str {
         match self {
             Light::None => "None",
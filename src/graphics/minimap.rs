@@ -0,0 +1,92 @@
+use nalgebra::{Point3, Vector2, Vector3};
+
+use super::{
+    camera::{Camera, Projection},
+    ctx::GraphicsCtx,
+    entities::{model::ModelsBuffer, renderer::EntitiesRenderer},
+    frame::FrameConstants,
+    light::LightsUniform,
+    settings::MinimapSettings,
+    view::OffscreenView,
+};
+
+/// Height of the minimap camera above the followed position
+const HEIGHT: f32 = 200.0;
+
+/// Top down orthographic view of the entities around a position, north is up
+pub struct Minimap {
+    pub view: OffscreenView,
+    /// Frames since the view was last rendered
+    age: u32,
+}
+
+impl Minimap {
+    pub fn new(
+        ctx: &GraphicsCtx,
+        settings: &MinimapSettings,
+        frame: &FrameConstants,
+        models: &ModelsBuffer,
+    ) -> Self {
+        let size = (settings.resolution, settings.resolution);
+        let mut view = OffscreenView::new("Minimap", ctx, size, frame, models);
+        view.update_proj(ctx, &projection(settings));
+        Self {
+            view,
+            age: u32::MAX,
+        }
+    }
+
+    /// Returns true when the targets were recreated, the texture must be registered again where it
+    /// is displayed
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &MinimapSettings) -> bool {
+        self.view.update_proj(ctx, &projection(settings));
+        self.age = u32::MAX;
+        self.view
+            .resize(ctx, (settings.resolution, settings.resolution))
+    }
+
+    /// Moves the view over `center`, returns true when it is due this frame
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &MinimapSettings,
+        models: &ModelsBuffer,
+        center: Point3<f32>,
+    ) -> bool {
+        self.age = self.age.saturating_add(1);
+        if !settings.enabled || self.age < settings.interval.max(1) {
+            return false;
+        }
+        self.age = 0;
+        self.view.update_view(
+            ctx,
+            models,
+            &Camera {
+                eye: center + Vector3::y() * HEIGHT,
+                pitch_deg: -90.0,
+                ..Default::default()
+            },
+        );
+        true
+    }
+
+    /// Must be recorded after the instances are animated for the frame
+    pub fn record(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        entities: &EntitiesRenderer,
+        lights: &LightsUniform,
+    ) {
+        self.view.record(ctx, encoder, entities, lights)
+    }
+}
+
+fn projection(settings: &MinimapSettings) -> Projection {
+    let size = Vector2::new(settings.resolution, settings.resolution);
+    Projection {
+        ortho: true,
+        ortho_height: settings.extent,
+        ..Projection::new(size, 90.0)
+    }
+}
@@ -0,0 +1,384 @@
+use nalgebra::{Matrix3, Matrix4, Vector2, Vector3};
+use wgpu::include_wgsl;
+
+use super::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    camera::CameraUniform,
+    color_attachment,
+    ctx::GraphicsCtx,
+    entities::{
+        draws::DrawGenerator,
+        model::ModelsBuffer,
+        renderer::{EntitiesPass, EntitiesRenderer},
+    },
+    frame::FrameConstants,
+    light::LightsUniform,
+    motion::VELOCITY_FORMAT,
+    post::HDR_FORMAT,
+    sampler::SamplerDesc,
+    settings::MirrorSettings,
+    utils::{stencil_state, TextureWrapper},
+};
+
+/// Stencil value of the pixels covered by the mirror
+const MIRROR_STENCIL: u32 = 1;
+
+/// Matches `Quad` in `mirror.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawQuad {
+    /// Clip space corners of the mirror, in fan order
+    corners: [[f32; 4]; 4],
+}
+
+/// Planar mirror, the entities are rendered a second time through the reflected camera
+///
+/// The quad of the mirror is first written into the stencil of the view, the masked entities pass
+/// then only shades the pixels it covers. The reflection is composited into the scene color with
+/// the depth of the quad. There is no oblique clip plane, the entities behind the mirror are
+/// reflected too, and the sky and the terrain are left out
+pub struct Mirror {
+    hdr: TextureWrapper,
+    velocity: TextureWrapper,
+    depth: TextureWrapper,
+    camera: CameraUniform,
+    draws: DrawGenerator,
+    quad: UniformBuffer<RawQuad>,
+    quad_bind_group: wgpu::BindGroup,
+    hdr_bind_group: wgpu::BindGroup,
+    mask_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl Mirror {
+    /// `size` is the render size of the scene, the reflection is drawn at the same pixels
+    pub fn new(
+        ctx: &GraphicsCtx,
+        frame: &FrameConstants,
+        models: &ModelsBuffer,
+        size: (u32, u32),
+    ) -> Self {
+        let hdr = new_target(ctx, size, HDR_FORMAT);
+        let quad = UniformBuffer::new(
+            "Mirror quad",
+            ctx,
+            &RawQuad {
+                corners: [[0.0; 4]; 4],
+            },
+        );
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("mirror.wgsl"));
+        let mask_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&quad_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let mask_pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Mirror mask"),
+                layout: Some(&mask_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureWrapper::DEPTH_STENCIL_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: stencil_state(
+                        wgpu::CompareFunction::Always,
+                        wgpu::StencilOperation::Replace,
+                    ),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: None,
+                multiview: None,
+                cache: None,
+            });
+
+        let composite_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&quad_bind_group_layout(ctx), &hdr_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let composite_pipeline =
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Mirror composite"),
+                    layout: Some(&composite_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: TextureWrapper::DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_composite"),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: HDR_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    multiview: None,
+                    cache: None,
+                });
+
+        Self {
+            velocity: new_target(ctx, size, VELOCITY_FORMAT),
+            depth: TextureWrapper::new_depth_stencil("Mirror", ctx, size),
+            camera: CameraUniform::new(ctx, frame),
+            draws: DrawGenerator::new(ctx, models),
+            quad_bind_group: quad_bind_group(ctx, &quad),
+            hdr_bind_group: hdr_bind_group(ctx, &hdr),
+            hdr,
+            quad,
+            mask_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, size: (u32, u32)) {
+        self.hdr = new_target(ctx, size, HDR_FORMAT);
+        self.velocity = new_target(ctx, size, VELOCITY_FORMAT);
+        self.depth = TextureWrapper::new_depth_stencil("Mirror", ctx, size);
+        self.hdr_bind_group = hdr_bind_group(ctx, &self.hdr);
+    }
+
+    /// Reflects `main` by the plane of the mirror and syncs the draws with the instances, before
+    /// recording. Returns false when the camera is behind the mirror, nothing is drawn then
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &MirrorSettings,
+        models: &ModelsBuffer,
+        main: &CameraUniform,
+    ) -> bool {
+        let Some(normal) = settings.normal.try_normalize(1e-6) else {
+            return false;
+        };
+        let distance = normal.dot(&settings.center.coords);
+        if normal.dot(&main.eye().coords) <= distance {
+            return false;
+        }
+
+        // Any axis of the plane, vertical unless the mirror lies flat
+        let up = if normal.y.abs() < 0.99 {
+            Vector3::y()
+        } else {
+            Vector3::z()
+        };
+        let right = up.cross(&normal).normalize() * settings.width * 0.5;
+        let up = normal.cross(&right).normalize() * settings.height * 0.5;
+        // Points of the plane are left in place, the quad is at the same pixels in both views
+        let view_proj = main.view_proj();
+        let corners = [-right - up, right - up, right + up, up - right]
+            .map(|offset| (view_proj * (settings.center + offset).to_homogeneous()).into());
+        self.quad.write(ctx, &RawQuad { corners });
+
+        self.draws.apply_changes(ctx, models);
+        self.camera
+            .update_view_matrix(ctx, main.view_matrix() * reflection(normal, distance));
+        let (width, height) = self.hdr.size();
+        self.camera
+            .update_proj_matrix(ctx, main.proj_matrix(), Vector2::new(width, height));
+        true
+    }
+
+    /// Must be recorded after the instances are animated for the frame
+    pub fn record(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        entities: &EntitiesRenderer,
+        lights: &LightsUniform,
+    ) {
+        entities.generate_draws_into(ctx, encoder, &self.camera, &self.draws);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mirror mask"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(self.depth_attachment(true)),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.mask_pipeline);
+        render_pass.set_bind_group(0, &self.quad_bind_group, &[]);
+        render_pass.set_stencil_reference(MIRROR_STENCIL);
+        render_pass.draw(0..6, 0..1);
+        drop(render_pass);
+
+        let mut render_pass = encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mirror entities"),
+                color_attachments: &[
+                    Some(color_attachment(&self.hdr.view, true)),
+                    Some(color_attachment(&self.velocity.view, true)),
+                ],
+                depth_stencil_attachment: Some(self.depth_attachment(false)),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            })
+            .forget_lifetime();
+        render_pass.set_stencil_reference(MIRROR_STENCIL);
+        entities.render_with(
+            &mut render_pass,
+            &self.camera,
+            lights,
+            EntitiesPass::Masked,
+            &self.draws,
+        );
+    }
+
+    /// Draws the reflection over the scene, after the commands of [`Self::record`] are submitted
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_color: &wgpu::TextureView,
+        depth_texture: &TextureWrapper,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mirror composite"),
+            color_attachments: &[Some(color_attachment(scene_color, false))],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.quad_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.hdr_bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+
+    /// The mask clears the depth and the stencil, the entities keep them
+    fn depth_attachment(&self, clear: bool) -> wgpu::RenderPassDepthStencilAttachment {
+        let ops = |clear_value| wgpu::Operations {
+            load: if clear {
+                wgpu::LoadOp::Clear(clear_value)
+            } else {
+                wgpu::LoadOp::Load
+            },
+            store: wgpu::StoreOp::Store,
+        };
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth.view,
+            depth_ops: Some(ops(1.0)),
+            stencil_ops: Some(wgpu::Operations {
+                load: if clear {
+                    wgpu::LoadOp::Clear(0)
+                } else {
+                    wgpu::LoadOp::Load
+                },
+                store: wgpu::StoreOp::Store,
+            }),
+        }
+    }
+}
+
+/// Reflection by the plane of the points `p` where `normal . p = distance`
+fn reflection(normal: Vector3<f32>, distance: f32) -> Matrix4<f32> {
+    let mut matrix = Matrix4::identity();
+    matrix
+        .fixed_view_mut::<3, 3>(0, 0)
+        .copy_from(&(Matrix3::identity() - normal * normal.transpose() * 2.0));
+    matrix
+        .fixed_view_mut::<3, 1>(0, 3)
+        .copy_from(&(normal * 2.0 * distance));
+    matrix
+}
+
+fn new_target(ctx: &GraphicsCtx, size: (u32, u32), format: wgpu::TextureFormat) -> TextureWrapper {
+    TextureWrapper::new_render_target(
+        "Mirror",
+        ctx,
+        size,
+        format,
+        SamplerDesc::LINEAR,
+        wgpu::TextureUsages::empty(),
+    )
+}
+
+fn quad_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Mirror Quad Bind Group Layout"),
+        })
+}
+
+fn quad_bind_group(ctx: &GraphicsCtx, quad: &UniformBuffer<RawQuad>) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &quad_bind_group_layout(ctx),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: quad.binding(),
+        }],
+        label: Some("Mirror Quad Bind Group"),
+    })
+}
+
+fn hdr_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("Mirror Hdr Bind Group Layout"),
+        })
+}
+
+fn hdr_bind_group(ctx: &GraphicsCtx, hdr: &TextureWrapper) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &hdr_bind_group_layout(ctx),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&hdr.view),
+        }],
+        label: Some("Mirror Hdr Bind Group"),
+    })
+}
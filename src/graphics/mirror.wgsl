@@ -0,0 +1,22 @@
+// Matches `RawQuad`
+struct Quad {
+    corners: array<vec4f, 4>,
+}
+
+@group(0) @binding(0)
+var<uniform> quad: Quad;
+@group(1) @binding(0)
+var t_reflection: texture_2d<f32>;
+
+// Two triangles of the mirror quad, already in clip space
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4f {
+    var fan = array<u32, 6>(0u, 1u, 2u, 0u, 2u, 3u);
+    return quad.corners[fan[vertex_index]];
+}
+
+// The reflected view is drawn at the pixels of the quad
+@fragment
+fn fs_composite(@builtin(position) frag_coord: vec4f) -> @location(0) vec4f {
+    return vec4f(textureLoad(t_reflection, vec2u(frag_coord.xy), 0).rgb, 1.0);
+}
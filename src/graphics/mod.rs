@@ -1,157 +1,1310 @@
-use std::cell::LazyCell;
+use std::{cell::RefCell, rc::Rc};
 
 use buffer::{CommonBuffer, UniformBuffer, WriteBuffer};
-use camera::{Camera, CameraUniform};
+use camera::{Camera, CameraUniform, Projection};
+use capture::FrameCapture;
 use color::Color3;
-use ctx::{Frame, GraphicsCtx};
+use ctx::GraphicsCtx;
+use debug_lines::DebugLines;
+use depth::SceneDepth;
 
+#[cfg(feature = "editor")]
 pub use egui::FullOutput as EguiOutput;
+#[cfg(feature = "editor")]
 pub use egui_wgpu::Renderer as EguiRenderer;
+#[cfg(feature = "editor")]
 use egui_wgpu::ScreenDescriptor;
-use entities::renderer::EntitiesRenderer;
-use light::{Light, LightsUniform, RawLight};
+use entities::renderer::{EntitiesPass, EntitiesRenderer};
+use frame::FrameConstants;
+use frame_target::{FrameBlit, FrameTarget};
+use graph::FrameGraph;
+use light::{light_flags, Light, LightsUniform, RawLight};
+use minimap::Minimap;
+use mirror::Mirror;
+use motion::MotionVectors;
 use nalgebra::{Matrix4, Point3, Vector3};
+use origin::WorldOrigin;
+use picking::DepthPicker;
+use plugin::{RenderPlugin, RenderStage, StageTargets};
+use post::{
+    compute::{ComputeEffectDesc, ComputeEffects},
+    upscale::Upscale,
+    PostStack,
+};
+#[cfg(feature = "post-processing")]
+use post::{fog::VolumetricFog, lens_flare::LensFlare, outline::Outline};
+use settings::RenderSettings;
+use sky::Sky;
+use stats::BufferPeaks;
+#[cfg(feature = "terrain")]
 use terrain::TerrainRenderer;
 use utils::TextureWrapper;
+use view::OffscreenView;
+use volumes::{PostVolume, PostVolumes};
+use weather::Precipitation;
+use world_bars::WorldBars;
+
+use crate::{constants, profile_scope, scene::Scene};
 
 pub mod assets;
 pub mod atlas;
+pub mod bounds;
 pub mod buffer;
+pub mod bundle;
 pub mod camera;
+pub mod capture;
+pub mod churn;
 pub mod color;
 pub mod ctx;
+pub mod cubemap;
+pub mod debug_lines;
+pub mod depth;
 pub mod entities;
+pub mod frame;
+pub mod frame_target;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "golden-tests")]
+pub mod golden;
+pub mod graph;
 pub mod light;
+pub mod minimap;
+pub mod mirror;
+pub mod motion;
+pub mod origin;
+pub mod picking;
+pub mod plugin;
+pub mod post;
+pub mod reflect;
+pub mod render_thread;
+pub mod sampler;
+pub mod settings;
+#[cfg(feature = "shader-tests")]
+pub mod shaders;
+pub mod sky;
+pub mod stats;
+pub mod streaming;
+#[cfg(feature = "terrain")]
 pub mod terrain;
 pub mod utils;
+pub mod view;
+pub mod volumes;
+pub mod weather;
+pub mod world_bars;
+
+/// Height in pixels of the camera preview, its width follows the aspect of the viewport
+pub const PREVIEW_HEIGHT: u32 = 180;
 
 pub struct GlobalRenderer {
+    #[cfg(feature = "editor")]
     egui: EguiRenderer,
+    /// [`Minimap::view`] registered with the egui renderer
+    #[cfg(feature = "editor")]
+    minimap_texture: egui::TextureId,
+    /// [`Self::preview`] registered with the egui renderer
+    #[cfg(feature = "editor")]
+    preview_texture: egui::TextureId,
+    #[cfg(feature = "terrain")]
     pub terrain: TerrainRenderer,
     pub entities: EntitiesRenderer,
+    pub post: PostStack,
+    upscale: Upscale,
+    pub compute_effects: ComputeEffects,
+    #[cfg(feature = "post-processing")]
+    fog: VolumetricFog,
+    #[cfg(feature = "post-processing")]
+    lens_flare: LensFlare,
+    #[cfg(feature = "post-processing")]
+    outline: Outline,
+    pub debug_lines: DebugLines,
+    pub world_bars: WorldBars,
+    pub picker: DepthPicker,
+    pub capture: FrameCapture,
+    pub motion_vectors: MotionVectors,
+    pub minimap: Minimap,
+    /// Picture in picture view from another camera, see [`Self::set_preview`]
+    pub preview: OffscreenView,
+    preview_camera: Option<(Camera, Projection)>,
+    /// Drawn from [`RenderSettings::mirror`]
+    pub mirror: Mirror,
+    pub sky: Sky,
+    pub weather: Precipitation,
+    plugins: Vec<Box<dyn RenderPlugin>>,
 
     pub lights: LightsUniform,
     pub camera: CameraUniform,
+    pub frame: FrameConstants,
+    pub origin: WorldOrigin,
+
+    /// Override [`Self::settings`] while the camera is around them
+    pub volumes: PostVolumes,
+    /// Largest buffer sizes since the last [`Self::presize`], saved with the scene
+    pub peaks: BufferPeaks,
+    /// Given to [`Self::presize`], allocated on the next submit
+    presize: Option<BufferPeaks>,
+    /// Edited freely, changes are detected against `applied_settings` on submit
+    pub settings: RenderSettings,
+    applied_settings: RenderSettings,
 
+    /// Of the scene targets, the viewport scaled by [`settings::ResolutionSettings::scale`]
+    render_size: (u32, u32),
     depth_texture: TextureWrapper,
+    pub scene_depth: SceneDepth,
+    /// Recorded into by [`Self::record_offscreen`], created on its first frame
+    frame_target: Option<FrameTarget>,
 }
 
 pub struct RenderData {
     pub window_size: (u32, u32),
     pub aspect_ratio: f32,
+    pub camera_position: Point3<f32>,
 
+    #[cfg(feature = "editor")]
     pub egui_ctx: egui::Context,
+    #[cfg(feature = "editor")]
     pub egui_output: EguiOutput,
 }
 
-const TEST_LIGHTS: LazyCell<[RawLight; 3]> = LazyCell::new(|| {
-    [
-        Light::Directional {
-            direction: Vector3::new(0.0, -0.9, -0.3).normalize(),
-            intensity: 1.5,
-            color: Color3::WHITE,
-        }
-        .into(),
-        Light::Point {
-            position: Point3::new(5.0, 5.0, 1.0),
-            intensity: 5.0,
-            color: Color3::CYAN,
-        }
-        .into(),
-        Light::Point {
-            position: Point3::new(-5.0, 1.0, 1.0),
-            intensity: 5.0,
-            color: Color3::RED,
-        }
-        .into(),
-    ]
-});
+/// Everything [`GlobalRenderer::record`] needs besides the renderer, fixed once the frame is
+/// prepared
+pub struct FrameSnapshot {
+    pub render_state: RenderData,
+    due: DuePasses,
+    /// Settings edited by the user, put back once the frame is recorded with the ones of the
+    /// post volumes
+    edited: Option<RenderSettings>,
+}
+
+/// Frame recorded by [`GlobalRenderer::record_offscreen`], submitted and drawn over the surface
+/// by [`Self::submit`] without the renderer
+pub struct RecordedFrame {
+    /// Frame encoder included, in submission order
+    commands: Vec<wgpu::CommandBuffer>,
+    blit: FrameBlit,
+}
+
+/// Optional passes recorded with the frame
+#[derive(Debug, Clone, Copy)]
+struct DuePasses {
+    minimap: bool,
+    preview: bool,
+    mirror: bool,
+}
+
+/// Used when the default scene is missing or defines no light
+fn builtin_lights() -> Vec<RawLight> {
+    vec![RawLight::from(Light::Directional {
+        direction: Vector3::new(0.0, -0.9, -0.3).normalize(),
+        intensity: 1.5,
+        color: Color3::WHITE,
+    })
+    .with_flag(light_flags::LENS_FLARE, true)]
+}
+
+/// Light rig and volumes of the default scene asset
+fn default_scene() -> (Vec<RawLight>, Vec<PostVolume>) {
+    match Scene::from_assets(constants::DEFAULT_SCENE) {
+        Ok(scene) if !scene.lights.is_empty() => (
+            scene.lights.into_iter().map(RawLight::from).collect(),
+            scene.volumes,
+        ),
+        Ok(scene) => (builtin_lights(), scene.volumes),
+        Err(e) => {
+            eprintln!("Using the builtin light rig: {e}");
+            (builtin_lights(), Vec::new())
+        }
+    }
+}
 
 impl GlobalRenderer {
     pub fn new(ctx: &GraphicsCtx) -> Self {
-        let lights = LightsUniform::new(ctx, TEST_LIGHTS.as_ref());
-        let camera = CameraUniform::new(ctx);
+        let settings = RenderSettings::default();
+        let (default_lights, default_volumes) = default_scene();
+        let lights = LightsUniform::new(ctx, &default_lights, &settings.lights);
+        let frame = FrameConstants::new(ctx);
+        let camera = CameraUniform::new(ctx, &frame);
 
-        let depth_texture = TextureWrapper::new_depth("3d", ctx, ctx.viewport_size);
+        let render_size = render_size(ctx.viewport_size, settings.resolution.scale);
+        let depth_texture = TextureWrapper::new_depth("3d", ctx, render_size);
+        let scene_depth = SceneDepth::new(ctx, render_size);
 
-        let egui = EguiRenderer::new(
+        #[cfg(feature = "editor")]
+        let mut egui = EguiRenderer::new(&ctx.device, ctx.surface_format, None, 1, false);
+
+        // Renderers decode their assets and upload them concurrently
+        let new_entities = || EntitiesRenderer::new(ctx, &settings, &frame, &scene_depth);
+        let new_post = || {
+            PostStack::new(
+                ctx,
+                &settings.post,
+                &settings.environment.exposure,
+                render_size,
+            )
+        };
+        #[cfg(feature = "terrain")]
+        let ((entities, terrain), post) = rayon::join(
+            || {
+                rayon::join(new_entities, || {
+                    TerrainRenderer::new(ctx, &camera, &scene_depth, &settings.terrain)
+                })
+            },
+            new_post,
+        );
+        #[cfg(not(feature = "terrain"))]
+        let (entities, post) = rayon::join(new_entities, new_post);
+        #[cfg(feature = "post-processing")]
+        let fog = VolumetricFog::new(ctx, &settings.environment.fog, &scene_depth.texture);
+        #[cfg(feature = "post-processing")]
+        let lens_flare = LensFlare::new(ctx, &settings.post, &scene_depth.texture);
+        #[cfg(feature = "post-processing")]
+        let outline = Outline::new(ctx, &settings.post.outline);
+        let minimap = Minimap::new(ctx, &settings.minimap, &frame, &entities.models);
+        #[cfg(feature = "editor")]
+        let minimap_texture = egui.register_native_texture(
             &ctx.device,
-            ctx.surface_format,
-            Some(TextureWrapper::DEPTH_FORMAT),
-            1,
-            false,
+            &minimap.view.texture.view,
+            wgpu::FilterMode::Linear,
+        );
+        let preview = OffscreenView::new(
+            "Camera preview",
+            ctx,
+            preview_size(ctx.viewport_size),
+            &frame,
+            &entities.models,
+        );
+        let mirror = Mirror::new(ctx, &frame, &entities.models, render_size);
+        #[cfg(feature = "editor")]
+        let preview_texture = egui.register_native_texture(
+            &ctx.device,
+            &preview.texture.view,
+            wgpu::FilterMode::Linear,
         );
 
-        let entities = EntitiesRenderer::new(ctx);
-        let terrain = TerrainRenderer::new(ctx, &camera);
-
-        Self {
+        let mut renderer = Self {
+            #[cfg(feature = "editor")]
             egui,
+            #[cfg(feature = "editor")]
+            minimap_texture,
+            #[cfg(feature = "editor")]
+            preview_texture,
             entities,
+            #[cfg(feature = "terrain")]
             terrain,
+            post,
+            upscale: Upscale::new(ctx, render_size, settings.resolution.filter),
+            compute_effects: ComputeEffects::new(ctx, render_size),
+            #[cfg(feature = "post-processing")]
+            fog,
+            #[cfg(feature = "post-processing")]
+            lens_flare,
+            #[cfg(feature = "post-processing")]
+            outline,
+            debug_lines: DebugLines::new(ctx),
+            world_bars: WorldBars::new(ctx),
+            picker: DepthPicker::new(ctx),
+            capture: FrameCapture::default(),
+            motion_vectors: MotionVectors::new(ctx, render_size),
+            minimap,
+            preview,
+            preview_camera: None,
+            mirror,
+            sky: Sky::new(ctx),
+            weather: Precipitation::new(ctx),
+            plugins: Vec::new(),
             lights,
             camera,
+            frame,
+            origin: WorldOrigin::default(),
+            volumes: PostVolumes {
+                volumes: default_volumes,
+                ..Default::default()
+            },
+            peaks: BufferPeaks::default(),
+            // A missing sidecar only means the scene was never played
+            presize: BufferPeaks::load(&Scene::stats_path(constants::DEFAULT_SCENE)).ok(),
+            applied_settings: settings.clone(),
+            settings,
+            render_size,
             depth_texture,
+            scene_depth,
+            frame_target: None,
+        };
+        if let Err(e) = renderer.validate_frame_graph() {
+            eprintln!("Invalid frame graph: {e}");
         }
+        renderer
     }
 
+    /// Recreates every resource from the assets of the project just opened, see
+    /// [`crate::ASSETS`]. The settings and the debug palette are kept, the plugins are set up
+    /// again and the compute effects rebuilt with their current params. The projection must be
+    /// uploaded again afterwards
+    pub fn reload_assets(&mut self, ctx: &GraphicsCtx) {
+        let mut renderer = Self::new(ctx);
+        renderer.settings = self.settings.clone();
+        renderer.debug_lines.palette = self.debug_lines.palette;
+        for mut plugin in std::mem::take(&mut self.plugins) {
+            plugin.setup(ctx);
+            renderer.plugins.push(plugin);
+        }
+        for effect in &self.compute_effects.effects {
+            let index = renderer.add_compute_effect(ctx, effect.desc());
+            renderer.compute_effects.effects[index].enabled = effect.enabled;
+        }
+        // Egui only sends its font textures once, the old renderer still has them
+        #[cfg(feature = "editor")]
+        {
+            std::mem::swap(&mut renderer.egui, &mut self.egui);
+            renderer.minimap_texture = self.minimap_texture;
+            renderer.preview_texture = self.preview_texture;
+            renderer.egui.update_egui_texture_from_wgpu_texture(
+                &ctx.device,
+                &renderer.minimap.view.texture.view,
+                wgpu::FilterMode::Linear,
+                renderer.minimap_texture,
+            );
+            renderer.egui.update_egui_texture_from_wgpu_texture(
+                &ctx.device,
+                &renderer.preview.texture.view,
+                wgpu::FilterMode::Linear,
+                renderer.preview_texture,
+            );
+        }
+        // The frames in flight still use the old resources
+        let _ = ctx.device.poll(wgpu::Maintain::Wait);
+        *self = renderer;
+    }
+
+    /// Allocates the buffers for `peaks` on the next submit, which the peaks then start from
+    pub fn presize(&mut self, peaks: BufferPeaks) {
+        self.presize = Some(peaks);
+    }
+
+    fn apply_presize(&mut self, ctx: &GraphicsCtx, peaks: BufferPeaks) {
+        let instances: Vec<_> = peaks.instances.iter().map(|n| *n as usize).collect();
+        self.entities.models.reserve_instances(&instances);
+        self.lights.reserve(ctx, peaks.lights);
+        self.debug_lines.reserve(ctx, peaks.debug_lines as usize);
+        self.world_bars.reserve(ctx, peaks.world_widgets as usize);
+        self.peaks = peaks;
+    }
+
+    pub fn add_plugin(&mut self, ctx: &GraphicsCtx, mut plugin: impl RenderPlugin + 'static) {
+        plugin.setup(ctx);
+        self.plugins.push(Box::new(plugin));
+    }
+
+    /// Returns the index of the effect in `compute_effects`
+    pub fn add_compute_effect(&mut self, ctx: &GraphicsCtx, desc: ComputeEffectDesc) -> usize {
+        self.compute_effects
+            .add(ctx, desc, &self.post.scene_color, &self.scene_depth.texture)
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn RenderPlugin> {
+        self.plugins.iter().map(|plugin| plugin.as_ref())
+    }
+
+    /// The minimap texture for egui images, kept registered when it is recreated
+    #[cfg(feature = "editor")]
+    pub fn minimap_texture(&self) -> egui::TextureId {
+        self.minimap_texture
+    }
+
+    /// The camera preview texture for egui images, sized by [`Self::preview`]
+    #[cfg(feature = "editor")]
+    pub fn preview_texture(&self) -> egui::TextureId {
+        self.preview_texture
+    }
+
+    /// Renders the scene from `camera` into [`Self::preview`] on the next submit, the projection
+    /// size is overridden by the one of the preview
+    pub fn set_preview(&mut self, camera: Camera, proj: Projection) {
+        self.preview_camera = Some((camera, proj));
+    }
+
+    /// Size the scene is drawn at, see [`settings::ResolutionSettings`]
+    pub fn render_size(&self) -> (u32, u32) {
+        self.render_size
+    }
+
+    /// Uploads the projection of the main camera, drawn at the render size
+    pub fn update_proj(&mut self, ctx: &GraphicsCtx, proj: &Projection) {
+        let (width, height) = self.render_size;
+        self.camera
+            .update_proj_matrix(ctx, proj.compute_matrix(), [width, height].into());
+    }
+
+    /// Recreates the screen sized targets, after the viewport or the render scale changed
     pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx) {
-        self.depth_texture = TextureWrapper::new_depth("3d", ctx, ctx.viewport_size);
+        let render_size = render_size(ctx.viewport_size, self.settings.resolution.scale);
+        self.render_size = render_size;
+        let (width, height) = render_size;
+        self.camera
+            .update_proj_matrix(ctx, self.camera.proj_matrix(), [width, height].into());
+        self.upscale
+            .resize(ctx, render_size, self.settings.resolution.filter);
+        self.depth_texture = TextureWrapper::new_depth("3d", ctx, render_size);
+        self.scene_depth.update_viewport_size(ctx, render_size);
+        self.entities.update_viewport_size(ctx, &self.scene_depth);
+        self.post.update_viewport_size(ctx, render_size);
+        self.motion_vectors.update_viewport_size(ctx, render_size);
+        self.mirror.update_viewport_size(ctx, render_size);
+        self.compute_effects.update_viewport_size(
+            ctx,
+            &self.post.scene_color,
+            &self.scene_depth.texture,
+        );
+        #[cfg(feature = "post-processing")]
+        {
+            self.fog
+                .update_viewport_size(ctx, &self.scene_depth.texture);
+            self.lens_flare
+                .update_viewport_size(ctx, &self.scene_depth.texture);
+        }
+        for plugin in &mut self.plugins {
+            plugin.resize(ctx, render_size);
+        }
+        let recreated = self.preview.resize(ctx, preview_size(ctx.viewport_size));
+        #[cfg(feature = "editor")]
+        if recreated {
+            self.egui.update_egui_texture_from_wgpu_texture(
+                &ctx.device,
+                &self.preview.texture.view,
+                wgpu::FilterMode::Linear,
+                self.preview_texture,
+            );
+        }
+        #[cfg(not(feature = "editor"))]
+        let _ = recreated;
     }
 
-    pub fn submit(&mut self, ctx: &GraphicsCtx, render_state: RenderData) {
-        self.lights.apply_changes(ctx);
-        self.entities.apply_changes(ctx);
+    fn apply_settings(&mut self, ctx: &GraphicsCtx) {
+        profile_scope!("Apply settings");
+        if self.settings == self.applied_settings {
+            return;
+        }
+
+        if self.settings.texture_quality != self.applied_settings.texture_quality {
+            self.entities
+                .textures
+                .apply_quality(ctx, &self.settings.texture_quality);
+        }
+
+        if self.settings.streaming != self.applied_settings.streaming {
+            self.entities
+                .textures
+                .apply_settings(ctx, &self.settings.streaming);
+        }
+
+        if self.settings.post != self.applied_settings.post {
+            self.post
+                .apply_settings(ctx, &self.settings.post, &self.applied_settings.post);
+            #[cfg(feature = "post-processing")]
+            {
+                self.lens_flare.apply_settings(ctx, &self.settings.post);
+                self.outline
+                    .apply_settings(ctx, &self.settings.post.outline);
+            }
+        }
+
+        if self.settings.resolution != self.applied_settings.resolution {
+            self.update_viewport_size(ctx);
+        }
 
-        if let Some(mut frame) = ctx.next_frame() {
-            let mut render_pass =
-                clear_color_render_pass(&mut frame, Some(&self.depth_texture)).forget_lifetime();
+        if self.settings.shadows.filter != self.applied_settings.shadows.filter {
+            self.entities
+                .set_shadow_filter(ctx, self.settings.shadows.filter);
+        }
 
-            render_pass.execute_bundles([&self.terrain.render_bundle]);
+        if self.settings.shadows != self.applied_settings.shadows {
             self.entities
-                .render(&mut render_pass, &self.camera, &self.lights);
+                .blobs
+                .apply_settings(ctx, &self.settings.shadows);
+        }
 
-            render_egui(
-                &mut self.egui,
+        if self.settings.lights != self.applied_settings.lights {
+            self.lights.apply_settings(ctx, &self.settings.lights);
+        }
+
+        if self.settings.environment.exposure != self.applied_settings.environment.exposure {
+            self.post
+                .apply_exposure_settings(ctx, &self.settings.environment.exposure);
+        }
+
+        #[cfg(feature = "terrain")]
+        if self.settings.terrain != self.applied_settings.terrain {
+            self.terrain.height.extent = self.settings.terrain.extent;
+            self.terrain.material.apply_settings(
                 ctx,
-                &mut frame,
-                &mut render_pass,
-                ScreenDescriptor {
-                    size_in_pixels: render_state.window_size.into(),
-                    pixels_per_point: render_state.aspect_ratio,
+                &self.settings.terrain,
+                &self.applied_settings.terrain,
+            );
+        }
+
+        #[cfg(feature = "post-processing")]
+        if self.settings.environment.fog != self.applied_settings.environment.fog {
+            self.fog.apply_settings(ctx, &self.settings.environment.fog);
+        }
+
+        if self.settings.minimap != self.applied_settings.minimap {
+            let recreated = self.minimap.apply_settings(ctx, &self.settings.minimap);
+            #[cfg(feature = "editor")]
+            if recreated {
+                self.egui.update_egui_texture_from_wgpu_texture(
+                    &ctx.device,
+                    &self.minimap.view.texture.view,
+                    wgpu::FilterMode::Linear,
+                    self.minimap_texture,
+                );
+            }
+            #[cfg(not(feature = "editor"))]
+            let _ = recreated;
+        }
+
+        if let Err(e) = self.validate_frame_graph() {
+            eprintln!("Invalid frame graph: {e}");
+        }
+
+        self.entities.wireframe = self.settings.wireframe;
+        self.entities.draws.depth_sort = self.settings.depth_sort;
+        self.entities.draws.min_pixels = self.settings.small_object_cull.min_pixels;
+
+        self.applied_settings = self.settings.clone();
+    }
+
+    /// Moves the world origin by `shift`, the instances on the gpu are moved back by a compute
+    /// pass submitted right away so it must run before this frame's instance writes
+    pub fn rebase(&mut self, ctx: &GraphicsCtx, shift: Vector3<f32>) {
+        self.origin.shift(shift);
+        self.entities.rebase(ctx, shift, self.origin.offset());
+        self.lights.rebase(shift);
+        self.volumes.rebase(shift);
+        #[cfg(feature = "terrain")]
+        self.terrain.rebase(shift);
+    }
+
+    /// Passes recorded by [`Self::record`] with the resources they use, in recording order. The
+    /// minimap and the mirror are declared when enabled, the preview as if it was requested
+    pub fn frame_graph(&mut self) -> FrameGraph<'_> {
+        let due = DuePasses {
+            minimap: self.settings.minimap.enabled,
+            preview: true,
+            mirror: self.settings.mirror.enabled,
+        };
+        self.frame_passes(due, None)
+    }
+
+    /// Every problem of [`Self::frame_graph`] joined on separate lines
+    pub fn validate_frame_graph(&mut self) -> Result<(), String> {
+        let errors = self.frame_graph().validate();
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("\n")),
+        }
+    }
+
+    /// Declares the passes of a frame along with how they are recorded, the gui is only drawn
+    /// with a `render_state`
+    fn frame_passes(&mut self, due: DuePasses, render_state: Option<RenderData>) -> FrameGraph<'_> {
+        use graph::Access::{Attachment, Read, Write};
+
+        let show_motion_vectors = self.settings.show_motion_vectors;
+        let Self {
+            #[cfg(feature = "editor")]
+            egui,
+            #[cfg(feature = "terrain")]
+            terrain,
+            entities,
+            post,
+            upscale,
+            compute_effects,
+            #[cfg(feature = "post-processing")]
+            fog,
+            #[cfg(feature = "post-processing")]
+            lens_flare,
+            #[cfg(feature = "post-processing")]
+            outline,
+            debug_lines,
+            world_bars,
+            picker,
+            capture,
+            motion_vectors,
+            minimap,
+            preview,
+            mirror,
+            sky,
+            weather,
+            plugins,
+            lights,
+            camera,
+            depth_texture,
+            scene_depth,
+            ..
+        } = self;
+        #[cfg(feature = "terrain")]
+        let terrain = &*terrain;
+        let (entities, post, upscale, compute_effects) =
+            (&*entities, &*post, &*upscale, &*compute_effects);
+        #[cfg(feature = "post-processing")]
+        let (fog, lens_flare, outline) = (&*fog, &*lens_flare, &*outline);
+        let (motion_vectors, minimap, preview, mirror) =
+            (&*motion_vectors, &*minimap, &*preview, &*mirror);
+        let (sky, weather, lights, camera) = (&*sky, &*weather, &*lights, &*camera);
+        let (depth_texture, scene_depth) = (&*depth_texture, &*scene_depth);
+        let scene_color = &post.scene_color;
+        let velocity = &motion_vectors.velocity;
+        // Recorded at three stages
+        let plugins = Rc::new(RefCell::new(plugins));
+        let scene_targets = move || StageTargets {
+            color: &scene_color.view,
+            color_format: post::HDR_FORMAT,
+            depth: Some(depth_texture),
+            scene_depth: Some(scene_depth),
+            camera,
+            lights,
+        };
+
+        let mut graph = FrameGraph::default();
+        graph
+            .import("Instances")
+            .import("Instance owners")
+            .import("Materials")
+            .import("Lightmap")
+            .import("Atlas")
+            // Sampled by the opaque pass before this frame's copy
+            .import("Scene depth");
+
+        // Clears the scene targets, the terrain is always the first scene pass
+        #[cfg(feature = "terrain")]
+        graph.parallel_pass(
+            "Terrain",
+            &[("Scene color", Attachment), ("Depth", Attachment)],
+            move |_, encoder| {
+                let mut render_pass =
+                    scene_render_pass(encoder, &scene_color.view, None, Some(depth_texture), true);
+                render_pass.execute_bundles([&*terrain.render_bundle]);
+                churn::record("Terrain", terrain::BUNDLE_STATE_CHANGES);
+            },
+        );
+
+        graph
+            .encoder("Entities")
+            .pass(
+                "Instance ranges",
+                &[("Instance owners", Read), ("Mesh args", Write)],
+                move |pass| entities.record_ranges(pass.ctx, pass.encoder),
+            )
+            .pass("Animate", &[("Instances", Write)], move |pass| {
+                entities.animate(pass.ctx, pass.encoder)
+            })
+            .pass(
+                "Generate draws",
+                &[("Instances", Read), ("Mesh args", Read), ("Draws", Write)],
+                move |pass| entities.generate_draws(pass.ctx, pass.encoder, camera),
+            )
+            .pass(
+                "Shadows",
+                &[
+                    ("Instances", Read),
+                    ("Mesh args", Read),
+                    ("Materials", Read),
+                    ("Shadow maps", Attachment),
+                ],
+                move |pass| entities.render_shadows(pass.ctx, pass.encoder),
+            )
+            .pass(
+                "Opaque entities",
+                &[
+                    ("Instances", Read),
+                    ("Draws", Read),
+                    ("Materials", Read),
+                    ("Atlas", Read),
+                    ("Lightmap", Read),
+                    ("Shadow maps", Read),
+                    ("Scene depth", Read),
+                    ("Scene color", Attachment),
+                    ("Velocity", Attachment),
+                    ("Depth", Attachment),
+                ],
+                move |pass| {
+                    let mut render_pass = scene_render_pass(
+                        pass.encoder,
+                        &scene_color.view,
+                        Some(color_attachment(&velocity.view, true)),
+                        Some(depth_texture),
+                        // Without terrain the entities are the first scene pass
+                        !cfg!(feature = "terrain"),
+                    )
+                    .forget_lifetime();
+                    entities.render(&mut render_pass, camera, lights, EntitiesPass::Opaque);
                 },
-                &render_state.egui_ctx,
-                render_state.egui_output,
+            )
+            .pass(
+                "Store history",
+                &[("Draws", Read), ("Draw history", Write)],
+                move |pass| entities.store_history(pass.encoder),
+            )
+            .pass(
+                "Copy scene depth",
+                &[("Depth", Read), ("Scene depth", Write)],
+                move |pass| scene_depth.copy(pass.encoder, depth_texture),
+            )
+            .pass(
+                "Blob shadows",
+                &[
+                    ("Instances", Read),
+                    ("Mesh args", Read),
+                    ("Draws", Read),
+                    ("Scene depth", Read),
+                    ("Scene color", Attachment),
+                ],
+                move |pass| {
+                    entities.render_blob_shadows(
+                        pass.ctx,
+                        pass.encoder,
+                        &scene_color.view,
+                        camera,
+                        scene_depth,
+                    )
+                },
+            )
+            // Behind the opaque scene, the faded materials blend over it
+            .pass(
+                "Sky",
+                &[("Scene color", Attachment), ("Depth", Attachment)],
+                move |pass| sky.render(pass.encoder, &scene_color.view, depth_texture, camera),
+            )
+            .pass(
+                "Faded entities",
+                &[
+                    ("Instances", Read),
+                    ("Draws", Read),
+                    ("Materials", Read),
+                    ("Atlas", Read),
+                    ("Shadow maps", Read),
+                    ("Scene depth", Read),
+                    ("Scene color", Attachment),
+                    ("Velocity", Attachment),
+                    ("Depth", Attachment),
+                ],
+                move |pass| {
+                    let mut render_pass = scene_render_pass(
+                        pass.encoder,
+                        &scene_color.view,
+                        Some(color_attachment(&velocity.view, false)),
+                        Some(depth_texture),
+                        false,
+                    )
+                    .forget_lifetime();
+                    entities.render(&mut render_pass, camera, lights, EntitiesPass::Fade);
+                },
+            )
+            .pass(
+                "Weather",
+                &[("Scene color", Attachment), ("Depth", Attachment)],
+                move |pass| weather.render(pass.encoder, &scene_color.view, depth_texture, camera),
+            );
+
+        // Submitted after the entities, the instances are animated by then
+        if due.minimap {
+            graph.encoder("Minimap").pass(
+                "Minimap",
+                &[
+                    ("Instances", Read),
+                    ("Mesh args", Read),
+                    ("Minimap", Attachment),
+                ],
+                move |pass| minimap.record(pass.ctx, pass.encoder, entities, lights),
+            );
+        }
+        if due.preview {
+            graph.encoder("Preview").pass(
+                "Preview",
+                &[
+                    ("Instances", Read),
+                    ("Mesh args", Read),
+                    ("Preview", Attachment),
+                ],
+                move |pass| preview.record(pass.ctx, pass.encoder, entities, lights),
+            );
+        }
+        if due.mirror {
+            graph.encoder("Mirror").pass(
+                "Mirror",
+                &[
+                    ("Instances", Read),
+                    ("Mesh args", Read),
+                    ("Mirror", Attachment),
+                ],
+                move |pass| mirror.record(pass.ctx, pass.encoder, entities, lights),
+            );
+        }
+
+        graph.frame_encoder();
+        if due.mirror {
+            graph.pass(
+                "Mirror composite",
+                &[
+                    ("Mirror", Read),
+                    ("Scene color", Attachment),
+                    ("Depth", Attachment),
+                ],
+                move |pass| mirror.composite(pass.encoder, &scene_color.view, depth_texture),
+            );
+        }
+        graph
+            .pass(
+                "Scene plugins",
+                &[("Scene color", Attachment), ("Depth", Attachment)],
+                {
+                    let plugins = plugins.clone();
+                    move |pass| {
+                        render_plugins(
+                            &mut plugins.borrow_mut(),
+                            pass.ctx,
+                            RenderStage::Scene,
+                            pass.encoder,
+                            &scene_targets(),
+                        )
+                    }
+                },
+            )
+            .pass(
+                "Picking",
+                &[("Depth", Read), ("Picked depth", Write)],
+                move |pass| picker.record(pass.ctx, pass.encoder, depth_texture, camera),
+            );
+        #[cfg(feature = "post-processing")]
+        graph
+            // Before the fog, distant lines fade with the scene
+            .pass(
+                "Outline",
+                &[("Scene depth", Read), ("Scene color", Attachment)],
+                move |pass| outline.render(pass.encoder, &scene_color.view, camera, scene_depth),
+            )
+            .pass(
+                "Fog",
+                &[("Scene depth", Read), ("Scene color", Attachment)],
+                move |pass| fog.render(pass.encoder, &scene_color.view, camera, lights),
+            )
+            .pass(
+                "Lens flare",
+                &[("Scene depth", Read), ("Scene color", Attachment)],
+                move |pass| lens_flare.render(pass.encoder, &scene_color.view, camera, lights),
+            );
+        graph
+            // Each effect copies the scene color into its input before running, the scene color
+            // is a storage texture while they run
+            .pass(
+                "Compute effects",
+                &[
+                    ("Compute input", Write),
+                    ("Scene depth", Read),
+                    ("Scene color", Write),
+                ],
+                move |pass| compute_effects.render(pass.encoder, scene_color),
+            )
+            .pass(
+                "Debug lines",
+                &[("Scene color", Attachment), ("Depth", Attachment)],
+                move |pass| {
+                    debug_lines.render(
+                        pass.ctx,
+                        pass.encoder,
+                        camera,
+                        &scene_color.view,
+                        depth_texture,
+                    )
+                },
+            )
+            .pass(
+                "Post effect plugins",
+                &[("Scene color", Attachment), ("Depth", Attachment)],
+                {
+                    let plugins = plugins.clone();
+                    move |pass| {
+                        render_plugins(
+                            &mut plugins.borrow_mut(),
+                            pass.ctx,
+                            RenderStage::PostEffects,
+                            pass.encoder,
+                            &scene_targets(),
+                        )
+                    }
+                },
+            )
+            // Drawn at the render size until the upscale, into the surface at the viewport size
+            .pass(
+                "Post",
+                &[("Scene color", Read), ("Post output", Attachment)],
+                move |pass| post.render(pass.ctx, pass.encoder, upscale.output(pass.surface)),
+            )
+            .pass(
+                "World bars",
+                &[("Scene depth", Read), ("Post output", Attachment)],
+                move |pass| {
+                    world_bars.render(
+                        pass.ctx,
+                        pass.encoder,
+                        camera,
+                        upscale.output(pass.surface),
+                        scene_depth,
+                    )
+                },
+            );
+        if show_motion_vectors {
+            graph.pass(
+                "Motion vectors",
+                &[("Velocity", Read), ("Post output", Attachment)],
+                move |pass| {
+                    motion_vectors.render_overlay(pass.encoder, upscale.output(pass.surface))
+                },
+            );
+        }
+        graph
+            .pass(
+                "Upscale",
+                &[("Post output", Read), ("Surface", Attachment)],
+                move |pass| upscale.render(pass.encoder, pass.surface),
+            )
+            .pass("Overlay plugins", &[("Surface", Attachment)], move |pass| {
+                render_plugins(
+                    &mut plugins.borrow_mut(),
+                    pass.ctx,
+                    RenderStage::Overlay,
+                    pass.encoder,
+                    &StageTargets {
+                        color: pass.surface,
+                        color_format: pass.ctx.surface_format,
+                        depth: None,
+                        scene_depth: None,
+                        camera,
+                        lights,
+                    },
+                )
+            })
+            // Without the gui, only the viewport is recorded
+            .pass("Capture", &[("Surface", Read)], move |pass| {
+                capture.copy(pass.encoder, pass.surface_texture)
+            });
+        #[cfg(feature = "editor")]
+        graph.pass("Egui", &[("Surface", Attachment)], move |pass| {
+            if let Some(render_state) = render_state {
+                render_egui(
+                    egui,
+                    pass.ctx,
+                    pass.encoder,
+                    pass.surface,
+                    ScreenDescriptor {
+                        size_in_pixels: render_state.window_size.into(),
+                        pixels_per_point: render_state.aspect_ratio,
+                    },
+                    &render_state.egui_ctx,
+                    render_state.egui_output,
+                );
+            }
+        });
+        // Only the gui reads it
+        #[cfg(not(feature = "editor"))]
+        let _ = render_state;
+        graph
+    }
+
+    /// Records the frame and presents it on the calling thread
+    pub fn submit(&mut self, ctx: &GraphicsCtx, render_state: RenderData) {
+        profile_scope!("Submit");
+        let snapshot = self.prepare(ctx, render_state);
+        if let Some(surface_texture) = self.record(ctx, snapshot) {
+            surface_texture.present();
+        }
+    }
+
+    /// Uploads the changes of the frame, what is left to record it is in the returned snapshot.
+    /// The queue writes of the next frame must wait until it is submitted by [`Self::record`]
+    pub fn prepare(&mut self, ctx: &GraphicsCtx, render_state: RenderData) -> FrameSnapshot {
+        profile_scope!("Prepare");
+        // Only for this frame, the edited settings are put back once it is recorded
+        let edited = self
+            .volumes
+            .apply(&mut self.settings, &render_state.camera_position);
+        self.apply_settings(ctx);
+        self.picker.poll(ctx);
+        // A frame recorded offscreen is submitted after it was recorded, by the next prepare
+        self.capture.after_submit();
+        self.capture.poll(ctx);
+        {
+            profile_scope!("Apply changes");
+            if let Some(peaks) = self.presize.take() {
+                self.apply_presize(ctx, peaks);
+            }
+            let peaks = BufferPeaks::of(self);
+            self.peaks.merge(&peaks);
+            self.sky.update(
+                ctx,
+                &self.settings.environment.sky,
+                self.lights.shadow_caster().map(|(_, direction)| direction),
             );
+            self.frame.update(
+                ctx,
+                self.render_size,
+                &self.settings,
+                render_state.camera_position,
+                &self.sky,
+                &self.origin,
+                &self.weather.state,
+            );
+            self.weather.update(
+                ctx,
+                &self.settings.environment.precipitation,
+                &self.settings.environment.wind,
+            );
+            self.lights.apply_changes(ctx, render_state.camera_position);
+            #[cfg(feature = "terrain")]
+            {
+                self.terrain.height.upload_dirty(ctx);
+                self.terrain.material.upload_dirty(ctx);
+                self.terrain
+                    .refresh_bundle(ctx, &self.camera, &self.scene_depth);
+            }
+            self.entities
+                .apply_changes(ctx, render_state.camera_position);
+            self.entities
+                .wind
+                .update(ctx, &self.settings.environment.wind);
+            self.entities.shadows.update(
+                ctx,
+                &self.settings.shadows,
+                self.camera.inv_view_proj(),
+                self.lights.shadow_caster(),
+            );
+            self.entities.shadows.local.update(
+                ctx,
+                &self.settings.shadows,
+                &self.lights,
+                render_state.camera_position,
+            );
+            for plugin in &mut self.plugins {
+                plugin.prepare(ctx);
+            }
+        }
+        let minimap = self.minimap.update(
+            ctx,
+            &self.settings.minimap,
+            &self.entities.models,
+            render_state.camera_position,
+        );
+        // Requested again every frame it is shown
+        let preview = match self.preview_camera.take() {
+            Some((camera, proj)) => {
+                let (width, height) = self.preview.size();
+                self.preview.update_proj(
+                    ctx,
+                    &Projection {
+                        size: [width, height].into(),
+                        ..proj
+                    },
+                );
+                self.preview
+                    .update_view(ctx, &self.entities.models, &camera);
+                true
+            }
+            None => false,
+        };
+        let mirror = self.settings.mirror.enabled
+            && self.mirror.update(
+                ctx,
+                &self.settings.mirror,
+                &self.entities.models,
+                &self.camera,
+            );
+        FrameSnapshot {
+            render_state,
+            due: DuePasses {
+                minimap,
+                preview,
+                mirror,
+            },
+            edited,
+        }
+    }
+
+    /// Records and submits the frame prepared by [`Self::prepare`], the surface texture is left to
+    /// present
+    pub fn record(
+        &mut self,
+        ctx: &GraphicsCtx,
+        snapshot: FrameSnapshot,
+    ) -> Option<wgpu::SurfaceTexture> {
+        profile_scope!("Record");
+        let Some(mut frame) = ctx.next_frame() else {
+            if let Some(edited) = snapshot.edited {
+                self.settings = edited;
+            }
+            return None;
+        };
+        let texture = frame.texture(ctx).cloned();
+        let scene_commands = self.record_passes(
+            ctx,
+            snapshot,
+            &frame.view,
+            texture.as_ref(),
+            &mut frame.encoder,
+        );
 
-            drop(render_pass);
+        profile_scope!("Submit frame");
+        let surface_texture = frame.submit(ctx, scene_commands);
+        self.capture.after_submit();
+        surface_texture
+    }
 
-            frame.present(ctx);
+    /// Records the frame prepared by [`Self::prepare`] into [`FrameTarget`] instead of the
+    /// surface, the returned frame is submitted and drawn over the surface without the renderer
+    pub fn record_offscreen(
+        &mut self,
+        ctx: &GraphicsCtx,
+        snapshot: FrameSnapshot,
+    ) -> RecordedFrame {
+        profile_scope!("Record");
+        // Kept alive by the blits of the frames still recorded from it
+        let target = self
+            .frame_target
+            .take()
+            .filter(|target| !target.outdated(ctx))
+            .unwrap_or_else(|| FrameTarget::new(ctx));
+        let mut frame_encoder =
+            ctx.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Frame"),
+                });
+        let mut commands = self.record_passes(
+            ctx,
+            snapshot,
+            &target.texture.view,
+            Some(&target.texture.texture),
+            &mut frame_encoder,
+        );
+        commands.push(frame_encoder.finish());
+        let blit = target.blit();
+        self.frame_target = Some(target);
+        RecordedFrame { commands, blit }
+    }
+
+    /// Records the passes into `surface`, returns the command buffers to submit before
+    /// `frame_encoder`
+    fn record_passes(
+        &mut self,
+        ctx: &GraphicsCtx,
+        snapshot: FrameSnapshot,
+        surface: &wgpu::TextureView,
+        surface_texture: Option<&wgpu::Texture>,
+        frame_encoder: &mut wgpu::CommandEncoder,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let FrameSnapshot {
+            render_state,
+            due,
+            edited,
+        } = snapshot;
+        let commands = self.frame_passes(due, Some(render_state)).record(
+            ctx,
+            surface,
+            surface_texture,
+            frame_encoder,
+        );
+        churn::end_frame();
+        if let Some(edited) = edited {
+            self.settings = edited;
         }
+        commands
+    }
+}
+
+impl RecordedFrame {
+    /// Submits the recorded commands, the queue writes of the next frame can follow. The frame is
+    /// drawn over the surface by the returned blit once a surface texture is acquired
+    pub fn submit(self, ctx: &GraphicsCtx) -> FrameBlit {
+        profile_scope!("Submit frame");
+        ctx.queue.submit(self.commands);
+        self.blit
     }
 }
 
-fn clear_color_render_pass<'a>(
-    r: &'a mut Frame,
+/// Size of [`GlobalRenderer::preview`] for a viewport
+fn preview_size(viewport_size: (u32, u32)) -> (u32, u32) {
+    let (width, height) = viewport_size;
+    let aspect = width.max(1) as f32 / height.max(1) as f32;
+    (
+        ((PREVIEW_HEIGHT as f32 * aspect).round() as u32).max(1),
+        PREVIEW_HEIGHT,
+    )
+}
+
+/// Size of the scene targets for a viewport, see [`settings::ResolutionSettings`]
+fn render_size(viewport_size: (u32, u32), scale: f32) -> (u32, u32) {
+    let (width, height) = viewport_size;
+    let scale = scale.clamp(0.1, 1.0);
+    (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    )
+}
+
+fn render_plugins(
+    plugins: &mut [Box<dyn RenderPlugin>],
+    ctx: &GraphicsCtx,
+    stage: RenderStage,
+    encoder: &mut wgpu::CommandEncoder,
+    targets: &StageTargets,
+) {
+    profile_scope!("Record plugins");
+    for plugin in plugins {
+        plugin.render(ctx, stage, encoder, targets);
+    }
+}
+
+/// Cleared when `clear` is set, loaded otherwise
+fn color_attachment(view: &wgpu::TextureView, clear: bool) -> wgpu::RenderPassColorAttachment {
+    wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+            load: if clear {
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+            } else {
+                wgpu::LoadOp::Load
+            },
+            store: wgpu::StoreOp::Store,
+        },
+    }
+}
+
+/// Targets are cleared when `clear` is set, loaded otherwise
+///
+/// The velocity is only written by the entities, which clear it in their first pass
+fn scene_render_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    target: &'a wgpu::TextureView,
+    velocity: Option<wgpu::RenderPassColorAttachment<'a>>,
     depth_texture: Option<&'a TextureWrapper>,
+    clear: bool,
 ) -> wgpu::RenderPass<'a> {
-    r.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+    let color_attachments = [Some(color_attachment(target, clear))]
+        .into_iter()
+        .chain(velocity.map(Some))
+        .collect::<Vec<_>>();
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: None,
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &r.view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                store: wgpu::StoreOp::Store,
-            },
-        })],
+        color_attachments: &color_attachments,
         occlusion_query_set: None,
         timestamp_writes: None,
         depth_stencil_attachment: depth_texture.map(|t: &TextureWrapper| {
             wgpu::RenderPassDepthStencilAttachment {
                 view: &t.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: if clear {
+                        wgpu::LoadOp::Clear(1.0)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -160,11 +1313,12 @@ fn clear_color_render_pass<'a>(
     })
 }
 
+#[cfg(feature = "editor")]
 fn render_egui(
     renderer: &mut EguiRenderer,
     g: &GraphicsCtx,
-    r: &mut Frame,
-    pass: &mut wgpu::RenderPass<'static>,
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
     screen_descriptor: ScreenDescriptor,
     ctx: &egui::Context,
     output: EguiOutput,
@@ -181,10 +1335,27 @@ fn render_egui(
     renderer.update_buffers(
         &g.device,
         &g.queue,
-        &mut r.encoder,
+        encoder,
         &paint_jobs,
         &screen_descriptor,
     );
 
-    renderer.render(pass, &paint_jobs, &screen_descriptor);
+    let mut pass = encoder
+        .begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Egui"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+        .forget_lifetime();
+
+    renderer.render(&mut pass, &paint_jobs, &screen_descriptor);
 }
@@ -0,0 +1,135 @@
+use wgpu::include_wgsl;
+
+use super::{ctx::GraphicsCtx, sampler::SamplerDesc, utils::TextureWrapper};
+
+/// Screen space motion of the entities since the previous frame, in uv per frame
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// Velocity target written by the entities pass next to the scene color
+///
+/// Only the motion of the instances is stored, the camera one is not. Other pixels are zero
+pub struct MotionVectors {
+    pub velocity: TextureWrapper,
+    bind_group: wgpu::BindGroup,
+    overlay_pipeline: wgpu::RenderPipeline,
+}
+
+impl MotionVectors {
+    /// `size` is the render size of the scene
+    pub fn new(ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        let velocity = new_velocity(ctx, size);
+        let bind_group = overlay_bind_group(ctx, &velocity);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&overlay_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("motion.wgsl"));
+
+        let overlay_pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Motion vectors overlay"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            velocity,
+            bind_group,
+            overlay_pipeline,
+        }
+    }
+
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, size: (u32, u32)) {
+        self.velocity = new_velocity(ctx, size);
+        self.bind_group = overlay_bind_group(ctx, &self.velocity);
+    }
+
+    /// Tints the moving pixels of the tonemapped frame by their direction, for the editor. `target`
+    /// is at the render size, see [`super::post::upscale::Upscale::output`]
+    pub fn render_overlay(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion vectors overlay"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.overlay_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn new_velocity(ctx: &GraphicsCtx, size: (u32, u32)) -> TextureWrapper {
+    TextureWrapper::new_render_target(
+        "Motion vectors",
+        ctx,
+        size,
+        VELOCITY_FORMAT,
+        SamplerDesc::NEAREST,
+        wgpu::TextureUsages::empty(),
+    )
+}
+
+fn overlay_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("Motion Vectors Overlay Bind Group Layout"),
+        })
+}
+
+fn overlay_bind_group(ctx: &GraphicsCtx, velocity: &TextureWrapper) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &overlay_bind_group_layout(ctx),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&velocity.view),
+        }],
+        label: Some("Motion Vectors Overlay Bind Group"),
+    })
+}
@@ -0,0 +1,21 @@
+@group(0) @binding(0)
+var t_velocity: texture_2d<f32>;
+
+// Velocity giving the full color, in uv per frame
+const FULL_SCALE: f32 = 0.02;
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4f {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+}
+
+// The direction is the hue, still pixels are left untouched
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4f) -> @location(0) vec4f {
+    let velocity = textureLoad(t_velocity, vec2u(frag_coord.xy), 0).xy / FULL_SCALE;
+    let strength = saturate(length(velocity));
+    let direction = velocity / max(length(velocity), 1e-6);
+    return vec4f(direction * 0.5 + 0.5, 0.5, strength);
+}
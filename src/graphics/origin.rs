@@ -0,0 +1,47 @@
+use nalgebra::{Point3, Vector3};
+
+use super::settings::OriginSettings;
+
+/// Floating origin, the world is moved back around the camera once it strays too far
+///
+/// Positions on the cpu and the gpu are relative to the origin, kept small so f32 stays precise.
+/// The absolute position of the origin is tracked in f64
+#[derive(Debug, Clone, Default)]
+pub struct WorldOrigin {
+    offset: Vector3<f64>,
+}
+
+impl WorldOrigin {
+    /// Absolute position of the origin
+    pub fn offset(&self) -> Vector3<f64> {
+        self.offset
+    }
+
+    /// Horizontal shift to apply to the world, in whole chunks, once the camera passed the
+    /// threshold
+    pub fn rebase_shift(
+        &self,
+        settings: &OriginSettings,
+        camera: &Point3<f32>,
+    ) -> Option<Vector3<f32>> {
+        if !settings.enabled || camera.xz().coords.norm() < settings.threshold {
+            return None;
+        }
+        let chunk = settings.chunk_size.max(1.0);
+        let snap = |v: f32| (v / chunk).round() * chunk;
+        Some(Vector3::new(snap(camera.x), 0.0, snap(camera.z)))
+    }
+
+    /// Moves the origin by `shift`, every relative position must be moved back by it
+    pub fn shift(&mut self, shift: Vector3<f32>) {
+        self.offset += shift.cast::<f64>();
+    }
+
+    pub fn to_absolute(&self, position: &Point3<f32>) -> Point3<f64> {
+        position.cast::<f64>() + self.offset
+    }
+
+    pub fn to_relative(&self, position: &Point3<f64>) -> Point3<f32> {
+        (position - self.offset).cast::<f32>()
+    }
+}
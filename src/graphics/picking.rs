@@ -0,0 +1,214 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use nalgebra::{Matrix4, Point3};
+
+use super::{camera::CameraUniform, ctx::GraphicsCtx, utils::TextureWrapper};
+
+/// Copies are done a row at a time, rows must be aligned to this
+const ROW_BYTES: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+/// Readback buffers in flight at most, a new pick can be recorded every frame while the
+/// previous ones are mapped
+const RING_LEN: usize = 3;
+
+/// World position under a pixel, completed some frames after its request
+#[derive(Debug, Clone, Copy)]
+pub struct Pick {
+    pub pixel: (u32, u32),
+    /// `None` when nothing was drawn at the pixel
+    pub position: Option<Point3<f32>>,
+    /// Frame the depth was copied in, see [`DepthPicker::frame`]
+    pub requested_frame: u64,
+    /// Frame the readback was mapped in
+    pub completed_frame: u64,
+}
+
+struct InFlight {
+    pixel: (u32, u32),
+    inv_view_proj: Matrix4<f32>,
+    frame: u64,
+    mapping: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+struct Slot {
+    readback: wgpu::Buffer,
+    in_flight: Option<InFlight>,
+}
+
+/// Reads back the scene depth under a pixel and unprojects it
+///
+/// Copies go to a ring of readback buffers mapped without waiting, the frame never blocks on a
+/// pick. Results arrive a few frames after the request as [`Pick`] events, requests made while
+/// every buffer is in flight wait for one to free up
+pub struct DepthPicker {
+    ring: Vec<Slot>,
+    pending: Option<(u32, u32)>,
+    completed: VecDeque<Pick>,
+    frame: u64,
+}
+
+impl DepthPicker {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let ring = (0..RING_LEN)
+            .map(|i| Slot {
+                readback: ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Depth pick readback {i}")),
+                    size: ROW_BYTES as u64,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                in_flight: None,
+            })
+            .collect();
+        Self {
+            ring,
+            pending: None,
+            completed: VecDeque::new(),
+            frame: 0,
+        }
+    }
+
+    /// Picks at the pixel in physical window coordinates, replaces the request not recorded yet
+    pub fn request(&mut self, pixel: (u32, u32)) {
+        self.pending = Some(pixel);
+    }
+
+    /// Frames submitted since startup, counted by [`Self::poll`]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Picks in flight, waiting for their readback
+    pub fn in_flight(&self) -> usize {
+        self.ring
+            .iter()
+            .filter(|slot| slot.in_flight.is_some())
+            .count()
+    }
+
+    /// Completed picks, oldest request first
+    pub fn take_events(&mut self) -> impl Iterator<Item = Pick> + '_ {
+        self.completed.drain(..)
+    }
+
+    /// Records the copy of the requested depth texel, after the scene passes
+    pub fn record(
+        &mut self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        depth: &TextureWrapper,
+        camera: &CameraUniform,
+    ) {
+        let Some(pixel) = self.pending else {
+            return;
+        };
+        let Some(slot) = self.ring.iter_mut().find(|slot| slot.in_flight.is_none()) else {
+            return;
+        };
+        self.pending = None;
+        let (width, height) = ctx.viewport_size;
+        if pixel.0 >= width || pixel.1 >= height {
+            return;
+        }
+        // The depth is drawn at the render size, the pick is still unprojected in window pixels
+        let (depth_width, depth_height) = depth.size();
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &depth.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pixel.0 * depth_width / width,
+                    y: pixel.1 * depth_height / height,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::DepthOnly,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &slot.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(ROW_BYTES),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        slot.in_flight = Some(InFlight {
+            pixel,
+            inv_view_proj: camera.inv_view_proj(),
+            frame: self.frame,
+            mapping: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        });
+    }
+
+    /// Maps the readbacks whose copy was submitted, then reads the mapped ones. Called once per
+    /// frame before recording, it counts the frames
+    pub fn poll(&mut self, ctx: &GraphicsCtx) {
+        self.frame += 1;
+        if self.in_flight() == 0 {
+            return;
+        }
+        ctx.device.poll(wgpu::Maintain::Poll);
+
+        let (width, height) = ctx.viewport_size;
+        let first = self.completed.len();
+        for slot in &mut self.ring {
+            let Some(in_flight) = &mut slot.in_flight else {
+                continue;
+            };
+            if !in_flight.mapping {
+                in_flight.mapping = true;
+                let mapped = in_flight.mapped.clone();
+                slot.readback.slice(..).map_async(
+                    wgpu::MapMode::Read,
+                    move |result| match result {
+                        Ok(()) => mapped.store(true, Ordering::Release),
+                        Err(e) => eprintln!("Failed to read back the picked depth: {e}"),
+                    },
+                );
+                continue;
+            }
+            if !in_flight.mapped.load(Ordering::Acquire) {
+                continue;
+            }
+
+            let depth = {
+                let bytes = slot.readback.slice(..).get_mapped_range();
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            };
+            slot.readback.unmap();
+
+            let (x, y) = in_flight.pixel;
+            let position = (depth < 1.0).then(|| {
+                let ndc = Point3::new(
+                    (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+                    1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+                    depth,
+                );
+                in_flight.inv_view_proj.transform_point(&ndc)
+            });
+            self.completed.push_back(Pick {
+                pixel: in_flight.pixel,
+                position,
+                requested_frame: in_flight.frame,
+                completed_frame: self.frame,
+            });
+            slot.in_flight = None;
+        }
+        // Slots free up out of ring order
+        self.completed.make_contiguous()[first..].sort_by_key(|pick| pick.requested_frame);
+    }
+}
@@ -0,0 +1,56 @@
+use super::{
+    camera::CameraUniform, ctx::GraphicsCtx, depth::SceneDepth, light::LightsUniform,
+    utils::TextureWrapper,
+};
+
+/// Points of the frame where plugins can record their passes, in submission order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStage {
+    /// Hdr scene color and depth, after the scene passes and before the screen space effects
+    Scene,
+    /// Hdr scene color after fog, lens flares, compute effects and debug lines, before tonemapping
+    PostEffects,
+    /// Surface texture after tonemapping and upscaling, under the editor ui
+    Overlay,
+}
+
+/// What a plugin can draw into at a stage
+pub struct StageTargets<'a> {
+    pub color: &'a wgpu::TextureView,
+    pub color_format: wgpu::TextureFormat,
+    /// Scene depth, not available on the overlay
+    pub depth: Option<&'a TextureWrapper>,
+    /// Sampled copy of the depth, can be bound while `depth` is attached
+    pub scene_depth: Option<&'a SceneDepth>,
+    pub camera: &'a CameraUniform,
+    pub lights: &'a LightsUniform,
+}
+
+/// Custom passes added to [`super::GlobalRenderer`] from outside of the renderer
+///
+/// Every hook defaults to doing nothing. Recorded on the update thread, also when the
+/// [`super::render_thread::RenderThread`] runs
+pub trait RenderPlugin: Send {
+    fn name(&self) -> &str;
+
+    /// Called when the plugin is added and again when another project is opened, resources
+    /// loaded from the assets should be created here
+    fn setup(&mut self, _ctx: &GraphicsCtx) {}
+
+    /// Called after the viewport or the render size changed, screen sized targets should be
+    /// recreated at `render_size`, the one of the scene and post effect stages
+    fn resize(&mut self, _ctx: &GraphicsCtx, _render_size: (u32, u32)) {}
+
+    /// Called every frame before anything is recorded, buffers should be written here
+    fn prepare(&mut self, _ctx: &GraphicsCtx) {}
+
+    /// Called at every stage of the frame, passes are recorded into the frame encoder
+    fn render(
+        &mut self,
+        _ctx: &GraphicsCtx,
+        _stage: RenderStage,
+        _encoder: &mut wgpu::CommandEncoder,
+        _targets: &StageTargets,
+    ) {
+    }
+}
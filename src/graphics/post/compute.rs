@@ -0,0 +1,279 @@
+use crate::graphics::{
+    buffer::{padded_uniform_bytes, CommonBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+    utils::TextureWrapper,
+};
+
+use super::HDR_FORMAT;
+
+/// Must match the `@workgroup_size` of the effects
+pub const WORKGROUP_SIZE: u32 = 8;
+
+/// Declarations every effect source is prefixed with
+const PRELUDE: &str = "
+/// Scene color as it was before the effect
+@group(0) @binding(0)
+var scene_input: texture_2d<f32>;
+@group(0) @binding(1)
+var scene_depth: texture_depth_2d;
+/// Pixels that are not written keep their color
+@group(0) @binding(2)
+var scene_output: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3)
+var<uniform> params: Params;
+";
+
+/// User compute pass over the hdr scene color, run before tonemapping
+///
+/// The source must define `struct Params` and an `@compute @workgroup_size(8, 8) fn main`
+/// entry invoked once per pixel, see [`PRELUDE`] for the bound resources
+pub struct ComputeEffectDesc<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+    /// Initial value of `Params`, laid out as in wgsl
+    pub params: &'a [u8],
+}
+
+pub struct ComputeEffect {
+    pub name: String,
+    pub enabled: bool,
+    /// Kept to build the effect again when the renderer is recreated
+    source: String,
+    /// Last written value of `Params`
+    param_bytes: Vec<u8>,
+    params: UniformBuffer<u8>,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ComputeEffect {
+    /// Must have the size given at creation
+    pub fn write_params(&mut self, ctx: &GraphicsCtx, params: &[u8]) {
+        self.params.write_array(ctx, &padded_uniform_bytes(params));
+        self.param_bytes = params.to_vec();
+    }
+
+    /// Description the effect can be added again from, with its current params
+    pub fn desc(&self) -> ComputeEffectDesc<'_> {
+        ComputeEffectDesc {
+            name: &self.name,
+            source: &self.source,
+            params: &self.param_bytes,
+        }
+    }
+}
+
+/// Compute effects in the order they were added
+pub struct ComputeEffects {
+    pub effects: Vec<ComputeEffect>,
+    /// Copy of the scene color read by the running effect
+    input: wgpu::Texture,
+    input_view: wgpu::TextureView,
+}
+
+impl ComputeEffects {
+    /// `size` is the render size of the scene
+    pub fn new(ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        let (input, input_view) = new_input(ctx, size);
+        Self {
+            effects: Vec::new(),
+            input,
+            input_view,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        ctx: &GraphicsCtx,
+        desc: ComputeEffectDesc,
+        scene_color: &TextureWrapper,
+        depth: &TextureWrapper,
+    ) -> usize {
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(desc.name),
+                source: wgpu::ShaderSource::Wgsl(format!("{PRELUDE}\n{}", desc.source).into()),
+            });
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&compute_effect_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let pipeline = ctx
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(desc.name),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+        let params = UniformBuffer::new_array(
+            &format!("Compute effect params: {}", desc.name),
+            ctx,
+            padded_uniform_bytes(desc.params),
+        );
+        let bind_group =
+            compute_effect_bind_group(ctx, &self.input_view, depth, scene_color, &params);
+
+        self.effects.push(ComputeEffect {
+            name: desc.name.to_string(),
+            enabled: true,
+            source: desc.source.to_string(),
+            param_bytes: desc.params.to_vec(),
+            params,
+            pipeline,
+            bind_group,
+        });
+        self.effects.len() - 1
+    }
+
+    pub fn update_viewport_size(
+        &mut self,
+        ctx: &GraphicsCtx,
+        scene_color: &TextureWrapper,
+        depth: &TextureWrapper,
+    ) {
+        (self.input, self.input_view) = new_input(ctx, scene_color.size());
+        for effect in &mut self.effects {
+            effect.bind_group = compute_effect_bind_group(
+                ctx,
+                &self.input_view,
+                depth,
+                scene_color,
+                &effect.params,
+            );
+        }
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, scene_color: &TextureWrapper) {
+        let (width, height) = scene_color.size();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        for effect in self.effects.iter().filter(|effect| effect.enabled) {
+            encoder.copy_texture_to_texture(
+                scene_color.texture.as_image_copy(),
+                self.input.as_image_copy(),
+                size,
+            );
+
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(&effect.name),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&effect.pipeline);
+            pass.set_bind_group(0, &effect.bind_group, &[]);
+            pass.dispatch_workgroups(
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+}
+
+fn new_input(ctx: &GraphicsCtx, (width, height): (u32, u32)) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Compute effects input"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+fn compute_effect_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: HDR_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Compute effect Bind Group Layout"),
+        })
+}
+
+fn compute_effect_bind_group(
+    ctx: &GraphicsCtx,
+    input: &wgpu::TextureView,
+    depth: &TextureWrapper,
+    scene_color: &TextureWrapper,
+    params: &UniformBuffer<u8>,
+) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &compute_effect_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&depth.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&scene_color.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: params.binding(),
+            },
+        ],
+        label: Some("Compute effect Bind Group"),
+    })
+}
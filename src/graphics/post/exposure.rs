@@ -0,0 +1,242 @@
+use std::{cell::Cell, time::Instant};
+
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, StorageBuffer, UniformBuffer, WriteBuffer},
+    ctx::GraphicsCtx,
+    settings::AutoExposureSettings,
+    utils::TextureWrapper,
+};
+
+const HISTOGRAM_BINS: usize = 256;
+/// Must match `KEY` in the shader
+const KEY: f32 = 0.18;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    pixel_count: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ExposureState {
+    /// Adapted average luminance of the scene
+    luminance: f32,
+    /// Multiplier applied on top of `PostSettings::exposure`
+    exposure: f32,
+}
+
+impl ExposureState {
+    const NEUTRAL: Self = Self {
+        luminance: KEY,
+        exposure: 1.0,
+    };
+}
+
+/// Eye adaptation driven by a luminance histogram of the hdr scene color
+///
+/// The adapted state stays on the gpu, the tonemapping pass reads its exposure directly
+pub struct AutoExposure {
+    settings: AutoExposureSettings,
+    last_update: Cell<Instant>,
+    /// Of the scene color, drawn at the render size
+    size: (u32, u32),
+
+    params: UniformBuffer<ExposureParams>,
+    histogram: StorageBuffer<u32>,
+    pub state: StorageBuffer<ExposureState>,
+
+    bind_group: wgpu::BindGroup,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+}
+
+impl AutoExposure {
+    pub fn new(
+        ctx: &GraphicsCtx,
+        settings: &AutoExposureSettings,
+        scene_color: &TextureWrapper,
+    ) -> Self {
+        let size = scene_color.size();
+        let params = UniformBuffer::new(
+            "Exposure params",
+            ctx,
+            &exposure_params(size, settings, 0.0),
+        );
+        let histogram =
+            StorageBuffer::new_array("Luminance histogram", ctx, [0u32; HISTOGRAM_BINS]);
+        let state = StorageBuffer::new("Exposure state", ctx, &ExposureState::NEUTRAL);
+        let bind_group = exposure_bind_group(ctx, &params, scene_color, &histogram, &state);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&exposure_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("exposure.wgsl"));
+
+        let pipeline = |label, entry_point| {
+            ctx.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                })
+        };
+        let histogram_pipeline = pipeline("Luminance histogram", "cs_histogram");
+        let average_pipeline = pipeline("Exposure adaptation", "cs_average");
+
+        Self {
+            settings: *settings,
+            last_update: Cell::new(Instant::now()),
+            size,
+            params,
+            histogram,
+            state,
+            bind_group,
+            histogram_pipeline,
+            average_pipeline,
+        }
+    }
+
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, scene_color: &TextureWrapper) {
+        self.size = scene_color.size();
+        self.bind_group =
+            exposure_bind_group(ctx, &self.params, scene_color, &self.histogram, &self.state);
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &AutoExposureSettings) {
+        if !settings.enabled {
+            self.state.write(ctx, &ExposureState::NEUTRAL);
+        }
+        self.settings = *settings;
+    }
+
+    /// Builds the histogram of the current scene color and adapts the exposure towards it
+    pub fn render(&self, ctx: &GraphicsCtx, encoder: &mut wgpu::CommandEncoder) {
+        let dt = self
+            .last_update
+            .replace(Instant::now())
+            .elapsed()
+            .as_secs_f32();
+
+        if !self.settings.enabled {
+            return;
+        }
+
+        self.params
+            .write(ctx, &exposure_params(self.size, &self.settings, dt));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Auto exposure"),
+            timestamp_writes: None,
+        });
+        pass.set_bind_group(0, &self.bind_group, &[]);
+
+        let (width, height) = self.size;
+        pass.set_pipeline(&self.histogram_pipeline);
+        pass.dispatch_workgroups(width.div_ceil(16), height.div_ceil(16), 1);
+
+        pass.set_pipeline(&self.average_pipeline);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+}
+
+fn exposure_params(
+    (width, height): (u32, u32),
+    settings: &AutoExposureSettings,
+    dt: f32,
+) -> ExposureParams {
+    ExposureParams {
+        min_log_luminance: settings.min_ev,
+        log_luminance_range: (settings.max_ev - settings.min_ev).max(0.01),
+        adaptation: 1.0 - (-dt * settings.speed).exp(),
+        pixel_count: width * height,
+    }
+}
+
+fn exposure_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let storage = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                storage(2),
+                storage(3),
+            ],
+            label: Some("Auto Exposure Bind Group Layout"),
+        })
+}
+
+fn exposure_bind_group(
+    ctx: &GraphicsCtx,
+    params: &UniformBuffer<ExposureParams>,
+    scene_color: &TextureWrapper,
+    histogram: &StorageBuffer<u32>,
+    state: &StorageBuffer<ExposureState>,
+) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &exposure_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&scene_color.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: histogram.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: state.binding(),
+            },
+        ],
+        label: Some("Auto Exposure Bind Group"),
+    })
+}
@@ -0,0 +1,82 @@
+struct ExposureParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // 1 - exp(-dt * speed), computed on the cpu every frame
+    adaptation: f32,
+    pixel_count: u32,
+}
+
+struct ExposureState {
+    luminance: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: ExposureParams;
+@group(0) @binding(1)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage, read_write> histogram: array<atomic<u32>, 256>;
+@group(0) @binding(3)
+var<storage, read_write> state: ExposureState;
+
+const BINS: u32 = 256u;
+// Middle grey the average scene luminance is exposed to
+const KEY: f32 = 0.18;
+
+var<workgroup> local_bins: array<atomic<u32>, 256>;
+var<workgroup> weighted_bins: array<f32, 256>;
+
+// Bin 0 is reserved for black pixels, which are ignored by the average
+fn luminance_bin(color: vec3f) -> u32 {
+    let luminance = dot(color, vec3f(0.2126, 0.7152, 0.0722));
+    if luminance < 0.0001 {
+        return 0u;
+    }
+    let log_luminance = saturate((log2(luminance) - params.min_log_luminance) / params.log_luminance_range);
+    return u32(log_luminance * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_histogram(
+    @builtin(global_invocation_id) id: vec3u,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_bins[local_index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(t_scene);
+    if all(id.xy < size) {
+        let color = textureLoad(t_scene, id.xy, 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[local_index], atomicLoad(&local_bins[local_index]));
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn cs_average(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicLoad(&histogram[local_index]);
+    weighted_bins[local_index] = f32(count) * f32(local_index);
+    atomicStore(&histogram[local_index], 0u);
+    workgroupBarrier();
+
+    for (var stride = BINS / 2u; stride > 0u; stride >>= 1u) {
+        if local_index < stride {
+            weighted_bins[local_index] += weighted_bins[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if local_index == 0u {
+        // `count` holds the black pixels on the first invocation
+        let lit_pixels = max(f32(params.pixel_count) - f32(count), 1.0);
+        let average_bin = max(weighted_bins[0] / lit_pixels - 1.0, 0.0);
+        let log_luminance = average_bin / 254.0 * params.log_luminance_range + params.min_log_luminance;
+
+        let luminance = mix(state.luminance, exp2(log_luminance), params.adaptation);
+        state.luminance = luminance;
+        state.exposure = KEY / max(luminance, 0.0001);
+    }
+}
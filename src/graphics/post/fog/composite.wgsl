@@ -0,0 +1,52 @@
+struct FogParams {
+    albedo: vec3f,
+    density: f32,
+    anisotropy: f32,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> inv_view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> inv_proj: mat4x4f;
+@group(0) @binding(2)
+var<uniform> viewport_size: vec2<u32>;
+
+@group(1) @binding(0)
+var<uniform> fog: FogParams;
+@group(1) @binding(1)
+var t_integrated: texture_3d<f32>;
+@group(1) @binding(2)
+var s_integrated: sampler;
+@group(1) @binding(3)
+var t_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Blended as `scene * transmittance + scattering`
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let depth = textureLoad(t_depth, vec2u(in.clip_position.xy), 0);
+    let ndc = vec2(in.uv.x, 1. - in.uv.y) * 2.0 - 1.0;
+    let view_pos = inv_proj * vec4f(ndc, depth, 1.0);
+    let view_depth = -view_pos.z / view_pos.w;
+
+    let slice = log(max(view_depth, fog.near) / fog.near) / log(fog.far / fog.near);
+    let fog_value = textureSampleLevel(t_integrated, s_integrated, vec3f(in.uv, saturate(slice)), 0.0);
+
+    return fog_value;
+}
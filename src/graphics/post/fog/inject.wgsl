@@ -0,0 +1,89 @@
+struct FogParams {
+    albedo: vec3f,
+    density: f32,
+    anisotropy: f32,
+    near: f32,
+    far: f32,
+}
+
+struct Light {
+    position: vec3f,
+    intensity: f32,
+    direction: vec3f,
+    cutoff: f32,
+    color: vec3f,
+    light_type: u32,
+    flags: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> inv_view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> inv_proj: mat4x4f;
+@group(0) @binding(2)
+var<uniform> viewport_size: vec2<u32>;
+
+@group(1) @binding(0)
+var<storage, read> lights: array<Light>;
+@group(1) @binding(1)
+var<uniform> lights_count: u32;
+
+@group(2) @binding(0)
+var<uniform> fog: FogParams;
+@group(2) @binding(1)
+var scattering: texture_storage_3d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+
+// Froxel slices are distributed exponentially between the fog near and far planes
+fn slice_depth(slice: f32, slices: f32) -> f32 {
+    return fog.near * pow(fog.far / fog.near, slice / slices);
+}
+
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(scattering);
+    if any(id >= size) {
+        return;
+    }
+
+    let uv = (vec2f(id.xy) + 0.5) / vec2f(size.xy);
+    let ndc = vec2(uv.x, 1. - uv.y) * 2.0 - 1.0;
+    let view_point = inv_proj * vec4f(ndc, 1.0, 1.0);
+    let view_dir = normalize(view_point.xyz / view_point.w);
+
+    let depth = slice_depth(f32(id.z) + 0.5, f32(size.z));
+    let view_pos = view_dir * (depth / -view_dir.z);
+    let world_pos = (inv_view * vec4f(view_pos, 1.0)).xyz;
+    let ray_dir = normalize((inv_view * vec4f(view_dir, 0.0)).xyz);
+
+    var in_scattering = vec3f(0.0);
+    for (var i: u32 = 0; i < lights_count; i = i + 1) {
+        let light = lights[i];
+        let light_dist = length(world_pos - light.position);
+        let attenuation = 1.0 / (1.0 + 0.09 * light_dist + 0.032 * light_dist * light_dist);
+        let to_point = (world_pos - light.position) / max(light_dist, 0.0001);
+
+        if light.light_type == 1 {
+            let phase = henyey_greenstein(dot(to_point, -ray_dir), fog.anisotropy);
+            in_scattering += light.color * light.intensity * attenuation * phase;
+        } else if light.light_type == 2 {
+            let phase = henyey_greenstein(dot(light.direction, -ray_dir), fog.anisotropy);
+            in_scattering += light.color * light.intensity * phase;
+        } else if light.light_type == 3 {
+            let spot_effect = dot(to_point, light.direction);
+            if spot_effect > light.cutoff {
+                let phase = henyey_greenstein(dot(to_point, -ray_dir), fog.anisotropy);
+                let intensity = smoothstep(light.cutoff, light.cutoff + 0.1, spot_effect);
+                in_scattering += light.color * light.intensity * intensity * attenuation * phase;
+            }
+        }
+    }
+
+    textureStore(scattering, id, vec4f(in_scattering * fog.albedo * fog.density, fog.density));
+}
@@ -0,0 +1,43 @@
+struct FogParams {
+    albedo: vec3f,
+    density: f32,
+    anisotropy: f32,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> fog: FogParams;
+@group(0) @binding(1)
+var scattering: texture_3d<f32>;
+@group(0) @binding(2)
+var integrated: texture_storage_3d<rgba16float, write>;
+
+fn slice_depth(slice: f32, slices: f32) -> f32 {
+    return fog.near * pow(fog.far / fog.near, slice / slices);
+}
+
+// Front to back accumulation of each froxel column, stores (scattering, transmittance)
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(integrated);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    var accum_scattering = vec3f(0.0);
+    var accum_transmittance = 1.0;
+    for (var z: u32 = 0; z < size.z; z = z + 1) {
+        let froxel = textureLoad(scattering, vec3u(id.xy, z), 0);
+        let extinction = max(froxel.a, 0.00001);
+        let thickness = slice_depth(f32(z + 1), f32(size.z)) - slice_depth(f32(z), f32(size.z));
+        let transmittance = exp(-extinction * thickness);
+
+        // Energy conserving integration of the scattering over the slice
+        let slice_scattering = (froxel.rgb - froxel.rgb * transmittance) / extinction;
+        accum_scattering += accum_transmittance * slice_scattering;
+        accum_transmittance *= transmittance;
+
+        textureStore(integrated, vec3u(id.xy, z), vec4f(accum_scattering, accum_transmittance));
+    }
+}
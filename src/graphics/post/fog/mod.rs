@@ -0,0 +1,405 @@
+use wgpu::include_wgsl;
+
+use crate::{
+    constants,
+    graphics::{
+        buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+        camera::{inv_view_proj_bind_group_layout, CameraUniform},
+        ctx::GraphicsCtx,
+        light::{lights_buffer_bind_group_layout, LightsUniform},
+        sampler::SamplerDesc,
+        settings::FogSettings,
+        utils::TextureWrapper,
+    },
+};
+
+use super::HDR_FORMAT;
+
+/// Resolution of the view aligned froxel volume, slices are distributed exponentially
+pub const FROXELS: (u32, u32, u32) = (64, 36, 64);
+const FROXEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogParams {
+    albedo: [f32; 3],
+    density: f32,
+    anisotropy: f32,
+    near: f32,
+    far: f32,
+    _padding: f32,
+}
+
+impl From<&FogSettings> for FogParams {
+    fn from(settings: &FogSettings) -> Self {
+        Self {
+            albedo: settings.albedo.into(),
+            density: settings.density,
+            anisotropy: settings.anisotropy.clamp(-0.99, 0.99),
+            near: constants::MODEL_ZNEAR,
+            far: settings.max_distance.max(constants::MODEL_ZNEAR * 2.0),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Froxel based volumetric fog composited over the hdr scene color
+///
+/// Light scattering is injected per froxel from the scene lights, integrated front to back
+/// along each froxel column and finally applied to every pixel using the scene depth
+pub struct VolumetricFog {
+    enabled: bool,
+    params: UniformBuffer<FogParams>,
+    integrated: TextureWrapper,
+
+    inject_bind_group: wgpu::BindGroup,
+    integrate_bind_group: wgpu::BindGroup,
+    composite_bind_group: wgpu::BindGroup,
+
+    inject_pipeline: wgpu::ComputePipeline,
+    integrate_pipeline: wgpu::ComputePipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+}
+
+impl VolumetricFog {
+    pub fn new(ctx: &GraphicsCtx, settings: &FogSettings, depth: &TextureWrapper) -> Self {
+        let params = UniformBuffer::new("Fog params", ctx, &FogParams::from(settings));
+        let scattering = new_froxel_volume("Fog scattering", ctx);
+        let integrated = new_froxel_volume("Fog integrated", ctx);
+
+        let inject_layout = inject_bind_group_layout(ctx);
+        let inject_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &inject_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scattering.view),
+                },
+            ],
+            label: Some("Fog Inject Bind Group"),
+        });
+
+        let integrate_layout = integrate_bind_group_layout(ctx);
+        let integrate_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &integrate_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scattering.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&integrated.view),
+                },
+            ],
+            label: Some("Fog Integrate Bind Group"),
+        });
+
+        let composite_bind_group = composite_bind_group(ctx, &params, &integrated, depth);
+
+        let inject_pipeline = compute_pipeline(
+            ctx,
+            "Fog Inject",
+            &[
+                &inv_view_proj_bind_group_layout(ctx),
+                &lights_buffer_bind_group_layout(ctx),
+                &inject_layout,
+            ],
+            ctx.device
+                .create_shader_module(include_wgsl!("inject.wgsl")),
+        );
+        let integrate_pipeline = compute_pipeline(
+            ctx,
+            "Fog Integrate",
+            &[&integrate_layout],
+            ctx.device
+                .create_shader_module(include_wgsl!("integrate.wgsl")),
+        );
+
+        let composite_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &inv_view_proj_bind_group_layout(ctx),
+                    &composite_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("composite.wgsl"));
+
+        let composite_pipeline =
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Fog Composite"),
+                    layout: Some(&composite_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fs_main"),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: HDR_FORMAT,
+                            // scene * transmittance + scattering, scene alpha is kept
+                            blend: Some(wgpu::BlendState {
+                                color: wgpu::BlendComponent {
+                                    src_factor: wgpu::BlendFactor::One,
+                                    dst_factor: wgpu::BlendFactor::SrcAlpha,
+                                    operation: wgpu::BlendOperation::Add,
+                                },
+                                alpha: wgpu::BlendComponent {
+                                    src_factor: wgpu::BlendFactor::Zero,
+                                    dst_factor: wgpu::BlendFactor::One,
+                                    operation: wgpu::BlendOperation::Add,
+                                },
+                            }),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    multiview: None,
+                    cache: None,
+                });
+
+        Self {
+            enabled: settings.enabled,
+            params,
+            integrated,
+            inject_bind_group,
+            integrate_bind_group,
+            composite_bind_group,
+            inject_pipeline,
+            integrate_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    /// The composite pass reads the depth texture, which is recreated with the viewport
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, depth: &TextureWrapper) {
+        self.composite_bind_group =
+            composite_bind_group(ctx, &self.params, &self.integrated, depth);
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &FogSettings) {
+        self.enabled = settings.enabled;
+        self.params.write(ctx, &FogParams::from(settings));
+    }
+
+    /// Applies the fog over `target`, the scene color, must run after the scene pass
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera: &CameraUniform,
+        lights: &LightsUniform,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Fog Froxels"),
+                timestamp_writes: None,
+            });
+            let (width, height, depth) = FROXELS;
+
+            pass.set_pipeline(&self.inject_pipeline);
+            pass.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
+            pass.set_bind_group(1, &lights.bind_group, &[]);
+            pass.set_bind_group(2, &self.inject_bind_group, &[]);
+            pass.dispatch_workgroups(width.div_ceil(4), height.div_ceil(4), depth.div_ceil(4));
+
+            pass.set_pipeline(&self.integrate_pipeline);
+            pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+            pass.dispatch_workgroups(width.div_ceil(8), height.div_ceil(8), 1);
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Fog Composite"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.composite_pipeline);
+        pass.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
+        pass.set_bind_group(1, &self.composite_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn new_froxel_volume(label: &str, ctx: &GraphicsCtx) -> TextureWrapper {
+    TextureWrapper::new_3d(
+        label,
+        ctx,
+        FROXELS,
+        FROXEL_FORMAT,
+        &[],
+        wgpu::TextureUsages::STORAGE_BINDING,
+        SamplerDesc::LINEAR,
+    )
+}
+
+fn compute_pipeline(
+    ctx: &GraphicsCtx,
+    label: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shader: wgpu::ShaderModule,
+) -> wgpu::ComputePipeline {
+    let layout = ctx
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+    ctx.device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+}
+
+fn params_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn froxel_storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: FROXEL_FORMAT,
+            view_dimension: wgpu::TextureViewDimension::D3,
+        },
+        count: None,
+    }
+}
+
+fn inject_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(0, wgpu::ShaderStages::COMPUTE),
+                froxel_storage_entry(1),
+            ],
+            label: Some("Fog Inject Bind Group Layout"),
+        })
+}
+
+fn integrate_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(0, wgpu::ShaderStages::COMPUTE),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                froxel_storage_entry(2),
+            ],
+            label: Some("Fog Integrate Bind Group Layout"),
+        })
+}
+
+fn composite_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let [integrated, integrated_sampler] = TextureWrapper::layout_entries(
+        1,
+        wgpu::ShaderStages::FRAGMENT,
+        wgpu::TextureViewDimension::D3,
+    );
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(0, wgpu::ShaderStages::FRAGMENT),
+                integrated,
+                integrated_sampler,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Fog Composite Bind Group Layout"),
+        })
+}
+
+fn composite_bind_group(
+    ctx: &GraphicsCtx,
+    params: &UniformBuffer<FogParams>,
+    integrated: &TextureWrapper,
+    depth: &TextureWrapper,
+) -> wgpu::BindGroup {
+    let [integrated, integrated_sampler] = integrated.bind_group_entries(1);
+
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &composite_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            },
+            integrated,
+            integrated_sampler,
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&depth.view),
+            },
+        ],
+        label: Some("Fog Composite Bind Group"),
+    })
+}
@@ -0,0 +1,181 @@
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    camera::{view_proj_bind_group_layout, CameraUniform},
+    ctx::GraphicsCtx,
+    light::{lights_buffer_bind_group_layout, LightsUniform},
+    settings::PostSettings,
+    utils::TextureWrapper,
+};
+
+use super::HDR_FORMAT;
+
+/// Ghost sprites drawn per light, must match `GHOSTS` in the shader
+const GHOSTS: u32 = 6;
+
+/// Screen space flares of the lights flagged with `light_flags::LENS_FLARE`
+///
+/// Each light is projected and tested against the scene depth in the vertex shader,
+/// its ghosts are then added on top of the hdr scene color
+pub struct LensFlare {
+    intensity: UniformBuffer<f32>,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl LensFlare {
+    pub fn new(ctx: &GraphicsCtx, settings: &PostSettings, depth: &TextureWrapper) -> Self {
+        let intensity = UniformBuffer::new("Lens flare intensity", ctx, &settings.lens_flare);
+        let bind_group = lens_flare_bind_group(ctx, &intensity, depth);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &view_proj_bind_group_layout(ctx),
+                    &lights_buffer_bind_group_layout(ctx),
+                    &lens_flare_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("lens_flare.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Lens flare"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            intensity,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Occlusion reads the depth texture, which is recreated with the viewport
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, depth: &TextureWrapper) {
+        self.bind_group = lens_flare_bind_group(ctx, &self.intensity, depth);
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &PostSettings) {
+        self.intensity.write(ctx, &settings.lens_flare);
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera: &CameraUniform,
+        lights: &LightsUniform,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lens flare"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
+        pass.set_bind_group(1, &lights.bind_group, &[]);
+        pass.set_bind_group(2, &self.bind_group, &[]);
+        pass.draw(0..GHOSTS * 6, 0..lights.count());
+    }
+}
+
+fn lens_flare_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Lens Flare Bind Group Layout"),
+        })
+}
+
+fn lens_flare_bind_group(
+    ctx: &GraphicsCtx,
+    intensity: &UniformBuffer<f32>,
+    depth: &TextureWrapper,
+) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &lens_flare_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: intensity.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&depth.view),
+            },
+        ],
+        label: Some("Lens Flare Bind Group"),
+    })
+}
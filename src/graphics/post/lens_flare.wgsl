@@ -0,0 +1,136 @@
+struct Light {
+    position: vec3f,
+    intensity: f32,
+    direction: vec3f,
+    cutoff: f32,
+    color: vec3f,
+    light_type: u32,
+    flags: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> proj: mat4x4f;
+
+@group(1) @binding(0)
+var<storage, read> lights: array<Light>;
+@group(1) @binding(1)
+var<uniform> lights_count: u32;
+
+@group(2) @binding(0)
+var<uniform> flare_intensity: f32;
+@group(2) @binding(1)
+var t_depth: texture_depth_2d;
+
+const LENS_FLARE: u32 = 1u;
+const GHOSTS: u32 = 6u;
+const OCCLUSION_TAPS: i32 = 2;
+
+// Position of each ghost along the light to screen center axis, 1 is the light itself
+var<private> ghost_offsets: array<f32, 6> = array<f32, 6>(1.0, 0.6, 0.25, -0.2, -0.5, -0.9);
+var<private> ghost_sizes: array<f32, 6> = array<f32, 6>(0.25, 0.05, 0.03, 0.08, 0.04, 0.12);
+var<private> ghost_tints: array<vec3f, 6> = array<vec3f, 6>(
+    vec3f(1.0, 1.0, 1.0),
+    vec3f(0.4, 0.6, 1.0),
+    vec3f(1.0, 0.7, 0.3),
+    vec3f(0.5, 1.0, 0.6),
+    vec3f(0.8, 0.4, 1.0),
+    vec3f(0.3, 0.5, 1.0),
+);
+var<private> corners: array<vec2f, 6> = array<vec2f, 6>(
+    vec2f(-1.0, -1.0),
+    vec2f(1.0, -1.0),
+    vec2f(1.0, 1.0),
+    vec2f(-1.0, -1.0),
+    vec2f(1.0, 1.0),
+    vec2f(-1.0, 1.0),
+);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) local: vec2f,
+    @location(1) color: vec3f,
+};
+
+fn culled() -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4f(0.0, 0.0, -1.0, 1.0);
+    return out;
+}
+
+// Fraction of the depth taps around the light which are not covered by the scene
+fn visibility(ndc: vec3f) -> f32 {
+    let size = vec2i(textureDimensions(t_depth));
+    let center = vec2i((vec2(ndc.x, -ndc.y) * 0.5 + 0.5) * vec2f(size));
+
+    var visible = 0.0;
+    var taps = 0.0;
+    for (var x = -OCCLUSION_TAPS; x <= OCCLUSION_TAPS; x++) {
+        for (var y = -OCCLUSION_TAPS; y <= OCCLUSION_TAPS; y++) {
+            let coords = center + vec2i(x, y) * 4;
+            taps += 1.0;
+            if any(coords < vec2i(0)) || any(coords >= size) {
+                continue;
+            }
+            let depth = textureLoad(t_depth, coords, 0);
+            visible += select(0.0, 1.0, depth >= ndc.z);
+        }
+    }
+    return visible / taps;
+}
+
+// Every light is an instance made of `GHOSTS` quads
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let light = lights[instance_index];
+    if (light.flags & LENS_FLARE) == 0u || (light.light_type != 1u && light.light_type != 2u) {
+        return culled();
+    }
+
+    // Directional lights are projected as a point at infinity
+    var clip: vec4f;
+    if light.light_type == 2u {
+        clip = proj * view * vec4f(-light.direction, 0.0);
+    } else {
+        clip = proj * view * vec4f(light.position, 1.0);
+    }
+    if clip.w <= 0.0 {
+        return culled();
+    }
+
+    var ndc = clip.xyz / clip.w;
+    ndc.z = min(ndc.z, 1.0);
+    if any(abs(ndc.xy) > vec2f(1.0)) {
+        return culled();
+    }
+
+    let visible = visibility(ndc);
+    if visible <= 0.0 {
+        return culled();
+    }
+
+    let ghost = vertex_index / 6u;
+    let corner = corners[vertex_index % 6u];
+    let size = vec2f(textureDimensions(t_depth));
+    let aspect = vec2f(size.y / size.x, 1.0);
+
+    let center = ndc.xy * ghost_offsets[ghost];
+    // The glow on the light itself doesn't fade out when reaching the screen edges
+    let edge_fade = select(1.0 - smoothstep(0.5, 1.0, length(ndc.xy)), 1.0, ghost == 0u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4f(center + corner * ghost_sizes[ghost] * aspect, 0.0, 1.0);
+    out.local = corner;
+    out.color = light.color * light.intensity * ghost_tints[ghost] * visible * edge_fade * flare_intensity;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let falloff = saturate(1.0 - length(in.local));
+    return vec4f(in.color * falloff * falloff, 0.0);
+}
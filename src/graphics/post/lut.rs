@@ -0,0 +1,139 @@
+use crate::{
+    graphics::{
+        ctx::GraphicsCtx,
+        sampler::SamplerDesc,
+        utils::{f32_to_f16, TextureWrapper},
+    },
+    ASSETS,
+};
+
+pub const LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// Cubic color lookup table, red varies fastest then green then blue
+pub struct Lut {
+    pub size: u32,
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    pub fn identity(size: u32) -> Self {
+        let max = (size - 1) as f32;
+        let data = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, (i / size) % size, i / (size * size));
+                [r as f32 / max, g as f32 / max, b as f32 / max]
+            })
+            .collect();
+        Self { size, data }
+    }
+
+    /// Resolve Adobe .cube luts first, then strip pngs from the textures folder
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(file) = ASSETS.tree().luts.get(name) {
+            Self::from_cube(&file.0)
+        } else if let Some(file) = ASSETS.tree().textures.get(name) {
+            Self::from_strip(&file.image().to_rgba8())
+        } else {
+            Err(format!("No lut named {name}"))
+        }
+    }
+
+    pub fn from_cube(src: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut data = Vec::new();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+                size = Some(
+                    value
+                        .trim()
+                        .parse::<u32>()
+                        .map_err(|e| format!("Invalid LUT_3D_SIZE: {e}"))?,
+                );
+                continue;
+            }
+            if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                // TITLE, DOMAIN_MIN, DOMAIN_MAX... are ignored
+                continue;
+            }
+            let mut values = line.split_whitespace().map(str::parse::<f32>);
+            match (values.next(), values.next(), values.next()) {
+                (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => data.push([r, g, b]),
+                _ => return Err(format!("Invalid lut entry: {line}")),
+            }
+        }
+
+        let size = size.ok_or("Missing LUT_3D_SIZE, only 3d luts are supported")?;
+        if data.len() != (size * size * size) as usize {
+            return Err(format!(
+                "Expected {} lut entries, found {}",
+                size * size * size,
+                data.len()
+            ));
+        }
+        Ok(Self { size, data })
+    }
+
+    /// Horizontal strip of `size` slices of `size * size` pixels, one slice per blue level
+    pub fn from_strip(image: &image::RgbaImage) -> Result<Self, String> {
+        let size = image.height();
+        if image.width() != size * size {
+            return Err(format!(
+                "Lut strip must be {}x{size}, found {}x{size}",
+                size * size,
+                image.width()
+            ));
+        }
+
+        let data = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, (i / size) % size, i / (size * size));
+                let pixel = image.get_pixel(b * size + r, g);
+                [
+                    pixel[0] as f32 / 255.0,
+                    pixel[1] as f32 / 255.0,
+                    pixel[2] as f32 / 255.0,
+                ]
+            })
+            .collect();
+        Ok(Self { size, data })
+    }
+
+    pub fn upload(&self, ctx: &GraphicsCtx) -> TextureWrapper {
+        let texels: Vec<u16> = self
+            .data
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 1.0])
+            .map(f32_to_f16)
+            .collect();
+
+        TextureWrapper::new_3d(
+            "Color grading lut",
+            ctx,
+            (self.size, self.size, self.size),
+            LUT_FORMAT,
+            bytemuck::cast_slice(&texels),
+            wgpu::TextureUsages::empty(),
+            SamplerDesc {
+                anisotropy: 1,
+                ..SamplerDesc::LINEAR
+            },
+        )
+    }
+}
+
+/// Falls back to the identity lut when `name` can't be loaded
+pub fn load_lut_texture(ctx: &GraphicsCtx, name: Option<&str>) -> TextureWrapper {
+    let lut = name
+        .map(|name| {
+            Lut::load(name).unwrap_or_else(|e| {
+                eprintln!("Failed to load lut {name}: {e}");
+                Lut::identity(IDENTITY_LUT_SIZE)
+            })
+        })
+        .unwrap_or_else(|| Lut::identity(IDENTITY_LUT_SIZE));
+    lut.upload(ctx)
+}
@@ -0,0 +1,290 @@
+use exposure::AutoExposure;
+use lut::load_lut_texture;
+use wgpu::include_wgsl;
+
+use super::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    ctx::{DisplayOutput, GraphicsCtx},
+    sampler::SamplerDesc,
+    settings::{AutoExposureSettings, PostSettings, Tonemapper},
+    utils::TextureWrapper,
+};
+
+pub mod compute;
+pub mod exposure;
+#[cfg(feature = "post-processing")]
+pub mod fog;
+#[cfg(feature = "post-processing")]
+pub mod lens_flare;
+pub mod lut;
+#[cfg(feature = "post-processing")]
+pub mod outline;
+pub mod upscale;
+
+/// Format of the scene color target, resolved to the surface by the tonemapping pass
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapParams {
+    exposure: f32,
+    tonemapper: u32,
+    lut_strength: f32,
+    /// Dithered levels per channel, 0 outside of the retro mode
+    color_levels: f32,
+    /// Non zero when presenting to an hdr surface, see [`DisplayOutput::Hdr`]
+    hdr_output: u32,
+    /// Scene value the curve tops at, relative to the sdr white
+    peak: f32,
+    /// Surface value of the sdr white, 1 is 80 nits
+    paper_white: f32,
+    _padding: f32,
+}
+
+impl TonemapParams {
+    fn new(settings: &PostSettings, output: DisplayOutput) -> Self {
+        let retro = &settings.retro;
+        let paper_white = settings.paper_white.max(1.0);
+        Self {
+            exposure: settings.exposure,
+            tonemapper: match settings.tonemapper {
+                Tonemapper::None => 0,
+                Tonemapper::Reinhard => 1,
+                Tonemapper::Aces => 2,
+            },
+            lut_strength: settings.lut_strength,
+            color_levels: if retro.enabled && retro.color_levels >= 2 {
+                retro.color_levels as f32
+            } else {
+                0.0
+            },
+            hdr_output: (output == DisplayOutput::Hdr) as u32,
+            peak: (settings.peak_brightness / paper_white).max(1.0),
+            paper_white: paper_white / 80.0,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Screen space passes running after the scene has been rendered into `scene_color`
+pub struct PostStack {
+    pub scene_color: TextureWrapper,
+    lut: TextureWrapper,
+    params: UniformBuffer<TonemapParams>,
+    exposure: AutoExposure,
+
+    tonemap_bind_group: wgpu::BindGroup,
+    tonemap_pipeline: wgpu::RenderPipeline,
+}
+
+impl PostStack {
+    /// `render_size` is the one of the scene targets
+    pub fn new(
+        ctx: &GraphicsCtx,
+        settings: &PostSettings,
+        exposure_settings: &AutoExposureSettings,
+        render_size: (u32, u32),
+    ) -> Self {
+        let scene_color = new_scene_color(ctx, render_size);
+        let lut = load_lut_texture(ctx, settings.lut.as_deref());
+        let params = UniformBuffer::new(
+            "Tonemap params",
+            ctx,
+            &TonemapParams::new(settings, ctx.output),
+        );
+        let exposure = AutoExposure::new(ctx, exposure_settings, &scene_color);
+        let tonemap_bind_group = tonemap_bind_group(ctx, &scene_color, &lut, &params, &exposure);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&tonemap_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("tonemap.wgsl"));
+
+        let tonemap_pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Tonemap"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            scene_color,
+            lut,
+            params,
+            exposure,
+            tonemap_bind_group,
+            tonemap_pipeline,
+        }
+    }
+
+    pub fn update_viewport_size(&mut self, ctx: &GraphicsCtx, render_size: (u32, u32)) {
+        self.scene_color = new_scene_color(ctx, render_size);
+        self.exposure.update_viewport_size(ctx, &self.scene_color);
+        self.recreate_bind_group(ctx);
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, new: &PostSettings, old: &PostSettings) {
+        if new.lut != old.lut {
+            self.lut = load_lut_texture(ctx, new.lut.as_deref());
+            self.recreate_bind_group(ctx);
+        }
+        self.params.write(ctx, &TonemapParams::new(new, ctx.output));
+    }
+
+    pub fn apply_exposure_settings(&mut self, ctx: &GraphicsCtx, settings: &AutoExposureSettings) {
+        self.exposure.apply_settings(ctx, settings);
+    }
+
+    /// Resolves the scene color into `target`, of the same size, see [`upscale::Upscale::output`]
+    pub fn render(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        self.exposure.render(ctx, encoder);
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.tonemap_pipeline);
+        pass.set_bind_group(0, &self.tonemap_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn recreate_bind_group(&mut self, ctx: &GraphicsCtx) {
+        self.tonemap_bind_group = tonemap_bind_group(
+            ctx,
+            &self.scene_color,
+            &self.lut,
+            &self.params,
+            &self.exposure,
+        );
+    }
+}
+
+fn new_scene_color(ctx: &GraphicsCtx, size: (u32, u32)) -> TextureWrapper {
+    TextureWrapper::new_render_target(
+        "Scene color",
+        ctx,
+        size,
+        HDR_FORMAT,
+        SamplerDesc::NEAREST,
+        // Compute effects copy it and write it back, see [`compute::ComputeEffects`]
+        wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::STORAGE_BINDING,
+    )
+}
+
+fn tonemap_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let [scene, scene_sampler] = TextureWrapper::layout_entries(
+        0,
+        wgpu::ShaderStages::FRAGMENT,
+        wgpu::TextureViewDimension::D2,
+    );
+    let [lut, lut_sampler] = TextureWrapper::layout_entries(
+        2,
+        wgpu::ShaderStages::FRAGMENT,
+        wgpu::TextureViewDimension::D3,
+    );
+
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                scene,
+                scene_sampler,
+                lut,
+                lut_sampler,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Tonemap Bind Group Layout"),
+        })
+}
+
+fn tonemap_bind_group(
+    ctx: &GraphicsCtx,
+    scene_color: &TextureWrapper,
+    lut: &TextureWrapper,
+    params: &UniformBuffer<TonemapParams>,
+    exposure: &AutoExposure,
+) -> wgpu::BindGroup {
+    let [scene, scene_sampler] = scene_color.bind_group_entries(0);
+    let [lut, lut_sampler] = lut.bind_group_entries(2);
+
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &tonemap_bind_group_layout(ctx),
+        entries: &[
+            scene,
+            scene_sampler,
+            lut,
+            lut_sampler,
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: params.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: exposure.state.binding(),
+            },
+        ],
+        label: Some("Tonemap Bind Group"),
+    })
+}
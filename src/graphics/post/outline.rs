@@ -0,0 +1,179 @@
+use crate::graphics::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    camera::{inv_view_proj_bind_group_layout, CameraUniform},
+    ctx::GraphicsCtx,
+    depth::{scene_depth_bind_group_layout, SceneDepth},
+    reflect::ShaderReflection,
+    settings::OutlineSettings,
+};
+
+use super::HDR_FORMAT;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineParams {
+    color: [f32; 3],
+    width: f32,
+    depth_threshold: f32,
+    normal_threshold: f32,
+    _padding: [f32; 2],
+}
+
+crate::wgsl_layout!(OutlineParams as "OutlineParams" {
+    color,
+    width,
+    depth_threshold,
+    normal_threshold,
+});
+
+impl From<&OutlineSettings> for OutlineParams {
+    fn from(settings: &OutlineSettings) -> Self {
+        Self {
+            color: settings.color.into(),
+            width: settings.width.max(1.0),
+            depth_threshold: settings.depth_threshold.max(1e-4),
+            normal_threshold: settings.normal_threshold.clamp(1e-4, 2.0),
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Toon lines drawn over the hdr scene color where the depth or the normals change abruptly
+///
+/// Normals are reconstructed from the scene depth, there is no normal target
+pub struct Outline {
+    enabled: bool,
+    params: UniformBuffer<OutlineParams>,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+    pub fn new(ctx: &GraphicsCtx, settings: &OutlineSettings) -> Self {
+        let source = include_str!("outline.wgsl");
+        // Layout and params follow the shader, a mismatch is caught here instead of by wgpu
+        let reflection =
+            ShaderReflection::new(source).unwrap_or_else(|e| panic!("Invalid outline shader: {e}"));
+        reflection
+            .check_layout::<OutlineParams>()
+            .unwrap_or_else(|e| panic!("{e}"));
+        let layout = reflection
+            .bind_group_layout(ctx, "Outline Bind Group Layout", 2)
+            .unwrap_or_else(|e| panic!("{e}"));
+
+        let params = UniformBuffer::new("Outline params", ctx, &OutlineParams::from(settings));
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            }],
+            label: Some("Outline Bind Group"),
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &inv_view_proj_bind_group_layout(ctx),
+                    &scene_depth_bind_group_layout(ctx),
+                    &layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("outline.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Outline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        // Lines are blended by their strength, scene alpha is kept
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::SrcAlpha,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            enabled: settings.enabled,
+            params,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &OutlineSettings) {
+        self.enabled = settings.enabled;
+        self.params.write(ctx, &OutlineParams::from(settings));
+    }
+
+    /// Draws the lines over `target`, the scene color, must run after the scene depth copy
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera: &CameraUniform,
+        scene_depth: &SceneDepth,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
+        pass.set_bind_group(1, &*scene_depth.bind_group, &[]);
+        pass.set_bind_group(2, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,75 @@
+struct OutlineParams {
+    color: vec3f,
+    // In pixels
+    width: f32,
+    // Relative change of the view depth giving a full line
+    depth_threshold: f32,
+    // One minus the cosine between the normals giving a full line
+    normal_threshold: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> inv_view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> inv_proj: mat4x4f;
+@group(0) @binding(2)
+var<uniform> viewport_size: vec2<u32>;
+
+@group(1) @binding(0)
+var t_scene_depth: texture_depth_2d;
+
+@group(2) @binding(0)
+var<uniform> outline: OutlineParams;
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4f {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+}
+
+// View space position under a pixel, clamped to the screen
+fn view_position(pixel: vec2i) -> vec3f {
+    let size = vec2i(viewport_size);
+    let texel = clamp(pixel, vec2i(0), size - 1);
+    let depth = textureLoad(t_scene_depth, texel, 0);
+    let uv = (vec2f(texel) + 0.5) / vec2f(size);
+    let ndc = vec2f(uv.x, 1.0 - uv.y) * 2.0 - 1.0;
+    let view = inv_proj * vec4f(ndc, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+// Reconstructed from the neighbouring depths
+fn view_normal(pixel: vec2i) -> vec3f {
+    let dx = view_position(pixel + vec2i(1, 0)) - view_position(pixel - vec2i(1, 0));
+    let dy = view_position(pixel + vec2i(0, 1)) - view_position(pixel - vec2i(0, 1));
+    return normalize(cross(dy, dx));
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(frag_coord.xy);
+    let width = i32(outline.width);
+    let offsets = array<vec2i, 4>(
+        vec2i(width, 0),
+        vec2i(-width, 0),
+        vec2i(0, width),
+        vec2i(0, -width),
+    );
+
+    let center = view_position(pixel);
+    let normal = view_normal(pixel);
+    var depth_edge = 0.0;
+    var normal_edge = 0.0;
+    for (var i = 0; i < 4; i++) {
+        let neighbour = view_position(pixel + offsets[i]);
+        let gap = abs(neighbour.z - center.z) / max(-center.z, 1e-4);
+        depth_edge = max(depth_edge, gap / outline.depth_threshold);
+        let bend = 1.0 - dot(normal, view_normal(pixel + offsets[i]));
+        normal_edge = max(normal_edge, bend / outline.normal_threshold);
+    }
+
+    // Half the threshold starts the line
+    let strength = smoothstep(0.5, 1.0, max(depth_edge, normal_edge));
+    return vec4f(outline.color, strength);
+}
@@ -0,0 +1,111 @@
+struct TonemapParams {
+    exposure: f32,
+    tonemapper: u32, // 0 = None, 1 = Reinhard, 2 = Aces
+    lut_strength: f32,
+    // Dithered levels per channel, 0 without dithering
+    color_levels: f32,
+    // Non zero on an hdr surface, linear with 1 at 80 nits
+    hdr_output: u32,
+    // Scene value the curve tops at, relative to the sdr white
+    peak: f32,
+    // Surface value of the sdr white
+    paper_white: f32,
+    _padding: f32,
+}
+
+struct ExposureState {
+    luminance: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(1)
+var s_scene: sampler;
+@group(0) @binding(2)
+var t_lut: texture_3d<f32>;
+@group(0) @binding(3)
+var s_lut: sampler;
+@group(0) @binding(4)
+var<uniform> params: TonemapParams;
+// Written by the adaptive exposure pass, 1 when it is disabled
+@group(0) @binding(5)
+var<storage, read> auto_exposure: ExposureState;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+};
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// 4x4 ordered dithering threshold, in [0, 1)
+fn bayer(pixel: vec2u) -> f32 {
+    let matrix = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    return matrix[(pixel.y % 4u) * 4u + pixel.x % 4u] / 16.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    var hdr = textureSample(t_scene, s_scene, in.uv).rgb * params.exposure * auto_exposure.exposure;
+    // On hdr displays the curve tops at the peak brightness instead of the sdr white
+    let peak = select(1.0, params.peak, params.hdr_output != 0u);
+    hdr /= peak;
+
+    var ldr = clamp(hdr, vec3f(0.0), vec3f(1.0));
+    if params.tonemapper == 1 {
+        ldr = hdr / (hdr + vec3f(1.0));
+    } else if params.tonemapper == 2 {
+        ldr = aces(hdr);
+    }
+
+    // Luts are authored in display space
+    let lut_size = f32(textureDimensions(t_lut).x);
+    let uvw = srgb_encode(ldr) * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+    let graded = srgb_decode(textureSampleLevel(t_lut, s_lut, uvw, 0.0).rgb);
+
+    var color = mix(ldr, graded, params.lut_strength);
+    if params.color_levels > 0.0 {
+        // Quantized in display space, like the framebuffers of the time. Drawn at the render
+        // size, the pattern is as coarse as the scene pixels
+        let steps = params.color_levels - 1.0;
+        let display = floor(srgb_encode(color) * steps + bayer(vec2u(in.clip_position.xy))) / steps;
+        color = srgb_decode(saturate(display));
+    }
+    if params.hdr_output != 0u {
+        color *= peak * params.paper_white;
+    }
+    return vec4f(color, 1.0);
+}
+
+// Narkowicz fit of the ACES filmic curve
+fn aces(x: vec3f) -> vec3f {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3f(0.0), vec3f(1.0));
+}
+
+fn srgb_encode(c: vec3f) -> vec3f {
+    return select(1.055 * pow(c, vec3f(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3f(0.0031308));
+}
+
+fn srgb_decode(c: vec3f) -> vec3f {
+    return select(pow((c + 0.055) / 1.055, vec3f(2.4)), c / 12.92, c <= vec3f(0.04045));
+}
@@ -0,0 +1,138 @@
+use wgpu::include_wgsl;
+
+use crate::graphics::{ctx::GraphicsCtx, sampler::SamplerDesc, utils::TextureWrapper};
+
+/// Stretches the frame drawn at the render size over the surface, see
+/// [`crate::graphics::settings::ResolutionSettings`]
+///
+/// The tonemapping and the passes drawn over it go to [`Self::output`], the surface itself when
+/// the scene is drawn at the viewport size and nothing is left to stretch
+pub struct Upscale {
+    /// Tonemapped frame, `None` at the viewport size
+    target: Option<(TextureWrapper, wgpu::BindGroup)>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Upscale {
+    pub fn new(ctx: &GraphicsCtx, render_size: (u32, u32), filter: wgpu::FilterMode) -> Self {
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&upscale_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("upscale.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Upscale"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        let mut upscale = Self {
+            target: None,
+            pipeline,
+        };
+        upscale.resize(ctx, render_size, filter);
+        upscale
+    }
+
+    /// Called when the viewport, the render size or the filter changed
+    pub fn resize(&mut self, ctx: &GraphicsCtx, render_size: (u32, u32), filter: wgpu::FilterMode) {
+        self.target = (render_size != ctx.viewport_size).then(|| {
+            let sampler = match filter {
+                wgpu::FilterMode::Nearest => SamplerDesc::NEAREST,
+                wgpu::FilterMode::Linear => SamplerDesc::LINEAR,
+            };
+            // Same format as the surface, the passes drawn over the tonemapping are unchanged
+            let texture = TextureWrapper::new_render_target(
+                "Upscaled frame",
+                ctx,
+                render_size,
+                ctx.surface_format,
+                sampler,
+                wgpu::TextureUsages::empty(),
+            );
+            let bind_group = upscale_bind_group(ctx, &texture);
+            (texture, bind_group)
+        });
+    }
+
+    /// Where the frame is drawn at the render size, `surface` when it is the viewport size
+    pub fn output<'a>(&'a self, surface: &'a wgpu::TextureView) -> &'a wgpu::TextureView {
+        match &self.target {
+            Some((texture, _)) => &texture.view,
+            None => surface,
+        }
+    }
+
+    /// Draws [`Self::output`] over the whole surface, nothing to do at the viewport size
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, surface: &wgpu::TextureView) {
+        let Some((_, bind_group)) = &self.target else {
+            return;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Upscale"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn upscale_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &TextureWrapper::layout_entries(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureViewDimension::D2,
+            ),
+            label: Some("Upscale Bind Group Layout"),
+        })
+}
+
+fn upscale_bind_group(ctx: &GraphicsCtx, frame: &TextureWrapper) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &upscale_bind_group_layout(ctx),
+        entries: &frame.bind_group_entries(0),
+        label: Some("Upscale Bind Group"),
+    })
+}
@@ -0,0 +1,293 @@
+use std::num::NonZeroU64;
+
+use naga::{
+    proc::Layouter,
+    valid::{Capabilities, ModuleInfo, ValidationFlags, Validator},
+    AddressSpace, ImageClass, ImageDimension, ScalarKind, ShaderStage, StorageAccess,
+    StorageFormat, TypeInner,
+};
+
+use super::ctx::GraphicsCtx;
+
+/// Cpu side of a wgsl struct, implemented with [`crate::wgsl_layout`] and checked against a
+/// shader by [`ShaderReflection::check_layout`]
+pub trait WgslLayout {
+    /// Name of the struct in the shaders
+    const NAME: &'static str;
+
+    /// Byte offset of each field, by the name of its member in the shaders
+    fn offsets() -> Vec<(&'static str, usize)>;
+}
+
+/// Implements [`WgslLayout`] from the fields matching the members of the wgsl struct, a field
+/// named differently in the shader is given as `field: member`
+///
+/// Also asserts at compile time that the struct fills whole 16 byte rows, the size uniforms and
+/// arrays of vec3 or vec4 members are rounded up to
+#[macro_export]
+macro_rules! wgsl_layout {
+    ($ty:ty as $name:literal { $($field:ident $(: $member:ident)?),* $(,)? }) => {
+        const _: () = assert!(
+            std::mem::size_of::<$ty>() % 16 == 0,
+            concat!(stringify!($ty), " is not a multiple of 16 bytes")
+        );
+
+        impl $crate::graphics::reflect::WgslLayout for $ty {
+            const NAME: &'static str = $name;
+
+            fn offsets() -> Vec<(&'static str, usize)> {
+                vec![$((
+                    $crate::wgsl_layout!(@member $field $($member)?),
+                    std::mem::offset_of!($ty, $field),
+                )),*]
+            }
+        }
+    };
+    (@member $field:ident) => {
+        stringify!($field)
+    };
+    (@member $field:ident $member:ident) => {
+        stringify!($member)
+    };
+}
+
+/// Parsed and validated wgsl, describes the bindings its entry points use
+///
+/// Bind group layouts are derived from the shader instead of being written by hand next to it,
+/// and the structs uploaded to it are checked against its types
+pub struct ShaderReflection {
+    module: naga::Module,
+    info: ModuleInfo,
+    layouter: Layouter,
+}
+
+impl ShaderReflection {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|e| e.emit_to_string(source))?;
+        let mut layouter = Layouter::default();
+        layouter
+            .update(module.to_ctx())
+            .map_err(|e| format!("Could not lay out the types: {e}"))?;
+        Ok(Self {
+            module,
+            info,
+            layouter,
+        })
+    }
+
+    /// Bindings of `group`, visible to the stages of the entry points using them
+    ///
+    /// Float textures are filterable, the unfilterable ones need a hand written layout
+    pub fn layout_entries(&self, group: u32) -> Result<Vec<wgpu::BindGroupLayoutEntry>, String> {
+        let mut entries = Vec::new();
+        for (handle, global) in self.module.global_variables.iter() {
+            let Some(binding) = global.binding.as_ref().filter(|b| b.group == group) else {
+                continue;
+            };
+            let name = global.name.as_deref().unwrap_or("unnamed");
+            let visibility = self
+                .module
+                .entry_points
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !self.info.get_entry_point(*i)[handle].is_empty())
+                .fold(wgpu::ShaderStages::NONE, |stages, (_, entry)| {
+                    stages | shader_stage(entry.stage)
+                });
+            if visibility.is_empty() {
+                continue;
+            }
+            let (ty, count) = match self.module.types[global.ty].inner {
+                TypeInner::BindingArray { base, size } => {
+                    let count = match size {
+                        naga::ArraySize::Constant(count) => Some(count),
+                        _ => return Err(format!("{name} is a binding array without a size")),
+                    };
+                    (base, count)
+                }
+                _ => (global.ty, None),
+            };
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty: self
+                    .binding_type(global.space, ty)
+                    .ok_or_else(|| format!("{name} has a type without a binding"))?,
+                count,
+            });
+        }
+        entries.sort_by_key(|entry| entry.binding);
+        Ok(entries)
+    }
+
+    pub fn bind_group_layout(
+        &self,
+        ctx: &GraphicsCtx,
+        label: &str,
+        group: u32,
+    ) -> Result<wgpu::BindGroupLayout, String> {
+        let entries = self.layout_entries(group)?;
+        Ok(ctx
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &entries,
+            }))
+    }
+
+    /// Fails when a member of the wgsl struct [`WgslLayout::NAME`] is missing from `T` or at
+    /// another offset, or when `T` is too small for it
+    ///
+    /// A struct stored in arrays must match the size of `T` exactly, the stride depends on it.
+    /// Uniforms may declare only the head of `T`
+    pub fn check_layout<T: WgslLayout>(&self) -> Result<(), String> {
+        let name = T::NAME;
+        let Some((handle, ty)) = self
+            .module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some(name))
+        else {
+            return Err(format!("No struct {name} in the shader"));
+        };
+        let TypeInner::Struct { members, .. } = &ty.inner else {
+            return Err(format!("{name} is not a struct in the shader"));
+        };
+        let offsets = T::offsets();
+        for member in members {
+            let member_name = member.name.as_deref().unwrap_or("unnamed");
+            let Some((_, offset)) = offsets.iter().find(|(field, _)| *field == member_name) else {
+                return Err(format!("{name}.{member_name} has no field on the cpu"));
+            };
+            if *offset as u32 != member.offset {
+                return Err(format!(
+                    "{name}.{member_name} is at byte {} in the shader and {offset} on the cpu",
+                    member.offset
+                ));
+            }
+        }
+
+        let layout = self.layouter[handle];
+        let size = std::mem::size_of::<T>() as u32;
+        let in_arrays = self
+            .module
+            .types
+            .iter()
+            .any(|(_, ty)| matches!(ty.inner, TypeInner::Array { base, .. } if base == handle));
+        if size < layout.size || (in_arrays && size != layout.size) {
+            return Err(format!(
+                "{name} is {} bytes in the shader and {size} bytes on the cpu",
+                layout.size
+            ));
+        }
+        if layout.alignment.round_up(size) != size {
+            return Err(format!(
+                "{name} is {size} bytes, not a multiple of its alignment"
+            ));
+        }
+        Ok(())
+    }
+
+    fn binding_type(
+        &self,
+        space: AddressSpace,
+        ty: naga::Handle<naga::Type>,
+    ) -> Option<wgpu::BindingType> {
+        Some(match (space, &self.module.types[ty].inner) {
+            (AddressSpace::Uniform, _) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(self.layouter[ty].size as u64),
+            },
+            // Runtime sized, the minimum size is left to the bind groups
+            (AddressSpace::Storage { access }, _) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage {
+                    read_only: !access.contains(StorageAccess::STORE),
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            (AddressSpace::Handle, TypeInner::Sampler { comparison }) => {
+                wgpu::BindingType::Sampler(match comparison {
+                    true => wgpu::SamplerBindingType::Comparison,
+                    false => wgpu::SamplerBindingType::Filtering,
+                })
+            }
+            (
+                AddressSpace::Handle,
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                },
+            ) => {
+                let view_dimension = view_dimension(*dim, *arrayed);
+                match *class {
+                    ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                        access: match (
+                            access.contains(StorageAccess::LOAD),
+                            access.contains(StorageAccess::STORE),
+                        ) {
+                            (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                            (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                            _ => wgpu::StorageTextureAccess::WriteOnly,
+                        },
+                        format: storage_format(format)?,
+                        view_dimension,
+                    },
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+fn shader_stage(stage: ShaderStage) -> wgpu::ShaderStages {
+    match stage {
+        ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+        ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+        ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        _ => wgpu::ShaderStages::NONE,
+    }
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+/// The storage formats the shaders of the engine use
+fn storage_format(format: StorageFormat) -> Option<wgpu::TextureFormat> {
+    Some(match format {
+        StorageFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+        StorageFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        StorageFormat::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
+        StorageFormat::R32Float => wgpu::TextureFormat::R32Float,
+        StorageFormat::R32Uint => wgpu::TextureFormat::R32Uint,
+        StorageFormat::Rg32Float => wgpu::TextureFormat::Rg32Float,
+        _ => return None,
+    })
+}
@@ -0,0 +1,115 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, RwLock},
+    thread::JoinHandle,
+};
+
+use crate::profile_scope;
+
+use super::{ctx::GraphicsCtx, RecordedFrame};
+
+/// Frames handed from the update thread to the render thread
+#[derive(Default)]
+struct FrameSlots {
+    /// Next frame to submit, taken by the render thread
+    front: Option<RecordedFrame>,
+    /// Filled by the update thread, swapped to the front once it is free
+    back: Option<RecordedFrame>,
+    stopping: bool,
+}
+
+/// Submits the frames, acquires the surface textures and presents them from a thread of its own,
+/// the update thread records the frames offscreen with [`super::GlobalRenderer::record_offscreen`]
+/// and goes on with the next one
+///
+/// The frames are fed through two swapped [`RecordedFrame`] slots, the renderer is never locked
+/// by the render thread. A frame is submitted before its slot is released: the buffers written
+/// for the next frame go through the same queue and must not land inside it. Waiting for the
+/// surface texture and presenting it happen after, while the next frame is updated
+///
+/// The graphics context is read locked by the render thread until the frame is presented, the
+/// surface is configured by write locking it
+pub struct RenderThread {
+    slots: Arc<(Mutex<FrameSlots>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RenderThread {
+    pub fn new(graphics: Arc<RwLock<GraphicsCtx>>) -> Self {
+        let slots: Arc<(Mutex<FrameSlots>, Condvar)> = Arc::default();
+        let thread = {
+            let slots = slots.clone();
+            std::thread::Builder::new()
+                .name("Render".to_string())
+                .spawn(move || run(&graphics, &slots))
+                .expect("Failed to spawn the render thread")
+        };
+        Self {
+            slots,
+            thread: Some(thread),
+        }
+    }
+
+    /// Hands the recorded frame to the render thread, returns once it is submitted
+    pub fn submit(&self, frame: RecordedFrame) {
+        profile_scope!("Hand frame");
+        let (slots, changed) = &*self.slots;
+        let mut slots = slots.lock().unwrap();
+        slots.back = Some(frame);
+        let mut slots = changed
+            .wait_while(slots, |slots| slots.front.is_some())
+            .unwrap();
+        let FrameSlots { front, back, .. } = &mut *slots;
+        std::mem::swap(front, back);
+        changed.notify_all();
+        // Taken once submitted, see above
+        drop(
+            changed
+                .wait_while(slots, |slots| slots.front.is_some())
+                .unwrap(),
+        );
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        let (slots, changed) = &*self.slots;
+        slots.lock().unwrap().stopping = true;
+        changed.notify_all();
+        // Records the frame already handed before stopping
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(graphics: &RwLock<GraphicsCtx>, slots: &(Mutex<FrameSlots>, Condvar)) {
+    let (slots, changed) = slots;
+    loop {
+        {
+            let slots = slots.lock().unwrap();
+            let slots = changed
+                .wait_while(slots, |slots| slots.front.is_none() && !slots.stopping)
+                .unwrap();
+            if slots.front.is_none() {
+                return;
+            }
+        }
+
+        let graphics = graphics.read().unwrap();
+        let blit = {
+            // Released once submitted, see above
+            let mut slots = slots.lock().unwrap();
+            let Some(frame) = slots.front.take() else {
+                continue;
+            };
+            frame.submit(&graphics)
+        };
+        changed.notify_all();
+
+        let surface_texture = blit.submit(&graphics);
+        if let Some(surface_texture) = surface_texture {
+            profile_scope!("Present");
+            surface_texture.present();
+        }
+    }
+}
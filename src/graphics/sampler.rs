@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::settings::TextureQuality;
+
+/// Sampler slots available to materials, indexed by `Material::sampler_id`
+///
+/// Atlas entries are always clamped, wrapping would bleed into neighbouring textures
+pub fn material_samplers(quality: &TextureQuality) -> [SamplerDesc; 2] {
+    [SamplerDesc::NEAREST, quality.filtered_sampler()]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub wrap: wgpu::AddressMode,
+    /// Requested anisotropy level, clamped to what the adapter supports
+    pub anisotropy: u16,
+}
+
+impl SamplerDesc {
+    pub const NEAREST: Self = Self {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        wrap: wgpu::AddressMode::ClampToEdge,
+        anisotropy: 1,
+    };
+    pub const LINEAR: Self = Self {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        wrap: wgpu::AddressMode::ClampToEdge,
+        anisotropy: 16,
+    };
+
+    pub fn with_wrap(self, wrap: wgpu::AddressMode) -> Self {
+        Self { wrap, ..self }
+    }
+
+    fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        wgpu::SamplerDescriptor {
+            label: Some("Cached Sampler"),
+            address_mode_u: self.wrap,
+            address_mode_v: self.wrap,
+            address_mode_w: self.wrap,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.min_filter,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            anisotropy_clamp: self.anisotropy,
+            ..Default::default()
+        }
+    }
+}
+
+/// Samplers are shared by every texture using the same [`SamplerDesc`]
+pub struct SamplerCache {
+    samplers: Mutex<HashMap<SamplerDesc, Arc<wgpu::Sampler>>>,
+    max_anisotropy: u16,
+}
+
+impl SamplerCache {
+    pub fn new(adapter: &wgpu::Adapter) -> Self {
+        let anisotropic = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+
+        Self {
+            samplers: Mutex::new(HashMap::new()),
+            max_anisotropy: if anisotropic { 16 } else { 1 },
+        }
+    }
+
+    pub fn get(&self, device: &wgpu::Device, desc: SamplerDesc) -> Arc<wgpu::Sampler> {
+        let desc = self.supported(desc);
+        self.samplers
+            .lock()
+            .unwrap()
+            .entry(desc)
+            .or_insert_with(|| Arc::new(device.create_sampler(&desc.descriptor())))
+            .clone()
+    }
+
+    /// Anisotropy is only valid when every filter is linear
+    fn supported(&self, desc: SamplerDesc) -> SamplerDesc {
+        let anisotropy = if desc.mag_filter == wgpu::FilterMode::Linear
+            && desc.min_filter == wgpu::FilterMode::Linear
+        {
+            desc.anisotropy.clamp(1, self.max_anisotropy)
+        } else {
+            1
+        };
+        SamplerDesc { anisotropy, ..desc }
+    }
+}
@@ -0,0 +1,722 @@
+use nalgebra::{Point3, Vector3};
+
+use super::{color::Color3, entities::draws::CULL_LAYERS, sampler::SamplerDesc};
+
+/// Renderer wide settings, edits are applied by `GlobalRenderer` at the start of the next frame
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RenderSettings {
+    pub texture_quality: TextureQuality,
+    pub streaming: TextureStreamingSettings,
+    pub post: PostSettings,
+    pub environment: EnvironmentSettings,
+    pub shadows: ShadowSettings,
+    pub lights: LightSettings,
+    pub resolution: ResolutionSettings,
+    /// Entities are drawn as lines, ignored when the device lacks `POLYGON_MODE_LINE`
+    pub wireframe: bool,
+    /// Visible instances of each mesh are drawn front to back, rejecting more hidden fragments early
+    pub depth_sort: bool,
+    pub small_object_cull: SmallObjectCull,
+    /// Tints the moving entities by the direction of their motion vectors
+    pub show_motion_vectors: bool,
+    pub minimap: MinimapSettings,
+    pub mirror: MirrorSettings,
+    pub terrain: TerrainSettings,
+    pub origin: OriginSettings,
+}
+
+impl RenderSettings {
+    /// Psx style preset, low resolution with snapped vertices, warped textures and dithering
+    pub fn retro() -> Self {
+        let mut settings = Self::default();
+        settings.post.retro = RetroSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        settings.post.tonemapper = Tonemapper::None;
+        settings.shadows.filter = ShadowFilter::Hard;
+        settings.resolution = ResolutionSettings {
+            scale: 1.0 / 3.0,
+            filter: wgpu::FilterMode::Nearest,
+        };
+        settings
+    }
+}
+
+/// Size of the scene targets, stretched over the surface by
+/// [`super::post::upscale::Upscale`] when it differs from the viewport
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolutionSettings {
+    /// Fraction of the viewport size the scene is drawn at
+    pub scale: f32,
+    /// Nearest keeps the scene pixels square
+    pub filter: wgpu::FilterMode,
+}
+
+impl Default for ResolutionSettings {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            filter: wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+/// Bundles of the settings trading quality for speed, applied at once through
+/// [`QualityPreset::apply`]
+///
+/// Settings edited by hand afterwards are a custom mode, [`QualityPreset::of`] then matches no
+/// preset. [`QualityPreset::High`] keeps the defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 4] = [
+        QualityPreset::Low,
+        QualityPreset::Medium,
+        QualityPreset::High,
+        QualityPreset::Ultra,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "Low",
+            QualityPreset::Medium => "Medium",
+            QualityPreset::High => "High",
+            QualityPreset::Ultra => "Ultra",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.label() == name)
+    }
+
+    /// Writes the settings of the preset, the others are kept
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        let level = *self as usize;
+
+        let shadows = &mut settings.shadows;
+        // Low end targets skip the shadow maps for the blobs
+        shadows.enabled = *self != QualityPreset::Low;
+        shadows.blobs.enabled = *self == QualityPreset::Low;
+        shadows.cascades = [2, 3, 4, 4][level];
+        shadows.max_distance = [100.0, 200.0, 300.0, 500.0][level];
+        shadows.filter = [
+            ShadowFilter::Hard,
+            ShadowFilter::Pcf3x3,
+            ShadowFilter::Pcf3x3,
+            ShadowFilter::Pcss,
+        ][level];
+
+        settings.texture_quality.anisotropy = [1, 4, 16, 16][level];
+        settings.streaming.budget_mb = [4.0, 6.0, 8.0, 32.0][level];
+        settings.lights.max_lights = [16, 32, 64, 128][level];
+        settings.small_object_cull = SmallObjectCull {
+            min_pixels: [
+                [0.0, 4.0, 8.0, 16.0],
+                [0.0, 3.0, 6.0, 12.0],
+                SmallObjectCull::default().min_pixels,
+                [0.0, 1.0, 2.0, 4.0],
+            ][level],
+        };
+
+        let environment = &mut settings.environment;
+        environment.sky.clouds.enabled = *self != QualityPreset::Low;
+        environment.precipitation.max_particles = [5000, 10000, 20000, 40000][level];
+    }
+
+    /// The preset the settings are at, `None` in custom mode
+    pub fn of(settings: &RenderSettings) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| {
+            let mut applied = settings.clone();
+            preset.apply(&mut applied);
+            applied == *settings
+        })
+    }
+}
+
+/// Instances covering fewer pixels across than the threshold of their layer are not drawn, see
+/// [`super::entities::draws::DrawGenerator::set_cull_layer`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmallObjectCull {
+    /// Per layer, 0 keeps every size. Layer 0 is the default one
+    pub min_pixels: [f32; CULL_LAYERS],
+}
+
+impl Default for SmallObjectCull {
+    fn default() -> Self {
+        Self {
+            min_pixels: [0.0, 2.0, 4.0, 8.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureQuality {
+    pub anisotropy: u16,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    /// Added to the mip level selected by the hardware
+    pub mip_bias: f32,
+}
+
+impl Default for TextureQuality {
+    fn default() -> Self {
+        Self {
+            anisotropy: 16,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mip_bias: 0.0,
+        }
+    }
+}
+
+impl TextureQuality {
+    /// Sampler used by materials asking for filtered sampling
+    pub fn filtered_sampler(&self) -> SamplerDesc {
+        SamplerDesc {
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            wrap: wgpu::AddressMode::ClampToEdge,
+            anisotropy: self.anisotropy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureStreamingSettings {
+    /// When disabled every texture is streamed to full resolution, within the budget
+    pub enabled: bool,
+    /// Resident atlas texels allowed, in megabytes
+    pub budget_mb: f32,
+    /// Closer than this the full resolution is wanted, each doubling of the distance drops a level
+    pub full_res_distance: f32,
+    pub uploads_per_frame: u32,
+    /// Repacks the atlas in the background when a level no longer fits in one piece
+    pub repack: bool,
+}
+
+impl Default for TextureStreamingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            budget_mb: 8.0,
+            full_res_distance: 10.0,
+            uploads_per_frame: 2,
+            repack: true,
+        }
+    }
+}
+
+/// Cascaded shadow maps of the first directional light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Up to [`super::entities::shadows::MAX_CASCADES`]
+    pub cascades: u32,
+    /// Nothing is shadowed past this distance from the camera
+    pub max_distance: f32,
+    /// 0 splits the distance evenly, 1 logarithmically, giving the nearby cascades more texels
+    pub split_lambda: f32,
+    /// Fraction of each cascade blended with the next one
+    pub blend: f32,
+    /// World units the shadowed positions are pushed along their normal, against acne
+    pub normal_bias: f32,
+    /// Changing it recompiles the entities pipelines
+    pub filter: ShadowFilter,
+    /// Size of the light for [`ShadowFilter::Pcss`], scaling how fast shadows soften with distance
+    pub pcss_softness: f32,
+    /// Point and spot lights given a shadow map each frame, the most important ones, see
+    /// [`super::entities::local_shadows::shadow_budget`]
+    pub local_lights: u32,
+    /// Fallback drawn while the shadow maps are disabled
+    pub blobs: BlobShadowSettings,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cascades: 4,
+            max_distance: 300.0,
+            split_lambda: 0.8,
+            blend: 0.1,
+            normal_bias: 0.05,
+            filter: ShadowFilter::default(),
+            pcss_softness: 0.02,
+            local_lights: 4,
+            blobs: BlobShadowSettings::default(),
+        }
+    }
+}
+
+/// Dark decal projected straight down under every visible instance, scaled to its bounds, see
+/// [`super::entities::blobs::BlobShadows`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlobShadowSettings {
+    /// Only drawn when [`ShadowSettings::enabled`] is off
+    pub enabled: bool,
+    /// Darkening at the center of a blob, from 0 to 1
+    pub strength: f32,
+    /// How far below the bounds the blob still lands, in radii of the bounds
+    pub reach: f32,
+    /// Fraction of the radius fading out at the rim
+    pub softness: f32,
+}
+
+impl Default for BlobShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.6,
+            reach: 1.0,
+            softness: 0.5,
+        }
+    }
+}
+
+/// Bound on the lights uploaded to the shaders, see [`super::light::LightsUniform`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSettings {
+    /// Turns every light off at once, only the ambient light is left
+    pub enabled: bool,
+    /// The light loops of the shaders run at most this many times, clamped to the device limits
+    pub max_lights: u32,
+    pub overflow: LightOverflow,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_lights: 64,
+            overflow: LightOverflow::default(),
+        }
+    }
+}
+
+/// What happens to a light pushed once the cap is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightOverflow {
+    /// The light is dropped
+    #[default]
+    Reject,
+    /// The light takes the slot of the dimmest one if it is brighter, directional lights are
+    /// never replaced
+    ReplaceDimmest,
+    /// Every light is kept, the ones nearest to the camera are uploaded
+    NearestToCamera,
+}
+
+/// Filtering of the shadow map lookups, a variant of the entities shader each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowFilter {
+    /// Single comparison, aliased edges
+    Hard,
+    #[default]
+    Pcf3x3,
+    Pcf5x5,
+    /// Percentage closer soft shadows, sharp at the contact and softer away from the caster
+    Pcss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapper {
+    None,
+    Reinhard,
+    #[default]
+    Aces,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostSettings {
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    /// Name of a .cube lut or of a strip png texture, graded after tonemapping
+    pub lut: Option<String>,
+    pub lut_strength: f32,
+    /// Intensity of the flares of lights flagged with `light_flags::LENS_FLARE`
+    pub lens_flare: f32,
+    /// Brightness of the sdr white on an hdr display, in nits
+    pub paper_white: f32,
+    /// Brightness the tonemapping curve tops at on an hdr display, in nits
+    pub peak_brightness: f32,
+    pub outline: OutlineSettings,
+    pub retro: RetroSettings,
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::default(),
+            lut: None,
+            lut_strength: 1.0,
+            lens_flare: 0.5,
+            paper_white: 200.0,
+            peak_brightness: 1000.0,
+            outline: OutlineSettings::default(),
+            retro: RetroSettings::default(),
+        }
+    }
+}
+
+/// Emulated limits of early 3d hardware, see [`RenderSettings::retro`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetroSettings {
+    /// Vertices snap to the pixels of the scene targets, see [`ResolutionSettings`]
+    pub enabled: bool,
+    /// Textures are interpolated in screen space, without perspective correction
+    pub affine_textures: bool,
+    /// Levels per color channel after tonemapping, ordered dithering hides the banding
+    pub color_levels: u32,
+}
+
+impl Default for RetroSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            affine_textures: true,
+            color_levels: 32,
+        }
+    }
+}
+
+/// Stylized lines along the depth and normal discontinuities of the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlineSettings {
+    pub enabled: bool,
+    /// Linear hdr color of the lines
+    pub color: Color3,
+    /// Pixels between the compared samples, wider lines
+    pub width: f32,
+    /// Relative change of the view depth drawn as a line
+    pub depth_threshold: f32,
+    /// One minus the cosine between the normals drawn as a line
+    pub normal_threshold: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color3::BLACK,
+            width: 1.0,
+            depth_threshold: 0.1,
+            normal_threshold: 0.3,
+        }
+    }
+}
+
+/// Top down view around the camera, see [`super::minimap::Minimap`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapSettings {
+    pub enabled: bool,
+    /// Width and height of the texture in pixels
+    pub resolution: u32,
+    /// World units covered by the side of the view
+    pub extent: f32,
+    /// Frames between two renders of the view, 1 renders it every frame
+    pub interval: u32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 256,
+            extent: 60.0,
+            interval: 1,
+        }
+    }
+}
+
+/// Planar reflection of the entities, see [`super::mirror::Mirror`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MirrorSettings {
+    pub enabled: bool,
+    /// World position of the middle of the mirror
+    pub center: Point3<f32>,
+    /// Facing side of the mirror, does not need to be normalized
+    pub normal: Vector3<f32>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for MirrorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            center: Point3::new(0.0, 2.0, -5.0),
+            normal: Vector3::z(),
+            width: 4.0,
+            height: 3.0,
+        }
+    }
+}
+
+/// Floating origin, see [`super::origin::WorldOrigin`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginSettings {
+    pub enabled: bool,
+    /// Horizontal distance of the camera from the origin triggering a rebase
+    pub threshold: f32,
+    /// The origin moves by whole chunks, keeping the world coordinates exact
+    pub chunk_size: f32,
+    /// Entities and their shadows are transformed relative to the eye of their view, through a
+    /// view composed in f64 on the cpu, so far from the origin their vertices don't jitter
+    pub camera_relative: bool,
+}
+
+impl Default for OriginSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 2048.0,
+            chunk_size: 1024.0,
+            camera_relative: true,
+        }
+    }
+}
+
+/// Layers blended by the terrain splat map, one per channel
+pub const TERRAIN_LAYER_COUNT: usize = 4;
+
+/// Texturing of the terrain, see [`super::terrain::splat::TerrainMaterial`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainSettings {
+    pub layers: [TerrainLayer; TERRAIN_LAYER_COUNT],
+    /// World units covered by the side of the splat map, centered on the origin
+    pub extent: f32,
+    /// Slopes, one minus the normal y, over which the top projection turns triplanar
+    pub triplanar_start: f32,
+    pub triplanar_end: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        let layer = |r, g, b| TerrainLayer {
+            tint: Color3::new(r, g, b),
+            ..Default::default()
+        };
+        Self {
+            layers: [
+                layer(0.3, 0.5, 0.2),
+                layer(0.45, 0.35, 0.25),
+                layer(0.5, 0.5, 0.5),
+                layer(0.9, 0.9, 0.95),
+            ],
+            extent: 16.0,
+            triplanar_start: 0.3,
+            triplanar_end: 0.6,
+        }
+    }
+}
+
+/// Tiling texture of the terrain, a white albedo and a flat normal are used without texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainLayer {
+    /// Names in the textures folder
+    pub albedo: Option<String>,
+    pub normal: Option<String>,
+    /// Multiplies the albedo
+    pub tint: Color3,
+    /// Repetitions of the textures per world unit
+    pub tiling: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            albedo: None,
+            normal: None,
+            tint: Color3::WHITE,
+            tiling: 0.5,
+        }
+    }
+}
+
+/// Settings describing the world surrounding the scene
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EnvironmentSettings {
+    pub fog: FogSettings,
+    pub exposure: AutoExposureSettings,
+    pub wind: WindSettings,
+    pub sky: SkySettings,
+    pub precipitation: PrecipitationSettings,
+}
+
+/// Procedural sky, see [`super::sky::Sky`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkySettings {
+    /// The scene is left over a transparent background when disabled
+    pub enabled: bool,
+    /// Haze of the atmosphere, from 2 for a clear sky to 10 for a hazy one
+    pub turbidity: f32,
+    /// Brightness of the sky model, in the units of the lights
+    pub intensity: f32,
+    /// Share of the sky light received by the scene as ambient light
+    pub ambient: f32,
+    /// Ambient light left once the sun has set
+    pub night_color: Color3,
+    pub ground_color: Color3,
+    /// Angular radius of the sun disk, in degrees
+    pub sun_size: f32,
+    pub sun_intensity: f32,
+    pub clouds: CloudSettings,
+}
+
+/// Noise layer drawn by the sky and shadowing the terrain, drifting along the wind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloudSettings {
+    pub enabled: bool,
+    /// Share of the sky covered, from 0 for a clear sky to 1 for an overcast one
+    pub coverage: f32,
+    /// Width of the fade at the edges of the clouds, in noise units
+    pub softness: f32,
+    /// World units covered by a cell of the noise
+    pub size: f32,
+    pub altitude: f32,
+    /// World units per second
+    pub speed: f32,
+    /// Share of the sun light blocked under a dense cloud
+    pub shadow: f32,
+}
+
+impl Default for CloudSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            coverage: 0.45,
+            softness: 0.2,
+            size: 300.0,
+            altitude: 400.0,
+            speed: 8.0,
+            shadow: 0.6,
+        }
+    }
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            turbidity: 3.0,
+            intensity: 0.05,
+            ambient: 0.7,
+            night_color: Color3::new(0.01, 0.012, 0.02),
+            ground_color: Color3::new(0.3, 0.28, 0.25),
+            sun_size: 0.5,
+            sun_intensity: 20.0,
+            clouds: CloudSettings::default(),
+        }
+    }
+}
+
+/// Rain and snow particles, see [`super::weather::Precipitation`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrecipitationSettings {
+    /// Particles drawn at full intensity
+    pub max_particles: u32,
+    /// Side of the box around the camera the particles fall in
+    pub extent: f32,
+    /// World units per second
+    pub rain_speed: f32,
+    pub snow_speed: f32,
+    /// Horizontal speed given by a wind of strength 1
+    pub rain_drift: f32,
+    pub snow_drift: f32,
+    pub rain_color: Color3,
+    pub snow_color: Color3,
+    pub streak_width: f32,
+    pub streak_length: f32,
+    pub flake_size: f32,
+}
+
+impl Default for PrecipitationSettings {
+    fn default() -> Self {
+        Self {
+            max_particles: 20000,
+            extent: 40.0,
+            rain_speed: 14.0,
+            snow_speed: 1.5,
+            rain_drift: 20.0,
+            snow_drift: 10.0,
+            rain_color: Color3::new(0.6, 0.65, 0.7),
+            snow_color: Color3::WHITE,
+            streak_width: 0.01,
+            streak_length: 0.6,
+            flake_size: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    pub enabled: bool,
+    /// Extinction per world unit
+    pub density: f32,
+    /// Henyey-Greenstein phase parameter, positive values scatter forward towards the viewer
+    pub anisotropy: f32,
+    pub albedo: Color3,
+    /// Distance covered by the froxel volume, fog does not accumulate past it
+    pub max_distance: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 0.02,
+            anisotropy: 0.6,
+            albedo: Color3::WHITE,
+            max_distance: 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindSettings {
+    /// Heading the wind blows towards, around the y axis from +x to +z
+    pub direction_deg: f32,
+    /// Sway in world units of a vertex one unit above its model origin, for a weight of 1
+    pub strength: f32,
+    /// Gusts per second
+    pub gust_frequency: f32,
+}
+
+impl Default for WindSettings {
+    fn default() -> Self {
+        Self {
+            direction_deg: 30.0,
+            strength: 0.1,
+            gust_frequency: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposureSettings {
+    pub enabled: bool,
+    /// Log2 luminance range the exposure adapts within
+    pub min_ev: f32,
+    pub max_ev: f32,
+    /// Rate of the exponential adaptation, per second
+    pub speed: f32,
+}
+
+impl Default for AutoExposureSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_ev: -8.0,
+            max_ev: 4.0,
+            speed: 1.5,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use super::{
+    entities::{
+        material_shader::MaterialShader,
+        renderer::SHADER_SOURCE,
+        toon::{toon_shader_desc, ToonParams},
+    },
+    reflect::ShaderReflection,
+};
+
+/// Every built-in wgsl module by name, as given to the device: the entities shader alone and with
+/// the toon shading appended, the sky and terrain with the clouds. The variants of the entities
+/// shader are only selected by the constants of its pipelines
+pub fn builtin_shaders() -> Vec<(String, String)> {
+    let mut shaders: Vec<(String, String)> = [
+        (
+            "sky",
+            concat!(include_str!("sky.wgsl"), include_str!("clouds.wgsl")),
+        ),
+        (
+            "terrain",
+            concat!(
+                include_str!("terrain/shader.wgsl"),
+                include_str!("clouds.wgsl")
+            ),
+        ),
+        ("equirect", include_str!("cubemap/equirect.wgsl")),
+        ("debug_lines", include_str!("debug_lines.wgsl")),
+        ("animation", include_str!("entities/animation.wgsl")),
+        ("blobs", include_str!("entities/blobs.wgsl")),
+        ("draws", include_str!("entities/draws.wgsl")),
+        ("meshlets", include_str!("entities/meshlets.wgsl")),
+        ("ranges", include_str!("entities/ranges.wgsl")),
+        ("rebase", include_str!("entities/rebase.wgsl")),
+        ("mirror", include_str!("mirror.wgsl")),
+        ("motion", include_str!("motion.wgsl")),
+        ("exposure", include_str!("post/exposure.wgsl")),
+        ("fog/composite", include_str!("post/fog/composite.wgsl")),
+        ("fog/inject", include_str!("post/fog/inject.wgsl")),
+        ("fog/integrate", include_str!("post/fog/integrate.wgsl")),
+        ("lens_flare", include_str!("post/lens_flare.wgsl")),
+        ("outline", include_str!("post/outline.wgsl")),
+        ("tonemap", include_str!("post/tonemap.wgsl")),
+        ("upscale", include_str!("post/upscale.wgsl")),
+        ("view", include_str!("view.wgsl")),
+        ("weather", include_str!("weather.wgsl")),
+        ("world_bars", include_str!("world_bars.wgsl")),
+    ]
+    .into_iter()
+    .map(|(name, source)| (name.to_string(), source.to_string()))
+    .collect();
+
+    let toon = toon_shader_desc(&ToonParams::default());
+    shaders.push(("entities".to_string(), SHADER_SOURCE.to_string()));
+    shaders.push((
+        "toon".to_string(),
+        MaterialShader::module_source(toon.source),
+    ));
+    shaders
+}
+
+/// Parses and validates every built-in shader with naga, one message per invalid shader
+///
+/// Catches the errors a device would only report when the pipeline is created
+pub fn validate_builtin_shaders() -> Vec<String> {
+    builtin_shaders()
+        .into_iter()
+        .filter_map(|(name, source)| {
+            ShaderReflection::new(&source)
+                .err()
+                .map(|e| format!("Shader {name}: {e}"))
+        })
+        .collect()
+}
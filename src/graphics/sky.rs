@@ -0,0 +1,355 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use nalgebra::Vector3;
+
+use super::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    camera::{inv_view_proj_bind_group_layout, CameraUniform},
+    color::Color3,
+    ctx::GraphicsCtx,
+    post::HDR_FORMAT,
+    settings::SkySettings,
+    utils::TextureWrapper,
+};
+
+/// Used without a directional light, matches the builtin light rig
+const DEFAULT_SUN: [f32; 3] = [0.0, 0.949, 0.316];
+/// Directions per ring and rings above the horizon averaged into the ambient light
+const AMBIENT_SAMPLES: (u32, u32) = (8, 4);
+/// Ambient light with the sky disabled, the constant used before
+const FLAT_AMBIENT: f32 = 0.2;
+
+/// Matches `SkyParams` in the sky shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+struct RawSkyParams {
+    /// Perez coefficients A to E, with the luminance and chromaticities in xyz
+    perez: [[f32; 4]; 5],
+    /// Zenith value divided by the Perez function at the zenith, brightness in w
+    scale: [f32; 4],
+    /// Direction towards the sun, cosine of the sun disk radius in w
+    sun: [f32; 4],
+    /// Color below the horizon, sun disk brightness in w
+    ground: [f32; 4],
+}
+
+/// Preetham analytic daylight model, luminance and chromaticity over the sky dome
+struct Preetham {
+    /// A to E for the luminance Y and the chromaticities x and y
+    perez: [[f32; 3]; 5],
+    scale: [f32; 3],
+}
+
+impl Preetham {
+    /// The sun is kept slightly above the horizon, the model diverges past it
+    fn new(turbidity: f32, sun: &Vector3<f32>) -> Self {
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = sun.y.clamp(-1.0, 1.0).acos().min(FRAC_PI_2 - 0.01);
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.0193 * t - 0.2592,
+                -0.0167 * t - 0.2608,
+            ],
+            [
+                -0.3554 * t + 0.4275,
+                -0.0665 * t + 0.0008,
+                -0.0950 * t + 0.0092,
+            ],
+            [
+                -0.0227 * t + 5.3251,
+                -0.0004 * t + 0.2125,
+                -0.0079 * t + 0.2102,
+            ],
+            [
+                0.1206 * t - 2.5771,
+                -0.0641 * t - 0.8989,
+                -0.0441 * t - 1.6537,
+            ],
+            [
+                -0.0670 * t + 0.3703,
+                -0.0033 * t + 0.0452,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (t2, th, th2, th3) = (t * t, theta_s, theta_s * theta_s, theta_s.powi(3));
+        let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        let zenith = [luminance, x, y];
+        let scale = std::array::from_fn(|i| {
+            zenith[i] / perez_function(&perez, i, 1.0, theta_s.cos(), theta_s)
+        });
+        Self { perez, scale }
+    }
+
+    /// Linear rgb of the sky towards `direction`, unscaled
+    fn radiance(&self, direction: &Vector3<f32>, sun: &Vector3<f32>) -> Color3 {
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(sun).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let [luminance, x, y] = std::array::from_fn(|i| {
+            self.scale[i] * perez_function(&self.perez, i, cos_theta, cos_gamma, gamma)
+        });
+        xy_luminance_to_rgb(x, y, luminance)
+    }
+}
+
+/// `(1 + A e^(B / cos θ)) (1 + C e^(D γ) + E cos² γ)` for the channel `i`
+fn perez_function(
+    perez: &[[f32; 3]; 5],
+    i: usize,
+    cos_theta: f32,
+    cos_gamma: f32,
+    gamma: f32,
+) -> f32 {
+    let [a, b, c, d, e] = perez.map(|coefficients| coefficients[i]);
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Through the cie xyz space, matches `xy_luminance_to_rgb` in the sky shader
+fn xy_luminance_to_rgb(x: f32, y: f32, luminance: f32) -> Color3 {
+    let y = y.max(1e-4);
+    let (cx, cy, cz) = (x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+    Color3::new(
+        (3.2406 * cx - 1.5372 * cy - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * cy + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * cy + 1.0570 * cz).max(0.0),
+    )
+}
+
+/// Procedural sky drawn behind the opaque scene, lit by the directional light casting shadows
+///
+/// The same model gives the ambient light of the scene shaders, see
+/// [`super::frame::RawFrameConstants::ambient`]
+pub struct Sky {
+    enabled: bool,
+    params: UniformBuffer<RawSkyParams>,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Sun and settings of the last update, the sky is only evaluated again when they change
+    last: Option<(Vector3<f32>, SkySettings)>,
+    sun: Vector3<f32>,
+    ambient: Color3,
+}
+
+impl Sky {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let params = UniformBuffer::new("Sky params", ctx, &RawSkyParams::default());
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sky_bind_group_layout(ctx),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            }],
+            label: Some("Sky Bind Group"),
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &inv_view_proj_bind_group_layout(ctx),
+                    &sky_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Sky shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(include_str!("sky.wgsl"), include_str!("clouds.wgsl")).into(),
+                ),
+            });
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Sky"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                // Drawn on the far plane, only where the scene left the depth cleared
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureWrapper::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            enabled: false,
+            params,
+            bind_group,
+            pipeline,
+            last: None,
+            sun: Vector3::from(DEFAULT_SUN).normalize(),
+            ambient: Color3::splat(FLAT_AMBIENT),
+        }
+    }
+
+    /// Follows the direction of the shadow casting light, `light_direction` points away from the
+    /// sun
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &SkySettings,
+        light_direction: Option<Vector3<f32>>,
+    ) {
+        let sun = light_direction
+            .and_then(|direction| (-direction).try_normalize(1e-6))
+            .unwrap_or_else(|| Vector3::from(DEFAULT_SUN).normalize());
+        if self.last.as_ref() == Some(&(sun, *settings)) {
+            return;
+        }
+        self.last = Some((sun, *settings));
+        self.sun = sun;
+        self.enabled = settings.enabled;
+        if !settings.enabled {
+            self.ambient = Color3::splat(FLAT_AMBIENT);
+            return;
+        }
+
+        let model = Preetham::new(settings.turbidity, &sun);
+        // Fades to the night color once the sun sets
+        let brightness = settings.intensity * smoothstep(-0.1, 0.05, sun.y);
+        let night = settings.night_color;
+
+        let (per_ring, rings) = AMBIENT_SAMPLES;
+        let mut sum = Color3::BLACK;
+        for ring in 0..rings {
+            let elevation = (ring as f32 + 0.5) / rings as f32 * FRAC_PI_2;
+            for i in 0..per_ring {
+                let azimuth = i as f32 / per_ring as f32 * 2.0 * PI;
+                let direction = Vector3::new(
+                    elevation.cos() * azimuth.cos(),
+                    elevation.sin(),
+                    elevation.cos() * azimuth.sin(),
+                );
+                // Weighted by the cosine with the up axis, as received by a flat surface
+                let radiance = model.radiance(&direction, &sun);
+                sum = sum + radiance * (brightness * direction.y) + night * direction.y;
+            }
+        }
+        // Normalized by the sum of the cosine weights
+        let weights: f32 = (0..rings)
+            .map(|ring| ((ring as f32 + 0.5) / rings as f32 * FRAC_PI_2).sin() * per_ring as f32)
+            .sum();
+        self.ambient = sum * (settings.ambient / weights);
+
+        let raw = RawSkyParams {
+            perez: model.perez.map(|[luminance, x, y]| [luminance, x, y, 0.0]),
+            scale: [model.scale[0], model.scale[1], model.scale[2], brightness],
+            sun: [sun.x, sun.y, sun.z, settings.sun_size.to_radians().cos()],
+            ground: [
+                settings.ground_color.r,
+                settings.ground_color.g,
+                settings.ground_color.b,
+                settings.sun_intensity * smoothstep(-0.02, 0.02, sun.y),
+            ],
+        };
+        self.params.write(ctx, &raw);
+    }
+
+    /// Direction towards the sun
+    pub fn sun(&self) -> Vector3<f32> {
+        self.sun
+    }
+
+    /// Light received from the sky by an upward facing surface
+    pub fn ambient(&self) -> Color3 {
+        self.ambient
+    }
+
+    /// Fills the pixels of `target` the opaque scene left empty, `depth` is only read
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        depth: &TextureWrapper,
+        camera: &CameraUniform,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn sky_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Sky Bind Group Layout"),
+        })
+}
@@ -0,0 +1,121 @@
+// Matches `RawSkyParams`
+struct SkyParams {
+    // Perez coefficients A to E, luminance in x and chromaticities in yz
+    perez: array<vec4f, 5>,
+    // Zenith value divided by the Perez function at the zenith, brightness in w
+    scale: vec4f,
+    // Direction towards the sun, cosine of the sun disk radius in w
+    sun: vec4f,
+    // Color below the horizon, sun disk brightness in w
+    ground: vec4f,
+};
+
+@group(0) @binding(0)
+var<uniform> inv_view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> inv_proj: mat4x4f;
+
+// Matches `RawFrameConstants`
+struct FrameConstants {
+    time: f32,
+    delta_time: f32,
+    frame_index: u32,
+    exposure: f32,
+    screen_size: vec2f,
+    inv_screen_size: vec2f,
+    snap_resolution: vec2f,
+    affine_textures: u32,
+    camera_relative: u32,
+    eye: vec3f,
+    wetness: f32,
+    ambient: vec3f,
+    sun_direction: vec3f,
+    cloud_coverage: f32,
+    cloud_velocity: vec2f,
+    cloud_origin: vec2f,
+    cloud_size: f32,
+    cloud_altitude: f32,
+    cloud_softness: f32,
+    cloud_shadow: f32,
+};
+
+@group(0) @binding(3)
+var<uniform> frame: FrameConstants;
+
+@group(1) @binding(0)
+var<uniform> sky: SkyParams;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) ndc: vec2f,
+};
+
+// Single triangle covering the whole screen, on the far plane
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0);
+    var out: VertexOutput;
+    out.clip_position = vec4f(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let view = inv_proj * vec4f(in.ndc, 1.0, 1.0);
+    let direction = normalize((inv_view * vec4f(view.xyz / view.w, 0.0)).xyz);
+    let sun = sky.sun.xyz;
+    let cos_gamma = clamp(dot(direction, sun), -1.0, 1.0);
+
+    let radiance = sky_radiance(direction, cos_gamma) * sky.scale.w;
+    // Soft edged sun disk
+    let disk = smoothstep(sky.sun.w, mix(sky.sun.w, 1.0, 0.1), cos_gamma);
+    var color = radiance + vec3f(1.0, 0.95, 0.85) * disk * sky.ground.w;
+    color = clouds_over(color, radiance, direction, cos_gamma);
+    // The ground takes over a little below the horizon
+    color = mix(color, sky.ground.rgb * max(sky.scale.w, 0.02), smoothstep(0.0, -0.05, direction.y));
+    return vec4f(color, 1.0);
+}
+
+// Cloud layer seen towards `direction`, lit by the sky around it and brighter towards the sun
+fn clouds_over(color: vec3f, radiance: vec3f, direction: vec3f, cos_gamma: f32) -> vec3f {
+    let height = frame.cloud_altitude - frame.eye.y;
+    if direction.y <= 0.0 || height <= 0.0 {
+        return color;
+    }
+    let hit = frame.eye.xz + direction.xz * (height / direction.y);
+    // Thinned out towards the horizon, where the layer is far and aliased
+    let density = cloud_density(hit) * smoothstep(0.0, 0.15, direction.y);
+    let zenith = sky_radiance(vec3f(0.0, 1.0, 0.0), sky.sun.y) * sky.scale.w;
+    let luminance = dot(zenith, vec3f(0.2126, 0.7152, 0.0722));
+    let lit = mix(vec3f(luminance * 2.0), radiance * 1.5, 0.3)
+        + vec3f(1.0, 0.95, 0.85) * pow(max(cos_gamma, 0.0), 8.0) * sky.scale.w * 4.0;
+    return mix(color, lit, density);
+}
+
+// Matches `Preetham::radiance`
+fn sky_radiance(direction: vec3f, cos_gamma: f32) -> vec3f {
+    let cos_theta = max(direction.y, 0.01);
+    let gamma = acos(cos_gamma);
+    let a = sky.perez[0].xyz;
+    let b = sky.perez[1].xyz;
+    let c = sky.perez[2].xyz;
+    let d = sky.perez[3].xyz;
+    let e = sky.perez[4].xyz;
+    let perez = (1.0 + a * exp(b / cos_theta)) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+    let value = sky.scale.xyz * perez;
+    return xy_luminance_to_rgb(value.y, value.z, value.x);
+}
+
+// Through the cie xyz space
+fn xy_luminance_to_rgb(x: f32, y: f32, luminance: f32) -> vec3f {
+    let safe_y = max(y, 1e-4);
+    let xyz = vec3f(x * luminance / safe_y, luminance, (1.0 - x - safe_y) * luminance / safe_y);
+    let rgb = mat3x3f(
+        vec3f(3.2406, -0.9689, 0.0557),
+        vec3f(-1.5372, 1.8758, -0.2040),
+        vec3f(-0.4986, 0.0415, 1.0570),
+    ) * xyz;
+    return max(rgb, vec3f(0.0));
+}
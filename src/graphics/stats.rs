@@ -0,0 +1,199 @@
+use std::path::Path;
+
+use super::GlobalRenderer;
+
+/// Counts of what the renderer holds, exported as `key = value` lines
+///
+/// Compared against the golden data of the render tests, a change means the scene itself changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    pub models: u32,
+    pub meshes: u32,
+    pub vertices: u32,
+    pub indices: u32,
+    pub instances: u32,
+    pub materials: u32,
+    /// Slots in use, empty and disabled ones included
+    pub lights: u32,
+}
+
+impl RenderStats {
+    pub fn of(renderer: &GlobalRenderer) -> Self {
+        let models = &renderer.entities.models;
+        Self {
+            models: models.model_count(),
+            meshes: models.mesh_count(),
+            vertices: models.vertex_count(),
+            indices: models.index_count(),
+            instances: models.instance_count(),
+            materials: renderer.entities.materials.len(),
+            lights: renderer.lights.len(),
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, u32); 7] {
+        [
+            ("models", self.models),
+            ("meshes", self.meshes),
+            ("vertices", self.vertices),
+            ("indices", self.indices),
+            ("instances", self.instances),
+            ("materials", self.materials),
+            ("lights", self.lights),
+        ]
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut u32> {
+        Some(match name {
+            "models" => &mut self.models,
+            "meshes" => &mut self.meshes,
+            "vertices" => &mut self.vertices,
+            "indices" => &mut self.indices,
+            "instances" => &mut self.instances,
+            "materials" => &mut self.materials,
+            "lights" => &mut self.lights,
+            _ => return None,
+        })
+    }
+
+    /// Missing keys are left at 0
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut stats = Self::default();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key = value: {line}"))?;
+            let field = stats
+                .field_mut(key.trim())
+                .ok_or_else(|| format!("Unknown stat {}", key.trim()))?;
+            *field = value
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid stat {line}: {e}"))?;
+        }
+        Ok(stats)
+    }
+
+    /// One message per count differing from `expected` by more than `tolerance`, relative to it
+    pub fn diff(&self, expected: &Self, tolerance: f32) -> Vec<String> {
+        self.fields()
+            .into_iter()
+            .zip(expected.fields())
+            .filter(|((_, value), (_, expected))| {
+                value.abs_diff(*expected) as f32 > *expected as f32 * tolerance
+            })
+            .map(|((name, value), (_, expected))| format!("{name} is {value}, expected {expected}"))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in self.fields() {
+            writeln!(f, "{name} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Most of what the growable buffers held at once while a scene was played, saved next to it so
+/// the next load allocates them up front instead of growing and copying them during gameplay
+///
+/// Saved as `key = value` lines, `instances` lists the peak of every mesh in
+/// [`super::entities::model::ModelsBuffer::mesh_index`] order. Weather particles are drawn from a
+/// count without a buffer, they have no peak
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BufferPeaks {
+    pub instances: Vec<u32>,
+    pub lights: u32,
+    /// Vertices queued in a frame
+    pub debug_lines: u32,
+    pub world_widgets: u32,
+}
+
+impl BufferPeaks {
+    /// What the renderer holds now, debug lines and widgets are the ones queued for this frame
+    pub fn of(renderer: &GlobalRenderer) -> Self {
+        Self {
+            instances: renderer
+                .entities
+                .models
+                .instance_occupancy()
+                .into_iter()
+                .map(|(len, _)| len as u32)
+                .collect(),
+            lights: renderer.lights.len(),
+            debug_lines: renderer.debug_lines.queued_len() as u32,
+            world_widgets: renderer.world_bars.queued_len() as u32,
+        }
+    }
+
+    /// Keeps the largest of both counts
+    pub fn merge(&mut self, other: &Self) {
+        if self.instances.len() < other.instances.len() {
+            self.instances.resize(other.instances.len(), 0);
+        }
+        for (peak, count) in self.instances.iter_mut().zip(&other.instances) {
+            *peak = (*peak).max(*count);
+        }
+        self.lights = self.lights.max(other.lights);
+        self.debug_lines = self.debug_lines.max(other.debug_lines);
+        self.world_widgets = self.world_widgets.max(other.world_widgets);
+    }
+
+    /// Missing keys are left empty
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut peaks = Self::default();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key = value: {line}"))?;
+            let parse = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid peak {line}: {e}"))
+            };
+            match key.trim() {
+                "instances" => {
+                    peaks.instances = value
+                        .split_whitespace()
+                        .map(parse)
+                        .collect::<Result<_, _>>()?
+                }
+                "lights" => peaks.lights = parse(value.trim())?,
+                "debug_lines" => peaks.debug_lines = parse(value.trim())?,
+                "world_widgets" => peaks.world_widgets = parse(value.trim())?,
+                key => return Err(format!("Unknown peak {key}")),
+            }
+        }
+        Ok(peaks)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&src)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|e| e.to_string())
+    }
+}
+
+impl std::fmt::Display for BufferPeaks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "instances =")?;
+        for count in &self.instances {
+            write!(f, " {count}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "lights = {}", self.lights)?;
+        writeln!(f, "debug_lines = {}", self.debug_lines)?;
+        writeln!(f, "world_widgets = {}", self.world_widgets)
+    }
+}
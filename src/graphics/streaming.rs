@@ -0,0 +1,511 @@
+use std::sync::Arc;
+
+use guillotiere::{size2, Allocation, AtlasAllocator};
+use image::{imageops::FilterType, RgbaImage};
+use nalgebra::Point3;
+
+use crate::jobs::{self, Job, Priority};
+
+use super::{
+    atlas::AtlasUniform,
+    ctx::GraphicsCtx,
+    settings::{TextureQuality, TextureStreamingSettings},
+    utils::ColorSpace,
+};
+
+/// Smallest side kept resident, textures never go below this resolution
+const MIN_RESIDENT_SIZE: u32 = 16;
+
+/// Level 0 is the full resolution image, each following level halves it
+struct StreamedTexture {
+    /// Shared with the repacks running in the background
+    image: Arc<RgbaImage>,
+    color_space: ColorSpace,
+    lowest_level: u32,
+    resident: Option<(u32, Allocation)>,
+    /// Bounds of the instances using the texture this frame, see [`TextureStreamer::clear_usage`]
+    usage: Option<[Point3<f32>; 2]>,
+    /// Level asked by the last update, depends on the distance to `usage`
+    wanted_level: u32,
+    last_used_frame: u64,
+}
+
+impl StreamedTexture {
+    fn new(image: RgbaImage, color_space: ColorSpace) -> Self {
+        let min_side = image.width().min(image.height());
+        let lowest_level = (min_side / MIN_RESIDENT_SIZE).max(1).ilog2();
+        Self {
+            image: Arc::new(image),
+            color_space,
+            lowest_level,
+            resident: None,
+            usage: None,
+            wanted_level: lowest_level,
+            last_used_frame: 0,
+        }
+    }
+
+    fn resident_level(&self) -> u32 {
+        self.resident.map_or(self.lowest_level, |(level, _)| level)
+    }
+
+    fn level_size(&self, level: u32) -> (u32, u32) {
+        level_size(&self.image, level)
+    }
+
+    fn level_bytes(&self, level: u32) -> u64 {
+        let (width, height) = self.level_size(level);
+        width as u64 * height as u64 * 4
+    }
+}
+
+/// Resident levels placed in a new atlas, built in the background by
+/// [`TextureStreamer::repack`]
+struct Repack {
+    allocator: AtlasAllocator,
+    /// Texture id, level and allocation, with the pixels of the level
+    regions: Vec<(usize, u32, Allocation, RgbaImage)>,
+}
+
+/// Keeps atlas textures resident at the resolution their usage needs
+///
+/// Every texture starts at its lowest level, higher levels are streamed in a few per frame
+/// while the resident bytes stay under the budget, least recently used textures are downgraded
+/// first to make room
+///
+/// Levels coming and going leave holes in the atlas, when a level no longer fits although the
+/// free space would hold it the atlas is repacked in the background. Streaming pauses meanwhile,
+/// the new atlas replaces the old one with its uvs and bind group at the start of an update
+///
+/// The atlas is sized to hold the budget, a new budget repacks it at its new size
+pub struct TextureStreamer {
+    pub atlas: AtlasUniform,
+    allocator: AtlasAllocator,
+    /// Size the next repack packs into, differs from the atlas one until a new budget is applied
+    dims: (u32, u32),
+    textures: Vec<StreamedTexture>,
+    settings: TextureStreamingSettings,
+    quality: TextureQuality,
+    resident_bytes: u64,
+    frame: u64,
+    /// `None` is sent when the resident levels do not fit the new atlas
+    repack: Option<Job<Option<Repack>>>,
+    repack_count: u32,
+}
+
+impl TextureStreamer {
+    /// Each image comes with the color space it is decoded from
+    pub fn new<T: Into<RgbaImage>>(
+        ctx: &GraphicsCtx,
+        images: impl IntoIterator<Item = (T, ColorSpace)>,
+        quality: &TextureQuality,
+        settings: &TextureStreamingSettings,
+    ) -> Self {
+        let textures: Vec<_> = images
+            .into_iter()
+            .map(|(image, color_space)| StreamedTexture::new(image.into(), color_space))
+            .collect();
+
+        let dims = atlas_dims(ctx, settings);
+        let atlas = AtlasUniform::new_empty(ctx, dims, textures.len(), quality);
+        let mut streamer = Self {
+            atlas,
+            allocator: AtlasAllocator::new(size2(dims.0 as i32, dims.1 as i32)),
+            dims,
+            textures,
+            settings: *settings,
+            quality: *quality,
+            resident_bytes: 0,
+            frame: 0,
+            repack: None,
+            repack_count: 0,
+        };
+
+        for id in 0..streamer.textures.len() {
+            let lowest_level = streamer.textures[id].lowest_level;
+            if !streamer.make_resident(ctx, id, lowest_level) {
+                panic!("Failed to allocate the lowest level of texture {id} to the atlas");
+            }
+        }
+        streamer.atlas.apply_changes(ctx);
+
+        streamer
+    }
+
+    /// Streamed from its lowest level like the textures given at creation, the id is the slot of
+    /// its uvs in the atlas
+    pub fn add_texture(
+        &mut self,
+        ctx: &GraphicsCtx,
+        image: impl Into<RgbaImage>,
+        color_space: ColorSpace,
+    ) -> u32 {
+        let texture = StreamedTexture::new(image.into(), color_space);
+        let lowest_level = texture.lowest_level;
+        let id = self.atlas.add_texture();
+        // Textures are never removed, the slots follow their order
+        debug_assert_eq!(id as usize, self.textures.len());
+        self.textures.push(texture);
+        if self.repack.is_none() && !self.make_resident(ctx, id as usize, lowest_level) {
+            eprintln!("No room in the atlas for texture {id}, it stays blank");
+        }
+        id
+    }
+
+    /// Forgets the usage of every texture, the instances using them this frame are added back
+    /// with [`Self::add_usage`] before the [`Self::update`]
+    pub fn clear_usage(&mut self) {
+        for texture in &mut self.textures {
+            texture.usage = None;
+        }
+    }
+
+    /// Grows the usage bounds of the texture, used to pick its resolution
+    pub fn add_usage(&mut self, texture_id: u32, position: Point3<f32>) {
+        let Some(texture) = self.textures.get_mut(texture_id as usize) else {
+            return;
+        };
+        texture.usage = Some(match texture.usage {
+            None => [position, position],
+            Some([min, max]) => [min.inf(&position), max.sup(&position)],
+        });
+    }
+
+    pub fn color_space(&self, texture_id: u32) -> ColorSpace {
+        self.textures
+            .get(texture_id as usize)
+            .map_or_else(Default::default, |texture| texture.color_space)
+    }
+
+    /// Gives `texture_id` the usage bounds of `from`, for textures swapped in for one another
+    pub fn share_usage(&mut self, from: u32, texture_id: u32) {
+        let usage = self.textures.get(from as usize).and_then(|t| t.usage);
+        if let Some(texture) = self.textures.get_mut(texture_id as usize) {
+            texture.usage = usage;
+        }
+    }
+
+    /// Grows the usage bounds of `texture_id` by the ones of `from`
+    pub fn merge_usage(&mut self, from: u32, texture_id: u32) {
+        let Some([min, max]) = self.textures.get(from as usize).and_then(|t| t.usage) else {
+            return;
+        };
+        self.add_usage(texture_id, min);
+        self.add_usage(texture_id, max);
+    }
+
+    /// A new budget resizes the atlas through a repack started by the next update, the resident
+    /// levels are kept
+    pub fn apply_settings(&mut self, ctx: &GraphicsCtx, settings: &TextureStreamingSettings) {
+        self.settings = *settings;
+        self.dims = atlas_dims(ctx, settings);
+    }
+
+    /// Kept for the atlases created by the repacks
+    pub fn apply_quality(&mut self, ctx: &GraphicsCtx, quality: &TextureQuality) {
+        self.quality = *quality;
+        self.atlas.apply_quality(ctx, quality);
+    }
+
+    /// Packs the resident levels into a new atlas in the background, largest first
+    pub fn repack(&mut self) {
+        if self.repack.is_some() {
+            return;
+        }
+        let mut levels: Vec<_> = self
+            .textures
+            .iter()
+            .enumerate()
+            .filter_map(|(id, texture)| {
+                let (level, _) = texture.resident?;
+                Some((id, level, texture.image.clone()))
+            })
+            .collect();
+        levels.sort_by_key(|(_, level, image)| {
+            let (width, height) = level_size(image, *level);
+            std::cmp::Reverse(width.max(height))
+        });
+
+        let size = size2(self.dims.0 as i32, self.dims.1 as i32);
+        let job = jobs::spawn("Atlas repack", Priority::Low, move || {
+            let mut allocator = AtlasAllocator::new(size);
+            let regions = levels
+                .into_iter()
+                .map(|(id, level, image)| {
+                    let (width, height) = level_size(&image, level);
+                    let allocation = allocator.allocate(size2(width as i32, height as i32))?;
+                    Some((id, level, allocation, level_image(&image, level)))
+                })
+                .collect::<Option<Vec<_>>>();
+            regions.map(|regions| Repack { allocator, regions })
+        });
+        self.repack = Some(job);
+    }
+
+    pub fn repacking(&self) -> bool {
+        self.repack.is_some()
+    }
+
+    pub fn repack_count(&self) -> u32 {
+        self.repack_count
+    }
+
+    pub fn resident_bytes(&self) -> u64 {
+        self.resident_bytes
+    }
+
+    /// Size of the atlas in pixels
+    pub fn atlas_size(&self) -> (u32, u32) {
+        let size = self.allocator.size();
+        (size.width as u32, size.height as u32)
+    }
+
+    /// Texture id, resident level and pixel rect, min then max, of every allocation in the atlas
+    pub fn allocations(&self) -> impl Iterator<Item = (usize, u32, [u32; 4])> + '_ {
+        self.textures
+            .iter()
+            .enumerate()
+            .filter_map(|(id, texture)| {
+                let (level, allocation) = texture.resident.as_ref()?;
+                let rect = allocation.rectangle;
+                Some((
+                    id,
+                    *level,
+                    [rect.min.x, rect.min.y, rect.max.x, rect.max.y].map(|v| v as u32),
+                ))
+            })
+    }
+
+    pub fn budget_bytes(&self) -> u64 {
+        budget_bytes(&self.settings)
+    }
+
+    /// Uploads at most `uploads_per_frame` higher levels, closest needs first
+    pub fn update(&mut self, ctx: &GraphicsCtx, camera_position: Point3<f32>) {
+        self.frame += 1;
+
+        if let Some(job) = &mut self.repack {
+            if job.lost() {
+                self.repack = None;
+            } else {
+                // The resident levels must stay the ones being packed
+                let Some(repack) = job.poll() else {
+                    return;
+                };
+                self.repack = None;
+                match repack {
+                    Some(repack) => self.apply_repack(ctx, repack),
+                    None => {
+                        eprintln!("Could not repack the texture atlas");
+                        // The resident levels do not fit the new budget, the size is kept
+                        self.dims = self.atlas_size();
+                    }
+                }
+            }
+        }
+        if self.dims != self.atlas_size() {
+            // Pending repacks pack into the old size
+            if self.repack.is_none() {
+                self.repack();
+            }
+            return;
+        }
+
+        for texture in &mut self.textures {
+            texture.wanted_level = match (self.settings.enabled, texture.usage) {
+                (false, _) => 0,
+                (true, None) => texture.lowest_level,
+                (true, Some([min, max])) => {
+                    let closest = camera_position.sup(&min).inf(&max);
+                    let distance = nalgebra::distance(&camera_position, &closest);
+                    let level = (distance / self.settings.full_res_distance.max(0.01))
+                        .max(1.0)
+                        .log2() as u32;
+                    level.min(texture.lowest_level)
+                }
+            };
+            if texture.wanted_level < texture.lowest_level {
+                texture.last_used_frame = self.frame;
+            }
+        }
+
+        let mut pending: Vec<_> = (0..self.textures.len())
+            .filter(|id| {
+                let texture = &self.textures[*id];
+                texture.wanted_level < texture.resident_level()
+            })
+            .collect();
+        pending.sort_by_key(|id| {
+            let texture = &self.textures[*id];
+            std::cmp::Reverse(texture.resident_level() - texture.wanted_level)
+        });
+
+        for id in pending
+            .into_iter()
+            .take(self.settings.uploads_per_frame as usize)
+        {
+            // One level at a time, the texture sharpens progressively
+            let level = self.textures[id].resident_level() - 1;
+            let extra =
+                self.textures[id].level_bytes(level) - self.textures[id].level_bytes(level + 1);
+
+            while self.resident_bytes + extra > self.budget_bytes() {
+                if !self.evict_one(ctx, id) {
+                    break;
+                }
+            }
+            if self.resident_bytes + extra > self.budget_bytes() {
+                continue;
+            }
+
+            self.make_resident(ctx, id, level);
+        }
+        self.atlas.apply_changes(ctx);
+    }
+
+    /// Downgrades a texture sharper than needed or unused this frame by one level,
+    /// `keep` is never chosen
+    fn evict_one(&mut self, ctx: &GraphicsCtx, keep: usize) -> bool {
+        let frame = self.frame;
+        let victim = (0..self.textures.len())
+            .filter(|id| {
+                let texture = &self.textures[*id];
+                let resident_level = texture.resident_level();
+                *id != keep
+                    && resident_level < texture.lowest_level
+                    && (resident_level < texture.wanted_level || texture.last_used_frame < frame)
+            })
+            .min_by_key(|id| {
+                let texture = &self.textures[*id];
+                // Textures sharper than needed go first
+                let over_resolved = texture.resident_level() < texture.wanted_level;
+                (!over_resolved, texture.last_used_frame)
+            });
+
+        match victim {
+            Some(id) => {
+                let level = self.textures[id].resident_level() + 1;
+                self.make_resident(ctx, id, level)
+            }
+            None => false,
+        }
+    }
+
+    /// Reallocates the texture at `level` and uploads it, the previous level is kept on failure
+    fn make_resident(&mut self, ctx: &GraphicsCtx, id: usize, level: u32) -> bool {
+        let texture = &self.textures[id];
+        let (width, height) = texture.level_size(level);
+        let Some(allocation) = self.allocator.allocate(size2(width as i32, height as i32)) else {
+            // Room left but not in one piece
+            let free = self.atlas_area() - self.allocated_area();
+            if self.settings.repack && free >= width as u64 * height as u64 {
+                self.repack();
+            }
+            return false;
+        };
+
+        let texture = &mut self.textures[id];
+        if let Some((old_level, old_allocation)) = texture.resident.take() {
+            self.allocator.deallocate(old_allocation.id);
+            self.resident_bytes -= texture.level_bytes(old_level);
+        }
+
+        let image = level_image(&texture.image, level);
+        let origin = allocation.rectangle.min;
+        self.atlas.write_region(
+            ctx,
+            id as u32,
+            (origin.x as u32, origin.y as u32),
+            &image,
+            texture.color_space,
+        );
+
+        self.resident_bytes += texture.level_bytes(level);
+        texture.resident = Some((level, allocation));
+        true
+    }
+
+    fn atlas_area(&self) -> u64 {
+        let (width, height) = self.atlas_size();
+        width as u64 * height as u64
+    }
+
+    fn allocated_area(&self) -> u64 {
+        self.allocations()
+            .map(|(_, _, [x0, y0, x1, y1])| (x1 - x0) as u64 * (y1 - y0) as u64)
+            .sum()
+    }
+
+    /// Swaps in the new atlas, its bind group replaces the old one for the next draws
+    fn apply_repack(&mut self, ctx: &GraphicsCtx, repack: Repack) {
+        let size = repack.allocator.size();
+        let mut atlas = AtlasUniform::new_empty(
+            ctx,
+            (size.width as u32, size.height as u32),
+            self.textures.len(),
+            &self.quality,
+        );
+        for (id, level, allocation, image) in repack.regions {
+            let origin = allocation.rectangle.min;
+            atlas.write_region(
+                ctx,
+                id as u32,
+                (origin.x as u32, origin.y as u32),
+                &image,
+                self.textures[id].color_space,
+            );
+            self.textures[id].resident = Some((level, allocation));
+        }
+        atlas.apply_changes(ctx);
+        self.atlas = atlas;
+        self.allocator = repack.allocator;
+        self.repack_count += 1;
+
+        // Added while the repack was running
+        for id in 0..self.textures.len() {
+            if self.textures[id].resident.is_none() {
+                let lowest_level = self.textures[id].lowest_level;
+                self.make_resident(ctx, id, lowest_level);
+            }
+        }
+        self.atlas.apply_changes(ctx);
+    }
+}
+
+fn level_size(image: &RgbaImage, level: u32) -> (u32, u32) {
+    (
+        (image.width() >> level).max(1),
+        (image.height() >> level).max(1),
+    )
+}
+
+fn level_image(image: &RgbaImage, level: u32) -> RgbaImage {
+    match level {
+        0 => image.clone(),
+        _ => {
+            let (width, height) = level_size(image, level);
+            image::imageops::resize(image, width, height, FilterType::Triangle)
+        }
+    }
+}
+
+fn budget_bytes(settings: &TextureStreamingSettings) -> u64 {
+    (settings.budget_mb.max(0.0) * 1024.0 * 1024.0) as u64
+}
+
+/// Smallest power of two atlas holding the budget, twice as wide as tall when it is not square,
+/// clamped to the device limit
+fn atlas_dims(ctx: &GraphicsCtx, settings: &TextureStreamingSettings) -> (u32, u32) {
+    let texels = (budget_bytes(settings) / 4)
+        .max((MIN_RESIDENT_SIZE * MIN_RESIDENT_SIZE) as u64)
+        .next_power_of_two();
+    let log = texels.ilog2();
+    let (width, height) = (1u64 << log.div_ceil(2), 1u64 << (log / 2));
+    let max = ctx.max_texture_size() as u64;
+    if width > max {
+        eprintln!(
+            "Texture atlas of {width}x{height} for the budget exceeds the device limit, shrinking it to {max}"
+        );
+    }
+    (width.min(max) as u32, height.min(max) as u32)
+}
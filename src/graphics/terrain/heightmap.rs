@@ -0,0 +1,399 @@
+use std::path::{Path, PathBuf};
+
+use nalgebra::{Point3, Vector3};
+
+use crate::{
+    graphics::{bundle::Tracked, ctx::GraphicsCtx},
+    ASSETS,
+};
+
+/// Texels per side of the heightmap, spread over the same square as the splat map
+pub const HEIGHTMAP_SIZE: u32 = 256;
+/// Texels per side of the chunks uploaded again once edited
+pub const CHUNK_SIZE: u32 = 32;
+const CHUNKS: u32 = HEIGHTMAP_SIZE / CHUNK_SIZE;
+/// Heights are saved as 16 bits over `-HEIGHT_RANGE / 2..HEIGHT_RANGE / 2`
+pub const HEIGHT_RANGE: f32 = 64.0;
+/// Heightmap loaded at startup from the terrain folder of the assets
+pub const DEFAULT_HEIGHTMAP: &str = "Default";
+
+/// Heights of the terrain in world units over the xz plane
+///
+/// Texels are laid out row by row from -x -z like [`super::splat::SplatMap`], each one at the
+/// center of its cell
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    pub heights: Vec<f32>,
+    /// One flag per chunk, cleared by [`TerrainHeight::upload_dirty`]
+    dirty: Vec<bool>,
+}
+
+impl Default for Heightmap {
+    fn default() -> Self {
+        Self {
+            heights: vec![0.0; (HEIGHTMAP_SIZE * HEIGHTMAP_SIZE) as usize],
+            dirty: vec![false; (CHUNKS * CHUNKS) as usize],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushKind {
+    Raise,
+    Lower,
+    /// Pulls the heights toward the average of their neighbours
+    Smooth,
+    /// Pulls the heights toward the height under the brush when the stroke started
+    Flatten,
+}
+
+impl BrushKind {
+    pub const ALL: [Self; 4] = [Self::Raise, Self::Lower, Self::Smooth, Self::Flatten];
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Brush {
+    pub kind: BrushKind,
+    /// World units
+    pub radius: f32,
+    /// World units per second for raise and lower, blend per second otherwise
+    pub strength: f32,
+}
+
+impl Heightmap {
+    pub fn path(name: &str) -> PathBuf {
+        ASSETS
+            .root()
+            .join("terrain")
+            .join(name)
+            .with_extension("png")
+    }
+
+    /// 16 bits grayscale png, see [`HEIGHT_RANGE`]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|e| e.to_string())?.into_luma16();
+        if image.dimensions() != (HEIGHTMAP_SIZE, HEIGHTMAP_SIZE) {
+            return Err(format!(
+                "Heightmap {} is {}x{}, expected {HEIGHTMAP_SIZE}x{HEIGHTMAP_SIZE}",
+                path.display(),
+                image.width(),
+                image.height()
+            ));
+        }
+        let heights = image
+            .pixels()
+            .map(|pixel| (pixel.0[0] as f32 / u16::MAX as f32 - 0.5) * HEIGHT_RANGE)
+            .collect();
+        let mut heightmap = Self {
+            heights,
+            ..Default::default()
+        };
+        heightmap.dirty.fill(true);
+        Ok(heightmap)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        }
+        let texels = self
+            .heights
+            .iter()
+            .map(|h| ((h / HEIGHT_RANGE + 0.5).clamp(0.0, 1.0) * u16::MAX as f32).round() as u16)
+            .collect();
+        image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_raw(
+            HEIGHTMAP_SIZE,
+            HEIGHTMAP_SIZE,
+            texels,
+        )
+        .ok_or("Heightmap texels don't match its size")?
+        .save(path)
+        .map_err(|e| e.to_string())
+    }
+
+    fn texel(&self, x: u32, z: u32) -> f32 {
+        self.heights[(z * HEIGHTMAP_SIZE + x) as usize]
+    }
+
+    /// Continuous texel coordinates of a world position, `extent` being the side of the square
+    fn to_texels(x: f32, z: f32, extent: f32) -> (f32, f32) {
+        let size = HEIGHTMAP_SIZE as f32;
+        (
+            (x / extent + 0.5) * size - 0.5,
+            (z / extent + 0.5) * size - 0.5,
+        )
+    }
+
+    /// Bilinear height, the border texels extend past the square
+    pub fn height_at(&self, x: f32, z: f32, extent: f32) -> f32 {
+        let max = (HEIGHTMAP_SIZE - 1) as f32;
+        let (u, v) = Self::to_texels(x, z, extent);
+        let (u, v) = (u.clamp(0.0, max), v.clamp(0.0, max));
+        let (x0, z0) = (u.floor() as u32, v.floor() as u32);
+        let (x1, z1) = (
+            (x0 + 1).min(HEIGHTMAP_SIZE - 1),
+            (z0 + 1).min(HEIGHTMAP_SIZE - 1),
+        );
+        let (fx, fz) = (u.fract(), v.fract());
+        let top = self.texel(x0, z0) * (1.0 - fx) + self.texel(x1, z0) * fx;
+        let bottom = self.texel(x0, z1) * (1.0 - fx) + self.texel(x1, z1) * fx;
+        top * (1.0 - fz) + bottom * fz
+    }
+
+    /// Upward unit normal from the slopes around the position
+    pub fn normal_at(&self, x: f32, z: f32, extent: f32) -> Vector3<f32> {
+        let step = extent / HEIGHTMAP_SIZE as f32;
+        let dx = self.height_at(x + step, z, extent) - self.height_at(x - step, z, extent);
+        let dz = self.height_at(x, z + step, extent) - self.height_at(x, z - step, extent);
+        Vector3::new(-dx, 2.0 * step, -dz).normalize()
+    }
+
+    /// First crossing of the surface along the ray within `max_distance`
+    ///
+    /// Marched a half texel at a time then refined by bisection
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        extent: f32,
+    ) -> Option<Point3<f32>> {
+        let step = extent / HEIGHTMAP_SIZE as f32 * 0.5;
+        let above = |t: f32| {
+            let p = origin + direction * t;
+            p.y - self.height_at(p.x, p.z, extent)
+        };
+        if above(0.0) < 0.0 {
+            return None;
+        }
+        let mut t = 0.0;
+        while t < max_distance {
+            let next = t + step;
+            if above(next) < 0.0 {
+                let (mut low, mut high) = (t, next);
+                for _ in 0..8 {
+                    let mid = (low + high) * 0.5;
+                    if above(mid) < 0.0 {
+                        high = mid;
+                    } else {
+                        low = mid;
+                    }
+                }
+                return Some(origin + direction * high);
+            }
+            t = next;
+        }
+        None
+    }
+
+    /// Applies the brush for `dt` seconds around a world position, `target` is the flatten height
+    pub fn apply_brush(
+        &mut self,
+        brush: &Brush,
+        center: Point3<f32>,
+        target: f32,
+        dt: f32,
+        extent: f32,
+    ) {
+        let texel_size = extent / HEIGHTMAP_SIZE as f32;
+        let (cu, cv) = Self::to_texels(center.x, center.z, extent);
+        let reach = brush.radius / texel_size;
+        let max = HEIGHTMAP_SIZE as i64 - 1;
+        let (x0, x1) = ((cu - reach).floor() as i64, (cu + reach).ceil() as i64);
+        let (z0, z1) = ((cv - reach).floor() as i64, (cv + reach).ceil() as i64);
+        if x1 < 0 || z1 < 0 || x0 > max || z0 > max {
+            return;
+        }
+        let (x0, x1, z0, z1) = (
+            x0.max(0) as u32,
+            x1.min(max) as u32,
+            z0.max(0) as u32,
+            z1.min(max) as u32,
+        );
+
+        // Smoothing reads the heights from before this step
+        let source = match brush.kind {
+            BrushKind::Smooth => self.heights.clone(),
+            _ => Vec::new(),
+        };
+        for z in z0..=z1 {
+            for x in x0..=x1 {
+                let distance = ((x as f32 - cu).powi(2) + (z as f32 - cv).powi(2)).sqrt() / reach;
+                if distance >= 1.0 {
+                    continue;
+                }
+                // Smooth falloff toward the rim of the brush
+                let falloff = (1.0 - distance * distance).powi(2);
+                let amount = brush.strength * falloff * dt;
+                let index = (z * HEIGHTMAP_SIZE + x) as usize;
+                let height = &mut self.heights[index];
+                match brush.kind {
+                    BrushKind::Raise => *height += amount,
+                    BrushKind::Lower => *height -= amount,
+                    BrushKind::Smooth => {
+                        let mut sum = 0.0;
+                        let mut count = 0.0;
+                        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1), (0, 0)] {
+                            let (nx, nz) = (x as i64 + dx, z as i64 + dz);
+                            if (0..=max).contains(&nx) && (0..=max).contains(&nz) {
+                                sum += source[(nz as u32 * HEIGHTMAP_SIZE + nx as u32) as usize];
+                                count += 1.0;
+                            }
+                        }
+                        *height += (sum / count - *height) * amount.min(1.0);
+                    }
+                    BrushKind::Flatten => *height += (target - *height) * amount.min(1.0),
+                }
+                *height = height.clamp(-HEIGHT_RANGE * 0.5, HEIGHT_RANGE * 0.5);
+            }
+        }
+
+        for cz in z0 / CHUNK_SIZE..=z1 / CHUNK_SIZE {
+            for cx in x0 / CHUNK_SIZE..=x1 / CHUNK_SIZE {
+                self.dirty[(cz * CHUNKS + cx) as usize] = true;
+            }
+        }
+    }
+}
+
+/// Heightmap of the terrain and its copy raymarched by the shader
+///
+/// The cpu side heights answer the ground queries of the gameplay and the editor
+pub struct TerrainHeight {
+    pub map: Heightmap,
+    /// Side of the square covered by the heightmap, follows `TerrainSettings::extent`
+    pub extent: f32,
+    /// Center of the square, moved with the world origin
+    pub center: Vector3<f32>,
+    texture: wgpu::Texture,
+    pub bind_group: Tracked<wgpu::BindGroup>,
+}
+
+impl TerrainHeight {
+    /// Starts from [`DEFAULT_HEIGHTMAP`] when saved, flat otherwise
+    pub fn new(ctx: &GraphicsCtx, extent: f32) -> Self {
+        let path = Heightmap::path(DEFAULT_HEIGHTMAP);
+        let mut map = if path.exists() {
+            Heightmap::load(&path).unwrap_or_else(|e| {
+                eprintln!("Using a flat terrain: {e}");
+                Heightmap::default()
+            })
+        } else {
+            Heightmap::default()
+        };
+        map.dirty.fill(true);
+
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: HEIGHTMAP_SIZE,
+                height: HEIGHTMAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("Terrain heightmap"),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &terrain_height_bind_group_layout(ctx),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+            label: Some("Terrain Height Bind Group"),
+        });
+
+        let mut height = Self {
+            map,
+            extent,
+            center: Vector3::zeros(),
+            texture,
+            bind_group: Tracked::new(bind_group),
+        };
+        height.upload_dirty(ctx);
+        height
+    }
+
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        self.map
+            .height_at(x - self.center.x, z - self.center.z, self.extent)
+    }
+
+    pub fn normal_at(&self, x: f32, z: f32) -> Vector3<f32> {
+        self.map
+            .normal_at(x - self.center.x, z - self.center.z, self.extent)
+    }
+
+    /// See [`Heightmap::raycast`]
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<Point3<f32>> {
+        self.map
+            .raycast(origin - self.center, direction, max_distance, self.extent)
+            .map(|hit| hit + self.center)
+    }
+
+    /// Replaces the heights, every chunk is uploaded again
+    pub fn set(&mut self, mut map: Heightmap) {
+        map.dirty.fill(true);
+        self.map = map;
+    }
+
+    /// Uploads the chunks edited since the last call
+    pub fn upload_dirty(&mut self, ctx: &GraphicsCtx) {
+        for (i, dirty) in self.map.dirty.iter_mut().enumerate() {
+            if !std::mem::take(dirty) {
+                continue;
+            }
+            let (x, z) = (
+                i as u32 % CHUNKS * CHUNK_SIZE,
+                i as u32 / CHUNKS * CHUNK_SIZE,
+            );
+            ctx.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y: z, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&self.map.heights),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 4 * (z * HEIGHTMAP_SIZE + x) as u64,
+                    bytes_per_row: Some(4 * HEIGHTMAP_SIZE),
+                    rows_per_image: Some(CHUNK_SIZE),
+                },
+                wgpu::Extent3d {
+                    width: CHUNK_SIZE,
+                    height: CHUNK_SIZE,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+/// Heights are read with `textureLoad` and filtered by the shader, float textures are not
+/// filterable everywhere
+pub fn terrain_height_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("Terrain Height Bind Group Layout"),
+        })
+}
@@ -3,31 +3,71 @@
   use sdf for terrain generation?
 */
 
-use wgpu::{include_wgsl, BindGroup, DepthStencilState, RenderBundle, RenderBundleDepthStencil};
+use nalgebra::Vector3;
+use wgpu::{DepthStencilState, RenderBundle, RenderBundleDepthStencil};
+
+use heightmap::{terrain_height_bind_group_layout, TerrainHeight};
+use splat::{terrain_material_bind_group_layout, TerrainMaterial};
 
 use super::{
+    bundle::{ResourceId, TrackedBundle},
     camera::{inv_view_proj_bind_group_layout, CameraUniform},
+    churn::StateChanges,
     ctx::GraphicsCtx,
+    depth::{scene_depth_bind_group_layout, SceneDepth},
+    post::HDR_FORMAT,
+    settings::TerrainSettings,
     utils::TextureWrapper,
 };
 
+pub mod heightmap;
+pub mod splat;
+
+/// Recorded by [`record_bundle`], counted each time the bundle runs
+pub const BUNDLE_STATE_CHANGES: StateChanges = StateChanges {
+    pipelines: 1,
+    bind_groups: 4,
+    vertex_buffers: 0,
+    index_buffers: 0,
+    draws: 1,
+};
+
 pub struct TerrainRenderer {
-    pub(super) render_bundle: RenderBundle,
+    pub material: TerrainMaterial,
+    pub height: TerrainHeight,
+    pipeline: wgpu::RenderPipeline,
+    pub(super) render_bundle: TrackedBundle,
 }
 
 impl TerrainRenderer {
-    pub fn new(ctx: &GraphicsCtx, camera: &CameraUniform) -> Self {
+    pub fn new(
+        ctx: &GraphicsCtx,
+        camera: &CameraUniform,
+        scene_depth: &SceneDepth,
+        settings: &TerrainSettings,
+    ) -> Self {
         let pipeline_layout = ctx
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&inv_view_proj_bind_group_layout(ctx)],
+                bind_group_layouts: &[
+                    &inv_view_proj_bind_group_layout(ctx),
+                    &scene_depth_bind_group_layout(ctx),
+                    &terrain_material_bind_group_layout(ctx),
+                    &terrain_height_bind_group_layout(ctx),
+                ],
                 push_constant_ranges: &[],
             });
 
+        // Shadowed by the cloud layer of the sky
         let shader = ctx
             .device
-            .create_shader_module(include_wgsl!("shader.wgsl"));
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Terrain shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(include_str!("shader.wgsl"), include_str!("../clouds.wgsl")).into(),
+                ),
+            });
 
         let pipeline = ctx
             .device
@@ -65,7 +105,7 @@ impl TerrainRenderer {
                     module: &shader,
                     entry_point: Some("fs_main"),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: ctx.surface_format,
+                        format: HDR_FORMAT,
                         blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
@@ -75,28 +115,93 @@ impl TerrainRenderer {
                 cache: None,
             });
 
-        let mut encoder =
-            ctx.device
-                .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
-                    label: None,
-                    color_formats: &[Some(ctx.surface_format)],
-                    depth_stencil: Some(RenderBundleDepthStencil {
-                        depth_read_only: false,
-                        stencil_read_only: false,
-                        format: TextureWrapper::DEPTH_FORMAT,
-                    }),
-                    multiview: None,
-                    sample_count: 1,
-                });
-
-        encoder.set_pipeline(&pipeline);
-        encoder.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
-        encoder.draw(0..6, 0..1);
-
-        let render_bundle = encoder.finish(&wgpu::RenderBundleDescriptor {
-            label: Some("TerrainRenderer"),
-        });
+        let material = TerrainMaterial::new(ctx, settings);
+        let height = TerrainHeight::new(ctx, settings.extent);
+        let render_bundle =
+            TrackedBundle::new(&dependencies(scene_depth, &material, &height), || {
+                record_bundle(ctx, &pipeline, camera, scene_depth, &material, &height)
+            });
+
+        Self {
+            material,
+            height,
+            pipeline,
+            render_bundle,
+        }
+    }
 
-        Self { render_bundle }
+    /// Keeps the terrain in place when the world origin moves by `shift`
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        let center = self.height.center - shift;
+        self.height.center = center;
+        self.material.set_center([center.x, center.z]);
+    }
+
+    /// Rerecords the bundle when one of the bind groups it binds was recreated, the scene depth
+    /// is with the viewport
+    pub fn refresh_bundle(
+        &mut self,
+        ctx: &GraphicsCtx,
+        camera: &CameraUniform,
+        scene_depth: &SceneDepth,
+    ) {
+        let dependencies = dependencies(scene_depth, &self.material, &self.height);
+        self.render_bundle.refresh(&dependencies, || {
+            record_bundle(
+                ctx,
+                &self.pipeline,
+                camera,
+                scene_depth,
+                &self.material,
+                &self.height,
+            )
+        });
     }
 }
+
+/// Recreated bind groups recorded in the bundle, the camera ones live as long as the renderer
+fn dependencies(
+    scene_depth: &SceneDepth,
+    material: &TerrainMaterial,
+    height: &TerrainHeight,
+) -> [ResourceId; 3] {
+    [
+        scene_depth.bind_group.id(),
+        material.bind_group.id(),
+        height.bind_group.id(),
+    ]
+}
+
+fn record_bundle(
+    ctx: &GraphicsCtx,
+    pipeline: &wgpu::RenderPipeline,
+    camera: &CameraUniform,
+    scene_depth: &SceneDepth,
+    material: &TerrainMaterial,
+    height: &TerrainHeight,
+) -> RenderBundle {
+    let mut encoder =
+        ctx.device
+            .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: None,
+                color_formats: &[Some(HDR_FORMAT)],
+                depth_stencil: Some(RenderBundleDepthStencil {
+                    depth_read_only: false,
+                    stencil_read_only: false,
+                    format: TextureWrapper::DEPTH_FORMAT,
+                }),
+                multiview: None,
+                sample_count: 1,
+            });
+
+    encoder.set_pipeline(pipeline);
+    encoder.set_bind_group(0, &camera.inv_view_proj_bindgroup, &[]);
+    encoder.set_bind_group(1, &*scene_depth.bind_group, &[]);
+    encoder.set_bind_group(2, &*material.bind_group, &[]);
+    encoder.set_bind_group(3, &*height.bind_group, &[]);
+    encoder.draw(0..6, 0..1);
+
+    encoder.finish(&wgpu::RenderBundleDescriptor {
+        label: Some("TerrainRenderer"),
+    })
+}
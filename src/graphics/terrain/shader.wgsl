@@ -24,10 +24,73 @@ var<uniform> inv_proj: mat4x4f;
 @group(0) @binding(2)
 var<uniform> viewport_size: vec2<u32>;
 
+// Matches `RawFrameConstants`
+struct FrameConstants {
+    time: f32,
+    delta_time: f32,
+    frame_index: u32,
+    exposure: f32,
+    screen_size: vec2f,
+    inv_screen_size: vec2f,
+    snap_resolution: vec2f,
+    affine_textures: u32,
+    camera_relative: u32,
+    eye: vec3f,
+    wetness: f32,
+    ambient: vec3f,
+    sun_direction: vec3f,
+    cloud_coverage: f32,
+    cloud_velocity: vec2f,
+    cloud_origin: vec2f,
+    cloud_size: f32,
+    cloud_altitude: f32,
+    cloud_softness: f32,
+    cloud_shadow: f32,
+};
 
+@group(0) @binding(3)
+var<uniform> frame: FrameConstants;
+
+// Copy of the depth of the previous frame, the terrain is the first scene pass
+@group(1) @binding(0)
+var scene_depth: texture_depth_2d;
+
+// Matches `RawTerrainParams`
+struct TerrainParams {
+    // Tint in rgb and tiling in w, per layer
+    layers: array<vec4f, 4>,
+    extent: f32,
+    triplanar_start: f32,
+    triplanar_end: f32,
+    center: vec2f,
+};
 
-const MAX_STEPS: u32 = 128;
+@group(2) @binding(0)
+var<uniform> params: TerrainParams;
+// Layer weights over the xz plane, one channel per layer
+@group(2) @binding(1)
+var t_splat: texture_2d<f32>;
+@group(2) @binding(2)
+var s_splat: sampler;
+@group(2) @binding(3)
+var t_albedo: texture_2d_array<f32>;
+@group(2) @binding(4)
+var s_albedo: sampler;
+@group(2) @binding(5)
+var t_normal: texture_2d_array<f32>;
+@group(2) @binding(6)
+var s_normal: sampler;
+
+// Heights in world units over the same square as the splat map
+@group(3) @binding(0)
+var t_height: texture_2d<f32>;
+
+const MAX_STEPS: u32 = 256;
+// Heightfields are not distance fields, steps are shortened so steep slopes are not overshot
+const HEIGHTFIELD_STEP: f32 = 0.5;
 const EPS: f32 = 0.01;
+// Sharpness of the blend between the triplanar projections
+const TRIPLANAR_POWER: f32 = 4.0;
 
 @fragment
 fn fs_main(@builtin(position) frag_coord: vec4f) -> FragOutput {
@@ -47,16 +110,17 @@ fn fs_main(@builtin(position) frag_coord: vec4f) -> FragOutput {
 
     var t = 0.;
     var first = true;
+    var hit = false;
     for (var i = 0u; i < MAX_STEPS; i++) {
         let p = ray_origin + t * ray_dir;
-        let d = sdf_torus(p, 3., .5); 
+        let d = sdf_scene(p);
         
         if (d < EPS) {
             let world_pos = t * view_dir;
 
-            out.color = vec4f((p+2.)/4., 1.0); // temp color
             let depth = (far+near)/(far-near) + 2.*far*near/(far-near) / world_pos.z;
             out.depth = select(depth, 0.0, first);
+            hit = true;
 
             break;
         }
@@ -69,14 +133,113 @@ fn fs_main(@builtin(position) frag_coord: vec4f) -> FragOutput {
         first = false;
     }
 
+    if (hit) {
+        // World units covered by a pixel at the hit, the mip levels are picked from it since the
+        // raymarching loop leaves no derivatives
+        let pixel_size = t * 2. * inv_proj[1][1] / f32(viewport_size.y);
+        let p = ray_origin + t * ray_dir;
+        out.color = vec4f(shade(p, sdf_normal(p), ray_dir, pixel_size), 1.0);
+    }
+
     return out;
 }
 
+struct LayerSample {
+    albedo: vec3f,
+    // Tangent space, z points away from the projection plane
+    normal: vec3f,
+};
+
+// Layers blended by the splat weights, sampled with the projection uv in world units
+fn sample_layers(uv: vec2f, weights: vec4f, pixel_size: f32) -> LayerSample {
+    let texels = f32(textureDimensions(t_albedo).x);
+    var blended = LayerSample(vec3f(0.), vec3f(0.));
+    for (var i = 0u; i < 4u; i++) {
+        let layer = params.layers[i];
+        let lod = log2(max(pixel_size * layer.w * texels, 1.));
+        let layer_uv = uv * layer.w;
+        let albedo = textureSampleLevel(t_albedo, s_albedo, layer_uv, i, lod).rgb * layer.rgb;
+        let normal = textureSampleLevel(t_normal, s_normal, layer_uv, i, lod).xyz * 2. - 1.;
+        blended.albedo += albedo * weights[i];
+        blended.normal += normal * weights[i];
+    }
+    return blended;
+}
+
+// Top down projection on gentle slopes, triplanar on steep ones. The normal maps are blended with
+// the whiteout method
+fn shade(p: vec3f, n: vec3f, ray_dir: vec3f, pixel_size: f32) -> vec3f {
+    // Relative to the terrain, the tiling stays put when the world origin moves
+    let local = p - vec3f(params.center.x, 0., params.center.y);
+    let splat_uv = local.xz / params.extent + 0.5;
+    let splat = textureSampleLevel(t_splat, s_splat, splat_uv, 0.);
+    let weights = splat / max(dot(splat, vec4f(1.)), 1e-4);
+
+    let steep = smoothstep(params.triplanar_start, params.triplanar_end, 1. - abs(n.y));
+    var triplanar = pow(abs(n), vec3f(TRIPLANAR_POWER));
+    triplanar /= dot(triplanar, vec3f(1.));
+    let blend = mix(vec3f(0., 1., 0.), triplanar, steep);
+
+    // Grazing views stretch the footprint of a pixel
+    let footprint = pixel_size / max(abs(dot(n, ray_dir)), 0.1);
+    let x = sample_layers(local.zy, weights, footprint);
+    let y = sample_layers(local.xz, weights, footprint);
+    let z = sample_layers(local.xy, weights, footprint);
+
+    let albedo = x.albedo * blend.x + y.albedo * blend.y + z.albedo * blend.z;
+    let nx = vec3f(x.normal.xy + n.zy, abs(x.normal.z) * n.x).zyx;
+    let ny = vec3f(y.normal.xy + n.xz, abs(y.normal.z) * n.y).xzy;
+    let nz = vec3f(z.normal.xy + n.xy, abs(z.normal.z) * n.z);
+    let normal = normalize(nx * blend.x + ny * blend.y + nz * blend.z);
+
+    let sunlit = cloud_shadow(p);
+    let diffuse = max(dot(normal, frame.sun_direction), 0.) * sunlit;
+    // Wet flat ground darkens and reflects the sun, steep slopes drain
+    let wet = frame.wetness * smoothstep(0.3, 0.9, n.y);
+    let half_dir = normalize(frame.sun_direction - ray_dir);
+    let specular = pow(max(dot(normal, half_dir), 0.), 64.) * wet * sunlit;
+    return albedo * mix(1., 0.55, wet) * (frame.ambient + diffuse) + specular;
+}
+
 
 fn pdiv(v: vec4f) -> vec3f {
     return v.xyz / v.w;
 }
 
+// Filtered by hand, matches `Heightmap::height_at`
+fn terrain_height(xz: vec2f) -> f32 {
+    let size = vec2i(textureDimensions(t_height));
+    let texel = clamp(((xz - params.center) / params.extent + 0.5) * vec2f(size) - 0.5, vec2f(0.), vec2f(size - 1));
+    let base = vec2i(floor(texel));
+    let next = min(base + 1, size - 1);
+    let f = texel - vec2f(base);
+    let top = mix(
+        textureLoad(t_height, base, 0).r,
+        textureLoad(t_height, vec2i(next.x, base.y), 0).r,
+        f.x,
+    );
+    let bottom = mix(
+        textureLoad(t_height, vec2i(base.x, next.y), 0).r,
+        textureLoad(t_height, next, 0).r,
+        f.x,
+    );
+    return mix(top, bottom, f.y);
+}
+
+fn sdf_scene(p: vec3f) -> f32 {
+    return (p.y - terrain_height(p.xz)) * HEIGHTFIELD_STEP;
+}
+
+// Central differences of the distance field
+fn sdf_normal(p: vec3f) -> vec3f {
+    let e = vec2f(EPS, 0.);
+    return normalize(vec3f(
+        sdf_scene(p + e.xyy) - sdf_scene(p - e.xyy),
+        sdf_scene(p + e.yxy) - sdf_scene(p - e.yxy),
+        sdf_scene(p + e.yyx) - sdf_scene(p - e.yyx),
+    ));
+}
+
 fn sdf_sphere(p: vec3f, radius: f32) -> f32 {
     return length(p) - radius;
 }
@@ -0,0 +1,368 @@
+use image::{imageops::FilterType, EncodableLayout, RgbaImage};
+use nalgebra::Point3;
+
+use crate::{
+    graphics::{
+        buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+        bundle::Tracked,
+        ctx::GraphicsCtx,
+        sampler::SamplerDesc,
+        settings::{TerrainLayer, TerrainSettings, TERRAIN_LAYER_COUNT},
+        utils::{ColorSpace, TextureWrapper},
+    },
+    ASSETS,
+};
+
+/// Width and height of every layer texture, layers are resized to it on load
+pub const LAYER_SIZE: u32 = 512;
+const LAYER_MIPS: u32 = LAYER_SIZE.ilog2() + 1;
+/// Texels per side of the splat map
+pub const SPLAT_SIZE: u32 = 256;
+
+const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+/// Matches `TerrainParams` in the terrain shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RawTerrainParams {
+    /// Tint in rgb and tiling in w, per layer
+    layers: [[f32; 4]; TERRAIN_LAYER_COUNT],
+    extent: f32,
+    triplanar_start: f32,
+    triplanar_end: f32,
+    _padding: f32,
+    /// Relative to the world origin, see [`crate::graphics::origin::WorldOrigin`]
+    center: [f32; 2],
+    _padding2: [f32; 2],
+}
+
+impl RawTerrainParams {
+    fn new(settings: &TerrainSettings, center: [f32; 2]) -> Self {
+        Self {
+            layers: settings.layers.each_ref().map(|layer| {
+                let [r, g, b]: [f32; 3] = layer.tint.into();
+                [r, g, b, layer.tiling]
+            }),
+            extent: settings.extent,
+            triplanar_start: settings.triplanar_start,
+            triplanar_end: settings.triplanar_end.max(settings.triplanar_start + 1e-3),
+            _padding: 0.0,
+            center,
+            _padding2: [0.0; 2],
+        }
+    }
+}
+
+/// Weights of the terrain layers over the xz plane, one channel per layer
+///
+/// Texels are laid out row by row from -x -z, the weights are normalized by the shader
+#[derive(Debug, Clone)]
+pub struct SplatMap {
+    pub weights: Vec<[u8; 4]>,
+    /// Set by edits, cleared by [`TerrainMaterial::upload_dirty`]
+    dirty: bool,
+}
+
+/// Covered by the first layer
+impl Default for SplatMap {
+    fn default() -> Self {
+        Self {
+            weights: vec![[255, 0, 0, 0]; (SPLAT_SIZE * SPLAT_SIZE) as usize],
+            dirty: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PaintBrush {
+    /// Layer whose weight is raised, the others fade out
+    pub layer: usize,
+    /// World units
+    pub radius: f32,
+    /// Fraction of the radius painted at full strength before fading out
+    pub hardness: f32,
+    /// Blend toward the layer per second
+    pub strength: f32,
+}
+
+impl SplatMap {
+    /// Replaces the weights, uploaded with the next changes
+    pub fn set(&mut self, weights: Vec<[u8; 4]>) {
+        self.weights = weights;
+        self.dirty = true;
+    }
+
+    /// Paints for `dt` seconds around a world position, `extent` being the side of the square
+    pub fn paint(&mut self, brush: &PaintBrush, center: Point3<f32>, dt: f32, extent: f32) {
+        let size = SPLAT_SIZE as f32;
+        let (cu, cv) = (
+            (center.x / extent + 0.5) * size - 0.5,
+            (center.z / extent + 0.5) * size - 0.5,
+        );
+        let reach = brush.radius / extent * size;
+        let max = SPLAT_SIZE as i64 - 1;
+        let (x0, x1) = ((cu - reach).floor() as i64, (cu + reach).ceil() as i64);
+        let (z0, z1) = ((cv - reach).floor() as i64, (cv + reach).ceil() as i64);
+        if x1 < 0 || z1 < 0 || x0 > max || z0 > max {
+            return;
+        }
+
+        let mut target = [0.0; TERRAIN_LAYER_COUNT];
+        target[brush.layer.min(TERRAIN_LAYER_COUNT - 1)] = 1.0;
+        let soft = (1.0 - brush.hardness).max(1e-3);
+        for z in z0.max(0)..=z1.min(max) {
+            for x in x0.max(0)..=x1.min(max) {
+                let distance = ((x as f32 - cu).powi(2) + (z as f32 - cv).powi(2)).sqrt() / reach;
+                let falloff = ((1.0 - distance) / soft).clamp(0.0, 1.0);
+                let blend = (brush.strength * falloff * dt).min(1.0);
+                if blend <= 0.0 {
+                    continue;
+                }
+                let texel = &mut self.weights[(z * SPLAT_SIZE as i64 + x) as usize];
+                let total = texel.iter().map(|w| *w as f32).sum::<f32>().max(1.0);
+                for (weight, goal) in texel.iter_mut().zip(target) {
+                    let normalized = *weight as f32 / total;
+                    let painted = normalized + (goal - normalized) * blend;
+                    *weight = (painted * 255.0).round() as u8;
+                }
+            }
+        }
+        self.dirty = true;
+    }
+}
+
+/// Splat map and tiling layers of the terrain, bound as its material group
+pub struct TerrainMaterial {
+    pub splat_map: SplatMap,
+    params: UniformBuffer<RawTerrainParams>,
+    /// Cpu side copy of the params, written again when dirty
+    raw_params: RawTerrainParams,
+    params_dirty: bool,
+    splat: TextureWrapper,
+    albedo: TextureWrapper,
+    normal: TextureWrapper,
+    pub bind_group: Tracked<wgpu::BindGroup>,
+}
+
+impl TerrainMaterial {
+    pub fn new(ctx: &GraphicsCtx, settings: &TerrainSettings) -> Self {
+        let splat_map = SplatMap::default();
+        let splat = TextureWrapper::new_rgba_2d(
+            "Terrain splat map",
+            ctx,
+            (SPLAT_SIZE, SPLAT_SIZE),
+            bytemuck::cast_slice(&splat_map.weights),
+            ColorSpace::Linear,
+            SamplerDesc::LINEAR,
+        );
+
+        let albedo = new_layers("Terrain albedo", ctx, wgpu::TextureFormat::Rgba8UnormSrgb);
+        let normal = new_layers("Terrain normal", ctx, wgpu::TextureFormat::Rgba8Unorm);
+        let raw_params = RawTerrainParams::new(settings, [0.0; 2]);
+        let params = UniformBuffer::new("Terrain params", ctx, &raw_params);
+        let bind_group = terrain_material_bind_group(ctx, &params, &splat, &albedo, &normal);
+
+        let material = Self {
+            splat_map,
+            params,
+            raw_params,
+            params_dirty: false,
+            splat,
+            albedo,
+            normal,
+            bind_group: Tracked::new(bind_group),
+        };
+        for (i, layer) in settings.layers.iter().enumerate() {
+            material.load_layer(ctx, i as u32, layer);
+        }
+        material
+    }
+
+    /// Reloads the textures of the layers whose names changed
+    pub fn apply_settings(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &TerrainSettings,
+        previous: &TerrainSettings,
+    ) {
+        self.raw_params = RawTerrainParams::new(settings, self.raw_params.center);
+        self.params.write(ctx, &self.raw_params);
+        for (i, (layer, previous)) in settings.layers.iter().zip(&previous.layers).enumerate() {
+            if layer.albedo != previous.albedo || layer.normal != previous.normal {
+                self.load_layer(ctx, i as u32, layer);
+            }
+        }
+    }
+
+    /// Center of the terrain square on the xz plane
+    pub fn set_center(&mut self, center: [f32; 2]) {
+        self.raw_params.center = center;
+        self.params_dirty = true;
+    }
+
+    /// Uploads the params and the splat map if they were edited since the last call
+    pub fn upload_dirty(&mut self, ctx: &GraphicsCtx) {
+        if std::mem::take(&mut self.params_dirty) {
+            self.params.write(ctx, &self.raw_params);
+        }
+        if !std::mem::take(&mut self.splat_map.dirty) {
+            return;
+        }
+        write_layer(
+            ctx,
+            &self.splat,
+            0,
+            0,
+            SPLAT_SIZE,
+            bytemuck::cast_slice(&self.splat_map.weights),
+        );
+    }
+
+    fn load_layer(&self, ctx: &GraphicsCtx, index: u32, layer: &TerrainLayer) {
+        let albedo = layer_image(layer.albedo.as_deref(), [255; 4]);
+        let normal = layer_image(layer.normal.as_deref(), FLAT_NORMAL);
+        for (texture, image) in [(&self.albedo, albedo), (&self.normal, normal)] {
+            for mip in 0..LAYER_MIPS {
+                let size = LAYER_SIZE >> mip;
+                let resized = image::imageops::resize(&image, size, size, FilterType::Triangle);
+                write_layer(ctx, texture, index, mip, size, resized.as_bytes());
+            }
+        }
+    }
+}
+
+/// Falls back to a single `fill` texel when the texture is missing
+fn layer_image(name: Option<&str>, fill: [u8; 4]) -> RgbaImage {
+    let fallback = || RgbaImage::from_pixel(1, 1, image::Rgba(fill));
+    let Some(name) = name else {
+        return fallback();
+    };
+    match ASSETS.tree().textures.get(name) {
+        Some(file) => file.image().to_rgba8(),
+        None => {
+            eprintln!("No terrain layer texture named {name}");
+            fallback()
+        }
+    }
+}
+
+fn new_layers(label: &str, ctx: &GraphicsCtx, format: wgpu::TextureFormat) -> TextureWrapper {
+    let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: LAYER_SIZE,
+            height: LAYER_SIZE,
+            depth_or_array_layers: TERRAIN_LAYER_COUNT as u32,
+        },
+        mip_level_count: LAYER_MIPS,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some(label),
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let sampler = ctx.sampler(SamplerDesc::LINEAR.with_wrap(wgpu::AddressMode::Repeat));
+    TextureWrapper {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+fn write_layer(
+    ctx: &GraphicsCtx,
+    texture: &TextureWrapper,
+    layer: u32,
+    mip_level: u32,
+    size: u32,
+    texels: &[u8],
+) {
+    ctx.queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture.texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        texels,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * size),
+            rows_per_image: Some(size),
+        },
+        wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+pub fn terrain_material_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    let fragment = wgpu::ShaderStages::FRAGMENT;
+    let [splat, splat_sampler] =
+        TextureWrapper::layout_entries(1, fragment, wgpu::TextureViewDimension::D2);
+    let [albedo, albedo_sampler] =
+        TextureWrapper::layout_entries(3, fragment, wgpu::TextureViewDimension::D2Array);
+    let [normal, normal_sampler] =
+        TextureWrapper::layout_entries(5, fragment, wgpu::TextureViewDimension::D2Array);
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: fragment,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                splat,
+                splat_sampler,
+                albedo,
+                albedo_sampler,
+                normal,
+                normal_sampler,
+            ],
+            label: Some("Terrain Material Bind Group Layout"),
+        })
+}
+
+fn terrain_material_bind_group(
+    ctx: &GraphicsCtx,
+    params: &UniformBuffer<RawTerrainParams>,
+    splat: &TextureWrapper,
+    albedo: &TextureWrapper,
+    normal: &TextureWrapper,
+) -> wgpu::BindGroup {
+    let [splat, splat_sampler] = splat.bind_group_entries(1);
+    let [albedo, albedo_sampler] = albedo.bind_group_entries(3);
+    let [normal, normal_sampler] = normal.bind_group_entries(5);
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &terrain_material_bind_group_layout(ctx),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            },
+            splat,
+            splat_sampler,
+            albedo,
+            albedo_sampler,
+            normal,
+            normal_sampler,
+        ],
+        label: Some("Terrain Material Bind Group"),
+    })
+}
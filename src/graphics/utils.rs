@@ -1,19 +1,186 @@
-use crate::graphics::GraphicsCtx;
+use std::sync::Arc;
+
+use crate::graphics::{sampler::SamplerDesc, GraphicsCtx};
 
 pub struct TextureWrapper {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    pub sampler: Arc<wgpu::Sampler>,
+}
+
+/// Color maps are authored in sRGB, data textures (normals, roughness, metallic...) must be
+/// sampled as is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(&self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// Guesses the color space from common naming conventions, e.g. `Astronaut_Normal`
+    pub fn from_texture_name(name: &str) -> Self {
+        const DATA_SUFFIXES: [&str; 7] = [
+            "_normal",
+            "_roughness",
+            "_metallic",
+            "_metalness",
+            "_occlusion",
+            "_height",
+            "_data",
+        ];
+        let name = name.to_ascii_lowercase();
+        if DATA_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        }
+    }
+}
+
+/// Storage precision of floating point textures
+///
+/// `Full` is not filterable unless `wgpu::Features::FLOAT32_FILTERABLE` is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HdrPrecision {
+    #[default]
+    Half,
+    Full,
+}
+
+impl HdrPrecision {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            HdrPrecision::Half => wgpu::TextureFormat::Rgba16Float,
+            HdrPrecision::Full => wgpu::TextureFormat::Rgba32Float,
+        }
+    }
 }
 
 impl TextureWrapper {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// For the passes masking their draws, see [`Self::new_depth_stencil`]
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+    /// Width and height of the texture
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
 
     pub fn new_rgba_2d(
+        label: &str,
+        ctx: &GraphicsCtx,
+        size: (u32, u32),
+        data: &[u8],
+        color_space: ColorSpace,
+        sampler: SamplerDesc,
+    ) -> Self {
+        Self::new_2d(
+            label,
+            ctx,
+            size,
+            data,
+            color_space.rgba8_format(),
+            4,
+            &[],
+            sampler,
+            1,
+        )
+    }
+
+    /// Rgba texture holding pixels of both color spaces, its view reads them as is and
+    /// [`Self::view_as`] gives one decoding them from sRGB
+    ///
+    /// `data` fills the first mip level, the following ones are left to the caller
+    pub fn new_rgba_2d_mixed(
+        label: &str,
+        ctx: &GraphicsCtx,
+        size: (u32, u32),
+        data: &[u8],
+        sampler: SamplerDesc,
+        mip_level_count: u32,
+    ) -> Self {
+        Self::new_2d(
+            label,
+            ctx,
+            size,
+            data,
+            ColorSpace::Linear.rgba8_format(),
+            4,
+            &[ColorSpace::Srgb.rgba8_format()],
+            sampler,
+            mip_level_count,
+        )
+    }
+
+    /// View of a texture created by [`Self::new_rgba_2d_mixed`] decoding its pixels as `color_space`
+    pub fn view_as(&self, color_space: ColorSpace) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(color_space.rgba8_format()),
+            ..Default::default()
+        })
+    }
+
+    /// Upload path for floating point (hdr/exr) rgba pixels
+    pub fn new_hdr_2d(
+        label: &str,
+        ctx: &GraphicsCtx,
+        size: (u32, u32),
+        data: &[f32],
+        precision: HdrPrecision,
+        sampler: SamplerDesc,
+    ) -> Self {
+        match precision {
+            HdrPrecision::Half => {
+                let half: Vec<u16> = data.iter().map(|v| f32_to_f16(*v)).collect();
+                let data = bytemuck::cast_slice(&half);
+                Self::new_2d(
+                    label,
+                    ctx,
+                    size,
+                    data,
+                    precision.format(),
+                    8,
+                    &[],
+                    sampler,
+                    1,
+                )
+            }
+            HdrPrecision::Full => {
+                let data = bytemuck::cast_slice(data);
+                Self::new_2d(
+                    label,
+                    ctx,
+                    size,
+                    data,
+                    precision.format(),
+                    16,
+                    &[],
+                    sampler,
+                    1,
+                )
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_2d(
         label: &str,
         ctx: &GraphicsCtx,
         (width, height): (u32, u32),
         data: &[u8],
+        format: wgpu::TextureFormat,
+        bytes_per_pixel: u32,
+        view_formats: &[wgpu::TextureFormat],
+        sampler: SamplerDesc,
+        mip_level_count: u32,
     ) -> Self {
         let texture_size = wgpu::Extent3d {
             width,
@@ -22,13 +189,13 @@ impl TextureWrapper {
         };
         let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1, //TODO: mipmaps
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some(&format!("Diffuse Texture: {}", label)),
-            view_formats: &[],
+            label: Some(&format!("Texture: {}", label)),
+            view_formats,
         });
 
         ctx.queue.write_texture(
@@ -41,34 +208,240 @@ impl TextureWrapper {
             data,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * width),
+                bytes_per_row: Some(bytes_per_pixel * width),
                 rows_per_image: Some(height),
             },
             texture_size,
         );
 
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&format!("Texture Sampler: {}", label)),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
+        let sampler = ctx.sampler(sampler);
+
+        Self {
+            texture,
+            view: texture_view,
+            sampler,
+        }
+    }
+
+    /// Color attachment that can be sampled by the following passes
+    pub fn new_render_target(
+        label: &str,
+        ctx: &GraphicsCtx,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        sampler: SamplerDesc,
+        extra_usage: wgpu::TextureUsages,
+    ) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | extra_usage,
+            label: Some(&format!("Render Target: {}", label)),
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = ctx.sampler(sampler);
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// 6 layers 2d texture viewed as a cube, layers are ordered +X, -X, +Y, -Y, +Z, -Z
+    pub fn new_cubemap(
+        label: &str,
+        ctx: &GraphicsCtx,
+        face_size: u32,
+        format: wgpu::TextureFormat,
+        extra_usages: wgpu::TextureUsages,
+    ) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | extra_usages,
+            label: Some(&format!("Cubemap: {}", label)),
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("Cubemap View: {}", label)),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
             ..Default::default()
         });
+        let sampler = ctx.sampler(SamplerDesc::LINEAR);
 
         Self {
             texture,
-            view: texture_view,
+            view,
             sampler,
         }
     }
 
-    pub fn new_depth(label: &str, ctx: &GraphicsCtx, (width, height): (u32, u32)) -> Self {
+    /// Volume texture, `data` may be empty when the texture is filled on the gpu
+    pub fn new_3d(
+        label: &str,
+        ctx: &GraphicsCtx,
+        (width, height, depth): (u32, u32, u32),
+        format: wgpu::TextureFormat,
+        data: &[u8],
+        extra_usages: wgpu::TextureUsages,
+        sampler: SamplerDesc,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth,
+        };
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | extra_usages,
+            label: Some(&format!("3d Texture: {}", label)),
+            view_formats: &[],
+        });
+
+        if !data.is_empty() {
+            let bytes_per_pixel = format
+                .block_copy_size(None)
+                .expect("3d texture format must have a single aspect");
+            ctx.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_pixel * width),
+                    rows_per_image: Some(height),
+                },
+                size,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("3d Texture View: {}", label)),
+            dimension: Some(wgpu::TextureViewDimension::D3),
+            ..Default::default()
+        });
+        let sampler = ctx.sampler(sampler);
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Layout of a filterable texture at `binding` followed by its sampler at `binding + 1`
+    pub fn layout_entries(
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+
+    /// Entries matching [`TextureWrapper::layout_entries`]
+    pub fn bind_group_entries(&self, binding: u32) -> [wgpu::BindGroupEntry<'_>; 2] {
+        [
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    pub fn new_depth(label: &str, ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        // Copied out for picking and for the sampled scene depth
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC;
+        Self::depth_with_usage(label, ctx, size, usage)
+    }
+
+    /// Destination of depth copies, only ever sampled
+    pub fn new_depth_copy(label: &str, ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        Self::depth_with_usage(label, ctx, size, usage)
+    }
+
+    /// Depth with a stencil, in [`Self::DEPTH_STENCIL_FORMAT`], only ever attached
+    pub fn new_depth_stencil(label: &str, ctx: &GraphicsCtx, size: (u32, u32)) -> Self {
+        Self::depth_with_format(
+            label,
+            ctx,
+            size,
+            Self::DEPTH_STENCIL_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    }
+
+    fn depth_with_usage(
+        label: &str,
+        ctx: &GraphicsCtx,
+        size: (u32, u32),
+        usage: wgpu::TextureUsages,
+    ) -> Self {
+        Self::depth_with_format(label, ctx, size, Self::DEPTH_FORMAT, usage)
+    }
+
+    fn depth_with_format(
+        label: &str,
+        ctx: &GraphicsCtx,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Self {
         let size = wgpu::Extent3d {
             width,
             height,
@@ -81,13 +454,13 @@ impl TextureWrapper {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            usage,
             view_formats: &[],
         };
         let texture = ctx.device.create_texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
+        let sampler = Arc::new(ctx.device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some(&format!("Depth Sampler: {}", label)),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -99,7 +472,7 @@ impl TextureWrapper {
             lod_max_clamp: 100.0,
             compare: Some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
-        });
+        }));
         Self {
             texture,
             view,
@@ -107,3 +480,49 @@ impl TextureWrapper {
         }
     }
 }
+
+/// Truncating f32 to IEEE 754 half conversion
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        // Infinity or NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f {
+        sign | 0x7c00
+    } else if exp <= 0 {
+        if exp < -10 {
+            return sign;
+        }
+        // Subnormal half
+        let mantissa = mantissa | 0x80_0000;
+        sign | (mantissa >> (14 - exp)) as u16
+    } else {
+        sign | ((exp as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
+
+/// Same test on both faces, the reference is set on the render pass
+pub fn stencil_state(
+    compare: wgpu::CompareFunction,
+    pass_op: wgpu::StencilOperation,
+) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: !0,
+        write_mask: !0,
+    }
+}
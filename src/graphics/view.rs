@@ -0,0 +1,212 @@
+use wgpu::include_wgsl;
+
+use super::{
+    camera::{Camera, CameraUniform, Projection},
+    color_attachment,
+    ctx::GraphicsCtx,
+    entities::{
+        draws::DrawGenerator,
+        model::ModelsBuffer,
+        renderer::{EntitiesPass, EntitiesRenderer},
+    },
+    frame::FrameConstants,
+    light::LightsUniform,
+    motion::VELOCITY_FORMAT,
+    post::HDR_FORMAT,
+    sampler::SamplerDesc,
+    scene_render_pass,
+    utils::TextureWrapper,
+};
+
+/// Format of [`OffscreenView::texture`], sampled as is by the egui renderer
+pub const VIEW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Scene seen from a camera of its own, rendered into a small texture
+///
+/// The view has its own camera and culled draws, the opaque entities pass is drawn a second time
+/// into its targets. Fading materials and the terrain, raymarched for the main camera, are left out
+pub struct OffscreenView {
+    /// Tonemapped view
+    pub texture: TextureWrapper,
+    hdr: TextureWrapper,
+    velocity: TextureWrapper,
+    depth: TextureWrapper,
+    camera: CameraUniform,
+    draws: DrawGenerator,
+    resolve_bind_group: wgpu::BindGroup,
+    resolve_pipeline: wgpu::RenderPipeline,
+    label: &'static str,
+    size: (u32, u32),
+}
+
+impl OffscreenView {
+    pub fn new(
+        label: &'static str,
+        ctx: &GraphicsCtx,
+        size: (u32, u32),
+        frame: &FrameConstants,
+        models: &ModelsBuffer,
+    ) -> Self {
+        let hdr = new_target(label, ctx, size, HDR_FORMAT);
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&resolve_bind_group_layout(ctx)],
+                push_constant_ranges: &[],
+            });
+        let shader = ctx.device.create_shader_module(include_wgsl!("view.wgsl"));
+        let resolve_pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Offscreen view resolve"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: VIEW_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            texture: new_target(label, ctx, size, VIEW_FORMAT),
+            velocity: new_target(label, ctx, size, VELOCITY_FORMAT),
+            depth: TextureWrapper::new_depth(label, ctx, size),
+            resolve_bind_group: resolve_bind_group(ctx, &hdr),
+            hdr,
+            camera: CameraUniform::new(ctx, frame),
+            draws: DrawGenerator::new(ctx, models),
+            resolve_pipeline,
+            label,
+            size,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Returns true when the targets were recreated, the texture must be registered again where it
+    /// is displayed
+    pub fn resize(&mut self, ctx: &GraphicsCtx, size: (u32, u32)) -> bool {
+        if size == self.size {
+            return false;
+        }
+        self.size = size;
+        self.hdr = new_target(self.label, ctx, size, HDR_FORMAT);
+        self.texture = new_target(self.label, ctx, size, VIEW_FORMAT);
+        self.velocity = new_target(self.label, ctx, size, VELOCITY_FORMAT);
+        self.depth = TextureWrapper::new_depth(self.label, ctx, size);
+        self.resolve_bind_group = resolve_bind_group(ctx, &self.hdr);
+        true
+    }
+
+    pub fn update_proj(&mut self, ctx: &GraphicsCtx, proj: &Projection) {
+        self.camera.update_proj(ctx, proj);
+    }
+
+    /// Moves the view to `camera` and syncs the draws with the instances, before recording
+    pub fn update_view(&mut self, ctx: &GraphicsCtx, models: &ModelsBuffer, camera: &Camera) {
+        self.draws.apply_changes(ctx, models);
+        self.camera.update_view(ctx, camera);
+    }
+
+    /// Must be recorded after the instances are animated for the frame
+    pub fn record(
+        &self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        entities: &EntitiesRenderer,
+        lights: &LightsUniform,
+    ) {
+        entities.generate_draws_into(ctx, encoder, &self.camera, &self.draws);
+        let mut render_pass = scene_render_pass(
+            encoder,
+            &self.hdr.view,
+            Some(color_attachment(&self.velocity.view, true)),
+            Some(&self.depth),
+            true,
+        )
+        .forget_lifetime();
+        entities.render_with(
+            &mut render_pass,
+            &self.camera,
+            lights,
+            EntitiesPass::Opaque,
+            &self.draws,
+        );
+        drop(render_pass);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Offscreen view resolve"),
+            color_attachments: &[Some(color_attachment(&self.texture.view, false))],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &self.resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn new_target(
+    label: &str,
+    ctx: &GraphicsCtx,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> TextureWrapper {
+    TextureWrapper::new_render_target(
+        label,
+        ctx,
+        size,
+        format,
+        SamplerDesc::LINEAR,
+        wgpu::TextureUsages::empty(),
+    )
+}
+
+fn resolve_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("Offscreen View Resolve Bind Group Layout"),
+        })
+}
+
+fn resolve_bind_group(ctx: &GraphicsCtx, hdr: &TextureWrapper) -> wgpu::BindGroup {
+    ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &resolve_bind_group_layout(ctx),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&hdr.view),
+        }],
+        label: Some("Offscreen View Resolve Bind Group"),
+    })
+}
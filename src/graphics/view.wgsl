@@ -0,0 +1,16 @@
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+
+// Single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4f {
+    let uv = vec2f(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Reinhard without exposure, the view does not follow the adaptation of the main one
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4f) -> @location(0) vec4f {
+    let color = max(textureLoad(t_hdr, vec2u(frag_coord.xy), 0).rgb, vec3f(0.0));
+    return vec4f(color / (1.0 + color), 1.0);
+}
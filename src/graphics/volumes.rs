@@ -0,0 +1,171 @@
+use nalgebra::{Point3, Vector3};
+
+use super::settings::RenderSettings;
+
+/// Box overriding the exposure, the fog density and the color grading while the camera is in it,
+/// to give interiors and exteriors their own look
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostVolume {
+    pub center: Point3<f32>,
+    pub half_extents: Vector3<f32>,
+    /// Distance outside the box over which the overrides fade out
+    pub blend_distance: f32,
+    pub exposure: f32,
+    /// Fog extinction per world unit
+    pub fog_density: f32,
+    /// Name of a lut as in [`super::settings::PostSettings::lut`], the grade is left as is when
+    /// none
+    pub lut: Option<String>,
+    pub lut_strength: f32,
+}
+
+impl PostVolume {
+    /// Around `center`, with the current values of `settings`
+    pub fn new(center: Point3<f32>, settings: &RenderSettings) -> Self {
+        Self {
+            center,
+            half_extents: Vector3::repeat(2.0),
+            blend_distance: 1.0,
+            exposure: settings.post.exposure,
+            fog_density: settings.environment.fog.density,
+            lut: settings.post.lut.clone(),
+            lut_strength: settings.post.lut_strength,
+        }
+    }
+
+    /// 1 inside the box, down to 0 at the blend distance from it
+    pub fn weight(&self, position: &Point3<f32>) -> f32 {
+        let outside = ((position - self.center).abs() - self.half_extents)
+            .sup(&Vector3::zeros())
+            .norm();
+        if outside <= 0.0 {
+            return 1.0;
+        }
+        let t = (outside / self.blend_distance.max(1e-3)).min(1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    }
+
+    /// `<center xyz> <half extents xyz> <blend distance> <exposure> <fog density>
+    /// [lut <strength> <name>]`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let (values, lut) = match src.split_once(" lut ") {
+            Some((values, lut)) => {
+                let (strength, name) = lut.trim().split_once(' ').ok_or("Missing lut name")?;
+                let strength = strength
+                    .parse::<f32>()
+                    .map_err(|e| format!("Invalid lut strength: {e}"))?;
+                (values, Some((strength, name.trim().to_string())))
+            }
+            None => (src, None),
+        };
+        let values = values
+            .split_whitespace()
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if values.len() != 9 {
+            return Err("Expected 9 values before the lut".into());
+        }
+        let (lut_strength, lut) = match lut {
+            Some((strength, name)) => (strength, Some(name)),
+            None => (1.0, None),
+        };
+        Ok(Self {
+            center: Point3::new(values[0], values[1], values[2]),
+            half_extents: Vector3::new(values[3], values[4], values[5]),
+            blend_distance: values[6],
+            exposure: values[7],
+            fog_density: values[8],
+            lut,
+            lut_strength,
+        })
+    }
+}
+
+impl std::fmt::Display for PostVolume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (c, e) = (self.center, self.half_extents);
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {}",
+            c.x, c.y, c.z, e.x, e.y, e.z, self.blend_distance, self.exposure, self.fog_density
+        )?;
+        if let Some(lut) = &self.lut {
+            write!(f, " lut {} {lut}", self.lut_strength)?;
+        }
+        Ok(())
+    }
+}
+
+/// Volumes placed in the world, positions are relative to the world origin
+#[derive(Debug, Clone)]
+pub struct PostVolumes {
+    pub enabled: bool,
+    /// Later volumes are blended over the earlier ones
+    pub volumes: Vec<PostVolume>,
+}
+
+impl Default for PostVolumes {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volumes: Vec::new(),
+        }
+    }
+}
+
+impl PostVolumes {
+    /// Blends the volumes around `camera` into `settings`, returns the settings as they were
+    /// when any applies so they can be put back once the frame is recorded
+    ///
+    /// Two luts cannot be mixed, the one of the heaviest volume is used and its strength fades
+    /// in from the edited one
+    pub fn apply(
+        &self,
+        settings: &mut RenderSettings,
+        camera: &Point3<f32>,
+    ) -> Option<RenderSettings> {
+        if !self.enabled {
+            return None;
+        }
+        let weighted = self
+            .volumes
+            .iter()
+            .map(|volume| (volume, volume.weight(camera)))
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect::<Vec<_>>();
+        if weighted.is_empty() {
+            return None;
+        }
+
+        let edited = settings.clone();
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        for (volume, weight) in &weighted {
+            let post = &mut settings.post;
+            post.exposure = lerp(post.exposure, volume.exposure, *weight);
+            let fog = &mut settings.environment.fog;
+            fog.density = lerp(fog.density, volume.fog_density, *weight);
+        }
+        let graded = weighted
+            .iter()
+            .filter(|(volume, _)| volume.lut.is_some())
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((volume, weight)) = graded {
+            let post = &mut settings.post;
+            let from = match post.lut == volume.lut {
+                true => post.lut_strength,
+                false => 0.0,
+            };
+            post.lut.clone_from(&volume.lut);
+            post.lut_strength = lerp(from, volume.lut_strength, *weight);
+        }
+        Some(edited)
+    }
+
+    /// Follows the world origin, see [`super::origin::WorldOrigin`]
+    pub fn rebase(&mut self, shift: Vector3<f32>) {
+        for volume in &mut self.volumes {
+            volume.center -= shift;
+        }
+    }
+}
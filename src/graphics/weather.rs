@@ -0,0 +1,241 @@
+use wgpu::include_wgsl;
+
+use super::{
+    buffer::{CommonBuffer, UniformBuffer, WriteBuffer},
+    camera::{view_proj_bind_group_layout, CameraUniform},
+    ctx::GraphicsCtx,
+    post::HDR_FORMAT,
+    settings::{PrecipitationSettings, WindSettings},
+    utils::TextureWrapper,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl WeatherKind {
+    pub const ALL: [WeatherKind; 3] = [WeatherKind::Clear, WeatherKind::Rain, WeatherKind::Snow];
+}
+
+/// Weather drawn this frame, driven by [`crate::game::weather::Weather`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WeatherState {
+    pub kind: WeatherKind,
+    /// From 0 to 1, ramps up and down when the weather changes
+    pub intensity: f32,
+    /// From 0 for dry surfaces to 1 for soaked ones, see
+    /// [`super::frame::RawFrameConstants::wetness`]
+    pub wetness: f32,
+}
+
+/// Matches `Params` in `weather.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, Default)]
+struct RawPrecipitation {
+    /// Horizontal drift and fall speed, in world units per second
+    velocity: [f32; 3],
+    /// 1 for snow, 0 for rain
+    snow: u32,
+    color: [f32; 3],
+    /// Side of the box around the camera the particles wrap in
+    extent: f32,
+    /// Size of a flake, or width of a streak
+    size: f32,
+    /// Length of a streak, along the velocity
+    length: f32,
+    /// Offsets the hashes of the particle positions
+    seed: u32,
+    _padding: f32,
+}
+
+/// Rain streaks and snow flakes falling in a box following the camera
+///
+/// Particles are not simulated, their positions are hashed from their index and moved with the
+/// time in the vertex shader, so only their count changes with the intensity
+pub struct Precipitation {
+    pub state: WeatherState,
+    /// Layout of the particles, the `precipitation` stream of [`crate::random::Random`]
+    pub seed: u32,
+    params: UniformBuffer<RawPrecipitation>,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    count: u32,
+}
+
+impl Precipitation {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let params = UniformBuffer::new("Precipitation", ctx, &RawPrecipitation::default());
+        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &precipitation_bind_group_layout(ctx),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.binding(),
+            }],
+            label: Some("Precipitation Bind Group"),
+        });
+
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &view_proj_bind_group_layout(ctx),
+                    &precipitation_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("weather.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Precipitation"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                // Hidden by the scene, without hiding each other
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: TextureWrapper::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::COLOR,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            state: WeatherState::default(),
+            seed: 0,
+            params,
+            bind_group,
+            pipeline,
+            count: 0,
+        }
+    }
+
+    /// Uploads the particles of the current state, every frame
+    pub fn update(
+        &mut self,
+        ctx: &GraphicsCtx,
+        settings: &PrecipitationSettings,
+        wind: &WindSettings,
+    ) {
+        let snow = self.state.kind == WeatherKind::Snow;
+        self.count = match self.state.kind {
+            WeatherKind::Clear => 0,
+            _ => (settings.max_particles as f32 * self.state.intensity.clamp(0.0, 1.0)) as u32,
+        };
+        if self.count == 0 {
+            return;
+        }
+
+        let (fall_speed, drift) = if snow {
+            (settings.snow_speed, settings.snow_drift)
+        } else {
+            (settings.rain_speed, settings.rain_drift)
+        };
+        let angle = wind.direction_deg.to_radians();
+        let drift = drift * wind.strength;
+        let raw = RawPrecipitation {
+            velocity: [angle.cos() * drift, -fall_speed, angle.sin() * drift],
+            snow: snow as u32,
+            color: if snow {
+                settings.snow_color.into()
+            } else {
+                settings.rain_color.into()
+            },
+            extent: settings.extent.max(1.0),
+            size: if snow {
+                settings.flake_size
+            } else {
+                settings.streak_width
+            },
+            length: settings.streak_length,
+            seed: self.seed,
+            _padding: 0.0,
+        };
+        self.params.write(ctx, &raw);
+    }
+
+    /// Draws over `target` after the scene, `depth` is only read
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        depth: &TextureWrapper,
+        camera: &CameraUniform,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Precipitation"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.draw(0..6, 0..self.count);
+    }
+}
+
+fn precipitation_bind_group_layout(ctx: &GraphicsCtx) -> wgpu::BindGroupLayout {
+    ctx.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Precipitation Bind Group Layout"),
+        })
+}
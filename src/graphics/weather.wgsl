@@ -0,0 +1,108 @@
+@group(0) @binding(0)
+var<uniform> view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> proj: mat4x4f;
+
+// Matches `RawFrameConstants`, up to the fields read here
+struct FrameConstants {
+    time: f32,
+    delta_time: f32,
+    frame_index: u32,
+    exposure: f32,
+    screen_size: vec2f,
+    inv_screen_size: vec2f,
+    snap_resolution: vec2f,
+    affine_textures: u32,
+    camera_relative: u32,
+    eye: vec3f,
+    wetness: f32,
+    ambient: vec3f,
+};
+
+@group(0) @binding(2)
+var<uniform> frame: FrameConstants;
+
+// Matches `RawPrecipitation`
+struct Params {
+    velocity: vec3f,
+    snow: u32,
+    color: vec3f,
+    extent: f32,
+    size: f32,
+    length: f32,
+    seed: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) uv: vec2f,
+    @location(1) fade: f32,
+};
+
+fn hash(n: u32) -> f32 {
+    var h = n * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    return f32((h >> 22u) ^ h) / 4294967295.0;
+}
+
+// Two triangles per particle, corners from -1 to 1
+fn corner_of(vertex_index: u32) -> vec2f {
+    var corners = array<vec2f, 6>(
+        vec2f(-1.0, -1.0), vec2f(1.0, -1.0), vec2f(1.0, 1.0),
+        vec2f(-1.0, -1.0), vec2f(1.0, 1.0), vec2f(-1.0, 1.0),
+    );
+    return corners[vertex_index];
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    // Another seed picks other particles of the hash, the layout changes without a pattern
+    let seed = (instance_index ^ params.seed) * 3u;
+    let start = vec3f(hash(seed), hash(seed + 1u), hash(seed + 2u)) * params.extent;
+    // Heavier particles fall a little faster
+    let speed = mix(0.8, 1.2, hash(seed + 7u));
+    var position = start + params.velocity * speed * frame.time;
+    if params.snow != 0u {
+        let phase = hash(seed + 11u) * 6.2831;
+        position += vec3f(sin(frame.time * 1.3 + phase), 0.0, cos(frame.time * 0.9 + phase)) * 0.3;
+    }
+    // Wrapped in the box centered on the camera, the particles follow it without popping
+    let local = (fract((position - frame.eye) / params.extent) - 0.5) * params.extent;
+    let center = frame.eye + local;
+    let corner = corner_of(vertex_index);
+
+    var out: VertexOutput;
+    if params.snow != 0u {
+        // Facing the camera
+        let view_position = view * vec4f(center, 1.0) + vec4f(corner * params.size, 0.0, 0.0);
+        out.clip_position = proj * view_position;
+    } else {
+        // Stretched along the fall, facing the camera around it
+        let direction = normalize(params.velocity);
+        let side = normalize(cross(direction, center - frame.eye));
+        let world = center + side * corner.x * params.size + direction * corner.y * params.length * 0.5;
+        out.clip_position = proj * view * vec4f(world, 1.0);
+    }
+    out.uv = corner;
+    // Faded out towards the walls of the box, where the particles wrap around
+    out.fade = 1.0 - smoothstep(0.3, 0.5, length(local) / params.extent);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    var alpha: f32;
+    if params.snow != 0u {
+        alpha = 1.0 - smoothstep(0.5, 1.0, length(in.uv));
+    } else {
+        alpha = (1.0 - abs(in.uv.x)) * (1.0 - in.uv.y * in.uv.y) * 0.4;
+    }
+    let color = params.color * max(frame.ambient * 2.0, vec3f(0.05));
+    return vec4f(color, alpha * in.fade);
+}
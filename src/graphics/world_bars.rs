@@ -0,0 +1,223 @@
+use nalgebra::{Matrix4, Point2, Point3};
+use wgpu::include_wgsl;
+
+use crate::graphics::{
+    buffer::{CommonBuffer, Growable, VertexBuffer, WriteBuffer},
+    camera::{view_proj_bind_group_layout, CameraUniform},
+    color::Color3,
+    ctx::GraphicsCtx,
+    depth::{scene_depth_bind_group_layout, SceneDepth},
+};
+
+const SHAPE_BAR: u32 = 0;
+const SHAPE_ICON: u32 = 1;
+
+/// Matches `WidgetInput` in the shader, drawn as one screen space quad per instance
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct WorldWidget {
+    pub position: [f32; 3],
+    /// From 0 to 1, the part of a bar filled with `fill`
+    pub fraction: f32,
+    pub fill: [f32; 4],
+    pub background: [f32; 4],
+    /// In pixels, the quad is centered horizontally above the projected position
+    pub size: [f32; 2],
+    pub shape: u32,
+    pub _padding: u32,
+}
+
+impl WorldWidget {
+    fn buffer_desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+            0 => Float32x3, 1 => Float32, 2 => Float32x4, 3 => Float32x4, 4 => Float32x2, 5 => Uint32
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<WorldWidget>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Pixel position of a world point in the viewport, none behind the camera
+///
+/// Points in front of the camera but off screen are still returned, outside of the viewport
+pub fn world_to_screen(
+    view_proj: &Matrix4<f32>,
+    point: &Point3<f32>,
+    viewport_size: (u32, u32),
+) -> Option<Point2<f32>> {
+    let clip = view_proj * point.to_homogeneous();
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.xy() / clip.w;
+    let (width, height) = viewport_size;
+    Some(Point2::new(
+        (ndc.x * 0.5 + 0.5) * width as f32,
+        (0.5 - ndc.y * 0.5) * height as f32,
+    ))
+}
+
+/// Immediate mode health bars and icons above world positions, queued by the gameplay systems
+/// during the frame and cleared once drawn
+///
+/// Drawn over the tonemapped frame at a constant pixel size. Widgets whose position is hidden by
+/// the scene depth fade out instead of disappearing
+pub struct WorldBars {
+    pub bar_size: [f32; 2],
+    pub icon_size: f32,
+    pub background: Color3,
+    pub background_alpha: f32,
+    widgets: Vec<WorldWidget>,
+    buffer: Growable<VertexBuffer<WorldWidget>>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl WorldBars {
+    pub fn new(ctx: &GraphicsCtx) -> Self {
+        let pipeline_layout = ctx
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &view_proj_bind_group_layout(ctx),
+                    &scene_depth_bind_group_layout(ctx),
+                ],
+                push_constant_ranges: &[],
+            });
+        let shader = ctx
+            .device
+            .create_shader_module(include_wgsl!("world_bars.wgsl"));
+
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("World bars"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[WorldWidget::buffer_desc()],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: ctx.surface_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            bar_size: [48.0, 6.0],
+            icon_size: 12.0,
+            background: Color3::splat(0.05),
+            background_alpha: 0.6,
+            widgets: Vec::new(),
+            buffer: VertexBuffer::new_empty_vec("World bars", ctx, 256),
+            pipeline,
+        }
+    }
+
+    /// Bar filled from the left up to `fraction`
+    pub fn bar(&mut self, position: Point3<f32>, fraction: f32, fill: Color3) {
+        self.widgets.push(WorldWidget {
+            position: position.into(),
+            fraction: fraction.clamp(0.0, 1.0),
+            fill: fill.into(),
+            background: self.background_color(),
+            size: self.bar_size,
+            shape: SHAPE_BAR,
+            _padding: 0,
+        });
+    }
+
+    /// Round marker, without background
+    pub fn icon(&mut self, position: Point3<f32>, color: Color3) {
+        self.widgets.push(WorldWidget {
+            position: position.into(),
+            fraction: 1.0,
+            fill: color.into(),
+            background: [0.0; 4],
+            size: [self.icon_size; 2],
+            shape: SHAPE_ICON,
+            _padding: 0,
+        });
+    }
+
+    fn background_color(&self) -> [f32; 4] {
+        let [r, g, b]: [f32; 3] = self.background.into();
+        [r, g, b, self.background_alpha]
+    }
+
+    /// Widgets queued for this frame, the ones behind the camera included
+    pub fn queued_len(&self) -> usize {
+        self.widgets.len()
+    }
+
+    /// Room for `widgets` drawn in a frame, see [`Growable::reserve`]
+    pub fn reserve(&mut self, ctx: &GraphicsCtx, widgets: usize) {
+        self.buffer.reserve(ctx, widgets);
+    }
+
+    /// Draws the queued widgets in front of the camera over the frame, then clears them
+    ///
+    /// `scene_depth` must hold the depth of this frame
+    pub fn render(
+        &mut self,
+        ctx: &GraphicsCtx,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &CameraUniform,
+        target: &wgpu::TextureView,
+        scene_depth: &SceneDepth,
+    ) {
+        let view_proj = camera.view_proj();
+        // Behind the camera the projection flips, the quads would be mirrored on screen
+        let widgets: Vec<_> = std::mem::take(&mut self.widgets)
+            .into_iter()
+            .filter(|widget| {
+                world_to_screen(&view_proj, &widget.position.into(), ctx.viewport_size).is_some()
+            })
+            .collect();
+        if widgets.is_empty() {
+            return;
+        }
+        self.buffer.maybe_grow(ctx, widgets.len());
+        let widgets = self.buffer.fitting(&widgets);
+        self.buffer.write_array(ctx, &widgets);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("World bars"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera.view_proj_bindgroup, &[]);
+        render_pass.set_bind_group(1, &*scene_depth.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.as_slice());
+        render_pass.draw(0..4, 0..widgets.len() as u32);
+    }
+}
@@ -0,0 +1,106 @@
+@group(0) @binding(0)
+var<uniform> view: mat4x4f;
+@group(0) @binding(1)
+var<uniform> proj: mat4x4f;
+
+// Matches `RawFrameConstants`
+struct FrameConstants {
+    time: f32,
+    delta_time: f32,
+    frame_index: u32,
+    exposure: f32,
+    screen_size: vec2f,
+    inv_screen_size: vec2f,
+    snap_resolution: vec2f,
+    affine_textures: u32,
+};
+
+@group(0) @binding(2)
+var<uniform> frame: FrameConstants;
+
+// Copy of the depth of this frame, taken after the opaque entities
+@group(1) @binding(0)
+var scene_depth: texture_depth_2d;
+
+// Matches `SHAPE_ICON`
+const SHAPE_ICON: u32 = 1u;
+// Alpha left to the widgets whose position is hidden by the scene
+const OCCLUDED_ALPHA: f32 = 0.25;
+// Gap around the fill of the bars, in pixels
+const BORDER: f32 = 1.0;
+// Distance between the depth samples around the position, in pixels
+const SAMPLE_SPREAD: i32 = 3;
+
+// Matches `WorldWidget`
+struct WidgetInput {
+    @location(0) position: vec3f,
+    @location(1) fraction: f32,
+    @location(2) fill: vec4f,
+    @location(3) background: vec4f,
+    @location(4) size: vec2f,
+    @location(5) shape: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    // From 0 to 1 across the quad, the top left is 0
+    @location(0) uv: vec2f,
+    // Pixel and depth of the projected position
+    @location(1) @interpolate(flat) anchor: vec3f,
+    @location(2) @interpolate(flat) fraction: f32,
+    @location(3) @interpolate(flat) fill: vec4f,
+    @location(4) @interpolate(flat) background: vec4f,
+    @location(5) @interpolate(flat) size: vec2f,
+    @location(6) @interpolate(flat) shape: u32,
+}
+
+// Triangle strip of 4 vertices per widget
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, in: WidgetInput) -> VertexOutput {
+    let uv = vec2f(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+    let clip = proj * view * vec4f(in.position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    // Bottom edge on the position, the offset is in pixels and y goes down on screen
+    let offset = (uv - vec2f(0.5, 1.0)) * in.size * vec2f(2.0, -2.0) * frame.inv_screen_size;
+
+    var out: VertexOutput;
+    out.clip_position = vec4f(ndc.xy + offset, ndc.z, 1.0);
+    out.uv = uv;
+    out.anchor = vec3f((ndc.xy * vec2f(0.5, -0.5) + 0.5) * frame.screen_size, ndc.z);
+    out.fraction = in.fraction;
+    out.fill = in.fill;
+    out.background = in.background;
+    out.size = in.size;
+    out.shape = in.shape;
+    return out;
+}
+
+// Part of the samples around the position in front of the scene
+fn visibility(anchor: vec3f) -> f32 {
+    let last = vec2i(textureDimensions(scene_depth)) - 1;
+    var visible = 0.0;
+    for (var i = 0; i < 4; i++) {
+        let offset = (vec2i(i & 1, i >> 1u) * 2 - 1) * SAMPLE_SPREAD;
+        let pixel = clamp(vec2i(anchor.xy) + offset, vec2i(0), last);
+        if anchor.z <= textureLoad(scene_depth, pixel, 0) {
+            visible += 0.25;
+        }
+    }
+    return mix(OCCLUDED_ALPHA, 1.0, visible);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    var color: vec4f;
+    if in.shape == SHAPE_ICON {
+        // Antialiased disc, a pixel wide edge
+        let radius = length(in.uv - 0.5) * in.size.x;
+        color = in.fill * vec4f(1.0, 1.0, 1.0, saturate(in.size.x * 0.5 - radius));
+    } else {
+        let pixel = in.uv * in.size;
+        let inner = all(pixel > vec2f(BORDER)) && all(pixel < in.size - BORDER);
+        let filled = inner && pixel.x - BORDER < in.fraction * (in.size.x - 2.0 * BORDER);
+        color = select(in.background, in.fill, filled);
+    }
+    return vec4f(color.rgb, color.a * visibility(in.anchor));
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, Receiver, TryRecvError},
+        Condvar, LazyLock, Mutex, MutexGuard, Once,
+    },
+    time::{Duration, Instant},
+};
+
+/// Finished jobs kept for the profiler
+pub const HISTORY_LEN: usize = 64;
+
+/// Main thread time given to the completions every frame, see [`run_completions`]
+pub const COMPLETION_BUDGET: Duration = Duration::from_millis(2);
+
+static SCHEDULER: LazyLock<Scheduler> = LazyLock::new(|| Scheduler {
+    state: Mutex::new(State {
+        queued: Default::default(),
+        running: Vec::new(),
+        completions: Default::default(),
+        history: VecDeque::with_capacity(HISTORY_LEN),
+    }),
+    queued: Condvar::new(),
+    completed: Condvar::new(),
+});
+
+static WORKERS: Once = Once::new();
+
+/// Order the queued jobs are started in, and their completions run in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Waited for by the frame, like the assets at startup
+    High,
+    Normal,
+    /// Long bakes the user can wait for
+    Low,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub fn label(&self) -> &str {
+        match self {
+            Priority::High => "High",
+            Priority::Normal => "Normal",
+            Priority::Low => "Low",
+        }
+    }
+}
+
+type Completion = Box<dyn FnOnce() + Send>;
+
+struct Task {
+    name: &'static str,
+    priority: Priority,
+    queued_at: Instant,
+    /// Runs the work and returns what the main thread runs once it is done
+    run: Box<dyn FnOnce() -> Completion + Send>,
+}
+
+/// Job running on a worker
+#[derive(Debug, Clone, Copy)]
+pub struct RunningJob {
+    pub name: &'static str,
+    pub priority: Priority,
+    pub started: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JobRecord {
+    pub name: &'static str,
+    pub priority: Priority,
+    /// Time spent queued before a worker took it
+    pub waited: Duration,
+    pub duration: Duration,
+    pub panicked: bool,
+}
+
+/// State of the scheduler for the profiler
+#[derive(Debug, Clone, Default)]
+pub struct JobStats {
+    /// Jobs waiting for a worker, by [`Priority::ALL`] order
+    pub queued: [usize; 3],
+    pub running: Vec<RunningJob>,
+    /// Finished jobs whose completion waits for the main thread
+    pub completions: usize,
+    /// Oldest first
+    pub history: Vec<JobRecord>,
+}
+
+struct State {
+    queued: [VecDeque<Task>; 3],
+    running: Vec<RunningJob>,
+    completions: [VecDeque<Completion>; 3],
+    history: VecDeque<JobRecord>,
+}
+
+struct Scheduler {
+    state: Mutex<State>,
+    /// Wakes the workers
+    queued: Condvar,
+    /// Wakes [`Job::wait`]
+    completed: Condvar,
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Result of a job, handed over once its completion ran on the main thread
+pub struct Job<T> {
+    receiver: Receiver<T>,
+    /// Received by [`Self::lost`]
+    ready: Option<T>,
+}
+
+impl<T> Job<T> {
+    /// The result, once
+    pub fn poll(&mut self) -> Option<T> {
+        self.ready.take().or_else(|| self.receiver.try_recv().ok())
+    }
+
+    /// True when the job panicked, it will never complete
+    pub fn lost(&mut self) -> bool {
+        match self.receiver.try_recv() {
+            Ok(result) => {
+                self.ready = Some(result);
+                false
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => self.ready.is_none(),
+        }
+    }
+
+    /// Blocks until the result is there, running the completions of every job meanwhile as
+    /// the frame would have. `None` if the job panicked
+    pub fn wait(mut self) -> Option<T> {
+        if let Some(result) = self.ready.take() {
+            return Some(result);
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(result) => return Some(result),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+            let mut state = SCHEDULER.lock();
+            match pop(&mut state.completions) {
+                Some(completion) => {
+                    drop(state);
+                    completion();
+                }
+                // Timed out as a job may panic between the check above and the wait
+                None => drop(
+                    SCHEDULER
+                        .completed
+                        .wait_timeout(state, Duration::from_millis(10))
+                        .unwrap(),
+                ),
+            }
+        }
+    }
+}
+
+/// Runs `work` on a worker, its result is handed to the [`Job`] from the main thread
+pub fn spawn<T: Send + 'static>(
+    name: &'static str,
+    priority: Priority,
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Job<T> {
+    let (sender, receiver) = channel();
+    spawn_then(name, priority, work, move |result| {
+        // The handle may have been dropped meanwhile
+        let _ = sender.send(result);
+    });
+    Job {
+        receiver,
+        ready: None,
+    }
+}
+
+/// Runs `work` on a worker then `then` on the main thread, within the budget of
+/// [`run_completions`]
+pub fn spawn_then<T: Send + 'static>(
+    name: &'static str,
+    priority: Priority,
+    work: impl FnOnce() -> T + Send + 'static,
+    then: impl FnOnce(T) + Send + 'static,
+) {
+    WORKERS.call_once(start_workers);
+    let task = Task {
+        name,
+        priority,
+        queued_at: Instant::now(),
+        run: Box::new(move || {
+            let result = work();
+            Box::new(move || then(result))
+        }),
+    };
+    SCHEDULER.lock().queued[priority as usize].push_back(task);
+    SCHEDULER.queued.notify_one();
+}
+
+/// Runs the completions of the finished jobs, highest priority first, until `budget` is spent.
+/// At least one runs so the completions never stall, called once per frame from the main loop
+pub fn run_completions(budget: Duration) {
+    let start = Instant::now();
+    loop {
+        let Some(completion) = pop(&mut SCHEDULER.lock().completions) else {
+            return;
+        };
+        completion();
+        if start.elapsed() >= budget {
+            return;
+        }
+    }
+}
+
+pub fn stats() -> JobStats {
+    let state = SCHEDULER.lock();
+    JobStats {
+        queued: state.queued.each_ref().map(VecDeque::len),
+        running: state.running.clone(),
+        completions: state.completions.iter().map(VecDeque::len).sum(),
+        history: state.history.iter().copied().collect(),
+    }
+}
+
+fn pop<T>(queues: &mut [VecDeque<T>; 3]) -> Option<T> {
+    queues.iter_mut().find_map(VecDeque::pop_front)
+}
+
+/// One worker per core left by the main thread
+fn start_workers() {
+    let count = std::thread::available_parallelism()
+        .map_or(2, |count| count.get())
+        .saturating_sub(1)
+        .max(1);
+    for i in 0..count {
+        std::thread::Builder::new()
+            .name(format!("Job worker {i}"))
+            .spawn(worker)
+            .expect("Failed to start a job worker");
+    }
+}
+
+fn worker() {
+    loop {
+        let mut state = SCHEDULER.lock();
+        let task = loop {
+            if let Some(task) = pop(&mut state.queued) {
+                break task;
+            }
+            state = SCHEDULER.queued.wait(state).unwrap();
+        };
+        let started = Instant::now();
+        let running = RunningJob {
+            name: task.name,
+            priority: task.priority,
+            started,
+        };
+        state.running.push(running);
+        drop(state);
+
+        let result = catch_unwind(AssertUnwindSafe(task.run));
+        if result.is_err() {
+            eprintln!("Job {} panicked", task.name);
+        }
+
+        let mut state = SCHEDULER.lock();
+        if let Some(i) = state
+            .running
+            .iter()
+            .position(|job| job.name == task.name && job.started == started)
+        {
+            state.running.swap_remove(i);
+        }
+        if state.history.len() == HISTORY_LEN {
+            state.history.pop_front();
+        }
+        state.history.push_back(JobRecord {
+            name: task.name,
+            priority: task.priority,
+            waited: started - task.queued_at,
+            duration: started.elapsed(),
+            panicked: result.is_err(),
+        });
+        if let Ok(completion) = result {
+            state.completions[task.priority as usize].push_back(completion);
+        }
+        // Also wakes the waits on a job that panicked, its handle is disconnected
+        SCHEDULER.completed.notify_all();
+    }
+}
@@ -1,25 +1,40 @@
 use asset_tree::builtin::Folder;
 
 pub mod app;
+pub mod asset_server;
+#[cfg(feature = "audio")]
+pub mod audio;
 pub mod constants;
+pub mod entity;
 pub mod game;
 pub mod graphics;
+pub mod jobs;
+pub mod locale;
+pub mod profiler;
+pub mod random;
+pub mod scene;
+pub mod scene_diff;
+pub mod sequence;
 pub mod utils;
+pub mod validate;
+pub mod world;
 
 asset_tree::asset_tree! {
     assets {
         models: Folder<graphics::assets::ModelFile>,
         materials: Folder<graphics::assets::MaterialFile>,
         textures: Folder<graphics::assets::TextureFile>,
+        environments: Folder<graphics::assets::HdrTextureFile>,
+        luts: Folder<graphics::assets::LutFile>,
+        themes: Folder<graphics::assets::ThemeFile>,
+        fonts: Folder<graphics::assets::FontFile>,
+        scenes: Folder<graphics::assets::SceneFile>,
+        locales: Folder<graphics::assets::LocaleFile>,
     }
 }
 
-pub static ASSETS: std::sync::LazyLock<assets::AssetsFolder> = std::sync::LazyLock::new(|| {
-    <assets::AssetsFolder as asset_tree::Asset>::load(
-        &<asset_tree::loader::StdOsLoader as asset_tree::loader::AssetLoader>::new(String::from(
-            "assets",
-        ))
-        .expect("Assets platform is not supported"),
-    )
-    .expect("Failed to load game assets")
-});
+/// Default root of the asset tree, also watched by the editor for hot reloading
+pub const ASSETS_PATH: &str = "assets";
+
+/// Assets of the open project, see [`asset_server::AssetServer::open`]
+pub static ASSETS: asset_server::AssetServer = asset_server::AssetServer::new(ASSETS_PATH);
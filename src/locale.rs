@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{LazyLock, RwLock},
+};
+
+use crate::{app::config::Config, ASSETS};
+
+/// Language whose strings are used when the current one lacks them
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// Config section holding the language
+const CONFIG_SECTION: &str = "locale";
+
+/// Strings of a language, `<language>.ftl` in the locales folder
+///
+/// Only the messages of Fluent are read: `key = value` lines, indented lines continuing the
+/// value above them and `{ $name }` placeholders filled by [`tr_args`]
+#[derive(Debug, Clone, Default)]
+pub struct StringTable {
+    messages: HashMap<String, String>,
+}
+
+impl StringTable {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut messages = HashMap::new();
+        let mut last_key: Option<String> = None;
+        for line in src.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if line.starts_with(char::is_whitespace) {
+                let key = last_key
+                    .as_ref()
+                    .ok_or_else(|| format!("Indented line outside of any message: {line}"))?;
+                let value: &mut String = messages.get_mut(key).unwrap();
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(trimmed);
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key = value: {line}"))?;
+            let key = key.trim();
+            let valid = key.starts_with(|c: char| c.is_ascii_alphabetic())
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(format!("Invalid message key: {key}"));
+            }
+            messages.insert(key.to_string(), value.trim().to_string());
+            last_key = Some(key.to_string());
+        }
+        Ok(Self { messages })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+}
+
+struct Localization {
+    language: String,
+    strings: StringTable,
+    fallback: StringTable,
+}
+
+static LOCALIZATION: LazyLock<RwLock<Localization>> = LazyLock::new(|| {
+    let fallback = load_fallback();
+    RwLock::new(Localization {
+        language: FALLBACK_LANGUAGE.to_string(),
+        strings: fallback.clone(),
+        fallback,
+    })
+});
+
+fn load_fallback() -> StringTable {
+    load_table(FALLBACK_LANGUAGE).unwrap_or_else(|e| {
+        eprintln!("UI strings are shown by key, the fallback language failed to load: {e}");
+        StringTable::default()
+    })
+}
+
+fn load_table(language: &str) -> Result<StringTable, String> {
+    let assets = ASSETS.tree();
+    let file = assets
+        .locales
+        .get(language)
+        .ok_or_else(|| format!("No strings for language {language}"))?;
+    StringTable::parse(&file.0).map_err(|e| format!("{language}.ftl: {e}"))
+}
+
+/// String of `key` in the current language, then in [`FALLBACK_LANGUAGE`], the key itself when
+/// neither has it
+pub fn tr(key: &str) -> String {
+    let localization = LOCALIZATION.read().unwrap();
+    localization
+        .strings
+        .get(key)
+        .or_else(|| localization.fallback.get(key))
+        .unwrap_or(key)
+        .to_string()
+}
+
+/// [`tr`] with the `{ $name }` placeholders replaced by the arguments
+pub fn tr_args(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut string = tr(key);
+    for (name, value) in args {
+        let value = value.to_string();
+        string = string
+            .replace(&format!("{{ ${name} }}"), &value)
+            .replace(&format!("{{${name}}}"), &value);
+    }
+    string
+}
+
+pub fn language() -> String {
+    LOCALIZATION.read().unwrap().language.clone()
+}
+
+/// Switches every string at once, the current language is kept on failure. The fallback strings
+/// are read again too, they may come from another project
+pub fn set_language(language: &str) -> Result<(), String> {
+    let strings = load_table(language)?;
+    let fallback = match language == FALLBACK_LANGUAGE {
+        true => strings.clone(),
+        false => load_fallback(),
+    };
+    let mut localization = LOCALIZATION.write().unwrap();
+    localization.language = language.to_string();
+    localization.strings = strings;
+    localization.fallback = fallback;
+    Ok(())
+}
+
+/// Code and name of the languages with a file in the locales folder, sorted by code. Each file
+/// is read to find its name, the list is meant to be kept
+pub fn languages() -> Vec<(String, String)> {
+    let Ok(entries) = std::fs::read_dir(ASSETS.root().join("locales")) else {
+        return vec![(
+            FALLBACK_LANGUAGE.to_string(),
+            language_name(FALLBACK_LANGUAGE),
+        )];
+    };
+    let mut languages: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ftl"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    languages.sort();
+    languages
+        .into_iter()
+        .map(|language| {
+            let name = language_name(&language);
+            (language, name)
+        })
+        .collect()
+}
+
+/// Name of the language written in itself, the `language-name` message of its file
+fn language_name(language: &str) -> String {
+    load_table(language)
+        .ok()
+        .and_then(|table| table.get("language-name").map(str::to_string))
+        .unwrap_or_else(|| language.to_string())
+}
+
+/// Loads the strings of the saved language from the open project, [`FALLBACK_LANGUAGE`] when it
+/// is missing or unknown. Called again once another project is opened
+pub fn load_config(config: &Config) {
+    let language = config
+        .get(CONFIG_SECTION, "language")
+        .unwrap_or(FALLBACK_LANGUAGE);
+    if let Err(e) = set_language(language) {
+        eprintln!("Using the {FALLBACK_LANGUAGE} strings: {e}");
+        let fallback = load_fallback();
+        let mut localization = LOCALIZATION.write().unwrap();
+        localization.language = FALLBACK_LANGUAGE.to_string();
+        localization.strings = fallback.clone();
+        localization.fallback = fallback;
+    }
+}
+
+pub fn write_config(config: &mut Config) {
+    config.set(CONFIG_SECTION, "language", language());
+}
@@ -1,6 +1,11 @@
-use foreigntech2::app::App;
+use foreigntech2::app::{App, GameApp};
+
+/// Empty game, the built-in systems and the editor are enough to play around
+struct Sandbox;
+
+impl GameApp for Sandbox {}
 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
-    App::run();
+    App::run(Sandbox);
 }
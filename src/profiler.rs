@@ -0,0 +1,161 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+/// Frames kept for the viewer
+pub const HISTORY_LEN: usize = 240;
+
+/// Scopes are free when disabled, only the flag is read
+pub static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static PROFILER: LazyLock<Mutex<Profiler>> = LazyLock::new(|| {
+    Mutex::new(Profiler {
+        frame_start: Instant::now(),
+        current: Vec::new(),
+        history: VecDeque::with_capacity(HISTORY_LEN),
+        memory: BTreeMap::new(),
+    })
+});
+
+thread_local! {
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone)]
+pub struct ScopeRecord {
+    pub name: &'static str,
+    pub thread: ThreadId,
+    /// Nesting level on its thread, 0 for the outermost scopes
+    pub depth: u32,
+    /// Offset from the start of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameRecord {
+    pub duration: Duration,
+    pub scopes: Vec<ScopeRecord>,
+}
+
+/// Memory used by a system, replaced by each new report
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryRecord {
+    pub bytes: usize,
+    /// What the data would take without compression, equal to `bytes` for raw data
+    pub uncompressed: usize,
+}
+
+struct Profiler {
+    frame_start: Instant,
+    current: Vec<ScopeRecord>,
+    history: VecDeque<FrameRecord>,
+    memory: BTreeMap<&'static str, MemoryRecord>,
+}
+
+/// Measures until dropped, see [`crate::profile_scope`]
+pub struct ProfileScope {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl ProfileScope {
+    pub fn new(name: &'static str) -> Self {
+        let start = is_enabled().then(|| {
+            DEPTH.with(|depth| depth.set(depth.get() + 1));
+            Instant::now()
+        });
+        Self { name, start }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let duration = start.elapsed();
+        let depth = DEPTH.with(|depth| {
+            depth.set(depth.get() - 1);
+            depth.get()
+        });
+
+        let mut profiler = PROFILER.lock().unwrap();
+        let offset = start.saturating_duration_since(profiler.frame_start);
+        profiler.current.push(ScopeRecord {
+            name: self.name,
+            thread: std::thread::current().id(),
+            depth,
+            start: offset,
+            duration,
+        });
+    }
+}
+
+/// Times the rest of the enclosing block as a scope of the current frame
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiler::ProfileScope::new($name);
+    };
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Closes the current frame, called once per frame from the main loop
+pub fn new_frame() {
+    let mut profiler = PROFILER.lock().unwrap();
+    let now = Instant::now();
+    let duration = now - profiler.frame_start;
+    profiler.frame_start = now;
+
+    if !is_enabled() {
+        profiler.current.clear();
+        return;
+    }
+
+    let scopes = std::mem::take(&mut profiler.current);
+    if profiler.history.len() == HISTORY_LEN {
+        profiler.history.pop_front();
+    }
+    profiler.history.push_back(FrameRecord { duration, scopes });
+}
+
+/// Completed frames, oldest first
+pub fn with_history<R>(f: impl FnOnce(&VecDeque<FrameRecord>) -> R) -> R {
+    f(&PROFILER.lock().unwrap().history)
+}
+
+pub fn clear() {
+    PROFILER.lock().unwrap().history.clear();
+}
+
+/// Latest memory stats of a system, ignored when disabled
+pub fn record_memory(name: &'static str, bytes: usize, uncompressed: usize) {
+    if !is_enabled() {
+        return;
+    }
+    let record = MemoryRecord {
+        bytes,
+        uncompressed,
+    };
+    PROFILER.lock().unwrap().memory.insert(name, record);
+}
+
+/// Memory stats by system name
+pub fn with_memory<R>(f: impl FnOnce(&BTreeMap<&'static str, MemoryRecord>) -> R) -> R {
+    f(&PROFILER.lock().unwrap().memory)
+}
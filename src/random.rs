@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+/// Seed of the scenes without one
+pub const DEFAULT_SEED: u32 = 0x9E37_79B9;
+
+/// Xorshift, fast and reproducible across platforms and builds
+#[derive(Debug, Clone)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed.wrapping_mul(0x9E37_79B9) | 1)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// In [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// In `0..len`, `len` must not be 0
+    pub fn below(&mut self, len: usize) -> usize {
+        self.next_u32() as usize % len
+    }
+
+    /// In [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// Random numbers of the procedural content and the gameplay, reproducible from a single seed
+/// saved in the scene file
+///
+/// Each system draws from its own named stream, so adding draws to one system leaves the
+/// sequences of the others unchanged
+#[derive(Debug, Clone)]
+pub struct Random {
+    seed: u32,
+    streams: HashMap<&'static str, Rng>,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Restarts every stream from the new seed
+    pub fn reseed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// Stream of a system, started from [`Self::stream_seed`] on first use
+    pub fn stream(&mut self, name: &'static str) -> &mut Rng {
+        let seed = self.stream_seed(name);
+        self.streams.entry(name).or_insert_with(|| Rng::new(seed))
+    }
+
+    /// Seed of the stream, for the systems hashing their randomness on the gpu
+    pub fn stream_seed(&self, name: &str) -> u32 {
+        // Fnv-1a over the seed and the name, stable across builds unlike the std hasher
+        let mut hash = 0x811c_9dc5u32;
+        for byte in self.seed.to_le_bytes().iter().chain(name.as_bytes()) {
+            hash = (hash ^ *byte as u32).wrapping_mul(0x0100_0193);
+        }
+        hash
+    }
+}
@@ -0,0 +1,400 @@
+use std::path::PathBuf;
+
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::{
+    entity::{EntityMeta, Uuid},
+    graphics::{
+        color::Color3,
+        entities::model::ModelInstance,
+        light::{light_flags, Light, RawLight},
+        volumes::PostVolume,
+    },
+    sequence::{CameraKey, LightKey, Sequence},
+    ASSETS,
+};
+
+/// Instances placed in the editor and the light rig, saved as text in the scenes folder of the assets
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub instances: Vec<SceneInstance>,
+    pub lights: Vec<SceneLight>,
+    /// Post processing volumes, see [`crate::graphics::volumes::PostVolumes`]
+    pub volumes: Vec<PostVolume>,
+    /// Of the [`crate::random::Random`] service, the default one when missing
+    pub seed: Option<u32>,
+    /// Cutscene of the scene, empty for most
+    pub sequence: Sequence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneLight {
+    pub light: Light,
+    pub lens_flare: bool,
+    pub casts_shadows: bool,
+    /// Matches the light across versions of the scene, see [`crate::scene_diff`]
+    pub id: Uuid,
+}
+
+impl From<SceneLight> for RawLight {
+    fn from(light: SceneLight) -> Self {
+        RawLight::from(light.light)
+            .with_flag(light_flags::LENS_FLARE, light.lens_flare)
+            .with_flag(light_flags::CAST_SHADOWS, light.casts_shadows)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneInstance {
+    pub model_id: u16,
+    pub mesh_id: u16,
+    pub instance: ModelInstance,
+    pub meta: EntityMeta,
+    /// Merged into the static batches on load instead of placed, see
+    /// [`crate::graphics::entities::statics::StaticBatches`]
+    pub is_static: bool,
+}
+
+impl SceneInstance {
+    /// `<mesh> <material> <tint rgba> <column major transform> [lightmap rect] [static]
+    /// [id <uuid>] [name <name>]`, the values following the model of an instance. Instances saved without
+    /// an id get a new one
+    pub fn parse(model_id: u16, values: &str) -> Result<Self, String> {
+        let (values, name) = match values.split_once(" name ") {
+            Some((values, name)) => (values, Some(name.trim().to_string())),
+            None => (values, None),
+        };
+        let (values, uuid) = match values.split_once(" id ") {
+            Some((values, uuid)) => (values, Some(uuid.trim().parse()?)),
+            None => (values, None),
+        };
+        let (values, is_static) = match values.trim_end().strip_suffix(" static") {
+            Some(values) => (values, true),
+            None => (values, false),
+        };
+        let values = values
+            .split_whitespace()
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if values.len() != 22 && values.len() != 26 {
+            return Err("Expected 22 values after the model, or 26 with a lightmap rect".into());
+        }
+
+        let transform = Matrix4::from_column_slice(&values[6..22]);
+        let mut lightmap = [0.0; 4];
+        if let Some(rect) = values.get(22..26) {
+            lightmap.copy_from_slice(rect);
+        }
+        let instance = ModelInstance {
+            tint: [values[2], values[3], values[4], values[5]].map(|c| c as u8),
+            lightmap,
+            ..ModelInstance::new(transform, values[1] as u32)
+        };
+        Ok(Self {
+            model_id,
+            mesh_id: values[0] as u16,
+            instance,
+            meta: EntityMeta {
+                uuid: uuid.unwrap_or_default(),
+                name: name.filter(|name| !name.is_empty()),
+            },
+            is_static,
+        })
+    }
+
+    /// Values read by [`Self::parse`], each preceded by a space
+    pub fn write_values(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        let instance = &self.instance;
+        write!(f, " {} {}", self.mesh_id, instance.material_id)?;
+        for c in instance.tint {
+            write!(f, " {c}")?;
+        }
+        for value in instance.transform().as_slice() {
+            write!(f, " {value}")?;
+        }
+        if instance.lightmap[2] > 0.0 {
+            for value in instance.lightmap {
+                write!(f, " {value}")?;
+            }
+        }
+        if self.is_static {
+            write!(f, " static")?;
+        }
+        write!(f, " id {}", self.meta.uuid)?;
+        if let Some(name) = &self.meta.name {
+            write!(f, " name {name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene {
+    pub fn path(name: &str) -> PathBuf {
+        ASSETS
+            .root()
+            .join("scenes")
+            .join(name)
+            .with_extension("scene")
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        let src = std::fs::read_to_string(Self::path(name)).map_err(|e| e.to_string())?;
+        Self::parse(&src)
+    }
+
+    /// Baked lightmap saved next to the scene file
+    pub fn lightmap_path(name: &str) -> PathBuf {
+        Self::path(name).with_extension("exr")
+    }
+
+    /// Peak buffer sizes recorded while the scene was played, saved next to the scene file, see
+    /// [`crate::graphics::stats::BufferPeaks`]
+    pub fn stats_path(name: &str) -> PathBuf {
+        Self::path(name).with_extension("stats")
+    }
+
+    /// Scene shipped in the asset tree, loaded at startup
+    pub fn from_assets(name: &str) -> Result<Self, String> {
+        let assets = ASSETS.tree();
+        let file = assets
+            .scenes
+            .get(name)
+            .ok_or_else(|| format!("No scene asset named {name}"))?;
+        Self::parse(&file.0)
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        let path = Self::path(name);
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, self.to_string()).map_err(|e| e.to_string())
+    }
+
+    /// One entry per line, either
+    /// - `seed <u32>`
+    /// - `instance <model> <mesh> <material> <tint rgba> <column major transform> [lightmap rect]
+    ///   [static] [id <uuid>] [name <name>]`
+    /// - `light point <color rgb> <intensity> <position> [flare] [shadows] [id <uuid>]`
+    /// - `light directional <color rgb> <intensity> <direction> [flare] [id <uuid>]`
+    /// - `light spotlight <color rgb> <intensity> <position> <direction> <cut off> [flare]
+    ///   [shadows] [id <uuid>]`
+    /// - `volume <center xyz> <half extents xyz> <blend distance> <exposure> <fog density>
+    ///   [lut <strength> <name>]`
+    /// - `camera_key <time> <easing> <eye xyz> <yaw> <pitch> <roll> <fov>`
+    /// - `light_key <light> <time> <easing> <color rgb> <intensity>`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut instances = Vec::new();
+        let mut lights = Vec::new();
+        let mut volumes = Vec::new();
+        let mut seed = None;
+        let mut sequence = Sequence::default();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("seed ") {
+                seed = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| format!("Invalid seed {line}: {e}"))?,
+                );
+                continue;
+            }
+            if let Some(values) = line.strip_prefix("light ") {
+                let index = lights.len();
+                lights.push(
+                    parse_light(values, index).map_err(|e| format!("Invalid light {line}: {e}"))?,
+                );
+                continue;
+            }
+            if let Some(values) = line.strip_prefix("volume ") {
+                volumes.push(
+                    PostVolume::parse(values).map_err(|e| format!("Invalid volume {line}: {e}"))?,
+                );
+                continue;
+            }
+            if let Some(values) = line.strip_prefix("camera_key ") {
+                sequence.camera.push(
+                    CameraKey::parse(values)
+                        .map_err(|e| format!("Invalid camera key {line}: {e}"))?,
+                );
+                continue;
+            }
+            if let Some(values) = line.strip_prefix("light_key ") {
+                sequence.lights.push(
+                    LightKey::parse(values)
+                        .map_err(|e| format!("Invalid light key {line}: {e}"))?,
+                );
+                continue;
+            }
+            let Some(values) = line.strip_prefix("instance ") else {
+                return Err(format!("Unknown scene entry: {line}"));
+            };
+            let (model_id, values) = values.split_once(' ').unwrap_or((values, ""));
+            let model_id = model_id
+                .parse()
+                .map_err(|e| format!("Invalid instance {line}: {e}"))?;
+            instances.push(
+                SceneInstance::parse(model_id, values)
+                    .map_err(|e| format!("Invalid instance {line}: {e}"))?,
+            );
+        }
+        sequence.sort();
+        Ok(Self {
+            instances,
+            lights,
+            volumes,
+            seed,
+            sequence,
+        })
+    }
+}
+
+/// Lights saved without an id are keyed by their position in the file, the scenes saved before
+/// the ids still merge with each other
+fn parse_light(src: &str, index: usize) -> Result<SceneLight, String> {
+    let mut words = src.split_whitespace().collect::<Vec<_>>();
+    let id: Option<Uuid> = match words[..] {
+        [.., "id", uuid] => Some(uuid.parse()?),
+        _ => None,
+    };
+    if id.is_some() {
+        words.truncate(words.len() - 2);
+    }
+    let id = id.unwrap_or(Uuid(index as u128));
+    let casts_shadows = words.last() == Some(&"shadows");
+    if casts_shadows {
+        words.pop();
+    }
+    let lens_flare = words.last() == Some(&"flare");
+    if lens_flare {
+        words.pop();
+    }
+    let (kind, values) = words.split_first().ok_or("Missing light type")?;
+    let values = values
+        .iter()
+        .map(|v| v.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let expected = match *kind {
+        "point" | "directional" => 7,
+        "spotlight" => 11,
+        _ => return Err(format!("Unknown light type {kind}")),
+    };
+    if values.len() != expected {
+        return Err(format!("Expected {expected} values"));
+    }
+
+    let color = Color3::new(values[0], values[1], values[2]);
+    let intensity = values[3];
+    let vector = |i: usize| Vector3::new(values[i], values[i + 1], values[i + 2]);
+    let light = match *kind {
+        "point" => Light::Point {
+            color,
+            intensity,
+            position: vector(4).into(),
+        },
+        "directional" => Light::Directional {
+            color,
+            intensity,
+            direction: vector(4).try_normalize(1e-6).ok_or("Zero direction")?,
+        },
+        _ => Light::Spotlight {
+            color,
+            intensity,
+            position: vector(4).into(),
+            direction: vector(7).try_normalize(1e-6).ok_or("Zero direction")?,
+            cut_off: values[10],
+        },
+    };
+    Ok(SceneLight {
+        light,
+        lens_flare,
+        casts_shadows,
+        id,
+    })
+}
+
+impl std::fmt::Display for Scene {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {seed}")?;
+        }
+        writeln!(
+            f,
+            "# instance <model> <mesh> <material> <tint rgba> <column major transform> [lightmap rect] [static] [id <uuid>] [name <name>]"
+        )?;
+        for instance in &self.instances {
+            write!(f, "instance {}", instance.model_id)?;
+            instance.write_values(f)?;
+            writeln!(f)?;
+        }
+        for SceneLight {
+            light,
+            lens_flare,
+            casts_shadows,
+            id,
+        } in &self.lights
+        {
+            let color = |c: &Color3| format!("{} {} {}", c.r, c.g, c.b);
+            let vector = |v: &Vector3<f32>| format!("{} {} {}", v.x, v.y, v.z);
+            let point = |p: &Point3<f32>| vector(&p.coords);
+            match light {
+                Light::None => continue,
+                Light::Point {
+                    color: c,
+                    intensity,
+                    position,
+                } => write!(
+                    f,
+                    "light point {} {intensity} {}",
+                    color(c),
+                    point(position)
+                )?,
+                Light::Directional {
+                    color: c,
+                    intensity,
+                    direction,
+                } => write!(
+                    f,
+                    "light directional {} {intensity} {}",
+                    color(c),
+                    vector(direction)
+                )?,
+                Light::Spotlight {
+                    color: c,
+                    intensity,
+                    position,
+                    direction,
+                    cut_off,
+                } => write!(
+                    f,
+                    "light spotlight {} {intensity} {} {} {cut_off}",
+                    color(c),
+                    point(position),
+                    vector(direction)
+                )?,
+            }
+            if *lens_flare {
+                write!(f, " flare")?;
+            }
+            if *casts_shadows {
+                write!(f, " shadows")?;
+            }
+            writeln!(f, " id {id}")?;
+        }
+        for volume in &self.volumes {
+            writeln!(f, "volume {volume}")?;
+        }
+        for key in &self.sequence.camera {
+            writeln!(f, "camera_key {key}")?;
+        }
+        for key in &self.sequence.lights {
+            writeln!(f, "light_key {key}")?;
+        }
+        Ok(())
+    }
+}
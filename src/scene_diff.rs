@@ -0,0 +1,434 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{
+    entity::Uuid,
+    scene::{Scene, SceneInstance, SceneLight},
+};
+
+/// Difference between two versions of a scene, instances and lights are matched by their uuid
+#[derive(Debug, Clone)]
+pub enum SceneChange {
+    SeedChanged {
+        from: Option<u32>,
+        to: Option<u32>,
+    },
+    Added(SceneInstance),
+    Removed(SceneInstance),
+    /// Only the translation changed
+    Moved {
+        from: SceneInstance,
+        to: [f32; 3],
+    },
+    /// Anything else changed, the translation included
+    Changed {
+        from: Box<SceneInstance>,
+        to: Box<SceneInstance>,
+    },
+    LightAdded(SceneLight),
+    LightRemoved(SceneLight),
+    LightChanged {
+        from: SceneLight,
+        to: SceneLight,
+    },
+    VolumesChanged,
+    SequenceChanged,
+}
+
+/// Changes from `from` to `to`, instances in the order of `from` then the added ones
+pub fn diff(from: &Scene, to: &Scene) -> Vec<SceneChange> {
+    let mut changes = Vec::new();
+    if from.seed != to.seed {
+        changes.push(SceneChange::SeedChanged {
+            from: from.seed,
+            to: to.seed,
+        });
+    }
+
+    let after = by_uuid(to);
+    for instance in &from.instances {
+        match after.get(&instance.meta.uuid) {
+            None => changes.push(SceneChange::Removed(instance.clone())),
+            Some(other) if same_instance(instance, other) => {}
+            Some(other) => {
+                let mut moved = instance.clone();
+                moved.instance.translation = other.instance.translation;
+                changes.push(match same_instance(&moved, other) {
+                    true => SceneChange::Moved {
+                        from: instance.clone(),
+                        to: other.instance.translation,
+                    },
+                    false => SceneChange::Changed {
+                        from: Box::new(instance.clone()),
+                        to: Box::new((*other).clone()),
+                    },
+                });
+            }
+        }
+    }
+    let before = by_uuid(from);
+    for instance in &to.instances {
+        if !before.contains_key(&instance.meta.uuid) {
+            changes.push(SceneChange::Added(instance.clone()));
+        }
+    }
+
+    let after = lights_by_id(to);
+    for light in &from.lights {
+        match after.get(&light.id) {
+            None => changes.push(SceneChange::LightRemoved(*light)),
+            Some(other) if *other != light => changes.push(SceneChange::LightChanged {
+                from: *light,
+                to: **other,
+            }),
+            Some(_) => {}
+        }
+    }
+    let before = lights_by_id(from);
+    for light in &to.lights {
+        if !before.contains_key(&light.id) {
+            changes.push(SceneChange::LightAdded(*light));
+        }
+    }
+    if from.volumes != to.volumes {
+        changes.push(SceneChange::VolumesChanged);
+    }
+    if from.sequence != to.sequence {
+        changes.push(SceneChange::SequenceChanged);
+    }
+    changes
+}
+
+/// Entity or light changed differently on both sides, the merged scene keeps `ours`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    Seed,
+    Instance(Uuid),
+    Light(Uuid),
+    Volumes,
+    Sequence,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeConflict::Seed => write!(f, "seed"),
+            MergeConflict::Instance(uuid) => write!(f, "instance {uuid}"),
+            MergeConflict::Light(id) => write!(f, "light {id}"),
+            MergeConflict::Volumes => write!(f, "volumes"),
+            MergeConflict::Sequence => write!(f, "sequence"),
+        }
+    }
+}
+
+pub struct SceneMerge {
+    pub scene: Scene,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Three-way merge of the edits made to `base` in `ours` and in `theirs`
+///
+/// An entity edited on one side only takes that edit, instances and lights are kept in the order
+/// of `ours` followed by the ones only `theirs` added. An entity deleted on one side and edited on
+/// the other is a conflict that keeps the edit
+pub fn merge(base: &Scene, ours: &Scene, theirs: &Scene) -> SceneMerge {
+    let mut conflicts = Vec::new();
+
+    let seed =
+        merge_value(&base.seed, &ours.seed, &theirs.seed, |a, b| a == b).unwrap_or_else(|ours| {
+            conflicts.push(MergeConflict::Seed);
+            ours
+        });
+
+    let (base_instances, their_instances) = (by_uuid(base), by_uuid(theirs));
+    let ours_uuids: BTreeSet<_> = ours.instances.iter().map(|i| i.meta.uuid).collect();
+    let uuids = ours.instances.iter().map(|i| i.meta.uuid).chain(
+        theirs
+            .instances
+            .iter()
+            .map(|i| i.meta.uuid)
+            .filter(|uuid| !ours_uuids.contains(uuid)),
+    );
+    let our_instances = by_uuid(ours);
+    let mut instances = Vec::new();
+    for uuid in uuids.collect::<Vec<_>>() {
+        let [base_instance, our_instance, their_instance] =
+            [&base_instances, &our_instances, &their_instances]
+                .map(|instances| instances.get(&uuid).map(|instance| (*instance).clone()));
+        let merged = merge_value(
+            &base_instance,
+            &our_instance,
+            &their_instance,
+            |a, b| match (a, b) {
+                (Some(a), Some(b)) => same_instance(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            },
+        )
+        .unwrap_or_else(|ours| {
+            conflicts.push(MergeConflict::Instance(uuid));
+            // A deletion loses against an edit
+            ours.or(their_instance)
+        });
+        instances.extend(merged);
+    }
+
+    let light_maps = [base, ours, theirs].map(lights_by_id);
+    let ids = ours.lights.iter().chain(
+        theirs
+            .lights
+            .iter()
+            .filter(|light| !light_maps[1].contains_key(&light.id)),
+    );
+    let mut lights = Vec::new();
+    for id in ids.map(|light| light.id) {
+        let [base_light, our_light, their_light] = light_maps
+            .each_ref()
+            .map(|lights| lights.get(&id).map(|light| **light));
+        let merged = merge_value(&base_light, &our_light, &their_light, |a, b| a == b)
+            .unwrap_or_else(|ours| {
+                conflicts.push(MergeConflict::Light(id));
+                ours.or(their_light)
+            });
+        lights.extend(merged);
+    }
+
+    // Volumes and keys have no identity, they are merged as a whole
+    let volumes = merge_value(&base.volumes, &ours.volumes, &theirs.volumes, |a, b| a == b)
+        .unwrap_or_else(|ours| {
+            conflicts.push(MergeConflict::Volumes);
+            ours
+        });
+
+    let sequence = merge_value(&base.sequence, &ours.sequence, &theirs.sequence, |a, b| {
+        a == b
+    })
+    .unwrap_or_else(|ours| {
+        conflicts.push(MergeConflict::Sequence);
+        ours
+    });
+
+    SceneMerge {
+        scene: Scene {
+            instances,
+            lights,
+            volumes,
+            seed,
+            sequence,
+        },
+        conflicts,
+    }
+}
+
+/// The side that changed from `base`, or `Err(ours)` when both changed differently
+fn merge_value<T: Clone>(
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    same: impl Fn(&T, &T) -> bool,
+) -> Result<T, T> {
+    if same(ours, theirs) || same(base, theirs) {
+        Ok(ours.clone())
+    } else if same(base, ours) {
+        Ok(theirs.clone())
+    } else {
+        Err(ours.clone())
+    }
+}
+
+fn by_uuid(scene: &Scene) -> BTreeMap<Uuid, &SceneInstance> {
+    scene
+        .instances
+        .iter()
+        .map(|instance| (instance.meta.uuid, instance))
+        .collect()
+}
+
+fn lights_by_id(scene: &Scene) -> BTreeMap<Uuid, &SceneLight> {
+    scene.lights.iter().map(|light| (light.id, light)).collect()
+}
+
+/// Compared as saved, values that round to the same text are the same
+fn same_instance(a: &SceneInstance, b: &SceneInstance) -> bool {
+    a.model_id == b.model_id && values(a) == values(b)
+}
+
+fn values(instance: &SceneInstance) -> String {
+    let mut values = String::new();
+    instance.write_values(&mut values).unwrap();
+    values
+}
+
+impl fmt::Display for SceneChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = |[x, y, z]: [f32; 3]| format!("({x:.2}, {y:.2}, {z:.2})");
+        match self {
+            SceneChange::SeedChanged { from, to } => write!(f, "Seed {from:?} -> {to:?}"),
+            SceneChange::Added(instance) => write!(
+                f,
+                "+ {} (model {}) at {}",
+                instance.meta.label(),
+                instance.model_id,
+                at(instance.instance.translation)
+            ),
+            SceneChange::Removed(instance) => write!(
+                f,
+                "- {} (model {}) at {}",
+                instance.meta.label(),
+                instance.model_id,
+                at(instance.instance.translation)
+            ),
+            SceneChange::Moved { from, to } => write!(
+                f,
+                "~ {} moved {} -> {}",
+                from.meta.label(),
+                at(from.instance.translation),
+                at(*to)
+            ),
+            SceneChange::Changed { from, .. } => write!(f, "~ {} changed", from.meta.label()),
+            SceneChange::LightAdded(light) => write!(f, "+ light {}", light.id.short()),
+            SceneChange::LightRemoved(light) => write!(f, "- light {}", light.id.short()),
+            SceneChange::LightChanged { from, .. } => {
+                write!(f, "~ light {} changed", from.id.short())
+            }
+            SceneChange::VolumesChanged => write!(f, "~ volumes changed"),
+            SceneChange::SequenceChanged => write!(f, "~ sequence changed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::light::Light;
+
+    fn scene(lines: &[String]) -> Scene {
+        Scene::parse(&lines.concat()).unwrap()
+    }
+
+    /// Unit instance at `x`, `red` tells the other edits apart from the moves
+    fn instance(id: u128, x: f32, red: u8) -> String {
+        format!(
+            "instance 0 0 0 {red} 255 255 255 1 0 0 0 0 1 0 0 0 0 1 0 {x} 0 0 1 id {}\n",
+            Uuid(id)
+        )
+    }
+
+    fn light(id: u128, intensity: f32) -> String {
+        format!("light point 1 1 1 {intensity} 0 2 0 id {}\n", Uuid(id))
+    }
+
+    fn uuids(scene: &Scene) -> Vec<u128> {
+        scene.instances.iter().map(|i| i.meta.uuid.0).collect()
+    }
+
+    fn intensities(scene: &Scene) -> Vec<(u128, f32)> {
+        scene
+            .lights
+            .iter()
+            .map(|light| match light.light {
+                Light::Point { intensity, .. } => (light.id.0, intensity),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moves_and_edits_are_told_apart() {
+        let base = scene(&[instance(1, 0.0, 255), instance(2, 0.0, 255)]);
+        let edited = scene(&[instance(1, 3.0, 255), instance(2, 0.0, 0)]);
+        let changes = diff(&base, &edited);
+        let [SceneChange::Moved { to, .. }, SceneChange::Changed { .. }] = &changes[..] else {
+            panic!("Expected a move then an edit, got {changes:?}");
+        };
+        assert_eq!(*to, [3.0, 0.0, 0.0]);
+        assert!(diff(&base, &base).is_empty());
+    }
+
+    #[test]
+    fn deletion_loses_against_an_edit() {
+        let base = scene(&[instance(1, 0.0, 255), instance(2, 0.0, 255)]);
+        let deleted = scene(&[instance(2, 0.0, 255)]);
+        let moved = scene(&[instance(1, 5.0, 255), instance(2, 0.0, 255)]);
+        for (ours, theirs) in [(&deleted, &moved), (&moved, &deleted)] {
+            let merge = merge(&base, ours, theirs);
+            assert_eq!(merge.conflicts, [MergeConflict::Instance(Uuid(1))]);
+            let kept = merge
+                .scene
+                .instances
+                .iter()
+                .find(|i| i.meta.uuid == Uuid(1));
+            assert_eq!(kept.unwrap().instance.translation, [5.0, 0.0, 0.0]);
+        }
+
+        // Deleted on one side and left alone on the other
+        let merge = merge(&base, &deleted, &base);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(uuids(&merge.scene), [2]);
+    }
+
+    #[test]
+    fn both_sides_edit_the_same_instance() {
+        let base = scene(&[instance(1, 0.0, 255)]);
+        let ours = scene(&[instance(1, 1.0, 255)]);
+        let theirs = scene(&[instance(1, 2.0, 255)]);
+
+        let same = merge(&base, &ours, &ours);
+        assert!(same.conflicts.is_empty());
+        assert_eq!(same.scene.instances[0].instance.translation[0], 1.0);
+
+        let different = merge(&base, &ours, &theirs);
+        assert_eq!(different.conflicts, [MergeConflict::Instance(Uuid(1))]);
+        assert_eq!(different.scene.instances[0].instance.translation[0], 1.0);
+
+        // One side only, the edit is taken whichever side made it
+        let theirs_only = merge(&base, &base, &theirs);
+        assert!(theirs_only.conflicts.is_empty());
+        assert_eq!(theirs_only.scene.instances[0].instance.translation[0], 2.0);
+    }
+
+    #[test]
+    fn instances_added_on_both_sides() {
+        let base = scene(&[instance(1, 0.0, 255)]);
+        let ours = scene(&[instance(1, 0.0, 255), instance(2, 1.0, 255)]);
+        let theirs = scene(&[instance(3, 2.0, 255), instance(1, 0.0, 255)]);
+        let merge = merge(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(uuids(&merge.scene), [1, 2, 3]);
+    }
+
+    #[test]
+    fn removed_light_does_not_shift_the_others() {
+        let base = scene(&[light(1, 1.0), light(2, 1.0), light(3, 1.0)]);
+        let ours = scene(&[light(2, 1.0), light(3, 1.0)]);
+        let theirs = scene(&[light(1, 1.0), light(2, 1.0), light(3, 4.0), light(4, 1.0)]);
+
+        assert!(matches!(
+            diff(&base, &ours)[..],
+            [SceneChange::LightRemoved(SceneLight { id: Uuid(1), .. })]
+        ));
+        let merge = merge(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(intensities(&merge.scene), [(2, 1.0), (3, 4.0), (4, 1.0)]);
+    }
+
+    #[test]
+    fn light_edited_on_both_sides() {
+        let base = scene(&[light(1, 1.0)]);
+        let merge = merge(&base, &scene(&[light(1, 2.0)]), &scene(&[light(1, 3.0)]));
+        assert_eq!(merge.conflicts, [MergeConflict::Light(Uuid(1))]);
+        assert_eq!(intensities(&merge.scene), [(1, 2.0)]);
+    }
+
+    /// Scenes saved before the light ids key them by position, and keep it once saved
+    #[test]
+    fn lights_without_id_are_keyed_by_position() {
+        let legacy =
+            Scene::parse("light point 1 1 1 1 0 2 0\nlight point 1 1 1 2 0 2 0\n").unwrap();
+        assert_eq!(intensities(&legacy), [(0, 1.0), (1, 2.0)]);
+        let saved = Scene::parse(&legacy.to_string()).unwrap();
+        assert_eq!(intensities(&saved), intensities(&legacy));
+        assert!(diff(&legacy, &saved).is_empty());
+    }
+}
@@ -0,0 +1,376 @@
+use std::time::Duration;
+
+use nalgebra::Point3;
+
+use crate::graphics::{camera::Camera, color::Color3};
+
+/// Shape of the motion from a key to the next, set on the later key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease in",
+            Easing::EaseOut => "Ease out",
+            Easing::EaseInOut => "Ease in out",
+        }
+    }
+
+    /// Word written in the scene files
+    pub fn key(&self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::EaseIn => "in",
+            Easing::EaseOut => "out",
+            Easing::EaseInOut => "in_out",
+        }
+    }
+
+    /// From [`Self::key`]
+    pub fn parse(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|easing| easing.key() == key)
+    }
+
+    /// `t` from 0 to 1
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKey {
+    /// In seconds from the start of the sequence
+    pub time: f32,
+    pub easing: Easing,
+    pub eye: Point3<f32>,
+    pub yaw_deg: f32,
+    pub pitch_deg: f32,
+    pub roll_deg: f32,
+    pub fov_deg: f32,
+}
+
+impl CameraKey {
+    pub fn new(time: f32, camera: &Camera, fov_deg: f32) -> Self {
+        Self {
+            time,
+            easing: Easing::default(),
+            eye: camera.eye,
+            yaw_deg: camera.yaw_deg,
+            pitch_deg: camera.pitch_deg,
+            roll_deg: camera.roll_deg,
+            fov_deg,
+        }
+    }
+
+    /// `<time> <easing> <eye xyz> <yaw> <pitch> <roll> <fov>`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let (time, easing, values) = parse_key(src, 7)?;
+        Ok(Self {
+            time,
+            easing,
+            eye: Point3::new(values[0], values[1], values[2]),
+            yaw_deg: values[3],
+            pitch_deg: values[4],
+            roll_deg: values[5],
+            fov_deg: values[6],
+        })
+    }
+}
+
+impl std::fmt::Display for CameraKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let eye = self.eye;
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} {}",
+            self.time,
+            self.easing.key(),
+            eye.x,
+            eye.y,
+            eye.z,
+            self.yaw_deg,
+            self.pitch_deg,
+            self.roll_deg,
+            self.fov_deg
+        )
+    }
+}
+
+/// Color and intensity of one light of the scene at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightKey {
+    /// Index of the light in the scene
+    pub light: u32,
+    pub time: f32,
+    pub easing: Easing,
+    pub color: Color3,
+    pub intensity: f32,
+}
+
+impl LightKey {
+    /// `<light> <time> <easing> <color rgb> <intensity>`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let (light, rest) = src.trim().split_once(' ').ok_or("Missing light index")?;
+        let light = light
+            .parse()
+            .map_err(|e| format!("Invalid light index: {e}"))?;
+        let (time, easing, values) = parse_key(rest, 4)?;
+        Ok(Self {
+            light,
+            time,
+            easing,
+            color: Color3::new(values[0], values[1], values[2]),
+            intensity: values[3],
+        })
+    }
+}
+
+impl std::fmt::Display for LightKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let color = self.color;
+        write!(
+            f,
+            "{} {} {} {} {} {} {}",
+            self.light,
+            self.time,
+            self.easing.key(),
+            color.r,
+            color.g,
+            color.b,
+            self.intensity
+        )
+    }
+}
+
+/// `<time> <easing>` followed by `count` numbers
+fn parse_key(src: &str, count: usize) -> Result<(f32, Easing, Vec<f32>), String> {
+    let mut words = src.split_whitespace();
+    let time = words
+        .next()
+        .ok_or("Missing time")?
+        .parse()
+        .map_err(|e| format!("Invalid time: {e}"))?;
+    let easing = words.next().ok_or("Missing easing")?;
+    let easing = Easing::parse(easing).ok_or_else(|| format!("Unknown easing {easing}"))?;
+    let values = words
+        .map(str::parse::<f32>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if values.len() != count {
+        return Err(format!("Expected {count} values after the easing"));
+    }
+    Ok((time, easing, values))
+}
+
+/// Camera and light keyframes of a cutscene, saved with the scene
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sequence {
+    /// Sorted by time
+    pub camera: Vec<CameraKey>,
+    /// Sorted by light then time
+    pub lights: Vec<LightKey>,
+}
+
+/// State of the sequence at a time
+#[derive(Debug, Clone)]
+pub struct SequenceSample {
+    /// With the fov, none without camera keys
+    pub camera: Option<(Camera, f32)>,
+    /// Index, color and intensity of every keyed light
+    pub lights: Vec<(u32, Color3, f32)>,
+}
+
+impl Sequence {
+    pub fn is_empty(&self) -> bool {
+        self.camera.is_empty() && self.lights.is_empty()
+    }
+
+    /// Time of the last key
+    pub fn duration(&self) -> f32 {
+        let camera = self.camera.iter().map(|key| key.time);
+        let lights = self.lights.iter().map(|key| key.time);
+        camera.chain(lights).fold(0.0, f32::max)
+    }
+
+    /// Replaces the camera key at the same time
+    pub fn set_camera_key(&mut self, key: CameraKey) {
+        self.camera.retain(|other| !same_time(other.time, key.time));
+        self.camera.push(key);
+        self.sort();
+    }
+
+    /// Replaces the key of the same light at the same time
+    pub fn set_light_key(&mut self, key: LightKey) {
+        self.lights
+            .retain(|other| other.light != key.light || !same_time(other.time, key.time));
+        self.lights.push(key);
+        self.sort();
+    }
+
+    /// Called after editing the times by hand
+    pub fn sort(&mut self) {
+        self.camera.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.lights
+            .sort_by(|a, b| a.light.cmp(&b.light).then(a.time.total_cmp(&b.time)));
+    }
+
+    pub fn sample(&self, time: f32) -> SequenceSample {
+        let mut lights = Vec::new();
+        for keys in self.lights.chunk_by(|a, b| a.light == b.light) {
+            let (from, to, t) = segment(keys, |key| (key.time, key.easing), time);
+            lights.push((
+                from.light,
+                from.color + (to.color - from.color) * t,
+                from.intensity + (to.intensity - from.intensity) * t,
+            ));
+        }
+        SequenceSample {
+            camera: self.sample_camera(time),
+            lights,
+        }
+    }
+
+    /// Catmull-Rom through the eyes, the angles are eased from key to key the short way around
+    fn sample_camera(&self, time: f32) -> Option<(Camera, f32)> {
+        let keys = &self.camera;
+        if keys.is_empty() {
+            return None;
+        }
+        let (from, to, t) = segment(keys, |key| (key.time, key.easing), time);
+        let i = keys
+            .iter()
+            .position(|key| std::ptr::eq(key, from))
+            .unwrap_or(0);
+        let before = keys[i.saturating_sub(1)].eye;
+        let after = keys.get(i + 2).unwrap_or(to).eye;
+        let eye = catmull_rom(before, from.eye, to.eye, after, t);
+
+        let angle = |from: f32, to: f32| from + ((to - from + 180.0).rem_euclid(360.0) - 180.0) * t;
+        let camera = Camera {
+            eye,
+            yaw_deg: angle(from.yaw_deg, to.yaw_deg),
+            pitch_deg: from.pitch_deg + (to.pitch_deg - from.pitch_deg) * t,
+            roll_deg: angle(from.roll_deg, to.roll_deg),
+            ..Camera::default()
+        };
+        Some((camera, from.fov_deg + (to.fov_deg - from.fov_deg) * t))
+    }
+}
+
+fn same_time(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+/// Keys around `time` and the eased progress between them, the first or last key is held outside
+/// of the keyed range. `keys` must not be empty
+fn segment<K>(keys: &[K], time_of: impl Fn(&K) -> (f32, Easing), time: f32) -> (&K, &K, f32) {
+    let next = keys.partition_point(|key| time_of(key).0 <= time);
+    if next == 0 {
+        return (&keys[0], &keys[0], 0.0);
+    }
+    let Some(to) = keys.get(next) else {
+        let last = &keys[keys.len() - 1];
+        return (last, last, 0.0);
+    };
+    let from = &keys[next - 1];
+    let (start, _) = time_of(from);
+    let (end, easing) = time_of(to);
+    let t = (time - start) / (end - start).max(1e-6);
+    (from, to, easing.apply(t))
+}
+
+fn catmull_rom(
+    p0: Point3<f32>,
+    p1: Point3<f32>,
+    p2: Point3<f32>,
+    p3: Point3<f32>,
+    t: f32,
+) -> Point3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+    let [p0, p1, p2, p3] = [p0, p1, p2, p3].map(|p| p.coords);
+    let position = ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+        * 0.5;
+    position.into()
+}
+
+/// Plays a sequence back over the camera and the lights, from the editor timeline or the
+/// gameplay. The camera controls are overridden while playing
+#[derive(Debug, Clone, Default)]
+pub struct Cutscene {
+    pub sequence: Sequence,
+    /// In seconds
+    pub time: f32,
+    pub playing: bool,
+    /// Starts over at the end instead of stopping
+    pub looping: bool,
+    /// Sampled once on the next update, even when not playing
+    seeked: bool,
+}
+
+impl Cutscene {
+    /// From the start
+    pub fn play(&mut self, sequence: Sequence) {
+        self.sequence = sequence;
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Shows the sequence at `time` without playing it
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.sequence.duration());
+        self.seeked = true;
+    }
+
+    /// Advances the time, returns the state to apply while playing or after a seek
+    pub fn update(&mut self, dt: Duration) -> Option<SequenceSample> {
+        if self.sequence.is_empty() {
+            self.playing = false;
+            self.seeked = false;
+            return None;
+        }
+        if !self.playing {
+            return std::mem::take(&mut self.seeked).then(|| self.sequence.sample(self.time));
+        }
+        self.seeked = false;
+        self.time += dt.as_secs_f32();
+        let duration = self.sequence.duration();
+        if self.time >= duration {
+            match self.looping && duration > 0.0 {
+                true => self.time %= duration,
+                false => {
+                    self.time = duration;
+                    self.playing = false;
+                }
+            }
+        }
+        Some(self.sequence.sample(self.time))
+    }
+}
@@ -1,6 +1,4 @@
-use std::collections::VecDeque;
-
-use egui::ahash::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 #[derive(Default)]
 pub struct SparseIdAllocator<T = u32> {
@@ -33,6 +31,10 @@ impl<T: Default + std::ops::AddAssign + From<u8> + Copy> SparseIdAllocator<T> {
     pub fn len(&self) -> T {
         self.len
     }
+
+    pub fn has_free(&self) -> bool {
+        !self.free_ids.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -121,4 +123,99 @@ impl DenseIdAllocator {
     pub fn iter(&self) -> impl Iterator<Item = &DenseId> {
         self.from_index.iter()
     }
+
+    /// Both mappings agree and the ids were all handed out, see [`crate::graphics::fuzz`]
+    pub fn check(&self) -> Result<(), String> {
+        if self.to_index.len() != self.from_index.len() {
+            return Err(format!(
+                "{} ids are mapped to {} indices",
+                self.to_index.len(),
+                self.from_index.len()
+            ));
+        }
+        for (index, id) in self.from_index.iter().enumerate() {
+            if self.to_index.get(id) != Some(&index) {
+                return Err(format!(
+                    "Id {} is at {index} but maps to {:?}",
+                    id.0,
+                    self.to_index.get(id)
+                ));
+            }
+            if id.0 >= self.next_dense {
+                return Err(format!("Id {} was never allocated", id.0));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    #[test]
+    fn sparse_ids_are_reused_in_free_order() {
+        let mut ids = SparseIdAllocator::<u32>::new_packed(3);
+        assert_eq!(ids.allocate(), 3);
+        ids.free(1);
+        ids.free(0);
+        assert!(ids.has_free());
+        assert_eq!(ids.allocate(), 1);
+        assert_eq!(ids.allocate(), 0);
+        assert!(!ids.has_free());
+        assert_eq!(ids.allocate(), 4);
+        assert_eq!(ids.len(), 5);
+    }
+
+    #[test]
+    fn dense_free_swaps_the_last_id_in() {
+        let mut ids = DenseIdAllocator::new_packed(3);
+        assert!(matches!(
+            ids.free(DenseId(0)),
+            Some(DenseArrayOp::SwapRemove { index: 0, last: 2 })
+        ));
+        assert_eq!(ids.get_index(DenseId(2)), Some(0));
+        assert!(matches!(
+            ids.free(DenseId(1)),
+            Some(DenseArrayOp::RemoveLast)
+        ));
+        assert!(ids.free(DenseId(1)).is_none());
+        assert_eq!(ids.allocate(), DenseId(3));
+        assert_eq!(ids.get_index(DenseId(3)), Some(1));
+        assert_eq!(ids.len(), 2);
+        ids.check().unwrap();
+    }
+
+    /// Replays random allocations and frees against a vec updated like the dense array is
+    #[test]
+    fn dense_ids_follow_their_array() {
+        for seed in 0..8 {
+            let mut rng = Rng::new(seed);
+            let mut ids = DenseIdAllocator::new_packed(4);
+            let mut array: Vec<DenseId> = ids.iter().copied().collect();
+            for step in 0..2000 {
+                if array.is_empty() || rng.below(3) != 0 {
+                    array.push(ids.allocate());
+                } else {
+                    let id = array[rng.below(array.len())];
+                    match ids.free(id).unwrap() {
+                        DenseArrayOp::SwapRemove { index, last } => {
+                            array.swap(index as usize, last as usize);
+                            array.pop();
+                        }
+                        DenseArrayOp::RemoveLast => {
+                            assert_eq!(array.pop(), Some(id));
+                        }
+                    }
+                }
+                ids.check()
+                    .unwrap_or_else(|e| panic!("Seed {seed}, step {step}: {e}"));
+                assert!(ids.iter().eq(&array), "Seed {seed}, step {step}");
+                for (index, id) in array.iter().enumerate() {
+                    assert_eq!(ids.get_index(*id), Some(index as u32));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,350 @@
+use std::{
+    cell::RefCell,
+    io::{BufReader, Cursor},
+    path::Path,
+};
+
+use crate::{
+    constants,
+    graphics::{
+        entities::{
+            import,
+            model::load_model,
+            renderer::{label_model, ASSET_MODELS},
+            EntityModel,
+        },
+        light::{Light, RawLight},
+        settings::LightSettings,
+    },
+    scene::Scene,
+    ASSETS,
+};
+
+/// Counts over which a scene fails the validation
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationBudget {
+    pub instances: usize,
+    pub triangles: usize,
+    pub lights: usize,
+}
+
+impl Default for ValidationBudget {
+    fn default() -> Self {
+        Self {
+            instances: 100_000,
+            triangles: 10_000_000,
+            lights: LightSettings::default().max_lights as usize,
+        }
+    }
+}
+
+/// Problems found in a scene and in the assets it references, the scene fails with any error
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Loads the models the way the renderer does, without a device, and checks the scene against
+/// them
+pub fn validate_scene(scene: &Scene, budget: &ValidationBudget) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    // Models with missing assets panic when loaded, they are left out
+    let mut models: Vec<Option<EntityModel>> = ASSET_MODELS
+        .iter()
+        .map(|name| {
+            let missing = missing_model_assets(name);
+            let loadable = missing.is_empty();
+            report.errors.extend(missing);
+            if let Err(e) = import::load_sidecar(name) {
+                report
+                    .warnings
+                    .push(format!("Model {name}: ignored import options: {e}"));
+            }
+            loadable.then(|| load_model(name))
+        })
+        .collect();
+    models.push(Some(label_model()));
+
+    for (model_id, model) in models.iter().enumerate() {
+        let Some(model) = model else {
+            continue;
+        };
+        for (mesh_id, mesh) in model.meshes.iter().enumerate() {
+            let name = model_name(model_id);
+            if mesh.indices.len() < 3 {
+                report
+                    .errors
+                    .push(format!("Model {name} mesh {mesh_id}: no triangles"));
+                continue;
+            }
+            if mesh.positions.iter().any(|p| !p.is_finite()) {
+                report
+                    .errors
+                    .push(format!("Model {name} mesh {mesh_id}: non finite positions"));
+            }
+            let degenerate = degenerate_triangles(&mesh.positions, &mesh.indices);
+            if degenerate * 3 == mesh.indices.len() {
+                report.errors.push(format!(
+                    "Model {name} mesh {mesh_id}: every triangle is degenerate"
+                ));
+            } else if degenerate > 0 {
+                report.warnings.push(format!(
+                    "Model {name} mesh {mesh_id}: {degenerate} degenerate triangles"
+                ));
+            }
+        }
+    }
+
+    let material_count: usize = models
+        .iter()
+        .flatten()
+        .map(|model| model.materials.len())
+        .sum();
+    let mut triangles = 0;
+    for (i, placed) in scene.instances.iter().enumerate() {
+        let label = format!("Instance {i} ({})", placed.meta.label());
+        let Some(model) = models.get(placed.model_id as usize) else {
+            report.errors.push(format!(
+                "{label}: model id {} out of range, {} models",
+                placed.model_id,
+                models.len()
+            ));
+            continue;
+        };
+        if models.iter().all(Option::is_some)
+            && placed.instance.material_id as usize >= material_count
+        {
+            report.errors.push(format!(
+                "{label}: material id {} out of range, {material_count} materials",
+                placed.instance.material_id
+            ));
+        }
+        let transform = placed.instance.transform();
+        if transform.iter().any(|v| !v.is_finite()) {
+            report.errors.push(format!("{label}: non finite transform"));
+        } else if placed.instance.scale.iter().any(|s| *s == 0.0) {
+            report.errors.push(format!("{label}: zero scale"));
+        }
+        let Some(model) = model else {
+            continue;
+        };
+        let Some(mesh) = model.meshes.get(placed.mesh_id as usize) else {
+            report.errors.push(format!(
+                "{label}: mesh id {} out of range, model {} has {} meshes",
+                placed.mesh_id,
+                model_name(placed.model_id as usize),
+                model.meshes.len()
+            ));
+            continue;
+        };
+        triangles += mesh.indices.len() / 3;
+        let lightmapped = model
+            .baked
+            .get(placed.mesh_id as usize)
+            .is_some_and(|baked| !baked.lightmap_uvs.is_empty());
+        if placed.instance.lightmap[2] > 0.0 && !lightmapped {
+            report.warnings.push(format!(
+                "{label}: lightmap rect on a mesh without lightmap uvs"
+            ));
+        }
+    }
+
+    for (i, light) in scene.lights.iter().enumerate() {
+        let intensity = match light.light {
+            Light::None => continue,
+            Light::Point { intensity, .. }
+            | Light::Directional { intensity, .. }
+            | Light::Spotlight { intensity, .. } => intensity,
+        };
+        if !intensity.is_finite() || intensity < 0.0 {
+            report
+                .errors
+                .push(format!("Light {i}: invalid intensity {intensity}"));
+        }
+        let raw = RawLight::from(light.light);
+        if [raw.position, raw.direction, raw.color]
+            .iter()
+            .flatten()
+            .chain([&raw.cut_off])
+            .any(|v| !v.is_finite())
+        {
+            report.errors.push(format!("Light {i}: non finite values"));
+        }
+    }
+
+    for (what, count, max) in [
+        ("instances", scene.instances.len(), budget.instances),
+        ("triangles", triangles, budget.triangles),
+        ("lights", scene.lights.len(), budget.lights),
+    ] {
+        if count > max {
+            report
+                .errors
+                .push(format!("{count} {what}, over the budget of {max}"));
+        }
+    }
+    report
+}
+
+/// Material and texture files referenced by the model that are not in the assets
+fn missing_model_assets(model_name: &str) -> Vec<String> {
+    let assets = ASSETS.tree();
+    let Some(model_file) = assets.models.get(model_name) else {
+        return vec![format!(
+            "Model {model_name}: missing from the models folder"
+        )];
+    };
+    let errors = RefCell::new(Vec::new());
+    let mut reader = BufReader::new(Cursor::new(model_file.0.clone()));
+    let loaded = tobj::load_obj_buf(
+        &mut reader,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+        |path: &Path| {
+            let name = path.to_string_lossy();
+            let Some(material) = name.strip_suffix(".mtl") else {
+                errors.borrow_mut().push(format!(
+                    "Model {model_name}: material library {name} is not a .mtl file"
+                ));
+                return Err(tobj::LoadError::OpenFileFailed);
+            };
+            let Some(material_file) = assets.materials.get(material) else {
+                errors.borrow_mut().push(format!(
+                    "Model {model_name}: missing material library {material}"
+                ));
+                return Err(tobj::LoadError::OpenFileFailed);
+            };
+            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(material_file.0.clone())))
+        },
+    );
+    let mut errors = errors.into_inner();
+    let materials = match loaded {
+        Ok((_, Ok(materials))) => materials,
+        Ok((_, Err(e))) => {
+            if errors.is_empty() {
+                errors.push(format!("Model {model_name}: invalid materials: {e}"));
+            }
+            return errors;
+        }
+        Err(e) => {
+            errors.push(format!("Model {model_name}: invalid obj: {e}"));
+            return errors;
+        }
+    };
+    for material in &materials {
+        let Some(texture) = &material.diffuse_texture else {
+            continue;
+        };
+        let Some(name) = texture
+            .strip_suffix(".png")
+            .or(texture.strip_suffix(".jpg"))
+        else {
+            errors.push(format!(
+                "Model {model_name} material {}: texture {texture} is not a .png or .jpg file",
+                material.name
+            ));
+            continue;
+        };
+        if ASSETS.tree().textures.get(name).is_none() {
+            errors.push(format!(
+                "Model {model_name} material {}: missing texture {name}",
+                material.name
+            ));
+        }
+    }
+    errors
+}
+
+fn model_name(model_id: usize) -> &'static str {
+    ASSET_MODELS.get(model_id).copied().unwrap_or("label")
+}
+
+/// Triangles whose area is zero
+fn degenerate_triangles(positions: &[f32], indices: &[u32]) -> usize {
+    let position = |i: u32| {
+        let i = i as usize * 3;
+        nalgebra::Vector3::new(positions[i], positions[i + 1], positions[i + 2])
+    };
+    indices
+        .chunks_exact(3)
+        .filter(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| position(triangle[i]));
+            (b - a).cross(&(c - a)).norm_squared() <= f32::EPSILON * f32::EPSILON
+        })
+        .count()
+}
+
+/// `--validate [scene name or .scene file] [--max-instances N] [--max-triangles N]
+/// [--max-lights N]`, returns the exit code when the arguments ask for the validation
+///
+/// The default scene is validated when no scene is given. Problems are printed on stderr and
+/// fail with a nonzero code, for the content checks of the continuous integration
+pub fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let start = args.iter().position(|arg| arg == "--validate")?;
+    let mut budget = ValidationBudget::default();
+    let mut scene_arg = None;
+    let mut args = args[start + 1..].iter();
+    while let Some(arg) = args.next() {
+        let limit = match arg.as_str() {
+            "--max-instances" => &mut budget.instances,
+            "--max-triangles" => &mut budget.triangles,
+            "--max-lights" => &mut budget.lights,
+            _ => {
+                scene_arg = Some(arg.clone());
+                continue;
+            }
+        };
+        match args.next().map(|value| value.parse()) {
+            Some(Ok(value)) => *limit = value,
+            _ => {
+                eprintln!("Expected a count after {arg}");
+                return Some(2);
+            }
+        }
+    }
+
+    let scene_arg = scene_arg.unwrap_or_else(|| constants::DEFAULT_SCENE.to_string());
+    let scene = match scene_arg.ends_with(".scene") {
+        true => std::fs::read_to_string(&scene_arg)
+            .map_err(|e| e.to_string())
+            .and_then(|src| Scene::parse(&src)),
+        false => Scene::load(&scene_arg).or_else(|_| Scene::from_assets(&scene_arg)),
+    };
+    let scene = match scene {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: failed to load scene {scene_arg}: {e}");
+            return Some(1);
+        }
+    };
+
+    let report = validate_scene(&scene, &budget);
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
+    for error in &report.errors {
+        eprintln!("error: {error}");
+    }
+    match report.is_ok() {
+        true => {
+            println!("Scene {scene_arg} is valid");
+            Some(0)
+        }
+        false => {
+            eprintln!("Scene {scene_arg} has {} errors", report.errors.len());
+            Some(1)
+        }
+    }
+}
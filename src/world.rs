@@ -0,0 +1,196 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    graphics::entities::{
+        renderer::ASSET_MODELS,
+        streaming::{CellCoord, CellLoader, StreamedInstance},
+    },
+    scene::{Scene, SceneInstance},
+    ASSETS,
+};
+
+/// File of a world folder listing its cells
+const INDEX_FILE: &str = "world.index";
+const CELL_EXTENSION: &str = "cell";
+const CELL_HEADER: &str =
+    "# <model name> <mesh> <material> <tint rgba> <column major transform> [lightmap rect] [id <uuid>] [name <name>]\n";
+
+/// Instances of a scene split in square cells, streamed by
+/// [`crate::graphics::entities::streaming::WorldStreamer`] around the camera
+///
+/// Saved as a folder of the worlds folder of the assets, with an index and a file per cell
+/// named `<x>_<z>.cell`. Cells reference their models by name so they stay valid when the
+/// asset models are reordered
+#[derive(Debug, Clone, Default)]
+pub struct WorldPartition {
+    pub cell_size: f32,
+    pub seed: Option<u32>,
+    pub cells: BTreeMap<CellCoord, Vec<SceneInstance>>,
+}
+
+/// What the streamer needs to know of a world before reading its cells
+#[derive(Debug, Clone)]
+pub struct WorldIndex {
+    pub cell_size: f32,
+    pub seed: Option<u32>,
+    /// Cells with instances, the others are empty and not read
+    pub cells: Vec<CellCoord>,
+}
+
+impl WorldPartition {
+    pub fn path(name: &str) -> PathBuf {
+        ASSETS.root().join("worlds").join(name)
+    }
+
+    fn cell_path(folder: &Path, (x, z): CellCoord) -> PathBuf {
+        folder.join(format!("{x}_{z}.{CELL_EXTENSION}"))
+    }
+
+    /// Instances are bucketed by the cell of their translation, in absolute coordinates
+    pub fn from_scene(scene: &Scene, cell_size: f32) -> Self {
+        let mut cells: BTreeMap<CellCoord, Vec<SceneInstance>> = BTreeMap::new();
+        for instance in &scene.instances {
+            let [x, _, z] = instance.instance.translation;
+            let coord = (
+                (x / cell_size).floor() as i32,
+                (z / cell_size).floor() as i32,
+            );
+            cells.entry(coord).or_default().push(instance.clone());
+        }
+        Self {
+            cell_size,
+            seed: scene.seed,
+            cells,
+        }
+    }
+
+    /// Replaces the cell files of a previous bake
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        let folder = Self::path(name);
+        std::fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+        for entry in std::fs::read_dir(&folder)
+            .map_err(|e| e.to_string())?
+            .flatten()
+        {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == CELL_EXTENSION) {
+                std::fs::remove_file(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            }
+        }
+
+        let mut index = String::from("# cell_size, seed, then the cells with instances\n");
+        writeln!(index, "cell_size {}", self.cell_size).unwrap();
+        if let Some(seed) = self.seed {
+            writeln!(index, "seed {seed}").unwrap();
+        }
+        for (&(x, z), instances) in &self.cells {
+            writeln!(index, "cell {x} {z}").unwrap();
+            let mut cell = String::from(CELL_HEADER);
+            for instance in instances {
+                let name = ASSET_MODELS
+                    .get(instance.model_id as usize)
+                    .ok_or_else(|| format!("Unknown model {}", instance.model_id))?;
+                cell.push_str(name);
+                instance.write_values(&mut cell).unwrap();
+                cell.push('\n');
+            }
+            let path = Self::cell_path(&folder, (x, z));
+            std::fs::write(&path, cell).map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        std::fs::write(folder.join(INDEX_FILE), index).map_err(|e| e.to_string())
+    }
+
+    pub fn load_index(name: &str) -> Result<WorldIndex, String> {
+        let path = Self::path(name).join(INDEX_FILE);
+        let src = std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut index = WorldIndex {
+            cell_size: 0.0,
+            seed: None,
+            cells: Vec::new(),
+        };
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let invalid = |e: &dyn std::fmt::Display| format!("Invalid {key} {value}: {e}");
+            match key {
+                "cell_size" => index.cell_size = value.trim().parse().map_err(|e| invalid(&e))?,
+                "seed" => index.seed = Some(value.trim().parse().map_err(|e| invalid(&e))?),
+                "cell" => {
+                    let coords = value
+                        .split_whitespace()
+                        .map(str::parse::<i32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| invalid(&e))?;
+                    let [x, z] = coords[..] else {
+                        return Err(invalid(&"expected 2 coordinates"));
+                    };
+                    index.cells.push((x, z));
+                }
+                _ => return Err(format!("Unknown world entry: {line}")),
+            }
+        }
+        if index.cell_size <= 0.0 {
+            return Err(format!("{}: missing cell_size", path.display()));
+        }
+        Ok(index)
+    }
+
+    /// Instances of a cell, models missing from the asset models are skipped
+    pub fn load_cell(name: &str, coord: CellCoord) -> Result<Vec<SceneInstance>, String> {
+        let path = Self::cell_path(&Self::path(name), coord);
+        let src = std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut instances = Vec::new();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (model, values) = line.split_once(' ').unwrap_or((line, ""));
+            let Some(model_id) = ASSET_MODELS.iter().position(|name| *name == model) else {
+                eprintln!(
+                    "Skipped instance of unknown model {model} in {}",
+                    path.display()
+                );
+                continue;
+            };
+            instances.push(
+                SceneInstance::parse(model_id as u16, values)
+                    .map_err(|e| format!("{}: {e}: {line}", path.display()))?,
+            );
+        }
+        Ok(instances)
+    }
+
+    /// Reads the cells listed by the index on demand, a cell failing to load stays empty
+    pub fn loader(name: &str) -> Result<(WorldIndex, CellLoader), String> {
+        let index = Self::load_index(name)?;
+        let cells: HashSet<_> = index.cells.iter().copied().collect();
+        let name = name.to_string();
+        let loader: CellLoader = Arc::new(move |coord| {
+            if !cells.contains(&coord) {
+                return Vec::new();
+            }
+            match Self::load_cell(&name, coord) {
+                Ok(instances) => instances
+                    .into_iter()
+                    .map(|i| StreamedInstance {
+                        model_id: i.model_id,
+                        mesh_id: i.mesh_id,
+                        instance: i.instance,
+                    })
+                    .collect(),
+                Err(e) => {
+                    eprintln!("Failed to load cell {coord:?} of world {name}: {e}");
+                    Vec::new()
+                }
+            }
+        });
+        Ok((index, loader))
+    }
+}
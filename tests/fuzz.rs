@@ -0,0 +1,34 @@
+#![cfg(feature = "fuzz")]
+
+use foreigntech2::graphics::{
+    ctx::GraphicsCtx,
+    fuzz::{fuzz_instances, fuzz_lights, FuzzOptions},
+};
+
+const SEEDS: std::ops::RangeInclusive<u32> = 1..=4;
+
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn instances() {
+    let ctx = GraphicsCtx::new_headless((1, 1)).expect("No adapter to read the buffers back");
+    for seed in SEEDS {
+        let options = FuzzOptions {
+            seed,
+            ..Default::default()
+        };
+        fuzz_instances(&ctx, options).unwrap();
+    }
+}
+
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn lights() {
+    let ctx = GraphicsCtx::new_headless((1, 1)).expect("No adapter to read the buffers back");
+    for seed in SEEDS {
+        let options = FuzzOptions {
+            seed,
+            ..Default::default()
+        };
+        fuzz_lights(&ctx, options).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "golden-tests")]
+
+use foreigntech2::{
+    constants,
+    graphics::{
+        camera::Camera,
+        ctx::GraphicsCtx,
+        golden::{check, render_scene, ImageTolerance},
+    },
+    scene::Scene,
+};
+use nalgebra::Point3;
+
+const SIZE: (u32, u32) = (320, 180);
+
+// Recorded again with `UPDATE_GOLDEN=1 cargo test --features golden-tests -- --ignored`
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn default_scene() {
+    let ctx = GraphicsCtx::new_headless(SIZE).expect("No adapter to render the golden frame");
+    let scene = Scene::from_assets(constants::DEFAULT_SCENE).unwrap();
+    let camera = Camera {
+        eye: Point3::new(0.0, 2.0, 6.0),
+        pitch_deg: -10.0,
+        ..Default::default()
+    };
+    let frame = render_scene(&ctx, &scene, &camera).unwrap();
+    check("default_scene", &frame, ImageTolerance::default()).unwrap();
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "demo-tests")]
+
+use std::time::Duration;
+
+use foreigntech2::{
+    game::{
+        projectiles::{ColliderOwner, Projectiles},
+        spatial::SpatialGrid,
+    },
+    graphics::{
+        ctx::GraphicsCtx,
+        entities::model::{ModelVertex, ModelsBuffer, VertexPrecision},
+    },
+};
+use nalgebra::{Point3, Vector3};
+
+/// Models of the projectile and impact bodies, a triangle each
+const MODEL_COUNT: u32 = 3;
+const STEP: Duration = Duration::from_millis(16);
+
+fn models(ctx: &GraphicsCtx) -> ModelsBuffer {
+    let vertices = (0..MODEL_COUNT)
+        .flat_map(|_| {
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|position| ModelVertex {
+                position,
+                ao: 1.0,
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = (0..MODEL_COUNT)
+        .flat_map(|_| [0, 1, 2])
+        .collect::<Vec<u16>>();
+    let indirects = (0..MODEL_COUNT)
+        .map(|mesh| wgpu::util::DrawIndexedIndirectArgs {
+            index_count: 3,
+            instance_count: 0,
+            first_index: mesh * 3,
+            base_vertex: mesh as i32 * 3,
+            first_instance: 0,
+        })
+        .collect::<Vec<_>>();
+    ModelsBuffer::from_raw(
+        ctx,
+        &vertices,
+        &indices,
+        &[],
+        &indirects,
+        &vec![VertexPrecision::Full; MODEL_COUNT as usize],
+        vec![vec![0]; MODEL_COUNT as usize],
+    )
+}
+
+/// Applies the changes of the step like a frame does and checks the instances against the gpu
+fn step(
+    ctx: &GraphicsCtx,
+    models: &mut ModelsBuffer,
+    projectiles: &mut Projectiles,
+    colliders: &SpatialGrid<ColliderOwner>,
+) {
+    projectiles.update(models, colliders, STEP);
+    models.apply_changes(ctx);
+    models.check(ctx).unwrap();
+}
+
+fn steps(duration: Duration) -> u32 {
+    duration.as_millis().div_ceil(STEP.as_millis()) as u32
+}
+
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn projectile_hits_then_its_impact_expires() {
+    let ctx = GraphicsCtx::new_headless((1, 1)).expect("No adapter to run the demo");
+    let mut models = models(&ctx);
+    let mut colliders = SpatialGrid::new(2.0);
+    colliders.insert(Point3::new(0.0, 0.0, -10.0), 1.0, ColliderOwner::Agent(7));
+    let mut projectiles = Projectiles {
+        gravity: 0.0,
+        ..Default::default()
+    };
+    let body = projectiles.projectile_body.model_id;
+
+    projectiles.fire(&mut models, Point3::origin(), -Vector3::z());
+    models.apply_changes(&ctx);
+    models.check(&ctx).unwrap();
+    assert_eq!(models.model_instance_count(body), 1);
+
+    // 9 units to the collider at 30 per second
+    for _ in 0..steps(Duration::from_millis(400)) {
+        step(&ctx, &mut models, &mut projectiles, &colliders);
+    }
+    assert!(projectiles.projectiles.is_empty());
+    assert_eq!(projectiles.impacts.len(), 1);
+    let hits = projectiles.take_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].data, ColliderOwner::Agent(7));
+    assert!((hits[0].position.z + 9.0).abs() < 0.1);
+    assert_eq!(models.instance_count(), 1);
+
+    for _ in 0..steps(projectiles.impact_lifetime) {
+        step(&ctx, &mut models, &mut projectiles, &colliders);
+    }
+    assert!(projectiles.impacts.is_empty());
+    assert_eq!(models.instance_count(), 0);
+}
+
+#[test]
+#[ignore = "needs a gpu adapter, run with --ignored"]
+fn missed_shots_despawn() {
+    let ctx = GraphicsCtx::new_headless((1, 1)).expect("No adapter to run the demo");
+    let mut models = models(&ctx);
+    let mut colliders = SpatialGrid::new(2.0);
+    colliders.insert(Point3::new(0.0, 0.0, -10.0), 1.0, ColliderOwner::Agent(0));
+    let mut projectiles = Projectiles::default();
+
+    // Hitscan spawns the impact at once, a shot away from the collider only falls
+    projectiles.hitscan(&mut models, &colliders, Point3::origin(), -Vector3::z());
+    projectiles.hitscan(&mut models, &colliders, Point3::origin(), Vector3::z());
+    for _ in 0..4 {
+        projectiles.fire(&mut models, Point3::origin(), Vector3::x());
+    }
+    models.apply_changes(&ctx);
+    models.check(&ctx).unwrap();
+    assert_eq!(projectiles.impacts.len(), 1);
+    assert_eq!(projectiles.take_hits().len(), 1);
+    assert_eq!(models.instance_count(), 5);
+
+    for _ in 0..steps(projectiles.lifetime) {
+        step(&ctx, &mut models, &mut projectiles, &colliders);
+    }
+    assert!(projectiles.projectiles.is_empty());
+    assert!(projectiles.impacts.is_empty());
+    assert!(projectiles.take_hits().is_empty());
+    assert_eq!(models.instance_count(), 0);
+}
@@ -0,0 +1,9 @@
+#![cfg(feature = "shader-tests")]
+
+use foreigntech2::graphics::shaders::validate_builtin_shaders;
+
+#[test]
+fn builtin_shaders() {
+    let errors = validate_builtin_shaders();
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+}